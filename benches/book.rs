@@ -0,0 +1,141 @@
+use std::convert::TryFrom;
+
+use chrono::Utc;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use ethereum_types::{Address, U256};
+use tokio::runtime::Runtime;
+
+use tracer_ome::book::{Book, BookSnapshot, ExternalBook};
+use tracer_ome::order::{Order, OrderId, OrderSide};
+use tracer_ome::test_util::{
+    deep_book, far_future, non_crossing_orders, FIXTURE_QUANTITY,
+};
+
+/* matches aren't forwarded, so nothing is ever actually waited on */
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+}
+
+/* 500 levels of 100 asks each */
+fn large_book() -> Book {
+    deep_book(Address::zero(), OrderSide::Ask, 1000, 500, 100)
+}
+
+fn submit(c: &mut Criterion) {
+    let runtime: Runtime = runtime();
+    let orders: Vec<Order> = non_crossing_orders(Address::zero(), 10_000);
+
+    c.bench_function("submit 10k non-crossing orders", |b| {
+        b.iter_batched(
+            || (Book::new(Address::zero()), orders.clone()),
+            |(mut book, orders)| {
+                runtime.block_on(async {
+                    for order in orders {
+                        book.submit_at(order, None, Utc::now()).await.unwrap();
+                    }
+                });
+                book
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn sweep(c: &mut Criterion) {
+    let runtime: Runtime = runtime();
+    let book: Book = deep_book(Address::zero(), OrderSide::Ask, 1000, 1000, 1);
+    let taker: Order = Order::new(
+        Address::zero(),
+        Address::zero(),
+        OrderSide::Bid,
+        U256::from(1999),
+        U256::from(1000 * FIXTURE_QUANTITY),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+
+    c.bench_function("sweep 1k levels", |b| {
+        b.iter_batched(
+            || (book.clone(), taker.clone()),
+            |(mut book, taker)| {
+                runtime
+                    .block_on(book.submit_at(taker, None, Utc::now()))
+                    .unwrap();
+                book
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn cancel(c: &mut Criterion) {
+    let book: Book = large_book();
+
+    /* the back of the book, which a scan would reach last */
+    let id: OrderId = book.asks.values().next_back().unwrap()[99].id;
+
+    c.bench_function("cancel by ID in a 50k-order book", |b| {
+        b.iter_batched(
+            || book.clone(),
+            |mut book| {
+                book.cancel(id).unwrap().unwrap();
+                book
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn depth(c: &mut Criterion) {
+    let book: Book = large_book();
+
+    c.bench_function("depth of a 50k-order book", |b| b.iter(|| book.depth()));
+    c.bench_function("aggregated depth of a 50k-order book", |b| {
+        b.iter(|| book.aggregated_depth(0))
+    });
+}
+
+fn external_book(c: &mut Criterion) {
+    let book: Book = large_book();
+    let external: ExternalBook = ExternalBook::from(book.clone());
+
+    c.bench_function("50k-order book to ExternalBook", |b| {
+        b.iter_batched(
+            || book.clone(),
+            ExternalBook::from,
+            BatchSize::LargeInput,
+        )
+    });
+    c.bench_function("50k-order book from ExternalBook", |b| {
+        b.iter_batched(
+            || external.clone(),
+            |external| Book::try_from(external).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn serialize(c: &mut Criterion) {
+    let book: Book = large_book();
+
+    c.bench_function("50k-order book to JSON via ExternalBook", |b| {
+        b.iter(|| serde_json::to_vec(&ExternalBook::from(book.clone())))
+    });
+    c.bench_function("50k-order book to JSON via BookSnapshot", |b| {
+        b.iter(|| serde_json::to_vec(&BookSnapshot(&book)))
+    });
+}
+
+criterion_group!(
+    benches,
+    submit,
+    sweep,
+    cancel,
+    depth,
+    external_book,
+    serialize
+);
+criterion_main!(benches);
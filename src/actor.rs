@@ -0,0 +1,411 @@
+//! Contains logic for hosting each order book in a task of its own, which is
+//! only ever reached through the commands sent to its mailbox
+//!
+//! Under the actor engine mode, matching in one market neither waits on nor
+//! holds up matching in any other, as no lock is shared between books.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use ethereum_types::U256;
+use thiserror::Error;
+use tokio::sync::{broadcast, mpsc, oneshot, RwLock};
+use web3::types::Address;
+
+use crate::book::{Book, BookError, MatchResult};
+use crate::event::{BookDelta, BookEvent};
+use crate::executioner::Executioner;
+use crate::order::{Order, OrderId};
+use crate::state::OmeState;
+use crate::wal::{self, SharedWal, WalChange, WalEntry};
+
+/// The number of commands that may wait in the mailbox of each book, beyond
+/// which senders wait for room
+pub const MAILBOX_CAPACITY: usize = 1024;
+
+/// A read-only query, run by the task hosting a book against the book itself
+pub type Query = Box<dyn FnOnce(&Book) + Send>;
+
+/// Represents the commands carried out by the task hosting a book, each of
+/// which is answered on a channel of its own
+#[allow(clippy::large_enum_variant)]
+pub enum BookCommand {
+    Submit {
+        order: Order,
+        executioner: Option<Executioner>, /* matches are forwarded to */
+        reply: oneshot::Sender<Result<Submitted, BookError>>,
+    },
+    Cancel {
+        order_id: OrderId,
+        reply: oneshot::Sender<Result<Option<Order>, BookError>>,
+    },
+    PurgeExpired {
+        now: DateTime<Utc>,
+        reply: oneshot::Sender<Vec<Order>>,
+    },
+    SetPaused {
+        paused: bool,
+        reply: oneshot::Sender<bool>,
+    },
+    SetSizeLimits {
+        max_quantity: Option<U256>,
+        max_notional: Option<U256>,
+        reply: oneshot::Sender<bool>,
+    },
+    Query(Query),
+    Snapshot {
+        reply: oneshot::Sender<Book>,
+    },
+}
+
+/// Represents the outcome of an order submitted to a book, along with the
+/// state it left the book in
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Submitted {
+    pub result: MatchResult,
+    pub sequence: u64, /* of the book once the order was matched */
+    pub resting: U256, /* remaining quantity of the order left on the book */
+}
+
+impl Submitted {
+    /// Constructor for the `Submitted` type, given the book the order has
+    /// just been matched against
+    pub fn new(book: &Book, result: MatchResult) -> Self {
+        let resting: U256 = book
+            .order(result.order_id)
+            .map(|order| order.remaining)
+            .unwrap_or_default();
+
+        Self {
+            result,
+            sequence: book.sequence,
+            resting,
+        }
+    }
+}
+
+/// Represents the reasons a command sent to a book cannot be carried out
+#[derive(Clone, Copy, Debug, Error, PartialEq, Eq)]
+pub enum ActorError {
+    #[error("Book {0:?} is no longer running")]
+    Stopped(Address),
+    #[error("{0}")]
+    Book(#[from] BookError),
+}
+
+/// A handle to the task hosting an order book, through which it is sent
+/// commands
+///
+/// The task stops once every handle to it has been dropped.
+#[derive(Clone, Debug)]
+pub struct BookHandle {
+    market: Address,
+    commands: mpsc::Sender<BookCommand>,
+    queued: Arc<AtomicUsize>, /* commands sent but not yet taken up */
+}
+
+impl BookHandle {
+    pub fn market(&self) -> Address {
+        self.market
+    }
+
+    /// Returns the number of commands waiting in the mailbox of the book
+    pub fn mailbox_depth(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    async fn send(&self, command: BookCommand) -> Result<(), ActorError> {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+
+        if self.commands.send(command).await.is_err() {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+            return Err(ActorError::Stopped(self.market));
+        }
+
+        Ok(())
+    }
+
+    async fn ask<T>(
+        &self,
+        command: BookCommand,
+        replied: oneshot::Receiver<T>,
+    ) -> Result<T, ActorError> {
+        self.send(command).await?;
+        replied.await.map_err(|_e| ActorError::Stopped(self.market))
+    }
+
+    /// Submits an order to the book for matching
+    pub async fn submit(
+        &self,
+        order: Order,
+        executioner: Option<Executioner>,
+    ) -> Result<Submitted, ActorError> {
+        let (reply, replied) = oneshot::channel();
+        let command: BookCommand = BookCommand::Submit {
+            order,
+            executioner,
+            reply,
+        };
+        Ok(self.ask(command, replied).await??)
+    }
+
+    /// Cancels an open order, returning it if there was one
+    pub async fn cancel(
+        &self,
+        order_id: OrderId,
+    ) -> Result<Option<Order>, ActorError> {
+        let (reply, replied) = oneshot::channel();
+        let command: BookCommand = BookCommand::Cancel { order_id, reply };
+        Ok(self.ask(command, replied).await??)
+    }
+
+    /// Purges the orders of the book that have expired as of `now`, returning
+    /// them
+    pub async fn purge_expired(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Order>, ActorError> {
+        let (reply, replied) = oneshot::channel();
+        let command: BookCommand = BookCommand::PurgeExpired { now, reply };
+        self.ask(command, replied).await
+    }
+
+    /// Pauses (or resumes) the acceptance of new orders by the book, returning
+    /// whether that changed anything
+    pub async fn set_paused(&self, paused: bool) -> Result<bool, ActorError> {
+        let (reply, replied) = oneshot::channel();
+        let command: BookCommand = BookCommand::SetPaused { paused, reply };
+        self.ask(command, replied).await
+    }
+
+    /// Changes the caps on the size of the orders of the book, returning
+    /// whether that changed anything
+    pub async fn set_size_limits(
+        &self,
+        max_quantity: Option<U256>,
+        max_notional: Option<U256>,
+    ) -> Result<bool, ActorError> {
+        let (reply, replied) = oneshot::channel();
+        let command: BookCommand = BookCommand::SetSizeLimits {
+            max_quantity,
+            max_notional,
+            reply,
+        };
+        self.ask(command, replied).await
+    }
+
+    /// Runs a read-only query against the book, returning its answer
+    ///
+    /// The query sees every change made by the commands sent before it, and
+    /// none of those sent after it.
+    pub async fn query<R, F>(&self, query: F) -> Result<R, ActorError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&Book) -> R + Send + 'static,
+    {
+        let (reply, replied) = oneshot::channel();
+        let command: BookCommand = BookCommand::Query(Box::new(move |book| {
+            let _ = reply.send(query(book));
+        }));
+        self.ask(command, replied).await
+    }
+
+    /// Returns a copy of the book as it stands
+    pub async fn snapshot(&self) -> Result<Book, ActorError> {
+        let (reply, replied) = oneshot::channel();
+        self.ask(BookCommand::Snapshot { reply }, replied).await
+    }
+}
+
+/// Hands an order book to a task of its own, returning the handle through
+/// which it is sent commands
+///
+/// Every change made by the task is recorded in the write-ahead log (if any)
+/// and its events and delta published to `events` and `deltas` before it is
+/// replied to, just as handlers do under the lock engine mode.
+pub fn spawn(
+    book: Book,
+    events: broadcast::Sender<BookEvent>,
+    deltas: broadcast::Sender<BookDelta>,
+    wal: Option<SharedWal>,
+) -> BookHandle {
+    let (commands, mailbox) = mpsc::channel(MAILBOX_CAPACITY);
+    let queued: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+    let handle: BookHandle = BookHandle {
+        market: *book.market(),
+        commands,
+        queued: queued.clone(),
+    };
+
+    tokio::spawn(run(book, mailbox, queued, Feeds { events, deltas }, wal));
+    handle
+}
+
+/// Carries out every command sent to a book, one at a time, until every
+/// handle to it has been dropped
+async fn run(
+    mut book: Book,
+    mut mailbox: mpsc::Receiver<BookCommand>,
+    queued: Arc<AtomicUsize>,
+    feeds: Feeds,
+    wal: Option<SharedWal>,
+) {
+    let market: Address = *book.market();
+
+    while let Some(command) = mailbox.recv().await {
+        queued.fetch_sub(1, Ordering::Relaxed);
+        let sequence: u64 = book.sequence;
+        let now: DateTime<Utc> = Utc::now();
+
+        match command {
+            BookCommand::Submit {
+                order,
+                executioner,
+                reply,
+            } => {
+                let change: WalChange = WalChange::Submit {
+                    order: order.clone().into(),
+                };
+                let result: Result<MatchResult, BookError> =
+                    book.submit_at(order, executioner, now).await;
+                if result.is_ok() {
+                    record(&wal, market, sequence, now, change);
+                }
+                feeds.publish(&mut book);
+                let _ = reply.send(result.map(|t| Submitted::new(&book, t)));
+            }
+            BookCommand::Cancel { order_id, reply } => {
+                let result: Result<Option<Order>, BookError> =
+                    book.cancel_at(order_id, now);
+                if let Ok(Some(_)) = result {
+                    let change: WalChange = WalChange::Cancel { order_id };
+                    record(&wal, market, sequence, now, change);
+                }
+                feeds.publish(&mut book);
+                let _ = reply.send(result);
+            }
+            BookCommand::PurgeExpired { now, reply } => {
+                let expired: Vec<Order> = book.purge_expired(now);
+                if !expired.is_empty() {
+                    let change: WalChange = WalChange::PurgeExpired;
+                    record(&wal, market, sequence, now, change);
+                }
+                feeds.publish(&mut book);
+                let _ = reply.send(expired);
+            }
+            BookCommand::SetPaused { paused, reply } => {
+                let changed: bool = book.set_paused(paused);
+                if changed {
+                    let change: WalChange = WalChange::SetPaused { paused };
+                    record(&wal, market, sequence, now, change);
+                }
+                let _ = reply.send(changed);
+            }
+            BookCommand::SetSizeLimits {
+                max_quantity,
+                max_notional,
+                reply,
+            } => {
+                let changed: bool =
+                    book.set_size_limits(max_quantity, max_notional);
+                if changed {
+                    let change: WalChange = WalChange::SetSizeLimits {
+                        max_quantity,
+                        max_notional,
+                    };
+                    record(&wal, market, sequence, now, change);
+                }
+                let _ = reply.send(changed);
+            }
+            BookCommand::Query(query) => query(&book),
+            BookCommand::Snapshot { reply } => {
+                let _ = reply.send(book.clone());
+            }
+        }
+    }
+
+    info!("Stopped hosting book {}", market);
+}
+
+/// Records a change made to a book in the write-ahead log, if any
+fn record(
+    wal: &Option<SharedWal>,
+    market: Address,
+    sequence: u64,
+    timestamp: DateTime<Utc>,
+    change: WalChange,
+) {
+    if let Some(wal) = wal {
+        wal::record(wal, WalEntry::new(market, sequence, timestamp, change));
+    }
+}
+
+/// The channels through which changes made by a book are published
+struct Feeds {
+    events: broadcast::Sender<BookEvent>,
+    deltas: broadcast::Sender<BookDelta>,
+}
+
+impl Feeds {
+    /// Publishes the changes recorded by a book to all subscribers
+    fn publish(&self, book: &mut Book) {
+        let events: Vec<BookEvent> = book.drain_events();
+        if events.is_empty() {
+            return;
+        }
+
+        for event in events {
+            /* sending only fails if there are no subscribers at all */
+            let _ = self.events.send(event);
+        }
+
+        if let Some(delta) = book.last_delta() {
+            let _ = self.deltas.send(delta.clone());
+        }
+    }
+}
+
+/// Runs a read-only query against the book of the given market, however the
+/// engine hosts it, returning `None` if there is no such book
+///
+/// Under the lock engine mode the query runs under a read lock of the state,
+/// whereas under the actor engine mode the state is only locked for long
+/// enough to find the book's handle.
+pub async fn query_book<R, F>(
+    state: &RwLock<OmeState>,
+    market: Address,
+    query: F,
+) -> Option<R>
+where
+    R: Send + 'static,
+    F: FnOnce(&Book) -> R + Send + 'static,
+{
+    let ome_state = state.read().await;
+
+    match ome_state.handle(market) {
+        Some(handle) => {
+            drop(ome_state);
+            handle.query(query).await.ok()
+        }
+        None => ome_state.book(market).map(query),
+    }
+}
+
+/// Renders the depth of the mailbox of every book in the Prometheus text
+/// exposition format
+pub fn render_mailbox_depths(handles: &[BookHandle]) -> String {
+    let mut rendered: String = "# HELP ome_book_mailbox_depth Commands \
+                                waiting in the mailbox of each book\n\
+                                # TYPE ome_book_mailbox_depth gauge\n"
+        .to_string();
+
+    for handle in handles {
+        rendered.push_str(&format!(
+            "ome_book_mailbox_depth{{market=\"{:?}\"}} {}\n",
+            handle.market(),
+            handle.mailbox_depth()
+        ));
+    }
+
+    rendered
+}
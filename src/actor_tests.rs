@@ -0,0 +1,235 @@
+use chrono::Utc;
+use ethereum_types::{Address, U256};
+
+use crate::actor::{self, ActorError, BookHandle};
+use crate::args::EngineMode;
+use crate::book::{Book, BookError, MatchResult, OrderStatus};
+use crate::event::{BookEventKind, EVENT_CAPACITY};
+use crate::order::{Order, OrderId, OrderSide, TimeInForce};
+use crate::state::OmeState;
+use crate::test_util::far_future;
+
+const MODES: [EngineMode; 2] = [EngineMode::Lock, EngineMode::Actor];
+
+/* a book driven either directly, as handlers do under the lock engine mode,
+ * or through the task hosting it, as under the actor engine mode */
+#[allow(clippy::large_enum_variant)]
+enum Engine {
+    Lock(Book),
+    Actor(BookHandle),
+}
+
+impl Engine {
+    fn new(book: Book, mode: EngineMode) -> Self {
+        match mode {
+            EngineMode::Lock => Engine::Lock(book),
+            EngineMode::Actor => Engine::Actor(actor::spawn(
+                book,
+                tokio::sync::broadcast::channel(EVENT_CAPACITY).0,
+                tokio::sync::broadcast::channel(EVENT_CAPACITY).0,
+                None,
+            )),
+        }
+    }
+
+    async fn submit(&mut self, order: Order) -> Result<MatchResult, BookError> {
+        match self {
+            Engine::Lock(book) => book.submit_at(order, None, Utc::now()).await,
+            Engine::Actor(handle) => match handle.submit(order, None).await {
+                Ok(t) => Ok(t.result),
+                Err(ActorError::Book(e)) => Err(e),
+                Err(e) => panic!("{}", e),
+            },
+        }
+    }
+
+    async fn cancel(
+        &mut self,
+        id: OrderId,
+    ) -> Result<Option<Order>, BookError> {
+        match self {
+            Engine::Lock(book) => book.cancel_at(id, Utc::now()),
+            Engine::Actor(handle) => match handle.cancel(id).await {
+                Ok(t) => Ok(t),
+                Err(ActorError::Book(e)) => Err(e),
+                Err(e) => panic!("{}", e),
+            },
+        }
+    }
+
+    async fn book(&self) -> Book {
+        match self {
+            Engine::Lock(book) => book.clone(),
+            Engine::Actor(handle) => handle.snapshot().await.unwrap(),
+        }
+    }
+}
+
+fn order(trader: u64, side: OrderSide, price: u64, quantity: u64) -> Order {
+    Order::new(
+        Address::from_low_u64_be(trader),
+        Address::zero(),
+        side,
+        U256::from(price),
+        U256::from(quantity),
+        far_future(),
+        Utc::now(),
+        vec![],
+    )
+}
+
+/* the same book as `book_tests`, with five levels on either side */
+async fn setup(mode: EngineMode) -> Engine {
+    let mut engine: Engine = Engine::new(Book::new(Address::zero()), mode);
+    let orders: Vec<(u64, OrderSide, u64, u64)> = vec![
+        (5, OrderSide::Bid, 95, 10),
+        (6, OrderSide::Bid, 94, 20),
+        (7, OrderSide::Bid, 93, 5),
+        (8, OrderSide::Bid, 92, 10),
+        (9, OrderSide::Bid, 91, 15),
+        (0, OrderSide::Ask, 100, 10),
+        (1, OrderSide::Ask, 99, 2),
+        (2, OrderSide::Ask, 98, 35),
+        (3, OrderSide::Ask, 97, 15),
+        (4, OrderSide::Ask, 96, 5),
+    ];
+
+    for (trader, side, price, quantity) in orders {
+        engine
+            .submit(order(trader, side, price, quantity))
+            .await
+            .unwrap();
+    }
+
+    engine
+}
+
+#[tokio::test]
+pub async fn test_simple_buy() {
+    for mode in MODES.iter() {
+        let mut engine: Engine = setup(*mode).await;
+
+        let result: MatchResult = engine
+            .submit(order(3, OrderSide::Bid, 96, 5))
+            .await
+            .unwrap();
+
+        assert_eq!(result.order_status, OrderStatus::FullMatch, "{:?}", mode);
+        assert_eq!(engine.book().await.depth(), (5, 4), "{:?}", mode);
+    }
+}
+
+#[tokio::test]
+pub async fn test_deep_buy() {
+    for mode in MODES.iter() {
+        let mut engine: Engine = setup(*mode).await;
+
+        engine
+            .submit(order(10, OrderSide::Bid, 99, 42))
+            .await
+            .unwrap();
+
+        let book: Book = engine.book().await;
+        assert_eq!(book.depth(), (5, 3), "{:?}", mode);
+        assert_eq!(book.validate(), Ok(()), "{:?}", mode);
+    }
+}
+
+#[tokio::test]
+pub async fn test_no_self_matching() {
+    for mode in MODES.iter() {
+        let mut engine: Engine = setup(*mode).await;
+
+        /* trader 1 already rests an ask at 99 */
+        let result: MatchResult = engine
+            .submit(order(1, OrderSide::Bid, 97, 15))
+            .await
+            .unwrap();
+
+        assert_eq!(result.order_status, OrderStatus::FullMatch, "{:?}", mode);
+        assert_eq!(engine.book().await.depth(), (5, 4), "{:?}", mode);
+    }
+}
+
+#[tokio::test]
+pub async fn test_immediate_or_cancel_drops_remainder() {
+    for mode in MODES.iter() {
+        let mut engine: Engine = setup(*mode).await;
+        let bid: Order = order(10, OrderSide::Bid, 97, 30)
+            .with_time_in_force(TimeInForce::ImmediateOrCancel);
+
+        let result: MatchResult = engine.submit(bid.clone()).await.unwrap();
+
+        let book: Book = engine.book().await;
+        assert_eq!(result.order_status, OrderStatus::PartialMatch);
+        assert_eq!(book.depth(), (5, 3), "{:?}", mode);
+        assert!(book.order(bid.id).is_none(), "{:?}", mode);
+    }
+}
+
+#[tokio::test]
+pub async fn test_fill_or_kill_insufficient_liquidity_is_killed() {
+    for mode in MODES.iter() {
+        let mut engine: Engine = setup(*mode).await;
+        let expected_book: Book = engine.book().await;
+        let bid: Order = order(10, OrderSide::Bid, 97, 30)
+            .with_time_in_force(TimeInForce::FillOrKill);
+
+        let result: MatchResult = engine.submit(bid).await.unwrap();
+
+        assert_eq!(result.order_status, OrderStatus::Killed, "{:?}", mode);
+        assert_eq!(engine.book().await, expected_book, "{:?}", mode);
+    }
+}
+
+#[tokio::test]
+pub async fn test_cancel() {
+    for mode in MODES.iter() {
+        let mut engine: Engine = setup(*mode).await;
+        let bid: Order = order(10, OrderSide::Bid, 90, 5);
+        engine.submit(bid.clone()).await.unwrap();
+
+        let cancelled: Option<Order> = engine.cancel(bid.id).await.unwrap();
+        assert_eq!(cancelled.map(|t| t.id), Some(bid.id), "{:?}", mode);
+        assert_eq!(engine.cancel(bid.id).await, Ok(None), "{:?}", mode);
+        assert_eq!(engine.book().await.depth(), (5, 5), "{:?}", mode);
+    }
+}
+
+#[tokio::test]
+pub async fn test_hosted_books_publish_and_recall() {
+    let market: Address = Address::zero();
+    let mut book: Book = Book::new(market);
+    book.submit_at(order(1, OrderSide::Ask, 100, 10), None, Utc::now())
+        .await
+        .unwrap();
+    let mut state: OmeState = OmeState::new();
+    state.add_book(book);
+    let mut events = state.subscribe();
+
+    state.host_books();
+    assert!(state.hosting());
+    assert!(state.book(market).is_none());
+    assert_eq!(state.markets(), vec![market]);
+
+    /* changes made by the book's task are published as they happen */
+    let handle: BookHandle = state.handle(market).unwrap();
+    let bid: Order = order(2, OrderSide::Bid, 90, 5);
+    let id: OrderId = bid.id;
+    handle.submit(bid, None).await.unwrap();
+    assert_eq!(handle.mailbox_depth(), 0);
+    assert!(matches!(
+        events.recv().await.unwrap().kind,
+        BookEventKind::Added(t) if t.id == id
+    ));
+
+    /* queries see every change sent before them */
+    assert_eq!(handle.query(move |t| t.order(id).is_some()).await, Ok(true));
+
+    /* books are handed back once recalled, and indexed once more */
+    state.recall_books().await;
+    assert!(!state.hosting());
+    assert!(state.handle(market).is_none());
+    assert_eq!(state.book(market).unwrap().depth(), (1, 1));
+    assert!(state.find_order(id).is_some());
+}
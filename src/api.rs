@@ -0,0 +1,986 @@
+//! Contains the types exchanged with the OME over its HTTP API
+//!
+//! These are shared by the server (see `handler`) and by clients of the OME
+//! (see `client`), so none of them depend upon the server itself.
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::serde::ts_seconds;
+use chrono::{DateTime, Utc};
+use ethereum_types::{Address, H256, U256};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::book::{
+    default_increment, BookError, ExternalBook, ExternalFill,
+    ExternalLastTrade, MatchingAlgorithm, OrderSizeLimit, SelfTradePolicy,
+    Ticker, TraderExposure,
+};
+use crate::event::BookDelta;
+use crate::history::{
+    ExternalOrderEvent, OrderEvent, OrderEventKind, OrderRecord,
+};
+use crate::order::{
+    ExternalOrder, Order, OrderId, OrderParseError, OrderSide, OrderType,
+    SignatureError, TimeInForce, TimestampError,
+};
+use crate::stats::Stats;
+use crate::util::{from_hex_de, from_hex_opt_de, from_hex_opt_se, from_hex_se};
+#[cfg(feature = "server")]
+use crate::webhook::Webhook;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct OmeResponse {
+    pub status: u16, /* this should be the StatusCode type, but serde */
+    pub message: String,
+}
+
+/// Represents the API response to a readiness check
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HealthResponse {
+    pub status: u16,
+    pub message: String,
+    pub last_market_sync: Option<DateTime<Utc>>, /* never, if `None` */
+}
+
+/// Represents an API request to create a new order book
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateBookRequest {
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub market: Address,
+    #[serde(default)]
+    pub self_trade_policy: SelfTradePolicy,
+    #[serde(default)]
+    pub matching_algorithm: MatchingAlgorithm,
+    #[serde(
+        default = "default_increment",
+        serialize_with = "from_hex_se",
+        deserialize_with = "from_hex_de"
+    )]
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub tick_size: U256, /* minimum price increment */
+    #[serde(
+        default = "default_increment",
+        serialize_with = "from_hex_se",
+        deserialize_with = "from_hex_de"
+    )]
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub lot_size: U256, /* minimum quantity increment */
+    #[serde(
+        default,
+        serialize_with = "from_hex_se",
+        deserialize_with = "from_hex_de"
+    )]
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub min_quantity: U256, /* minimum order quantity (defaults to none) */
+    #[serde(
+        default,
+        serialize_with = "from_hex_opt_se",
+        deserialize_with = "from_hex_opt_de"
+    )]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>))]
+    pub max_quantity: Option<U256>, /* maximum order quantity (if any) */
+    #[serde(
+        default,
+        serialize_with = "from_hex_opt_se",
+        deserialize_with = "from_hex_opt_de"
+    )]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>))]
+    pub max_notional: Option<U256>, /* maximum order notional (if any) */
+    #[serde(default)]
+    pub max_orders_per_trader: usize, /* open order cap (defaults to none) */
+    #[serde(default)]
+    pub maker_fee_bps: u32, /* fee charged to makers (defaults to none) */
+    #[serde(default)]
+    pub taker_fee_bps: u32, /* fee charged to takers (defaults to none) */
+}
+
+/// Represents an API request to change the caps on the size of the orders of
+/// a book
+///
+/// Both caps are replaced, so a cap left out of the request is removed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateBookLimitsRequest {
+    #[serde(
+        default,
+        serialize_with = "from_hex_opt_se",
+        deserialize_with = "from_hex_opt_de"
+    )]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>))]
+    pub max_quantity: Option<U256>, /* maximum order quantity (if any) */
+    #[serde(
+        default,
+        serialize_with = "from_hex_opt_se",
+        deserialize_with = "from_hex_opt_de"
+    )]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>))]
+    pub max_notional: Option<U256>, /* maximum order notional (if any) */
+}
+
+/// Represents an API request to create a new order
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema),
+    schema(example = json!({
+        "user": "0x00000000000000000000000000000000000000aa",
+        "target_tracer": "0x00000000000000000000000000000000000000bb",
+        "side": "Bid",
+        "price": "100",
+        "amount": "10",
+        "expiration": 1900000000,
+        "created": 1600000000,
+        "signed_data": "0x",
+        "time_in_force": "GTC",
+        "order_type": "Limit",
+        "trigger_price": null,
+        "client_order_id": "mm-1",
+        "nonce": "0"
+    }))
+)]
+pub struct CreateOrderRequest {
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub user: Address, /* Ethereum address of trader */
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub target_tracer: Address, /* address of the Tracer smart contract */
+    pub side: OrderSide, /* side of the market of the order */
+    #[serde(serialize_with = "from_hex_se", deserialize_with = "from_hex_de")]
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub price: U256, /* price */
+    #[serde(serialize_with = "from_hex_se", deserialize_with = "from_hex_de")]
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub amount: U256, /* quantity */
+    #[serde(with = "ts_seconds")]
+    #[cfg_attr(feature = "openapi", schema(value_type = i64))]
+    pub expiration: DateTime<Utc>, /* expiration of the order */
+    #[serde(with = "ts_seconds")]
+    #[cfg_attr(feature = "openapi", schema(value_type = i64))]
+    pub created: DateTime<Utc>, /* creation time of the order */
+    pub signed_data: String, /* digital signature of the order */
+    #[serde(default)]
+    pub time_in_force: TimeInForce, /* GTC, IOC, or FOK (defaults to GTC) */
+    #[serde(default)]
+    pub order_type: OrderType, /* limit, stop, or stop-limit (default limit) */
+    #[serde(
+        default,
+        serialize_with = "from_hex_opt_se",
+        deserialize_with = "from_hex_opt_de"
+    )]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>))]
+    pub trigger_price: Option<U256>, /* trigger price of stop orders */
+    #[serde(default)]
+    pub client_order_id: Option<String>, /* trader's own ID for the order */
+    #[serde(
+        default,
+        serialize_with = "from_hex_se",
+        deserialize_with = "from_hex_de"
+    )]
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub nonce: U256, /* distinguishes otherwise identical orders */
+}
+
+impl CreateOrderRequest {
+    /// Returns the trader the order is for
+    pub fn trader(&self) -> Address {
+        self.user
+    }
+}
+
+impl From<CreateOrderRequest> for ExternalOrder {
+    fn from(value: CreateOrderRequest) -> Self {
+        /* extract request fields */
+        let user: Address = value.user;
+        let target_tracer: Address = value.target_tracer;
+        let side: OrderSide = value.side;
+        let price: U256 = value.price;
+        let amount: U256 = value.amount;
+        let expiration: DateTime<Utc> = value.expiration;
+        let created: DateTime<Utc> = value.created;
+        let signed_data: String = value.signed_data;
+        let time_in_force: TimeInForce = value.time_in_force;
+        let order_type: OrderType = value.order_type;
+        let trigger_price: Option<U256> = value.trigger_price;
+        let client_order_id: Option<String> = value.client_order_id;
+        let nonce: U256 = value.nonce;
+
+        let user_bytes: Vec<u8> = user.as_ref().to_vec();
+        let target_tracer_bytes: Vec<u8> = target_tracer.as_ref().to_vec();
+
+        let order: ExternalOrder = Self {
+            id: hex::encode(H256::zero().as_ref()),
+            user: hex::encode(&user_bytes),
+            target_tracer: hex::encode(&target_tracer_bytes),
+            side: side.to_string(),
+            price: price.to_string(),
+            amount: amount.to_string(),
+            amount_left: amount.to_string(),
+            expiration: expiration.timestamp().to_string(),
+            created: created.timestamp().to_string(),
+            signed_data,
+            time_in_force: time_in_force.to_string(),
+            order_type: order_type.to_string(),
+            trigger_price: trigger_price
+                .map(|price| price.to_string())
+                .unwrap_or_default(),
+            client_order_id: client_order_id.unwrap_or_default(),
+            nonce: nonce.to_string(),
+        };
+
+        order
+    }
+}
+
+/// Represents an API request to amend an existing order
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateOrderRequest {
+    #[serde(serialize_with = "from_hex_se", deserialize_with = "from_hex_de")]
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub price: U256, /* new price */
+    #[serde(serialize_with = "from_hex_se", deserialize_with = "from_hex_de")]
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub amount: U256, /* new total quantity (including any filled volume) */
+}
+
+/// Represents an API request to cancel several orders at once
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CancelOrdersRequest {
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<String>))]
+    pub order_ids: Vec<OrderId>,
+}
+
+/// Represents the outcome of cancelling a single order of a batch
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum CancelOutcome {
+    Cancelled,
+    NotFound, /* no such open order in the book */
+}
+
+/// Represents the result of cancelling a single order of a batch
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CancelResult {
+    pub order_id: String,
+    pub outcome: CancelOutcome,
+    pub remaining: Option<String>, /* unfilled amount of a cancelled order */
+}
+
+/// Represents the API response to a batch cancellation
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CancelOrdersResponse {
+    pub results: Vec<CancelResult>, /* in the order the IDs were requested */
+    pub sequence: u64,              /* sequence number of the book afterwards */
+}
+
+/// Represents a request to register a webhook
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateWebhookRequest {
+    pub url: String, /* HTTP(S) URL that fills are posted to */
+    #[serde(default)]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>))]
+    pub market: Option<Address>, /* only fills in this market */
+    #[serde(default)]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>))]
+    pub user: Option<Address>, /* only fills of this trader's orders */
+    pub secret: String, /* key the payloads are signed with */
+}
+
+/// Represents a registered webhook, less its secret
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WebhookResponse {
+    pub id: u64,
+    pub url: String,
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>))]
+    pub market: Option<Address>,
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>))]
+    pub user: Option<Address>,
+}
+
+#[cfg(feature = "server")]
+impl From<&Webhook> for WebhookResponse {
+    fn from(value: &Webhook) -> Self {
+        Self {
+            id: value.id,
+            url: value.url.clone(),
+            market: value.market,
+            user: value.user,
+        }
+    }
+}
+
+/// Represents the outcome of dumping the state on request
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DumpResponse {
+    pub path: String, /* of the dump file written */
+    pub size: u64,    /* of the dump, in bytes */
+}
+
+/// Represents the outcome of reloading an order book from the source of
+/// markets
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReloadBookResponse {
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub market: Address,
+    pub orders_before: usize, /* open orders in the book that was replaced */
+    pub orders_after: usize,  /* open orders in the book now */
+}
+
+/// Represents the query parameters of a request to block a trader
+#[derive(
+    Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize,
+)]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct BlockTraderQuery {
+    #[serde(default)]
+    pub cancel_existing: bool, /* whether to cancel their open orders too */
+}
+
+/// Represents the outcome of blocking or unblocking a trader
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BlockTraderResponse {
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub trader: Address,
+    pub blocked: bool, /* whether the trader is now blocked */
+    pub changed: bool, /* whether the trader was (un)blocked by the request */
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<String>))]
+    pub cancelled: Vec<OrderId>, /* open orders cancelled with the block */
+}
+
+/// Represents the traders blocked from submitting orders
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BlocklistResponse {
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<String>))]
+    pub traders: Vec<Address>, /* in ascending order */
+}
+
+/// Represents the query parameters of an aggregated depth request
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct DepthQuery {
+    #[serde(default)]
+    pub levels: usize, /* levels per side (zero for all of them) */
+}
+
+/// Represents the aggregated depth of an order book, as (price, quantity)
+/// pairs of decimal strings with the best prices first
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DepthResponse {
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<Vec<String>>))]
+    pub bids: Vec<(String, String)>,
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<Vec<String>>))]
+    pub asks: Vec<(String, String)>,
+    pub sequence: u64, /* sequence number of the book */
+}
+
+/// Represents the query parameters of a request to catch up with an order
+/// book
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct SnapshotQuery {
+    pub since_seq: Option<u64>, /* sequence number the client is at */
+}
+
+/// Represents what a client needs to catch up with an order book: either
+/// every delta since the sequence number it is at, or (if some of those are
+/// no longer retained) the entire book
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[allow(clippy::large_enum_variant)]
+pub enum SnapshotResponse {
+    Deltas {
+        seq: u64, /* sequence number of the book after the last delta */
+        deltas: Vec<BookDelta>,
+    },
+    Snapshot {
+        seq: u64, /* sequence number of the book */
+        book: ExternalBook,
+    },
+}
+
+/// Represents the query parameters of a lookup by client order ID
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct ClientOrderQuery {
+    #[cfg_attr(feature = "openapi", param(value_type = String))]
+    pub user: Address, /* trader who assigned the client order ID */
+}
+
+/// Represents the query parameters of a trade history request
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct TradesQuery {
+    #[serde(default)]
+    pub limit: usize, /* maximum number of trades (zero for all of them) */
+}
+
+/// Represents the query parameters of a request for a trader's orders
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct UserOrdersQuery {
+    #[serde(default)]
+    pub limit: usize, /* maximum number of orders (zero for all of them) */
+    #[serde(default)]
+    pub offset: usize, /* number of orders to skip */
+}
+
+/// Represents a trader's orders in every market, keyed by market
+pub type UserOrdersResponse = BTreeMap<String, Vec<ExternalOrder>>;
+
+/// Represents the exposure of a trader in a market (or across markets), with
+/// every number as a decimal string
+///
+/// See `Book::trader_exposure`.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ExposureResponse {
+    pub open_orders: String, /* including untriggered stop orders */
+    pub bid_quantity: String,
+    pub ask_quantity: String,
+    pub notional: String,
+}
+
+impl From<TraderExposure> for ExposureResponse {
+    fn from(value: TraderExposure) -> Self {
+        Self {
+            open_orders: value.open_orders.to_string(),
+            bid_quantity: value.bid_quantity.to_string(),
+            ask_quantity: value.ask_quantity.to_string(),
+            notional: value.notional.to_string(),
+        }
+    }
+}
+
+/// Represents a summary of a trader's open orders, in each market they have
+/// any in (keyed by market) and in total
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserSummaryResponse {
+    pub markets: BTreeMap<String, ExposureResponse>,
+    pub total: ExposureResponse,
+}
+
+/// Represents the recent trades of an order book, newest first
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TradesResponse {
+    pub trades: Vec<ExternalFill>,
+    pub sequence: u64, /* sequence number of the book */
+}
+
+/// Represents the history of an order, oldest event first
+///
+/// If `truncated` is set, the earliest events of the order were evicted.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct OrderHistoryResponse {
+    pub order_id: String,
+    pub events: Vec<ExternalOrderEvent>,
+    pub truncated: bool,
+}
+
+impl OrderHistoryResponse {
+    /// Builds the response from the retained history of an order
+    pub fn new(order_id: OrderId, record: &OrderRecord) -> Self {
+        Self {
+            order_id: format!("{:?}", order_id),
+            events: record.events.iter().cloned().map(Into::into).collect(),
+            truncated: record.truncated,
+        }
+    }
+}
+
+/// Represents a version of the HTTP API, as given by the prefix of its paths
+///
+/// Unversioned paths are served as `V1`, which keeps the original shapes of
+/// responses. `V2` serves orders along with their status (see `OrderView`).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    /// The version used by `OmeClient`
+    pub const LATEST: ApiVersion = ApiVersion::V2;
+
+    /// Returns the prefix of the paths of this version, e.g., `v1`
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "v1",
+            ApiVersion::V2 => "v2",
+        }
+    }
+}
+
+/// Represents the status of an order, as served alongside it
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum OrderViewStatus {
+    Open,            /* in the book, with nothing yet matched */
+    PartiallyFilled, /* in the book, with some volume matched */
+    Filled,
+    Cancelled,
+    Expired,
+    Killed,
+}
+
+/// Represents a single order and its status
+///
+/// `order` is `null` once the order has closed, in which case the status and
+/// `remaining` are those given by the last event of its history.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct OrderView {
+    pub order_id: String,
+    pub status: OrderViewStatus,
+    pub remaining: String,
+    pub order: Option<ExternalOrder>,
+}
+
+impl OrderView {
+    /// Builds the view of an order from the order itself, if still open, or
+    /// otherwise from its retained history
+    ///
+    /// Returns `None` if nothing is known of the order, or if its history
+    /// doesn't show it as closed.
+    pub fn new(
+        order_id: OrderId,
+        order: Option<&Order>,
+        record: Option<&OrderRecord>,
+    ) -> Option<Self> {
+        if let Some(order) = order {
+            let status: OrderViewStatus = if order.remaining < order.quantity {
+                OrderViewStatus::PartiallyFilled
+            } else {
+                OrderViewStatus::Open
+            };
+
+            return Some(Self {
+                order_id: format!("{:?}", order_id),
+                status,
+                remaining: order.remaining.to_string(),
+                order: Some(order.clone().into()),
+            });
+        }
+
+        let event: &OrderEvent = record?.events.back()?;
+        let status: OrderViewStatus = match event.kind {
+            OrderEventKind::Filled => OrderViewStatus::Filled,
+            OrderEventKind::Cancelled => OrderViewStatus::Cancelled,
+            OrderEventKind::Expired => OrderViewStatus::Expired,
+            OrderEventKind::Killed => OrderViewStatus::Killed,
+            _ => return None,
+        };
+
+        Some(Self {
+            order_id: format!("{:?}", order_id),
+            status,
+            remaining: event.remaining.to_string(),
+            order: None,
+        })
+    }
+}
+
+/// Represents the ticker of an order book, as decimal strings
+///
+/// The best price and volume fields of an empty side are `null`, as is the
+/// last trade of a book that has seen none.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TickerResponse {
+    pub best_bid: Option<String>,
+    pub best_ask: Option<String>,
+    pub bid_volume_at_best: Option<String>,
+    pub ask_volume_at_best: Option<String>,
+    pub last_traded_price: String,
+    #[serde(default)]
+    pub last_trade: Option<ExternalLastTrade>,
+    pub spread: String,
+    pub sequence: u64, /* sequence number of the book */
+}
+
+impl From<Ticker> for TickerResponse {
+    fn from(value: Ticker) -> Self {
+        Self {
+            best_bid: value.best_bid.map(|t| t.to_string()),
+            best_ask: value.best_ask.map(|t| t.to_string()),
+            bid_volume_at_best: value.bid_volume_at_best.map(|t| t.to_string()),
+            ask_volume_at_best: value.ask_volume_at_best.map(|t| t.to_string()),
+            last_traded_price: value.last_traded_price.to_string(),
+            last_trade: value.last_trade.map(ExternalLastTrade::from),
+            spread: value.spread.to_string(),
+            sequence: value.sequence,
+        }
+    }
+}
+
+/// Represents the trade statistics of an order book over the last 24 hours,
+/// as decimal strings
+///
+/// The high and low prices are `null` if there were no trades.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StatsResponse {
+    pub volume_24h: String,   /* total quantity traded */
+    pub notional_24h: String, /* total notional value of the trades */
+    pub trades_24h: String,
+    pub high_24h: Option<String>,
+    pub low_24h: Option<String>,
+    pub last_price: String, /* last traded price, however long ago */
+}
+
+impl StatsResponse {
+    /// Builds the response from the statistics and last traded price of a
+    /// book
+    pub fn new(stats: Stats, last_price: U256) -> Self {
+        Self {
+            volume_24h: stats.volume.to_string(),
+            notional_24h: stats.notional.to_string(),
+            trades_24h: stats.trades.to_string(),
+            high_24h: stats.high.map(|t| t.to_string()),
+            low_24h: stats.low.map(|t| t.to_string()),
+            last_price: last_price.to_string(),
+        }
+    }
+}
+
+/// Represents the API response to a successful order submission
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MatchResponse {
+    pub status: u16,
+    pub message: String,  /* status of the submitted order */
+    pub order_id: String, /* ID of the submitted order */
+    #[serde(default)]
+    pub client_order_id: Option<String>, /* trader's own ID for the order */
+    pub sequence: u64,    /* sequence number of the book after matching */
+    pub remaining: String, /* unfilled amount of the order */
+    pub resting: String,  /* amount of the order left open in the book */
+    pub average_price: String, /* volume-weighted price of the fills */
+    pub fills: Vec<ExternalFill>, /* trades resulting from the order */
+    #[serde(default)]
+    pub triggered: Vec<String>, /* IDs of stop orders triggered by the fills */
+    #[serde(default)]
+    pub closed_makers: Vec<String>, /* IDs of resting orders filled entirely */
+    #[serde(default)]
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<Vec<String>>))]
+    pub reduced_makers: Vec<(String, String)>, /* (ID, remaining) pairs */
+}
+
+/// Represents the API response to a successful order replacement
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReplaceOrderResponse {
+    pub cancelled: ExternalOrder, /* replaced order, with unfilled remainder */
+    pub created: MatchResponse,   /* result of matching the new order */
+}
+
+/// Represents the API response to a quote, i.e., the hypothetical outcome of
+/// submitting an order
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct QuoteResponse {
+    pub message: String,       /* status the order would have */
+    pub order_id: String,      /* ID the order would have */
+    pub sequence: u64,         /* sequence number of the book quoted against */
+    pub remaining: String,     /* amount of the order that would go unfilled */
+    pub average_price: String, /* volume-weighted price of the fills */
+    pub slippage: String,      /* distance of the average price from the best */
+    pub fills: Vec<ExternalFill>, /* trades that would result from the order */
+}
+
+/// Represents an error response from the API
+///
+/// Every failed request, including those rejected before reaching a handler,
+/// is answered with this envelope. `code` is a stable, machine-readable
+/// identifier whereas `message` is meant for humans.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ApiError {
+    pub status: u16, /* this should be the StatusCode type, but serde */
+    pub code: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_ms: Option<u64>, /* when the request may be retried */
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "from_hex_opt_se",
+        deserialize_with = "from_hex_opt_de"
+    )]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>))]
+    pub limit: Option<U256>, /* the cap an order exceeded (if any) */
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &str, message: &str) -> Self {
+        Self {
+            status: status.as_u16(),
+            code: code.to_string(),
+            message: message.to_string(),
+            retry_after_ms: None,
+            limit: None,
+        }
+    }
+
+    pub fn unauthorized() -> Self {
+        Self::new(
+            StatusCode::UNAUTHORIZED,
+            "UNAUTHORIZED",
+            "Missing or invalid admin token",
+        )
+    }
+
+    pub fn rate_limited(retry_after: Duration) -> Self {
+        Self {
+            retry_after_ms: Some(
+                (retry_after.as_secs_f64() * 1000.0).ceil() as u64
+            ),
+            ..Self::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                "RATE_LIMITED",
+                "Too many requests",
+            )
+        }
+    }
+
+    pub fn market_not_found() -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            "MARKET_NOT_FOUND",
+            "Market does not exist",
+        )
+    }
+
+    pub fn shutting_down() -> Self {
+        Self::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "SHUTTING_DOWN",
+            "Engine is shutting down",
+        )
+    }
+
+    pub fn not_ready(reason: &str) -> Self {
+        Self::new(StatusCode::SERVICE_UNAVAILABLE, "NOT_READY", reason)
+    }
+
+    pub fn trader_blocked() -> Self {
+        Self::new(
+            StatusCode::FORBIDDEN,
+            "TRADER_BLOCKED",
+            "Trader may not submit orders",
+        )
+    }
+
+    pub fn unsupported_engine_mode() -> Self {
+        Self::new(
+            StatusCode::NOT_IMPLEMENTED,
+            "UNSUPPORTED_ENGINE_MODE",
+            "Not supported under the current engine mode",
+        )
+    }
+
+    /// Returns the HTTP status code of this error
+    pub fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.status)
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+impl From<BookError> for ApiError {
+    fn from(error: BookError) -> Self {
+        match error {
+            BookError::NoSuchOrder => Self::new(
+                StatusCode::NOT_FOUND,
+                "ORDER_NOT_FOUND",
+                "Order does not exist in this market",
+            ),
+            BookError::OrderExpired => Self::new(
+                StatusCode::BAD_REQUEST,
+                "ORDER_EXPIRED",
+                "Order expired",
+            ),
+            BookError::InvalidAmendment => Self::new(
+                StatusCode::BAD_REQUEST,
+                "INVALID_AMENDMENT",
+                "Invalid amendment",
+            ),
+            BookError::InvalidQuantity | BookError::InvalidPrice => Self::new(
+                StatusCode::BAD_REQUEST,
+                "INVALID_ORDER",
+                "Invalid order",
+            ),
+            BookError::InvalidTickSize => Self::new(
+                StatusCode::BAD_REQUEST,
+                "INVALID_TICK_SIZE",
+                "Price is not a multiple of the tick size",
+            ),
+            BookError::InvalidLotSize => Self::new(
+                StatusCode::BAD_REQUEST,
+                "INVALID_LOT_SIZE",
+                "Amount is not a multiple of the lot size",
+            ),
+            BookError::InvalidTrigger => Self::new(
+                StatusCode::BAD_REQUEST,
+                "INVALID_TRIGGER",
+                "Invalid trigger price",
+            ),
+            BookError::BelowMinQuantity => Self::new(
+                StatusCode::BAD_REQUEST,
+                "BELOW_MIN_QUANTITY",
+                "Amount is below the minimum order size",
+            ),
+            BookError::OrderTooLarge(OrderSizeLimit::Quantity(limit)) => Self {
+                limit: Some(limit),
+                ..Self::new(
+                    StatusCode::BAD_REQUEST,
+                    "ORDER_TOO_LARGE",
+                    &format!(
+                        "Amount is above the maximum order size of {}",
+                        limit
+                    ),
+                )
+            },
+            BookError::OrderTooLarge(OrderSizeLimit::Notional(limit)) => Self {
+                limit: Some(limit),
+                ..Self::new(
+                    StatusCode::BAD_REQUEST,
+                    "ORDER_TOO_LARGE",
+                    &format!(
+                        "Notional value is above the maximum of {}",
+                        limit
+                    ),
+                )
+            },
+            BookError::TooManyOrders => Self::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                "TOO_MANY_ORDERS",
+                "Too many open orders",
+            ),
+            BookError::DuplicateOrder => Self::new(
+                StatusCode::CONFLICT,
+                "DUPLICATE_ORDER",
+                "Order already exists in this market (give identical orders \
+                 distinct nonces)",
+            ),
+            BookError::DuplicateClientOrderId => Self::new(
+                StatusCode::CONFLICT,
+                "DUPLICATE_CLIENT_ORDER_ID",
+                "Client order ID is already in use by an open order",
+            ),
+            BookError::WrongMarket => Self::new(
+                StatusCode::BAD_REQUEST,
+                "MARKET_MISMATCH",
+                "Order is for another market than the book it was submitted to",
+            ),
+            BookError::MarketPaused => Self::new(
+                StatusCode::LOCKED,
+                "MARKET_PAUSED",
+                "Market is paused and not accepting new orders",
+            ),
+            BookError::Web3Error => Self::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "MATCHING_ERROR",
+                "Matching error occurred",
+            ),
+        }
+    }
+}
+
+/// The message names the field of the order at fault.
+impl From<OrderParseError> for ApiError {
+    fn from(error: OrderParseError) -> Self {
+        let code: &str = match error {
+            OrderParseError::InvalidSignedData => "INVALID_SIGNED_DATA",
+            OrderParseError::ExpiryBeforeCreation => "EXPIRY_BEFORE_CREATION",
+            _ => "INVALID_ORDER",
+        };
+
+        Self::new(StatusCode::BAD_REQUEST, code, &error.to_string())
+    }
+}
+
+impl From<TimestampError> for ApiError {
+    fn from(error: TimestampError) -> Self {
+        match error {
+            TimestampError::Expired => Self::new(
+                StatusCode::BAD_REQUEST,
+                "ORDER_EXPIRED",
+                "Order expired",
+            ),
+            TimestampError::CreatedInFuture => Self::new(
+                StatusCode::BAD_REQUEST,
+                "CREATED_IN_FUTURE",
+                "Order created further in the future than the allowed clock \
+                 skew",
+            ),
+            TimestampError::CreatedTooOld => Self::new(
+                StatusCode::BAD_REQUEST,
+                "CREATED_TOO_OLD",
+                "Order created further in the past than the allowed clock skew",
+            ),
+        }
+    }
+}
+
+/// Malformed signatures are distinguished from well-formed signatures that
+/// were not made by the trader of the order.
+impl From<SignatureError> for ApiError {
+    fn from(error: SignatureError) -> Self {
+        match error {
+            SignatureError::InvalidLength
+            | SignatureError::InvalidRecoveryId => Self::new(
+                StatusCode::BAD_REQUEST,
+                "MALFORMED_SIGNATURE",
+                "Malformed signature",
+            ),
+            SignatureError::Unrecoverable | SignatureError::SignerMismatch => {
+                Self::new(
+                    StatusCode::UNAUTHORIZED,
+                    "INVALID_SIGNATURE",
+                    "Invalid signature",
+                )
+            }
+        }
+    }
+}
@@ -1,10 +1,32 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::TryFrom;
 use std::env;
-use std::net::IpAddr;
-use std::path::PathBuf;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 
-use clap::ArgMatches;
+use clap::{Arg, ArgMatches};
+use ethereum_types::{Address, U256};
+use reqwest::Url;
+use serde::Deserialize;
+use thiserror::Error;
+use warp::http::Method;
+
+use crate::event::publisher::DEFAULT_TOPIC_PREFIX;
+use crate::expiry::DEFAULT_EXPIRY_SWEEP_INTERVAL;
+use crate::handler::DEFAULT_MAX_BODY_SIZE;
+use crate::limit::{
+    DEFAULT_RATE_LIMIT_BURST, DEFAULT_RATE_LIMIT_ORDERS_PER_SEC,
+};
+use crate::markets::DEFAULT_MARKET_SYNC_INTERVAL;
+use crate::rpc::{
+    DEFAULT_RPC_CONNECT_TIMEOUT, DEFAULT_RPC_MAX_ATTEMPTS, DEFAULT_RPC_TIMEOUT,
+};
+use crate::util::{from_hex_opt_de, parse_u256, strip_hex_prefix};
 
 /// The default IP address that the OME will listen on
 pub const DEFAULT_IP: &str = "0.0.0.0";
@@ -22,117 +44,1045 @@ pub const DEFAULT_KEYFILE: &str = "pkey.secret";
 
 pub const DEFAULT_TLS_TOGGLE: bool = false;
 
+/// Whether larger responses are compressed for clients that accept it, by
+/// default
+pub const DEFAULT_COMPRESSION_TOGGLE: bool = true;
+
+/// The default chain ID that order signatures are verified against
+pub const DEFAULT_CHAIN_ID: u64 = 1;
+
+/// The default number of seconds in-flight requests are given to complete upon
+/// shutdown (within Kubernetes' default grace period of 30 seconds)
+pub const DEFAULT_SHUTDOWN_TIMEOUT: u64 = 25;
+
+/// The default number of seconds that the creation time of an order may be
+/// ahead of or behind the clock of the OME
+pub const DEFAULT_MAX_CLOCK_SKEW: u64 = 300;
+
+/// The format that log output is written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    Json,   /* one JSON object per line, for log aggregation */
+    Pretty, /* human-readable */
+}
+
+impl FromStr for LogFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(LogFormat::Json),
+            "pretty" => Ok(LogFormat::Pretty),
+            _ => Err("Invalid log format"),
+        }
+    }
+}
+
+/// The way the order books are shared between requests
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EngineMode {
+    #[default]
+    Lock, /* every book is behind a single lock on the state */
+    Actor, /* each book is hosted by a task of its own (see `actor`) */
+}
+
+impl FromStr for EngineMode {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lock" => Ok(EngineMode::Lock),
+            "actor" => Ok(EngineMode::Actor),
+            _ => Err("Invalid engine mode"),
+        }
+    }
+}
+
+/// A place the OME listens for requests on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Listener {
+    Tcp(SocketAddr),
+    Unix(PathBuf), /* the path of a Unix domain socket */
+}
+
+impl fmt::Display for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Listener::Tcp(address) => write!(f, "{}", address),
+            Listener::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Parses a comma-separated list of listeners, each either `ip:port` (with
+/// IPv6 addresses in brackets, as in `[::]:8989`), a bare IP address (which
+/// listens on the given port), or `unix:/path/to.sock`
+pub fn parse_listeners(
+    s: &str,
+    port: u16,
+) -> Result<Vec<Listener>, &'static str> {
+    s.split(',')
+        .map(|listener| {
+            let listener: &str = listener.trim();
+
+            if let Some(path) = listener.strip_prefix("unix:") {
+                return if path.is_empty() {
+                    Err("Invalid listening address")
+                } else {
+                    Ok(Listener::Unix(path.into()))
+                };
+            }
+
+            /* bare IPv6 addresses may be bracketed, as with a port */
+            let ip: &str = listener
+                .strip_prefix('[')
+                .and_then(|t| t.strip_suffix(']'))
+                .unwrap_or(listener);
+
+            match (SocketAddr::from_str(listener), IpAddr::from_str(ip)) {
+                (Ok(address), _) => Ok(Listener::Tcp(address)),
+                (_, Ok(ip)) => Ok(Listener::Tcp(SocketAddr::new(ip, port))),
+                _ => Err("Invalid listening address"),
+            }
+        })
+        .collect()
+}
+
+/// The lowest version of TLS that clients may connect with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    #[default]
+    V1_2,
+    V1_3,
+}
+
+impl FromStr for TlsVersion {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1.2" => Ok(TlsVersion::V1_2),
+            "1.3" => Ok(TlsVersion::V1_3),
+            _ => Err("Invalid minimum TLS version"),
+        }
+    }
+}
+
+impl fmt::Display for TlsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsVersion::V1_2 => write!(f, "1.2"),
+            TlsVersion::V1_3 => write!(f, "1.3"),
+        }
+    }
+}
+
+/// The default methods that cross-origin requests may use
+pub const DEFAULT_CORS_ALLOWED_METHODS: &str = "GET,POST,PUT,DELETE";
+
+/// The origins that cross-origin requests may be made from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AllowedOrigins {
+    Any,
+    Only(Vec<String>), /* each as `scheme://host[:port]` */
+}
+
+/// Parses either `*` or a comma-separated list of origins, each of which must
+/// consist of an HTTP(S) scheme and a host (and optionally a port) alone
+impl FromStr for AllowedOrigins {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim() == "*" {
+            return Ok(AllowedOrigins::Any);
+        }
+
+        let origins: Vec<String> = s
+            .split(',')
+            .map(|origin| {
+                let url: Url = match Url::parse(origin.trim()) {
+                    Ok(t) => t,
+                    Err(_e) => return Err(INVALID_CORS_ORIGINS),
+                };
+
+                let is_origin: bool = matches!(url.scheme(), "http" | "https")
+                    && url.host_str().is_some()
+                    && url.username().is_empty()
+                    && url.password().is_none()
+                    && url.path() == "/"
+                    && url.query().is_none()
+                    && url.fragment().is_none();
+
+                if is_origin {
+                    Ok(url.origin().ascii_serialization())
+                } else {
+                    Err(INVALID_CORS_ORIGINS)
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(AllowedOrigins::Only(origins))
+    }
+}
+
+const INVALID_CORS_ORIGINS: &str = "Invalid CORS allowed origins (expected \
+                                    `*` or a comma-separated list of origins \
+                                    such as https://example.com)";
+
+/// Parses a comma-separated list of HTTP methods (e.g., `GET,POST`)
+pub fn parse_methods(s: &str) -> Result<Vec<Method>, &'static str> {
+    s.split(',')
+        .map(|method| {
+            match Method::from_bytes(method.trim().to_uppercase().as_bytes()) {
+                Ok(t) if !method.trim().is_empty() => Ok(t),
+                _ => Err("Invalid CORS allowed methods"),
+            }
+        })
+        .collect()
+}
+
+/// Parses a comma-separated list of trader addresses, each of which may or
+/// may not carry a 0x prefix
+pub fn parse_traders(s: &str) -> Result<BTreeSet<Address>, &'static str> {
+    s.split(',')
+        .map(|trader| {
+            Address::from_str(strip_hex_prefix(trader.trim()))
+                .map_err(|_e| "Invalid allowed traders")
+        })
+        .collect()
+}
+
+/// Returns the arguments the OME is run with (i.e., those of the `serve`
+/// subcommand)
+///
+/// Each may also be given by an environment variable or in the configuration
+/// file (see `Config`), which is itself given by `--config`.
+pub fn serve_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("config")
+            .long("config")
+            .value_name("config")
+            .help("The path to a TOML configuration file")
+            .takes_value(true),
+        Arg::with_name("address")
+            .short("l")
+            .long("listen")
+            .value_name("address")
+            .help("An ip:port, IP or unix:/path to listen on (repeatable)")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1),
+        Arg::with_name("port")
+            .short("p")
+            .long("port")
+            .value_name("port")
+            .help("The TCP port to listen on")
+            .takes_value(true),
+        Arg::with_name("dumpfile")
+            .long("dumpfile")
+            .value_name("dumpfile")
+            .help("The path to the dump file to use for state resumes")
+            .takes_value(true),
+        Arg::with_name("no-restore")
+            .long("no-restore")
+            .help("Flag to start without restoring any books"),
+        Arg::with_name("wal_path")
+            .long("wal-path")
+            .value_name("wal_path")
+            .help("The path to the write-ahead log of changes to books")
+            .takes_value(true),
+        Arg::with_name("executioner_address")
+            .long("executioner_address")
+            .value_name("executioner_address")
+            .help("Address of the Web3 executioner")
+            .takes_value(true),
+        Arg::with_name("rpc_connect_timeout")
+            .long("rpc-connect-timeout")
+            .value_name("rpc_connect_timeout")
+            .help("Seconds to wait for a connection to the executioner")
+            .takes_value(true),
+        Arg::with_name("rpc_timeout")
+            .long("rpc-timeout")
+            .value_name("rpc_timeout")
+            .help("Seconds to wait for the executioner to respond")
+            .takes_value(true),
+        Arg::with_name("rpc_max_attempts")
+            .long("rpc-max-attempts")
+            .value_name("rpc_max_attempts")
+            .help("Attempts made at requests to the executioner")
+            .takes_value(true),
+        Arg::with_name("markets_address")
+            .long("markets-address")
+            .value_name("markets_address")
+            .help("Address of the API listing known Tracer markets")
+            .takes_value(true),
+        Arg::with_name("market_sync_interval")
+            .long("market-sync-interval")
+            .value_name("market_sync_interval")
+            .help("Seconds between syncs of the known markets")
+            .takes_value(true),
+        Arg::with_name("divergence_check_interval")
+            .long("divergence-check-interval")
+            .value_name("divergence_check_interval")
+            .help("Seconds between checks of the books against the source")
+            .takes_value(true),
+        Arg::with_name("divergence_tolerance")
+            .long("divergence-tolerance")
+            .value_name("divergence_tolerance")
+            .help("Quantity a price level may differ by from the source")
+            .takes_value(true),
+        Arg::with_name("expiry_sweep_interval")
+            .long("expiry-sweep-interval")
+            .value_name("expiry_sweep_interval")
+            .help("Seconds between purges of expired orders")
+            .takes_value(true),
+        Arg::with_name("certificate_path")
+            .long("certificate_path")
+            .value_name("certificate_path")
+            .help("File path to the TLS certificate file")
+            .takes_value(true),
+        Arg::with_name("private_key_path")
+            .long("private_key_path")
+            .value_name("private_key_path")
+            .help("File path to the TLS private key file")
+            .takes_value(true),
+        Arg::with_name("tls_chain_path")
+            .long("tls-chain-path")
+            .value_name("tls_chain_path")
+            .help("File path to the intermediate certificates, if any")
+            .takes_value(true),
+        Arg::with_name("tls_min_version")
+            .long("tls-min-version")
+            .value_name("tls_min_version")
+            .help("Lowest TLS version clients may use, 1.2 or 1.3")
+            .possible_values(&["1.2", "1.3"])
+            .takes_value(true),
+        Arg::with_name("force-no-tls")
+            .long("force-no-tls")
+            .help("Flag to force TLS to be turned off"),
+        Arg::with_name("verify-signatures")
+            .long("verify-signatures")
+            .help("Flag to reject orders not signed by their trader"),
+        Arg::with_name("shutdown_timeout")
+            .long("shutdown-timeout")
+            .value_name("shutdown_timeout")
+            .help("Seconds to let in-flight requests finish upon shutdown")
+            .takes_value(true),
+        Arg::with_name("max_clock_skew_secs")
+            .long("max-clock-skew-secs")
+            .value_name("max_clock_skew_secs")
+            .help("Seconds the creation time of orders may be off by")
+            .takes_value(true),
+        Arg::with_name("rate_limit_orders_per_sec")
+            .long("rate-limit-orders-per-sec")
+            .value_name("rate_limit_orders_per_sec")
+            .help("Rate at which each trader or IP may change orders")
+            .takes_value(true),
+        Arg::with_name("rate_limit_burst")
+            .long("rate-limit-burst")
+            .value_name("rate_limit_burst")
+            .help("Requests each trader or IP may make in a single burst")
+            .takes_value(true),
+        Arg::with_name("admin_token")
+            .long("admin-token")
+            .value_name("admin_token")
+            .help("Bearer token required by admin routes")
+            .takes_value(true),
+        Arg::with_name("max_body_size")
+            .long("max-body-size")
+            .value_name("max_body_size")
+            .help("Maximum size of request bodies in bytes")
+            .takes_value(true),
+        Arg::with_name("cors_allowed_origins")
+            .long("cors-allowed-origins")
+            .value_name("cors_allowed_origins")
+            .help("Comma-separated origins allowed to make requests, or *")
+            .takes_value(true),
+        Arg::with_name("cors_allowed_methods")
+            .long("cors-allowed-methods")
+            .value_name("cors_allowed_methods")
+            .help("Comma-separated methods cross-origin requests may use")
+            .takes_value(true),
+        Arg::with_name("allowed_traders")
+            .long("allowed-traders")
+            .value_name("allowed_traders")
+            .help("Comma-separated traders that alone may submit orders")
+            .takes_value(true),
+        Arg::with_name("enable_compression")
+            .long("enable-compression")
+            .value_name("enable_compression")
+            .help("Whether to compress larger responses, true or false")
+            .possible_values(&["true", "false"])
+            .takes_value(true),
+        Arg::with_name("kafka_brokers")
+            .long("kafka-brokers")
+            .value_name("kafka_brokers")
+            .help("Comma-separated Kafka brokers to publish events to")
+            .takes_value(true),
+        Arg::with_name("kafka_topic_prefix")
+            .long("kafka-topic-prefix")
+            .value_name("kafka_topic_prefix")
+            .help("Prefix of the Kafka topics events are published to")
+            .takes_value(true),
+        Arg::with_name("log_format")
+            .long("log-format")
+            .value_name("log_format")
+            .help("Format of log output, either json or pretty")
+            .possible_values(&["json", "pretty"])
+            .takes_value(true),
+        Arg::with_name("engine_mode")
+            .long("engine-mode")
+            .value_name("engine_mode")
+            .help("How books are shared between requests, lock or actor")
+            .possible_values(&["lock", "actor"])
+            .takes_value(true),
+        Arg::with_name("chain_id")
+            .long("chain_id")
+            .value_name("chain_id")
+            .help("Chain ID that order signatures are verified against")
+            .takes_value(true),
+    ]
+}
+
+/// Represents the configuration file, in which any of the arguments may be
+/// given
+///
+/// Keys are named as the fields of `Arguments` are, with durations in
+/// seconds. Keys that name no argument are collected in `unknown`, so that
+/// they can be warned about rather than silently ignored.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub listen_address: Option<String>, /* as a list, as in CLI */
+    pub listen_port: Option<u16>,
+    pub executioner_address: Option<String>,
+    pub rpc_connect_timeout: Option<u64>,
+    pub rpc_timeout: Option<u64>,
+    pub rpc_max_attempts: Option<u32>,
+    pub markets_address: Option<String>,
+    pub market_sync_interval: Option<u64>,
+    pub divergence_check_interval: Option<u64>,
+    #[serde(deserialize_with = "from_hex_opt_de")]
+    pub divergence_tolerance: Option<U256>,
+    pub expiry_sweep_interval: Option<u64>,
+    pub dumpfile_path: Option<PathBuf>,
+    pub no_restore: Option<bool>,
+    pub wal_path: Option<PathBuf>,
+    pub certificate_path: Option<PathBuf>,
+    pub private_key_path: Option<PathBuf>,
+    pub tls_chain_path: Option<PathBuf>,
+    pub tls_min_version: Option<String>,
+    pub force_no_tls: Option<bool>,
+    pub verify_signatures: Option<bool>,
+    pub chain_id: Option<u64>,
+    pub shutdown_timeout: Option<u64>,
+    pub max_clock_skew: Option<u64>,
+    pub log_format: Option<String>,
+    pub engine_mode: Option<String>,
+    pub rate_limit_orders_per_sec: Option<f64>,
+    pub rate_limit_burst: Option<u32>,
+    pub admin_token: Option<String>,
+    pub max_body_size: Option<u64>,
+    pub cors_allowed_origins: Option<String>, /* as `*` or a list, as in CLI */
+    pub cors_allowed_methods: Option<String>,
+    pub allowed_traders: Option<String>, /* as a list, as in CLI */
+    pub enable_compression: Option<bool>,
+    pub kafka_brokers: Option<String>,
+    pub kafka_topic_prefix: Option<String>,
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, toml::Value>,
+}
+
+/// Represents the reasons the configuration file cannot be loaded
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to read configuration file: {0}")]
+    Read(#[from] io::Error),
+    #[error("Malformed configuration file: {0}")]
+    Parse(#[from] toml::de::Error),
+}
+
+impl Config {
+    /// Loads the configuration file at the given path
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+/// Represents the places the arguments may be given in, in order of
+/// precedence
+pub struct Sources<'a, 'b> {
+    pub matches: &'a ArgMatches<'b>, /* the command line */
+    pub env: &'a dyn Fn(&str) -> Option<String>, /* environment variables */
+    pub config: &'a Config,
+}
+
+impl Sources<'_, '_> {
+    /* returns the value of an argument from the first place it's given in
+     * (with repeated arguments joined by commas, as they'd be listed in the
+     * others) */
+    fn value<T: ToString>(
+        &self,
+        name: &str,
+        var: &str,
+        config: Option<T>,
+    ) -> Option<String> {
+        self.matches
+            .values_of(name)
+            .map(|t| t.collect::<Vec<&str>>().join(","))
+            .or_else(|| (self.env)(var))
+            .or_else(|| config.map(|t| t.to_string()))
+    }
+
+    /* as `value`, for flags (which are set by their presence on the command
+     * line) */
+    fn flag(
+        &self,
+        name: &str,
+        var: &str,
+        config: Option<bool>,
+        error: &'static str,
+    ) -> Result<Option<bool>, &'static str> {
+        if self.matches.is_present(name) {
+            return Ok(Some(true));
+        }
+
+        match (self.env)(var) {
+            Some(t) => match t.parse::<bool>() {
+                Ok(p) => Ok(Some(p)),
+                Err(_e) => Err(error),
+            },
+            None => Ok(config),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Arguments {
-    pub listen_address: IpAddr,
-    pub listen_port: u16,
+    pub listeners: Vec<Listener>,
+    pub listen_port: u16, /* of listeners given as bare IP addresses */
     pub executioner_address: String,
+    pub rpc_connect_timeout: Duration, /* to connect to the executioner */
+    pub rpc_timeout: Duration,         /* for the executioner to respond */
+    pub rpc_max_attempts: u32,         /* of requests safe to retry */
+    pub markets_address: Option<String>, /* source of known markets */
+    pub market_sync_interval: Duration,
+    pub divergence_check_interval: Option<Duration>, /* never if unset */
+    pub divergence_tolerance: U256, /* per price level, before it's noted */
+    pub expiry_sweep_interval: Duration, /* between purges of expired orders */
     pub dumpfile_path: PathBuf,
+    pub no_restore: bool, /* start without any books */
+    pub wal_path: Option<PathBuf>, /* write-ahead log (if any) */
     pub certificate_path: PathBuf,
     pub private_key_path: PathBuf,
+    pub tls_chain_path: Option<PathBuf>, /* intermediates, after the leaf */
+    pub tls_min_version: TlsVersion,
     pub force_no_tls: bool,
+    pub verify_signatures: bool,
+    pub chain_id: u64,
+    pub shutdown_timeout: Duration, /* to drain in-flight requests */
+    pub max_clock_skew: Duration,   /* of the creation times of orders */
+    pub log_format: LogFormat,
+    pub engine_mode: EngineMode,
+    pub rate_limit_orders_per_sec: f64, /* per trader or IP address */
+    pub rate_limit_burst: u32,
+    pub admin_token: Option<String>, /* required by admin routes (if any) */
+    pub max_body_size: u64,          /* of requests, in bytes */
+    pub cors_allowed_origins: AllowedOrigins,
+    pub cors_allowed_methods: Vec<Method>,
+    pub allowed_traders: Option<BTreeSet<Address>>, /* any if unset */
+    pub enable_compression: bool,                   /* of larger responses */
+    pub kafka_brokers: Option<String>, /* events are published to (if any) */
+    pub kafka_topic_prefix: String,
+    pub unknown_config_keys: Vec<String>, /* to be warned about */
 }
 
 impl TryFrom<ArgMatches<'_>> for Arguments {
-    type Error = &'static str;
+    type Error = String;
 
     fn try_from(value: ArgMatches<'_>) -> Result<Self, Self::Error> {
+        let lookup = |name: &str| env::var(name).ok();
+
+        /* the configuration file can't itself be given in one */
+        let config: Config = match value
+            .value_of("config")
+            .map(str::to_string)
+            .or_else(|| lookup("OME_CONFIG"))
+        {
+            Some(t) => Config::from_file(Path::new(&t))
+                .map_err(|e| format!("{}: {}", t, e))?,
+            None => Config::default(),
+        };
+
+        Self::from_sources(&Sources {
+            matches: &value,
+            env: &lookup,
+            config: &config,
+        })
+        .map_err(String::from)
+    }
+}
+
+impl Arguments {
+    /// Resolves every argument from the first of the sources it is given in
+    /// (the command line, then the environment, then the configuration file),
+    /// falling back to its default if it is given in none of them
+    pub fn from_sources(sources: &Sources) -> Result<Self, &'static str> {
+        let config: &Config = sources.config;
+
         /* start with the hardcoded values as defaults */
-        let mut listen_address: IpAddr = IpAddr::from_str(DEFAULT_IP).unwrap();
         let mut listen_port: u16 = DEFAULT_PORT.parse::<u16>().unwrap();
         let mut executioner_address: String = DEFAULT_EXECUTIONER.to_string();
+        let mut rpc_connect_timeout: u64 = DEFAULT_RPC_CONNECT_TIMEOUT;
+        let mut rpc_timeout: u64 = DEFAULT_RPC_TIMEOUT;
+        let mut rpc_max_attempts: u32 = DEFAULT_RPC_MAX_ATTEMPTS;
+        let mut market_sync_interval: u64 = DEFAULT_MARKET_SYNC_INTERVAL;
+        let mut divergence_check_interval: Option<u64> = None;
+        let mut divergence_tolerance: U256 = U256::zero();
+        let mut expiry_sweep_interval: u64 = DEFAULT_EXPIRY_SWEEP_INTERVAL;
         let mut dumpfile_path: PathBuf = DEFAULT_DUMPFILE.into();
+        let mut wal_path: Option<PathBuf> = None;
         let mut certificate_path: PathBuf = DEFAULT_CERTFILE.into();
         let mut private_key_path: PathBuf = DEFAULT_KEYFILE.into();
-        let mut force_no_tls: bool = DEFAULT_TLS_TOGGLE;
+        let mut tls_chain_path: Option<PathBuf> = None;
+        let mut tls_min_version: TlsVersion = TlsVersion::default();
+        let mut chain_id: u64 = DEFAULT_CHAIN_ID;
+        let mut shutdown_timeout: u64 = DEFAULT_SHUTDOWN_TIMEOUT;
+        let mut max_clock_skew: u64 = DEFAULT_MAX_CLOCK_SKEW;
+        let mut log_format: LogFormat = LogFormat::Pretty;
+        let mut engine_mode: EngineMode = EngineMode::default();
+        let mut rate_limit_orders_per_sec: f64 =
+            DEFAULT_RATE_LIMIT_ORDERS_PER_SEC;
+        let mut rate_limit_burst: u32 = DEFAULT_RATE_LIMIT_BURST;
+        let mut max_body_size: u64 = DEFAULT_MAX_BODY_SIZE;
+        let mut cors_allowed_origins: AllowedOrigins = AllowedOrigins::Any;
+        let mut cors_allowed_methods: Vec<Method> =
+            parse_methods(DEFAULT_CORS_ALLOWED_METHODS).unwrap();
+        let mut enable_compression: bool = DEFAULT_COMPRESSION_TOGGLE;
+        let mut kafka_topic_prefix: String = DEFAULT_TOPIC_PREFIX.to_string();
 
-        /* handle listening address */
-        if let Some(t) = value.value_of("listen") {
-            listen_address = match IpAddr::from_str(t) {
+        /* handle listening port */
+        if let Some(t) =
+            sources.value("port", "OME_LISTEN_PORT", config.listen_port)
+        {
+            listen_port = match t.parse::<u16>() {
                 Ok(p) => p,
-                Err(_e) => return Err("Invalid listening address"),
+                Err(_e) => return Err("Invalid listening port"),
             };
-        } else {
-            match env::var("OME_LISTEN_ADDRESS") {
-                Ok(t) => {
-                    listen_address = match IpAddr::from_str(&t) {
-                        Ok(p) => p,
-                        Err(_err) => return Err("Invalid listening address"),
-                    }
-                }
-                Err(_e) => {}
+        }
+
+        /* handle listening addresses (after the port bare ones listen on) */
+        let listeners: Vec<Listener> = parse_listeners(
+            &sources
+                .value(
+                    "address",
+                    "OME_LISTEN_ADDRESS",
+                    config.listen_address.as_ref(),
+                )
+                .unwrap_or_else(|| DEFAULT_IP.to_string()),
+            listen_port,
+        )?;
+
+        /* handle executioner address */
+        if let Some(t) = sources.value(
+            "executioner_address",
+            "OME_EXECUTIONER_ADDRESS",
+            config.executioner_address.as_ref(),
+        ) {
+            executioner_address = t;
+        }
+
+        /* handle executioner connection timeout */
+        if let Some(t) = sources.value(
+            "rpc_connect_timeout",
+            "OME_RPC_CONNECT_TIMEOUT",
+            config.rpc_connect_timeout,
+        ) {
+            rpc_connect_timeout = match t.parse::<u64>() {
+                Ok(p) => p,
+                Err(_e) => return Err("Invalid executioner connect timeout"),
             };
         }
 
-        /* handle listening port */
-        if let Some(t) = value.value_of("port") {
-            listen_port = match t.parse::<u16>() {
+        /* handle executioner request timeout */
+        if let Some(t) =
+            sources.value("rpc_timeout", "OME_RPC_TIMEOUT", config.rpc_timeout)
+        {
+            rpc_timeout = match t.parse::<u64>() {
                 Ok(p) => p,
-                Err(_e) => return Err("Invalid listening port"),
+                Err(_e) => return Err("Invalid executioner timeout"),
             };
-        } else {
-            match env::var("OME_LISTEN_PORT") {
-                Ok(t) => match t.parse::<u16>() {
-                    Ok(p) => listen_port = p,
-                    Err(_err) => return Err("Invalid listening port"),
-                },
-                Err(_e) => {}
-            }
         }
 
-        /* handle executioner address */
-        if let Some(t) = value.value_of("executioner_address") {
-            executioner_address = t.to_string();
-        } else {
-            match env::var("OME_EXECUTIONER_ADDRESS") {
-                Ok(t) => executioner_address = t,
-                Err(_e) => {}
-            }
+        /* handle executioner request attempts */
+        if let Some(t) = sources.value(
+            "rpc_max_attempts",
+            "OME_RPC_MAX_ATTEMPTS",
+            config.rpc_max_attempts,
+        ) {
+            rpc_max_attempts = match t.parse::<u32>() {
+                Ok(p) if p > 0 => p,
+                _ => return Err("Invalid executioner attempts"),
+            };
+        }
+
+        /* handle known markets address */
+        let markets_address: Option<String> = sources.value(
+            "markets_address",
+            "OME_MARKETS_ADDRESS",
+            config.markets_address.as_ref(),
+        );
+
+        /* handle market sync interval */
+        if let Some(t) = sources.value(
+            "market_sync_interval",
+            "OME_MARKET_SYNC_INTERVAL",
+            config.market_sync_interval,
+        ) {
+            market_sync_interval = match t.parse::<u64>() {
+                Ok(p) if p > 0 => p,
+                _ => return Err("Invalid market sync interval"),
+            };
+        }
+
+        /* handle divergence check interval */
+        if let Some(t) = sources.value(
+            "divergence_check_interval",
+            "OME_DIVERGENCE_CHECK_INTERVAL",
+            config.divergence_check_interval,
+        ) {
+            divergence_check_interval = match t.parse::<u64>() {
+                Ok(p) if p > 0 => Some(p),
+                _ => return Err("Invalid divergence check interval"),
+            };
+        }
+
+        /* handle divergence tolerance */
+        if let Some(t) = sources.value(
+            "divergence_tolerance",
+            "OME_DIVERGENCE_TOLERANCE",
+            config.divergence_tolerance,
+        ) {
+            divergence_tolerance = match parse_u256(&t) {
+                Some(p) => p,
+                None => return Err("Invalid divergence tolerance"),
+            };
+        }
+
+        /* handle expiry sweep interval */
+        if let Some(t) = sources.value(
+            "expiry_sweep_interval",
+            "OME_EXPIRY_SWEEP_INTERVAL",
+            config.expiry_sweep_interval,
+        ) {
+            expiry_sweep_interval = match t.parse::<u64>() {
+                Ok(p) if p > 0 => p,
+                _ => return Err("Invalid expiry sweep interval"),
+            };
         }
 
         /* handle dumpfile path */
-        if let Some(t) = value.value_of("dumpfile_path") {
+        if let Some(t) = sources.value(
+            "dumpfile",
+            "OME_DUMPFILE",
+            config.dumpfile_path.as_ref().map(|t| t.display()),
+        ) {
             dumpfile_path = t.into();
-        };
+        }
+
+        /* handle restore toggle */
+        let no_restore: bool = sources
+            .flag(
+                "no-restore",
+                "OME_NO_RESTORE",
+                config.no_restore,
+                "Invalid restore toggle",
+            )?
+            .unwrap_or(false);
+
+        /* handle write-ahead log path */
+        if let Some(t) = sources.value(
+            "wal_path",
+            "OME_WAL_PATH",
+            config.wal_path.as_ref().map(|t| t.display()),
+        ) {
+            wal_path = Some(t.into());
+        }
 
         /* handle TLS certificate path */
-        if let Some(t) = value.value_of("certificate_path") {
+        if let Some(t) = sources.value(
+            "certificate_path",
+            "OME_CERTIFICATE_PATH",
+            config.certificate_path.as_ref().map(|t| t.display()),
+        ) {
             certificate_path = t.into();
-        } else {
-            match env::var("OME_CERTIFICATE_PATH") {
-                Ok(t) => certificate_path = t.into(),
-                Err(_e) => {}
-            }
         }
 
         /* handle TLS private key path */
-        if let Some(t) = value.value_of("private_key_path") {
+        if let Some(t) = sources.value(
+            "private_key_path",
+            "OME_PRIVATE_KEY_PATH",
+            config.private_key_path.as_ref().map(|t| t.display()),
+        ) {
             private_key_path = t.into();
-        } else {
-            match env::var("OME_PRIVATE_KEY_PATH") {
-                Ok(t) => certificate_path = t.into(),
-                Err(_e) => {}
-            }
+        }
+
+        /* handle TLS certificate chain path */
+        if let Some(t) = sources.value(
+            "tls_chain_path",
+            "OME_TLS_CHAIN_PATH",
+            config.tls_chain_path.as_ref().map(|t| t.display()),
+        ) {
+            tls_chain_path = Some(t.into());
+        }
+
+        /* handle minimum TLS version */
+        if let Some(t) = sources.value(
+            "tls_min_version",
+            "OME_TLS_MIN_VERSION",
+            config.tls_min_version.as_ref(),
+        ) {
+            tls_min_version = t.parse::<TlsVersion>()?;
         }
 
         /* handle TLS toggle */
-        if value.is_present("force-no-tls") {
-            force_no_tls = true;
-        } else {
-            match env::var("OME_FORCE_NO_TLS") {
-                Ok(t) => force_no_tls = t.parse::<bool>().unwrap(),
-                Err(_e) => {}
-            }
+        let force_no_tls: bool = sources
+            .flag(
+                "force-no-tls",
+                "OME_FORCE_NO_TLS",
+                config.force_no_tls,
+                "Invalid TLS toggle",
+            )?
+            .unwrap_or(DEFAULT_TLS_TOGGLE);
+
+        if !force_no_tls
+            && listeners.iter().any(|t| matches!(t, Listener::Unix(_)))
+        {
+            return Err("TLS can't be served on a Unix socket (serve plain \
+                        HTTP with --force-no-tls)");
+        }
+
+        /* handle signature verification toggle */
+        let verify_signatures: bool = sources
+            .flag(
+                "verify-signatures",
+                "OME_VERIFY_SIGNATURES",
+                config.verify_signatures,
+                "Invalid signature verification toggle",
+            )?
+            .unwrap_or(false);
+
+        /* handle chain ID */
+        if let Some(t) =
+            sources.value("chain_id", "OME_CHAIN_ID", config.chain_id)
+        {
+            chain_id = match t.parse::<u64>() {
+                Ok(p) => p,
+                Err(_e) => return Err("Invalid chain ID"),
+            };
+        }
+
+        /* handle shutdown timeout */
+        if let Some(t) = sources.value(
+            "shutdown_timeout",
+            "OME_SHUTDOWN_TIMEOUT",
+            config.shutdown_timeout,
+        ) {
+            shutdown_timeout = match t.parse::<u64>() {
+                Ok(p) => p,
+                Err(_e) => return Err("Invalid shutdown timeout"),
+            };
+        }
+
+        /* handle maximum clock skew */
+        if let Some(t) = sources.value(
+            "max_clock_skew_secs",
+            "OME_MAX_CLOCK_SKEW_SECS",
+            config.max_clock_skew,
+        ) {
+            max_clock_skew = match t.parse::<u64>() {
+                Ok(p) => p,
+                Err(_e) => return Err("Invalid maximum clock skew"),
+            };
+        }
+
+        /* handle log format */
+        if let Some(t) = sources.value(
+            "log_format",
+            "OME_LOG_FORMAT",
+            config.log_format.as_ref(),
+        ) {
+            log_format = t.parse::<LogFormat>()?;
+        }
+
+        /* handle engine mode */
+        if let Some(t) = sources.value(
+            "engine_mode",
+            "OME_ENGINE_MODE",
+            config.engine_mode.as_ref(),
+        ) {
+            engine_mode = t.parse::<EngineMode>()?;
+        }
+
+        /* handle order rate limit */
+        if let Some(t) = sources.value(
+            "rate_limit_orders_per_sec",
+            "OME_RATE_LIMIT_ORDERS_PER_SEC",
+            config.rate_limit_orders_per_sec,
+        ) {
+            rate_limit_orders_per_sec = match t.parse::<f64>() {
+                Ok(p) if p > 0.0 && p.is_finite() => p,
+                _ => return Err("Invalid order rate limit"),
+            };
+        }
+
+        /* handle rate limit burst size */
+        if let Some(t) = sources.value(
+            "rate_limit_burst",
+            "OME_RATE_LIMIT_BURST",
+            config.rate_limit_burst,
+        ) {
+            rate_limit_burst = match t.parse::<u32>() {
+                Ok(p) if p > 0 => p,
+                _ => return Err("Invalid rate limit burst size"),
+            };
+        }
+
+        /* handle admin token */
+        let admin_token: Option<String> = sources.value(
+            "admin_token",
+            "OME_ADMIN_TOKEN",
+            config.admin_token.as_ref(),
+        );
+
+        if admin_token.as_deref() == Some("") {
+            return Err("Invalid admin token");
+        }
+
+        /* handle maximum body size */
+        if let Some(t) = sources.value(
+            "max_body_size",
+            "OME_MAX_BODY_SIZE",
+            config.max_body_size,
+        ) {
+            max_body_size = match t.parse::<u64>() {
+                Ok(p) if p > 0 => p,
+                _ => return Err("Invalid maximum body size"),
+            };
+        }
+
+        /* handle CORS allowed origins */
+        if let Some(t) = sources.value(
+            "cors_allowed_origins",
+            "OME_CORS_ALLOWED_ORIGINS",
+            config.cors_allowed_origins.as_ref(),
+        ) {
+            cors_allowed_origins = t.parse::<AllowedOrigins>()?;
+        }
+
+        /* handle CORS allowed methods */
+        if let Some(t) = sources.value(
+            "cors_allowed_methods",
+            "OME_CORS_ALLOWED_METHODS",
+            config.cors_allowed_methods.as_ref(),
+        ) {
+            cors_allowed_methods = parse_methods(&t)?;
+        }
+
+        /* handle allowed traders */
+        let allowed_traders: Option<BTreeSet<Address>> = sources
+            .value(
+                "allowed_traders",
+                "OME_ALLOWED_TRADERS",
+                config.allowed_traders.as_ref(),
+            )
+            .map(|t| parse_traders(&t))
+            .transpose()?;
+
+        /* handle compression toggle */
+        if let Some(t) = sources.value(
+            "enable_compression",
+            "OME_ENABLE_COMPRESSION",
+            config.enable_compression,
+        ) {
+            enable_compression = match t.parse::<bool>() {
+                Ok(p) => p,
+                Err(_e) => return Err("Invalid compression toggle"),
+            };
+        }
+
+        /* handle Kafka brokers */
+        let kafka_brokers: Option<String> = sources.value(
+            "kafka_brokers",
+            "OME_KAFKA_BROKERS",
+            config.kafka_brokers.as_ref(),
+        );
+
+        if kafka_brokers.is_some() && !cfg!(feature = "kafka") {
+            return Err(
+                "Kafka brokers given, but built without Kafka support \
+                        (the kafka feature)",
+            );
+        }
+
+        if kafka_brokers
+            .as_deref()
+            .is_some_and(|t| t.trim().is_empty())
+        {
+            return Err("Invalid Kafka brokers");
+        }
+
+        /* handle Kafka topic prefix */
+        if let Some(t) = sources.value(
+            "kafka_topic_prefix",
+            "OME_KAFKA_TOPIC_PREFIX",
+            config.kafka_topic_prefix.as_ref(),
+        ) {
+            kafka_topic_prefix = t;
+        }
+
+        if kafka_topic_prefix.is_empty() {
+            return Err("Invalid Kafka topic prefix");
         }
 
         Ok(Self {
-            listen_address,
+            listeners,
             listen_port,
             executioner_address,
+            rpc_connect_timeout: Duration::from_secs(rpc_connect_timeout),
+            rpc_timeout: Duration::from_secs(rpc_timeout),
+            rpc_max_attempts,
+            markets_address,
+            market_sync_interval: Duration::from_secs(market_sync_interval),
+            divergence_check_interval: divergence_check_interval
+                .map(Duration::from_secs),
+            divergence_tolerance,
+            expiry_sweep_interval: Duration::from_secs(expiry_sweep_interval),
             dumpfile_path,
+            no_restore,
+            wal_path,
             certificate_path,
             private_key_path,
+            tls_chain_path,
+            tls_min_version,
             force_no_tls,
+            verify_signatures,
+            chain_id,
+            shutdown_timeout: Duration::from_secs(shutdown_timeout),
+            max_clock_skew: Duration::from_secs(max_clock_skew),
+            log_format,
+            engine_mode,
+            rate_limit_orders_per_sec,
+            rate_limit_burst,
+            admin_token,
+            max_body_size,
+            cors_allowed_origins,
+            cors_allowed_methods,
+            allowed_traders,
+            enable_compression,
+            kafka_brokers,
+            kafka_topic_prefix,
+            unknown_config_keys: config.unknown.keys().cloned().collect(),
         })
     }
 }
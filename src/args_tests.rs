@@ -0,0 +1,612 @@
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::fs;
+use std::iter;
+use std::path::PathBuf;
+
+use clap::{App, ArgMatches};
+use ethereum_types::Address;
+use warp::http::Method;
+
+use crate::args::{
+    parse_listeners, parse_methods, parse_traders, serve_args, AllowedOrigins,
+    Arguments, Config, EngineMode, Listener, Sources,
+};
+
+/* resolves the arguments from the given command line, environment and
+ * configuration file */
+fn resolve(
+    cli: &[&str],
+    env: &[(&str, &str)],
+    config: &str,
+) -> Result<Arguments, &'static str> {
+    let matches: ArgMatches<'_> = App::new("tracer-ome")
+        .args(&serve_args())
+        .get_matches_from(iter::once("tracer-ome").chain(cli.iter().copied()));
+    let env: HashMap<String, String> = env
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    let config: Config = toml::from_str(config).unwrap();
+
+    Arguments::from_sources(&Sources {
+        matches: &matches,
+        env: &|name: &str| env.get(name).cloned(),
+        config: &config,
+    })
+}
+
+/* an argument given in every place it can be */
+struct Case {
+    cli: &'static [&'static str],
+    var: (&'static str, &'static str),
+    config: &'static str,
+    get: fn(&Arguments) -> String,
+    expected: [&'static str; 4], /* from the CLI, env, config, and default */
+}
+
+#[test]
+pub fn test_parse_allowed_origins() {
+    assert_eq!("*".parse::<AllowedOrigins>(), Ok(AllowedOrigins::Any));
+    assert_eq!(
+        "https://app.tracer.finance, http://localhost:3000/"
+            .parse::<AllowedOrigins>(),
+        Ok(AllowedOrigins::Only(vec![
+            "https://app.tracer.finance".to_string(),
+            "http://localhost:3000".to_string(),
+        ]))
+    );
+
+    /* default ports are dropped, as browsers omit them from origins */
+    assert_eq!(
+        "https://example.com:443".parse::<AllowedOrigins>(),
+        Ok(AllowedOrigins::Only(
+            vec!["https://example.com".to_string()]
+        ))
+    );
+
+    for garbage in &[
+        "",
+        "example.com",
+        "ftp://example.com",
+        "https://example.com/app",
+        "https://user@example.com",
+        "https://example.com?x=1",
+        "https://example.com,*",
+    ] {
+        assert!(
+            garbage.parse::<AllowedOrigins>().is_err(),
+            "{} was accepted",
+            garbage
+        );
+    }
+}
+
+#[test]
+pub fn test_parse_allowed_methods() {
+    assert_eq!(
+        parse_methods("get, POST,Delete"),
+        Ok(vec![Method::GET, Method::POST, Method::DELETE])
+    );
+    assert!(parse_methods("GET,").is_err());
+    assert!(parse_methods("GET POST").is_err());
+}
+
+#[test]
+pub fn test_parse_engine_mode() {
+    assert_eq!("lock".parse::<EngineMode>(), Ok(EngineMode::Lock));
+    assert_eq!("actor".parse::<EngineMode>(), Ok(EngineMode::Actor));
+    assert_eq!("Actor".parse::<EngineMode>(), Err("Invalid engine mode"));
+    assert_eq!(EngineMode::default(), EngineMode::Lock);
+}
+
+#[test]
+pub fn test_parse_listeners() {
+    assert_eq!(
+        parse_listeners("0.0.0.0:80, [::]:443,::1, unix:/run/ome.sock", 8989),
+        Ok(vec![
+            Listener::Tcp("0.0.0.0:80".parse().unwrap()),
+            Listener::Tcp("[::]:443".parse().unwrap()),
+            Listener::Tcp("[::1]:8989".parse().unwrap()),
+            Listener::Unix(PathBuf::from("/run/ome.sock")),
+        ])
+    );
+    assert_eq!(
+        Listener::Unix(PathBuf::from("/run/ome.sock")).to_string(),
+        "unix:/run/ome.sock"
+    );
+
+    for garbage in &["", "localhost:80", "0.0.0.0:", "unix:", "0.0.0.0:80,"] {
+        assert!(
+            parse_listeners(garbage, 8989).is_err(),
+            "{} was accepted",
+            garbage
+        );
+    }
+}
+
+#[test]
+pub fn test_mixed_listeners() {
+    /* the flag may be repeated, each occurrence taking a list of its own */
+    let arguments: Arguments = resolve(
+        &[
+            "--listen",
+            "0.0.0.0",
+            "--listen",
+            "[::],unix:/tmp/ome.sock",
+            "--port",
+            "9000",
+            "--force-no-tls",
+        ],
+        &[("OME_LISTEN_ADDRESS", "127.0.0.1:1")],
+        "",
+    )
+    .unwrap();
+    assert_eq!(
+        arguments.listeners,
+        vec![
+            Listener::Tcp("0.0.0.0:9000".parse().unwrap()),
+            Listener::Tcp("[::]:9000".parse().unwrap()),
+            Listener::Unix(PathBuf::from("/tmp/ome.sock")),
+        ]
+    );
+
+    /* a list is taken as a whole from the first place it's given in */
+    let arguments: Arguments = resolve(
+        &[],
+        &[("OME_LISTEN_ADDRESS", "127.0.0.1:1,[::1]:2")],
+        "listen_address = \"unix:/tmp/ome.sock\"",
+    )
+    .unwrap();
+    assert_eq!(arguments.listeners.len(), 2);
+
+    /* TLS is only served over TCP */
+    assert!(resolve(&["--listen", "unix:/tmp/ome.sock"], &[], "").is_err());
+    assert!(resolve(
+        &[],
+        &[],
+        "listen_address = \"0.0.0.0, unix:/tmp/ome.sock\""
+    )
+    .is_err());
+    assert!(resolve(&["--listen", "[::]:443"], &[], "").is_ok());
+}
+
+#[test]
+pub fn test_argument_precedence() {
+    let mut cases: Vec<Case> = vec![
+        Case {
+            cli: &["--listen", "127.0.0.1"],
+            var: ("OME_LISTEN_ADDRESS", "10.0.0.1"),
+            config: "listen_address = \"10.0.0.2\"",
+            get: |t| format!("{:?}", t.listeners),
+            expected: [
+                "[Tcp(127.0.0.1:8989)]",
+                "[Tcp(10.0.0.1:8989)]",
+                "[Tcp(10.0.0.2:8989)]",
+                "[Tcp(0.0.0.0:8989)]",
+            ],
+        },
+        Case {
+            cli: &["--port", "1"],
+            var: ("OME_LISTEN_PORT", "2"),
+            config: "listen_port = 3",
+            get: |t| t.listen_port.to_string(),
+            expected: ["1", "2", "3", "8989"],
+        },
+        Case {
+            cli: &["--executioner_address", "http://a"],
+            var: ("OME_EXECUTIONER_ADDRESS", "http://b"),
+            config: "executioner_address = \"http://c\"",
+            get: |t| t.executioner_address.clone(),
+            expected: [
+                "http://a",
+                "http://b",
+                "http://c",
+                "http://localhost:3000",
+            ],
+        },
+        Case {
+            cli: &["--rpc-connect-timeout", "1"],
+            var: ("OME_RPC_CONNECT_TIMEOUT", "2"),
+            config: "rpc_connect_timeout = 3",
+            get: |t| t.rpc_connect_timeout.as_secs().to_string(),
+            expected: ["1", "2", "3", "5"],
+        },
+        Case {
+            cli: &["--rpc-timeout", "1"],
+            var: ("OME_RPC_TIMEOUT", "2"),
+            config: "rpc_timeout = 3",
+            get: |t| t.rpc_timeout.as_secs().to_string(),
+            expected: ["1", "2", "3", "10"],
+        },
+        Case {
+            cli: &["--rpc-max-attempts", "1"],
+            var: ("OME_RPC_MAX_ATTEMPTS", "2"),
+            config: "rpc_max_attempts = 4",
+            get: |t| t.rpc_max_attempts.to_string(),
+            expected: ["1", "2", "4", "3"],
+        },
+        Case {
+            cli: &["--markets-address", "http://a"],
+            var: ("OME_MARKETS_ADDRESS", "http://b"),
+            config: "markets_address = \"http://c\"",
+            get: |t| format!("{:?}", t.markets_address),
+            expected: [
+                "Some(\"http://a\")",
+                "Some(\"http://b\")",
+                "Some(\"http://c\")",
+                "None",
+            ],
+        },
+        Case {
+            cli: &["--market-sync-interval", "1"],
+            var: ("OME_MARKET_SYNC_INTERVAL", "2"),
+            config: "market_sync_interval = 3",
+            get: |t| t.market_sync_interval.as_secs().to_string(),
+            expected: ["1", "2", "3", "60"],
+        },
+        Case {
+            cli: &["--divergence-check-interval", "1"],
+            var: ("OME_DIVERGENCE_CHECK_INTERVAL", "2"),
+            config: "divergence_check_interval = 3",
+            get: |t| {
+                format!(
+                    "{:?}",
+                    t.divergence_check_interval.map(|t| t.as_secs())
+                )
+            },
+            expected: ["Some(1)", "Some(2)", "Some(3)", "None"],
+        },
+        Case {
+            cli: &["--divergence-tolerance", "1"],
+            var: ("OME_DIVERGENCE_TOLERANCE", "2"),
+            config: "divergence_tolerance = 3",
+            get: |t| t.divergence_tolerance.to_string(),
+            expected: ["1", "2", "3", "0"],
+        },
+        Case {
+            cli: &["--expiry-sweep-interval", "1"],
+            var: ("OME_EXPIRY_SWEEP_INTERVAL", "2"),
+            config: "expiry_sweep_interval = 3",
+            get: |t| t.expiry_sweep_interval.as_secs().to_string(),
+            expected: ["1", "2", "3", "5"],
+        },
+        Case {
+            cli: &["--dumpfile", "a.json"],
+            var: ("OME_DUMPFILE", "b.json"),
+            config: "dumpfile_path = \"c.json\"",
+            get: |t| t.dumpfile_path.display().to_string(),
+            expected: ["a.json", "b.json", "c.json", ".omedump.json"],
+        },
+        Case {
+            cli: &["--no-restore"],
+            var: ("OME_NO_RESTORE", "false"),
+            config: "no_restore = true",
+            get: |t| t.no_restore.to_string(),
+            expected: ["true", "false", "true", "false"],
+        },
+        Case {
+            cli: &["--wal-path", "a.wal"],
+            var: ("OME_WAL_PATH", "b.wal"),
+            config: "wal_path = \"c.wal\"",
+            get: |t| format!("{:?}", t.wal_path),
+            expected: [
+                "Some(\"a.wal\")",
+                "Some(\"b.wal\")",
+                "Some(\"c.wal\")",
+                "None",
+            ],
+        },
+        Case {
+            cli: &["--certificate_path", "a.pem"],
+            var: ("OME_CERTIFICATE_PATH", "b.pem"),
+            config: "certificate_path = \"c.pem\"",
+            get: |t| t.certificate_path.display().to_string(),
+            expected: ["a.pem", "b.pem", "c.pem", "cert.pem"],
+        },
+        Case {
+            cli: &["--private_key_path", "a.key"],
+            var: ("OME_PRIVATE_KEY_PATH", "b.key"),
+            config: "private_key_path = \"c.key\"",
+            get: |t| t.private_key_path.display().to_string(),
+            expected: ["a.key", "b.key", "c.key", "pkey.secret"],
+        },
+        Case {
+            cli: &["--tls-chain-path", "a.pem"],
+            var: ("OME_TLS_CHAIN_PATH", "b.pem"),
+            config: "tls_chain_path = \"c.pem\"",
+            get: |t| format!("{:?}", t.tls_chain_path),
+            expected: [
+                "Some(\"a.pem\")",
+                "Some(\"b.pem\")",
+                "Some(\"c.pem\")",
+                "None",
+            ],
+        },
+        Case {
+            cli: &["--tls-min-version", "1.3"],
+            var: ("OME_TLS_MIN_VERSION", "1.2"),
+            config: "tls_min_version = \"1.3\"",
+            get: |t| t.tls_min_version.to_string(),
+            expected: ["1.3", "1.2", "1.3", "1.2"],
+        },
+        Case {
+            cli: &["--force-no-tls"],
+            var: ("OME_FORCE_NO_TLS", "false"),
+            config: "force_no_tls = true",
+            get: |t| t.force_no_tls.to_string(),
+            expected: ["true", "false", "true", "false"],
+        },
+        Case {
+            cli: &["--verify-signatures"],
+            var: ("OME_VERIFY_SIGNATURES", "false"),
+            config: "verify_signatures = true",
+            get: |t| t.verify_signatures.to_string(),
+            expected: ["true", "false", "true", "false"],
+        },
+        Case {
+            cli: &["--chain_id", "4"],
+            var: ("OME_CHAIN_ID", "2"),
+            config: "chain_id = 3",
+            get: |t| t.chain_id.to_string(),
+            expected: ["4", "2", "3", "1"],
+        },
+        Case {
+            cli: &["--shutdown-timeout", "1"],
+            var: ("OME_SHUTDOWN_TIMEOUT", "2"),
+            config: "shutdown_timeout = 3",
+            get: |t| t.shutdown_timeout.as_secs().to_string(),
+            expected: ["1", "2", "3", "25"],
+        },
+        Case {
+            cli: &["--max-clock-skew-secs", "1"],
+            var: ("OME_MAX_CLOCK_SKEW_SECS", "2"),
+            config: "max_clock_skew = 3",
+            get: |t| t.max_clock_skew.as_secs().to_string(),
+            expected: ["1", "2", "3", "300"],
+        },
+        Case {
+            cli: &["--log-format", "json"],
+            var: ("OME_LOG_FORMAT", "pretty"),
+            config: "log_format = \"json\"",
+            get: |t| format!("{:?}", t.log_format),
+            expected: ["Json", "Pretty", "Json", "Pretty"],
+        },
+        Case {
+            cli: &["--engine-mode", "actor"],
+            var: ("OME_ENGINE_MODE", "lock"),
+            config: "engine_mode = \"actor\"",
+            get: |t| format!("{:?}", t.engine_mode),
+            expected: ["Actor", "Lock", "Actor", "Lock"],
+        },
+        Case {
+            cli: &["--rate-limit-orders-per-sec", "1.5"],
+            var: ("OME_RATE_LIMIT_ORDERS_PER_SEC", "2"),
+            config: "rate_limit_orders_per_sec = 3",
+            get: |t| t.rate_limit_orders_per_sec.to_string(),
+            expected: ["1.5", "2", "3", "10"],
+        },
+        Case {
+            cli: &["--rate-limit-burst", "1"],
+            var: ("OME_RATE_LIMIT_BURST", "2"),
+            config: "rate_limit_burst = 3",
+            get: |t| t.rate_limit_burst.to_string(),
+            expected: ["1", "2", "3", "20"],
+        },
+        Case {
+            cli: &["--admin-token", "a"],
+            var: ("OME_ADMIN_TOKEN", "b"),
+            config: "admin_token = \"c\"",
+            get: |t| format!("{:?}", t.admin_token),
+            expected: ["Some(\"a\")", "Some(\"b\")", "Some(\"c\")", "None"],
+        },
+        Case {
+            cli: &["--max-body-size", "1"],
+            var: ("OME_MAX_BODY_SIZE", "2"),
+            config: "max_body_size = 3",
+            get: |t| t.max_body_size.to_string(),
+            expected: ["1", "2", "3", "65536"],
+        },
+        Case {
+            cli: &["--cors-allowed-origins", "https://a.com"],
+            var: ("OME_CORS_ALLOWED_ORIGINS", "https://b.com"),
+            config: "cors_allowed_origins = \"https://c.com\"",
+            get: |t| format!("{:?}", t.cors_allowed_origins),
+            expected: [
+                "Only([\"https://a.com\"])",
+                "Only([\"https://b.com\"])",
+                "Only([\"https://c.com\"])",
+                "Any",
+            ],
+        },
+        Case {
+            cli: &["--cors-allowed-methods", "GET"],
+            var: ("OME_CORS_ALLOWED_METHODS", "POST"),
+            config: "cors_allowed_methods = \"PUT\"",
+            get: |t| format!("{:?}", t.cors_allowed_methods),
+            expected: ["[GET]", "[POST]", "[PUT]", "[GET, POST, PUT, DELETE]"],
+        },
+        Case {
+            cli: &[
+                "--allowed-traders",
+                "0x0000000000000000000000000000000000000001",
+            ],
+            var: (
+                "OME_ALLOWED_TRADERS",
+                "0000000000000000000000000000000000000002",
+            ),
+            config: "allowed_traders = \
+                     \"0x0000000000000000000000000000000000000003\"",
+            get: |t| {
+                format!(
+                    "{:?}",
+                    t.allowed_traders.as_ref().map(|t| t
+                        .iter()
+                        .map(|trader| trader.to_low_u64_be())
+                        .collect::<Vec<u64>>())
+                )
+            },
+            expected: ["Some([1])", "Some([2])", "Some([3])", "None"],
+        },
+        Case {
+            cli: &["--enable-compression", "false"],
+            var: ("OME_ENABLE_COMPRESSION", "true"),
+            config: "enable_compression = false",
+            get: |t| t.enable_compression.to_string(),
+            expected: ["false", "true", "false", "true"],
+        },
+        Case {
+            cli: &["--kafka-topic-prefix", "a"],
+            var: ("OME_KAFKA_TOPIC_PREFIX", "b"),
+            config: "kafka_topic_prefix = \"c\"",
+            get: |t| t.kafka_topic_prefix.clone(),
+            expected: ["a", "b", "c", "ome"],
+        },
+    ];
+
+    /* brokers can only be given at all when built with Kafka support */
+    if cfg!(feature = "kafka") {
+        cases.push(Case {
+            cli: &["--kafka-brokers", "a:9092"],
+            var: ("OME_KAFKA_BROKERS", "b:9092"),
+            config: "kafka_brokers = \"c:9092\"",
+            get: |t| format!("{:?}", t.kafka_brokers),
+            expected: [
+                "Some(\"a:9092\")",
+                "Some(\"b:9092\")",
+                "Some(\"c:9092\")",
+                "None",
+            ],
+        });
+    } else {
+        assert!(resolve(&[], &[], "kafka_brokers = \"c:9092\"").is_err());
+    }
+
+    for case in &cases {
+        let resolved: Vec<String> = vec![
+            resolve(case.cli, &[case.var], case.config),
+            resolve(&[], &[case.var], case.config),
+            resolve(&[], &[], case.config),
+            resolve(&[], &[], ""),
+        ]
+        .into_iter()
+        .map(|t| (case.get)(&t.unwrap()))
+        .collect();
+
+        assert_eq!(resolved, case.expected, "{}", case.var.0);
+    }
+}
+
+#[test]
+pub fn test_invalid_arguments_rejected_from_every_source() {
+    assert_eq!(
+        resolve(&["--port", "x"], &[], "").unwrap_err(),
+        "Invalid listening port"
+    );
+    assert_eq!(
+        resolve(&[], &[("OME_LISTEN_PORT", "x")], "").unwrap_err(),
+        "Invalid listening port"
+    );
+    assert_eq!(
+        resolve(&[], &[], "rpc_max_attempts = 0").unwrap_err(),
+        "Invalid executioner attempts"
+    );
+    assert_eq!(
+        resolve(&[], &[("OME_FORCE_NO_TLS", "yes")], "").unwrap_err(),
+        "Invalid TLS toggle"
+    );
+    assert_eq!(
+        resolve(&[], &[], "engine_mode = \"threads\"").unwrap_err(),
+        "Invalid engine mode"
+    );
+    assert_eq!(
+        resolve(&[], &[("OME_TLS_MIN_VERSION", "1.1")], "").unwrap_err(),
+        "Invalid minimum TLS version"
+    );
+    assert_eq!(
+        resolve(&[], &[], "allowed_traders = \"0x01\"").unwrap_err(),
+        "Invalid allowed traders"
+    );
+}
+
+#[test]
+pub fn test_parse_traders() {
+    let traders: BTreeSet<Address> = parse_traders(
+        "0x0000000000000000000000000000000000000002, \
+         0000000000000000000000000000000000000001,\
+         0x0000000000000000000000000000000000000002",
+    )
+    .unwrap();
+    assert_eq!(
+        traders.into_iter().collect::<Vec<Address>>(),
+        vec![Address::from_low_u64_be(1), Address::from_low_u64_be(2)]
+    );
+
+    assert!(parse_traders("").is_err());
+    assert!(
+        parse_traders("0x0000000000000000000000000000000000000001,").is_err()
+    );
+}
+
+#[test]
+pub fn test_load_config_file() {
+    let dir: PathBuf =
+        std::env::temp_dir().join(format!("ome-config-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path: PathBuf = dir.join("ome.toml");
+
+    fs::write(
+        &path,
+        "listen_port = 9000\nlisten_prot = 9001\n[tls]\nenabled = true\n",
+    )
+    .unwrap();
+    let config: Config = Config::from_file(&path).unwrap();
+    assert_eq!(config.listen_port, Some(9000));
+    assert_eq!(
+        config.unknown.keys().collect::<Vec<_>>(),
+        vec!["listen_prot", "tls"]
+    );
+
+    /* unknown keys are reported rather than refused */
+    let matches: ArgMatches<'_> = App::new("tracer-ome")
+        .args(&serve_args())
+        .get_matches_from(vec![
+            "tracer-ome",
+            "--config",
+            path.to_str().unwrap(),
+        ]);
+    let arguments: Arguments = Arguments::try_from(matches).unwrap();
+    assert_eq!(arguments.listen_port, 9000);
+    assert_eq!(arguments.unknown_config_keys, vec!["listen_prot", "tls"]);
+
+    /* a malformed or missing file is an error */
+    fs::write(&path, "listen_port = ").unwrap();
+    assert!(Config::from_file(&path).is_err());
+    assert!(Config::from_file(&dir.join("missing.toml")).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+pub fn test_bare_environment_uses_defaults() {
+    /* nothing is required, so the OME starts with no flags, env or file */
+    let arguments: Arguments = resolve(&[], &[], "").unwrap();
+    assert_eq!(arguments.markets_address, None);
+    assert_eq!(arguments.divergence_check_interval, None);
+    assert_eq!(arguments.executioner_address, "http://localhost:3000");
+    assert!(arguments.unknown_config_keys.is_empty());
+
+    /* the source of markets may be given by its env var alone... */
+    let arguments: Arguments =
+        resolve(&[], &[("OME_MARKETS_ADDRESS", "http://env")], "").unwrap();
+    assert_eq!(arguments.markets_address.as_deref(), Some("http://env"));
+
+    /* ...which its flag overrides */
+    let arguments: Arguments = resolve(
+        &["--markets-address", "http://flag"],
+        &[("OME_MARKETS_ADDRESS", "http://env")],
+        "",
+    )
+    .unwrap();
+    assert_eq!(arguments.markets_address.as_deref(), Some("http://flag"));
+}
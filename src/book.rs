@@ -2,37 +2,526 @@
 //! matching engine also
 use std::{
     cmp::Ordering,
-    collections::{BTreeMap, VecDeque},
-    fmt::Display,
+    collections::{vec_deque, BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    convert::TryFrom,
+    fmt::{self, Display},
+    iter::FromIterator,
+    ops::Deref,
 };
 
-use chrono::{DateTime, Utc};
-use ethereum_types::U256;
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use ethereum_types::{U256, U512};
 use itertools::Either;
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use thiserror::Error;
 use web3::types::Address;
 
-use crate::order::{ExternalOrder, Order, OrderId, OrderSide};
-use crate::rpc;
-use crate::util::{from_hex_de, from_hex_se};
+use crate::event::{BookDelta, BookEvent, BookEventKind, LevelChange};
+use crate::executioner::{Execution, Executioner};
+use crate::history::{OrderEvent, OrderEventKind, OrderHistory, OrderRecord};
+use crate::order::{
+    parse_address, parse_decimal, parse_timestamp, ExternalOrder, Order,
+    OrderId, OrderParseError, OrderSide, OrderSnapshot, OrderType, TimeInForce,
+};
+use crate::stats::{Stats, TradeStats};
+use crate::util::{
+    from_hex_de, from_hex_opt_de, from_hex_opt_se, from_hex_se, mul_div,
+    scale_18, AsString, Hex,
+};
 
 /// Represents an order book for a particular Tracer market
-#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[derive(Clone, Eq, Debug, Serialize, Deserialize)]
 pub struct Book {
     pub market: Address, /* the address of the Tracer market */
-    pub bids: BTreeMap<U256, VecDeque<Order>>, /* buy-side */
-    pub asks: BTreeMap<U256, VecDeque<Order>>, /* sell-side */
+    pub bids: BTreeMap<U256, Level>, /* buy-side */
+    pub asks: BTreeMap<U256, Level>, /* sell-side */
     #[serde(
         serialize_with = "from_hex_se",
         deserialize_with = "from_hex_de",
         rename = "LTP"
     )]
     pub ltp: U256, /* last traded price */
-    pub depth: (usize, usize), /* depth  */
+    #[serde(default)]
+    pub last_trade: Option<LastTrade>, /* details of the last trade (if any) */
+    pub depth: (usize, usize), /* number of open orders on each side */
+    #[serde(skip)]
+    pub(crate) volume: (U256, U256), /* open volume on each side */
     pub crossed: bool,   /* is book crossed? */
     #[serde(serialize_with = "from_hex_se", deserialize_with = "from_hex_de")]
-    pub spread: U256, /* bid-ask spread */
+    pub spread: U256, /* bid-ask spread (zero if one-sided or crossed) */
+    #[serde(default)]
+    pub self_trade_policy: SelfTradePolicy, /* handling of self-matches */
+    #[serde(default)]
+    pub matching_algorithm: MatchingAlgorithm, /* allocation within levels */
+    #[serde(
+        default = "default_increment",
+        serialize_with = "from_hex_se",
+        deserialize_with = "from_hex_de"
+    )]
+    pub tick_size: U256, /* minimum price increment */
+    #[serde(
+        default = "default_increment",
+        serialize_with = "from_hex_se",
+        deserialize_with = "from_hex_de"
+    )]
+    pub lot_size: U256, /* minimum quantity increment */
+    #[serde(
+        default,
+        serialize_with = "from_hex_se",
+        deserialize_with = "from_hex_de"
+    )]
+    pub min_quantity: U256, /* minimum order quantity (zero for none) */
+    #[serde(
+        default,
+        serialize_with = "from_hex_opt_se",
+        deserialize_with = "from_hex_opt_de"
+    )]
+    pub max_quantity: Option<U256>, /* maximum order quantity (if any) */
+    #[serde(
+        default,
+        serialize_with = "from_hex_opt_se",
+        deserialize_with = "from_hex_opt_de"
+    )]
+    pub max_notional: Option<U256>, /* maximum order notional (if any) */
+    #[serde(default)]
+    pub max_orders_per_trader: usize, /* open order cap (zero for none) */
+    #[serde(default)]
+    pub maker_fee_bps: u32, /* fee charged to makers, in basis points */
+    #[serde(default)]
+    pub taker_fee_bps: u32, /* fee charged to takers, in basis points */
+    #[serde(default)]
+    pub sequence: u64, /* incremented on every mutation of the book */
+    #[serde(default)]
+    pub buy_stops: BTreeMap<U256, Level>, /* by trigger price */
+    #[serde(default)]
+    pub sell_stops: BTreeMap<U256, Level>, /* by trigger price */
+    #[serde(default)]
+    pub trades: VecDeque<Fill>, /* most recent trades, oldest first */
+    #[serde(default)]
+    pub trade_stats: TradeStats, /* rolling statistics of recent trades */
+    #[serde(default)]
+    pub history: OrderHistory, /* lifecycles of open and recent orders */
+    #[serde(default)]
+    pub paused: bool, /* are new orders refused (see `Book::set_paused`)? */
+    #[serde(skip)]
+    pub(crate) index: HashMap<OrderId, (OrderSide, U256)>, /* order locations */
+    #[serde(skip)]
+    pub(crate) stop_index: HashMap<OrderId, (OrderSide, U256)>, /* triggers */
+    #[serde(skip)]
+    pub(crate) open_orders: HashMap<Address, usize>, /* orders per trader */
+    #[serde(skip)]
+    pub(crate) client_orders: HashMap<(Address, String), OrderId>, /* by CID */
+    #[serde(skip)]
+    pub(crate) events: Vec<BookEvent>, /* changes not yet published */
+    #[serde(skip)]
+    pub(crate) changed_levels: Vec<(OrderSide, U256)>, /* since last delta */
+    #[serde(skip)]
+    pub(crate) deltas: VecDeque<BookDelta>, /* most recent, oldest first */
+    #[serde(skip)]
+    pub(crate) delta_sequence: u64, /* as of the most recent delta */
+}
+
+/// Maximum number of trades retained in the trade history of each book
+pub const MAX_TRADES: usize = 1000;
+
+/// Maximum number of deltas retained by each book for subscribers catching up
+pub const MAX_DELTAS: usize = 1024;
+
+/// Represents a single match (i.e., trade) between a resting (maker) order and
+/// an incoming (taker) order
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Fill {
+    pub trade_id: u64, /* sequence number of the book at the time of the trade */
+    pub maker_id: OrderId,
+    pub taker_id: OrderId,
+    pub maker_address: Address,
+    pub taker_address: Address,
+    pub price: U256,
+    pub quantity: U256,
+    #[serde(with = "ts_seconds")]
+    pub timestamp: DateTime<Utc>, /* time of the match */
+    #[serde(default)]
+    pub maker_client_order_id: Option<String>,
+    #[serde(default)]
+    pub taker_client_order_id: Option<String>,
+    #[serde(default)]
+    pub maker_fee: U256, /* owed by the maker (see `Book::fee`) */
+    #[serde(default)]
+    pub taker_fee: U256, /* owed by the taker (see `Book::fee`) */
+}
+
+/// The order index and open volumes are derived entirely from the two sides of
+/// the book, so they take no part in comparisons, and nor do the trade history,
+/// its statistics, the last trade, order histories, and unpublished events as
+/// they record past changes rather than the state of the book
+impl PartialEq for Book {
+    fn eq(&self, other: &Self) -> bool {
+        self.market == other.market
+            && self.bids == other.bids
+            && self.asks == other.asks
+            && self.ltp == other.ltp
+            && self.depth == other.depth
+            && self.crossed == other.crossed
+            && self.spread == other.spread
+            && self.self_trade_policy == other.self_trade_policy
+            && self.matching_algorithm == other.matching_algorithm
+            && self.tick_size == other.tick_size
+            && self.lot_size == other.lot_size
+            && self.min_quantity == other.min_quantity
+            && self.max_quantity == other.max_quantity
+            && self.max_notional == other.max_notional
+            && self.max_orders_per_trader == other.max_orders_per_trader
+            && self.maker_fee_bps == other.maker_fee_bps
+            && self.taker_fee_bps == other.taker_fee_bps
+            && self.sequence == other.sequence
+            && self.buy_stops == other.buy_stops
+            && self.sell_stops == other.sell_stops
+            && self.paused == other.paused
+    }
+}
+
+/// Represents the most recent trade in an order book
+///
+/// The price is that of the maker's level, as is the last traded price, and the
+/// side is that of the taker (i.e., `Ask` if the aggressor was selling).
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct LastTrade {
+    #[serde(serialize_with = "from_hex_se", deserialize_with = "from_hex_de")]
+    pub price: U256,
+    #[serde(serialize_with = "from_hex_se", deserialize_with = "from_hex_de")]
+    pub quantity: U256,
+    pub taker_side: OrderSide,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Represents the last trade of an order book in its external (API) format
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ExternalLastTrade {
+    pub price: String,
+    pub quantity: String,
+    pub taker_side: OrderSide,
+    pub timestamp: String, /* Unix timestamp in seconds */
+}
+
+impl From<LastTrade> for ExternalLastTrade {
+    fn from(value: LastTrade) -> Self {
+        Self {
+            price: value.price.to_string(),
+            quantity: value.quantity.to_string(),
+            taker_side: value.taker_side,
+            timestamp: value.timestamp.timestamp().to_string(),
+        }
+    }
+}
+
+impl TryFrom<ExternalLastTrade> for LastTrade {
+    type Error = OrderParseError;
+
+    fn try_from(value: ExternalLastTrade) -> Result<Self, Self::Error> {
+        Ok(Self {
+            price: parse_decimal(&value.price, "last_trade.price")?,
+            quantity: parse_decimal(&value.quantity, "last_trade.quantity")?,
+            taker_side: value.taker_side,
+            timestamp: parse_timestamp(
+                &value.timestamp,
+                "last_trade.timestamp",
+            )?,
+        })
+    }
+}
+
+/// Represents the top of an order book along with its last traded price
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Ticker {
+    pub best_bid: Option<U256>, /* highest bid price (if any bids) */
+    pub best_ask: Option<U256>, /* lowest ask price (if any asks) */
+    pub bid_volume_at_best: Option<U256>, /* remaining quantity at best bid */
+    pub ask_volume_at_best: Option<U256>, /* remaining quantity at best ask */
+    pub last_traded_price: U256,
+    pub last_trade: Option<LastTrade>,
+    pub spread: U256,
+    pub sequence: u64,
+}
+
+/// Summarises the open orders of a single trader in an order book (see
+/// `Book::trader_exposure`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TraderExposure {
+    pub open_orders: usize, /* including untriggered stop orders */
+    pub bid_quantity: U256, /* remaining on resting bids */
+    pub ask_quantity: U256, /* remaining on resting asks */
+    pub notional: U256,     /* of the remainder of resting orders */
+}
+
+impl TraderExposure {
+    /// Adds up the exposure of a trader in two books, saturating rather than
+    /// overflowing
+    pub fn combine(self, other: Self) -> Self {
+        Self {
+            open_orders: self.open_orders.saturating_add(other.open_orders),
+            bid_quantity: self.bid_quantity.saturating_add(other.bid_quantity),
+            ask_quantity: self.ask_quantity.saturating_add(other.ask_quantity),
+            notional: self.notional.saturating_add(other.notional),
+        }
+    }
+}
+
+/// (price, total remaining quantity) pairs for one side of an order book
+pub type AggregatedLevels = Vec<(U256, U256)>;
+
+/// Represents the differences between two order books of the same market, as
+/// found by `Book::diff`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BookDiff {
+    pub levels: Vec<LevelDiff>, /* levels whose totals differ, bids first */
+    pub top: (Option<U256>, Option<U256>), /* best bid and ask of this book */
+    pub other_top: (Option<U256>, Option<U256>), /* ...and of the other */
+}
+
+/// Represents a price level whose total remaining quantity differs between two
+/// order books
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LevelDiff {
+    pub side: OrderSide,
+    pub price: U256,
+    pub ours: U256, /* total remaining quantity in this book (if any) */
+    pub theirs: U256, /* total remaining quantity in the other book */
+}
+
+impl LevelDiff {
+    /// Returns how far apart the totals of the level are
+    pub fn difference(&self) -> U256 {
+        if self.ours > self.theirs {
+            self.ours - self.theirs
+        } else {
+            self.theirs - self.ours
+        }
+    }
+}
+
+impl BookDiff {
+    /// Returns whether the books are alike, so far as `Book::diff` compares
+    /// them
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty() && self.top == self.other_top
+    }
+
+    /// Returns whether the books differ at the top of the book, or by more
+    /// than `tolerance` at any price level
+    pub fn exceeds(&self, tolerance: U256) -> bool {
+        self.top != self.other_top
+            || self
+                .levels
+                .iter()
+                .any(|level| level.difference() > tolerance)
+    }
+}
+
+/// Represents the orders at a single price of an order book (or at a single
+/// trigger price of its stop orders) in order of arrival, along with their
+/// total remaining quantity
+///
+/// The orders are read through `Deref`, whereas every change goes through the
+/// level itself so that its total stays up to date. A level is serialised as
+/// its orders alone, and its total recomputed upon deserialisation.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Level {
+    orders: VecDeque<Order>,
+    total_remaining: U256, /* sum of the remaining quantity of the orders */
+}
+
+impl Level {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the total remaining quantity of the orders at this level
+    pub fn total_remaining(&self) -> U256 {
+        self.total_remaining
+    }
+
+    /// Adds an order to the back of the level
+    pub(crate) fn push_back(&mut self, order: Order) {
+        self.total_remaining =
+            self.total_remaining.saturating_add(order.remaining);
+        self.orders.push_back(order);
+    }
+
+    /// Removes the order at the given position in the level
+    pub(crate) fn remove(&mut self, index: usize) -> Option<Order> {
+        let order: Order = self.orders.remove(index)?;
+        self.total_remaining =
+            self.total_remaining.saturating_sub(order.remaining);
+        Some(order)
+    }
+
+    /// Returns a mutable reference to the order at the given position in the
+    /// level
+    ///
+    /// The remaining quantity of the order must not be changed through this
+    /// reference, as the total of the level would no longer reflect it.
+    pub(crate) fn get_mut(&mut self, index: usize) -> Option<&mut Order> {
+        self.orders.get_mut(index)
+    }
+
+    /// Changes the quantity and remaining quantity of the order at the given
+    /// position in the level, without moving it
+    pub(crate) fn resize(
+        &mut self,
+        index: usize,
+        quantity: U256,
+        remaining: U256,
+    ) -> Option<&Order> {
+        let order: &mut Order = self.orders.get_mut(index)?;
+        self.total_remaining = self
+            .total_remaining
+            .saturating_sub(order.remaining)
+            .saturating_add(remaining);
+        order.quantity = quantity;
+        order.remaining = remaining;
+        Some(order)
+    }
+
+    /// Accounts for quantity filled from the orders of the level in place
+    fn consume(&mut self, amount: U256) {
+        self.total_remaining = self.total_remaining.saturating_sub(amount);
+    }
+
+    /// Removes every order matching the predicate from among the first
+    /// `count` orders of the level, preserving the order of those left
+    pub(crate) fn take_front_where<F: FnMut(&Order) -> bool>(
+        &mut self,
+        count: usize,
+        mut predicate: F,
+    ) -> Vec<Order> {
+        let count: usize = count.min(self.orders.len());
+        let mut taken: Vec<Order> = vec![];
+        let mut kept: Vec<Order> = vec![];
+
+        for order in self.orders.drain(..count) {
+            if predicate(&order) {
+                self.total_remaining =
+                    self.total_remaining.saturating_sub(order.remaining);
+                taken.push(order);
+            } else {
+                kept.push(order);
+            }
+        }
+
+        for order in kept.into_iter().rev() {
+            self.orders.push_front(order);
+        }
+
+        taken
+    }
+
+    /// Removes every order matching the predicate from the level, preserving
+    /// the order of those left
+    pub(crate) fn take_where<F: FnMut(&Order) -> bool>(
+        &mut self,
+        predicate: F,
+    ) -> Vec<Order> {
+        self.take_front_where(self.orders.len(), predicate)
+    }
+
+    /// Totals the remaining quantity of the orders at this level from scratch
+    fn count_remaining(orders: &VecDeque<Order>) -> U256 {
+        orders.iter().fold(U256::zero(), |acc, order| {
+            acc.saturating_add(order.remaining)
+        })
+    }
+}
+
+impl Deref for Level {
+    type Target = VecDeque<Order>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.orders
+    }
+}
+
+impl<'a> IntoIterator for &'a Level {
+    type Item = &'a Order;
+    type IntoIter = vec_deque::Iter<'a, Order>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.orders.iter()
+    }
+}
+
+impl From<VecDeque<Order>> for Level {
+    fn from(orders: VecDeque<Order>) -> Self {
+        Self {
+            total_remaining: Level::count_remaining(&orders),
+            orders,
+        }
+    }
+}
+
+impl FromIterator<Order> for Level {
+    fn from_iter<I: IntoIterator<Item = Order>>(iter: I) -> Self {
+        Level::from(iter.into_iter().collect::<VecDeque<Order>>())
+    }
+}
+
+impl Serialize for Level {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        self.orders.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Level {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        VecDeque::<Order>::deserialize(deserializer).map(Level::from)
+    }
+}
+
+/// Number of basis points in a whole (i.e., a fee of 100%)
+pub const BPS_PER_UNIT: u32 = 10_000;
+
+/// Returns the default tick and lot size of a book (i.e., no constraint)
+pub fn default_increment() -> U256 {
+    U256::one()
+}
+
+/// Represents how the matching engine treats an incoming order that would
+/// match against a resting order from the same trader
+///
+/// - `Skip` leaves the resting order in place and matches past it
+/// - `CancelResting` removes the resting order and keeps matching
+/// - `CancelIncoming` stops matching and drops the rest of the incoming order
+#[derive(
+    Clone, Copy, PartialEq, Eq, Debug, Default, Display, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum SelfTradePolicy {
+    #[default]
+    Skip,
+    CancelResting,
+    CancelIncoming,
+}
+
+/// Represents how the matching engine allocates an incoming order among the
+/// resting orders at each price level it matches against
+///
+/// - `Fifo` fills resting orders strictly in order of arrival
+/// - `ProRata` fills every resting order at the level in proportion to its
+///   remaining amount
+#[derive(
+    Clone, Copy, PartialEq, Eq, Debug, Default, Display, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum MatchingAlgorithm {
+    #[default]
+    Fifo,
+    ProRata,
 }
 
 #[derive(
@@ -40,6 +529,42 @@ pub struct Book {
 )]
 pub enum BookError {
     Web3Error,
+    OrderExpired,
+    NoSuchOrder,
+    InvalidAmendment,
+    InvalidQuantity,
+    InvalidPrice,
+    InvalidTickSize, /* price is not a multiple of the book's tick size */
+    InvalidLotSize,  /* quantity is not a multiple of the book's lot size */
+    InvalidTrigger,  /* missing, zero, or unexpected trigger price */
+    BelowMinQuantity, /* quantity is below the book's minimum quantity */
+    OrderTooLarge(OrderSizeLimit), /* quantity or notional is above a cap */
+    TooManyOrders,   /* trader has reached the book's open order cap */
+    DuplicateOrder,  /* an order with the same ID is already open */
+    DuplicateClientOrderId, /* trader already has an open order with the CID */
+    WrongMarket,     /* the order is for the market of another book */
+    MarketPaused,    /* the book is not accepting new orders */
+}
+
+/// Represents a cap on the size of the orders of a book, as exceeded by an
+/// order rejected with `BookError::OrderTooLarge`
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum OrderSizeLimit {
+    Quantity(U256), /* the book's maximum quantity */
+    Notional(U256), /* the book's maximum notional (see `util::scale_18`) */
+}
+
+impl Display for OrderSizeLimit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Quantity(limit) => {
+                write!(f, "OrderTooLarge (maximum quantity {})", limit)
+            }
+            Self::Notional(limit) => {
+                write!(f, "OrderTooLarge (maximum notional {})", limit)
+            }
+        }
+    }
 }
 
 impl From<web3::Error> for BookError {
@@ -54,13 +579,106 @@ impl From<ethabi::Error> for BookError {
     }
 }
 
-#[derive(
-    Clone, Copy, Debug, Display, Error, Serialize, Deserialize, PartialEq, Eq,
-)]
+/// Represents the outcome of an order upon its submission to the engine
+///
+/// Statuses are reported to clients by name, except that placed orders are
+/// reported as `Add` (as they always have been).
+#[derive(Clone, Copy, Debug, Error, Serialize, Deserialize, PartialEq, Eq)]
 pub enum OrderStatus {
-    Add,
+    #[serde(rename = "Add")]
+    Placed, /* order was added to the book without crossing */
     PartialMatch,
     FullMatch,
+    Killed,  /* order was dropped without resting (IOC/FOK) */
+    Pending, /* stop order is awaiting its trigger price */
+    Rejected {
+        reason: BookError,
+    }, /* engine refused the order */
+}
+
+impl Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Placed => write!(f, "Add"),
+            Self::PartialMatch => write!(f, "PartialMatch"),
+            Self::FullMatch => write!(f, "FullMatch"),
+            Self::Killed => write!(f, "Killed"),
+            Self::Pending => write!(f, "Pending"),
+            Self::Rejected { .. } => write!(f, "Rejected"),
+        }
+    }
+}
+
+/// Represents the outcome of submitting an order to the matching engine
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MatchResult {
+    pub order_id: OrderId, /* the submitted (taker) order */
+    pub order_status: OrderStatus,
+    pub cancelled: Vec<OrderId>, /* orders cancelled by self-trade prevention */
+    pub fills: Vec<Fill>,        /* trades resulting from the order */
+    pub remaining: U256,         /* unfilled quantity (rested or dropped) */
+    pub average_price: U256,     /* volume-weighted fill price (zero if none) */
+    pub triggered: Vec<OrderId>, /* stop orders triggered by the fills */
+    #[serde(default)]
+    pub triggered_fills: Vec<Fill>, /* trades made by the triggered orders */
+    #[serde(default)]
+    pub closed_makers: Vec<OrderId>, /* resting orders filled entirely */
+    #[serde(default)]
+    pub reduced_makers: Vec<(OrderId, U256)>, /* resting orders left open */
+}
+
+impl MatchResult {
+    /// Constructor for the `MatchResult` type
+    ///
+    /// The average price is computed from the provided fills, weighted by
+    /// their quantities and rounded down.
+    pub fn new(
+        order_id: OrderId,
+        order_status: OrderStatus,
+        remaining: U256,
+        fills: Vec<Fill>,
+        cancelled: Vec<OrderId>,
+    ) -> Self {
+        let volume: U256 = fills
+            .iter()
+            .fold(U256::zero(), |acc, fill| acc.saturating_add(fill.quantity));
+        let average_price: U256 = if volume.is_zero() {
+            U256::zero()
+        } else {
+            /* notional can exceed 256 bits, but the average price can't */
+            let notional: U512 =
+                fills.iter().fold(U512::zero(), |acc, fill| {
+                    acc + fill.price.full_mul(fill.quantity)
+                });
+            U256::try_from(notional / U512::from(volume))
+                .unwrap_or_else(|_| U256::max_value())
+        };
+
+        Self {
+            order_id,
+            order_status,
+            cancelled,
+            fills,
+            remaining,
+            average_price,
+            triggered: vec![],
+            triggered_fills: vec![],
+            closed_makers: vec![],
+            reduced_makers: vec![],
+        }
+    }
+
+    /// Sets the resting orders the fills consumed entirely and those they
+    /// left open, with their new remaining quantities
+    pub fn with_makers(
+        mut self,
+        closed_makers: Vec<OrderId>,
+        reduced_makers: Vec<(OrderId, U256)>,
+    ) -> Self {
+        self.closed_makers = closed_makers;
+        self.reduced_makers = reduced_makers;
+        self
+    }
 }
 
 impl Book {
@@ -74,61 +692,284 @@ impl Book {
             bids: BTreeMap::new(),
             asks: BTreeMap::new(),
             ltp: Default::default(),
+            last_trade: None,
             depth: (0, 0),
+            volume: (U256::zero(), U256::zero()),
             crossed: false,
             spread: Default::default(),
+            self_trade_policy: SelfTradePolicy::default(),
+            matching_algorithm: MatchingAlgorithm::default(),
+            tick_size: default_increment(),
+            lot_size: default_increment(),
+            min_quantity: U256::zero(),
+            max_quantity: None,
+            max_notional: None,
+            max_orders_per_trader: 0,
+            maker_fee_bps: 0,
+            taker_fee_bps: 0,
+            sequence: 0,
+            buy_stops: BTreeMap::new(),
+            sell_stops: BTreeMap::new(),
+            trades: VecDeque::new(),
+            trade_stats: TradeStats::default(),
+            history: OrderHistory::default(),
+            paused: false,
+            index: HashMap::new(),
+            stop_index: HashMap::new(),
+            open_orders: HashMap::new(),
+            client_orders: HashMap::new(),
+            events: vec![],
+            changed_levels: vec![],
+            deltas: VecDeque::new(),
+            delta_sequence: 0,
         }
     }
 
+    /// Sets the tick size (minimum price increment) of this book
+    pub fn with_tick_size(mut self, tick_size: U256) -> Self {
+        self.tick_size = tick_size;
+        self
+    }
+
+    /// Sets the lot size (minimum quantity increment) of this book
+    pub fn with_lot_size(mut self, lot_size: U256) -> Self {
+        self.lot_size = lot_size;
+        self
+    }
+
+    /// Sets the minimum quantity of orders submitted to this book
+    pub fn with_min_quantity(mut self, min_quantity: U256) -> Self {
+        self.min_quantity = min_quantity;
+        self
+    }
+
+    /// Sets the maximum quantity of orders submitted to this book
+    pub fn with_max_quantity(mut self, max_quantity: Option<U256>) -> Self {
+        self.max_quantity = max_quantity;
+        self
+    }
+
+    /// Sets the maximum notional value (see `util::scale_18`) of orders
+    /// submitted to this book
+    pub fn with_max_notional(mut self, max_notional: Option<U256>) -> Self {
+        self.max_notional = max_notional;
+        self
+    }
+
+    /// Sets the maximum number of open orders (including untriggered stop
+    /// orders) that each trader may have in this book
+    pub fn with_max_orders_per_trader(
+        mut self,
+        max_orders_per_trader: usize,
+    ) -> Self {
+        self.max_orders_per_trader = max_orders_per_trader;
+        self
+    }
+
+    /// Sets the fee charged to the maker of each trade in this book, in basis
+    /// points of the notional value of the trade
+    pub fn with_maker_fee_bps(mut self, maker_fee_bps: u32) -> Self {
+        self.maker_fee_bps = maker_fee_bps;
+        self
+    }
+
+    /// Sets the fee charged to the taker of each trade in this book, in basis
+    /// points of the notional value of the trade
+    pub fn with_taker_fee_bps(mut self, taker_fee_bps: u32) -> Self {
+        self.taker_fee_bps = taker_fee_bps;
+        self
+    }
+
+    /// Sets the self-trade prevention policy of this book
+    pub fn with_self_trade_policy(
+        mut self,
+        self_trade_policy: SelfTradePolicy,
+    ) -> Self {
+        self.self_trade_policy = self_trade_policy;
+        self
+    }
+
+    /// Sets the algorithm used to allocate incoming orders among the resting
+    /// orders at each price level of this book
+    pub fn with_matching_algorithm(
+        mut self,
+        matching_algorithm: MatchingAlgorithm,
+    ) -> Self {
+        self.matching_algorithm = matching_algorithm;
+        self
+    }
+
     /// Returns the ticker of this market
     pub fn market(&self) -> &Address {
         &self.market
     }
 
     /// Returns a reference to the order matching the provided order ID
+    ///
+    /// Stop orders which have not yet been triggered are included.
     pub fn order(&self, id: OrderId) -> Option<&Order> {
-        /* search bids */
-        for (_, curr_level) in self.bids.iter() {
-            for curr_order in curr_level.iter() {
-                if curr_order.id == id {
-                    return Some(curr_order);
-                }
-            }
+        if let Some((side, price, index)) = self.locate(id) {
+            return match side {
+                OrderSide::Bid => self.bids.get(&price)?.get(index),
+                OrderSide::Ask => self.asks.get(&price)?.get(index),
+            };
         }
 
-        /* search asks */
-        for (_, curr_level) in self.asks.iter() {
-            for curr_order in curr_level.iter() {
-                if curr_order.id == id {
-                    return Some(curr_order);
-                }
-            }
-        }
+        let (side, trigger_price, index) = self.locate_stop(id)?;
 
-        None
+        match side {
+            OrderSide::Bid => self.buy_stops.get(&trigger_price)?.get(index),
+            OrderSide::Ask => self.sell_stops.get(&trigger_price)?.get(index),
+        }
     }
 
     /// Returns a mutable reference to the order matching the provided order ID
+    ///
+    /// The side, price, and remaining quantity of the order must not be
+    /// changed through this reference, as the book's index and depth would no
+    /// longer reflect them.
     pub fn order_mut(&mut self, id: OrderId) -> Option<&mut Order> {
-        /* search bids */
-        for (_, curr_level) in self.bids.iter_mut() {
-            for curr_order in curr_level.iter_mut() {
-                if curr_order.id == id {
-                    return Some(curr_order);
-                }
+        let (side, price, index) = self.locate(id)?;
+
+        match side {
+            OrderSide::Bid => self.bids.get_mut(&price)?.get_mut(index),
+            OrderSide::Ask => self.asks.get_mut(&price)?.get_mut(index),
+        }
+    }
+
+    /// Restores a book from its external representation as of the provided
+    /// time, pruning any order that had expired by then
+    ///
+    /// `Book::try_from` restores books as of now. Replaying a write-ahead log
+    /// restores them as of when they were logged instead, so that the changes
+    /// logged after them apply to the same book.
+    pub fn from_external_at(
+        value: ExternalBook,
+        now: DateTime<Utc>,
+    ) -> Result<Self, BookRestoreError> {
+        let mut book: Book = parse_external_book(value)?;
+
+        /* recompute metadata rather than trusting the external source */
+        book.reindex();
+        book.check_restored()?;
+        book.prune(now);
+        book.update();
+
+        Ok(book)
+    }
+
+    /// Rebuilds the indices of order and stop order locations, the depth and
+    /// volume of each side, and the open order count and client order IDs of
+    /// each trader, from the contents of the book itself
+    ///
+    /// Must be called whenever the sides of the book are populated directly
+    /// (e.g., after deserialisation) rather than via order submission.
+    pub fn reindex(&mut self) {
+        /* deltas before now are unknown, so catching up needs a snapshot */
+        self.changed_levels.clear();
+        self.deltas.clear();
+        self.delta_sequence = self.sequence;
+
+        self.depth = self.count_depth();
+        self.volume = (
+            Book::count_volume(&self.bids),
+            Book::count_volume(&self.asks),
+        );
+
+        self.index = self
+            .bids
+            .iter()
+            .map(|level| (OrderSide::Bid, level))
+            .chain(self.asks.iter().map(|level| (OrderSide::Ask, level)))
+            .flat_map(|(side, (price, orders))| {
+                orders.iter().map(move |order| (order.id, (side, *price)))
+            })
+            .collect();
+        self.stop_index = self
+            .buy_stops
+            .iter()
+            .map(|level| (OrderSide::Bid, level))
+            .chain(self.sell_stops.iter().map(|level| (OrderSide::Ask, level)))
+            .flat_map(|(side, (trigger_price, orders))| {
+                orders
+                    .iter()
+                    .map(move |order| (order.id, (side, *trigger_price)))
+            })
+            .collect();
+
+        self.open_orders = HashMap::new();
+        self.client_orders = HashMap::new();
+        let orders: Vec<Order> = self
+            .bids
+            .values()
+            .chain(self.asks.values())
+            .chain(self.buy_stops.values())
+            .chain(self.sell_stops.values())
+            .flatten()
+            .cloned()
+            .collect();
+
+        for order in &orders {
+            self.track(order);
+        }
+    }
+
+    /// Pauses (or resumes) the acceptance of new orders by this book,
+    /// returning whether that changed anything
+    ///
+    /// Open orders are left untouched by a pause, and may still be cancelled
+    /// or amended; orders submitted while the book is paused are rejected with
+    /// `BookError::MarketPaused`.
+    pub fn set_paused(&mut self, paused: bool) -> bool {
+        let changed: bool = self.paused != paused;
+        self.paused = paused;
+        changed
+    }
+
+    /// Changes the caps on the size of orders submitted to (or amended in)
+    /// this book, returning whether that changed anything
+    ///
+    /// Open orders are left untouched, even if they exceed the new caps.
+    pub fn set_size_limits(
+        &mut self,
+        max_quantity: Option<U256>,
+        max_notional: Option<U256>,
+    ) -> bool {
+        let changed: bool = self.max_quantity != max_quantity
+            || self.max_notional != max_notional;
+        self.max_quantity = max_quantity;
+        self.max_notional = max_notional;
+        changed
+    }
+
+    /// Checks an order of the given price and quantity against the caps on
+    /// the size of orders in this book
+    ///
+    /// The notional value is `price * quantity / 10^18` (see
+    /// `util::scale_18`), and a notional too large for 256 bits exceeds any
+    /// cap. Orders exactly at a cap are accepted.
+    pub fn check_size(
+        &self,
+        price: U256,
+        quantity: U256,
+    ) -> Result<(), BookError> {
+        if let Some(limit) = self.max_quantity {
+            if quantity > limit {
+                return Err(BookError::OrderTooLarge(
+                    OrderSizeLimit::Quantity(limit),
+                ));
             }
         }
 
-        /* search asks */
-        for (_, curr_level) in self.asks.iter_mut() {
-            for curr_order in curr_level.iter_mut() {
-                if curr_order.id == id {
-                    return Some(curr_order);
-                }
+        if let Some(limit) = self.max_notional {
+            if scale_18(price, quantity).is_none_or(|t| t > limit) {
+                return Err(BookError::OrderTooLarge(
+                    OrderSizeLimit::Notional(limit),
+                ));
             }
         }
 
-        None
+        Ok(())
     }
 
     /// Returns the last traded price of the order book
@@ -137,7 +978,48 @@ impl Book {
     }
 
     /// Returns a pair (2-tuple) containing the depths of each side of the book
+    ///
+    /// Orders with nothing remaining are not counted.
     pub fn depth(&self) -> (usize, usize) {
+        self.depth
+    }
+
+    /// Returns a pair (2-tuple) containing the total remaining quantity of the
+    /// open orders on each side of the book
+    pub fn volume(&self) -> (U256, U256) {
+        self.volume
+    }
+
+    /// Returns the number of open orders in the book, including untriggered
+    /// stop orders
+    pub fn order_count(&self) -> usize {
+        self.index.len() + self.stop_index.len()
+    }
+
+    /// Returns the total remaining quantity of the open orders on both sides
+    /// of the book (excluding untriggered stop orders)
+    pub fn total_resting_quantity(&self) -> U256 {
+        self.volume.0.saturating_add(self.volume.1)
+    }
+
+    /// Returns the total notional value (see `util::scale_18`) of the open
+    /// orders on both sides of the book (excluding untriggered stop orders)
+    ///
+    /// Each level is valued at its own price and rounded down, and a total
+    /// too large for 256 bits saturates.
+    pub fn open_notional(&self) -> U256 {
+        self.bids
+            .iter()
+            .chain(self.asks.iter())
+            .map(|(price, level)| {
+                scale_18(*price, level.total_remaining())
+                    .unwrap_or_else(U256::max_value)
+            })
+            .fold(U256::zero(), U256::saturating_add)
+    }
+
+    /// Counts the open orders on each side of the book from scratch
+    fn count_depth(&self) -> (usize, usize) {
         (
             self.bids
                 .values()
@@ -152,50 +1034,982 @@ impl Book {
         )
     }
 
-    /// Returns whether the order book is currently crossed or not
-    pub fn crossed(&self) -> bool {
-        self.crossed
+    /// Totals the remaining quantity of every order on one side of the book
+    fn count_volume(side: &BTreeMap<U256, Level>) -> U256 {
+        side.values().flatten().fold(U256::zero(), |acc, order| {
+            acc.saturating_add(order.remaining)
+        })
     }
 
-    /// Returns the bid-ask spread of the book
-    pub fn spread(&self) -> U256 {
-        self.spread
-    }
+    /// Checks that a book restored from an external (or otherwise untrusted)
+    /// source is well-formed
+    ///
+    /// Every order must belong to this book's market, have no more remaining
+    /// than its quantity, appear only once, and be stored on its own side
+    /// under its own price (or trigger price, for stop orders), and the book
+    /// must not be crossed.
+    pub fn check_restored(&self) -> Result<(), BookRestoreError> {
+        let levels = [
+            (OrderSide::Bid, false, &self.bids),
+            (OrderSide::Ask, false, &self.asks),
+            (OrderSide::Bid, true, &self.buy_stops),
+            (OrderSide::Ask, true, &self.sell_stops),
+        ];
+        let mut seen: HashSet<OrderId> = HashSet::new();
+        let mut seen_client_ids: HashSet<(Address, &String)> = HashSet::new();
 
-    pub fn top(&self) -> (Option<U256>, Option<U256>) {
-        (
-            self.bids.last_key_value().map(|t| *t.0),
-            self.asks.first_key_value().map(|t| *t.0),
-        )
-    }
+        for (side, stops, level) in levels.iter() {
+            for (key, orders) in level.iter() {
+                for order in orders {
+                    let keyed_by: Option<U256> = if *stops {
+                        order.trigger_price
+                    } else {
+                        Some(order.price)
+                    };
 
-    fn price_viable(
-        opposite: U256,
-        incoming: U256,
-        incoming_side: OrderSide,
-    ) -> bool {
-        match incoming_side {
-            OrderSide::Bid => opposite <= incoming,
-            OrderSide::Ask => opposite >= incoming,
-        }
-    }
+                    if order.market != self.market {
+                        return Err(BookRestoreError::OrderMarketMismatch(
+                            order.id,
+                        ));
+                    }
 
-    #[allow(unused_must_use)]
-    async fn r#match(
+                    if order.remaining > order.quantity {
+                        return Err(
+                            BookRestoreError::RemainingExceedsQuantity(
+                                order.id,
+                            ),
+                        );
+                    }
+
+                    if order.side != *side
+                        || order.is_stop() != *stops
+                        || keyed_by != Some(*key)
+                    {
+                        return Err(BookRestoreError::LevelMismatch(order.id));
+                    }
+
+                    if !seen.insert(order.id) {
+                        return Err(BookRestoreError::DuplicateOrder(order.id));
+                    }
+
+                    if let Some(client_order_id) = &order.client_order_id {
+                        if !seen_client_ids
+                            .insert((order.trader, client_order_id))
+                        {
+                            return Err(
+                                BookRestoreError::DuplicateClientOrderId(
+                                    order.id,
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        match self.top() {
+            (Some(best_bid), Some(best_ask)) if best_bid >= best_ask => {
+                Err(BookRestoreError::Crossed)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the invariants of the book, returning every violation found
+    ///
+    /// Every order must belong to this book's market, have something
+    /// remaining, and be stored on its own side under its own price (or
+    /// trigger price, for stop orders), no price level may be empty, the
+    /// running depth and volume must agree with a full recount, the book must
+    /// be marked as crossed if it is, and the order indices must locate every
+    /// order and nothing else. Holds of a book whenever it has been updated.
+    pub fn validate(&self) -> Result<(), Vec<InvariantViolation>> {
+        let levels = [
+            (OrderSide::Bid, false, &self.bids),
+            (OrderSide::Ask, false, &self.asks),
+            (OrderSide::Bid, true, &self.buy_stops),
+            (OrderSide::Ask, true, &self.sell_stops),
+        ];
+        /* indices that were never built (e.g., upon deserialisation) aren't
+         * checked */
+        let indexed: bool =
+            !self.index.is_empty() || !self.stop_index.is_empty();
+        let mut seen: HashSet<(bool, OrderId)> = HashSet::new();
+        let mut violations: Vec<InvariantViolation> = vec![];
+
+        for (side, stops, side_levels) in levels.iter() {
+            let index: &HashMap<OrderId, (OrderSide, U256)> = if *stops {
+                &self.stop_index
+            } else {
+                &self.index
+            };
+
+            for (key, orders) in side_levels.iter() {
+                let level: PriceLevel = PriceLevel {
+                    side: *side,
+                    stop: *stops,
+                    price: *key,
+                };
+
+                if orders.is_empty() {
+                    violations.push(InvariantViolation::EmptyLevel(level));
+                }
+
+                let total: U256 = Level::count_remaining(orders);
+                if orders.total_remaining() != total {
+                    violations.push(InvariantViolation::TotalMismatch {
+                        level,
+                        cached: orders.total_remaining(),
+                        actual: total,
+                    });
+                }
+
+                for order in orders {
+                    let id: OrderId = order.id;
+                    let keyed_by: Option<U256> = if *stops {
+                        order.trigger_price
+                    } else {
+                        Some(order.price)
+                    };
+                    seen.insert((*stops, id));
+
+                    if order.market != self.market {
+                        violations.push(InvariantViolation::WrongMarket {
+                            id,
+                            level,
+                        });
+                    }
+
+                    if order.side != *side
+                        || order.is_stop() != *stops
+                        || keyed_by != Some(*key)
+                    {
+                        violations
+                            .push(InvariantViolation::WrongLevel { id, level });
+                    }
+
+                    if order.remaining.is_zero() {
+                        violations.push(InvariantViolation::NothingRemaining {
+                            id,
+                            level,
+                        });
+                    }
+
+                    if indexed && index.get(&id) != Some(&(*side, *key)) {
+                        violations
+                            .push(InvariantViolation::Unindexed { id, level });
+                    }
+                }
+            }
+        }
+
+        if indexed {
+            violations.extend(
+                self.index
+                    .keys()
+                    .map(|id| (false, *id))
+                    .chain(self.stop_index.keys().map(|id| (true, *id)))
+                    .filter(|entry| !seen.contains(entry))
+                    .map(|(_, id)| InvariantViolation::StaleIndex(id)),
+            );
+        }
+
+        let depth: (usize, usize) = self.count_depth();
+        if self.depth != depth {
+            violations.push(InvariantViolation::DepthMismatch {
+                cached: self.depth,
+                actual: depth,
+            });
+        }
+
+        let volume: (U256, U256) = (
+            Book::count_volume(&self.bids),
+            Book::count_volume(&self.asks),
+        );
+        if self.volume != volume {
+            violations.push(InvariantViolation::VolumeMismatch {
+                cached: self.volume,
+                actual: volume,
+            });
+        }
+
+        if let (Some(best_bid), Some(best_ask)) = self.top() {
+            if best_bid >= best_ask && !self.crossed {
+                violations
+                    .push(InvariantViolation::Crossed { best_bid, best_ask });
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Adds an open order to the running depth and volume of its side
+    fn open(&mut self, side: OrderSide, remaining: U256) {
+        if remaining.is_zero() {
+            return;
+        }
+
+        let (depth, volume): (&mut usize, &mut U256) = match side {
+            OrderSide::Bid => (&mut self.depth.0, &mut self.volume.0),
+            OrderSide::Ask => (&mut self.depth.1, &mut self.volume.1),
+        };
+        *depth += 1;
+        *volume = volume.saturating_add(remaining);
+    }
+
+    /// Removes an open order from the running depth and volume of its side
+    fn close(&mut self, side: OrderSide, remaining: U256) {
+        if remaining.is_zero() {
+            return;
+        }
+
+        let (depth, volume): (&mut usize, &mut U256) = match side {
+            OrderSide::Bid => (&mut self.depth.0, &mut self.volume.0),
+            OrderSide::Ask => (&mut self.depth.1, &mut self.volume.1),
+        };
+        *depth -= 1;
+        *volume = volume.saturating_sub(remaining);
+    }
+
+    /// Increments the sequence number of the book, returning the new value
+    fn next_sequence(&mut self) -> u64 {
+        self.sequence += 1;
+        self.sequence
+    }
+
+    /// Records a change to the book, stamped with its current sequence number,
+    /// for later publication, and in the histories of the orders it concerns
+    fn emit(&mut self, kind: BookEventKind, now: DateTime<Utc>) {
+        match &kind {
+            BookEventKind::Added(order)
+            | BookEventKind::Amended(order)
+            | BookEventKind::Cancelled(order)
+            | BookEventKind::Expired(order)
+            | BookEventKind::Triggered(order) => {
+                self.change_level(order.side, order.price)
+            }
+            BookEventKind::Fill { .. } => {}
+        }
+
+        let event: BookEvent = BookEvent::new(self.market, self.sequence, kind);
+        self.history.record_book_event(&event, now);
+        self.events.push(event);
+    }
+
+    /// Notes that the total remaining at a price level may have changed, so
+    /// that it is included in the next delta
+    fn change_level(&mut self, side: OrderSide, price: U256) {
+        if !self.changed_levels.contains(&(side, price)) {
+            self.changed_levels.push((side, price));
+        }
+    }
+
+    /// Removes and returns the changes to the book recorded since this was
+    /// last called, oldest first
+    ///
+    /// Callers should only publish these once the mutations they describe are
+    /// complete, so that subscribers never observe an event before the book
+    /// reflects it. The net effect of the changes is recorded as a delta (see
+    /// `Book::deltas_since`).
+    pub fn drain_events(&mut self) -> Vec<BookEvent> {
+        let events: Vec<BookEvent> = std::mem::take(&mut self.events);
+
+        if !events.is_empty() {
+            self.record_delta(&events);
+        }
+
+        events
+    }
+
+    /// Records the net change to the levels of the book made since the last
+    /// delta, along with the fills that made it, evicting the oldest delta
+    /// beyond `MAX_DELTAS`
+    fn record_delta(&mut self, events: &[BookEvent]) {
+        let mut changed: Vec<(OrderSide, U256)> =
+            std::mem::take(&mut self.changed_levels);
+        changed.sort_by_key(|(side, price)| (*side == OrderSide::Ask, *price));
+
+        let delta: BookDelta = BookDelta {
+            market: format!("{:?}", self.market),
+            prev_seq: self.delta_sequence,
+            seq: self.sequence,
+            changes: changed
+                .into_iter()
+                .map(|(side, price)| {
+                    let level: Option<&Level> = match side {
+                        OrderSide::Bid => self.bids.get(&price),
+                        OrderSide::Ask => self.asks.get(&price),
+                    };
+                    LevelChange {
+                        side,
+                        price: price.to_string(),
+                        new_total: level
+                            .map(Level::total_remaining)
+                            .unwrap_or_default()
+                            .to_string(),
+                    }
+                })
+                .collect(),
+            fills: events
+                .iter()
+                .filter_map(|event| match &event.kind {
+                    BookEventKind::Fill { fill, .. } => {
+                        Some(ExternalFill::from(fill.clone()))
+                    }
+                    _ => None,
+                })
+                .collect(),
+        };
+
+        self.delta_sequence = self.sequence;
+        self.deltas.push_back(delta);
+
+        while self.deltas.len() > MAX_DELTAS {
+            self.deltas.pop_front();
+        }
+    }
+
+    /// Returns the most recently recorded delta
+    pub fn last_delta(&self) -> Option<&BookDelta> {
+        self.deltas.back()
+    }
+
+    /// Returns every delta recorded since the book was at sequence number
+    /// `since`, oldest first, or `None` if some of them are no longer retained
+    /// (in which case a subscriber must start over from a snapshot)
+    pub fn deltas_since(&self, since: u64) -> Option<Vec<BookDelta>> {
+        if since > self.delta_sequence {
+            return None;
+        }
+
+        let covered: bool = match self.deltas.front() {
+            Some(oldest) => oldest.prev_seq <= since,
+            None => since == self.delta_sequence,
+        };
+
+        if !covered {
+            return None;
+        }
+
+        Some(
+            self.deltas
+                .iter()
+                .filter(|delta| delta.seq > since)
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Removes an order that has been taken out of its price level from the
+    /// index and from the depth and volume of its side
+    ///
+    /// Removing an order with volume remaining is a mutation of the book in
+    /// its own right, whereas an order that has been filled was already
+    /// accounted for by its final fill.
+    fn forget(&mut self, order: &Order) {
+        self.index.remove(&order.id);
+        self.untrack(order);
+
+        if !order.remaining.is_zero() {
+            self.close(order.side, order.remaining);
+            self.next_sequence();
+        }
+    }
+
+    /// Removes a stop order that has been taken out of its trigger price level
+    /// from the stop index
+    fn forget_stop(&mut self, order: &Order) {
+        self.stop_index.remove(&order.id);
+        self.untrack(order);
+        self.next_sequence();
+    }
+
+    /// Returns the number of open orders (including untriggered stop orders)
+    /// the given trader has in this book
+    pub fn open_orders(&self, trader: Address) -> usize {
+        self.open_orders.get(&trader).copied().unwrap_or_default()
+    }
+
+    /// Returns a reference to the open order (including untriggered stop
+    /// orders) of the given trader with the provided client order ID
+    pub fn order_by_client_id(
+        &self,
+        trader: Address,
+        client_order_id: &str,
+    ) -> Option<&Order> {
+        let id: OrderId = *self
+            .client_orders
+            .get(&(trader, client_order_id.to_string()))?;
+        self.order(id)
+    }
+
+    /// Counts a newly opened order towards its trader's open orders
+    fn track(&mut self, order: &Order) {
+        *self.open_orders.entry(order.trader).or_default() += 1;
+
+        if let Some(client_order_id) = &order.client_order_id {
+            self.client_orders
+                .insert((order.trader, client_order_id.clone()), order.id);
+        }
+    }
+
+    /// Stops counting a closed order towards its trader's open orders
+    fn untrack(&mut self, order: &Order) {
+        if let Some(count) = self.open_orders.get_mut(&order.trader) {
+            *count -= 1;
+
+            if *count == 0 {
+                self.open_orders.remove(&order.trader);
+            }
+        }
+
+        if let Some(client_order_id) = &order.client_order_id {
+            self.client_orders
+                .remove(&(order.trader, client_order_id.clone()));
+        }
+    }
+
+    /// Returns whether the order book is currently crossed or not
+    pub fn crossed(&self) -> bool {
+        self.crossed
+    }
+
+    /// Returns the bid-ask spread of the book
+    ///
+    /// The spread is zero if either side of the book is empty or if the book
+    /// is crossed (see `Book::crossed`), as it is never negative.
+    pub fn spread(&self) -> U256 {
+        self.spread
+    }
+
+    /// Returns the total remaining quantity at each price level of the book,
+    /// as a pair of bids and asks with the best prices first
+    ///
+    /// At most `levels` price levels are returned for each side, unless
+    /// `levels` is zero, in which case every level is returned.
+    pub fn aggregated_depth(
+        &self,
+        levels: usize,
+    ) -> (AggregatedLevels, AggregatedLevels) {
+        let levels: usize = if levels == 0 { usize::MAX } else { levels };
+        let aggregate =
+            |(price, level): (&U256, &Level)| (*price, level.total_remaining());
+
+        (
+            self.bids
+                .iter()
+                .rev()
+                .map(aggregate)
+                .filter(|(_, total)| !total.is_zero())
+                .take(levels)
+                .collect(),
+            self.asks
+                .iter()
+                .map(aggregate)
+                .filter(|(_, total)| !total.is_zero())
+                .take(levels)
+                .collect(),
+        )
+    }
+
+    /// Compares this book with another of the same market, price level by
+    /// price level
+    ///
+    /// Levels are compared by their total remaining quantity (a level missing
+    /// from either book counting as empty), so books whose orders differ but
+    /// add up to the same totals are alike. Untriggered stop orders aren't
+    /// compared.
+    pub fn diff(&self, other: &Book) -> BookDiff {
+        let (bids, asks) = self.aggregated_depth(0);
+        let (other_bids, other_asks) = other.aggregated_depth(0);
+        let mut levels: Vec<LevelDiff> = vec![];
+
+        for (side, ours, theirs) in [
+            (OrderSide::Bid, bids, other_bids),
+            (OrderSide::Ask, asks, other_asks),
+        ] {
+            let ours: BTreeMap<U256, U256> = ours.into_iter().collect();
+            let theirs: BTreeMap<U256, U256> = theirs.into_iter().collect();
+            let prices: BTreeSet<U256> =
+                ours.keys().chain(theirs.keys()).copied().collect();
+
+            for price in prices {
+                let ours: U256 = ours.get(&price).copied().unwrap_or_default();
+                let theirs: U256 =
+                    theirs.get(&price).copied().unwrap_or_default();
+
+                if ours != theirs {
+                    levels.push(LevelDiff {
+                        side,
+                        price,
+                        ours,
+                        theirs,
+                    });
+                }
+            }
+        }
+
+        BookDiff {
+            levels,
+            top: self.top(),
+            other_top: other.top(),
+        }
+    }
+
+    /// Returns the resting orders of the given trader in this book
+    ///
+    /// Bids are returned before asks, with each side ordered best price first
+    /// (and by time priority within a price level). Untriggered stop orders
+    /// are not included.
+    pub fn orders_by_trader(&self, trader: Address) -> Vec<&Order> {
+        self.bids
+            .values()
+            .rev()
+            .chain(self.asks.values())
+            .flatten()
+            .filter(|order| order.trader == trader)
+            .collect()
+    }
+
+    /// Summarises the open orders of the given trader in this book
+    ///
+    /// Quantities and notional value only cover the remainder of resting
+    /// orders, as untriggered stop orders have no fixed price. Each order is
+    /// valued at its own price (see `util::scale_18`) and rounded down, and
+    /// totals too large for 256 bits saturate. A trader without open orders
+    /// has no exposure, rather than being an error.
+    pub fn trader_exposure(&self, trader: Address) -> TraderExposure {
+        let mut exposure: TraderExposure = TraderExposure {
+            open_orders: self.open_orders(trader),
+            ..TraderExposure::default()
+        };
+
+        for order in self.orders_by_trader(trader) {
+            let quantity: &mut U256 = match order.side {
+                OrderSide::Bid => &mut exposure.bid_quantity,
+                OrderSide::Ask => &mut exposure.ask_quantity,
+            };
+            *quantity = quantity.saturating_add(order.remaining);
+            exposure.notional = exposure.notional.saturating_add(
+                scale_18(order.price, order.remaining)
+                    .unwrap_or_else(U256::max_value),
+            );
+        }
+
+        exposure
+    }
+
+    /// Returns the history of the order with the given ID, if retained (see
+    /// `OrderHistory`)
+    pub fn order_history(&self, id: OrderId) -> Option<&OrderRecord> {
+        self.history.get(id)
+    }
+
+    /// Returns the most recent trades in this book, newest first
+    ///
+    /// At most `limit` trades are returned, unless `limit` is zero, in which
+    /// case the entire trade history is returned.
+    pub fn recent_trades(&self, limit: usize) -> Vec<&Fill> {
+        let limit: usize = if limit == 0 { usize::MAX } else { limit };
+        self.trades.iter().rev().take(limit).collect()
+    }
+
+    /// Returns the statistics of the trades made in this book within the
+    /// last `STATS_WINDOW_HOURS` hours as of the given time
+    pub fn stats(&self, now: DateTime<Utc>) -> Stats {
+        self.trade_stats.summary(now)
+    }
+
+    /// Appends trades to the trade history, discarding the oldest trades
+    /// beyond `MAX_TRADES`, and counts them towards the trade statistics
+    pub(crate) fn record_trades(&mut self, trades: Vec<Fill>) {
+        for fill in trades.iter() {
+            self.trade_stats.record(fill);
+        }
+        self.trades.extend(trades);
+
+        while self.trades.len() > MAX_TRADES {
+            self.trades.pop_front();
+        }
+    }
+
+    /// Returns the best bid and ask of the book (and the total remaining
+    /// quantity at each), along with its last traded price and spread
+    pub fn ticker(&self) -> Ticker {
+        let best_bid = self.bids.iter().next_back();
+        let best_ask = self.asks.iter().next();
+
+        Ticker {
+            best_bid: best_bid.map(|(price, _)| *price),
+            best_ask: best_ask.map(|(price, _)| *price),
+            bid_volume_at_best: best_bid
+                .map(|(_, level)| level.total_remaining()),
+            ask_volume_at_best: best_ask
+                .map(|(_, level)| level.total_remaining()),
+            last_traded_price: self.ltp,
+            last_trade: self.last_trade,
+            spread: self.spread,
+            sequence: self.sequence,
+        }
+    }
+
+    pub fn top(&self) -> (Option<U256>, Option<U256>) {
+        (
+            self.bids.iter().next_back().map(|t| *t.0),
+            self.asks.iter().next().map(|t| *t.0),
+        )
+    }
+
+    fn price_viable(
+        opposite: U256,
+        incoming: U256,
+        incoming_side: OrderSide,
+    ) -> bool {
+        match incoming_side {
+            OrderSide::Bid => opposite <= incoming,
+            OrderSide::Ask => opposite >= incoming,
+        }
+    }
+
+    /// Returns the fee charged at the given rate (in basis points) on a trade
+    /// of the given price and quantity
+    ///
+    /// Prices are fixed-point with 18 decimal places, so the notional value
+    /// of the trade is `price * quantity / 10^18` (see `util::scale_18`) and
+    /// the fee is `notional * bps / 10_000`, in the same units as the
+    /// quantity. Each step is rounded down, and a fee too large for 256 bits
+    /// saturates.
+    pub fn fee(price: U256, quantity: U256, bps: u32) -> U256 {
+        /* nothing is owed at a zero rate, however large the notional */
+        if bps == 0 {
+            return U256::zero();
+        }
+
+        scale_18(price, quantity)
+            .and_then(|notional| {
+                mul_div(notional, U256::from(bps), U256::from(BPS_PER_UNIT))
+            })
+            .unwrap_or_else(U256::max_value)
+    }
+
+    /// Returns the amount of the given quantity allocated to each order of a
+    /// price level under pro-rata matching, by position in the level
+    ///
+    /// Orders which have expired or which belong to the trader of the
+    /// incoming order are allocated nothing. Every other order is allocated
+    /// its share of the quantity in proportion to its remaining amount,
+    /// rounded down, and the units lost to rounding are then allocated one
+    /// apiece to the orders with the largest remainders (the earliest winning
+    /// ties). The allocations therefore sum to exactly the quantity, or to the
+    /// total remaining at the level if that is less.
+    fn pro_rata(
+        level: &VecDeque<Order>,
+        incoming: &Order,
+        quantity: U256,
+        now: DateTime<Utc>,
+    ) -> Vec<U256> {
+        let eligible = |resting: &Order| {
+            !resting.expired(now) && resting.trader != incoming.trader
+        };
+        let total: U512 = level
+            .iter()
+            .filter(|resting| eligible(resting))
+            .fold(U512::zero(), |acc, resting| {
+                acc + U512::from(resting.remaining)
+            });
+
+        /* if the level can't cover the quantity, everything is filled */
+        if total <= U512::from(quantity) {
+            return level
+                .iter()
+                .map(|resting| {
+                    if eligible(resting) {
+                        resting.remaining
+                    } else {
+                        U256::zero()
+                    }
+                })
+                .collect();
+        }
+
+        let mut allocations: Vec<U256> = Vec::with_capacity(level.len());
+        let mut remainders: Vec<(U512, usize)> = vec![];
+        let mut allocated: U256 = U256::zero();
+
+        for (position, resting) in level.iter().enumerate() {
+            if !eligible(resting) {
+                allocations.push(U256::zero());
+                continue;
+            }
+
+            /* the quantity is below the total, so each share fits */
+            let product: U512 = quantity.full_mul(resting.remaining);
+            let share: U256 = U256::try_from(product / total)
+                .unwrap_or_else(|_| U256::max_value());
+            allocations.push(share);
+            allocated += share;
+            remainders.push((product % total, position));
+        }
+
+        /* the dust is less than the number of shares, as is each remainder
+         * than its order's remaining amount, so no order is overfilled */
+        remainders.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        let dust: usize = (quantity - allocated).low_u64() as usize;
+
+        for (_remainder, position) in remainders.into_iter().take(dust) {
+            allocations[position] += U256::one();
+        }
+
+        allocations
+    }
+
+    /// Returns the outcome of submitting the provided order as of the given
+    /// time, without changing the book in any way
+    ///
+    /// Orders the book would refuse are `OrderStatus::Rejected` with the
+    /// reason, and stop orders are `OrderStatus::Pending`. Any other order is
+    /// simulated as for `Book::simulate`.
+    pub fn quote_at(&self, order: &Order, now: DateTime<Utc>) -> MatchResult {
+        let status: OrderStatus = match self.check_order(order, None, now) {
+            Err(reason) => OrderStatus::Rejected { reason },
+            Ok(()) if order.is_stop() => OrderStatus::Pending,
+            Ok(()) => return self.simulate_at(order, now),
+        };
+
+        MatchResult::new(order.id, status, order.remaining, vec![], vec![])
+    }
+
+    /// Returns the outcome of matching the provided order against the
+    /// current state of the book, without changing the book in any way
+    ///
+    /// The order is matched exactly as `Book::submit` would match it (as a
+    /// limit order at its price), including its time-in-force and the book's
+    /// self-trade prevention policy and matching algorithm. Orders which would
+    /// rest in the book are reported as such, but no order is added or
+    /// removed, no trade is recorded, and the last traded price is left
+    /// unchanged.
+    pub fn simulate(&self, order: &Order) -> MatchResult {
+        self.simulate_at(order, Utc::now())
+    }
+
+    /// Simulates submitting an order as of the given time, as for
+    /// `Book::simulate`
+    fn simulate_at(&self, order: &Order, now: DateTime<Utc>) -> MatchResult {
+        let killed: MatchResult = MatchResult::new(
+            order.id,
+            OrderStatus::Killed,
+            order.remaining,
+            vec![],
+            vec![],
+        );
+
+        /* if we haven't crossed the spread, we're not going to match */
+        let opposing_top: Option<U256> = match order.side {
+            OrderSide::Bid => self.top().1,
+            OrderSide::Ask => self.top().0,
+        };
+        if !opposing_top
+            .map(|top| Book::price_viable(top, order.price, order.side))
+            .unwrap_or(false)
+        {
+            return match order.time_in_force {
+                TimeInForce::GoodTillCancel => MatchResult::new(
+                    order.id,
+                    OrderStatus::Placed,
+                    order.remaining,
+                    vec![],
+                    vec![],
+                ),
+                _ => killed,
+            };
+        }
+
+        /* the totals of the levels rule most infeasible orders out cheaply */
+        if order.time_in_force == TimeInForce::FillOrKill && !self.covers(order)
+        {
+            return killed;
+        }
+
+        let opposing_side: &BTreeMap<U256, Level> = match order.side {
+            OrderSide::Bid => &self.asks,
+            OrderSide::Ask => &self.bids,
+        };
+        let levels = match order.side {
+            OrderSide::Bid => Either::Left(opposing_side.iter()),
+            OrderSide::Ask => Either::Right(opposing_side.iter().rev()),
+        };
+
+        let mut remaining: U256 = order.remaining;
+        let mut sequence: u64 = self.sequence;
+        let mut aborted: bool = false;
+        let mut cancelled: Vec<OrderId> = vec![];
+        let mut fills: Vec<Fill> = vec![];
+        let mut closed_makers: Vec<OrderId> = vec![];
+        let mut reduced_makers: Vec<(OrderId, U256)> = vec![];
+
+        'levels: for (price, opposites) in levels {
+            if !Book::price_viable(*price, order.price, order.side) {
+                break;
+            }
+
+            let allocations: Option<Vec<U256>> = match self.matching_algorithm {
+                MatchingAlgorithm::Fifo => None,
+                MatchingAlgorithm::ProRata => {
+                    if self.self_trade_policy == SelfTradePolicy::CancelIncoming
+                        && opposites.iter().any(|resting| {
+                            !resting.expired(now)
+                                && resting.trader == order.trader
+                        })
+                    {
+                        cancelled.push(order.id);
+                        aborted = true;
+                        break 'levels;
+                    }
+
+                    Some(Book::pro_rata(opposites, order, remaining, now))
+                }
+            };
+
+            for (position, opposite) in opposites.iter().enumerate() {
+                if opposite.expired(now) {
+                    continue;
+                }
+
+                if opposite.trader == order.trader {
+                    match self.self_trade_policy {
+                        SelfTradePolicy::Skip => continue,
+                        SelfTradePolicy::CancelResting => {
+                            cancelled.push(opposite.id);
+                            continue;
+                        }
+                        SelfTradePolicy::CancelIncoming => {
+                            cancelled.push(order.id);
+                            aborted = true;
+                            break 'levels;
+                        }
+                    }
+                }
+
+                let amount: U256 = match &allocations {
+                    Some(allocations) => allocations[position],
+                    None => remaining.min(opposite.remaining),
+                };
+                if amount.is_zero() {
+                    continue;
+                }
+
+                sequence += 1;
+                fills.push(Fill {
+                    trade_id: sequence,
+                    maker_id: opposite.id,
+                    taker_id: order.id,
+                    maker_address: opposite.trader,
+                    taker_address: order.trader,
+                    price: *price,
+                    quantity: amount,
+                    timestamp: now,
+                    maker_client_order_id: opposite.client_order_id.clone(),
+                    taker_client_order_id: order.client_order_id.clone(),
+                    maker_fee: Book::fee(*price, amount, self.maker_fee_bps),
+                    taker_fee: Book::fee(*price, amount, self.taker_fee_bps),
+                });
+                remaining -= amount;
+
+                if amount == opposite.remaining {
+                    closed_makers.push(opposite.id);
+                } else {
+                    reduced_makers
+                        .push((opposite.id, opposite.remaining - amount));
+                }
+
+                if remaining.is_zero() {
+                    break 'levels;
+                }
+            }
+        }
+
+        /* fill-or-kill orders do nothing at all unless they fill entirely */
+        if order.time_in_force == TimeInForce::FillOrKill
+            && !remaining.is_zero()
+        {
+            return killed;
+        }
+
+        let order_status: OrderStatus = if remaining.is_zero() {
+            OrderStatus::FullMatch
+        } else if (order.time_in_force == TimeInForce::GoodTillCancel
+            && !aborted)
+            || remaining < order.remaining
+        {
+            OrderStatus::PartialMatch
+        } else {
+            OrderStatus::Killed
+        };
+
+        MatchResult::new(order.id, order_status, remaining, fills, cancelled)
+            .with_makers(closed_makers, reduced_makers)
+    }
+
+    /// Returns whether the opposing price levels acceptable to an order hold
+    /// at least its remaining quantity between them
+    ///
+    /// Only the totals of the levels are consulted, so this is an upper bound
+    /// on what the order can fill, as expired orders and self-trades aren't
+    /// accounted for.
+    fn covers(&self, order: &Order) -> bool {
+        let opposing_side: &BTreeMap<U256, Level> = match order.side {
+            OrderSide::Bid => &self.asks,
+            OrderSide::Ask => &self.bids,
+        };
+        let levels = match order.side {
+            OrderSide::Bid => Either::Left(opposing_side.iter()),
+            OrderSide::Ask => Either::Right(opposing_side.iter().rev()),
+        };
+        let mut available: U256 = U256::zero();
+
+        for (price, level) in levels {
+            if !Book::price_viable(*price, order.price, order.side) {
+                break;
+            }
+
+            available = available.saturating_add(level.total_remaining());
+            if available >= order.remaining {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    #[allow(unused_must_use)]
+    #[tracing::instrument(
+        skip(self, order, executioner, opposing_top, now),
+        fields(market = ?self.market, order_id = ?order.id)
+    )]
+    async fn r#match(
         &mut self,
         mut order: Order,
-        executioner_address: String,
+        executioner: Option<Executioner>,
         opposing_top: Option<U256>,
-    ) -> Result<OrderStatus, BookError> {
+        now: DateTime<Utc>,
+    ) -> Result<MatchResult, BookError> {
         info!("Matching {}...", order);
 
-        let opposing_side: &mut BTreeMap<U256, VecDeque<Order>> =
-            match order.side {
-                OrderSide::Bid => &mut self.asks,
-                OrderSide::Ask => &mut self.bids,
-            };
+        let order_id: OrderId = order.id;
         let mut running_total: U256 = order.remaining;
         let mut done: bool = false;
+        let mut aborted: bool = false;
+        let mut cancelled: Vec<OrderId> = vec![];
+        let mut fills: Vec<Fill> = vec![];
+        let mut closed_makers: Vec<OrderId> = vec![];
+        let mut reduced_makers: Vec<(OrderId, U256)> = vec![];
+        let mut executions: Vec<Execution> = vec![];
+        let mut drained: Vec<Order> = vec![];
+        let mut touched: Vec<U256> = vec![];
 
         /* if we haven't crossed the spread, we're not going to match */
         if opposing_top.is_none()
@@ -205,127 +2019,798 @@ impl Book {
                 order.side,
             )
         {
+            if order.time_in_force != TimeInForce::GoodTillCancel {
+                info!("{} does not cross, killing...", order);
+                self.close_unmatched(&order, OrderEventKind::Killed, now);
+                return Ok(MatchResult::new(
+                    order_id,
+                    OrderStatus::Killed,
+                    order.remaining,
+                    vec![],
+                    vec![],
+                ));
+            }
+
             info!("{} does not cross, adding...", order);
-            self.add_order(order);
-            return Ok(OrderStatus::Add);
+            let remaining: U256 = order.remaining;
+            self.add_order(order.clone());
+            self.emit(BookEventKind::Added(order), now);
+            return Ok(MatchResult::new(
+                order_id,
+                OrderStatus::Placed,
+                remaining,
+                vec![],
+                vec![],
+            ));
+        }
+
+        /* fill-or-kill orders must be matchable in their entirety up front */
+        if order.time_in_force == TimeInForce::FillOrKill
+            && self.simulate_at(&order, now).order_status == OrderStatus::Killed
+        {
+            info!("{} cannot be fully filled, killing...", order);
+            self.close_unmatched(&order, OrderEventKind::Killed, now);
+            return Ok(MatchResult::new(
+                order_id,
+                OrderStatus::Killed,
+                order.remaining,
+                vec![],
+                vec![],
+            ));
+        }
+
+        let self_trade_policy: SelfTradePolicy = self.self_trade_policy;
+        let matching_algorithm: MatchingAlgorithm = self.matching_algorithm;
+        let (maker_fee_bps, taker_fee_bps): (u32, u32) =
+            (self.maker_fee_bps, self.taker_fee_bps);
+        let initial_remaining: U256 = order.remaining;
+        let (opposing_side, opposing_depth, opposing_volume): (
+            &mut BTreeMap<U256, Level>,
+            &mut usize,
+            &mut U256,
+        ) = match order.side {
+            OrderSide::Bid => {
+                (&mut self.asks, &mut self.depth.1, &mut self.volume.1)
+            }
+            OrderSide::Ask => {
+                (&mut self.bids, &mut self.depth.0, &mut self.volume.0)
+            }
+        };
+        let opposing_side_iterator = match order.side {
+            OrderSide::Bid => Either::Left(opposing_side.iter_mut()),
+            OrderSide::Ask => Either::Right(opposing_side.iter_mut().rev()),
+        };
+
+        for (price, opposites) in opposing_side_iterator {
+            /* if we've run out of viable prices or we're done, halt */
+            if done || !Book::price_viable(*price, order.price, order.side) {
+                break;
+            }
+
+            /* under pro-rata, the whole level is allocated up front */
+            let allocations: Option<Vec<U256>> = match matching_algorithm {
+                MatchingAlgorithm::Fifo => None,
+                MatchingAlgorithm::ProRata => {
+                    /* there's no order of arrival to match up to, so a
+                     * self-match anywhere at the level halts matching */
+                    if self_trade_policy == SelfTradePolicy::CancelIncoming
+                        && opposites.iter().any(|resting| {
+                            !resting.expired(now)
+                                && resting.trader == order.trader
+                        })
+                    {
+                        info!("Self-trade, cancelling {}...", order);
+                        cancelled.push(order.id);
+                        aborted = true;
+                        break;
+                    }
+
+                    Some(Book::pro_rata(
+                        opposites,
+                        &order,
+                        order.remaining,
+                        now,
+                    ))
+                }
+            };
+
+            let mut visited: usize = 0;
+            let mut level_filled: U256 = U256::zero();
+
+            for (position, opposite) in opposites.orders.iter_mut().enumerate()
+            {
+                visited = position + 1;
+
+                /* expired orders are skipped here and drained afterwards */
+                if opposite.expired(now) {
+                    info!("{} has expired, skipping...", opposite);
+                    continue;
+                }
+
+                /* no self-trading allowed */
+                if opposite.trader == order.trader {
+                    match self_trade_policy {
+                        SelfTradePolicy::Skip => {
+                            info!("Self-trade, skipping...");
+                            continue;
+                        }
+                        SelfTradePolicy::CancelResting => {
+                            info!("Self-trade, cancelling {}...", opposite);
+                            cancelled.push(opposite.id);
+                            continue;
+                        }
+                        SelfTradePolicy::CancelIncoming => {
+                            info!("Self-trade, cancelling {}...", order);
+                            cancelled.push(order.id);
+                            aborted = true;
+                            done = true;
+                            break;
+                        }
+                    }
+                }
+
+                /* determine how much to match */
+                let amount: U256 = match &allocations {
+                    Some(allocations) => allocations[position],
+                    None => match opposite.remaining.cmp(&order.remaining) {
+                        Ordering::Greater => order.remaining,
+                        _ => opposite.remaining,
+                    },
+                };
+                if amount.is_zero() {
+                    continue;
+                }
+                info!("Matching with amount of {}...", amount);
+
+                /* match */
+                Book::fill_in_place(&mut order, amount);
+                Book::fill_in_place(opposite, amount);
+                level_filled += amount;
+
+                *opposing_volume = opposing_volume.saturating_sub(amount);
+                if opposite.remaining.is_zero() {
+                    *opposing_depth -= 1;
+                    closed_makers.push(opposite.id);
+                } else {
+                    reduced_makers.push((opposite.id, opposite.remaining));
+                }
+                self.sequence += 1;
+
+                self.ltp = *price;
+                self.last_trade = Some(LastTrade {
+                    price: *price,
+                    quantity: amount,
+                    taker_side: order.side,
+                    timestamp: now,
+                });
+                info!("LTP updated, is now {}", self.ltp);
+
+                let fill: Fill = Fill {
+                    trade_id: self.sequence,
+                    maker_id: opposite.id,
+                    taker_id: order.id,
+                    maker_address: opposite.trader,
+                    taker_address: order.trader,
+                    price: *price,
+                    quantity: amount,
+                    timestamp: now,
+                    maker_client_order_id: opposite.client_order_id.clone(),
+                    taker_client_order_id: order.client_order_id.clone(),
+                    maker_fee: Book::fee(*price, amount, maker_fee_bps),
+                    taker_fee: Book::fee(*price, amount, taker_fee_bps),
+                };
+
+                /* report the fill to both sides, not just the taker */
+                let event: BookEvent = BookEvent::new(
+                    self.market,
+                    self.sequence,
+                    BookEventKind::Fill {
+                        fill: fill.clone(),
+                        maker_remaining: opposite.remaining,
+                        taker_remaining: order.remaining,
+                    },
+                );
+                self.history.record_book_event(&event, now);
+                self.events.push(event);
+                executions.push(
+                    Execution::new(opposite, &order, amount)
+                        .with_fees(fill.maker_fee, fill.taker_fee),
+                );
+                fills.push(fill);
+
+                running_total -= amount;
+
+                /* check if we've totally matched our incoming order */
+                if running_total.is_zero() {
+                    info!("Totally matched {}", order);
+                    done = true;
+                    break;
+                }
+            }
+
+            opposites.consume(level_filled);
+
+            /* take the orders consumed (or found expired) off the front of
+             * the level, rather than leaving them for a prune of the book */
+            drained.extend(opposites.take_front_where(visited, |resting| {
+                resting.remaining.is_zero() || resting.expired(now)
+            }));
+            touched.push(*price);
+        }
+
+        self.record_trades(fills.clone());
+
+        if let Some(executioner) = &executioner {
+            if !executions.is_empty() {
+                executioner.forward(executions);
+            }
+        }
+
+        let opposing_side: &mut BTreeMap<U256, Level> = match order.side {
+            OrderSide::Bid => &mut self.asks,
+            OrderSide::Ask => &mut self.bids,
+        };
+        let mut removed: Vec<Order> = vec![];
+
+        /* remove any resting orders cancelled by self-trade prevention */
+        if !cancelled.is_empty() {
+            for price in &touched {
+                if let Some(level) = opposing_side.get_mut(price) {
+                    removed.extend(
+                        level.take_where(|resting| {
+                            cancelled.contains(&resting.id)
+                        }),
+                    );
+                }
+            }
+        }
+
+        /* only the levels matched against can have been emptied */
+        for price in &touched {
+            if opposing_side
+                .get(price)
+                .is_some_and(|level| level.is_empty())
+            {
+                opposing_side.remove(price);
+            }
         }
 
-        let opposing_side_iterator = match order.side {
-            OrderSide::Bid => Either::Left(opposing_side.iter_mut()),
-            OrderSide::Ask => Either::Right(opposing_side.iter_mut().rev()),
+        /* the levels matched against are on the opposite side */
+        let opposing: OrderSide = match order.side {
+            OrderSide::Bid => OrderSide::Ask,
+            OrderSide::Ask => OrderSide::Bid,
+        };
+        for price in touched {
+            self.change_level(opposing, price);
+        }
+
+        for resting in removed {
+            self.forget(&resting);
+            self.emit(BookEventKind::Cancelled(resting), now);
+        }
+
+        /* an incoming order stopped by self-trade prevention is cancelled */
+        let closing: OrderEventKind = if aborted {
+            OrderEventKind::Cancelled
+        } else {
+            OrderEventKind::Killed
+        };
+
+        /* if our incoming order has any volume left, add it to the book */
+        let order_status: OrderStatus = if running_total.is_zero() {
+            OrderStatus::FullMatch
+        } else if order.time_in_force == TimeInForce::GoodTillCancel && !aborted
+        {
+            self.add_order(order.clone());
+            self.emit(BookEventKind::Added(order), now);
+            OrderStatus::PartialMatch
+        } else if running_total < initial_remaining {
+            info!("Dropping unmatched remainder of {}", order);
+            self.close_unmatched(&order, closing, now);
+            OrderStatus::PartialMatch
+        } else {
+            info!("Nothing matched for {}, killing...", order);
+            self.close_unmatched(&order, closing, now);
+            OrderStatus::Killed
+        };
+
+        self.bury(drained, now);
+
+        Ok(MatchResult::new(
+            order_id,
+            order_status,
+            running_total,
+            fills,
+            cancelled,
+        )
+        .with_makers(closed_makers, reduced_makers))
+    }
+
+    /// Records the closing of an incoming order whose remainder is dropped
+    /// rather than added to the book
+    fn close_unmatched(
+        &mut self,
+        order: &Order,
+        kind: OrderEventKind,
+        now: DateTime<Utc>,
+    ) {
+        self.history.record(
+            order.id,
+            OrderEvent::new(kind, self.sequence, now, order.remaining),
+        );
+    }
+
+    /// Takes a filled amount off the remaining quantity of an order, leaving
+    /// the order untouched should the amount exceed what remains of it
+    fn fill_in_place(order: &mut Order, amount: U256) {
+        info!("Filling {} of {}...", amount, order);
+
+        if let Some(remaining) = order.remaining.checked_sub(amount) {
+            order.remaining = remaining;
+        }
+    }
+
+    /// Forgets orders drained from the book for having been filled or having
+    /// expired, reporting each expired one
+    fn bury(&mut self, dead: Vec<Order>, now: DateTime<Utc>) {
+        for order in dead {
+            self.forget(&order);
+
+            /* filled orders were already reported by their final fill */
+            if !order.remaining.is_zero() {
+                self.emit(BookEventKind::Expired(order), now);
+            }
+        }
+    }
+
+    /// Removes every filled or expired order from the book, along with any
+    /// price level left empty
+    ///
+    /// This scans the whole book, so is kept off the hot path: matching drains
+    /// the levels it touches itself, leaving this to purge orders that expire
+    /// at rest and to clean up restored books.
+    fn prune(&mut self, now: DateTime<Utc>) {
+        let mut removed: Vec<Order> = vec![];
+
+        for orders in self.bids.values_mut().chain(self.asks.values_mut()) {
+            if orders
+                .iter()
+                .all(|order| !order.remaining.is_zero() && !order.expired(now))
+            {
+                continue;
+            }
+
+            removed.extend(orders.take_where(|order| {
+                order.remaining.is_zero() || order.expired(now)
+            }));
+        }
+
+        self.bury(removed, now);
+
+        self.bids.retain(|_price, orders| !orders.is_empty());
+        self.asks.retain(|_price, orders| !orders.is_empty());
+
+        /* untriggered stop orders can expire too */
+        let mut expired: Vec<Order> = vec![];
+
+        for orders in self
+            .buy_stops
+            .values_mut()
+            .chain(self.sell_stops.values_mut())
+        {
+            expired.extend(orders.take_where(|order| order.expired(now)));
+        }
+
+        for order in expired {
+            self.forget_stop(&order);
+            self.emit(BookEventKind::Expired(order), now);
+        }
+
+        self.buy_stops.retain(|_price, orders| !orders.is_empty());
+        self.sell_stops.retain(|_price, orders| !orders.is_empty());
+    }
+
+    /// Checks that an incoming order is acceptable to this book
+    ///
+    /// If the order is to replace an open order, it is checked as though that
+    /// order had already been cancelled.
+    pub(crate) fn check_order(
+        &self,
+        order: &Order,
+        replacing: Option<&Order>,
+        now: DateTime<Utc>,
+    ) -> Result<(), BookError> {
+        if order.market != self.market {
+            return Err(BookError::WrongMarket);
+        }
+
+        if self.paused {
+            return Err(BookError::MarketPaused);
+        }
+
+        if order.expired(now) {
+            return Err(BookError::OrderExpired);
+        }
+
+        /* order IDs are deterministic, so retried submissions collide */
+        if (self.index.contains_key(&order.id)
+            || self.stop_index.contains_key(&order.id))
+            && replacing.map(|old| old.id) != Some(order.id)
+        {
+            return Err(BookError::DuplicateOrder);
+        }
+
+        /* client order IDs are only unique among a trader's open orders */
+        if let Some(client_order_id) = &order.client_order_id {
+            if self
+                .order_by_client_id(order.trader, client_order_id)
+                .map(|open| open.id)
+                .filter(|id| replacing.map(|old| old.id) != Some(*id))
+                .is_some()
+            {
+                return Err(BookError::DuplicateClientOrderId);
+            }
+        }
+
+        if order.quantity.is_zero()
+            || order.remaining.is_zero()
+            || order.remaining > order.quantity
+        {
+            return Err(BookError::InvalidQuantity);
+        }
+
+        match (order.order_type, order.trigger_price) {
+            (OrderType::Limit, None) => {}
+            (OrderType::Stop, Some(t)) | (OrderType::StopLimit, Some(t))
+                if !t.is_zero() => {}
+            _ => return Err(BookError::InvalidTrigger),
+        }
+
+        /* stop orders take liquidity at any price once triggered */
+        let price: Option<U256> = match order.order_type {
+            OrderType::Stop => None,
+            _ => Some(order.price),
+        };
+        if price.is_some_and(|t| t.is_zero()) {
+            return Err(BookError::InvalidPrice);
+        }
+        self.check_increments(price, order.quantity)?;
+
+        if order.quantity < self.min_quantity {
+            return Err(BookError::BelowMinQuantity);
+        }
+
+        /* stop orders have no price of their own, so are valued at their
+         * trigger price */
+        let price: U256 = match (order.order_type, order.trigger_price) {
+            (OrderType::Stop, Some(t)) => t,
+            _ => order.price,
+        };
+        self.check_size(price, order.quantity)?;
+
+        /* orders that can never rest are exempt from the open order cap */
+        let may_rest: bool = order.is_stop()
+            || order.time_in_force == TimeInForce::GoodTillCancel;
+
+        let open_orders: usize = self.open_orders(order.trader)
+            - replacing
+                .filter(|old| old.trader == order.trader)
+                .map_or(0, |_| 1);
+
+        if may_rest
+            && self.max_orders_per_trader != 0
+            && open_orders >= self.max_orders_per_trader
+        {
+            return Err(BookError::TooManyOrders);
+        }
+
+        Ok(())
+    }
+
+    /* checks that the price (if any) and quantity of an order are multiples
+     * of the tick and lot sizes of the book */
+    fn check_increments(
+        &self,
+        price: Option<U256>,
+        quantity: U256,
+    ) -> Result<(), BookError> {
+        if let Some(price) = price {
+            if !self.tick_size.is_zero() && !(price % self.tick_size).is_zero()
+            {
+                return Err(BookError::InvalidTickSize);
+            }
+        }
+
+        if !self.lot_size.is_zero() && !(quantity % self.lot_size).is_zero() {
+            return Err(BookError::InvalidLotSize);
+        }
+
+        Ok(())
+    }
+
+    /// Submits an order to the matching engine
+    ///
+    /// In the event the order cannot be (fully) matched, it will be stored
+    /// in the order book for future matching.
+    ///
+    /// Stop and stop-limit orders are instead stored until the last traded
+    /// price reaches their trigger price (see `Book::next_triggered_stop`).
+    /// Triggers are checked after every order that results in any fills, and
+    /// triggered orders are then matched in turn.
+    ///
+    /// Returns `BookError::OrderExpired` if the order has already expired,
+    /// `BookError::InvalidQuantity` or `BookError::InvalidPrice` if the
+    /// order has a zero (or otherwise inconsistent) quantity or price,
+    /// `BookError::InvalidTickSize` or `BookError::InvalidLotSize` if the
+    /// order's price or quantity is not a multiple of the book's tick size or
+    /// lot size respectively, and `BookError::InvalidTrigger` if a stop order
+    /// lacks a (non-zero) trigger price or a limit order has one.
+    /// `BookError::OrderTooLarge` is returned if the order's quantity or
+    /// notional value exceeds the book's cap (see `Book::check_size`).
+    /// `BookError::DuplicateOrder` is returned if an order with the same ID
+    /// is already open in the book (including untriggered stop orders), and
+    /// `BookError::MarketPaused` if the book has been paused.
+    pub async fn submit(
+        &mut self,
+        order: Order,
+        executioner: Executioner,
+    ) -> Result<MatchResult, BookError> {
+        self.submit_at(order, Some(executioner), Utc::now()).await
+    }
+
+    /// Submits an order to the matching engine as of the given time, as for
+    /// `Book::submit`
+    ///
+    /// Matches are only forwarded if an executioner is given, so that orders
+    /// replayed from the write-ahead log don't have their matches executed a
+    /// second time.
+    #[tracing::instrument(
+        skip(self, order, executioner, now),
+        fields(market = ?self.market, order_id = ?order.id)
+    )]
+    pub async fn submit_at(
+        &mut self,
+        order: Order,
+        executioner: Option<Executioner>,
+        now: DateTime<Utc>,
+    ) -> Result<MatchResult, BookError> {
+        info!("Submitting {}...", order);
+
+        if let Err(e) = self.check_order(&order, None, now) {
+            warn!("Rejecting {}: {}", order, e);
+            return Err(e);
+        }
+
+        self.accept(order, executioner, now).await
+    }
+
+    /// Atomically cancels an open order and submits another in its place
+    ///
+    /// The new order is checked (as by `Book::submit`) before the open order
+    /// is cancelled, so the open order is left untouched if the new order is
+    /// rejected. There is hence never a moment where neither or both of the
+    /// orders are open.
+    ///
+    /// # Returns #
+    ///
+    /// Returns the cancelled order (including its unfilled remaining quantity)
+    /// along with the result of matching the new order.
+    ///
+    /// Returns `BookError::NoSuchOrder` if there is no such open order in the
+    /// book and `BookError::InvalidAmendment` if the new order is for another
+    /// trader, along with any error `Book::submit` returns for the new order.
+    pub async fn replace(
+        &mut self,
+        order_id: OrderId,
+        order: Order,
+        executioner: Executioner,
+    ) -> Result<(Order, MatchResult), BookError> {
+        self.replace_at(order_id, order, Some(executioner), Utc::now())
+            .await
+    }
+
+    /// Atomically replaces an open order as of the given time, as for
+    /// `Book::replace`
+    ///
+    /// Matches are only forwarded if an executioner is given (see
+    /// `Book::submit_at`).
+    #[tracing::instrument(
+        skip(self, order_id, order, executioner, now),
+        fields(market = ?self.market, order_id = ?order.id, replaces = ?order_id)
+    )]
+    pub async fn replace_at(
+        &mut self,
+        order_id: OrderId,
+        order: Order,
+        executioner: Option<Executioner>,
+        now: DateTime<Utc>,
+    ) -> Result<(Order, MatchResult), BookError> {
+        info!("Replacing {:?} with {}...", order_id, order);
+
+        let previous: Order = match self.order(order_id) {
+            Some(t) if !t.remaining.is_zero() => t.clone(),
+            _ => return Err(BookError::NoSuchOrder),
         };
 
-        for (price, opposites) in opposing_side_iterator {
-            /* if we've run out of viable prices or we're done, halt */
-            if done || !Book::price_viable(*price, order.price, order.side) {
-                break;
-            }
+        if previous.trader != order.trader {
+            warn!("Rejecting replacement of {} by {}", previous, order);
+            return Err(BookError::InvalidAmendment);
+        }
 
-            for opposite in opposites {
-                /* no self-trading allowed */
-                if opposite.trader == order.trader {
-                    info!("Self-trade, skipping...");
-                    continue;
-                }
+        if let Err(e) = self.check_order(&order, Some(&previous), now) {
+            warn!("Rejecting {}: {}", order, e);
+            return Err(e);
+        }
 
-                /* determine how much to match */
-                let amount: U256 =
-                    match opposite.remaining.cmp(&order.remaining) {
-                        Ordering::Greater => order.remaining,
-                        _ => opposite.remaining,
-                    };
-                info!("Matching with amount of {}...", amount);
+        let cancelled: Order = self
+            .cancel_at(order_id, now)?
+            .ok_or(BookError::NoSuchOrder)?;
+        let match_result: MatchResult =
+            self.accept(order, executioner, now).await?;
 
-                /* match */
-                order = Book::fill(order, amount);
-                *opposite = Book::fill(opposite.clone(), amount);
+        Ok((cancelled, match_result))
+    }
 
-                self.ltp = *price;
-                info!("LTP updated, is now {}", self.ltp);
+    /// Matches (or stores) an order that has already been checked
+    async fn accept(
+        &mut self,
+        order: Order,
+        executioner: Option<Executioner>,
+        now: DateTime<Utc>,
+    ) -> Result<MatchResult, BookError> {
+        self.history.record(
+            order.id,
+            OrderEvent::new(
+                OrderEventKind::Accepted,
+                self.sequence,
+                now,
+                order.remaining,
+            ),
+        );
 
-                info!("Forwarding {} and {}...", order, opposite);
-                rpc::send_matched_orders(
-                    order.clone(),
-                    opposite.clone(),
-                    executioner_address.clone(),
-                )
-                .await;
+        if order.is_stop() {
+            let order_id: OrderId = order.id;
+            let remaining: U256 = order.remaining;
+            self.add_stop(order, now);
+            self.update();
+            return Ok(MatchResult::new(
+                order_id,
+                OrderStatus::Pending,
+                remaining,
+                vec![],
+                vec![],
+            ));
+        }
 
-                running_total -= amount;
+        let opposing_top: Option<U256> = match order.side {
+            OrderSide::Bid => self.top().1,
+            OrderSide::Ask => self.top().0,
+        };
+        let mut match_result: Result<MatchResult, BookError> = self
+            .r#match(order, executioner.clone(), opposing_top, now)
+            .await;
 
-                /* check if we've totally matched our incoming order */
-                if running_total.is_zero() {
-                    info!("Totally matched {}", order);
-                    done = true;
-                    break;
-                }
+        self.update();
+
+        if let Ok(result) = match_result.as_mut() {
+            if !result.fills.is_empty() {
+                let (triggered, triggered_fills) =
+                    self.trigger_stops(executioner, now).await;
+                result.triggered = triggered;
+                result.triggered_fills = triggered_fills;
             }
         }
 
-        /* if our incoming order has any volume left, add it to the book */
-        if running_total > U256::zero() {
-            self.add_order(order);
-            Ok(OrderStatus::PartialMatch)
-        } else {
-            Ok(OrderStatus::FullMatch)
-        }
+        match_result
     }
 
-    fn fill(order: Order, amount: U256) -> Order {
-        info!("Filling {} of {}...", amount, order);
-        match amount.cmp(&order.remaining) {
-            Ordering::Greater => order,
-            _ => Order {
-                id: order.id,
-                trader: order.trader,
-                market: order.market,
-                side: order.side,
-                price: order.price,
-                quantity: order.quantity,
-                remaining: order.remaining - amount,
-                expiration: order.expiration,
-                created: order.created,
-                signed_data: order.signed_data,
-            },
-        }
-    }
+    /// Matches every stop order triggered by the last traded price, including
+    /// any triggered by the fills of previously triggered stop orders
+    ///
+    /// Returns the IDs of the triggered stop orders, along with the fills
+    /// they made.
+    async fn trigger_stops(
+        &mut self,
+        executioner: Option<Executioner>,
+        now: DateTime<Utc>,
+    ) -> (Vec<OrderId>, Vec<Fill>) {
+        let mut triggered: Vec<OrderId> = vec![];
+        let mut fills: Vec<Fill> = vec![];
 
-    fn prune(&mut self) {
-        for (_price, orders) in self.bids.iter_mut() {
-            orders.retain(|order| !order.remaining.is_zero());
-        }
+        while let Some(mut order) = self.next_triggered_stop(now) {
+            info!("LTP of {} triggered {}", self.ltp, order);
+            triggered.push(order.id);
+
+            /* stop orders become marketable immediate-or-cancel orders, and
+             * stop-limit orders plain limit orders (which may then rest) */
+            if order.order_type == OrderType::Stop {
+                order.price = match order.side {
+                    OrderSide::Bid => U256::max_value(),
+                    OrderSide::Ask => U256::zero(),
+                };
+                order.time_in_force = TimeInForce::ImmediateOrCancel;
+            }
+            order.order_type = OrderType::Limit;
+            order.trigger_price = None;
+
+            let opposing_top: Option<U256> = match order.side {
+                OrderSide::Bid => self.top().1,
+                OrderSide::Ask => self.top().0,
+            };
+
+            match self
+                .r#match(order, executioner.clone(), opposing_top, now)
+                .await
+            {
+                Ok(result) => fills.extend(result.fills),
+                Err(e) => warn!("Failed to match triggered stop order: {}", e),
+            }
 
-        for (_price, orders) in self.asks.iter_mut() {
-            orders.retain(|order| !order.remaining.is_zero());
+            self.update();
         }
 
-        self.bids.retain(|_price, orders| !orders.is_empty());
-        self.asks.retain(|_price, orders| !orders.is_empty());
+        (triggered, fills)
     }
 
-    /// Submits an order to the matching engine
+    /// Removes and returns the next stop order whose trigger price has been
+    /// reached by the last traded price, if any
     ///
-    /// In the event the order cannot be (fully) matched, it will be stored
-    /// in the order book for future matching.
-    pub async fn submit(
-        &mut self,
-        order: Order,
-        executioner_address: String,
-    ) -> Result<OrderStatus, BookError> {
-        info!("Submitting {}...", order);
+    /// Buy stops trigger once the last traded price rises to (or above) their
+    /// trigger price, and sell stops once it falls to (or below) theirs.
+    /// Stops that have expired by the time they are triggered are dropped
+    /// (and reported as expired) instead.
+    fn next_triggered_stop(&mut self, now: DateTime<Utc>) -> Option<Order> {
+        loop {
+            let ltp: U256 = self.ltp;
+            let buy_trigger: Option<U256> =
+                self.buy_stops.keys().next().copied();
+            let sell_trigger: Option<U256> =
+                self.sell_stops.keys().next_back().copied();
+            let (stops, trigger_price) = match (buy_trigger, sell_trigger) {
+                (Some(t), _) if t <= ltp => (&mut self.buy_stops, t),
+                (_, Some(t)) if t >= ltp => (&mut self.sell_stops, t),
+                _ => return None,
+            };
 
-        let match_result: Result<OrderStatus, BookError> = match order.side {
-            OrderSide::Bid => {
-                self.r#match(order, executioner_address, self.top().1).await
+            let level: &mut Level = stops.get_mut(&trigger_price)?;
+            let order: Order = level.remove(0)?;
+
+            if level.is_empty() {
+                stops.remove(&trigger_price);
             }
-            OrderSide::Ask => {
-                self.r#match(order, executioner_address, self.top().0).await
+
+            self.forget_stop(&order);
+
+            if order.expired(now) {
+                info!("Dropping expired stop {}", order);
+                self.emit(BookEventKind::Expired(order), now);
+                continue;
             }
-        };
 
-        self.update();
+            self.emit(BookEventKind::Triggered(order.clone()), now);
+            return Some(order);
+        }
+    }
 
-        match_result
+    /// Stores a stop order until it is triggered
+    fn add_stop(&mut self, order: Order, now: DateTime<Utc>) {
+        let trigger_price: U256 = order.trigger_price.unwrap_or_default();
+
+        info!("Adding stop {} with trigger {}", order, trigger_price);
+        self.stop_index
+            .insert(order.id, (order.side, trigger_price));
+        self.track(&order);
+
+        let stops: &mut BTreeMap<U256, Level> = match order.side {
+            OrderSide::Bid => &mut self.buy_stops,
+            OrderSide::Ask => &mut self.sell_stops,
+        };
+        stops
+            .entry(trigger_price)
+            .or_default()
+            .push_back(order.clone());
+        self.next_sequence();
+        self.emit(BookEventKind::Added(order), now);
     }
 
     #[allow(clippy::unnecessary_wraps)]
@@ -335,7 +2820,12 @@ impl Book {
         let tmp_order: Order = order.clone();
         let order_side = order.side;
         let order_price = order.price;
-        let orders = VecDeque::new();
+        let orders: Level = Level::new();
+
+        self.index.insert(order.id, (order_side, order_price));
+        self.track(&order);
+        self.open(order_side, order.remaining);
+        self.next_sequence();
 
         match order_side {
             OrderSide::Bid => {
@@ -365,38 +2855,325 @@ impl Book {
     ///
     /// # Returns #
     ///
-    /// Returns `Ok(Some(dt))` upon success, where `dt` is a `DateTime<Utc>`
-    /// type representing the time of successful cancellation of the order.
+    /// Returns `Ok(Some(order))` upon success, where `order` is the order that
+    /// was removed from the book (including its unfilled remaining quantity).
     ///
     /// Returns `Ok(None)` if there is no such order currently in the book.
     ///
     /// Returns a `BookError` if there is an error condition
-    #[allow(unused_variables)] /* TODO: remove when cancel is implemented */
     pub fn cancel(
         &mut self,
         order_id: OrderId,
-    ) -> Result<Option<DateTime<Utc>>, BookError> {
-        for (_, orders) in self.bids.iter_mut() {
-            for (index, order) in orders.iter_mut().enumerate() {
-                if order.id == order_id {
-                    info!("Cancelled {}", order.clone());
-                    orders.remove(index);
-                    return Ok(Some(Utc::now()));
-                }
-            }
+    ) -> Result<Option<Order>, BookError> {
+        self.cancel_at(order_id, Utc::now())
+    }
+
+    /// Cancels an open order as of the given time, as for `Book::cancel`
+    pub fn cancel_at(
+        &mut self,
+        order_id: OrderId,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Order>, BookError> {
+        let (side, price, index) = match self.locate(order_id) {
+            Some(t) => t,
+            None => return Ok(self.cancel_stop(order_id, now)),
+        };
+
+        let order: Order = self.remove_at(side, price, index);
+        info!("Cancelled {}", order);
+        self.emit(BookEventKind::Cancelled(order.clone()), now);
+
+        self.update();
+        Ok(Some(order))
+    }
+
+    /// Returns the side, price level, and position within that level of the
+    /// order matching the provided order ID
+    ///
+    /// Only the order's own price level is searched, as its side and price are
+    /// looked up from the order index.
+    fn locate(&self, id: OrderId) -> Option<(OrderSide, U256, usize)> {
+        let (side, price) = *self.index.get(&id)?;
+        let level: &Level = match side {
+            OrderSide::Bid => self.bids.get(&price)?,
+            OrderSide::Ask => self.asks.get(&price)?,
+        };
+
+        level
+            .iter()
+            .position(|order| order.id == id)
+            .map(|index| (side, price, index))
+    }
+
+    /// Returns the side, trigger price, and position within that trigger price
+    /// of the untriggered stop order matching the provided order ID
+    fn locate_stop(&self, id: OrderId) -> Option<(OrderSide, U256, usize)> {
+        let (side, trigger_price) = *self.stop_index.get(&id)?;
+        let stops: &Level = match side {
+            OrderSide::Bid => self.buy_stops.get(&trigger_price)?,
+            OrderSide::Ask => self.sell_stops.get(&trigger_price)?,
+        };
+
+        stops
+            .iter()
+            .position(|order| order.id == id)
+            .map(|index| (side, trigger_price, index))
+    }
+
+    /// Cancels the untriggered stop order with the matching ID, if any
+    fn cancel_stop(
+        &mut self,
+        order_id: OrderId,
+        now: DateTime<Utc>,
+    ) -> Option<Order> {
+        let (side, trigger_price, index) = self.locate_stop(order_id)?;
+        let stops: &mut BTreeMap<U256, Level> = match side {
+            OrderSide::Bid => &mut self.buy_stops,
+            OrderSide::Ask => &mut self.sell_stops,
+        };
+        let level: &mut Level = stops.get_mut(&trigger_price)?;
+        let order: Order = level.remove(index)?;
+
+        if level.is_empty() {
+            stops.remove(&trigger_price);
         }
 
-        for (_, orders) in self.asks.iter_mut() {
-            for (index, order) in orders.iter_mut().enumerate() {
-                if order.id == order_id {
-                    info!("Cancelled {}", order.clone());
-                    orders.remove(index);
-                    return Ok(Some(Utc::now()));
-                }
+        self.forget_stop(&order);
+        info!("Cancelled stop {}", order);
+        self.emit(BookEventKind::Cancelled(order.clone()), now);
+        Some(order)
+    }
+
+    /// Removes the order at the given position from the book, also removing
+    /// its price level if it becomes empty
+    fn remove_at(
+        &mut self,
+        side: OrderSide,
+        price: U256,
+        index: usize,
+    ) -> Order {
+        let levels: &mut BTreeMap<U256, Level> = match side {
+            OrderSide::Bid => &mut self.bids,
+            OrderSide::Ask => &mut self.asks,
+        };
+        let level: &mut Level = levels.get_mut(&price).unwrap();
+        let order: Order = level.remove(index).unwrap();
+
+        if level.is_empty() {
+            levels.remove(&price);
+        }
+
+        self.forget(&order);
+        order
+    }
+
+    /// Amends the price and/or quantity of an open order in the order book
+    ///
+    /// `new_quantity` is the new total quantity of the order (i.e., including
+    /// any volume that has already been filled). Reducing the quantity of an
+    /// order retains its time priority, whereas changing its price or
+    /// increasing its quantity moves it to the back of the (new) price level.
+    ///
+    /// # Returns #
+    ///
+    /// Returns the amended order upon success.
+    ///
+    /// Returns `BookError::NoSuchOrder` if there is no such open order in the
+    /// book, `BookError::InvalidPrice` if the new price is zero,
+    /// `BookError::InvalidTickSize` or `BookError::InvalidLotSize` if the new
+    /// price or quantity isn't a multiple of the book's tick or lot size,
+    /// `BookError::BelowMinQuantity` if the new quantity is below the book's
+    /// minimum, `BookError::OrderTooLarge` if the amended order would exceed
+    /// the book's caps on the size of orders, and `BookError::InvalidAmendment`
+    /// if the new quantity does not exceed the amount already filled or the
+    /// new price would cross the book.
+    pub fn amend(
+        &mut self,
+        order_id: OrderId,
+        new_price: U256,
+        new_quantity: U256,
+    ) -> Result<Order, BookError> {
+        self.amend_at(order_id, new_price, new_quantity, Utc::now())
+    }
+
+    /// Amends an open order as of the given time, as for `Book::amend`
+    pub fn amend_at(
+        &mut self,
+        order_id: OrderId,
+        new_price: U256,
+        new_quantity: U256,
+        now: DateTime<Utc>,
+    ) -> Result<Order, BookError> {
+        let (side, price, index) = match self.locate(order_id) {
+            Some(t) => t,
+            None => return Err(BookError::NoSuchOrder),
+        };
+
+        self.check_size(new_price, new_quantity)?;
+        self.check_increments(Some(new_price), new_quantity)?;
+
+        if new_quantity < self.min_quantity {
+            return Err(BookError::BelowMinQuantity);
+        }
+
+        let level: &mut Level = match side {
+            OrderSide::Bid => self.bids.get_mut(&price).unwrap(),
+            OrderSide::Ask => self.asks.get_mut(&price).unwrap(),
+        };
+        let order: &Order = &level[index];
+
+        if order.remaining.is_zero() {
+            return Err(BookError::NoSuchOrder);
+        }
+
+        if new_price.is_zero() {
+            return Err(BookError::InvalidPrice);
+        }
+
+        let filled: U256 = order.quantity - order.remaining;
+
+        if new_quantity <= filled {
+            warn!(
+                "Rejecting amendment of {} to quantity {} ({} already filled)",
+                order, new_quantity, filled
+            );
+            return Err(BookError::InvalidAmendment);
+        }
+
+        /* reducing quantity at the same price retains time priority */
+        if new_price == price && new_quantity <= order.quantity {
+            let previous: U256 = order.remaining;
+            let amended: Order = level
+                .resize(index, new_quantity, new_quantity - filled)
+                .unwrap()
+                .clone();
+            info!("Amended {} in place", amended);
+
+            self.close(side, previous);
+            self.open(side, amended.remaining);
+            self.next_sequence();
+            self.emit(BookEventKind::Amended(amended.clone()), now);
+            self.update();
+            return Ok(amended);
+        }
+
+        /* otherwise, cancel and replace at the back of the new level */
+        let opposing_top: Option<U256> = match side {
+            OrderSide::Bid => self.top().1,
+            OrderSide::Ask => self.top().0,
+        };
+
+        if let Some(top) = opposing_top {
+            if Book::price_viable(top, new_price, side) {
+                warn!(
+                    "Rejecting amendment of {} to price {} as it would cross",
+                    order_id, new_price
+                );
+                return Err(BookError::InvalidAmendment);
             }
         }
 
-        Ok(None)
+        let mut order: Order = self.remove_at(side, price, index);
+        self.change_level(side, price);
+        order.price = new_price;
+        order.quantity = new_quantity;
+        order.remaining = new_quantity - filled;
+        info!("Amended {}, moving to back of level", order);
+
+        let amended: Order = order.clone();
+        self.add_order(order)?;
+        self.emit(BookEventKind::Amended(amended.clone()), now);
+        self.update();
+        Ok(amended)
+    }
+
+    /// Cancels every open order in the order book owned by the given trader
+    ///
+    /// # Returns #
+    ///
+    /// Returns the cancelled orders (which is empty if the trader has no open
+    /// orders in this book). The relative order of all remaining orders at
+    /// each price level is preserved.
+    pub fn cancel_all(&mut self, trader: Address) -> Vec<Order> {
+        self.cancel_all_at(trader, Utc::now())
+    }
+
+    /// Cancels every open order of the given trader as of the given time, as
+    /// for `Book::cancel_all`
+    pub fn cancel_all_at(
+        &mut self,
+        trader: Address,
+        now: DateTime<Utc>,
+    ) -> Vec<Order> {
+        let mut cancelled: Vec<Order> = vec![];
+
+        for orders in self.bids.values_mut().chain(self.asks.values_mut()) {
+            cancelled.extend(orders.take_where(|order| order.trader == trader));
+        }
+
+        for order in cancelled.iter() {
+            self.forget(order);
+            info!("Cancelled {}", order);
+            self.emit(BookEventKind::Cancelled(order.clone()), now);
+        }
+
+        self.bids.retain(|_price, orders| !orders.is_empty());
+        self.asks.retain(|_price, orders| !orders.is_empty());
+
+        let mut stops: Vec<Order> = vec![];
+
+        for orders in self
+            .buy_stops
+            .values_mut()
+            .chain(self.sell_stops.values_mut())
+        {
+            stops.extend(orders.take_where(|order| order.trader == trader));
+        }
+
+        for order in stops.iter() {
+            self.forget_stop(order);
+            info!("Cancelled stop {}", order);
+            self.emit(BookEventKind::Cancelled(order.clone()), now);
+        }
+
+        self.buy_stops.retain(|_price, orders| !orders.is_empty());
+        self.sell_stops.retain(|_price, orders| !orders.is_empty());
+        cancelled.extend(stops);
+
+        self.update();
+        cancelled
+    }
+
+    /// Removes every open order (including untriggered stop orders) that has
+    /// expired as of the given time
+    ///
+    /// Expired orders are otherwise only removed once matching reaches them,
+    /// so this keeps the depth of idle books truthful.
+    ///
+    /// # Returns #
+    ///
+    /// Returns the removed orders (which is empty if none have expired). The
+    /// relative order of all remaining orders at each price level is
+    /// preserved.
+    pub fn purge_expired(&mut self, now: DateTime<Utc>) -> Vec<Order> {
+        let expired: Vec<Order> = self
+            .bids
+            .values()
+            .chain(self.asks.values())
+            .chain(self.buy_stops.values())
+            .chain(self.sell_stops.values())
+            .flatten()
+            .filter(|order| order.expired(now))
+            .cloned()
+            .collect();
+
+        /* pruning removes them, along with reporting each one as expired */
+        if !expired.is_empty() {
+            self.prune(now);
+            self.update();
+        }
+
+        expired
     }
 
     /// Updates internal metadata of the order book
@@ -404,57 +3181,438 @@ impl Book {
     /// Should be called *after successful* mutation of order book state.
     #[allow(dead_code)]
     fn update(&mut self) {
-        self.prune();
-        self.depth = self.depth();
+        match self.top() {
+            (Some(best_bid), Some(best_ask)) => {
+                self.crossed = best_bid >= best_ask;
+                self.spread = best_ask.saturating_sub(best_bid);
+            }
+            _ => {
+                self.crossed = false;
+                self.spread = U256::zero();
+            }
+        }
+
+        /* a full scan of the book, so only done in debug builds */
+        if cfg!(debug_assertions) {
+            if let Err(violations) = self.validate() {
+                let violations: Vec<String> =
+                    violations.iter().map(ToString::to_string).collect();
+                panic!(
+                    "Book {:?} is corrupt: {}",
+                    self.market,
+                    violations.join("; ")
+                );
+            }
+        }
+
         info!("Updated book metadata");
     }
 }
 
+/// Represents one side of an order book in its external (API) format, as
+/// (price, orders) pairs of price levels in ascending order of price
+///
+/// A list (rather than a map keyed by decimal strings) is used so that the
+/// price levels keep their numeric order, as decimal strings of different
+/// lengths do not sort numerically.
+pub type ExternalLevels = Vec<(String, VecDeque<ExternalOrder>)>;
+
+/// Represents an order book in its external (API) format
+///
+/// The trade history of the book is not included (see `ExternalFill` and the
+/// trades endpoint), so a book converted from this format has no trades.
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct ExternalBook {
     pub market: String, /* the address of the Tracer market */
-    pub bids: BTreeMap<String, VecDeque<ExternalOrder>>, /* buy-side */
-    pub asks: BTreeMap<String, VecDeque<ExternalOrder>>, /* sell-side */
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<Object>))]
+    pub bids: ExternalLevels, /* buy-side */
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<Object>))]
+    pub asks: ExternalLevels, /* sell-side */
+    #[serde(default)]
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<Object>))]
+    pub buy_stops: ExternalLevels, /* by trigger price */
+    #[serde(default)]
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<Object>))]
+    pub sell_stops: ExternalLevels, /* by trigger price */
     pub ltp: String,    /* last traded price */
+    #[serde(default)]
+    pub last_trade: Option<ExternalLastTrade>, /* absent from older books */
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<u64>))]
     pub depth: (usize, usize), /* depth  */
     pub crossed: bool,  /* is book crossed? */
     pub spread: String, /* bid-ask spread */
+    #[serde(default)]
+    pub self_trade_policy: SelfTradePolicy, /* handling of self-matches */
+    #[serde(default)]
+    pub matching_algorithm: MatchingAlgorithm, /* allocation within levels */
+    #[serde(default = "default_external_increment")]
+    pub tick_size: String, /* minimum price increment */
+    #[serde(default = "default_external_increment")]
+    pub lot_size: String, /* minimum quantity increment */
+    #[serde(default = "default_external_min_quantity")]
+    pub min_quantity: String, /* minimum order quantity (zero for none) */
+    #[serde(default)]
+    pub max_quantity: Option<String>, /* maximum order quantity (if any) */
+    #[serde(default)]
+    pub max_notional: Option<String>, /* maximum order notional (if any) */
+    #[serde(default)]
+    pub max_orders_per_trader: usize, /* open order cap (zero for none) */
+    #[serde(default)]
+    pub maker_fee_bps: u32, /* fee charged to makers, in basis points */
+    #[serde(default)]
+    pub taker_fee_bps: u32, /* fee charged to takers, in basis points */
+    #[serde(default)]
+    pub sequence: u64, /* incremented on every mutation of the book */
+    #[serde(default)]
+    pub paused: bool, /* are new orders refused? */
+}
+
+fn default_external_increment() -> String {
+    default_increment().to_string()
+}
+
+fn default_external_min_quantity() -> String {
+    U256::zero().to_string()
 }
 
 impl From<Book> for ExternalBook {
     fn from(value: Book) -> Self {
         Self {
-            market: value.market.to_string(),
-            bids: value
-                .bids
-                .iter()
-                .map(|(price, orders)| {
-                    (
-                        price.to_string(),
-                        orders
-                            .iter()
-                            .map(|order| ExternalOrder::from(order.clone()))
-                            .collect(),
-                    )
-                })
-                .collect(),
-            asks: value
-                .asks
-                .iter()
-                .map(|(price, orders)| {
-                    (
-                        price.to_string(),
-                        orders
-                            .iter()
-                            .map(|order| ExternalOrder::from(order.clone()))
-                            .collect(),
-                    )
-                })
-                .collect(),
+            market: format!("{:?}", value.market),
+            bids: external_levels(&value.bids),
+            asks: external_levels(&value.asks),
+            buy_stops: external_levels(&value.buy_stops),
+            sell_stops: external_levels(&value.sell_stops),
             ltp: value.ltp.to_string(),
+            last_trade: value.last_trade.map(ExternalLastTrade::from),
             depth: value.depth,
             crossed: value.crossed,
             spread: value.spread.to_string(),
+            self_trade_policy: value.self_trade_policy,
+            matching_algorithm: value.matching_algorithm,
+            tick_size: value.tick_size.to_string(),
+            lot_size: value.lot_size.to_string(),
+            min_quantity: value.min_quantity.to_string(),
+            max_quantity: value.max_quantity.map(|t| t.to_string()),
+            max_notional: value.max_notional.map(|t| t.to_string()),
+            max_orders_per_trader: value.max_orders_per_trader,
+            maker_fee_bps: value.maker_fee_bps,
+            taker_fee_bps: value.taker_fee_bps,
+            sequence: value.sequence,
+            paused: value.paused,
+        }
+    }
+}
+
+/// Borrows a book to serialize it exactly as its `ExternalBook` would be,
+/// without cloning or converting any of its orders
+///
+/// Responses carrying an entire book are serialized from this while the book
+/// is borrowed, so that nothing the size of the book is allocated besides the
+/// response itself.
+pub struct BookSnapshot<'a>(pub &'a Book);
+
+/* borrows one side of a book, serializing it as its `ExternalLevels` */
+struct LevelsSnapshot<'a>(&'a BTreeMap<U256, Level>);
+
+/* borrows the orders of a price level */
+struct LevelSnapshot<'a>(&'a Level);
+
+impl Serialize for LevelSnapshot<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.0.iter().map(OrderSnapshot))
+    }
+}
+
+impl Serialize for LevelsSnapshot<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(
+            self.0.iter().map(|(price, orders)| {
+                (AsString(price), LevelSnapshot(orders))
+            }),
+        )
+    }
+}
+
+impl Serialize for BookSnapshot<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let book: &Book = self.0;
+        let mut state = serializer.serialize_struct("ExternalBook", 22)?;
+        state.serialize_field(
+            "market",
+            &AsString(Hex(book.market.as_bytes())),
+        )?;
+        state.serialize_field("bids", &LevelsSnapshot(&book.bids))?;
+        state.serialize_field("asks", &LevelsSnapshot(&book.asks))?;
+        state.serialize_field("buy_stops", &LevelsSnapshot(&book.buy_stops))?;
+        state
+            .serialize_field("sell_stops", &LevelsSnapshot(&book.sell_stops))?;
+        state.serialize_field("ltp", &AsString(book.ltp))?;
+        state.serialize_field(
+            "last_trade",
+            &book.last_trade.map(ExternalLastTrade::from),
+        )?;
+        state.serialize_field("depth", &book.depth)?;
+        state.serialize_field("crossed", &book.crossed)?;
+        state.serialize_field("spread", &AsString(book.spread))?;
+        state.serialize_field("self_trade_policy", &book.self_trade_policy)?;
+        state
+            .serialize_field("matching_algorithm", &book.matching_algorithm)?;
+        state.serialize_field("tick_size", &AsString(book.tick_size))?;
+        state.serialize_field("lot_size", &AsString(book.lot_size))?;
+        state.serialize_field("min_quantity", &AsString(book.min_quantity))?;
+        state.serialize_field(
+            "max_quantity",
+            &book.max_quantity.map(AsString),
+        )?;
+        state.serialize_field(
+            "max_notional",
+            &book.max_notional.map(AsString),
+        )?;
+        state.serialize_field(
+            "max_orders_per_trader",
+            &book.max_orders_per_trader,
+        )?;
+        state.serialize_field("maker_fee_bps", &book.maker_fee_bps)?;
+        state.serialize_field("taker_fee_bps", &book.taker_fee_bps)?;
+        state.serialize_field("sequence", &book.sequence)?;
+        state.serialize_field("paused", &book.paused)?;
+        state.end()
+    }
+}
+
+/// Converts one side of a book into its external representation
+fn external_levels(levels: &BTreeMap<U256, Level>) -> ExternalLevels {
+    levels
+        .iter()
+        .map(|(price, orders)| {
+            (
+                price.to_string(),
+                orders
+                    .iter()
+                    .map(|order| ExternalOrder::from(order.clone()))
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+/// Parses one side of an external book into its internal representation
+///
+/// Price levels may appear in any order, and repeated price levels are
+/// merged (in the order they appear) rather than overwriting one another.
+fn parse_external_levels(
+    levels: ExternalLevels,
+) -> Result<BTreeMap<U256, Level>, OrderParseError> {
+    let mut parsed: BTreeMap<U256, Level> = BTreeMap::new();
+
+    for (price, orders) in levels {
+        let orders: Vec<Order> = orders
+            .into_iter()
+            .map(Order::try_from)
+            .collect::<Result<Vec<Order>, OrderParseError>>(
+        )?;
+        let level: &mut Level =
+            parsed.entry(parse_decimal(&price, "price")?).or_default();
+
+        for order in orders {
+            level.push_back(order);
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Represents the reasons an order book cannot be restored from an external
+/// (or otherwise untrusted) source
+#[derive(Clone, Copy, Debug, Error, PartialEq, Eq)]
+pub enum BookRestoreError {
+    #[error("Malformed book: {0}")]
+    Malformed(OrderParseError),
+    #[error("Order {0:?} belongs to a different market")]
+    OrderMarketMismatch(OrderId),
+    #[error("Order {0:?} has more remaining than its quantity")]
+    RemainingExceedsQuantity(OrderId),
+    #[error("Order {0:?} is not keyed by its own side and price")]
+    LevelMismatch(OrderId),
+    #[error("Order {0:?} appears more than once")]
+    DuplicateOrder(OrderId),
+    #[error("Order {0:?} reuses an open client order ID of its trader")]
+    DuplicateClientOrderId(OrderId),
+    #[error("Book is crossed")]
+    Crossed,
+}
+
+impl From<OrderParseError> for BookRestoreError {
+    fn from(value: OrderParseError) -> Self {
+        BookRestoreError::Malformed(value)
+    }
+}
+
+/// Identifies a price level of an order book, for reporting
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PriceLevel {
+    pub side: OrderSide,
+    pub stop: bool, /* level of stop orders, keyed by trigger price */
+    pub price: U256,
+}
+
+impl Display for PriceLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.stop {
+            write!(f, "{} stop {}", self.side, self.price)
+        } else {
+            write!(f, "{} {}", self.side, self.price)
+        }
+    }
+}
+
+/// Represents the ways an order book can violate its invariants (see
+/// `Book::validate`)
+#[derive(Clone, Copy, Debug, Error, PartialEq, Eq)]
+pub enum InvariantViolation {
+    #[error("Order {id:?} at {level} belongs to a different market")]
+    WrongMarket { id: OrderId, level: PriceLevel },
+    #[error("Order {id:?} at {level} is not keyed by its own side and price")]
+    WrongLevel { id: OrderId, level: PriceLevel },
+    #[error("Order {id:?} at {level} has nothing remaining")]
+    NothingRemaining { id: OrderId, level: PriceLevel },
+    #[error(
+        "Order {id:?} at {level} is missing from (or misplaced in) the index"
+    )]
+    Unindexed { id: OrderId, level: PriceLevel },
+    #[error("Index holds order {0:?}, which is not in the book")]
+    StaleIndex(OrderId),
+    #[error("Price level {0} is empty")]
+    EmptyLevel(PriceLevel),
+    #[error(
+        "Total of {level} is {cached}, but its orders have {actual} remaining"
+    )]
+    TotalMismatch {
+        level: PriceLevel,
+        cached: U256,
+        actual: U256,
+    },
+    #[error("Depth is {cached:?}, but the book holds {actual:?} open orders")]
+    DepthMismatch {
+        cached: (usize, usize),
+        actual: (usize, usize),
+    },
+    #[error("Volume is {cached:?}, but the book holds {actual:?} remaining")]
+    VolumeMismatch {
+        cached: (U256, U256),
+        actual: (U256, U256),
+    },
+    #[error("Book is crossed at {best_bid}/{best_ask}, but not marked so")]
+    Crossed { best_bid: U256, best_ask: U256 },
+}
+
+/// Parses an external book into its internal representation without any
+/// validation of its contents
+fn parse_external_book(value: ExternalBook) -> Result<Book, OrderParseError> {
+    Ok(Book {
+        market: parse_address(&value.market, "market")?,
+        bids: parse_external_levels(value.bids)?,
+        asks: parse_external_levels(value.asks)?,
+        buy_stops: parse_external_levels(value.buy_stops)?,
+        sell_stops: parse_external_levels(value.sell_stops)?,
+        ltp: parse_decimal(&value.ltp, "ltp")?,
+        last_trade: value.last_trade.map(LastTrade::try_from).transpose()?,
+        depth: value.depth,
+        crossed: value.crossed,
+        spread: parse_decimal(&value.spread, "spread")?,
+        self_trade_policy: value.self_trade_policy,
+        matching_algorithm: value.matching_algorithm,
+        tick_size: parse_decimal(&value.tick_size, "tick_size")?,
+        lot_size: parse_decimal(&value.lot_size, "lot_size")?,
+        min_quantity: parse_decimal(&value.min_quantity, "min_quantity")?,
+        max_quantity: value
+            .max_quantity
+            .map(|t| parse_decimal(&t, "max_quantity"))
+            .transpose()?,
+        max_notional: value
+            .max_notional
+            .map(|t| parse_decimal(&t, "max_notional"))
+            .transpose()?,
+        max_orders_per_trader: value.max_orders_per_trader,
+        maker_fee_bps: value.maker_fee_bps,
+        taker_fee_bps: value.taker_fee_bps,
+        sequence: value.sequence,
+        trades: VecDeque::new(),
+        trade_stats: TradeStats::default(),
+        history: OrderHistory::default(),
+        paused: value.paused,
+        index: HashMap::new(),
+        stop_index: HashMap::new(),
+        open_orders: HashMap::new(),
+        client_orders: HashMap::new(),
+        events: vec![],
+        changed_levels: vec![],
+        deltas: VecDeque::new(),
+        delta_sequence: value.sequence,
+        volume: (U256::zero(), U256::zero()),
+    })
+}
+
+impl TryFrom<ExternalBook> for Book {
+    type Error = BookRestoreError;
+
+    fn try_from(value: ExternalBook) -> Result<Self, Self::Error> {
+        Book::from_external_at(value, Utc::now())
+    }
+}
+
+/// Represents a fill in its external (API) format
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ExternalFill {
+    pub trade_id: u64,
+    pub maker_id: String,
+    pub taker_id: String,
+    pub maker_address: String,
+    pub taker_address: String,
+    pub price: String,
+    pub quantity: String,
+    pub timestamp: String,
+    #[serde(default)]
+    pub maker_client_order_id: Option<String>,
+    #[serde(default)]
+    pub taker_client_order_id: Option<String>,
+    #[serde(default = "default_external_fee")]
+    pub maker_fee: String,
+    #[serde(default = "default_external_fee")]
+    pub taker_fee: String,
+}
+
+fn default_external_fee() -> String {
+    U256::zero().to_string()
+}
+
+impl From<Fill> for ExternalFill {
+    fn from(value: Fill) -> Self {
+        Self {
+            trade_id: value.trade_id,
+            maker_id: format!("{:?}", value.maker_id),
+            taker_id: format!("{:?}", value.taker_id),
+            maker_address: format!("{:?}", value.maker_address),
+            taker_address: format!("{:?}", value.taker_address),
+            price: value.price.to_string(),
+            quantity: value.quantity.to_string(),
+            timestamp: value.timestamp.timestamp().to_string(),
+            maker_client_order_id: value.maker_client_order_id,
+            taker_client_order_id: value.taker_client_order_id,
+            maker_fee: value.maker_fee.to_string(),
+            taker_fee: value.taker_fee.to_string(),
         }
     }
 }
@@ -1,12 +1,45 @@
 use std::collections::{BTreeMap, VecDeque};
+use std::convert::TryFrom;
 
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, Duration, TimeZone, Utc};
 use ethereum_types::{Address, U256};
 
-use crate::book::{Book, BookError, OrderStatus};
-use crate::order::{Order, OrderSide};
+use crate::book::{
+    Book, BookDiff, BookError, BookRestoreError, BookSnapshot, ExternalBook,
+    ExternalFill, ExternalLastTrade, ExternalLevels, Fill, InvariantViolation,
+    LastTrade, Level, LevelDiff, MatchResult, MatchingAlgorithm,
+    OrderSizeLimit, OrderStatus, PriceLevel, SelfTradePolicy, TraderExposure,
+    BPS_PER_UNIT, MAX_DELTAS, MAX_TRADES,
+};
+use crate::event::{
+    BookDelta, BookEvent, BookEventKind, ExternalBookEvent, LevelChange,
+    SnapshotEvent,
+};
+use crate::executioner::Executioner;
+use crate::history::{OrderEvent, OrderEventKind};
+use crate::order::{
+    ExternalOrder, Order, OrderId, OrderParseError, OrderSide, OrderType,
+    TimeInForce,
+};
+use crate::rpc::RpcClient;
+use crate::stats::{Stats, TradeStats};
+use crate::test_util::{deep_book, far_future};
+
+/* an executioner that nothing listens at, so forwarded matches go nowhere */
+pub fn test_executioner() -> Executioner {
+    Executioner::new(
+        RpcClient::new(
+            "http://localhost:3000",
+            std::time::Duration::from_secs(1),
+            std::time::Duration::from_secs(1),
+            1,
+        )
+        .unwrap(),
+    )
+}
 
-pub const TEST_RPC_ADDRESS: &str = "http://localhost:3000";
+/* a whole unit of a fixed-point price or amount */
+const ONE: u64 = 1_000_000_000_000_000_000;
 
 async fn submit_orders(
     market: Address,
@@ -22,7 +55,7 @@ async fn submit_orders(
                 *side,
                 (*price).into(),
                 (*qty).into(),
-                Utc::now(),
+                far_future(),
                 Utc::now(),
                 vec![],
             )
@@ -33,11 +66,12 @@ async fn submit_orders(
 
     /* apply each order to the book (sadly we can't `map` here due to our blocking requirement) */
     for order in orders {
-        book.submit(order.clone(), TEST_RPC_ADDRESS.to_string())
+        book.submit(order.clone(), test_executioner())
             .await
             .expect("Failed to submit order to book");
     }
 
+    assert_eq!(book.validate(), Ok(()));
     book
 }
 
@@ -45,8 +79,7 @@ async fn setup() -> Book {
     let market: Address = Address::zero();
 
     /* placeholders for trader addresses (saves us writing real Ethereum addresses) */
-    let traders: Vec<Address> =
-        (0..10).map(|x| Address::from_low_u64_be(x)).collect();
+    let traders: Vec<Address> = (0..10).map(Address::from_low_u64_be).collect();
 
     let asks: Vec<(Address, OrderSide, u64, u64)> = vec![
         (traders[0], OrderSide::Ask, 100, 10),
@@ -96,20 +129,20 @@ pub async fn test_simple_buy() {
         Address::from_low_u64_be(3),
         Address::zero(),
         OrderSide::Bid,
-        U256::from_dec_str(&"96").unwrap(),
-        U256::from_dec_str(&"5").unwrap(),
-        Utc::now(),
+        U256::from_dec_str("96").unwrap(),
+        U256::from_dec_str("5").unwrap(),
+        far_future(),
         Utc::now(),
         vec![],
     );
 
-    let submit_res: Result<OrderStatus, BookError> =
-        book.submit(bid, TEST_RPC_ADDRESS.to_string()).await;
+    let submit_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
 
     let (bid_length, ask_length) = book.depth();
 
     assert!(
-        submit_res.is_ok() || submit_res.contains_err(&BookError::Web3Error)
+        submit_res.is_ok() || matches!(submit_res, Err(BookError::Web3Error))
     );
 
     assert_eq!(bid_length, 5);
@@ -125,20 +158,20 @@ pub async fn test_simple_buy_partially_filled() {
         Address::from_low_u64_be(3),
         market_address,
         OrderSide::Bid,
-        U256::from_dec_str(&"96").unwrap(),
-        U256::from_dec_str(&"3").unwrap(),
-        Utc::now(),
+        U256::from_dec_str("96").unwrap(),
+        U256::from_dec_str("3").unwrap(),
+        far_future(),
         Utc::now(),
         vec![],
     );
 
-    let submit_res: Result<OrderStatus, BookError> =
-        book.submit(bid, TEST_RPC_ADDRESS.to_string()).await;
+    let submit_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
 
     let (bid_length, ask_length) = book.depth();
 
     assert!(
-        submit_res.is_ok() || submit_res.contains_err(&BookError::Web3Error)
+        submit_res.is_ok() || matches!(submit_res, Err(BookError::Web3Error))
     );
 
     // Ensure the depths are correct
@@ -153,20 +186,20 @@ pub async fn test_simple_sell() {
         Address::from_low_u64_be(3),
         Address::zero(),
         OrderSide::Ask,
-        U256::from_dec_str(&"95").unwrap(),
-        U256::from_dec_str(&"10").unwrap(),
-        Utc::now(),
+        U256::from_dec_str("95").unwrap(),
+        U256::from_dec_str("10").unwrap(),
+        far_future(),
         Utc::now(),
         vec![],
     );
 
-    let submit_res: Result<OrderStatus, BookError> =
-        book.submit(ask, TEST_RPC_ADDRESS.to_string()).await;
+    let submit_res: Result<MatchResult, BookError> =
+        book.submit(ask, test_executioner()).await;
 
     let (bid_length, ask_length) = book.depth();
 
     assert!(
-        submit_res.is_ok() || submit_res.contains_err(&BookError::Web3Error)
+        submit_res.is_ok() || matches!(submit_res, Err(BookError::Web3Error))
     );
 
     assert_eq!(bid_length, 4);
@@ -182,20 +215,20 @@ pub async fn test_simple_sell_partially_filled() {
         Address::from_low_u64_be(3),
         market_address,
         OrderSide::Ask,
-        U256::from_dec_str(&"95").unwrap(),
-        U256::from_dec_str(&"1").unwrap(),
-        Utc::now(),
+        U256::from_dec_str("95").unwrap(),
+        U256::from_dec_str("1").unwrap(),
+        far_future(),
         Utc::now(),
         vec![],
     );
 
-    let submit_res: Result<OrderStatus, BookError> =
-        book.submit(bid, TEST_RPC_ADDRESS.to_string()).await;
+    let submit_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
 
     let (bid_length, ask_length) = book.depth();
 
     assert!(
-        submit_res.is_ok() || submit_res.contains_err(&BookError::Web3Error)
+        submit_res.is_ok() || matches!(submit_res, Err(BookError::Web3Error))
     );
 
     // Ensure the depths are correct
@@ -211,20 +244,20 @@ pub async fn test_deep_buy() {
         Address::from_low_u64_be(10),
         market_address,
         OrderSide::Bid,
-        U256::from_dec_str(&"99").unwrap(),
-        U256::from_dec_str(&"42").unwrap(),
-        Utc::now(),
+        U256::from_dec_str("99").unwrap(),
+        U256::from_dec_str("42").unwrap(),
+        far_future(),
         Utc::now(),
         vec![],
     );
 
-    let submit_res: Result<OrderStatus, BookError> =
-        book.submit(bid, TEST_RPC_ADDRESS.to_string()).await;
+    let submit_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
 
     let (bid_length, ask_length) = book.depth();
 
     assert!(
-        submit_res.is_ok() || submit_res.contains_err(&BookError::Web3Error)
+        submit_res.is_ok() || matches!(submit_res, Err(BookError::Web3Error))
     );
 
     // Ensure the depths are correct
@@ -243,17 +276,20 @@ pub async fn test_no_self_matching() {
         OrderSide::Bid,
         97.into(),
         15.into(),
-        Utc::now(),
+        far_future(),
         Utc::now(),
         vec![],
     );
 
-    let actual_res: Result<OrderStatus, BookError> =
-        book.submit(bid, TEST_RPC_ADDRESS.to_string()).await;
+    let actual_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
 
     let (bid_depth, ask_depth) = book.depth();
 
-    assert_eq!(actual_res, Ok(OrderStatus::FullMatch));
+    assert_eq!(
+        actual_res.map(|r| r.order_status),
+        Ok(OrderStatus::FullMatch)
+    );
     assert_eq!(bid_depth, 5);
     assert_eq!(ask_depth, 4);
 }
@@ -269,7 +305,7 @@ pub async fn test_no_self_matching_when_last_order() {
         OrderSide::Ask,
         97.into(),
         15.into(),
-        Utc::now(),
+        far_future(),
         Utc::now(),
         vec![],
     );
@@ -280,21 +316,22 @@ pub async fn test_no_self_matching_when_last_order() {
         OrderSide::Bid,
         97.into(),
         15.into(),
-        Utc::now(),
+        far_future(),
         Utc::now(),
         vec![],
     );
 
-    book.submit(ask, TEST_RPC_ADDRESS.to_string())
-        .await
-        .unwrap();
+    book.submit(ask, test_executioner()).await.unwrap();
 
-    let actual_res: Result<OrderStatus, BookError> =
-        book.submit(bid, TEST_RPC_ADDRESS.to_string()).await;
+    let actual_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
 
     let (bid_depth, ask_depth) = book.depth();
 
-    assert_eq!(actual_res, Ok(OrderStatus::PartialMatch));
+    assert_eq!(
+        actual_res.map(|r| r.order_status),
+        Ok(OrderStatus::PartialMatch)
+    );
     assert_eq!(bid_depth, 1);
     assert_eq!(ask_depth, 1);
 }
@@ -307,20 +344,20 @@ pub async fn test_deep_buy_with_limit() {
         Address::from_low_u64_be(10),
         market_address,
         OrderSide::Bid,
-        U256::from_dec_str(&"97").unwrap(),
-        U256::from_dec_str(&"42").unwrap(),
-        Utc::now(),
+        U256::from_dec_str("97").unwrap(),
+        U256::from_dec_str("42").unwrap(),
+        far_future(),
         Utc::now(),
         vec![],
     );
 
-    let submit_res: Result<OrderStatus, BookError> =
-        book.submit(bid, TEST_RPC_ADDRESS.to_string()).await;
+    let submit_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
 
     let (bid_length, ask_length) = book.depth();
 
     assert!(
-        submit_res.is_ok() || submit_res.contains_err(&BookError::Web3Error)
+        submit_res.is_ok() || matches!(submit_res, Err(BookError::Web3Error))
     );
 
     assert_eq!(bid_length, 6); // There should be one more bid with 22 units at 97.
@@ -335,20 +372,20 @@ pub async fn test_deep_sell() {
         Address::from_low_u64_be(10),
         market_address,
         OrderSide::Ask,
-        U256::from_dec_str(&"94").unwrap(),
-        U256::from_dec_str(&"20").unwrap(),
-        Utc::now(),
+        U256::from_dec_str("94").unwrap(),
+        U256::from_dec_str("20").unwrap(),
+        far_future(),
         Utc::now(),
         vec![],
     );
 
-    let submit_res: Result<OrderStatus, BookError> =
-        book.submit(ask, TEST_RPC_ADDRESS.to_string()).await;
+    let submit_res: Result<MatchResult, BookError> =
+        book.submit(ask, test_executioner()).await;
 
     let (bid_length, ask_length) = book.depth();
 
     assert!(
-        submit_res.is_ok() || submit_res.contains_err(&BookError::Web3Error)
+        submit_res.is_ok() || matches!(submit_res, Err(BookError::Web3Error))
     );
 
     // Ensure the depths are correct
@@ -364,20 +401,20 @@ pub async fn test_deep_sell_with_limit() {
         Address::from_low_u64_be(33),
         market_address,
         OrderSide::Ask,
-        U256::from_dec_str(&"94").unwrap(),
-        U256::from_dec_str(&"35").unwrap(),
-        Utc::now(),
+        U256::from_dec_str("94").unwrap(),
+        U256::from_dec_str("35").unwrap(),
+        far_future(),
         Utc::now(),
         vec![],
     );
 
-    let submit_res: Result<OrderStatus, BookError> =
-        book.submit(ask, TEST_RPC_ADDRESS.to_string()).await;
+    let submit_res: Result<MatchResult, BookError> =
+        book.submit(ask, test_executioner()).await;
 
     let (bid_length, ask_length) = book.depth();
 
     assert!(
-        submit_res.is_ok() || submit_res.contains_err(&BookError::Web3Error)
+        submit_res.is_ok() || matches!(submit_res, Err(BookError::Web3Error))
     );
 
     assert_eq!(bid_length, 3);
@@ -389,10 +426,7 @@ pub async fn test_partial_matching_mutability() {
     /* need at least three for this test */
     let traders: Vec<Address> =
         vec![Address::random(), Address::random(), Address::random()];
-    let the_far_future: DateTime<Utc> = DateTime::<Utc>::from_utc(
-        NaiveDateTime::from_timestamp(1699025703, 0),
-        Utc,
-    );
+    let the_far_future: DateTime<Utc> = far_future();
     let market: Address = Address::zero();
 
     let orders: Vec<Order> = vec![
@@ -435,7 +469,7 @@ pub async fn test_partial_matching_mutability() {
 
     for order in orders.iter() {
         actual_book
-            .submit(order.clone(), TEST_RPC_ADDRESS.to_string())
+            .submit(order.clone(), test_executioner())
             .await
             .unwrap();
     }
@@ -443,8 +477,8 @@ pub async fn test_partial_matching_mutability() {
     let expected_book: Book = Book {
         market,
         bids: {
-            let mut side: BTreeMap<U256, VecDeque<Order>> = BTreeMap::new();
-            let mut level: VecDeque<Order> = VecDeque::new();
+            let mut side: BTreeMap<U256, Level> = BTreeMap::new();
+            let mut level: Level = Level::new();
             let mut order: Order = orders[2].clone();
             order.remaining =
                 U256::from_dec_str("0800000000000000000").unwrap();
@@ -456,8 +490,3269 @@ pub async fn test_partial_matching_mutability() {
         ltp: orders[0].price, // trade price is whichever order came first eg make
         depth: (1, 0),
         crossed: false,
-        spread: U256::from_dec_str("0").unwrap(), // todo check how this is calculated
+        spread: U256::from_dec_str("0").unwrap(), // no asks, so no spread
+        sequence: 4, // ask added, two fills, then bid remainder added
+        ..Book::new(market)
     };
 
     assert_eq!(actual_book, expected_book);
+    assert_eq!(actual_book.validate(), Ok(()));
+}
+
+#[tokio::test]
+pub async fn test_immediate_or_cancel_drops_remainder() {
+    let mut book = setup().await;
+    let bid = Order::new(
+        Address::from_low_u64_be(10),
+        Address::zero(),
+        OrderSide::Bid,
+        97.into(),
+        30.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    )
+    .with_time_in_force(TimeInForce::ImmediateOrCancel);
+
+    let actual_res: Result<MatchResult, BookError> =
+        book.submit(bid.clone(), test_executioner()).await;
+
+    assert_eq!(
+        actual_res.map(|r| r.order_status),
+        Ok(OrderStatus::PartialMatch)
+    );
+    assert_eq!(book.depth(), (5, 3));
+    assert!(book.order(bid.id).is_none());
+}
+
+#[tokio::test]
+pub async fn test_immediate_or_cancel_without_cross_is_killed() {
+    let mut book = setup().await;
+    let bid = Order::new(
+        Address::from_low_u64_be(10),
+        Address::zero(),
+        OrderSide::Bid,
+        90.into(),
+        30.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    )
+    .with_time_in_force(TimeInForce::ImmediateOrCancel);
+
+    let actual_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
+
+    assert_eq!(actual_res.map(|r| r.order_status), Ok(OrderStatus::Killed));
+    assert_eq!(book.depth(), (5, 5));
+}
+
+#[tokio::test]
+pub async fn test_fill_or_kill_insufficient_liquidity_is_killed() {
+    let mut book = setup().await;
+    let expected_book: Book = book.clone();
+    let bid = Order::new(
+        Address::from_low_u64_be(10),
+        Address::zero(),
+        OrderSide::Bid,
+        97.into(),
+        30.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    )
+    .with_time_in_force(TimeInForce::FillOrKill);
+
+    let actual_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
+
+    assert_eq!(actual_res.map(|r| r.order_status), Ok(OrderStatus::Killed));
+    assert_eq!(book, expected_book);
+}
+
+#[tokio::test]
+pub async fn test_fill_or_kill_full_match() {
+    let mut book = setup().await;
+    let bid = Order::new(
+        Address::from_low_u64_be(10),
+        Address::zero(),
+        OrderSide::Bid,
+        97.into(),
+        20.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    )
+    .with_time_in_force(TimeInForce::FillOrKill);
+
+    let actual_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
+
+    assert_eq!(
+        actual_res.map(|r| r.order_status),
+        Ok(OrderStatus::FullMatch)
+    );
+    assert_eq!(book.depth(), (5, 3));
+}
+
+#[tokio::test]
+pub async fn test_fill_or_kill_excludes_own_orders() {
+    let mut book = setup().await;
+    let expected_book: Book = book.clone();
+    let bid = Order::new(
+        Address::from_low_u64_be(3), /* owns the 15 units resting at 97 */
+        Address::zero(),
+        OrderSide::Bid,
+        97.into(),
+        20.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    )
+    .with_time_in_force(TimeInForce::FillOrKill);
+
+    let actual_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
+
+    assert_eq!(actual_res.map(|r| r.order_status), Ok(OrderStatus::Killed));
+    assert_eq!(book, expected_book);
+}
+
+#[tokio::test]
+pub async fn test_expired_order_rejected() {
+    let mut book = setup().await;
+    let expected_book: Book = book.clone();
+    let bid = Order::new(
+        Address::from_low_u64_be(10),
+        Address::zero(),
+        OrderSide::Bid,
+        97.into(),
+        20.into(),
+        Utc::now() - Duration::seconds(1),
+        Utc::now(),
+        vec![],
+    );
+
+    let actual_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
+
+    assert_eq!(actual_res, Err(BookError::OrderExpired));
+    assert_eq!(book, expected_book);
+}
+
+#[tokio::test]
+pub async fn test_expired_resting_order_not_matched() {
+    let market: Address = Address::zero();
+    let mut book = Book::new(market);
+
+    let ask: Order = Order::new(
+        Address::from_low_u64_be(1),
+        market,
+        OrderSide::Ask,
+        97.into(),
+        15.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+    book.submit(ask.clone(), test_executioner()).await.unwrap();
+
+    /* let the resting ask lapse */
+    book.order_mut(ask.id).unwrap().expiration =
+        Utc::now() - Duration::seconds(1);
+
+    let bid: Order = Order::new(
+        Address::from_low_u64_be(2),
+        market,
+        OrderSide::Bid,
+        97.into(),
+        15.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+
+    let actual_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
+
+    assert_eq!(
+        actual_res.map(|r| r.order_status),
+        Ok(OrderStatus::PartialMatch)
+    );
+    assert_eq!(book.depth(), (1, 0));
+    assert!(book.order(ask.id).is_none());
+    assert!(book.asks.is_empty());
+}
+
+#[tokio::test]
+pub async fn test_self_trade_cancel_resting() {
+    let market: Address = Address::zero();
+    let mut book = Book::new(market)
+        .with_self_trade_policy(SelfTradePolicy::CancelResting);
+
+    let ask: Order = Order::new(
+        Address::from_low_u64_be(1),
+        market,
+        OrderSide::Ask,
+        97.into(),
+        15.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+    let other_ask: Order = Order::new(
+        Address::from_low_u64_be(2),
+        market,
+        OrderSide::Ask,
+        97.into(),
+        10.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+    let bid: Order = Order::new(
+        Address::from_low_u64_be(1), /* previously placed an ask */
+        market,
+        OrderSide::Bid,
+        97.into(),
+        10.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+
+    book.submit(ask.clone(), test_executioner()).await.unwrap();
+    book.submit(other_ask.clone(), test_executioner())
+        .await
+        .unwrap();
+
+    let actual_res: MatchResult =
+        book.submit(bid, test_executioner()).await.unwrap();
+
+    assert_eq!(actual_res.order_status, OrderStatus::FullMatch);
+    assert_eq!(actual_res.cancelled, vec![ask.id]);
+    assert!(book.order(ask.id).is_none());
+    assert!(book.order(other_ask.id).is_none());
+    assert_eq!(book.depth(), (0, 0));
+}
+
+#[tokio::test]
+pub async fn test_self_trade_cancel_incoming() {
+    let market: Address = Address::zero();
+    let mut book = Book::new(market)
+        .with_self_trade_policy(SelfTradePolicy::CancelIncoming);
+
+    let ask: Order = Order::new(
+        Address::from_low_u64_be(1),
+        market,
+        OrderSide::Ask,
+        97.into(),
+        15.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+    let bid: Order = Order::new(
+        Address::from_low_u64_be(1), /* previously placed an ask */
+        market,
+        OrderSide::Bid,
+        97.into(),
+        10.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+
+    book.submit(ask.clone(), test_executioner()).await.unwrap();
+
+    let actual_res: MatchResult =
+        book.submit(bid.clone(), test_executioner()).await.unwrap();
+
+    assert_eq!(actual_res.order_status, OrderStatus::Killed);
+    assert_eq!(actual_res.cancelled, vec![bid.id]);
+    assert!(book.order(ask.id).is_some());
+    assert_eq!(book.depth(), (0, 1));
+}
+
+/* fill timestamps differ between runs, so pin them before comparing */
+fn without_timestamps(mut result: MatchResult) -> MatchResult {
+    let epoch: DateTime<Utc> = DateTime::from(std::time::UNIX_EPOCH);
+    for fill in result.fills.iter_mut() {
+        fill.timestamp = epoch;
+    }
+    result
+}
+
+#[tokio::test]
+pub async fn test_simulate_does_not_mutate() {
+    let book = setup().await;
+    let expected_book: Book = book.clone();
+    let bid = Order::new(
+        Address::from_low_u64_be(10),
+        Address::zero(),
+        OrderSide::Bid,
+        98.into(),
+        45.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+
+    let simulated: MatchResult = book.simulate(&bid);
+
+    assert_eq!(book, expected_book);
+    assert_eq!(book.ltp(), expected_book.ltp());
+
+    let mut live_book: Book = book.clone();
+    let submitted: MatchResult =
+        live_book.submit(bid, test_executioner()).await.unwrap();
+
+    assert_eq!(simulated.order_status, OrderStatus::FullMatch);
+    assert_eq!(simulated.fills.len(), 3);
+    assert_eq!(without_timestamps(simulated), without_timestamps(submitted));
+}
+
+#[tokio::test]
+pub async fn test_simulate_honours_self_trade_policy() {
+    for policy in [
+        SelfTradePolicy::Skip,
+        SelfTradePolicy::CancelResting,
+        SelfTradePolicy::CancelIncoming,
+    ]
+    .iter()
+    .copied()
+    {
+        let book = setup().await.with_self_trade_policy(policy);
+        let expected_book: Book = book.clone();
+        let bid = Order::new(
+            Address::from_low_u64_be(4), /* owns the 5 units resting at 96 */
+            Address::zero(),
+            OrderSide::Bid,
+            97.into(),
+            10.into(),
+            far_future(),
+            Utc::now(),
+            vec![],
+        );
+
+        let simulated: MatchResult = book.simulate(&bid);
+        assert_eq!(book, expected_book);
+
+        let mut live_book: Book = book.clone();
+        let submitted: MatchResult =
+            live_book.submit(bid, test_executioner()).await.unwrap();
+
+        assert_eq!(
+            without_timestamps(simulated),
+            without_timestamps(submitted)
+        );
+    }
+}
+
+#[tokio::test]
+pub async fn test_quote_outcomes() {
+    let book = setup().await;
+    let expected_book: Book = book.clone();
+    let now: DateTime<Utc> = Utc::now();
+    let bid = |price: u64| {
+        Order::new(
+            Address::from_low_u64_be(10),
+            Address::zero(),
+            OrderSide::Bid,
+            price.into(),
+            5.into(),
+            far_future(),
+            now,
+            vec![],
+        )
+    };
+
+    /* orders are quoted as they would be matched... */
+    assert_eq!(
+        book.quote_at(&bid(90), now).order_status,
+        OrderStatus::Placed
+    );
+    assert_eq!(
+        book.quote_at(&bid(96), now).order_status,
+        OrderStatus::FullMatch
+    );
+
+    /* ...stop orders as awaiting their trigger... */
+    let stop: Order = bid(100).with_trigger(OrderType::Stop, U256::from(100));
+    assert_eq!(book.quote_at(&stop, now).order_status, OrderStatus::Pending);
+
+    /* ...and orders the book would refuse as rejected, with the reason */
+    let mut expired: Order = bid(96);
+    expired.expiration = now;
+    let rejected: MatchResult = book.quote_at(&expired, now);
+    assert_eq!(
+        rejected.order_status,
+        OrderStatus::Rejected {
+            reason: BookError::OrderExpired
+        }
+    );
+    assert!(rejected.fills.is_empty());
+    assert_eq!(book, expected_book);
+}
+
+#[test]
+pub fn test_order_status_names() {
+    /* placed orders keep the name they have always been reported by */
+    assert_eq!(OrderStatus::Placed.to_string(), "Add");
+    assert_eq!(
+        serde_json::to_string(&OrderStatus::Placed).unwrap(),
+        "\"Add\""
+    );
+    assert_eq!(OrderStatus::PartialMatch.to_string(), "PartialMatch");
+    assert_eq!(
+        OrderStatus::Rejected {
+            reason: BookError::DuplicateOrder
+        }
+        .to_string(),
+        "Rejected"
+    );
+}
+
+/* a pro-rata book with asks at 100 from each of the given (trader,
+ * quantity) pairs, in order of arrival */
+async fn pro_rata_book(makers: Vec<(u64, u64)>) -> Book {
+    submit_orders(
+        Address::zero(),
+        makers
+            .into_iter()
+            .map(|(trader, qty)| {
+                (Address::from_low_u64_be(trader), OrderSide::Ask, 100, qty)
+            })
+            .collect(),
+    )
+    .await
+    .with_matching_algorithm(MatchingAlgorithm::ProRata)
+}
+
+fn pro_rata_taker(trader: u64, qty: u64) -> Order {
+    pro_rata_taker_at(trader, 100, qty)
+}
+
+fn pro_rata_taker_at(trader: u64, price: u64, qty: u64) -> Order {
+    Order::new(
+        Address::from_low_u64_be(trader),
+        Address::zero(),
+        OrderSide::Bid,
+        price.into(),
+        qty.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    )
+}
+
+/* (maker, quantity) of each fill, in the order the fills were made */
+fn fill_quantities(result: &MatchResult) -> Vec<(Address, U256)> {
+    result
+        .fills
+        .iter()
+        .map(|fill| (fill.maker_address, fill.quantity))
+        .collect()
+}
+
+fn level_remaining(book: &Book, price: u64) -> Vec<U256> {
+    book.asks
+        .get(&U256::from(price))
+        .map(|level| level.iter().map(|order| order.remaining).collect())
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+pub async fn test_pro_rata_rounding() {
+    let mut book: Book = pro_rata_book(vec![(1, 6), (2, 4), (3, 3)]).await;
+    let taker: Order = pro_rata_taker(4, 7);
+
+    let simulated: MatchResult = book.simulate(&taker);
+    let result: MatchResult =
+        book.submit(taker, test_executioner()).await.unwrap();
+
+    /* shares of 42/13, 28/13, and 21/13 round down to 3, 2, and 1, and the
+     * unit of dust goes to the largest remainder (the smallest maker's) */
+    assert_eq!(result.order_status, OrderStatus::FullMatch);
+    assert_eq!(
+        fill_quantities(&result),
+        vec![
+            (Address::from_low_u64_be(1), U256::from(3)),
+            (Address::from_low_u64_be(2), U256::from(2)),
+            (Address::from_low_u64_be(3), U256::from(2)),
+        ]
+    );
+    assert_eq!(
+        level_remaining(&book, 100),
+        vec![U256::from(3), U256::from(2), U256::from(1)]
+    );
+    assert_eq!(book.depth(), (0, 3));
+    assert_eq!(without_timestamps(simulated), without_timestamps(result));
+
+    /* equal remainders go to the earliest order */
+    let mut book: Book = pro_rata_book(vec![(1, 10), (2, 10), (3, 5)]).await;
+    let result: MatchResult = book
+        .submit(pro_rata_taker(4, 11), test_executioner())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        fill_quantities(&result),
+        vec![
+            (Address::from_low_u64_be(1), U256::from(5)),
+            (Address::from_low_u64_be(2), U256::from(4)),
+            (Address::from_low_u64_be(3), U256::from(2)),
+        ]
+    );
+}
+
+#[tokio::test]
+pub async fn test_pro_rata_sweeps_levels() {
+    let mut book: Book = pro_rata_book(vec![(1, 6), (2, 4)]).await;
+    book.submit(
+        Order::new(
+            Address::from_low_u64_be(3),
+            Address::zero(),
+            OrderSide::Ask,
+            101.into(),
+            10.into(),
+            far_future(),
+            Utc::now(),
+            vec![],
+        ),
+        test_executioner(),
+    )
+    .await
+    .unwrap();
+
+    let taker: Order = pro_rata_taker_at(4, 101, 15);
+    let result: MatchResult =
+        book.submit(taker, test_executioner()).await.unwrap();
+
+    /* the first level is consumed whole, and the rest taken from the next */
+    assert_eq!(result.order_status, OrderStatus::FullMatch);
+    assert_eq!(
+        fill_quantities(&result),
+        vec![
+            (Address::from_low_u64_be(1), U256::from(6)),
+            (Address::from_low_u64_be(2), U256::from(4)),
+            (Address::from_low_u64_be(3), U256::from(5)),
+        ]
+    );
+    assert!(!book.asks.contains_key(&U256::from(100)));
+    assert_eq!(level_remaining(&book, 101), vec![U256::from(5)]);
+    assert_eq!(book.ltp(), U256::from(101));
+    assert_eq!(book.validate(), Ok(()));
+}
+
+#[tokio::test]
+pub async fn test_pro_rata_single_unit() {
+    let mut book: Book = pro_rata_book(vec![(1, 6), (2, 4), (3, 3)]).await;
+    let result: MatchResult = book
+        .submit(pro_rata_taker(4, 1), test_executioner())
+        .await
+        .unwrap();
+
+    /* every share rounds down to nothing, so the largest order takes it */
+    assert_eq!(
+        fill_quantities(&result),
+        vec![(Address::from_low_u64_be(1), U256::one())]
+    );
+    assert_eq!(
+        level_remaining(&book, 100),
+        vec![U256::from(5), U256::from(4), U256::from(3)]
+    );
+
+    /* among orders of the same size, the earliest takes it */
+    let mut book: Book = pro_rata_book(vec![(1, 3), (2, 5), (3, 5)]).await;
+    let result: MatchResult = book
+        .submit(pro_rata_taker(4, 1), test_executioner())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        fill_quantities(&result),
+        vec![(Address::from_low_u64_be(2), U256::one())]
+    );
+}
+
+#[tokio::test]
+pub async fn test_pro_rata_self_trade() {
+    let makers: Vec<(u64, u64)> = vec![(1, 6), (2, 4), (3, 3)];
+
+    /* the taker's own order takes no share of the level */
+    let mut book: Book = pro_rata_book(makers.clone())
+        .await
+        .with_self_trade_policy(SelfTradePolicy::Skip);
+    let own: OrderId = book.asks[&U256::from(100)][0].id;
+    let taker: Order = pro_rata_taker(1, 5);
+    let simulated: MatchResult = book.simulate(&taker);
+    let result: MatchResult =
+        book.submit(taker, test_executioner()).await.unwrap();
+
+    assert_eq!(result.order_status, OrderStatus::FullMatch);
+    assert_eq!(
+        fill_quantities(&result),
+        vec![
+            (Address::from_low_u64_be(2), U256::from(3)),
+            (Address::from_low_u64_be(3), U256::from(2)),
+        ]
+    );
+    assert!(result.cancelled.is_empty());
+    assert_eq!(
+        book.order(own).map(|order| order.remaining),
+        Some(U256::from(6))
+    );
+    assert_eq!(without_timestamps(simulated), without_timestamps(result));
+
+    /* nor does it when cancelled, leaving the rest to rest */
+    let mut book: Book = pro_rata_book(makers.clone())
+        .await
+        .with_self_trade_policy(SelfTradePolicy::CancelResting);
+    let own: OrderId = book.asks[&U256::from(100)][0].id;
+    let taker: Order = pro_rata_taker(1, 10);
+    let simulated: MatchResult = book.simulate(&taker);
+    let result: MatchResult =
+        book.submit(taker, test_executioner()).await.unwrap();
+
+    assert_eq!(result.order_status, OrderStatus::PartialMatch);
+    assert_eq!(result.cancelled, vec![own]);
+    assert_eq!(result.remaining, U256::from(3));
+    assert!(book.order(own).is_none());
+    assert!(book.asks.is_empty());
+    assert_eq!(book.depth(), (1, 0));
+    assert_eq!(without_timestamps(simulated), without_timestamps(result));
+
+    /* whereas cancelling the taker matches nothing at the level at all */
+    let mut book: Book = pro_rata_book(makers)
+        .await
+        .with_self_trade_policy(SelfTradePolicy::CancelIncoming);
+    let expected_book: Book = book.clone();
+    let taker: Order = pro_rata_taker(1, 5);
+    let taker_id: OrderId = taker.id;
+    let simulated: MatchResult = book.simulate(&taker);
+    let result: MatchResult =
+        book.submit(taker, test_executioner()).await.unwrap();
+
+    assert_eq!(result.order_status, OrderStatus::Killed);
+    assert_eq!(result.cancelled, vec![taker_id]);
+    assert!(result.fills.is_empty());
+    assert_eq!(book.bids, expected_book.bids);
+    assert_eq!(book.asks, expected_book.asks);
+    assert_eq!(without_timestamps(simulated), without_timestamps(result));
+}
+
+#[tokio::test]
+pub async fn test_matching_algorithm_survives_restore() {
+    let book: Book = pro_rata_book(vec![(1, 6)]).await;
+    let restored: Book = Book::try_from(ExternalBook::from(book))
+        .expect("Failed to restore book");
+
+    assert_eq!(restored.matching_algorithm, MatchingAlgorithm::ProRata);
+}
+
+#[tokio::test]
+pub async fn test_cancel_all_preserves_fifo() {
+    let market: Address = Address::zero();
+    let trader: Address = Address::from_low_u64_be(1);
+    let others: Vec<Address> =
+        vec![Address::from_low_u64_be(2), Address::from_low_u64_be(3)];
+
+    let mut book: Book = submit_orders(
+        market,
+        vec![
+            (others[0], OrderSide::Bid, 95, 10),
+            (trader, OrderSide::Bid, 95, 5),
+            (others[1], OrderSide::Bid, 95, 7),
+            (trader, OrderSide::Ask, 99, 3),
+        ],
+    )
+    .await;
+
+    let cancelled: Vec<Order> = book.cancel_all(trader);
+
+    assert_eq!(cancelled.len(), 2);
+    assert!(cancelled.iter().all(|order| order.trader == trader));
+    assert_eq!(book.depth(), (2, 0));
+    assert!(book.asks.is_empty());
+
+    let level: Vec<Address> = book.bids[&U256::from(95)]
+        .iter()
+        .map(|order| order.trader)
+        .collect();
+    assert_eq!(level, others);
+}
+
+#[tokio::test]
+pub async fn test_cancel_all_without_orders() {
+    let mut book = setup().await;
+    let expected_book: Book = book.clone();
+
+    let cancelled: Vec<Order> = book.cancel_all(Address::from_low_u64_be(42));
+
+    assert!(cancelled.is_empty());
+    assert_eq!(book, expected_book);
+}
+
+#[tokio::test]
+pub async fn test_orders_by_trader() {
+    let trader: Address = Address::from_low_u64_be(1);
+    let book: Book = submit_orders(
+        Address::zero(),
+        vec![
+            (trader, OrderSide::Ask, 102, 1),
+            (trader, OrderSide::Bid, 90, 1),
+            (Address::from_low_u64_be(2), OrderSide::Bid, 95, 1),
+            (trader, OrderSide::Ask, 101, 1),
+            (trader, OrderSide::Bid, 92, 1),
+        ],
+    )
+    .await;
+
+    /* bids then asks, each best price first */
+    let prices: Vec<(OrderSide, U256)> = book
+        .orders_by_trader(trader)
+        .into_iter()
+        .map(|order| (order.side, order.price))
+        .collect();
+    assert_eq!(
+        prices,
+        vec![
+            (OrderSide::Bid, U256::from(92)),
+            (OrderSide::Bid, U256::from(90)),
+            (OrderSide::Ask, U256::from(101)),
+            (OrderSide::Ask, U256::from(102)),
+        ]
+    );
+    assert!(book
+        .orders_by_trader(Address::from_low_u64_be(3))
+        .is_empty());
+}
+
+/* builds a book with two asks at 97 where the first has been partially filled */
+async fn amendment_setup() -> (Book, Order, Order) {
+    let market: Address = Address::zero();
+    let mut book: Book = Book::new(market);
+
+    let first: Order = Order::new(
+        Address::from_low_u64_be(1),
+        market,
+        OrderSide::Ask,
+        97.into(),
+        10.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+    let second: Order = Order::new(
+        Address::from_low_u64_be(2),
+        market,
+        OrderSide::Ask,
+        97.into(),
+        10.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+    let bid: Order = Order::new(
+        Address::from_low_u64_be(3),
+        market,
+        OrderSide::Bid,
+        97.into(),
+        4.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+
+    for order in [first.clone(), second.clone(), bid] {
+        book.submit(order, test_executioner()).await.unwrap();
+    }
+
+    (book, first, second)
+}
+
+#[tokio::test]
+pub async fn test_amend_reduce_quantity_keeps_priority() {
+    let (mut book, first, second) = amendment_setup().await;
+
+    let amended: Order = book.amend(first.id, 97.into(), 8.into()).unwrap();
+
+    assert_eq!(amended.quantity, U256::from(8));
+    assert_eq!(amended.remaining, U256::from(4));
+
+    let level: Vec<OrderId> = book.asks[&U256::from(97)]
+        .iter()
+        .map(|order| order.id)
+        .collect();
+    assert_eq!(level, vec![first.id, second.id]);
+}
+
+#[tokio::test]
+pub async fn test_amend_increase_quantity_loses_priority() {
+    let (mut book, first, second) = amendment_setup().await;
+
+    let amended: Order = book.amend(first.id, 97.into(), 12.into()).unwrap();
+
+    assert_eq!(amended.remaining, U256::from(8));
+
+    let level: Vec<OrderId> = book.asks[&U256::from(97)]
+        .iter()
+        .map(|order| order.id)
+        .collect();
+    assert_eq!(level, vec![second.id, first.id]);
+}
+
+#[tokio::test]
+pub async fn test_amend_price_moves_level() {
+    let (mut book, first, _second) = amendment_setup().await;
+
+    book.amend(first.id, 99.into(), 10.into()).unwrap();
+
+    assert_eq!(book.asks[&U256::from(97)].len(), 1);
+    assert_eq!(book.asks[&U256::from(99)][0].id, first.id);
+    assert_eq!(book.order(first.id).unwrap().remaining, U256::from(6));
+    assert_eq!(book.depth(), (0, 2));
+    assert_eq!(book.validate(), Ok(()));
+}
+
+#[tokio::test]
+pub async fn test_amend_below_filled_rejected() {
+    let (mut book, first, _second) = amendment_setup().await;
+    let expected_book: Book = book.clone();
+
+    assert_eq!(
+        book.amend(first.id, 97.into(), 3.into()),
+        Err(BookError::InvalidAmendment)
+    );
+    assert_eq!(book, expected_book);
+}
+
+#[tokio::test]
+pub async fn test_amend_unknown_order() {
+    let (mut book, _first, _second) = amendment_setup().await;
+
+    assert_eq!(
+        book.amend(OrderId::zero(), 97.into(), 3.into()),
+        Err(BookError::NoSuchOrder)
+    );
+}
+
+/* builds a replacement for the first ask of `amendment_setup` */
+fn replacement(book: &Book, price: u64, quantity: u64) -> Order {
+    Order::new(
+        Address::from_low_u64_be(1),
+        book.market,
+        OrderSide::Ask,
+        price.into(),
+        quantity.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    )
+}
+
+#[tokio::test]
+pub async fn test_replace_partially_filled_order() {
+    let (mut book, first, second) = amendment_setup().await;
+    let order: Order = replacement(&book, 98, 10);
+
+    let (cancelled, match_result) = book
+        .replace(first.id, order.clone(), test_executioner())
+        .await
+        .unwrap();
+
+    assert_eq!(cancelled.id, first.id);
+    assert_eq!(cancelled.remaining, U256::from(6));
+    assert_eq!(match_result.order_id, order.id);
+    assert_eq!(match_result.order_status, OrderStatus::Placed);
+    assert!(book.order(first.id).is_none());
+    assert_eq!(book.asks[&U256::from(97)][0].id, second.id);
+    assert_eq!(book.asks[&U256::from(98)][0].id, order.id);
+    assert_eq!(book.open_orders(first.trader), 1);
+}
+
+#[tokio::test]
+pub async fn test_replace_immediately_matches() {
+    let (mut book, first, second) = amendment_setup().await;
+    let bid: Order = Order::new(
+        Address::from_low_u64_be(3),
+        book.market,
+        OrderSide::Bid,
+        96.into(),
+        5.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+    book.submit(bid.clone(), test_executioner()).await.unwrap();
+    let order: Order = replacement(&book, 96, 8);
+
+    let (cancelled, match_result) = book
+        .replace(first.id, order.clone(), test_executioner())
+        .await
+        .unwrap();
+
+    assert_eq!(cancelled.remaining, U256::from(6));
+    assert_eq!(match_result.order_status, OrderStatus::PartialMatch);
+    assert_eq!(match_result.remaining, U256::from(3));
+    assert_eq!(match_result.fills.len(), 1);
+    assert_eq!(match_result.fills[0].maker_id, bid.id);
+    assert_eq!(book.asks[&U256::from(96)][0].id, order.id);
+    assert_eq!(book.asks[&U256::from(97)][0].id, second.id);
+    assert_eq!(book.depth(), (0, 2));
+    assert_eq!(book.validate(), Ok(()));
+}
+
+#[tokio::test]
+pub async fn test_rejected_replacement_leaves_order() {
+    let (mut book, first, _second) = amendment_setup().await;
+    let expected_book: Book = book.clone();
+
+    /* the new order is invalid */
+    let order: Order = replacement(&book, 98, 0);
+    assert_eq!(
+        book.replace(first.id, order, test_executioner()).await,
+        Err(BookError::InvalidQuantity)
+    );
+
+    /* the new order is for another trader */
+    let mut order: Order = replacement(&book, 98, 10);
+    order.trader = Address::from_low_u64_be(2);
+    assert_eq!(
+        book.replace(first.id, order, test_executioner()).await,
+        Err(BookError::InvalidAmendment)
+    );
+
+    /* there is no such open order */
+    let order: Order = replacement(&book, 98, 10);
+    assert_eq!(
+        book.replace(OrderId::zero(), order, test_executioner())
+            .await,
+        Err(BookError::NoSuchOrder)
+    );
+
+    assert_eq!(book, expected_book);
+}
+
+#[tokio::test]
+pub async fn test_wrong_market_rejected() {
+    let mut book: Book = setup().await;
+    let expected_book: Book = book.clone();
+    let order: Order = Order::new(
+        Address::from_low_u64_be(5), /* placed the bid at 95 */
+        Address::from_low_u64_be(1),
+        OrderSide::Bid,
+        100.into(),
+        5.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+
+    assert_eq!(
+        book.submit(order.clone(), test_executioner()).await,
+        Err(BookError::WrongMarket)
+    );
+    assert_eq!(
+        book.replace(
+            expected_book.bids[&U256::from(95)][0].id,
+            order,
+            test_executioner()
+        )
+        .await,
+        Err(BookError::WrongMarket)
+    );
+    assert_eq!(book, expected_book);
+}
+
+#[tokio::test]
+pub async fn test_zero_quantity_rejected() {
+    let mut book = setup().await;
+    let expected_book: Book = book.clone();
+    let bid = Order::new(
+        Address::from_low_u64_be(10),
+        Address::zero(),
+        OrderSide::Bid,
+        97.into(),
+        0.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+
+    let actual_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
+
+    assert_eq!(actual_res, Err(BookError::InvalidQuantity));
+    assert_eq!(book, expected_book);
+}
+
+#[tokio::test]
+pub async fn test_zero_price_rejected() {
+    let mut book = setup().await;
+    let expected_book: Book = book.clone();
+    let bid = Order::new(
+        Address::from_low_u64_be(10),
+        Address::zero(),
+        OrderSide::Bid,
+        0.into(),
+        10.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+
+    let actual_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
+
+    assert_eq!(actual_res, Err(BookError::InvalidPrice));
+    assert_eq!(book, expected_book);
+}
+
+#[tokio::test]
+pub async fn test_remaining_exceeding_quantity_rejected() {
+    let mut book = setup().await;
+    let expected_book: Book = book.clone();
+    let mut bid = Order::new(
+        Address::from_low_u64_be(10),
+        Address::zero(),
+        OrderSide::Bid,
+        90.into(),
+        10.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+    bid.remaining = 11.into();
+
+    let actual_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
+
+    assert_eq!(actual_res, Err(BookError::InvalidQuantity));
+    assert_eq!(book, expected_book);
+}
+
+#[tokio::test]
+pub async fn test_tick_size_enforced() {
+    let market: Address = Address::zero();
+    let mut book = Book::new(market).with_tick_size(5.into());
+    let bid = Order::new(
+        Address::from_low_u64_be(1),
+        market,
+        OrderSide::Bid,
+        97.into(),
+        10.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+
+    let actual_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
+
+    assert_eq!(actual_res, Err(BookError::InvalidTickSize));
+    assert_eq!(book.depth(), (0, 0));
+}
+
+#[tokio::test]
+pub async fn test_lot_size_enforced() {
+    let market: Address = Address::zero();
+    let mut book = Book::new(market).with_lot_size(4.into());
+    let bid = Order::new(
+        Address::from_low_u64_be(1),
+        market,
+        OrderSide::Bid,
+        97.into(),
+        10.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+
+    let actual_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
+
+    assert_eq!(actual_res, Err(BookError::InvalidLotSize));
+
+    let bid = Order::new(
+        Address::from_low_u64_be(1),
+        market,
+        OrderSide::Bid,
+        97.into(),
+        12.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+
+    let actual_res: Result<MatchResult, BookError> =
+        book.submit(bid, test_executioner()).await;
+
+    assert!(actual_res.is_ok());
+    assert_eq!(book.depth(), (1, 0));
+}
+
+#[tokio::test]
+pub async fn test_amend_held_to_tick_size() {
+    let market: Address = Address::zero();
+    let mut book = Book::new(market).with_tick_size(5.into());
+    let bid: Order = stop_test_order(1, OrderSide::Bid, 95, 10);
+    book.submit(bid.clone(), test_executioner()).await.unwrap();
+    let expected_book: Book = book.clone();
+
+    assert_eq!(
+        book.amend(bid.id, 97.into(), 10.into()),
+        Err(BookError::InvalidTickSize)
+    );
+    assert_eq!(book, expected_book);
+
+    book.amend(bid.id, 90.into(), 10.into()).unwrap();
+    assert_eq!(book.order(bid.id).unwrap().price, U256::from(90));
+}
+
+#[tokio::test]
+pub async fn test_amend_held_to_lot_size() {
+    let market: Address = Address::zero();
+    let mut book = Book::new(market).with_lot_size(4.into());
+    let bid: Order = stop_test_order(1, OrderSide::Bid, 97, 12);
+    book.submit(bid.clone(), test_executioner()).await.unwrap();
+    let expected_book: Book = book.clone();
+
+    assert_eq!(
+        book.amend(bid.id, 97.into(), 10.into()),
+        Err(BookError::InvalidLotSize)
+    );
+    assert_eq!(book, expected_book);
+
+    book.amend(bid.id, 97.into(), 8.into()).unwrap();
+    assert_eq!(book.order(bid.id).unwrap().quantity, U256::from(8));
+}
+
+#[test]
+pub fn test_converting_book_to_external_book_and_back() {
+    let market: Address = Address::repeat_byte(0xab);
+    let book: Book = Book::new(market)
+        .with_tick_size(5.into())
+        .with_lot_size(100.into());
+
+    let external_book: ExternalBook = ExternalBook::from(book.clone());
+
+    assert_eq!(external_book.tick_size, "5");
+    assert_eq!(external_book.lot_size, "100");
+    assert_eq!(Book::try_from(external_book).unwrap(), book);
+}
+
+#[tokio::test]
+pub async fn test_last_trade_recorded() {
+    let mut book: Book = Book::new(Address::zero());
+    assert_eq!(book.last_trade, None);
+
+    for order in [
+        stop_test_order(1, OrderSide::Bid, 100, 5),
+        stop_test_order(2, OrderSide::Bid, 99, 5),
+    ] {
+        book.submit(order, test_executioner()).await.unwrap();
+    }
+
+    /* a sell sweeping both bid levels last trades at the lower one */
+    let now: DateTime<Utc> = Utc.timestamp(1_600_000_000, 0);
+    book.submit_at(stop_test_order(3, OrderSide::Ask, 98, 8), None, now)
+        .await
+        .unwrap();
+    assert_eq!(book.ltp(), U256::from(99));
+    assert_eq!(
+        book.last_trade,
+        Some(LastTrade {
+            price: U256::from(99),
+            quantity: U256::from(3),
+            taker_side: OrderSide::Ask,
+            timestamp: now,
+        })
+    );
+    assert_eq!(book.ticker().last_trade, book.last_trade);
+
+    /* it survives a restore in either format */
+    let external_book: ExternalBook = ExternalBook::from(book.clone());
+    assert_eq!(
+        external_book.last_trade,
+        Some(ExternalLastTrade {
+            price: "99".to_string(),
+            quantity: "3".to_string(),
+            taker_side: OrderSide::Ask,
+            timestamp: "1600000000".to_string(),
+        })
+    );
+    assert_eq!(
+        Book::try_from(external_book.clone()).unwrap().last_trade,
+        book.last_trade
+    );
+    let restored: Book =
+        serde_json::from_str(&serde_json::to_string(&book).unwrap()).unwrap();
+    assert_eq!(restored.last_trade, book.last_trade);
+
+    /* books from before the last trade was recorded have none */
+    let mut payload: serde_json::Value =
+        serde_json::to_value(&external_book).unwrap();
+    payload.as_object_mut().unwrap().remove("last_trade");
+    let old: ExternalBook = serde_json::from_value(payload).unwrap();
+    assert_eq!(Book::try_from(old).unwrap().last_trade, None);
+}
+
+#[tokio::test]
+pub async fn test_paused_book() {
+    let mut book: Book = setup().await;
+    let resting: OrderId = book.bids[&U256::from(95)].front().unwrap().id;
+    let crossing: Order = stop_test_order(20, OrderSide::Bid, 96, 5);
+
+    assert!(book.set_paused(true));
+    assert!(!book.set_paused(true));
+
+    let before: Book = book.clone();
+    assert_eq!(
+        book.submit(crossing.clone(), test_executioner()).await,
+        Err(BookError::MarketPaused)
+    );
+    assert_eq!(
+        book.quote_at(&crossing, Utc::now()).order_status,
+        OrderStatus::Rejected {
+            reason: BookError::MarketPaused
+        }
+    );
+    assert_eq!(book, before);
+
+    /* open orders may still be amended and cancelled */
+    book.amend(resting, U256::from(95), U256::from(5)).unwrap();
+    assert!(book.cancel(resting).unwrap().is_some());
+
+    /* the pause survives a dump and restore in either format */
+    let restored: Book =
+        Book::try_from(ExternalBook::from(book.clone())).unwrap();
+    assert!(restored.paused);
+    let restored: Book =
+        serde_json::from_str(&serde_json::to_string(&book).unwrap()).unwrap();
+    assert!(restored.paused);
+    assert_eq!(
+        serde_json::to_value(BookSnapshot(&book)).unwrap()["paused"],
+        true
+    );
+
+    assert!(book.set_paused(false));
+    assert_eq!(
+        book.submit(crossing, test_executioner())
+            .await
+            .unwrap()
+            .order_status,
+        OrderStatus::FullMatch
+    );
+}
+
+#[tokio::test]
+pub async fn test_book_diff_identical() {
+    let book: Book = setup().await;
+    let copy: Book = Book::try_from(ExternalBook::from(book.clone())).unwrap();
+
+    let diff: BookDiff = book.diff(&copy);
+    assert!(diff.is_empty());
+    assert!(!diff.exceeds(U256::zero()));
+    assert_eq!(diff.top, (Some(U256::from(95)), Some(U256::from(96))));
+
+    /* orders adding up to the same totals make for alike levels */
+    let trader: Address = Address::from_low_u64_be(1);
+    let one: Book =
+        submit_orders(Address::zero(), vec![(trader, OrderSide::Bid, 95, 10)])
+            .await;
+    let two: Book = submit_orders(
+        Address::zero(),
+        vec![
+            (trader, OrderSide::Bid, 95, 4),
+            (trader, OrderSide::Bid, 95, 6),
+        ],
+    )
+    .await;
+    assert!(one.diff(&two).is_empty());
+}
+
+#[tokio::test]
+pub async fn test_book_diff_missing_level() {
+    let book: Book = setup().await;
+    let mut other: Book = book.clone();
+    let best_ask: OrderId = other.asks[&U256::from(96)].front().unwrap().id;
+    other.cancel(best_ask).unwrap();
+
+    let diff: BookDiff = book.diff(&other);
+    assert_eq!(
+        diff.levels,
+        vec![LevelDiff {
+            side: OrderSide::Ask,
+            price: U256::from(96),
+            ours: U256::from(5),
+            theirs: U256::zero(),
+        }]
+    );
+    assert_eq!(diff.other_top, (Some(U256::from(95)), Some(U256::from(97))));
+
+    /* a different top of book exceeds any tolerance */
+    assert!(diff.exceeds(U256::from(1000)));
+    assert_eq!(other.diff(&book).levels[0].ours, U256::zero());
+}
+
+#[tokio::test]
+pub async fn test_book_diff_different_quantity() {
+    let book: Book = setup().await;
+    let mut other: Book = book.clone();
+    let bid: OrderId = other.bids[&U256::from(93)].front().unwrap().id;
+    other.amend(bid, U256::from(93), U256::from(3)).unwrap();
+
+    let diff: BookDiff = book.diff(&other);
+    assert!(!diff.is_empty());
+    assert_eq!(
+        diff.levels,
+        vec![LevelDiff {
+            side: OrderSide::Bid,
+            price: U256::from(93),
+            ours: U256::from(5),
+            theirs: U256::from(3),
+        }]
+    );
+    assert_eq!(diff.levels[0].difference(), U256::from(2));
+    assert_eq!(diff.top, diff.other_top);
+
+    /* quantities may differ by up to the tolerance */
+    assert!(diff.exceeds(U256::from(1)));
+    assert!(!diff.exceeds(U256::from(2)));
+}
+
+#[tokio::test]
+pub async fn test_book_snapshot_serialized_as_external_book() {
+    let mut book: Book = setup().await;
+    let stop: Order = stop_test_order(2, OrderSide::Bid, 0, 3)
+        .with_trigger(OrderType::Stop, U256::from(120));
+    let tagged: Order = stop_test_order(3, OrderSide::Bid, 90, 4)
+        .with_client_order_id("quote-1".to_string())
+        .with_nonce(U256::from(7));
+    let mut signed: Order = stop_test_order(4, OrderSide::Ask, 110, 4);
+    signed.signed_data = vec![0xab; 65];
+
+    for order in [stop, tagged, signed] {
+        book.submit(order, test_executioner()).await.unwrap();
+    }
+
+    let external: ExternalBook = ExternalBook::from(book.clone());
+    assert_eq!(
+        serde_json::to_value(BookSnapshot(&book)).unwrap(),
+        serde_json::to_value(&external).unwrap()
+    );
+    assert_eq!(
+        serde_json::to_value(SnapshotEvent::from(&book)).unwrap(),
+        serde_json::to_value(ExternalBookEvent::snapshot(external)).unwrap()
+    );
+}
+
+#[tokio::test]
+pub async fn test_external_book_levels_in_price_order() {
+    let market: Address = Address::zero();
+    let book: Book = submit_orders(
+        market,
+        vec![
+            (Address::from_low_u64_be(1), OrderSide::Bid, 9, 1),
+            (Address::from_low_u64_be(1), OrderSide::Bid, 100, 1),
+            (Address::from_low_u64_be(1), OrderSide::Bid, 99, 1),
+            (Address::from_low_u64_be(2), OrderSide::Ask, 10000, 1),
+            (Address::from_low_u64_be(2), OrderSide::Ask, 1000, 1),
+        ],
+    )
+    .await;
+
+    let external_book: ExternalBook = ExternalBook::from(book.clone());
+    let prices = |levels: &ExternalLevels| -> Vec<String> {
+        levels.iter().map(|(price, _)| price.clone()).collect()
+    };
+
+    assert_eq!(prices(&external_book.bids), vec!["9", "99", "100"]);
+    assert_eq!(prices(&external_book.asks), vec!["1000", "10000"]);
+
+    /* orders keep their levels and IDs on the way back */
+    let restored: Book = Book::try_from(external_book).unwrap();
+    let ids = |levels: &BTreeMap<U256, Level>| {
+        levels
+            .iter()
+            .map(|(price, orders)| {
+                (*price, orders.iter().map(|o| o.id).collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(ids(&restored.bids), ids(&book.bids));
+    assert_eq!(ids(&restored.asks), ids(&book.asks));
+    assert_eq!(restored.depth(), book.depth());
+}
+
+#[tokio::test]
+pub async fn test_spread_and_crossed_maintained() {
+    let mut book = setup().await;
+
+    /* best bid is 95 and best ask is 96 */
+    assert_eq!(book.spread(), U256::from(1));
+    assert!(!book.crossed());
+
+    /* take out the best ask */
+    let bid = Order::new(
+        Address::from_low_u64_be(10),
+        Address::zero(),
+        OrderSide::Bid,
+        96.into(),
+        5.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+    book.submit(bid, test_executioner()).await.unwrap();
+
+    assert_eq!(book.spread(), U256::from(2));
+    assert!(!book.crossed());
+
+    /* pull every bid, leaving a one-sided book */
+    for trader in 5..10 {
+        book.cancel_all(Address::from_low_u64_be(trader));
+    }
+
+    assert_eq!(book.spread(), U256::zero());
+    assert!(!book.crossed());
+}
+
+#[tokio::test]
+pub async fn test_crossed_by_self_trade() {
+    let market: Address = Address::zero();
+    let trader: Address = Address::from_low_u64_be(1);
+
+    let book: Book = submit_orders(
+        market,
+        vec![
+            (trader, OrderSide::Ask, 97, 15),
+            (trader, OrderSide::Bid, 98, 15),
+        ],
+    )
+    .await;
+
+    assert!(book.crossed());
+    assert_eq!(book.spread(), U256::zero());
+    assert_eq!(book.validate(), Ok(()));
+}
+
+#[tokio::test]
+pub async fn test_validate_reports_violations() {
+    let book: Book = setup().await;
+    let level = |side: OrderSide, price: u64| PriceLevel {
+        side,
+        stop: false,
+        price: U256::from(price),
+    };
+
+    /* the only ask at 96, moved under 97 */
+    let mut misplaced: Book = book.clone();
+    let moved: Order = misplaced
+        .asks
+        .get_mut(&U256::from(96))
+        .unwrap()
+        .remove(0)
+        .unwrap();
+    misplaced
+        .asks
+        .get_mut(&U256::from(97))
+        .unwrap()
+        .push_back(moved.clone());
+    assert_eq!(
+        misplaced.validate(),
+        Err(vec![
+            InvariantViolation::EmptyLevel(level(OrderSide::Ask, 96)),
+            InvariantViolation::WrongLevel {
+                id: moved.id,
+                level: level(OrderSide::Ask, 97)
+            },
+            InvariantViolation::Unindexed {
+                id: moved.id,
+                level: level(OrderSide::Ask, 97)
+            },
+        ])
+    );
+
+    /* the best bid, emptied (and moved to another market) behind the
+     * book's back */
+    let mut emptied: Book = book.clone();
+    let best_bid: &mut Order = emptied
+        .bids
+        .get_mut(&U256::from(95))
+        .unwrap()
+        .get_mut(0)
+        .unwrap();
+    best_bid.remaining = U256::zero();
+    best_bid.market = Address::from_low_u64_be(1);
+    let id: OrderId = best_bid.id;
+    assert_eq!(
+        emptied.validate(),
+        Err(vec![
+            InvariantViolation::TotalMismatch {
+                level: level(OrderSide::Bid, 95),
+                cached: U256::from(10),
+                actual: U256::zero()
+            },
+            InvariantViolation::WrongMarket {
+                id,
+                level: level(OrderSide::Bid, 95)
+            },
+            InvariantViolation::NothingRemaining {
+                id,
+                level: level(OrderSide::Bid, 95)
+            },
+            InvariantViolation::DepthMismatch {
+                cached: (5, 5),
+                actual: (4, 5)
+            },
+            InvariantViolation::VolumeMismatch {
+                cached: (U256::from(60), U256::from(67)),
+                actual: (U256::from(50), U256::from(67))
+            },
+        ])
+    );
+
+    /* an index entry outliving its order, and a stale crossed flag */
+    let mut stale: Book = submit_orders(
+        Address::zero(),
+        vec![
+            (Address::from_low_u64_be(1), OrderSide::Ask, 97, 15),
+            (Address::from_low_u64_be(1), OrderSide::Bid, 98, 15),
+        ],
+    )
+    .await;
+    let ghost: OrderId = OrderId::from_low_u64_be(1);
+    stale.index.insert(ghost, (OrderSide::Bid, U256::from(98)));
+    stale.crossed = false;
+    let violations: Vec<InvariantViolation> = stale.validate().unwrap_err();
+    assert_eq!(
+        violations,
+        vec![
+            InvariantViolation::StaleIndex(ghost),
+            InvariantViolation::Crossed {
+                best_bid: U256::from(98),
+                best_ask: U256::from(97)
+            },
+        ]
+    );
+
+    /* violations carry enough context to debug from a log line */
+    assert_eq!(
+        violations[1].to_string(),
+        "Book is crossed at 98/97, but not marked so"
+    );
+    assert_eq!(
+        InvariantViolation::EmptyLevel(level(OrderSide::Ask, 96)).to_string(),
+        "Price level Ask 96 is empty"
+    );
+}
+
+#[tokio::test]
+pub async fn test_cancel_updates_metadata() {
+    let market: Address = Address::zero();
+    let ask: Order = Order::new(
+        Address::from_low_u64_be(1),
+        market,
+        OrderSide::Ask,
+        97.into(),
+        15.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+
+    let mut book: Book = submit_orders(
+        market,
+        vec![
+            (Address::from_low_u64_be(2), OrderSide::Bid, 95, 10),
+            (Address::from_low_u64_be(3), OrderSide::Bid, 94, 10),
+        ],
+    )
+    .await;
+    book.submit(ask.clone(), test_executioner()).await.unwrap();
+    assert_eq!(book.spread(), U256::from(2));
+
+    let cancelled: Option<Order> = book.cancel(ask.id).unwrap();
+
+    assert_eq!(cancelled.map(|o| o.remaining), Some(U256::from(15)));
+    assert_eq!(book.depth, (2, 0));
+    assert_eq!(ExternalBook::from(book.clone()).depth, (2, 0));
+    assert!(book.asks.is_empty());
+    assert_eq!(book.spread(), U256::zero());
+}
+
+/* checks the order index matches a from-scratch rebuild of it */
+fn assert_index_consistent(book: &Book) {
+    let mut rebuilt: Book = book.clone();
+    rebuilt.reindex();
+    assert_eq!(book.index, rebuilt.index);
+    assert_eq!(book.open_orders, rebuilt.open_orders);
+    assert_eq!(book.validate(), Ok(()));
+}
+
+#[tokio::test]
+pub async fn test_order_index_tracks_fills_and_cancels() {
+    let mut book = setup().await;
+    assert_index_consistent(&book);
+
+    /* fully fills the asks at 96 and 97, partially fills the one at 98 */
+    let bid = Order::new(
+        Address::from_low_u64_be(10),
+        Address::zero(),
+        OrderSide::Bid,
+        U256::from(98),
+        U256::from(30),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+    let filled: Vec<OrderId> = book.asks[&U256::from(96)]
+        .iter()
+        .chain(book.asks[&U256::from(97)].iter())
+        .map(|order| order.id)
+        .collect();
+    let partial: OrderId = book.asks[&U256::from(98)][0].id;
+
+    book.submit(bid, test_executioner()).await.unwrap();
+
+    assert_index_consistent(&book);
+    assert!(filled.iter().all(|id| book.order(*id).is_none()));
+    assert_eq!(
+        book.order(partial).map(|order| order.remaining),
+        Some(U256::from(25))
+    );
+
+    book.cancel(partial).unwrap();
+    book.cancel_all(Address::from_low_u64_be(5));
+
+    assert_index_consistent(&book);
+    assert!(book.order(partial).is_none());
+    assert_eq!(book.depth(), (4, 2));
+}
+
+#[tokio::test]
+pub async fn test_cancel_with_many_resting_orders() {
+    /* 500 levels of 100 orders each, populated directly for speed */
+    let mut book: Book =
+        deep_book(Address::zero(), OrderSide::Ask, 1000, 500, 100);
+    assert_eq!(book.depth(), (0, 50_000));
+
+    /* cancel from the back of the book, which a scan would reach last */
+    let ids: Vec<OrderId> = book
+        .asks
+        .values()
+        .rev()
+        .take(2)
+        .flatten()
+        .map(|order| order.id)
+        .collect();
+
+    for id in ids.iter() {
+        assert_eq!(book.cancel(*id).unwrap().map(|order| order.id), Some(*id));
+    }
+
+    assert_index_consistent(&book);
+    assert_eq!(book.index.len(), 49_800);
+    assert_eq!(book.depth, (0, 49_800));
+    assert_eq!(book.asks.len(), 498);
+}
+
+#[tokio::test]
+pub async fn test_depth_and_volume_are_maintained() {
+    let mut book = setup().await;
+    assert_eq!(book.depth(), (5, 5));
+    assert_eq!(book.volume(), (U256::from(60), U256::from(67)));
+
+    /* fully fills the bid at 95 and partially fills the one at 94 */
+    let ask = Order::new(
+        Address::from_low_u64_be(10),
+        Address::zero(),
+        OrderSide::Ask,
+        U256::from(94),
+        U256::from(12),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+    book.submit(ask, test_executioner()).await.unwrap();
+
+    assert_eq!(book.depth(), (4, 5));
+    assert_eq!(book.volume(), (U256::from(48), U256::from(67)));
+
+    /* shrinks the partially filled bid from 20 (18 left) to 10 (8 left) */
+    let partial: OrderId = book.bids[&U256::from(94)][0].id;
+    book.amend(partial, U256::from(94), U256::from(10)).unwrap();
+    assert_eq!(book.volume(), (U256::from(38), U256::from(67)));
+
+    let best_ask: OrderId = book.asks[&U256::from(96)][0].id;
+    book.cancel(best_ask).unwrap();
+
+    assert_eq!(book.depth(), (4, 4));
+    assert_eq!(book.volume(), (U256::from(38), U256::from(62)));
+    assert_eq!(book.depth, book.depth());
+    assert_eq!(book.validate(), Ok(()));
+}
+
+#[tokio::test]
+pub async fn test_aggregated_depth() {
+    let market: Address = Address::zero();
+    let book: Book = submit_orders(
+        market,
+        vec![
+            (Address::from_low_u64_be(1), OrderSide::Bid, 95, 10),
+            (Address::from_low_u64_be(2), OrderSide::Bid, 95, 5),
+            (Address::from_low_u64_be(3), OrderSide::Bid, 94, 20),
+            (Address::from_low_u64_be(4), OrderSide::Bid, 93, 1),
+            (Address::from_low_u64_be(5), OrderSide::Ask, 97, 15),
+            (Address::from_low_u64_be(6), OrderSide::Ask, 98, 7),
+            (Address::from_low_u64_be(7), OrderSide::Ask, 98, 3),
+        ],
+    )
+    .await;
+
+    let level = |price: u64, quantity: u64| (price.into(), quantity.into());
+
+    assert_eq!(
+        book.aggregated_depth(0),
+        (
+            vec![level(95, 15), level(94, 20), level(93, 1)],
+            vec![level(97, 15), level(98, 10)]
+        )
+    );
+    assert_eq!(
+        book.aggregated_depth(1),
+        (vec![level(95, 15)], vec![level(97, 15)])
+    );
+    assert_eq!(book.aggregated_depth(10), book.aggregated_depth(0));
+}
+
+#[tokio::test]
+pub async fn test_trade_history() {
+    let mut book = setup().await;
+    let taker: Address = Address::from_low_u64_be(10);
+    let bid = Order::new(
+        taker,
+        Address::zero(),
+        OrderSide::Bid,
+        U256::from(97),
+        U256::from(8),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+    let makers: Vec<OrderId> = vec![
+        book.asks[&U256::from(96)][0].id,
+        book.asks[&U256::from(97)][0].id,
+    ];
+
+    book.submit(bid.clone(), test_executioner()).await.unwrap();
+
+    /* newest first */
+    let trades: Vec<Fill> =
+        book.recent_trades(0).into_iter().cloned().collect();
+    assert_eq!(trades.len(), 2);
+    assert_eq!(trades[0].maker_id, makers[1]);
+    assert_eq!(trades[0].maker_address, Address::from_low_u64_be(3));
+    assert_eq!(trades[0].price, U256::from(97));
+    assert_eq!(trades[0].quantity, U256::from(3));
+    assert_eq!(trades[1].maker_id, makers[0]);
+    assert_eq!(trades[1].maker_address, Address::from_low_u64_be(4));
+    assert_eq!(trades[1].price, U256::from(96));
+    assert_eq!(trades[1].quantity, U256::from(5));
+    assert!(trades
+        .iter()
+        .all(|trade| trade.taker_id == bid.id && trade.taker_address == taker));
+
+    assert_eq!(book.recent_trades(1), vec![&trades[0]]);
+}
+
+#[tokio::test]
+pub async fn test_trade_history_is_bounded() {
+    let mut book: Book = Book::new(Address::zero());
+    let trades: Vec<Fill> = (0..MAX_TRADES as u64 + 10)
+        .map(|i| Fill {
+            trade_id: i + 1,
+            maker_id: OrderId::from_low_u64_be(i),
+            taker_id: OrderId::zero(),
+            maker_address: Address::from_low_u64_be(i),
+            taker_address: Address::zero(),
+            price: U256::from(100),
+            quantity: U256::from(1),
+            timestamp: Utc::now(),
+            maker_client_order_id: None,
+            taker_client_order_id: None,
+            maker_fee: U256::zero(),
+            taker_fee: U256::zero(),
+        })
+        .collect();
+
+    book.record_trades(trades[..10].to_vec());
+    book.record_trades(trades[10..].to_vec());
+
+    assert_eq!(book.trades.len(), MAX_TRADES);
+    assert_eq!(book.trades.front(), Some(&trades[10]));
+    assert_eq!(book.recent_trades(1), vec![trades.last().unwrap()]);
+}
+
+#[tokio::test]
+pub async fn test_sequence_and_trade_ids_increase() {
+    let mut book = setup().await;
+    let mut last: u64 = book.sequence;
+    assert_eq!(last, 10); // one per order added
+
+    let mut ids: Vec<u64> = vec![];
+
+    for (trader, side, price, quantity) in [
+        (10, OrderSide::Bid, 97, 8),  /* two fills */
+        (11, OrderSide::Ask, 94, 32), /* two fills, remainder rests */
+        (12, OrderSide::Bid, 94, 1),  /* fill against the remainder */
+    ] {
+        let result: MatchResult = book
+            .submit(
+                Order::new(
+                    Address::from_low_u64_be(trader),
+                    Address::zero(),
+                    side,
+                    price.into(),
+                    quantity.into(),
+                    far_future(),
+                    Utc::now(),
+                    vec![],
+                ),
+                test_executioner(),
+            )
+            .await
+            .unwrap();
+
+        assert!(!result.fills.is_empty());
+        ids.extend(result.fills.iter().map(|fill| fill.trade_id));
+        assert!(result.fills.iter().all(|fill| fill.trade_id > last));
+        assert!(book.sequence > last);
+        last = book.sequence;
+
+        /* interleave a cancellation of the worst remaining ask */
+        let worst: OrderId = book.asks.values().last().unwrap()[0].id;
+        book.cancel(worst).unwrap();
+        assert_eq!(book.sequence, last + 1);
+        last = book.sequence;
+    }
+
+    assert_eq!(ids.len(), 5);
+    assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+
+    /* the sequence survives conversion to the external format and back */
+    let external: ExternalBook = ExternalBook::from(book.clone());
+    let restored: Book = Book::try_from(external).unwrap();
+    assert_eq!(restored.sequence, book.sequence);
+}
+
+/* strips the timestamps from fills, checking they fall within the window */
+fn untimed_fills(
+    fills: &[Fill],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<(OrderId, OrderId, Address, Address, U256, U256)> {
+    fills
+        .iter()
+        .map(|fill| {
+            assert!(fill.timestamp >= start && fill.timestamp <= end);
+            (
+                fill.maker_id,
+                fill.taker_id,
+                fill.maker_address,
+                fill.taker_address,
+                fill.price,
+                fill.quantity,
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+pub async fn test_fills_output_full_match() {
+    let mut book = setup().await;
+    let taker: Address = Address::from_low_u64_be(10);
+    let bid = Order::new(
+        taker,
+        Address::zero(),
+        OrderSide::Bid,
+        U256::from(97),
+        U256::from(20),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+    let first: Order = book.asks[&U256::from(96)][0].clone();
+    let second: Order = book.asks[&U256::from(97)][0].clone();
+
+    let start: DateTime<Utc> = Utc::now();
+    let result: MatchResult =
+        book.submit(bid.clone(), test_executioner()).await.unwrap();
+    let end: DateTime<Utc> = Utc::now();
+
+    assert_eq!(result.order_status, OrderStatus::FullMatch);
+    assert_eq!(
+        untimed_fills(&result.fills, start, end),
+        vec![
+            (first.id, bid.id, first.trader, taker, 96.into(), 5.into()),
+            (
+                second.id,
+                bid.id,
+                second.trader,
+                taker,
+                97.into(),
+                15.into()
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+pub async fn test_fills_output_partial_match() {
+    let mut book = setup().await;
+    let taker: Address = Address::from_low_u64_be(10);
+    let ask = Order::new(
+        taker,
+        Address::zero(),
+        OrderSide::Ask,
+        U256::from(95),
+        U256::from(25),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+    let maker: Order = book.bids[&U256::from(95)][0].clone();
+
+    let start: DateTime<Utc> = Utc::now();
+    let result: MatchResult =
+        book.submit(ask.clone(), test_executioner()).await.unwrap();
+    let end: DateTime<Utc> = Utc::now();
+
+    assert_eq!(result.order_status, OrderStatus::PartialMatch);
+    assert_eq!(
+        untimed_fills(&result.fills, start, end),
+        vec![(maker.id, ask.id, maker.trader, taker, 95.into(), 10.into())]
+    );
+
+    /* external fills use hex addresses and Unix timestamps like orders do */
+    let external: ExternalFill = result.fills[0].clone().into();
+    assert_eq!(external.maker_address, ExternalOrder::from(maker).user);
+    assert_eq!(
+        external.timestamp,
+        result.fills[0].timestamp.timestamp().to_string()
+    );
+}
+
+#[tokio::test]
+pub async fn test_match_result_remaining_and_average_price() {
+    let mut book = setup().await;
+    let order = |trader: u64, side: OrderSide, price: u64, quantity: u64| {
+        Order::new(
+            Address::from_low_u64_be(trader),
+            Address::zero(),
+            side,
+            price.into(),
+            quantity.into(),
+            far_future(),
+            Utc::now(),
+            vec![],
+        )
+    };
+
+    /* no fills */
+    let resting: Order = order(10, OrderSide::Bid, 90, 7);
+    let result: MatchResult = book
+        .submit(resting.clone(), test_executioner())
+        .await
+        .unwrap();
+    assert_eq!(result.order_id, resting.id);
+    assert_eq!(result.order_status, OrderStatus::Placed);
+    assert_eq!(result.remaining, U256::from(7));
+    assert_eq!(result.average_price, U256::zero());
+
+    /* 5 @ 96 and 15 @ 97, with 2 left over, averages 96.75 */
+    let result: MatchResult = book
+        .submit(order(10, OrderSide::Bid, 97, 22), test_executioner())
+        .await
+        .unwrap();
+    assert_eq!(result.order_status, OrderStatus::PartialMatch);
+    assert_eq!(result.remaining, U256::from(2));
+    assert_eq!(result.average_price, U256::from(96));
+
+    /* a single fill averages to its own price */
+    let result: MatchResult = book
+        .submit(order(11, OrderSide::Ask, 95, 2), test_executioner())
+        .await
+        .unwrap();
+    assert_eq!(result.order_status, OrderStatus::FullMatch);
+    assert_eq!(result.remaining, U256::zero());
+    assert_eq!(result.average_price, U256::from(97));
+}
+
+/* builds an order for the stop order tests */
+fn stop_test_order(
+    trader: u64,
+    side: OrderSide,
+    price: u64,
+    quantity: u64,
+) -> Order {
+    Order::new(
+        Address::from_low_u64_be(trader),
+        Address::zero(),
+        side,
+        price.into(),
+        quantity.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    )
+}
+
+#[tokio::test]
+pub async fn test_stop_order_triggers_on_last_traded_price() {
+    let mut book: Book = submit_orders(
+        Address::zero(),
+        vec![
+            (Address::from_low_u64_be(1), OrderSide::Ask, 100, 5),
+            (Address::from_low_u64_be(1), OrderSide::Ask, 105, 5),
+        ],
+    )
+    .await;
+
+    let stop: Order = stop_test_order(2, OrderSide::Bid, 0, 3)
+        .with_trigger(OrderType::Stop, U256::from(100));
+    let result: MatchResult =
+        book.submit(stop.clone(), test_executioner()).await.unwrap();
+
+    /* untriggered stops rest outside of the visible book */
+    assert_eq!(result.order_status, OrderStatus::Pending);
+    assert_eq!(book.depth(), (0, 2));
+    assert_eq!(book.order(stop.id), Some(&stop));
+
+    let result: MatchResult = book
+        .submit(
+            stop_test_order(3, OrderSide::Bid, 100, 2),
+            test_executioner(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(result.order_status, OrderStatus::FullMatch);
+    assert_eq!(result.triggered, vec![stop.id]);
+    assert!(book.order(stop.id).is_none());
+    assert!(book.buy_stops.is_empty());
+
+    /* the triggered stop took the rest of the liquidity at 100 */
+    assert_eq!(book.depth(), (0, 1));
+    assert_eq!(book.asks.keys().collect::<Vec<_>>(), vec![&U256::from(105)]);
+    let fill: &Fill = book.recent_trades(1)[0];
+    assert_eq!(fill.taker_id, stop.id);
+    assert_eq!(fill.price, U256::from(100));
+    assert_eq!(fill.quantity, U256::from(3));
+    assert_eq!(result.triggered_fills, vec![fill.clone()]);
+    assert_eq!(book.validate(), Ok(()));
+}
+
+#[tokio::test]
+pub async fn test_expired_stop_order_not_triggered() {
+    let mut book: Book = submit_orders(
+        Address::zero(),
+        vec![(Address::from_low_u64_be(1), OrderSide::Ask, 100, 5)],
+    )
+    .await;
+    let now: DateTime<Utc> = Utc::now();
+
+    let stop: Order = Order::new(
+        Address::from_low_u64_be(2),
+        Address::zero(),
+        OrderSide::Bid,
+        U256::zero(),
+        3.into(),
+        now + Duration::minutes(1),
+        now,
+        vec![],
+    )
+    .with_trigger(OrderType::Stop, U256::from(100));
+    book.submit_at(stop.clone(), None, now).await.unwrap();
+    book.drain_events();
+
+    /* the stop lapses while waiting on its trigger, so never trades */
+    let result: MatchResult = book
+        .submit_at(
+            stop_test_order(3, OrderSide::Bid, 100, 2),
+            None,
+            now + Duration::minutes(2),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(result.order_status, OrderStatus::FullMatch);
+    assert!(result.triggered.is_empty());
+    assert!(book.order(stop.id).is_none());
+    assert!(book.buy_stops.is_empty());
+    assert_eq!(book.depth(), (0, 1));
+    assert_eq!(book.recent_trades(10).len(), 1);
+    assert!(book.drain_events().iter().any(|event| matches!(&event.kind,
+        BookEventKind::Expired(order) if order.id == stop.id)));
+    assert_eq!(book.validate(), Ok(()));
+}
+
+#[tokio::test]
+pub async fn test_stop_limit_order_rests_at_limit_price() {
+    let mut book: Book = submit_orders(
+        Address::zero(),
+        vec![(Address::from_low_u64_be(1), OrderSide::Bid, 95, 5)],
+    )
+    .await;
+
+    let stop: Order = stop_test_order(2, OrderSide::Ask, 97, 4)
+        .with_trigger(OrderType::StopLimit, U256::from(95));
+    book.submit(stop.clone(), test_executioner()).await.unwrap();
+
+    let result: MatchResult = book
+        .submit(
+            stop_test_order(3, OrderSide::Ask, 95, 1),
+            test_executioner(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(result.triggered, vec![stop.id]);
+    assert!(book.sell_stops.is_empty());
+    assert_eq!(book.depth(), (1, 1));
+    assert_eq!(book.order(stop.id).unwrap().price, U256::from(97));
+}
+
+#[tokio::test]
+pub async fn test_cancel_untriggered_stop_order() {
+    let mut book: Book = Book::new(Address::zero());
+    let stop: Order = stop_test_order(2, OrderSide::Ask, 97, 4)
+        .with_trigger(OrderType::StopLimit, U256::from(95));
+    book.submit(stop.clone(), test_executioner()).await.unwrap();
+    let sequence: u64 = book.sequence;
+
+    assert_eq!(book.cancel(stop.id), Ok(Some(stop.clone())));
+    assert!(book.order(stop.id).is_none());
+    assert!(book.sell_stops.is_empty());
+    assert!(book.sequence > sequence);
+    assert_eq!(book.cancel(stop.id), Ok(None));
+}
+
+#[tokio::test]
+pub async fn test_invalid_trigger_rejected() {
+    let mut book: Book = Book::new(Address::zero());
+    let invalid: Vec<Order> = vec![
+        stop_test_order(1, OrderSide::Bid, 100, 1)
+            .with_trigger(OrderType::Limit, U256::from(100)),
+        stop_test_order(1, OrderSide::Bid, 100, 1)
+            .with_trigger(OrderType::Stop, U256::zero()),
+        Order {
+            order_type: OrderType::StopLimit,
+            ..stop_test_order(1, OrderSide::Bid, 100, 1)
+        },
+    ];
+
+    for order in invalid {
+        assert_eq!(
+            book.submit(order, test_executioner()).await,
+            Err(BookError::InvalidTrigger)
+        );
+    }
+
+    assert!(book.buy_stops.is_empty());
+    assert_eq!(book.sequence, 0);
+}
+
+#[tokio::test]
+pub async fn test_min_quantity_enforced() {
+    let mut book: Book =
+        Book::new(Address::zero()).with_min_quantity(U256::from(5));
+
+    assert_eq!(
+        book.submit(
+            stop_test_order(1, OrderSide::Bid, 100, 4),
+            test_executioner()
+        )
+        .await,
+        Err(BookError::BelowMinQuantity)
+    );
+    let resting: Order = stop_test_order(1, OrderSide::Bid, 100, 5);
+    assert!(book
+        .submit(resting.clone(), test_executioner())
+        .await
+        .is_ok());
+    assert_eq!(book.depth(), (1, 0));
+
+    /* amendments are held to the same minimum */
+    assert_eq!(
+        book.amend(resting.id, U256::from(100), U256::from(4)),
+        Err(BookError::BelowMinQuantity)
+    );
+    assert_eq!(book.order(resting.id).unwrap().quantity, U256::from(5));
+}
+
+#[tokio::test]
+pub async fn test_max_quantity_enforced() {
+    let mut book: Book =
+        Book::new(Address::zero()).with_max_quantity(Some(U256::from(10)));
+
+    assert_eq!(
+        book.submit(
+            stop_test_order(1, OrderSide::Bid, 100, 11),
+            test_executioner()
+        )
+        .await,
+        Err(BookError::OrderTooLarge(OrderSizeLimit::Quantity(
+            U256::from(10)
+        )))
+    );
+    let resting: Order = stop_test_order(1, OrderSide::Bid, 100, 10);
+    book.submit(resting.clone(), test_executioner())
+        .await
+        .unwrap();
+    assert_eq!(book.depth(), (1, 0));
+
+    /* amendments are held to the same cap */
+    assert_eq!(
+        book.amend(resting.id, U256::from(100), U256::from(11)),
+        Err(BookError::OrderTooLarge(OrderSizeLimit::Quantity(
+            U256::from(10)
+        )))
+    );
+    assert_eq!(book.order(resting.id).unwrap().quantity, U256::from(10));
+
+    /* lifting the cap lets the same order through */
+    assert!(book.set_size_limits(None, None));
+    assert!(!book.set_size_limits(None, None));
+    assert!(book
+        .submit(
+            stop_test_order(1, OrderSide::Bid, 100, 11),
+            test_executioner()
+        )
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+pub async fn test_max_notional_enforced() {
+    /* a price of 10 units makes the notional of each order ten times its
+     * quantity */
+    let mut book: Book =
+        Book::new(Address::zero()).with_max_notional(Some(U256::from(100)));
+
+    assert_eq!(
+        book.submit(
+            stop_test_order(1, OrderSide::Ask, 10 * ONE, 11),
+            test_executioner()
+        )
+        .await,
+        Err(BookError::OrderTooLarge(OrderSizeLimit::Notional(
+            U256::from(100)
+        )))
+    );
+    assert!(book
+        .submit(
+            stop_test_order(1, OrderSide::Ask, 10 * ONE, 10),
+            test_executioner()
+        )
+        .await
+        .is_ok());
+
+    /* stop orders are valued at their trigger price */
+    let stop: Order = stop_test_order(1, OrderSide::Bid, 1, 10)
+        .with_trigger(OrderType::Stop, U256::from(11 * ONE));
+    assert_eq!(
+        book.submit(stop, test_executioner()).await,
+        Err(BookError::OrderTooLarge(OrderSizeLimit::Notional(
+            U256::from(100)
+        )))
+    );
+
+    /* a notional too large to represent exceeds any cap */
+    assert_eq!(
+        book.check_size(U256::max_value(), U256::from(2 * ONE)),
+        Err(BookError::OrderTooLarge(OrderSizeLimit::Notional(
+            U256::from(100)
+        )))
+    );
+}
+
+#[test]
+pub fn test_size_limits_persisted() {
+    let book: Book = Book::new(Address::repeat_byte(0xab))
+        .with_max_quantity(Some(U256::from(1000)))
+        .with_max_notional(Some(U256::from(50) * U256::from(ONE)));
+
+    let external_book: ExternalBook = ExternalBook::from(book.clone());
+    assert_eq!(external_book.max_quantity, Some("1000".to_string()));
+    assert_eq!(
+        external_book.max_notional,
+        Some("50000000000000000000".to_string())
+    );
+    assert_eq!(Book::try_from(external_book).unwrap(), book);
+
+    let restored: Book =
+        serde_json::from_str(&serde_json::to_string(&book).unwrap()).unwrap();
+    assert_eq!(restored, book);
+
+    /* books dumped before the caps existed have none */
+    let mut dumped: serde_json::Value = serde_json::to_value(&book).unwrap();
+    let fields = dumped.as_object_mut().unwrap();
+    fields.remove("max_quantity");
+    fields.remove("max_notional");
+    let restored: Book = serde_json::from_value(dumped).unwrap();
+    assert_eq!(restored.max_quantity, None);
+    assert_eq!(restored.max_notional, None);
+}
+
+#[tokio::test]
+pub async fn test_trader_exposure() {
+    let mut book: Book = Book::new(Address::zero());
+    let trader: Address = Address::from_low_u64_be(1);
+
+    for order in [
+        stop_test_order(1, OrderSide::Bid, 2 * ONE, 5),
+        stop_test_order(1, OrderSide::Ask, 3 * ONE, 10),
+        /* partially fills the ask, leaving 6 */
+        stop_test_order(2, OrderSide::Bid, 3 * ONE, 4),
+        stop_test_order(1, OrderSide::Ask, 1, 5)
+            .with_trigger(OrderType::Stop, U256::from(ONE)),
+    ] {
+        book.submit(order, test_executioner()).await.unwrap();
+    }
+
+    /* the untriggered stop order is counted, but has no quantity or value */
+    assert_eq!(
+        book.trader_exposure(trader),
+        TraderExposure {
+            open_orders: 3,
+            bid_quantity: U256::from(5),
+            ask_quantity: U256::from(6),
+            notional: U256::from(2 * 5 + 3 * 6),
+        }
+    );
+
+    /* the counterparty's order was filled in full */
+    assert_eq!(
+        book.trader_exposure(Address::from_low_u64_be(2)),
+        TraderExposure::default()
+    );
+    assert_eq!(
+        book.trader_exposure(Address::from_low_u64_be(99)),
+        TraderExposure::default()
+    );
+
+    assert_eq!(
+        book.trader_exposure(trader)
+            .combine(book.trader_exposure(trader))
+            .notional,
+        U256::from(56)
+    );
+}
+
+#[tokio::test]
+pub async fn test_max_orders_per_trader_enforced() {
+    let mut book: Book =
+        Book::new(Address::zero()).with_max_orders_per_trader(2);
+    let trader: Address = Address::from_low_u64_be(1);
+    let resting: Order = stop_test_order(1, OrderSide::Bid, 90, 5);
+    let stop: Order = stop_test_order(1, OrderSide::Ask, 80, 5)
+        .with_trigger(OrderType::StopLimit, U256::from(85));
+
+    /* untriggered stop orders count towards the cap */
+    for order in [resting.clone(), stop.clone()] {
+        book.submit(order, test_executioner()).await.unwrap();
+    }
+    assert_eq!(book.open_orders(trader), 2);
+    assert_eq!(
+        book.submit(
+            stop_test_order(1, OrderSide::Bid, 91, 5),
+            test_executioner()
+        )
+        .await,
+        Err(BookError::TooManyOrders)
+    );
+
+    /* immediate-or-cancel orders never rest, so are exempt */
+    let ioc: Order = Order {
+        time_in_force: TimeInForce::ImmediateOrCancel,
+        ..stop_test_order(1, OrderSide::Bid, 91, 5)
+    };
+    assert_eq!(
+        book.submit(ioc, test_executioner())
+            .await
+            .unwrap()
+            .order_status,
+        OrderStatus::Killed
+    );
+
+    /* other traders are unaffected */
+    book.submit(
+        stop_test_order(2, OrderSide::Bid, 89, 5),
+        test_executioner(),
+    )
+    .await
+    .unwrap();
+
+    /* filled and cancelled orders free up their slots */
+    book.submit(
+        stop_test_order(3, OrderSide::Ask, 90, 5),
+        test_executioner(),
+    )
+    .await
+    .unwrap();
+    assert!(book.order(resting.id).is_none());
+    assert_eq!(book.open_orders(trader), 1);
+    book.cancel(stop.id).unwrap();
+    assert_eq!(book.open_orders(trader), 0);
+    assert_index_consistent(&book);
+}
+
+#[tokio::test]
+pub async fn test_duplicate_order_rejected() {
+    let mut book = setup().await;
+    let bid: Order = stop_test_order(20, OrderSide::Bid, 90, 5);
+    let stop: Order = stop_test_order(20, OrderSide::Bid, 99, 5)
+        .with_trigger(OrderType::StopLimit, U256::from(99));
+
+    for order in [bid, stop] {
+        let sequence: u64 = book.sequence;
+
+        assert!(book.submit(order.clone(), test_executioner()).await.is_ok());
+        assert_eq!(
+            book.submit(order, test_executioner()).await,
+            Err(BookError::DuplicateOrder)
+        );
+        assert_eq!(book.sequence, sequence + 1);
+    }
+
+    assert_eq!(book.depth(), (6, 5));
+}
+
+#[tokio::test]
+pub async fn test_client_order_ids() {
+    let mut book: Book = Book::new(Address::zero());
+    let bid: Order = stop_test_order(20, OrderSide::Bid, 90, 5)
+        .with_client_order_id("quote-1".to_string());
+
+    book.submit(bid.clone(), test_executioner()).await.unwrap();
+    assert_eq!(
+        book.order_by_client_id(bid.trader, "quote-1")
+            .map(|order| order.id),
+        Some(bid.id)
+    );
+
+    /* client order IDs are unique per trader among open orders only */
+    let reused: Order = stop_test_order(20, OrderSide::Bid, 89, 5)
+        .with_client_order_id("quote-1".to_string());
+    assert_eq!(
+        book.submit(reused.clone(), test_executioner()).await,
+        Err(BookError::DuplicateClientOrderId)
+    );
+    let other: Order = stop_test_order(21, OrderSide::Bid, 88, 5)
+        .with_client_order_id("quote-1".to_string());
+    assert!(book.submit(other, test_executioner()).await.is_ok());
+
+    book.cancel(bid.id).unwrap();
+    assert!(book.order_by_client_id(bid.trader, "quote-1").is_none());
+    assert!(book
+        .submit(reused.clone(), test_executioner())
+        .await
+        .is_ok());
+
+    /* both sides of a trade are labelled with their client order IDs */
+    let ask: Order = stop_test_order(22, OrderSide::Ask, 89, 5)
+        .with_client_order_id("hit".to_string());
+    let match_result: MatchResult =
+        book.submit(ask, test_executioner()).await.unwrap();
+
+    assert_eq!(
+        match_result.fills[0].maker_client_order_id,
+        Some("quote-1".to_string())
+    );
+    assert_eq!(
+        match_result.fills[0].taker_client_order_id,
+        Some("hit".to_string())
+    );
+    assert!(book.order_by_client_id(reused.trader, "quote-1").is_none());
+}
+
+/* builds a book of resting orders directly, bypassing submission */
+fn restored_book(orders: Vec<Order>) -> Book {
+    let mut book: Book = Book::new(Address::zero());
+
+    for order in orders {
+        let side = match order.side {
+            OrderSide::Bid => &mut book.bids,
+            OrderSide::Ask => &mut book.asks,
+        };
+        side.entry(order.price).or_default().push_back(order);
+    }
+
+    book.reindex();
+    book
+}
+
+#[test]
+pub fn test_restored_book_validated() {
+    let bid: Order = stop_test_order(1, OrderSide::Bid, 95, 5);
+    let ask: Order = stop_test_order(2, OrderSide::Ask, 96, 5);
+    assert_eq!(
+        restored_book(vec![bid.clone(), ask.clone()]).check_restored(),
+        Ok(())
+    );
+
+    let crossing: Order = stop_test_order(2, OrderSide::Ask, 95, 5);
+    assert_eq!(
+        restored_book(vec![bid.clone(), crossing]).check_restored(),
+        Err(BookRestoreError::Crossed)
+    );
+
+    let foreign: Order = Order {
+        market: Address::from_low_u64_be(7),
+        ..ask.clone()
+    };
+    assert_eq!(
+        restored_book(vec![bid.clone(), foreign]).check_restored(),
+        Err(BookRestoreError::OrderMarketMismatch(ask.id))
+    );
+
+    let overfilled: Order = Order {
+        remaining: U256::from(6),
+        ..ask.clone()
+    };
+    assert_eq!(
+        restored_book(vec![overfilled]).check_restored(),
+        Err(BookRestoreError::RemainingExceedsQuantity(ask.id))
+    );
+
+    assert_eq!(
+        restored_book(vec![bid.clone(), bid.clone()]).check_restored(),
+        Err(BookRestoreError::DuplicateOrder(bid.id))
+    );
+
+    let tagged: Order = bid.clone().with_client_order_id("a".to_string());
+    let retagged: Order = stop_test_order(1, OrderSide::Bid, 94, 5)
+        .with_client_order_id("a".to_string());
+    assert_eq!(
+        restored_book(vec![tagged, retagged]).check_restored(),
+        Err(BookRestoreError::DuplicateClientOrderId(bid.id))
+    );
+
+    let mut misplaced: Book = restored_book(vec![bid.clone()]);
+    let orders = misplaced.bids.remove(&bid.price).unwrap();
+    misplaced.bids.insert(U256::from(94), orders);
+    assert_eq!(
+        misplaced.check_restored(),
+        Err(BookRestoreError::LevelMismatch(bid.id))
+    );
+}
+
+#[test]
+pub fn test_malformed_external_book_rejected() {
+    let mut external: ExternalBook =
+        ExternalBook::from(Book::new(Address::zero()));
+    external.ltp = "not a number".to_string();
+
+    assert_eq!(
+        Book::try_from(external),
+        Err(BookRestoreError::Malformed(
+            OrderParseError::InvalidDecimal { field: "ltp" }
+        ))
+    );
+}
+
+#[tokio::test]
+pub async fn test_restored_order_keeps_id_and_remaining() {
+    let market: Address = Address::zero();
+    let mut book: Book = submit_orders(
+        market,
+        vec![(Address::from_low_u64_be(1), OrderSide::Ask, 100, 10)],
+    )
+    .await;
+    book.submit(
+        stop_test_order(2, OrderSide::Bid, 100, 4),
+        test_executioner(),
+    )
+    .await
+    .unwrap();
+
+    /* an ID the external system holds that we wouldn't compute ourselves */
+    let original: OrderId = OrderId::from_low_u64_be(42);
+    let mut external: ExternalBook = ExternalBook::from(book);
+    external.asks[0].1[0].id = format!("{:?}", original);
+
+    let mut restored: Book = Book::try_from(external).unwrap();
+    let cancelled: Order = restored.cancel(original).unwrap().unwrap();
+
+    assert_eq!(cancelled.id, original);
+    assert_eq!(cancelled.remaining, U256::from(6));
+    assert_eq!(restored.depth(), (0, 0));
+}
+
+#[test]
+pub fn test_restored_book_keeps_client_order_ids() {
+    let bid: Order = stop_test_order(1, OrderSide::Bid, 95, 5)
+        .with_client_order_id("quote-1".to_string());
+    let book: Book = restored_book(vec![bid.clone()]);
+
+    let restored: Book =
+        Book::try_from(ExternalBook::from(book.clone())).unwrap();
+
+    /* timestamps are only kept to the second externally */
+    assert_eq!(restored.client_orders, book.client_orders);
+    assert_eq!(
+        restored
+            .order_by_client_id(bid.trader, "quote-1")
+            .map(|t| (t.id, t.client_order_id.clone())),
+        Some((bid.id, bid.client_order_id.clone()))
+    );
+}
+
+#[tokio::test]
+pub async fn test_book_events() {
+    let mut book: Book = Book::new(Address::zero());
+    let ask: Order = stop_test_order(1, OrderSide::Ask, 100, 10);
+    let bid: Order = stop_test_order(2, OrderSide::Bid, 100, 15);
+
+    for order in [ask.clone(), bid.clone()] {
+        book.submit(order, test_executioner()).await.unwrap();
+    }
+    book.amend(bid.id, 100.into(), 12.into()).unwrap();
+    book.cancel(bid.id).unwrap();
+
+    let events: Vec<BookEvent> = book.drain_events();
+    let kinds: Vec<&str> = events
+        .iter()
+        .map(|event| match &event.kind {
+            BookEventKind::Added(order) if order.id == ask.id => "add ask",
+            BookEventKind::Fill {
+                fill,
+                maker_remaining,
+                taker_remaining,
+            } if fill.maker_id == ask.id
+                && maker_remaining.is_zero()
+                && *taker_remaining == U256::from(5) =>
+            {
+                "fill"
+            }
+            BookEventKind::Added(order) if order.id == bid.id => "add bid",
+            BookEventKind::Amended(order) if order.id == bid.id => "amend",
+            BookEventKind::Cancelled(order) if order.id == bid.id => "cancel",
+            _ => "unexpected",
+        })
+        .collect();
+
+    assert_eq!(kinds, vec!["add ask", "fill", "add bid", "amend", "cancel"]);
+
+    /* each event carries the sequence number of the book right after it */
+    let sequences: Vec<u64> = events.iter().map(|e| e.sequence).collect();
+    assert_eq!(sequences, (1..=5).collect::<Vec<u64>>());
+    assert_eq!(book.sequence, 5);
+    assert!(book.drain_events().is_empty());
+}
+
+#[tokio::test]
+pub async fn test_expiry_events() {
+    let mut book: Book = Book::new(Address::zero());
+    let ask: Order = stop_test_order(1, OrderSide::Ask, 100, 10);
+    book.submit(ask.clone(), test_executioner()).await.unwrap();
+    book.drain_events();
+
+    /* let the resting ask lapse, and have it drained by the next order to
+     * reach it */
+    book.order_mut(ask.id).unwrap().expiration =
+        Utc::now() - Duration::seconds(1);
+    book.submit(
+        stop_test_order(2, OrderSide::Bid, 100, 1),
+        test_executioner(),
+    )
+    .await
+    .unwrap();
+
+    let events: Vec<BookEvent> = book.drain_events();
+    assert_eq!(events.len(), 2);
+    assert!(matches!(&events[1].kind,
+        BookEventKind::Expired(order) if order.id == ask.id));
+    assert_eq!(events[1].sequence, book.sequence);
+}
+
+#[tokio::test]
+pub async fn test_filled_makers_drained() {
+    let mut book: Book = Book::new(Address::zero());
+    let first: Order = stop_test_order(1, OrderSide::Ask, 100, 10);
+    let second: Order = stop_test_order(2, OrderSide::Ask, 100, 10);
+    let deeper: Order = stop_test_order(3, OrderSide::Ask, 101, 10);
+    for order in [first.clone(), second.clone(), deeper.clone()] {
+        book.submit(order, test_executioner()).await.unwrap();
+    }
+
+    /* the first maker is consumed, and is gone as soon as it's filled */
+    book.submit(
+        stop_test_order(4, OrderSide::Bid, 100, 15),
+        test_executioner(),
+    )
+    .await
+    .unwrap();
+    assert!(book.order(first.id).is_none());
+    assert_eq!(book.order(second.id).unwrap().remaining, U256::from(5));
+    assert_eq!(book.asks[&U256::from(100)].len(), 1);
+
+    /* as is a level, once every order at it is */
+    book.submit(
+        stop_test_order(5, OrderSide::Bid, 101, 5),
+        test_executioner(),
+    )
+    .await
+    .unwrap();
+    assert!(book.order(second.id).is_none());
+    assert!(!book.asks.contains_key(&U256::from(100)));
+    assert_eq!(book.depth(), (0, 1));
+    assert!(book.validate().is_ok());
+}
+
+#[tokio::test]
+pub async fn test_level_totals_maintained() {
+    let mut book: Book = Book::new(Address::zero());
+    let first: Order = stop_test_order(1, OrderSide::Ask, 100, 10);
+    let second: Order = stop_test_order(2, OrderSide::Ask, 100, 20);
+    for order in [first.clone(), second.clone()] {
+        book.submit(order, test_executioner()).await.unwrap();
+    }
+    let total = |book: &Book| book.asks[&U256::from(100)].total_remaining();
+    assert_eq!(total(&book), U256::from(30));
+
+    /* fills, amendments and cancellations are each reflected */
+    book.submit(
+        stop_test_order(3, OrderSide::Bid, 100, 4),
+        test_executioner(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(total(&book), U256::from(26));
+
+    book.amend(second.id, U256::from(100), U256::from(15))
+        .unwrap();
+    assert_eq!(total(&book), U256::from(21));
+
+    book.cancel(first.id).unwrap();
+    assert_eq!(total(&book), U256::from(15));
+    assert_eq!(
+        book.aggregated_depth(0),
+        (vec![], vec![(U256::from(100), U256::from(15))])
+    );
+    assert!(book.validate().is_ok());
+
+    /* levels serialise as their orders alone, their totals recomputed */
+    let json: String = serde_json::to_string(&book).unwrap();
+    assert!(!json.contains("total_remaining"));
+    let restored: Book = serde_json::from_str(&json).unwrap();
+    assert_eq!(total(&restored), U256::from(15));
+    assert_eq!(restored, book);
+}
+
+/* builds a bid at the given price expiring at the given time */
+fn expiring_bid(trader: u64, price: u64, expiration: DateTime<Utc>) -> Order {
+    Order::new(
+        Address::from_low_u64_be(trader),
+        Address::zero(),
+        OrderSide::Bid,
+        price.into(),
+        10.into(),
+        expiration,
+        Utc::now(),
+        vec![],
+    )
+}
+
+#[tokio::test]
+pub async fn test_purge_expired_retains_fifo_order() {
+    let mut book: Book = Book::new(Address::zero());
+    let soon: DateTime<Utc> = Utc::now() + Duration::hours(1);
+    let later: DateTime<Utc> = soon + Duration::hours(1);
+
+    /* expired and live orders interleaved at the same price level */
+    let bids: Vec<Order> = vec![
+        expiring_bid(1, 100, far_future()),
+        expiring_bid(2, 100, soon),
+        expiring_bid(3, 100, far_future()),
+        expiring_bid(4, 100, soon),
+        expiring_bid(5, 100, far_future()),
+        expiring_bid(6, 90, soon),
+    ];
+    let stop: Order = expiring_bid(7, 110, soon)
+        .with_trigger(OrderType::StopLimit, U256::from(105));
+
+    for order in bids.iter().chain(std::iter::once(&stop)) {
+        book.submit(order.clone(), test_executioner())
+            .await
+            .unwrap();
+    }
+    book.drain_events();
+    let sequence: u64 = book.sequence;
+
+    let purged: Vec<OrderId> =
+        book.purge_expired(later).iter().map(|t| t.id).collect();
+    assert_eq!(purged, vec![bids[5].id, bids[1].id, bids[3].id, stop.id]);
+
+    /* survivors keep their time priority */
+    let survivors: Vec<OrderId> =
+        book.bids[&U256::from(100)].iter().map(|t| t.id).collect();
+    assert_eq!(survivors, vec![bids[0].id, bids[2].id, bids[4].id]);
+    assert!(!book.bids.contains_key(&U256::from(90)));
+    assert!(book.buy_stops.is_empty());
+    assert_eq!(book.depth(), (3, 0));
+    assert_index_consistent(&book);
+
+    /* each purged order is reported as expired */
+    let events: Vec<BookEvent> = book.drain_events();
+    assert_eq!(events.len(), 4);
+    assert!(events
+        .iter()
+        .all(|t| matches!(t.kind, BookEventKind::Expired(_))));
+    assert_eq!(book.sequence, sequence + 4);
+    assert_eq!(book.validate(), Ok(()));
+}
+
+#[tokio::test]
+pub async fn test_purge_expired_without_expired_orders() {
+    let mut book: Book = Book::new(Address::zero());
+    let bid: Order = expiring_bid(1, 100, Utc::now() + Duration::hours(1));
+    book.submit(bid.clone(), test_executioner()).await.unwrap();
+    book.drain_events();
+    let expected: Book = book.clone();
+
+    assert!(book.purge_expired(Utc::now()).is_empty());
+    assert_eq!(book, expected);
+    assert!(book.drain_events().is_empty());
+}
+
+#[tokio::test]
+pub async fn test_stats_track_fills() {
+    let market: Address = Address::zero();
+    let mut book: Book = submit_orders(
+        market,
+        vec![
+            (Address::from_low_u64_be(1), OrderSide::Ask, 100, 5 * ONE),
+            (Address::from_low_u64_be(1), OrderSide::Ask, 110, 5 * ONE),
+        ],
+    )
+    .await;
+    let now: DateTime<Utc> = Utc::now();
+    assert_eq!(book.stats(now).trades, 0);
+    assert_eq!(book.stats(now).high, None);
+
+    let bid: Order = Order::new(
+        Address::from_low_u64_be(2),
+        market,
+        OrderSide::Bid,
+        110.into(),
+        (8 * ONE).into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+    book.submit(bid, test_executioner()).await.unwrap();
+
+    assert_eq!(
+        book.stats(Utc::now()),
+        Stats {
+            volume: U256::from(8 * ONE),
+            notional: U256::from(5 * 100 + 3 * 110),
+            trades: 2,
+            high: Some(U256::from(110)),
+            low: Some(U256::from(100)),
+        }
+    );
+
+    /* the statistics don't outlive their window */
+    let stats: Stats = book.stats(Utc::now() + Duration::hours(25));
+    assert_eq!(stats.volume, U256::zero());
+    assert_eq!(stats.trades, 0);
+    assert_eq!(stats.low, None);
+}
+
+/* builds a trade of the given price and quantity (in whole units) made at
+ * the given time */
+fn trade_at(price: u64, quantity: u64, timestamp: DateTime<Utc>) -> Fill {
+    Fill {
+        trade_id: 0,
+        maker_id: OrderId::zero(),
+        taker_id: OrderId::zero(),
+        maker_address: Address::zero(),
+        taker_address: Address::zero(),
+        price: price.into(),
+        quantity: U256::from(quantity * ONE),
+        timestamp,
+        maker_client_order_id: None,
+        taker_client_order_id: None,
+        maker_fee: U256::zero(),
+        taker_fee: U256::zero(),
+    }
+}
+
+#[test]
+pub fn test_stats_age_out_hourly() {
+    let start: DateTime<Utc> = Utc.timestamp(3600 * 450_000, 0);
+    let mut stats: TradeStats = TradeStats::default();
+    stats.record(&trade_at(100, 1, start + Duration::minutes(59)));
+    stats.record(&trade_at(200, 2, start + Duration::hours(23)));
+
+    assert_eq!(
+        stats.summary(start + Duration::hours(23)),
+        Stats {
+            volume: U256::from(3 * ONE),
+            notional: U256::from(500),
+            trades: 2,
+            high: Some(U256::from(200)),
+            low: Some(U256::from(100)),
+        }
+    );
+
+    /* the first hour falls out of the window as a whole */
+    let summary: Stats = stats.summary(start + Duration::hours(24));
+    assert_eq!(summary.volume, U256::from(2 * ONE));
+    assert_eq!(summary.trades, 1);
+    assert_eq!(summary.low, Some(U256::from(200)));
+
+    /* and is dropped once a later trade is recorded */
+    stats.record(&trade_at(150, 4, start + Duration::hours(30)));
+    let mut expected: TradeStats = TradeStats::default();
+    expected.record(&trade_at(200, 2, start + Duration::hours(23)));
+    expected.record(&trade_at(150, 4, start + Duration::hours(30)));
+    assert_eq!(stats, expected);
+    assert_eq!(
+        stats.summary(start + Duration::hours(30)).volume,
+        U256::from(6 * ONE)
+    );
+}
+
+#[test]
+pub fn test_fee_rounding() {
+    let price: U256 = U256::from(1_500_000_000_000_000_000u64); /* 1.5 */
+
+    /* no fee is charged at a zero rate, however large the trade */
+    assert_eq!(Book::fee(price, U256::max_value(), 0), U256::zero());
+
+    /* a notional of 1500004.5 is rounded down, as is its fee of 4500.012 */
+    assert_eq!(
+        Book::fee(price, U256::from(1_000_003), 30),
+        U256::from(4500)
+    );
+    assert_eq!(Book::fee(price, U256::from(3), 30), U256::zero());
+}
+
+#[test]
+pub fn test_fee_large_values() {
+    let one: U256 = U256::exp10(18);
+
+    /* the notional overflows 256 bits before it's scaled back down */
+    let price: U256 = U256::one() << 200;
+    assert_eq!(Book::fee(price, one, BPS_PER_UNIT), price);
+
+    let price: U256 = U256::one() << 255;
+    assert_eq!(
+        Book::fee(price, one, 1),
+        U256::from_dec_str(
+            "5789604461865809771178549250434395392663499233282028201972879200395656481"
+        )
+        .unwrap()
+    );
+
+    /* fees on notionals too large for 256 bits saturate */
+    assert_eq!(Book::fee(price, one * 3, 1), U256::max_value());
+    assert_eq!(
+        Book::fee(U256::max_value(), U256::max_value(), BPS_PER_UNIT),
+        U256::max_value()
+    );
+}
+
+#[tokio::test]
+pub async fn test_fills_carry_fees() {
+    let market: Address = Address::zero();
+    let maker: Address = Address::from_low_u64_be(1);
+    let quantity: u64 = 3_000_000_000_000_000_001;
+    let mut book: Book = submit_orders(
+        market,
+        vec![(maker, OrderSide::Ask, 2_000_000_000_000_000_000, quantity)],
+    )
+    .await
+    .with_maker_fee_bps(10)
+    .with_taker_fee_bps(25);
+    book.drain_events();
+
+    let taker: Order = Order::new(
+        Address::from_low_u64_be(2),
+        market,
+        OrderSide::Bid,
+        U256::exp10(18) * 2,
+        quantity.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+
+    let simulated: MatchResult = book.simulate(&taker);
+    let result: MatchResult =
+        book.submit(taker, test_executioner()).await.unwrap();
+
+    /* on a notional of 6.000000000000000002, rounded down */
+    assert_eq!(result.fills.len(), 1);
+    assert_eq!(
+        result.fills[0].maker_fee,
+        U256::from(6_000_000_000_000_000u64)
+    );
+    assert_eq!(
+        result.fills[0].taker_fee,
+        U256::from(15_000_000_000_000_000u64)
+    );
+    assert_eq!(
+        without_timestamps(simulated),
+        without_timestamps(result.clone())
+    );
+
+    /* the fees are published along with the fill */
+    match book.drain_events().first().map(|event| &event.kind) {
+        Some(BookEventKind::Fill { fill, .. }) => {
+            assert_eq!(fill, &result.fills[0])
+        }
+        other => panic!("Expected a fill, got {:?}", other),
+    }
+
+    let external: ExternalFill = ExternalFill::from(result.fills[0].clone());
+    assert_eq!(external.maker_fee, "6000000000000000");
+    assert_eq!(external.taker_fee, "15000000000000000");
+}
+
+#[tokio::test]
+pub async fn test_match_result_lists_makers() {
+    let market: Address = Address::zero();
+    let mut book: Book = submit_orders(
+        market,
+        vec![
+            (Address::from_low_u64_be(1), OrderSide::Ask, 100, 10),
+            (Address::from_low_u64_be(2), OrderSide::Ask, 101, 10),
+            (Address::from_low_u64_be(3), OrderSide::Ask, 102, 10),
+        ],
+    )
+    .await;
+    let (emptied, reduced): (OrderId, OrderId) = {
+        let asks: Vec<&Order> = book.asks.values().flatten().collect();
+        (asks[0].id, asks[1].id)
+    };
+
+    let taker: Order = Order::new(
+        Address::from_low_u64_be(4),
+        market,
+        OrderSide::Bid,
+        101.into(),
+        14.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+
+    let simulated: MatchResult = book.simulate(&taker);
+    let result: MatchResult =
+        book.submit(taker, test_executioner()).await.unwrap();
+
+    assert_eq!(result.closed_makers, vec![emptied]);
+    assert_eq!(result.reduced_makers, vec![(reduced, U256::from(6))]);
+    assert_eq!(without_timestamps(simulated), without_timestamps(result));
+    assert_eq!(book.order(reduced).unwrap().remaining, U256::from(6));
+}
+
+/* the kinds of the events in the history of an order, oldest first */
+fn history_kinds(book: &Book, id: OrderId) -> Vec<OrderEventKind> {
+    book.order_history(id)
+        .map(|record| record.events.iter().map(|event| event.kind).collect())
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+pub async fn test_order_history_lifecycle() {
+    let mut book: Book = Book::new(Address::zero());
+    let soon: DateTime<Utc> = Utc::now() + Duration::hours(1);
+
+    let ask: Order = Order::new(
+        Address::from_low_u64_be(1),
+        Address::zero(),
+        OrderSide::Ask,
+        100.into(),
+        10.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+    let filled: Order = Order::new(
+        Address::from_low_u64_be(2),
+        Address::zero(),
+        OrderSide::Bid,
+        100.into(),
+        4.into(),
+        far_future(),
+        Utc::now(),
+        vec![],
+    );
+    let killed: Order = expiring_bid(3, 100, far_future())
+        .with_time_in_force(TimeInForce::ImmediateOrCancel);
+    let cancelled: Order = expiring_bid(4, 90, far_future());
+    let expired: Order = expiring_bid(5, 80, soon);
+
+    for order in [
+        ask.clone(),
+        filled.clone(),
+        killed.clone(),
+        cancelled.clone(),
+        expired.clone(),
+    ] {
+        book.submit(order, test_executioner()).await.unwrap();
+    }
+    book.cancel(cancelled.id).unwrap();
+    book.purge_expired(soon + Duration::hours(1));
+
+    assert_eq!(
+        history_kinds(&book, ask.id),
+        vec![
+            OrderEventKind::Accepted,
+            OrderEventKind::PartiallyFilled,
+            OrderEventKind::Filled
+        ]
+    );
+    assert_eq!(
+        history_kinds(&book, filled.id),
+        vec![OrderEventKind::Accepted, OrderEventKind::Filled]
+    );
+    assert_eq!(
+        history_kinds(&book, killed.id),
+        vec![
+            OrderEventKind::Accepted,
+            OrderEventKind::PartiallyFilled,
+            OrderEventKind::Killed
+        ]
+    );
+    assert_eq!(
+        history_kinds(&book, cancelled.id),
+        vec![OrderEventKind::Accepted, OrderEventKind::Cancelled]
+    );
+    assert_eq!(
+        history_kinds(&book, expired.id),
+        vec![OrderEventKind::Accepted, OrderEventKind::Expired]
+    );
+
+    /* fills carry the trade, and every event the unfilled amount after it */
+    let events: &VecDeque<OrderEvent> =
+        &book.order_history(ask.id).unwrap().events;
+    assert_eq!(events[1].price, Some(100.into()));
+    assert_eq!(events[1].quantity, Some(4.into()));
+    assert_eq!(events[1].remaining, U256::from(6));
+    assert_eq!(events[2].quantity, Some(6.into()));
+    assert_eq!(events[2].remaining, U256::zero());
+    assert_eq!(
+        book.order_history(killed.id).unwrap().events[2].remaining,
+        U256::from(4)
+    );
+    assert_eq!(events[0].price, None);
+
+    /* orders the book never saw have no history */
+    assert!(book.order_history(OrderId::zero()).is_none());
+}
+
+fn level_change(side: OrderSide, price: u64, new_total: u64) -> LevelChange {
+    LevelChange {
+        side,
+        price: price.to_string(),
+        new_total: new_total.to_string(),
+    }
+}
+
+#[tokio::test]
+pub async fn test_book_deltas() {
+    let mut book: Book = Book::new(Address::zero());
+    let ask: Order = stop_test_order(1, OrderSide::Ask, 100, 10);
+    let other: Order = stop_test_order(3, OrderSide::Ask, 101, 5);
+
+    for order in [ask.clone(), other.clone()] {
+        book.submit(order, test_executioner()).await.unwrap();
+    }
+    book.drain_events();
+    book.submit(
+        stop_test_order(2, OrderSide::Bid, 100, 4),
+        test_executioner(),
+    )
+    .await
+    .unwrap();
+    book.drain_events();
+    book.amend(other.id, 102.into(), 5.into()).unwrap();
+    book.cancel(ask.id).unwrap();
+    book.drain_events();
+
+    /* each delta carries the net change to the levels since the last one */
+    let deltas: Vec<BookDelta> = book.deltas_since(0).unwrap();
+    assert_eq!(deltas.len(), 3);
+    assert_eq!(
+        deltas[0].changes,
+        vec![
+            level_change(OrderSide::Ask, 100, 10),
+            level_change(OrderSide::Ask, 101, 5)
+        ]
+    );
+    assert_eq!((deltas[0].prev_seq, deltas[0].seq), (0, 2));
+    assert!(deltas[0].fills.is_empty());
+    assert_eq!(
+        deltas[1].changes,
+        vec![level_change(OrderSide::Ask, 100, 6)]
+    );
+    assert_eq!((deltas[1].prev_seq, deltas[1].seq), (2, 3));
+    assert_eq!(deltas[1].fills.len(), 1);
+    assert_eq!(deltas[1].fills[0].quantity, "4");
+    assert_eq!(
+        deltas[2].changes,
+        vec![
+            level_change(OrderSide::Ask, 100, 0),
+            level_change(OrderSide::Ask, 101, 0),
+            level_change(OrderSide::Ask, 102, 5)
+        ]
+    );
+    assert_eq!(deltas[2].seq, book.sequence);
+
+    /* catching up from any retained point yields the deltas after it */
+    assert_eq!(book.deltas_since(2).unwrap(), deltas[1..].to_vec());
+    assert_eq!(book.deltas_since(book.sequence), Some(vec![]));
+    assert_eq!(book.deltas_since(book.sequence + 1), None);
+
+    /* nothing drained, nothing recorded */
+    assert!(book.drain_events().is_empty());
+    assert_eq!(book.deltas_since(0).unwrap().len(), 3);
+}
+
+#[tokio::test]
+pub async fn test_book_deltas_bounded() {
+    let mut book: Book = Book::new(Address::zero());
+
+    for price in 1..=(MAX_DELTAS as u64 + 1) {
+        book.submit(
+            stop_test_order(1, OrderSide::Bid, price, 1),
+            test_executioner(),
+        )
+        .await
+        .unwrap();
+        book.drain_events();
+    }
+
+    /* the oldest delta has been evicted, so catching up from before it
+     * requires a snapshot */
+    assert_eq!(book.deltas.len(), MAX_DELTAS);
+    assert_eq!(book.deltas_since(0), None);
+    assert_eq!(book.deltas_since(1).unwrap().len(), MAX_DELTAS);
+
+    /* as does catching up with a restored book */
+    let restored: Book =
+        Book::try_from(ExternalBook::from(book.clone())).unwrap();
+    assert_eq!(restored.deltas_since(book.sequence), Some(vec![]));
+    assert_eq!(restored.deltas_since(book.sequence - 1), None);
 }
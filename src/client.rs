@@ -0,0 +1,227 @@
+//! Contains a typed client for the HTTP API of the OME
+use reqwest::{
+    header, Client, Method, RequestBuilder, Response, StatusCode, Url,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
+use web3::types::Address;
+
+use crate::api::{
+    ApiError, ApiVersion, CreateBookRequest, CreateOrderRequest, MatchResponse,
+    OmeResponse, OrderView, UserOrdersResponse,
+};
+use crate::book::ExternalBook;
+use crate::order::{ExternalOrder, OrderId};
+
+/// Represents the failure of a request made via `OmeClient`
+///
+/// Failures to reach the OME (or to understand it) are distinguished from
+/// errors returned by the OME itself, the most common of which have variants
+/// of their own.
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("Invalid URL {0}")]
+    InvalidUrl(String),
+    #[error("Request to {url} failed: {message}")]
+    HttpError { url: Url, message: String },
+    #[error("{url} responded with {status}")]
+    BadStatus { url: Url, status: StatusCode },
+    #[error("Invalid response from {url}: {message}")]
+    InvalidResponse { url: Url, message: String },
+    #[error("Market does not exist")]
+    NoSuchBook,
+    #[error("Order does not exist in this market")]
+    NoSuchOrder,
+    #[error("Market already exists")]
+    BookExists,
+    #[error("{} ({})", .0.message, .0.code)]
+    Api(ApiError),
+}
+
+impl From<ApiError> for ClientError {
+    fn from(error: ApiError) -> Self {
+        match error.code.as_str() {
+            "MARKET_NOT_FOUND" => Self::NoSuchBook,
+            "ORDER_NOT_FOUND" => Self::NoSuchOrder,
+            "MARKET_EXISTS" => Self::BookExists,
+            _ => Self::Api(error),
+        }
+    }
+}
+
+/// Represents a connection to the OME, for integrators such as keepers and
+/// market makers
+///
+/// Cloning an `OmeClient` is cheap, and clones share the same pool of
+/// connections.
+#[derive(Clone, Debug)]
+pub struct OmeClient {
+    client: Client,
+    base: Url, /* ends in a slash, so that paths are joined onto it */
+    admin_token: Option<String>, /* sent to routes reserved for admins */
+}
+
+impl OmeClient {
+    /// Constructor for the `OmeClient` type
+    pub fn new(base_url: &str) -> Result<Self, ClientError> {
+        let mut base: Url = match Url::parse(base_url) {
+            Ok(t) if !t.cannot_be_a_base() => t,
+            _ => return Err(ClientError::InvalidUrl(base_url.to_string())),
+        };
+
+        /* without a trailing slash, joining would replace the last segment */
+        if !base.path().ends_with('/') {
+            let path: String = format!("{}/", base.path());
+            base.set_path(&path);
+        }
+
+        Ok(Self {
+            client: Client::new(),
+            base,
+            admin_token: None,
+        })
+    }
+
+    /// Sets the token presented to routes reserved for admins (i.e., book
+    /// creation and bulk cancellation)
+    pub fn with_admin_token(mut self, token: &str) -> Self {
+        self.admin_token = Some(token.to_string());
+        self
+    }
+
+    /// Returns the URL of the given path of the API, under the prefix of the
+    /// latest version (see `ApiVersion::LATEST`)
+    pub fn url(&self, path: &str) -> Result<Url, ClientError> {
+        self.base
+            .join(&format!("{}/{}", ApiVersion::LATEST.prefix(), path))
+            .map_err(|_e| ClientError::InvalidUrl(path.to_string()))
+    }
+
+    /// Sends a request to the given path of the API, reading the body of a
+    /// successful response as JSON
+    async fn send<T: Serialize, R: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        payload: Option<&T>,
+    ) -> Result<R, ClientError> {
+        let url: Url = self.url(path)?;
+        let mut request: RequestBuilder =
+            self.client.request(method, url.clone());
+
+        if let Some(payload) = payload {
+            let body: String = match serde_json::to_string(payload) {
+                Ok(t) => t,
+                Err(e) => {
+                    return Err(ClientError::HttpError {
+                        url,
+                        message: e.to_string(),
+                    })
+                }
+            };
+            request = request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(body);
+        }
+
+        if let Some(token) = &self.admin_token {
+            request = request.bearer_auth(token);
+        }
+
+        let response: Response = match request.send().await {
+            Ok(t) => t,
+            Err(e) => {
+                return Err(ClientError::HttpError {
+                    url,
+                    message: e.to_string(),
+                })
+            }
+        };
+        let status: StatusCode = response.status();
+
+        let body: String = match response.text().await {
+            Ok(t) => t,
+            Err(e) => {
+                return Err(ClientError::HttpError {
+                    url,
+                    message: e.to_string(),
+                })
+            }
+        };
+
+        /* errors are answered with the envelope, barring a broken proxy */
+        if !status.is_success() {
+            return match serde_json::from_str::<ApiError>(&body) {
+                Ok(t) => Err(ClientError::from(t)),
+                Err(_e) => Err(ClientError::BadStatus { url, status }),
+            };
+        }
+
+        serde_json::from_str(&body).map_err(|e| ClientError::InvalidResponse {
+            url,
+            message: e.to_string(),
+        })
+    }
+
+    /// Creates a new order book
+    pub async fn create_book(
+        &self,
+        request: &CreateBookRequest,
+    ) -> Result<OmeResponse, ClientError> {
+        self.send(Method::POST, "book", Some(request)).await
+    }
+
+    /// Submits an order to the book of the given market, returning the
+    /// result of matching it
+    pub async fn submit_order(
+        &self,
+        market: Address,
+        request: &CreateOrderRequest,
+    ) -> Result<MatchResponse, ClientError> {
+        let path: String = format!("book/{:x}/order", market);
+        self.send(Method::POST, &path, Some(request)).await
+    }
+
+    /// Retrieves the order book of the given market
+    pub async fn get_book(
+        &self,
+        market: Address,
+    ) -> Result<ExternalBook, ClientError> {
+        let path: String = format!("book/{:x}", market);
+        self.send::<(), _>(Method::GET, &path, None).await
+    }
+
+    /// Retrieves an order and its status from the book of the given market
+    ///
+    /// Closed orders are served for as long as the book retains their
+    /// histories.
+    pub async fn get_order(
+        &self,
+        market: Address,
+        id: OrderId,
+    ) -> Result<OrderView, ClientError> {
+        let path: String = format!("book/{:x}/order/{:x}", market, id);
+        self.send::<(), _>(Method::GET, &path, None).await
+    }
+
+    /// Cancels an open order in the book of the given market, returning the
+    /// order as it was when cancelled
+    pub async fn cancel_order(
+        &self,
+        market: Address,
+        id: OrderId,
+    ) -> Result<ExternalOrder, ClientError> {
+        let path: String = format!("book/{:x}/order/{:x}", market, id);
+        self.send::<(), _>(Method::DELETE, &path, None).await
+    }
+
+    /// Retrieves the open orders of the given trader in every market
+    pub async fn user_orders(
+        &self,
+        user: Address,
+    ) -> Result<UserOrdersResponse, ClientError> {
+        let path: String = format!("user/{:x}/orders", user);
+        self.send::<(), _>(Method::GET, &path, None).await
+    }
+}
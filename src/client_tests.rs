@@ -0,0 +1,258 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use ethereum_types::{Address, U256};
+use tokio::sync::RwLock;
+use warp::http::StatusCode;
+use warp::{Filter, Reply};
+
+use crate::api::{
+    ApiVersion, CreateBookRequest, CreateOrderRequest, MatchResponse,
+    OrderView, OrderViewStatus,
+};
+use crate::args::DEFAULT_MAX_CLOCK_SKEW;
+use crate::book::{ExternalBook, MatchingAlgorithm, SelfTradePolicy};
+use crate::client::{ClientError, OmeClient};
+use crate::executioner::Executioner;
+use crate::handler::{self, DEFAULT_MAX_BODY_SIZE};
+use crate::handler_tests::mock_executioner;
+use crate::order::{ExternalOrder, OrderId, OrderSide, OrderType, TimeInForce};
+use crate::state::OmeState;
+
+const ADMIN_TOKEN: &str = "secret";
+
+/* builds the routes of `main` used by the client over the supplied state,
+ * under the prefix of the version it uses and including the rejection
+ * handling */
+fn routes(
+    state: Arc<RwLock<OmeState>>,
+    executioner: Executioner,
+) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
+    let create_book_state = state.clone();
+    let create_book = warp::path!("book")
+        .and(warp::post())
+        .and(handler::admin(Some(ADMIN_TOKEN.to_string())))
+        .and(handler::json_body(DEFAULT_MAX_BODY_SIZE))
+        .and(warp::any().map(move || create_book_state.clone()))
+        .and_then(handler::create_book_handler);
+    let read_book_state = state.clone();
+    let read_book = warp::path!("book" / Address)
+        .and(warp::get())
+        .and(handler::accept())
+        .and(warp::any().map(move || read_book_state.clone()))
+        .and_then(handler::read_book_handler);
+    let create_order_state = state.clone();
+    let create_order = warp::path!("book" / Address / "order")
+        .and(warp::post())
+        .and(handler::json_body(DEFAULT_MAX_BODY_SIZE))
+        .and(warp::any().map(move || create_order_state.clone()))
+        .and(warp::any().map(move || executioner.clone()))
+        .and(warp::any().map(|| None))
+        .and(
+            warp::any()
+                .map(|| std::time::Duration::from_secs(DEFAULT_MAX_CLOCK_SKEW)),
+        )
+        .and_then(handler::create_order_handler);
+    let read_order_state = state.clone();
+    let read_order = warp::path!("book" / Address / "order" / OrderId)
+        .and(warp::get())
+        .and(handler::accept())
+        .and(warp::any().map(|| ApiVersion::LATEST))
+        .and(warp::any().map(move || read_order_state.clone()))
+        .and_then(handler::read_order_handler);
+    let destroy_order_state = state.clone();
+    let destroy_order = warp::path!("book" / Address / "order" / OrderId)
+        .and(warp::delete())
+        .and(warp::any().map(move || destroy_order_state.clone()))
+        .and_then(handler::destroy_order_handler);
+    let user_orders = warp::path!("user" / Address / "orders")
+        .and(warp::get())
+        .and(warp::query())
+        .and(handler::accept())
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::user_orders_handler);
+
+    warp::path(ApiVersion::LATEST.prefix())
+        .and(
+            create_book
+                .or(read_book)
+                .or(create_order)
+                .or(read_order)
+                .or(destroy_order)
+                .or(user_orders),
+        )
+        .recover(handler::handle_rejection)
+}
+
+/* serves the routes locally over empty engine state, returning a client for
+ * them along with the state */
+fn serve() -> (OmeClient, Arc<RwLock<OmeState>>) {
+    let state: Arc<RwLock<OmeState>> = Arc::new(RwLock::new(OmeState::new()));
+    let executioner: Executioner = mock_executioner(StatusCode::OK);
+    let (address, server) = warp::serve(routes(state.clone(), executioner))
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let client: OmeClient = OmeClient::new(&format!("http://{}", address))
+        .unwrap()
+        .with_admin_token(ADMIN_TOKEN);
+    (client, state)
+}
+
+fn book_request(market: Address) -> CreateBookRequest {
+    CreateBookRequest {
+        market,
+        self_trade_policy: SelfTradePolicy::default(),
+        matching_algorithm: MatchingAlgorithm::default(),
+        tick_size: U256::one(),
+        lot_size: U256::one(),
+        min_quantity: U256::zero(),
+        max_quantity: None,
+        max_notional: None,
+        max_orders_per_trader: 0,
+        maker_fee_bps: 0,
+        taker_fee_bps: 0,
+    }
+}
+
+fn order_request(market: Address, trader: Address) -> CreateOrderRequest {
+    CreateOrderRequest {
+        user: trader,
+        target_tracer: market,
+        side: OrderSide::Bid,
+        price: U256::from(100),
+        amount: U256::from(10),
+        expiration: Utc::now() + Duration::days(1),
+        created: Utc::now(),
+        signed_data: "0x".to_string(),
+        time_in_force: TimeInForce::default(),
+        order_type: OrderType::default(),
+        trigger_price: None,
+        client_order_id: None,
+        nonce: U256::zero(),
+    }
+}
+
+#[tokio::test]
+pub async fn test_client_books() {
+    let (client, _state) = serve();
+    let market: Address = Address::from_low_u64_be(1);
+
+    let created = client.create_book(&book_request(market)).await.unwrap();
+    assert_eq!(created.status, 201);
+
+    let book: ExternalBook = client.get_book(market).await.unwrap();
+    assert_eq!(book.market, format!("{:?}", market));
+    assert_eq!(book.depth, (0, 0));
+
+    /* the most common API errors have variants of their own */
+    assert!(matches!(
+        client.create_book(&book_request(market)).await,
+        Err(ClientError::BookExists)
+    ));
+    assert!(matches!(
+        client.get_book(Address::from_low_u64_be(99)).await,
+        Err(ClientError::NoSuchBook)
+    ));
+
+    /* whereas the rest are passed on as they are */
+    let client: OmeClient = client.clone().with_admin_token("wrong");
+    match client.create_book(&book_request(market)).await {
+        Err(ClientError::Api(e)) => {
+            assert_eq!(e.status, 401);
+            assert_eq!(e.code, "UNAUTHORIZED");
+        }
+        other => panic!("Expected an API error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+pub async fn test_client_orders() {
+    let (client, state) = serve();
+    let market: Address = Address::from_low_u64_be(1);
+    let trader: Address = Address::from_low_u64_be(2);
+    client.create_book(&book_request(market)).await.unwrap();
+
+    let matched: MatchResponse = client
+        .submit_order(market, &order_request(market, trader))
+        .await
+        .unwrap();
+    assert_eq!(matched.resting, "10");
+    assert!(matched.fills.is_empty());
+
+    let id: OrderId = state
+        .read()
+        .await
+        .book(market)
+        .unwrap()
+        .orders_by_trader(trader)[0]
+        .id;
+    assert_eq!(matched.order_id, format!("{:?}", id));
+
+    let view: OrderView = client.get_order(market, id).await.unwrap();
+    assert_eq!(view.status, OrderViewStatus::Open);
+    let order: ExternalOrder = view.order.unwrap();
+    assert_eq!(order.amount_left, "10");
+
+    let orders = client.user_orders(trader).await.unwrap();
+    assert_eq!(orders.len(), 1);
+    assert_eq!(orders[&format!("{:?}", market)], vec![order.clone()]);
+
+    assert_eq!(client.cancel_order(market, id).await.unwrap(), order);
+    let view: OrderView = client.get_order(market, id).await.unwrap();
+    assert_eq!(view.status, OrderViewStatus::Cancelled);
+    assert_eq!(view.order, None);
+    assert!(matches!(
+        client.get_order(market, OrderId::from_low_u64_be(42)).await,
+        Err(ClientError::NoSuchOrder)
+    ));
+    assert!(matches!(
+        client.cancel_order(market, id).await,
+        Err(ClientError::NoSuchOrder)
+    ));
+    assert!(client.user_orders(trader).await.unwrap().is_empty());
+
+    let unknown: Address = Address::from_low_u64_be(99);
+    assert!(matches!(
+        client
+            .submit_order(unknown, &order_request(unknown, trader))
+            .await,
+        Err(ClientError::NoSuchBook)
+    ));
+}
+
+#[tokio::test]
+pub async fn test_client_transport_errors() {
+    assert!(matches!(
+        OmeClient::new("localhost:3000"),
+        Err(ClientError::InvalidUrl(_))
+    ));
+
+    /* failures to reach the OME are told apart from its own errors */
+    let client: OmeClient = OmeClient::new("http://127.0.0.1:1").unwrap();
+    assert!(matches!(
+        client.get_book(Address::zero()).await,
+        Err(ClientError::HttpError { .. })
+    ));
+
+    /* as are responses that aren't from the OME at all */
+    let route = warp::any().map(|| {
+        warp::reply::with_status("Bad gateway", StatusCode::BAD_GATEWAY)
+    });
+    let (address, server) =
+        warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    let client: OmeClient =
+        OmeClient::new(&format!("http://{}/api", address)).unwrap();
+    match client.get_book(Address::zero()).await {
+        Err(ClientError::BadStatus { url, status }) => {
+            assert_eq!(
+                url.path(),
+                format!("/api/v2/book/{:x}", Address::zero())
+            );
+            assert_eq!(status, StatusCode::BAD_GATEWAY);
+        }
+        other => panic!("Expected a bad status, got {:?}", other),
+    }
+}
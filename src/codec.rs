@@ -0,0 +1,95 @@
+//! Contains logic for encoding and decoding the bodies exchanged over the HTTP
+//! API in whichever format a client asks for
+//!
+//! JSON remains the default. High-frequency clients may ask for MessagePack
+//! instead, which carries exactly the same fields (structs are encoded as
+//! maps, so optional fields may still be left out).
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
+
+/// The media type of JSON bodies
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// The media type of MessagePack bodies
+pub const MSGPACK_CONTENT_TYPE: &str = "application/msgpack";
+
+/// Represents the formats bodies may be encoded in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Json,
+    MessagePack,
+}
+
+/// Represents the reasons a body cannot be encoded or decoded
+#[derive(Debug, Error)]
+pub enum CodecError {
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+    #[error("{0}")]
+    EncodeMessagePack(#[from] rmp_serde::encode::Error),
+    #[error("{0}")]
+    DecodeMessagePack(#[from] rmp_serde::decode::Error),
+}
+
+/// Returns whether a media type (as in `Content-Type`, or a single entry of
+/// `Accept`) names MessagePack, ignoring any parameters
+pub fn is_msgpack(media_type: &str) -> bool {
+    let essence: &str = media_type.split(';').next().unwrap_or("").trim();
+    essence.eq_ignore_ascii_case(MSGPACK_CONTENT_TYPE)
+        || essence.eq_ignore_ascii_case("application/x-msgpack")
+}
+
+impl Format {
+    /// Returns the format a client asks to be answered in, given its `Accept`
+    /// header
+    ///
+    /// Clients are answered in JSON unless they accept MessagePack, including
+    /// when they only accept types the API doesn't serve.
+    pub fn from_accept(accept: &str) -> Self {
+        if accept.split(',').any(is_msgpack) {
+            Format::MessagePack
+        } else {
+            Format::Json
+        }
+    }
+
+    /// Returns the format of a request body, given its `Content-Type` header
+    pub fn from_content_type(content_type: &str) -> Self {
+        if is_msgpack(content_type) {
+            Format::MessagePack
+        } else {
+            Format::Json
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Json => JSON_CONTENT_TYPE,
+            Format::MessagePack => MSGPACK_CONTENT_TYPE,
+        }
+    }
+
+    /// Encodes a value in this format
+    pub fn encode<T: Serialize + ?Sized>(
+        &self,
+        value: &T,
+    ) -> Result<Vec<u8>, CodecError> {
+        match self {
+            Format::Json => Ok(serde_json::to_vec(value)?),
+            Format::MessagePack => Ok(rmp_serde::to_vec_named(value)?),
+        }
+    }
+
+    /// Decodes a value from bytes in this format
+    pub fn decode<T: DeserializeOwned>(
+        &self,
+        bytes: &[u8],
+    ) -> Result<T, CodecError> {
+        match self {
+            Format::Json => Ok(serde_json::from_slice(bytes)?),
+            Format::MessagePack => Ok(rmp_serde::from_slice(bytes)?),
+        }
+    }
+}
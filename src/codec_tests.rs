@@ -0,0 +1,379 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt::Debug;
+
+use chrono::{TimeZone, Utc};
+use ethereum_types::{Address, U256};
+use http::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::api::{
+    ApiError, CancelOrdersRequest, CancelOrdersResponse, CancelOutcome,
+    CancelResult, ClientOrderQuery, CreateBookRequest, CreateOrderRequest,
+    CreateWebhookRequest, DepthQuery, DepthResponse, HealthResponse,
+    MatchResponse, OmeResponse, OrderHistoryResponse, OrderView, QuoteResponse,
+    ReplaceOrderResponse, SnapshotQuery, SnapshotResponse, StatsResponse,
+    TickerResponse, TradesQuery, TradesResponse, UpdateBookLimitsRequest,
+    UpdateOrderRequest, UserOrdersQuery, UserOrdersResponse, WebhookResponse,
+};
+use crate::book::{
+    Book, BookError, ExternalBook, ExternalFill, MatchingAlgorithm,
+    OrderSizeLimit, SelfTradePolicy,
+};
+use crate::codec::{is_msgpack, Format};
+use crate::event::SnapshotEvent;
+use crate::order::{
+    ExternalOrder, Order, OrderId, OrderSide, OrderType, TimeInForce,
+};
+use crate::test_util::far_future;
+
+const FORMATS: [Format; 2] = [Format::Json, Format::MessagePack];
+
+/* encodes and decodes the value in every format, checking that nothing is
+ * lost along the way (compared through JSON, as not every type is `Eq`) */
+fn round_trip<T: Serialize + DeserializeOwned>(value: &T) {
+    let expected: serde_json::Value = serde_json::to_value(value).unwrap();
+
+    for format in FORMATS.iter() {
+        let encoded: Vec<u8> = format.encode(value).unwrap();
+        let decoded: T = format.decode(&encoded).unwrap();
+        assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            expected,
+            "{:?}",
+            format
+        );
+    }
+}
+
+/* as `round_trip`, for types that can be compared directly */
+fn round_trip_eq<T: Serialize + DeserializeOwned + PartialEq + Debug>(
+    value: &T,
+) {
+    for format in FORMATS.iter() {
+        let encoded: Vec<u8> = format.encode(value).unwrap();
+        assert_eq!(
+            &format.decode::<T>(&encoded).unwrap(),
+            value,
+            "{:?}",
+            format
+        );
+    }
+}
+
+fn order(trader: u64, side: OrderSide, price: u64, quantity: u64) -> Order {
+    Order::new(
+        Address::from_low_u64_be(trader),
+        Address::from_low_u64_be(1),
+        side,
+        U256::from(price),
+        U256::from(quantity),
+        far_future(),
+        Utc.timestamp(1_600_000_000, 0),
+        vec![],
+    )
+}
+
+/* a book with an ask partially filled by a bid, and the trade between them */
+async fn traded_book() -> (Book, Order, ExternalFill) {
+    let mut book: Book = Book::new(Address::from_low_u64_be(1));
+    let ask: Order = order(2, OrderSide::Ask, 100, 10);
+    book.submit_at(ask.clone(), None, Utc::now()).await.unwrap();
+    book.submit_at(order(3, OrderSide::Bid, 100, 4), None, Utc::now())
+        .await
+        .unwrap();
+
+    let fill: ExternalFill = book.recent_trades(1)[0].clone().into();
+    (book, ask, fill)
+}
+
+fn match_response(fill: &ExternalFill) -> MatchResponse {
+    MatchResponse {
+        status: 200,
+        message: "Partial Match".to_string(),
+        order_id: fill.taker_id.clone(),
+        client_order_id: Some("mm-1".to_string()),
+        sequence: 2,
+        remaining: "6".to_string(),
+        resting: "6".to_string(),
+        average_price: "100".to_string(),
+        fills: vec![fill.clone()],
+        triggered: vec![],
+        closed_makers: vec![],
+        reduced_makers: vec![(fill.maker_id.clone(), "6".to_string())],
+    }
+}
+
+#[test]
+pub fn test_negotiate_format() {
+    assert_eq!(
+        Format::from_accept("application/msgpack"),
+        Format::MessagePack
+    );
+    assert_eq!(
+        Format::from_accept("application/json;q=0.5, application/x-msgpack"),
+        Format::MessagePack
+    );
+    assert_eq!(Format::from_accept("application/json"), Format::Json);
+    assert_eq!(Format::from_accept("*/*"), Format::Json);
+    assert_eq!(Format::from_accept("text/html"), Format::Json);
+    assert_eq!(
+        Format::from_content_type("application/msgpack; charset=binary"),
+        Format::MessagePack
+    );
+    assert_eq!(Format::from_content_type("text/plain"), Format::Json);
+    assert!(is_msgpack("Application/MsgPack"));
+    assert!(!is_msgpack("application/msgpack-ish"));
+}
+
+#[test]
+pub fn test_requests_round_trip() {
+    round_trip_eq(&CreateOrderRequest {
+        user: Address::from_low_u64_be(2),
+        target_tracer: Address::from_low_u64_be(1),
+        side: OrderSide::Ask,
+        price: U256::from(100),
+        amount: U256::max_value(),
+        expiration: Utc.timestamp(1_700_000_000, 0),
+        created: Utc.timestamp(1_600_000_000, 0),
+        signed_data: "0x1234".to_string(),
+        time_in_force: TimeInForce::ImmediateOrCancel,
+        order_type: OrderType::StopLimit,
+        trigger_price: Some(U256::from(99)),
+        client_order_id: Some("mm-1".to_string()),
+        nonce: U256::from(7),
+    });
+    round_trip(&CreateBookRequest {
+        market: Address::from_low_u64_be(1),
+        self_trade_policy: SelfTradePolicy::CancelResting,
+        matching_algorithm: MatchingAlgorithm::ProRata,
+        tick_size: U256::from(5),
+        lot_size: U256::from(1),
+        min_quantity: U256::from(10),
+        max_quantity: Some(U256::from(1000)),
+        max_notional: None,
+        max_orders_per_trader: 100,
+        maker_fee_bps: 1,
+        taker_fee_bps: 5,
+    });
+    round_trip_eq(&UpdateOrderRequest {
+        price: U256::from(101),
+        amount: U256::from(20),
+    });
+    round_trip_eq(&UpdateBookLimitsRequest {
+        max_quantity: None,
+        max_notional: Some(U256::exp10(30)),
+    });
+    round_trip_eq(&CancelOrdersRequest {
+        order_ids: vec![OrderId::from_low_u64_be(1), OrderId::zero()],
+    });
+    round_trip_eq(&CreateWebhookRequest {
+        url: "https://example.com/fills".to_string(),
+        market: Some(Address::from_low_u64_be(1)),
+        user: None,
+        secret: "hunter2".to_string(),
+    });
+    round_trip_eq(&DepthQuery { levels: 25 });
+    round_trip_eq(&SnapshotQuery {
+        since_seq: Some(42),
+    });
+    round_trip_eq(&ClientOrderQuery {
+        user: Address::from_low_u64_be(2),
+    });
+    round_trip_eq(&TradesQuery { limit: 10 });
+    round_trip_eq(&UserOrdersQuery {
+        limit: 10,
+        offset: 20,
+    });
+}
+
+#[test]
+pub fn test_optional_fields_may_be_left_out() {
+    /* as in JSON, structs are encoded as maps so defaults still apply */
+    #[derive(Serialize)]
+    struct Minimal {
+        url: String,
+        secret: String,
+    }
+
+    let encoded: Vec<u8> = Format::MessagePack
+        .encode(&Minimal {
+            url: "https://example.com".to_string(),
+            secret: "s".to_string(),
+        })
+        .unwrap();
+    let request: CreateWebhookRequest =
+        Format::MessagePack.decode(&encoded).unwrap();
+    assert_eq!(request.market, None);
+    assert_eq!(request.user, None);
+}
+
+#[test]
+pub fn test_malformed_msgpack_rejected() {
+    assert!(Format::MessagePack
+        .decode::<CreateOrderRequest>(&[0xc1, 0x00])
+        .is_err());
+    assert!(Format::MessagePack
+        .decode::<CreateOrderRequest>(
+            &Format::MessagePack
+                .encode(&DepthQuery { levels: 1 })
+                .unwrap()
+        )
+        .is_err());
+}
+
+#[tokio::test]
+pub async fn test_responses_round_trip() {
+    let (book, ask, fill) = traded_book().await;
+    let external: ExternalOrder = book.order(ask.id).unwrap().clone().into();
+
+    round_trip(&OmeResponse {
+        status: 200,
+        message: "ok".to_string(),
+    });
+    round_trip(&HealthResponse {
+        status: 200,
+        message: "ready".to_string(),
+        last_market_sync: Some(Utc.timestamp(1_600_000_000, 0)),
+    });
+    round_trip_eq(&CancelOrdersResponse {
+        results: vec![
+            CancelResult {
+                order_id: format!("{:?}", ask.id),
+                outcome: CancelOutcome::Cancelled,
+                remaining: Some("6".to_string()),
+            },
+            CancelResult {
+                order_id: format!("{:?}", OrderId::zero()),
+                outcome: CancelOutcome::NotFound,
+                remaining: None,
+            },
+        ],
+        sequence: 3,
+    });
+    round_trip_eq(&WebhookResponse {
+        id: 1,
+        url: "https://example.com/fills".to_string(),
+        market: None,
+        user: Some(Address::from_low_u64_be(2)),
+    });
+    round_trip_eq(&DepthResponse {
+        bids: vec![],
+        asks: vec![("100".to_string(), "6".to_string())],
+        sequence: book.sequence,
+    });
+    round_trip_eq(&SnapshotResponse::Deltas {
+        seq: book.delta_sequence,
+        deltas: book.deltas_since(0).unwrap_or_default(),
+    });
+    round_trip_eq(&SnapshotResponse::Snapshot {
+        seq: book.sequence,
+        book: book.clone().into(),
+    });
+    round_trip_eq(&TradesResponse {
+        trades: vec![fill.clone()],
+        sequence: book.sequence,
+    });
+    let mut user_orders: UserOrdersResponse = BTreeMap::new();
+    user_orders.insert(format!("{:?}", book.market()), vec![external.clone()]);
+    round_trip_eq(&user_orders);
+    round_trip_eq(&external);
+    round_trip_eq(&OrderHistoryResponse::new(
+        ask.id,
+        book.order_history(ask.id).unwrap(),
+    ));
+    round_trip_eq(
+        &OrderView::new(ask.id, book.order(ask.id), book.order_history(ask.id))
+            .unwrap(),
+    );
+    let bid: OrderId = order(3, OrderSide::Bid, 100, 4).id;
+    round_trip_eq(&OrderView::new(bid, None, book.order_history(bid)).unwrap());
+    round_trip_eq(&TickerResponse::from(book.ticker()));
+    round_trip_eq(&StatsResponse::new(book.stats(Utc::now()), book.ltp));
+    round_trip(&match_response(&fill));
+    round_trip(&ReplaceOrderResponse {
+        cancelled: external,
+        created: match_response(&fill),
+    });
+    round_trip(&QuoteResponse {
+        message: "Full Match".to_string(),
+        order_id: fill.taker_id.clone(),
+        sequence: book.sequence,
+        remaining: "0".to_string(),
+        average_price: "100".to_string(),
+        slippage: "0".to_string(),
+        fills: vec![fill],
+    });
+    round_trip_eq(&ApiError::new(
+        StatusCode::BAD_REQUEST,
+        "INVALID_BODY",
+        "Invalid request body",
+    ));
+    round_trip_eq(&ApiError::from(BookError::OrderTooLarge(
+        OrderSizeLimit::Notional(U256::exp10(30)),
+    )));
+}
+
+/* collects the values of every field with the given name, at any depth */
+fn fields<'a>(
+    value: &'a serde_json::Value,
+    name: &str,
+) -> Vec<&'a serde_json::Value> {
+    match value {
+        serde_json::Value::Object(t) => t
+            .iter()
+            .flat_map(|(key, value)| {
+                let mut found: Vec<&serde_json::Value> = fields(value, name);
+                if key == name {
+                    found.push(value);
+                }
+                found
+            })
+            .collect(),
+        serde_json::Value::Array(t) => {
+            t.iter().flat_map(|value| fields(value, name)).collect()
+        }
+        _ => vec![],
+    }
+}
+
+#[tokio::test]
+pub async fn test_responses_carry_signatures_as_hex() {
+    let mut book: Book = Book::new(Address::from_low_u64_be(1));
+    let mut signed: Order = order(2, OrderSide::Ask, 100, 10);
+    signed.signed_data = vec![0xab; 65];
+    book.submit_at(signed.clone(), None, Utc::now())
+        .await
+        .unwrap();
+
+    let view: OrderView = OrderView::new(
+        signed.id,
+        book.order(signed.id),
+        book.order_history(signed.id),
+    )
+    .unwrap();
+    let payloads: Vec<serde_json::Value> = vec![
+        serde_json::to_value(&view).unwrap(),
+        serde_json::to_value(ExternalBook::from(book.clone())).unwrap(),
+        serde_json::to_value(SnapshotEvent::from(&book)).unwrap(),
+    ];
+
+    /* never as an array of bytes, however the order is served */
+    let hex: serde_json::Value =
+        serde_json::Value::String(format!("0x{}", "ab".repeat(65)));
+    for payload in payloads.iter() {
+        assert_eq!(fields(payload, "signed_data"), vec![&hex], "{}", payload);
+    }
+
+    /* which survives the trip back into an order */
+    let decoded: ExternalBook =
+        serde_json::from_value(payloads[1].clone()).unwrap();
+    assert_eq!(
+        Book::try_from(decoded)
+            .unwrap()
+            .order(signed.id)
+            .map(|t| t.signed_data.clone()),
+        Some(signed.signed_data)
+    );
+}
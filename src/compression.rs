@@ -0,0 +1,158 @@
+//! Contains logic for compressing the larger responses of the API for clients
+//! that accept it
+//!
+//! Compression is negotiated per request from `Accept-Encoding`, so clients
+//! that don't ask for it (or that only accept encodings other than gzip and
+//! deflate) are always answered uncompressed.
+use std::io::Write;
+
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use warp::filters::BoxedFilter;
+use warp::http::header::{HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH, VARY};
+use warp::http::StatusCode;
+use warp::hyper::body::{self, Body};
+use warp::reply::Response;
+use warp::{Filter, Rejection, Reply};
+
+/// Bodies smaller than this (in bytes) are sent as they are, as compressing
+/// them saves next to nothing
+pub const MIN_COMPRESSED_SIZE: usize = 1024;
+
+/// Represents the encodings responses may be compressed with, in order of
+/// preference
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    /// Returns the preferred encoding of those accepted by a client, given its
+    /// `Accept-Encoding` header
+    ///
+    /// Quality values are only honoured so far as an encoding given a quality
+    /// of zero is refused.
+    pub fn negotiate(accept_encoding: &str) -> Option<Self> {
+        let accepted: Vec<&str> = accept_encoding
+            .split(',')
+            .filter_map(|t| {
+                let mut parts = t.split(';').map(str::trim);
+                let name: &str = parts.next()?;
+                let refused: bool = parts.any(|param| {
+                    param.strip_prefix("q=").is_some_and(|q| {
+                        q.parse::<f32>().is_ok_and(|q| q <= 0.0)
+                    })
+                });
+                if refused {
+                    None
+                } else {
+                    Some(name)
+                }
+            })
+            .collect();
+
+        [Encoding::Gzip, Encoding::Deflate]
+            .iter()
+            .copied()
+            .find(|encoding| {
+                accepted.iter().any(|name| {
+                    name.eq_ignore_ascii_case(encoding.name()) || *name == "*"
+                })
+            })
+    }
+
+    /// Compresses the provided bytes
+    pub fn encode(&self, bytes: &[u8]) -> Vec<u8> {
+        /* writing to a vector never fails */
+        match self {
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], Compression::fast());
+                encoder.write_all(bytes).unwrap();
+                encoder.finish().unwrap()
+            }
+            Encoding::Deflate => {
+                let mut encoder = ZlibEncoder::new(vec![], Compression::fast());
+                encoder.write_all(bytes).unwrap();
+                encoder.finish().unwrap()
+            }
+        }
+    }
+}
+
+/// Compresses a response with the given encoding, unless it has no body to
+/// speak of (such as a `304 Not Modified`), is already encoded, or is too
+/// small to be worth it
+pub async fn compress(response: Response, encoding: Encoding) -> Response {
+    let status: StatusCode = response.status();
+    if status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+        || response.headers().contains_key(CONTENT_ENCODING)
+    {
+        return response;
+    }
+
+    let (mut parts, original) = response.into_parts();
+    let bytes = match body::to_bytes(original).await {
+        Ok(t) => t,
+        Err(e) => {
+            warn!("Failed to read response body for compression: {}", e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+
+    /* caches must tell apart the replies to clients that accept it */
+    parts
+        .headers
+        .append(VARY, HeaderValue::from_static("accept-encoding"));
+
+    if bytes.len() < MIN_COMPRESSED_SIZE {
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+
+    parts.headers.remove(CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(CONTENT_ENCODING, HeaderValue::from_static(encoding.name()));
+    Response::from_parts(parts, Body::from(encoding.encode(&bytes)))
+}
+
+/// Wraps a route so that its replies are compressed for clients that accept
+/// it, if `enabled`
+///
+/// Rejections are passed through untouched, so the wrapped route composes
+/// with `recover` and CORS just as it did before.
+pub fn compressed<F, R>(route: F, enabled: bool) -> BoxedFilter<(Response,)>
+where
+    F: Filter<Extract = (R,), Error = Rejection>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    R: Reply + Send + 'static,
+{
+    warp::header::optional::<String>("accept-encoding")
+        .and(route)
+        .and_then(
+            move |accept_encoding: Option<String>, reply: R| async move {
+                let response: Response = reply.into_response();
+                let encoding: Option<Encoding> = accept_encoding
+                    .as_deref()
+                    .filter(|_| enabled)
+                    .and_then(Encoding::negotiate);
+
+                Ok::<Response, Rejection>(match encoding {
+                    Some(t) => compress(response, t).await,
+                    None => response,
+                })
+            },
+        )
+        .boxed()
+}
@@ -0,0 +1,149 @@
+use std::io::Read;
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use ethereum_types::{Address, U256};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use tokio::sync::RwLock;
+use warp::http::header::{CONTENT_ENCODING, VARY};
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+
+use crate::book::Book;
+use crate::book_tests::test_executioner;
+use crate::compression::{self, Encoding, MIN_COMPRESSED_SIZE};
+use crate::handler;
+use crate::order::{Order, OrderSide};
+use crate::state::OmeState;
+
+/* creates engine state holding a single book with enough orders for its
+ * serialized form to be worth compressing */
+async fn setup(orders: u64) -> (Arc<RwLock<OmeState>>, Address) {
+    let market: Address = Address::from_low_u64_be(1);
+    let mut book: Book = Book::new(market);
+
+    for price in 1..=orders {
+        let order: Order = Order::new(
+            Address::from_low_u64_be(2),
+            market,
+            OrderSide::Bid,
+            U256::from(price),
+            U256::from(10),
+            Utc::now() + Duration::days(365),
+            Utc::now(),
+            vec![],
+        );
+        book.submit(order, test_executioner()).await.unwrap();
+    }
+
+    let mut state: OmeState = OmeState::new();
+    state.add_book(book);
+
+    (Arc::new(RwLock::new(state)), market)
+}
+
+/* builds the same book read route as `main` over the supplied state */
+fn read_book_route(
+    state: Arc<RwLock<OmeState>>,
+    enabled: bool,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    compression::compressed(
+        warp::path!("book" / Address)
+            .and(warp::get())
+            .and(handler::accept())
+            .and(warp::any().map(move || state.clone()))
+            .and_then(handler::read_book_handler),
+        enabled,
+    )
+}
+
+#[test]
+pub fn test_negotiate_encoding() {
+    assert_eq!(Encoding::negotiate("gzip"), Some(Encoding::Gzip));
+    assert_eq!(
+        Encoding::negotiate("deflate, gzip;q=0.8"),
+        Some(Encoding::Gzip)
+    );
+    assert_eq!(
+        Encoding::negotiate("gzip;q=0, deflate"),
+        Some(Encoding::Deflate)
+    );
+    assert_eq!(Encoding::negotiate("*"), Some(Encoding::Gzip));
+    assert_eq!(Encoding::negotiate("br, identity"), None);
+    assert_eq!(Encoding::negotiate(""), None);
+}
+
+#[tokio::test]
+pub async fn test_book_compressed_when_accepted() {
+    let (state, market) = setup(20).await;
+    let route = read_book_route(state, true);
+    let path: String = format!("/book/{:x}", market);
+
+    let plain = warp::test::request().path(&path).reply(&route).await;
+    assert_eq!(plain.status(), StatusCode::OK);
+    assert!(plain.headers().get(CONTENT_ENCODING).is_none());
+    assert!(plain.body().len() >= MIN_COMPRESSED_SIZE);
+
+    let gzipped = warp::test::request()
+        .path(&path)
+        .header("accept-encoding", "gzip, deflate")
+        .reply(&route)
+        .await;
+    assert_eq!(gzipped.status(), StatusCode::OK);
+    assert_eq!(gzipped.headers()[CONTENT_ENCODING], "gzip");
+    assert_eq!(gzipped.headers()[VARY], "accept-encoding");
+    assert!(gzipped.body().len() < plain.body().len());
+
+    let mut body: Vec<u8> = vec![];
+    GzDecoder::new(&gzipped.body()[..])
+        .read_to_end(&mut body)
+        .unwrap();
+    assert_eq!(body, plain.body().to_vec());
+
+    let deflated = warp::test::request()
+        .path(&path)
+        .header("accept-encoding", "deflate")
+        .reply(&route)
+        .await;
+    assert_eq!(deflated.headers()[CONTENT_ENCODING], "deflate");
+
+    let mut body: Vec<u8> = vec![];
+    ZlibDecoder::new(&deflated.body()[..])
+        .read_to_end(&mut body)
+        .unwrap();
+    assert_eq!(body, plain.body().to_vec());
+}
+
+#[tokio::test]
+pub async fn test_book_uncompressed_when_disabled_or_small() {
+    let (state, market) = setup(20).await;
+    let res = warp::test::request()
+        .path(&format!("/book/{:x}", market))
+        .header("accept-encoding", "gzip")
+        .reply(&read_book_route(state, false))
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().get(CONTENT_ENCODING).is_none());
+
+    let (state, market) = setup(0).await;
+    let res = warp::test::request()
+        .path(&format!("/book/{:x}", market))
+        .header("accept-encoding", "gzip")
+        .reply(&read_book_route(state, true))
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().get(CONTENT_ENCODING).is_none());
+    assert!(serde_json::from_slice::<serde_json::Value>(res.body()).is_ok());
+
+    /* errors have little to compress either */
+    let res = warp::test::request()
+        .path(&format!("/book/{:x}", Address::from_low_u64_be(99)))
+        .header("accept-encoding", "gzip")
+        .reply(&read_book_route(
+            Arc::new(RwLock::new(OmeState::new())),
+            true,
+        ))
+        .await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    assert!(res.headers().get(CONTENT_ENCODING).is_none());
+}
@@ -0,0 +1,144 @@
+//! Contains logic for noticing when the order books drift apart from those
+//! held by the source of markets
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use ethereum_types::U256;
+use tokio::sync::RwLock;
+use tokio::time::{self, Interval};
+use web3::types::Address;
+
+use crate::actor;
+use crate::book::{Book, BookDiff, ExternalBook};
+use crate::rpc::RpcClient;
+use crate::state::OmeState;
+
+/// Counts the checks that found each book to have diverged from its source
+#[derive(Debug, Default)]
+pub struct DivergenceMetrics {
+    divergences: Mutex<BTreeMap<Address, u64>>, /* by market */
+}
+
+impl DivergenceMetrics {
+    /// Returns the number of checks that found the book of the given market
+    /// to have diverged
+    pub fn divergences(&self, market: Address) -> u64 {
+        self.divergences
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&market)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn record(&self, market: Address) {
+        *self
+            .divergences
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(market)
+            .or_insert(0) += 1;
+    }
+
+    /// Renders the counters in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut rendered: String = "# HELP ome_book_divergence Checks that \
+                                    found each book to differ from its \
+                                    source\n\
+                                    # TYPE ome_book_divergence counter\n"
+            .to_string();
+
+        let divergences = self
+            .divergences
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        for (market, count) in divergences.iter() {
+            rendered.push_str(&format!(
+                "ome_book_divergence{{market=\"{:?}\"}} {}\n",
+                market, count
+            ));
+        }
+
+        rendered
+    }
+}
+
+/// Checks every order book against its source every `interval`, forever
+///
+/// Books that differ by more than `tolerance` at any price level (see
+/// `BookDiff::exceeds`) are logged and counted in `metrics`, but are left
+/// as they are (see the admin route reloading a book from its source).
+pub async fn run(
+    client: RpcClient,
+    state: Arc<RwLock<OmeState>>,
+    interval: Duration,
+    tolerance: U256,
+    metrics: Arc<DivergenceMetrics>,
+) {
+    let mut ticks: Interval = time::interval(interval);
+
+    loop {
+        ticks.tick().await;
+        check(&client, &state, tolerance, &metrics).await;
+    }
+}
+
+/// Checks every order book against its source once, returning the markets
+/// whose books were found to have diverged
+///
+/// Books whose source can't be fetched, or is itself invalid, are logged and
+/// skipped.
+pub async fn check(
+    client: &RpcClient,
+    state: &RwLock<OmeState>,
+    tolerance: U256,
+    metrics: &DivergenceMetrics,
+) -> Vec<Address> {
+    let markets: Vec<Address> = state.read().await.markets();
+    let mut diverged: Vec<Address> = vec![];
+
+    for market in markets {
+        let external: ExternalBook =
+            match client.get_external_book(market).await {
+                Ok(t) => t,
+                Err(e) => {
+                    warn!("Failed to fetch book {} to check: {}", market, e);
+                    continue;
+                }
+            };
+
+        let source: Book = match Book::try_from(external) {
+            Ok(t) => t,
+            Err(e) => {
+                warn!("Source of book {} is invalid: {}", market, e);
+                continue;
+            }
+        };
+
+        /* the book may have been removed since the source was fetched */
+        let diff: BookDiff =
+            match actor::query_book(state, market, move |t| t.diff(&source))
+                .await
+            {
+                Some(t) => t,
+                None => continue,
+            };
+
+        if diff.exceeds(tolerance) {
+            warn!(
+                "Book {} has diverged from its source: {} levels differ, top \
+                 of book is {:?} rather than {:?}",
+                market,
+                diff.levels.len(),
+                diff.top,
+                diff.other_top
+            );
+            metrics.record(market);
+            diverged.push(market);
+        }
+    }
+
+    diverged
+}
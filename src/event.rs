@@ -0,0 +1,219 @@
+//! Contains type definitions for the events describing changes to order books
+use ethereum_types::U256;
+use serde::{Deserialize, Serialize};
+use web3::types::Address;
+
+use crate::book::{Book, BookSnapshot, ExternalBook, ExternalFill, Fill};
+use crate::order::{ExternalOrder, Order, OrderSide};
+use crate::util::{AsString, Hex};
+
+#[cfg(feature = "server")]
+pub mod publisher;
+
+/// Maximum number of events buffered for each subscriber before it is deemed
+/// too slow to keep up
+pub const EVENT_CAPACITY: usize = 1024;
+
+/// Represents a single change to an order book
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum BookEventKind {
+    Added(Order),     /* an order was opened in the book */
+    Amended(Order),   /* an open order was amended */
+    Cancelled(Order), /* an open order was cancelled */
+    Expired(Order),   /* an open order was removed upon expiry */
+    Triggered(Order), /* a stop order was triggered and is being matched */
+    Fill {
+        fill: Fill,            /* a resting order matched an incoming one */
+        maker_remaining: U256, /* unfilled amount of the resting order */
+        taker_remaining: U256, /* unfilled amount of the incoming order */
+    },
+}
+
+/// Represents a change to an order book, stamped with the sequence number of
+/// the book immediately after the change
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BookEvent {
+    pub market: Address,
+    pub sequence: u64,
+    pub kind: BookEventKind,
+}
+
+impl BookEvent {
+    pub fn new(market: Address, sequence: u64, kind: BookEventKind) -> Self {
+        Self {
+            market,
+            sequence,
+            kind,
+        }
+    }
+
+    /// Returns whether the given trader owns an order affected by this event
+    pub fn concerns(&self, trader: Address) -> bool {
+        match &self.kind {
+            BookEventKind::Added(order)
+            | BookEventKind::Amended(order)
+            | BookEventKind::Cancelled(order)
+            | BookEventKind::Expired(order)
+            | BookEventKind::Triggered(order) => order.trader == trader,
+            BookEventKind::Fill { fill, .. } => {
+                fill.maker_address == trader || fill.taker_address == trader
+            }
+        }
+    }
+}
+
+/// Represents the payload of an event in its external (API) format
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExternalBookEventKind {
+    Snapshot {
+        book: ExternalBook, /* the entire book upon subscription */
+    },
+    Added {
+        order: ExternalOrder,
+    },
+    Amended {
+        order: ExternalOrder,
+    },
+    Cancelled {
+        order: ExternalOrder,
+    },
+    Expired {
+        order: ExternalOrder,
+    },
+    Triggered {
+        order: ExternalOrder,
+    },
+    Fill {
+        fill: ExternalFill,
+        maker_remaining: String,
+        taker_remaining: String,
+    },
+}
+
+/// Represents an event in its external (API) format
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ExternalBookEvent {
+    pub market: String,
+    pub sequence: u64,
+    #[serde(flatten)]
+    pub kind: ExternalBookEventKind,
+}
+
+impl ExternalBookEvent {
+    /// Builds the snapshot sent to new subscribers of a book, which is stamped
+    /// with the sequence number of the book itself
+    pub fn snapshot(book: ExternalBook) -> Self {
+        Self {
+            market: book.market.clone(),
+            sequence: book.sequence,
+            kind: ExternalBookEventKind::Snapshot { book },
+        }
+    }
+}
+
+/// Represents the snapshot sent to new subscribers of a book, which borrows
+/// the book rather than converting it, but is serialized exactly as
+/// `ExternalBookEvent::snapshot` is
+#[derive(Serialize)]
+pub struct SnapshotEvent<'a> {
+    market: AsString<Hex<'a>>,
+    sequence: u64,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    book: BookSnapshot<'a>,
+}
+
+impl<'a> From<&'a Book> for SnapshotEvent<'a> {
+    fn from(value: &'a Book) -> Self {
+        Self {
+            market: AsString(Hex(value.market.as_bytes())),
+            sequence: value.sequence,
+            kind: "snapshot",
+            book: BookSnapshot(value),
+        }
+    }
+}
+
+/// Represents the net change to a single price level of an order book
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LevelChange {
+    pub side: OrderSide,
+    pub price: String,
+    pub new_total: String, /* remaining at the level, zero once emptied */
+}
+
+/// Represents the net change to an order book between two of its sequence
+/// numbers, which subscribers apply in order to keep a copy of the book
+///
+/// A delta follows on from the previous one exactly when its `prev_seq` is
+/// the `seq` of the previous one, so a gap means a delta was missed.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BookDelta {
+    pub market: String,
+    pub prev_seq: u64,
+    pub seq: u64,
+    pub changes: Vec<LevelChange>,
+    pub fills: Vec<ExternalFill>,
+}
+
+/// Represents a delta as sent to subscribers of a book
+#[derive(Serialize)]
+pub struct DeltaEvent<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(flatten)]
+    delta: &'a BookDelta,
+}
+
+impl<'a> From<&'a BookDelta> for DeltaEvent<'a> {
+    fn from(value: &'a BookDelta) -> Self {
+        Self {
+            kind: "delta",
+            delta: value,
+        }
+    }
+}
+
+impl From<BookEvent> for ExternalBookEvent {
+    fn from(value: BookEvent) -> Self {
+        let kind: ExternalBookEventKind = match value.kind {
+            BookEventKind::Added(order) => ExternalBookEventKind::Added {
+                order: order.into(),
+            },
+            BookEventKind::Amended(order) => ExternalBookEventKind::Amended {
+                order: order.into(),
+            },
+            BookEventKind::Cancelled(order) => {
+                ExternalBookEventKind::Cancelled {
+                    order: order.into(),
+                }
+            }
+            BookEventKind::Expired(order) => ExternalBookEventKind::Expired {
+                order: order.into(),
+            },
+            BookEventKind::Triggered(order) => {
+                ExternalBookEventKind::Triggered {
+                    order: order.into(),
+                }
+            }
+            BookEventKind::Fill {
+                fill,
+                maker_remaining,
+                taker_remaining,
+            } => ExternalBookEventKind::Fill {
+                fill: fill.into(),
+                maker_remaining: maker_remaining.to_string(),
+                taker_remaining: taker_remaining.to_string(),
+            },
+        };
+
+        Self {
+            market: format!("{:?}", value.market),
+            sequence: value.sequence,
+            kind,
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Contains logic for publishing the events of order books to external sinks,
+//! such as Kafka
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use thiserror::Error;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+
+#[cfg(feature = "kafka")]
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+#[cfg(feature = "kafka")]
+use rdkafka::producer::{
+    BaseRecord, DeliveryResult, ProducerContext, ThreadedProducer,
+};
+#[cfg(feature = "kafka")]
+use rdkafka::{ClientConfig, ClientContext};
+
+#[cfg(feature = "kafka")]
+use crate::event::ExternalBookEvent;
+use crate::event::{BookEvent, BookEventKind};
+use crate::rpc;
+
+/// The default prefix of the topics events are published to
+pub const DEFAULT_TOPIC_PREFIX: &str = "ome";
+
+/// The attempt whose backoff caps the delay between retries of a full sink
+const MAX_FULL_BACKOFF_ATTEMPT: u32 = 5;
+
+/// Represents the reasons an event cannot be handed to a sink
+#[derive(Debug, Error)]
+pub enum SinkError {
+    #[error("Event sink is full")]
+    Full, /* the event may be retried once the sink has caught up */
+    #[error("Failed to publish event: {0}")]
+    Failed(String),
+}
+
+/// Represents a destination that the events of every book are published to
+///
+/// Implementations must not block: an event that can't be accepted promptly
+/// should be refused with `SinkError::Full`, upon which it is retried.
+pub trait EventSink: Send + 'static {
+    fn publish(&mut self, event: &BookEvent) -> Result<(), SinkError>;
+}
+
+/// Counts the events handled by the publisher, for monitoring
+#[derive(Debug, Default)]
+pub struct PublisherMetrics {
+    published: AtomicU64, /* handed to the sink */
+    dropped: AtomicU64,   /* overwritten while the sink fell behind */
+    failed: AtomicU64,    /* refused by the sink, or never delivered */
+}
+
+impl PublisherMetrics {
+    pub fn published(&self) -> u64 {
+        self.published.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    /// Records that an event accepted by the sink was never delivered
+    pub fn record_failure(&self) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Renders the counters in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        [
+            (
+                "ome_events_published_total",
+                "Events handed to the event sink",
+                self.published(),
+            ),
+            (
+                "ome_events_dropped_total",
+                "Events dropped while the event sink fell behind",
+                self.dropped(),
+            ),
+            (
+                "ome_events_failed_total",
+                "Events the event sink failed to publish",
+                self.failed(),
+            ),
+        ]
+        .iter()
+        .map(|(name, help, value)| {
+            format!(
+                "# HELP {0} {1}\n# TYPE {0} counter\n{0} {2}\n",
+                name, help, value
+            )
+        })
+        .collect()
+    }
+}
+
+/// Returns the topic an event is published to, given the prefix of the topics
+///
+/// Fills go to `<prefix>.fills`, and every other change to the orders of a
+/// book goes to `<prefix>.orders`.
+pub fn topic(prefix: &str, event: &BookEvent) -> String {
+    match event.kind {
+        BookEventKind::Fill { .. } => format!("{}.fills", prefix),
+        _ => format!("{}.orders", prefix),
+    }
+}
+
+/// Returns the key an event is published under, i.e., its market, so that
+/// the events of each book are kept in order
+pub fn key(event: &BookEvent) -> String {
+    format!("{:?}", event.market)
+}
+
+/// Hands every event published to `events` to the sink, until the OME exits
+///
+/// `events` is the buffer between the books and the sink, so matching never
+/// waits on the sink. While the sink is full, the event at the head of the
+/// buffer is retried with exponential backoff. Should this fall more than
+/// `EVENT_CAPACITY` events behind, the oldest events are dropped to make room
+/// for the newest, and counted in `PublisherMetrics::dropped`.
+pub async fn run<S: EventSink>(
+    mut sink: S,
+    mut events: Receiver<BookEvent>,
+    metrics: Arc<PublisherMetrics>,
+) {
+    loop {
+        let event: BookEvent = match events.recv().await {
+            Ok(t) => t,
+            Err(RecvError::Lagged(missed)) => {
+                metrics.dropped.fetch_add(missed, Ordering::Relaxed);
+                warn!("Event sink fell behind, dropped {} events", missed);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        let mut attempt: u32 = 1;
+
+        loop {
+            match sink.publish(&event) {
+                Ok(()) => {
+                    metrics.published.fetch_add(1, Ordering::Relaxed);
+                    break;
+                }
+                Err(SinkError::Full) => {
+                    let delay: Duration =
+                        rpc::backoff(attempt.min(MAX_FULL_BACKOFF_ATTEMPT));
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    metrics.record_failure();
+                    warn!(
+                        "Dropping event {} of {:?}: {}",
+                        event.sequence, event.market, e
+                    );
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Counts the events Kafka failed to deliver
+#[cfg(feature = "kafka")]
+pub struct DeliveryCounter {
+    metrics: Arc<PublisherMetrics>,
+}
+
+#[cfg(feature = "kafka")]
+impl ClientContext for DeliveryCounter {}
+
+#[cfg(feature = "kafka")]
+impl ProducerContext for DeliveryCounter {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, result: &DeliveryResult<'_>, _opaque: ()) {
+        if let Err((e, _)) = result {
+            self.metrics.record_failure();
+            warn!("Failed to deliver event to Kafka: {}", e);
+        }
+    }
+}
+
+/// Publishes events to Kafka, as the JSON of the WebSocket feed
+///
+/// The producer is idempotent, so retries within the client never reorder
+/// the events of a market. Events are queued by the client and delivered by
+/// its own thread, so publishing only fails fast (with `SinkError::Full`)
+/// once its queue fills up.
+#[cfg(feature = "kafka")]
+pub struct KafkaSink {
+    producer: ThreadedProducer<DeliveryCounter>,
+    topic_prefix: String,
+}
+
+#[cfg(feature = "kafka")]
+impl KafkaSink {
+    pub fn new(
+        brokers: &str,
+        topic_prefix: &str,
+        metrics: Arc<PublisherMetrics>,
+    ) -> Result<Self, SinkError> {
+        let producer: ThreadedProducer<DeliveryCounter> = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("enable.idempotence", "true")
+            .create_with_context(DeliveryCounter { metrics })
+            .map_err(|e| SinkError::Failed(e.to_string()))?;
+
+        Ok(Self {
+            producer,
+            topic_prefix: topic_prefix.to_string(),
+        })
+    }
+}
+
+#[cfg(feature = "kafka")]
+impl EventSink for KafkaSink {
+    fn publish(&mut self, event: &BookEvent) -> Result<(), SinkError> {
+        let topic: String = topic(&self.topic_prefix, event);
+        let key: String = key(event);
+        let payload: String =
+            serde_json::to_string(&ExternalBookEvent::from(event.clone()))
+                .unwrap();
+
+        match self
+            .producer
+            .send(BaseRecord::to(&topic).key(&key).payload(&payload))
+        {
+            Ok(()) => Ok(()),
+            Err((
+                KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull),
+                _,
+            )) => Err(SinkError::Full),
+            Err((e, _)) => Err(SinkError::Failed(e.to_string())),
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! Contains logic for forwarding matches to the executioner
+#[cfg(feature = "rpc-client")]
+use std::collections::VecDeque;
+#[cfg(not(feature = "rpc-client"))]
+use std::sync::mpsc::{self, Receiver, Sender};
+#[cfg(feature = "rpc-client")]
+use std::time::Duration;
+
+use ethereum_types::U256;
+#[cfg(feature = "rpc-client")]
+use futures_util::FutureExt;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "rpc-client")]
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::order::{ExternalOrder, Order};
+#[cfg(feature = "rpc-client")]
+use crate::rpc::{self, RpcClient};
+use crate::util::{from_hex_de, from_hex_se};
+
+/// Represents a single match to be executed on-chain
+///
+/// Both orders are sent in their entirety, signatures included.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Execution {
+    pub maker: ExternalOrder,
+    pub taker: ExternalOrder,
+    #[serde(serialize_with = "from_hex_se", deserialize_with = "from_hex_de")]
+    pub amount: U256, /* quantity filled by the match */
+    #[serde(
+        default,
+        serialize_with = "from_hex_se",
+        deserialize_with = "from_hex_de"
+    )]
+    pub maker_fee: U256, /* owed by the maker */
+    #[serde(
+        default,
+        serialize_with = "from_hex_se",
+        deserialize_with = "from_hex_de"
+    )]
+    pub taker_fee: U256, /* owed by the taker */
+}
+
+impl Execution {
+    /// Constructor for the `Execution` type
+    pub fn new(maker: &Order, taker: &Order, amount: U256) -> Self {
+        Self {
+            maker: maker.clone().into(),
+            taker: taker.clone().into(),
+            amount,
+            maker_fee: U256::zero(),
+            taker_fee: U256::zero(),
+        }
+    }
+
+    /// Sets the fees owed by each side of this match
+    pub fn with_fees(mut self, maker_fee: U256, taker_fee: U256) -> Self {
+        self.maker_fee = maker_fee;
+        self.taker_fee = taker_fee;
+        self
+    }
+}
+
+/// Represents the executioner, which executes matches on-chain
+///
+/// Matches are forwarded in the background, in the order they were made, so
+/// that the book never waits on the executioner. A batch of matches that
+/// cannot be forwarded is retried with exponential backoff, holding back the
+/// batches queued after it, rather than being dropped. Cloning an
+/// `Executioner` is cheap, and clones share the same queue.
+///
+/// Without the `rpc-client` feature, there is no executioner to forward to,
+/// and matches are instead handed to whoever holds the other end of the queue
+/// (see `Executioner::channel`).
+#[derive(Clone, Debug)]
+pub struct Executioner {
+    #[cfg(feature = "rpc-client")]
+    client: RpcClient,
+    #[cfg(feature = "rpc-client")]
+    queue: UnboundedSender<Vec<Execution>>, /* batches awaiting forwarding */
+    #[cfg(not(feature = "rpc-client"))]
+    queue: Sender<Vec<Execution>>,
+}
+
+impl Executioner {
+    /// Constructor for the `Executioner` type, which spawns the task that
+    /// forwards matches via the given client
+    #[cfg(feature = "rpc-client")]
+    pub fn new(client: RpcClient) -> Self {
+        let (queue, batches) = mpsc::unbounded_channel();
+        tokio::spawn(Executioner::run(client.clone(), batches));
+
+        Self { client, queue }
+    }
+
+    /// Constructor for the `Executioner` type, which returns the receiving
+    /// end of its queue so that matches can be executed by the caller
+    ///
+    /// The queue is a standard library channel, as there is no async runtime
+    /// without the `rpc-client` feature.
+    #[cfg(not(feature = "rpc-client"))]
+    pub fn channel() -> (Self, Receiver<Vec<Execution>>) {
+        let (queue, batches) = mpsc::channel();
+        (Self { queue }, batches)
+    }
+
+    /// Returns the client used to reach the executioner
+    #[cfg(feature = "rpc-client")]
+    pub fn client(&self) -> &RpcClient {
+        &self.client
+    }
+
+    /// Queues the matches made by a single order for forwarding
+    pub fn forward(&self, batch: Vec<Execution>) {
+        if self.queue.send(batch).is_err() {
+            error!("Executioner task has stopped, dropping matches");
+        }
+    }
+
+    /// Forwards queued batches until every handle to the queue is dropped and
+    /// there is nothing left to forward
+    #[cfg(feature = "rpc-client")]
+    async fn run(
+        client: RpcClient,
+        mut batches: UnboundedReceiver<Vec<Execution>>,
+    ) {
+        let mut pending: VecDeque<Vec<Execution>> = VecDeque::new();
+        let mut attempt: u32 = 1;
+
+        loop {
+            /* wait for matches unless there are some to retry */
+            if pending.is_empty() {
+                match batches.recv().await {
+                    Some(t) => pending.push_back(t),
+                    None => return,
+                }
+            }
+
+            /* pick up any matches queued while the last batch was sent */
+            while let Some(Some(t)) = batches.recv().now_or_never() {
+                pending.push_back(t);
+            }
+
+            let batch: &[Execution] = &pending[0];
+
+            match client.send_matches(batch).await {
+                Ok(hashes) => {
+                    info!("Executed {} matches in {:?}", batch.len(), hashes);
+                    pending.pop_front();
+                    attempt = 1;
+                }
+                Err(e) => {
+                    let delay: Duration = rpc::backoff(attempt);
+                    warn!(
+                        "Failed to forward {} matches ({} batches queued), \
+                         retrying in {:?}: {}",
+                        batch.len(),
+                        pending.len(),
+                        delay,
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Contains logic for removing expired orders from the order books
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tokio::sync::{RwLock, RwLockWriteGuard};
+use tokio::time::{self, Interval};
+use web3::types::Address;
+
+use crate::actor::BookHandle;
+use crate::book::Book;
+use crate::order::Order;
+use crate::state::OmeState;
+use crate::wal::WalChange;
+
+/// The default number of seconds between sweeps for expired orders
+pub const DEFAULT_EXPIRY_SWEEP_INTERVAL: u64 = 5;
+
+/// Purges expired orders from every order book every `interval`, forever
+pub async fn run(state: Arc<RwLock<OmeState>>, interval: Duration) {
+    let mut ticks: Interval = time::interval(interval);
+
+    loop {
+        ticks.tick().await;
+        sweep(&state, Utc::now()).await;
+    }
+}
+
+/// Purges the orders that have expired as of `now` from every order book,
+/// returning the number purged
+///
+/// Each purge is logged and published like any other change to a book, so
+/// subscribers see the purged orders expire.
+pub async fn sweep(state: &RwLock<OmeState>, now: DateTime<Utc>) -> usize {
+    let mut ome_state: RwLockWriteGuard<OmeState> = state.write().await;
+
+    /* the books must not change while draining for shutdown */
+    if ome_state.shutting_down() {
+        return 0;
+    }
+
+    let mut purged: usize = 0;
+
+    /* hosted books purge their own orders, so the state needn't stay locked */
+    if ome_state.hosting() {
+        let handles: Vec<BookHandle> = ome_state.handles();
+        drop(ome_state);
+
+        for handle in handles {
+            if let Ok(expired) = handle.purge_expired(now).await {
+                for order in expired.iter() {
+                    info!(
+                        "Purged expired {} from book {}",
+                        order,
+                        handle.market()
+                    );
+                }
+                purged += expired.len();
+            }
+        }
+
+        return purged;
+    }
+
+    let markets: Vec<Address> = ome_state.books().keys().copied().collect();
+
+    for market in markets {
+        let book: &mut Book = match ome_state.book_mut(market) {
+            Some(t) => t,
+            None => continue,
+        };
+        let sequence: u64 = book.sequence;
+        let expired: Vec<Order> = book.purge_expired(now);
+
+        if expired.is_empty() {
+            continue;
+        }
+
+        for order in expired.iter() {
+            info!("Purged expired {} from book {}", order, market);
+        }
+
+        ome_state.log(market, sequence, now, WalChange::PurgeExpired);
+        ome_state.publish(market);
+        purged += expired.len();
+    }
+
+    purged
+}
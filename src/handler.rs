@@ -1,92 +1,359 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::{From, Infallible, TryFrom};
+use std::error::Error;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
-use chrono::serde::ts_seconds;
 use chrono::{DateTime, Utc};
-use ethereum_types::{Address, H256, U256};
-use serde::{Deserialize, Serialize};
-use tokio::sync::{Mutex, MutexGuard};
+use ethereum_types::{Address, U256};
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tokio::time;
+use warp::body::BodyDeserializeError;
+use warp::cors::Builder;
 use warp::http;
 use warp::http::StatusCode;
-use warp::reply::json;
-use warp::{Rejection, Reply};
+use warp::hyper::body::Bytes;
+use warp::reject::{
+    self, InvalidQuery, LengthRequired, MethodNotAllowed, PayloadTooLarge,
+    Reject, UnsupportedMediaType,
+};
+use warp::reply::{json, Json, Response, WithStatus};
+use warp::ws::Ws;
+use warp::{Filter, Rejection, Reply};
 
-use crate::book::{Book, ExternalBook};
-use crate::order::{ExternalOrder, Order, OrderId, OrderSide};
-use crate::rpc;
-use crate::state::OmeState;
-use crate::util::{from_hex_de, from_hex_se};
+use crate::actor::{self, ActorError, BookHandle, Submitted};
+use crate::api::{
+    ApiError, ApiVersion, BlockTraderQuery, BlockTraderResponse,
+    BlocklistResponse, CancelOrdersRequest, CancelOrdersResponse,
+    CancelOutcome, CancelResult, ClientOrderQuery, CreateBookRequest,
+    CreateOrderRequest, CreateWebhookRequest, DepthQuery, DepthResponse,
+    DumpResponse, ExposureResponse, HealthResponse, MatchResponse, OmeResponse,
+    OrderHistoryResponse, OrderView, QuoteResponse, ReloadBookResponse,
+    ReplaceOrderResponse, SnapshotQuery, SnapshotResponse, StatsResponse,
+    TickerResponse, TradesQuery, TradesResponse, UpdateBookLimitsRequest,
+    UpdateOrderRequest, UserOrdersQuery, UserOrdersResponse,
+    UserSummaryResponse, WebhookResponse,
+};
+use crate::args::AllowedOrigins;
+use crate::book::{
+    AggregatedLevels, Book, BookError, BookSnapshot, ExternalBook,
+    ExternalFill, MatchResult, OrderStatus, Ticker, TraderExposure,
+    BPS_PER_UNIT,
+};
+use crate::codec::Format;
+use crate::divergence::DivergenceMetrics;
+use crate::event::publisher::PublisherMetrics;
+use crate::executioner::Executioner;
+use crate::limit::RateLimited;
+use crate::order::{self, ExternalOrder, Order, OrderId, OrderParseError};
+use crate::routes::LegacyRouteMetrics;
+use crate::rpc::{RpcClient, RpcError};
+use crate::state::{OmeState, Readiness};
+use crate::util::constant_time_eq;
+use crate::wal::WalChange;
+use crate::ws;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct OmeResponse {
-    pub status: u16, /* this should be the StatusCode type, but serde */
-    pub message: String,
+/// The default maximum size (in bytes) of request bodies
+pub const DEFAULT_MAX_BODY_SIZE: u64 = 64 * 1024;
+
+/// How long a readiness check waits to lock the engine state before deeming
+/// the OME unready
+pub const READINESS_LOCK_TIMEOUT: Duration = Duration::from_millis(100);
+
+impl ApiError {
+    /// Builds the reply for this error
+    ///
+    /// Handlers use this rather than `Reply::into_response` so that their
+    /// error and success branches share a single reply type.
+    pub fn reply(&self) -> WithStatus<Json> {
+        warp::reply::with_status(json(self), self.status_code())
+    }
 }
 
-/// Represents an API request to create a new order book
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct CreateBookRequest {
-    market: Address,
+impl Reply for ApiError {
+    fn into_response(self) -> Response {
+        self.reply().into_response()
+    }
 }
 
-/// Represents an API request to create a new order
-#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
-pub struct CreateOrderRequest {
-    user: Address,          /* Ethereum address of trader */
-    target_tracer: Address, /* Ethereum address of the Tracer smart contract */
-    side: OrderSide,        /* side of the market of the order */
-    #[serde(serialize_with = "from_hex_se", deserialize_with = "from_hex_de")]
-    price: U256, /* price */
-    #[serde(serialize_with = "from_hex_se", deserialize_with = "from_hex_de")]
-    amount: U256, /* quantity */
-    #[serde(with = "ts_seconds")]
-    expiration: DateTime<Utc>, /* expiration of the order */
-    #[serde(with = "ts_seconds")]
-    created: DateTime<Utc>, /* creation time of the order */
-    signed_data: String,    /* digital signature of the order */
-}
-
-impl From<CreateOrderRequest> for ExternalOrder {
-    fn from(value: CreateOrderRequest) -> Self {
-        /* extract request fields */
-        let user: Address = value.user;
-        let target_tracer: Address = value.target_tracer;
-        let side: OrderSide = value.side;
-        let price: U256 = value.price;
-        let amount: U256 = value.amount;
-        let expiration: DateTime<Utc> = value.expiration;
-        let created: DateTime<Utc> = value.created;
-        let signed_data: String = value.signed_data;
-
-        let user_bytes: Vec<u8> = user.as_ref().to_vec();
-        let target_tracer_bytes: Vec<u8> = target_tracer.as_ref().to_vec();
-
-        let order: ExternalOrder = Self {
-            id: hex::encode(H256::zero().as_ref().to_vec()),
-            user: hex::encode(&user_bytes),
-            target_tracer: hex::encode(&target_tracer_bytes),
-            side: side.to_string(),
-            price: price.to_string(),
-            amount: amount.to_string(),
-            amount_left: amount.to_string(),
-            expiration: expiration.timestamp().to_string(),
-            created: created.timestamp().to_string(),
-            signed_data: {
-                let mut chr = signed_data.chars();
-                chr.next();
-                chr.next();
-                chr.as_str().to_string()
-            },
-        };
+impl From<RpcError> for ApiError {
+    fn from(_error: RpcError) -> Self {
+        Self::new(
+            StatusCode::BAD_GATEWAY,
+            "EXECUTIONER_UNAVAILABLE",
+            "Unable to reach the executioner",
+        )
+    }
+}
+
+impl From<ActorError> for ApiError {
+    fn from(error: ActorError) -> Self {
+        match error {
+            /* books only stop once they've been removed */
+            ActorError::Stopped(_) => Self::market_not_found(),
+            ActorError::Book(e) => e.into(),
+        }
+    }
+}
+
+/// Builds the CORS policy allowing requests from the given origins with the
+/// given methods
+pub fn cors(origins: &AllowedOrigins, methods: &[http::Method]) -> Builder {
+    let cors: Builder = warp::cors()
+        .allow_headers(vec![
+            "Origin",
+            "Access-Control-Request-Headers",
+            "Content-Type",
+            "Access-Control-Allow-Origin",
+        ])
+        .allow_methods(methods.to_vec());
+
+    match origins {
+        AllowedOrigins::Any => cors.allow_any_origin(),
+        AllowedOrigins::Only(t) => {
+            cors.allow_origins(t.iter().map(|x| x.as_str()))
+        }
+    }
+}
+
+/// Represents the rejection of a JSON body that isn't of the expected shape,
+/// naming the field at fault (if any)
+#[derive(Debug)]
+pub struct InvalidBody(pub String);
+
+impl Reject for InvalidBody {}
+
+/// Filter that extracts a body of at most `max_size` bytes, in whichever
+/// format its `Content-Type` names (see `codec`)
+///
+/// Bodies that aren't MessagePack are read as JSON, exactly as `json_body`
+/// reads them, and malformed MessagePack bodies are rejected as bodies of the
+/// wrong shape are.
+pub fn body<T: DeserializeOwned + Send>(
+    max_size: u64,
+) -> impl Filter<Extract = (T,), Error = Rejection> + Clone {
+    let msgpack = warp::header::optional::<String>("content-type")
+        .and_then(|content_type: Option<String>| async move {
+            match content_type.as_deref().map(Format::from_content_type) {
+                Some(Format::MessagePack) => Ok(()),
+                _ => Err(reject::not_found()),
+            }
+        })
+        .untuple_one()
+        .and(warp::body::content_length_limit(max_size))
+        .and(warp::body::bytes())
+        .and_then(|bytes: Bytes| async move {
+            Format::MessagePack
+                .decode(&bytes)
+                .map_err(|e| reject::custom(InvalidBody(e.to_string())))
+        });
+
+    msgpack.or(json_body(max_size)).unify()
+}
+
+/// Filter that extracts the format a client asks to be answered in from its
+/// `Accept` header, defaulting to JSON
+pub fn accept() -> impl Filter<Extract = (Format,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("accept").map(|accept: Option<String>| {
+        accept
+            .as_deref()
+            .map(Format::from_accept)
+            .unwrap_or_default()
+    })
+}
 
-        order
+/// Builds a successful reply carrying the payload in the given format
+fn encoded<T: Serialize + ?Sized>(payload: &T, format: Format) -> Response {
+    match format.encode(payload) {
+        Ok(t) => warp::reply::with_header(
+            t,
+            http::header::CONTENT_TYPE,
+            format.content_type(),
+        )
+        .into_response(),
+        Err(e) => {
+            error!("Failed to encode response: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
     }
 }
 
-pub type UpdateOrderRequest = CreateOrderRequest;
+/// Filter that extracts a JSON body of at most `max_size` bytes
+///
+/// Larger bodies are rejected before they are buffered. Bodies of the wrong
+/// shape are rejected with the path to the offending field (e.g., `price:
+/// invalid value`), which serde alone wouldn't name.
+pub fn json_body<T: DeserializeOwned + Send>(
+    max_size: u64,
+) -> impl Filter<Extract = (T,), Error = Rejection> + Clone {
+    warp::body::content_length_limit(max_size)
+        .and(warp::body::json())
+        .and_then(|body: serde_json::Value| async move {
+            serde_path_to_error::deserialize(body)
+                .map_err(|e| reject::custom(InvalidBody(e.to_string())))
+        })
+}
+
+/// Represents the rejection of a change requested before the OME is ready
+#[derive(Debug)]
+pub struct NotReady;
+
+impl Reject for NotReady {}
+
+/// Filter that rejects requests until the engine state has been restored, so
+/// that changes aren't made to partially restored books
+pub fn ready(
+    readiness: Arc<Readiness>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::any()
+        .and_then(move || {
+            let restored: bool = readiness.restored();
+            async move {
+                if restored {
+                    Ok(())
+                } else {
+                    Err(reject::custom(NotReady))
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// Represents the rejection of an admin request without the admin token
+#[derive(Debug)]
+pub struct Unauthorized;
+
+impl Reject for Unauthorized {}
+
+/// Filter that requires the admin token (if any) as a bearer token in the
+/// `Authorization` header
+///
+/// If no token is given, every request is let through.
+pub fn admin(
+    token: Option<String>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    let token: Option<Arc<String>> = token.map(Arc::new);
+
+    warp::header::optional::<String>("authorization")
+        .and_then(move |header: Option<String>| {
+            let authorized: bool = match &token {
+                Some(token) => header
+                    .as_deref()
+                    .and_then(|t| t.strip_prefix("Bearer "))
+                    .is_some_and(|t| {
+                        constant_time_eq(t.as_bytes(), token.as_bytes())
+                    }),
+                None => true,
+            };
+            async move {
+                if authorized {
+                    Ok(())
+                } else {
+                    Err(reject::custom(Unauthorized))
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// Converts rejections from warp's own filters into the API error envelope
+///
+/// Errors that were not raised by a filter (e.g., an unmatched path) are
+/// reported as a missing resource.
+pub async fn handle_rejection(
+    rejection: Rejection,
+) -> Result<impl Reply, Infallible> {
+    let error: ApiError = if rejection.is_not_found() {
+        ApiError::new(StatusCode::NOT_FOUND, "NOT_FOUND", "Not found")
+    } else if rejection.find::<Unauthorized>().is_some() {
+        ApiError::unauthorized()
+    } else if rejection.find::<NotReady>().is_some() {
+        ApiError::not_ready("Restoring state")
+    } else if let Some(e) = rejection.find::<RateLimited>() {
+        ApiError::rate_limited(e.retry_after)
+    } else if let Some(e) = rejection.find::<BodyDeserializeError>() {
+        warn!("Rejecting request: {}", e);
+        /* distinguish bodies that are not JSON at all from those that are
+         * merely the wrong shape, naming what is wrong with them */
+        match e
+            .source()
+            .and_then(|cause| cause.downcast_ref::<serde_json::Error>())
+        {
+            Some(cause) if cause.is_syntax() || cause.is_eof() => {
+                ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    "MALFORMED_JSON",
+                    &format!("Request body is not valid JSON: {}", cause),
+                )
+            }
+            Some(cause) => ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "INVALID_BODY",
+                &format!("Invalid request body: {}", cause),
+            ),
+            None => ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "INVALID_BODY",
+                "Invalid request body",
+            ),
+        }
+    } else if let Some(InvalidBody(e)) = rejection.find::<InvalidBody>() {
+        warn!("Rejecting request: {}", e);
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "INVALID_BODY",
+            &format!("Invalid request body: {}", e),
+        )
+    } else if rejection.find::<InvalidQuery>().is_some() {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "INVALID_QUERY",
+            "Malformed query string",
+        )
+    } else if rejection.find::<UnsupportedMediaType>().is_some() {
+        ApiError::new(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "UNSUPPORTED_MEDIA_TYPE",
+            "Unsupported media type",
+        )
+    } else if rejection.find::<PayloadTooLarge>().is_some() {
+        ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "PAYLOAD_TOO_LARGE",
+            "Request body too large",
+        )
+    } else if rejection.find::<LengthRequired>().is_some() {
+        ApiError::new(
+            StatusCode::LENGTH_REQUIRED,
+            "LENGTH_REQUIRED",
+            "Request body must have a Content-Length",
+        )
+    } else if rejection.find::<MethodNotAllowed>().is_some() {
+        ApiError::new(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "METHOD_NOT_ALLOWED",
+            "Method not allowed",
+        )
+    } else {
+        warn!("Unhandled rejection: {:?}", rejection);
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "INTERNAL_ERROR",
+            "Internal server error",
+        )
+    };
+
+    Ok(error.reply())
+}
 
 /// HEALTH POINT HANDLER
+///
+/// Reports that the process is up (i.e., liveness), without touching the
+/// engine state.
 pub async fn health_check_handler() -> Result<impl Reply, Infallible> {
     let status: StatusCode = http::StatusCode::OK;
     let resp_body: OmeResponse = OmeResponse {
@@ -99,49 +366,202 @@ pub async fn health_check_handler() -> Result<impl Reply, Infallible> {
     ))
 }
 
+/// REST API route handler for readiness checks
+///
+/// The OME is ready once its state has been restored, as long as the state
+/// can be locked promptly and the known markets were synced recently (if they
+/// are synced at all). Otherwise, the reason it isn't ready is given.
+pub async fn readiness_handler(
+    state: Arc<RwLock<OmeState>>,
+    readiness: Arc<Readiness>,
+) -> Result<impl Reply, Infallible> {
+    if !readiness.restored() {
+        return Ok(ApiError::not_ready("Restoring state").reply());
+    }
+
+    let last_market_sync: Option<DateTime<Utc>> =
+        match time::timeout(READINESS_LOCK_TIMEOUT, state.read()).await {
+            Ok(t) => t.last_market_sync(),
+            Err(_e) => {
+                return Ok(ApiError::not_ready("State is locked").reply())
+            }
+        };
+
+    if !readiness.market_sync_fresh(last_market_sync, Utc::now()) {
+        return Ok(ApiError::not_ready(
+            "Known markets have not been synced recently",
+        )
+        .reply());
+    }
+
+    let status: StatusCode = http::StatusCode::OK;
+    let resp_body: HealthResponse = HealthResponse {
+        status: status.as_u16(),
+        message: "Ready".to_string(),
+        last_market_sync,
+    };
+    Ok(warp::reply::with_status(
+        warp::reply::json(&resp_body),
+        status,
+    ))
+}
+
+/// Route handler for the metrics of the OME, in the Prometheus text format
+///
+/// The depth of the mailbox of each book is only reported while books are
+/// hosted by tasks of their own.
+pub async fn metrics_handler(
+    metrics: Arc<PublisherMetrics>,
+    divergence: Arc<DivergenceMetrics>,
+    legacy: Arc<LegacyRouteMetrics>,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Infallible> {
+    let mut rendered: String = metrics.render();
+    rendered.push_str(&divergence.render());
+    rendered.push_str(&legacy.render());
+    let handles: Vec<BookHandle> = state.read().await.handles();
+    if !handles.is_empty() {
+        rendered.push_str(&actor::render_mailbox_depths(&handles));
+    }
+
+    Ok(warp::reply::with_header(
+        rendered,
+        http::header::CONTENT_TYPE,
+        "text/plain; version=0.0.4",
+    ))
+}
+
 /// REST API route handler for listing all order books
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/book",
+        tag = "books",
+        responses(
+            (
+                status = 200,
+                description = "The markets that have books",
+                body = HashMap<String, Vec<String>>
+            )
+        )
+    )
+)]
 pub async fn index_book_handler(
-    state: Arc<Mutex<OmeState>>,
+    state: Arc<RwLock<OmeState>>,
 ) -> Result<impl Reply, Infallible> {
-    let ome_state: MutexGuard<OmeState> = state.lock().await;
+    let ome_state: RwLockReadGuard<OmeState> = state.read().await;
 
     let mut result: HashMap<String, Vec<Address>> = HashMap::new();
-    result.insert(
-        "markets".to_string(),
-        ome_state.books().keys().cloned().collect(),
-    );
+    result.insert("markets".to_string(), ome_state.markets());
 
     Ok(json(&result))
 }
 
 /// REST API route handler for creating new order books
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        post,
+        path = "/book",
+        tag = "books",
+        request_body = CreateBookRequest,
+        responses(
+            (
+                status = 201,
+                description = "The book was created",
+                body = OmeResponse
+            ),
+            (
+                status = 400,
+                description = "Invalid book parameters",
+                body = ApiError
+            ),
+            (
+                status = 401,
+                description = "Missing or wrong admin token",
+                body = ApiError
+            ),
+            (
+                status = 409,
+                description = "The market already has a book",
+                body = ApiError
+            )
+        ),
+        security(("admin_token" = []))
+    )
+)]
 pub async fn create_book_handler(
     request: CreateBookRequest,
-    state: Arc<Mutex<OmeState>>,
+    state: Arc<RwLock<OmeState>>,
 ) -> Result<impl Reply, Rejection> {
     /* build our new order book */
     let market: Address = request.market;
-    let new_book: Book = Book::new(market);
+    /* a zero increment would make every order invalid */
+    if request.tick_size.is_zero() || request.lot_size.is_zero() {
+        return Ok(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "INVALID_INCREMENT",
+            "Invalid tick or lot size",
+        )
+        .reply());
+    }
+
+    /* a fee can't exceed the notional value of the trade it's charged on */
+    if request.maker_fee_bps > BPS_PER_UNIT
+        || request.taker_fee_bps > BPS_PER_UNIT
+    {
+        return Ok(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "INVALID_FEE",
+            "Invalid maker or taker fee",
+        )
+        .reply());
+    }
+
+    if let Err(e) =
+        check_size_limits(request.max_quantity, request.max_notional)
+    {
+        return Ok(e.reply());
+    }
+
+    let new_book: Book = Book::new(market)
+        .with_self_trade_policy(request.self_trade_policy)
+        .with_matching_algorithm(request.matching_algorithm)
+        .with_tick_size(request.tick_size)
+        .with_lot_size(request.lot_size)
+        .with_min_quantity(request.min_quantity)
+        .with_max_quantity(request.max_quantity)
+        .with_max_notional(request.max_notional)
+        .with_max_orders_per_trader(request.max_orders_per_trader)
+        .with_maker_fee_bps(request.maker_fee_bps)
+        .with_taker_fee_bps(request.taker_fee_bps);
 
     info!("Creating book {}...", market);
 
-    let mut ome_state: MutexGuard<OmeState> = state.lock().await;
+    let mut ome_state: RwLockWriteGuard<OmeState> = state.write().await;
+
+    /* reject changes while draining for shutdown */
+    if ome_state.shutting_down() {
+        return Ok(ApiError::shutting_down().reply());
+    }
 
     /* check if the market already exists and, if so, return HTTP 409 */
-    if ome_state.book(market).is_some() {
-        let status: StatusCode = StatusCode::CONFLICT;
-        let resp_body: OmeResponse = OmeResponse {
-            status: status.as_u16(),
-            message: "Market already exists".to_string(),
-        };
-        return Ok(warp::reply::with_status(
-            warp::reply::json(&resp_body),
-            status,
-        ));
+    if ome_state.has_book(market) {
+        return Ok(ApiError::new(
+            StatusCode::CONFLICT,
+            "MARKET_EXISTS",
+            "Market already exists",
+        )
+        .reply());
     }
 
     /* add the new book to the engine state */
+    let change: WalChange = WalChange::CreateBook {
+        book: new_book.clone(),
+    };
     ome_state.add_book(new_book);
+    ome_state.log(market, 0, Utc::now(), change);
 
     info!("Created book {}", market);
 
@@ -157,281 +577,2342 @@ pub async fn create_book_handler(
     ))
 }
 
-/// REST API route handler for retrieving a single order book
-pub async fn read_book_handler(
+/// REST API route handler for pausing the acceptance of new orders by a book
+///
+/// Open orders may still be cancelled (and reads continue to be served) while
+/// the book is paused.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        post,
+        path = "/book/{market}/pause",
+        tag = "books",
+        params(
+            ("market" = String, Path, description = "Address of the market")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The book was paused",
+                body = OmeResponse
+            ),
+            (
+                status = 401,
+                description = "Missing or wrong admin token",
+                body = ApiError
+            ),
+            (
+                status = 404,
+                description = "No book for the market",
+                body = ApiError
+            )
+        ),
+        security(("admin_token" = []))
+    )
+)]
+pub async fn pause_book_handler(
     market: Address,
-    state: Arc<Mutex<OmeState>>,
+    state: Arc<RwLock<OmeState>>,
 ) -> Result<impl Reply, Rejection> {
-    let ome_state: MutexGuard<OmeState> = state.lock().await;
-    let book: Book = match ome_state.book(market) {
-        Some(t) => t.clone(),
-        None => {
-            return Ok(warp::reply::with_status(
-                "Market does not exist".to_string(),
-                http::StatusCode::NOT_FOUND,
-            )
-            .into_response());
-        }
-    };
-    let payload: ExternalBook = ExternalBook::from(book);
-    Ok(json(&payload).into_response())
+    set_paused(market, true, state).await
 }
 
-/// REST API route handler for creating a single order
-pub async fn create_order_handler(
+/// REST API route handler for resuming the acceptance of new orders by a book
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        post,
+        path = "/book/{market}/resume",
+        tag = "books",
+        params(
+            ("market" = String, Path, description = "Address of the market")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The book was resumed",
+                body = OmeResponse
+            ),
+            (
+                status = 401,
+                description = "Missing or wrong admin token",
+                body = ApiError
+            ),
+            (
+                status = 404,
+                description = "No book for the market",
+                body = ApiError
+            )
+        ),
+        security(("admin_token" = []))
+    )
+)]
+pub async fn resume_book_handler(
     market: Address,
-    request: CreateOrderRequest,
-    state: Arc<Mutex<OmeState>>,
-    rpc_endpoint: String,
+    state: Arc<RwLock<OmeState>>,
 ) -> Result<impl Reply, Rejection> {
-    /* bounds check price and amount */
-    if request.price > U256::from(u128::MAX)
-        || request.amount > U256::from(u128::MAX)
-    {
-        let status: StatusCode = http::StatusCode::BAD_REQUEST;
-        let resp_body: OmeResponse = OmeResponse {
-            status: status.as_u16(),
-            message: "Integer out of bounds".to_string(),
-        };
-        return Ok(warp::reply::with_status(
-            warp::reply::json(&resp_body),
-            status,
-        ));
-    }
+    set_paused(market, false, state).await
+}
+
+/* pauses or resumes a book, however the engine hosts it */
+async fn set_paused(
+    market: Address,
+    paused: bool,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<WithStatus<Json>, Rejection> {
+    let message: &str = if paused {
+        "Market paused"
+    } else {
+        "Market resumed"
+    };
 
-    let new_order: ExternalOrder = ExternalOrder::from(request);
+    match hosted_book(&state, market).await {
+        Ok(Some(handle)) => {
+            if let Err(e) = handle.set_paused(paused).await {
+                return Ok(ApiError::from(e).reply());
+            }
+        }
+        Ok(None) => {
+            let mut ome_state: RwLockWriteGuard<OmeState> = state.write().await;
 
-    let internal_order: Order = match Order::try_from(new_order.clone()) {
-        Ok(t) => t,
-        Err(_e) => {
-            let status: StatusCode = StatusCode::BAD_REQUEST;
-            let resp_body: OmeResponse = OmeResponse {
-                status: status.as_u16(),
-                message: "Invalid order".to_string(),
+            let book: &mut Book = match ome_state.book_mut(market) {
+                Some(b) => b,
+                None => return Ok(ApiError::market_not_found().reply()),
             };
-            return Ok(warp::reply::with_status(
-                warp::reply::json(&resp_body),
-                status,
-            ));
+
+            let sequence: u64 = book.sequence;
+            if book.set_paused(paused) {
+                let change: WalChange = WalChange::SetPaused { paused };
+                ome_state.log(market, sequence, Utc::now(), change);
+            }
         }
-    };
+        Err(e) => return Ok(e.reply()),
+    }
 
-    info!("Creating order {}...", internal_order.clone());
+    info!("{} {}", message, market);
 
-    let valid_order: bool = match rpc::check_order_validity(
-        Order::try_from(new_order.clone()).unwrap(),
-        rpc_endpoint.clone(),
-    )
-    .await
-    {
-        Ok(t) => t,
-        Err(_e) => false,
+    let status: StatusCode = http::StatusCode::OK;
+    let resp_body: OmeResponse = OmeResponse {
+        status: status.as_u16(),
+        message: message.to_string(),
     };
+    Ok(warp::reply::with_status(
+        warp::reply::json(&resp_body),
+        status,
+    ))
+}
 
-    if !valid_order {
-        let status: StatusCode = warp::http::StatusCode::BAD_REQUEST;
-        let resp_body: OmeResponse = OmeResponse {
-            status: status.as_u16(),
-            message: "Invalid order".to_string(),
-        };
-        return Ok(warp::reply::with_status(
-            warp::reply::json(&resp_body),
-            status,
+/* a zero cap would make every order invalid */
+fn check_size_limits(
+    max_quantity: Option<U256>,
+    max_notional: Option<U256>,
+) -> Result<(), ApiError> {
+    if max_quantity.is_some_and(|t| t.is_zero())
+        || max_notional.is_some_and(|t| t.is_zero())
+    {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "INVALID_LIMIT",
+            "Invalid maximum quantity or notional",
         ));
     }
 
-    /* acquire lock on global state */
-    let mut ome_state: MutexGuard<OmeState> = state.lock().await;
+    Ok(())
+}
 
-    /* retrieve order book from global state */
-    let book: &mut Book = match ome_state.book_mut(market) {
-        Some(b) => b,
-        None => {
-            warn!(
-                "Failed to create order {:?} as market does not exist!",
-                new_order
-            );
-            let status: StatusCode = warp::http::StatusCode::NOT_FOUND;
-            let resp_body: OmeResponse = OmeResponse {
-                status: status.as_u16(),
-                message: "Market does not exist".to_string(),
+/// REST API route handler for changing the caps on the size of the orders of
+/// a book
+///
+/// Both caps are replaced by those of the request. Open orders are left as
+/// they are, even if they exceed the new caps.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        put,
+        path = "/book/{market}/limits",
+        tag = "books",
+        request_body = UpdateBookLimitsRequest,
+        params(
+            ("market" = String, Path, description = "Address of the market")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The caps were replaced",
+                body = OmeResponse
+            ),
+            (status = 400, description = "Invalid caps", body = ApiError),
+            (
+                status = 401,
+                description = "Missing or wrong admin token",
+                body = ApiError
+            ),
+            (
+                status = 404,
+                description = "No book for the market",
+                body = ApiError
+            )
+        ),
+        security(("admin_token" = []))
+    )
+)]
+pub async fn update_book_limits_handler(
+    market: Address,
+    request: UpdateBookLimitsRequest,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let (max_quantity, max_notional) =
+        (request.max_quantity, request.max_notional);
+
+    if let Err(e) = check_size_limits(max_quantity, max_notional) {
+        return Ok(e.reply());
+    }
+
+    match hosted_book(&state, market).await {
+        Ok(Some(handle)) => {
+            if let Err(e) =
+                handle.set_size_limits(max_quantity, max_notional).await
+            {
+                return Ok(ApiError::from(e).reply());
+            }
+        }
+        Ok(None) => {
+            let mut ome_state: RwLockWriteGuard<OmeState> = state.write().await;
+
+            let book: &mut Book = match ome_state.book_mut(market) {
+                Some(b) => b,
+                None => return Ok(ApiError::market_not_found().reply()),
             };
-            return Ok(warp::reply::with_status(
-                warp::reply::json(&resp_body),
-                status,
-            ));
+
+            let sequence: u64 = book.sequence;
+            if book.set_size_limits(max_quantity, max_notional) {
+                let change: WalChange = WalChange::SetSizeLimits {
+                    max_quantity,
+                    max_notional,
+                };
+                ome_state.log(market, sequence, Utc::now(), change);
+            }
         }
+        Err(e) => return Ok(e.reply()),
+    }
+
+    info!(
+        "Set the size limits of book {} to {:?} quantity and {:?} notional",
+        market, max_quantity, max_notional
+    );
+
+    let status: StatusCode = http::StatusCode::OK;
+    let resp_body: OmeResponse = OmeResponse {
+        status: status.as_u16(),
+        message: "Market limits updated".to_string(),
     };
+    Ok(warp::reply::with_status(
+        warp::reply::json(&resp_body),
+        status,
+    ))
+}
 
-    /* submit order to the engine for matching */
-    match book
-        .submit(Order::try_from(new_order.clone()).unwrap(), rpc_endpoint)
-        .await
-    {
-        Ok(order_status) => {
-            info!("Created order {}", internal_order.clone());
-            let status: StatusCode = StatusCode::OK;
-            let resp_body: OmeResponse = OmeResponse {
-                status: status.as_u16(),
-                message: order_status.to_string(),
+/// REST API route handler for dumping the state immediately (e.g., ahead of
+/// maintenance), rather than waiting for the OME to shut down
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        post,
+        path = "/admin/snapshot",
+        tag = "admin",
+        responses(
+            (
+                status = 200,
+                description = "The dump file was written",
+                body = DumpResponse
+            ),
+            (
+                status = 401,
+                description = "Missing or wrong admin token",
+                body = ApiError
+            ),
+            (
+                status = 500,
+                description = "The dump file could not be written",
+                body = ApiError
+            )
+        ),
+        security(("admin_token" = []))
+    )
+)]
+pub async fn dump_state_handler(
+    dumpfile: PathBuf,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    /* the books can't be changed while the lock is held, so the dump is of a
+     * single moment (besides hosted books, which are copied one by one) */
+    let ome_state: RwLockReadGuard<OmeState> = state.read().await;
+
+    /* shutting down dumps the state anyway */
+    if ome_state.shutting_down() {
+        return Ok(ApiError::shutting_down().reply());
+    }
+
+    match ome_state.dump(&dumpfile).await {
+        Ok(size) => {
+            info!("Dumped state to {} on request", dumpfile.display());
+            let payload: DumpResponse = DumpResponse {
+                path: dumpfile.display().to_string(),
+                size,
             };
-            Ok(warp::reply::with_status(
-                warp::reply::json(&resp_body),
-                status,
-            ))
+            Ok(warp::reply::with_status(json(&payload), StatusCode::OK))
         }
         Err(e) => {
-            warn!("Failed to create order {:?}! Engine said: {}", new_order, e);
-            let status: StatusCode = StatusCode::INTERNAL_SERVER_ERROR;
-            let resp_body: OmeResponse = OmeResponse {
-                status: status.as_u16(),
-                message: "Matching error occurred".to_string(),
-            };
-            Ok(warp::reply::with_status(
-                warp::reply::json(&resp_body),
-                status,
-            ))
+            error!("Failed to dump state to {}: {}", dumpfile.display(), e);
+            Ok(ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "DUMP_FAILED",
+                &format!("Failed to dump state: {}", e),
+            )
+            .reply())
         }
     }
 }
 
-/// REST API route handler for retrieving a single order
-pub async fn read_order_handler(
+/// REST API route handler for replacing an order book with the one held by
+/// the source of markets (e.g., once the two are found to have diverged)
+///
+/// The fetched book is checked just as a restored one is before it replaces
+/// the current book, which is left untouched if it can't be fetched or fails
+/// the checks.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        post,
+        path = "/admin/book/{market}/reload",
+        tag = "admin",
+        params(
+            ("market" = String, Path, description = "Address of the market")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The book was replaced",
+                body = ReloadBookResponse
+            ),
+            (
+                status = 401,
+                description = "Missing or wrong admin token",
+                body = ApiError
+            ),
+            (
+                status = 404,
+                description = "No book for the market",
+                body = ApiError
+            ),
+            (
+                status = 502,
+                description = "The book could not be fetched",
+                body = ApiError
+            )
+        ),
+        security(("admin_token" = []))
+    )
+)]
+pub async fn reload_book_handler(
     market: Address,
-    id: OrderId,
-    state: Arc<Mutex<OmeState>>,
+    client: Option<RpcClient>,
+    state: Arc<RwLock<OmeState>>,
 ) -> Result<impl Reply, Rejection> {
-    let ome_state: MutexGuard<OmeState> = state.lock().await;
-
-    /* retrieve order book */
-    let book: &Book = match ome_state.book(market) {
-        Some(b) => b,
+    let client: RpcClient = match client {
+        Some(t) => t,
         None => {
-            let status: StatusCode = warp::http::StatusCode::NOT_FOUND;
-            let resp_body: OmeResponse = OmeResponse {
-                status: status.as_u16(),
-                message: "Market does not exist".to_string(),
-            };
-            return Ok(warp::reply::with_status(
-                warp::reply::json(&resp_body),
-                status,
-            ));
+            return Ok(ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "NO_BOOK_SOURCE",
+                "No source of markets is configured to reload books from",
+            )
+            .reply())
         }
     };
 
-    /* retrieve order */
-    let order: ExternalOrder = match book.order(id) {
-        Some(o) => o.clone().into(),
-        None => {
-            let status: StatusCode = warp::http::StatusCode::NOT_FOUND;
-            let resp_body: OmeResponse = OmeResponse {
-                status: status.as_u16(),
-                message: "Order does not exist in this market".to_string(),
+    if !state.read().await.has_book(market) {
+        return Ok(ApiError::market_not_found().reply());
+    }
+
+    info!("Reloading book {}...", market);
+
+    let external: ExternalBook = match client.get_external_book(market).await {
+        Ok(t) => t,
+        Err(e) => {
+            warn!("Failed to fetch book {} for reloading: {}", market, e);
+            return Ok(ApiError::new(
+                StatusCode::BAD_GATEWAY,
+                "BOOK_SOURCE_UNAVAILABLE",
+                &format!("Failed to fetch book: {}", e),
+            )
+            .reply());
+        }
+    };
+
+    /* the book is logged as of the time it was restored, so that replaying
+     * the log prunes it alike */
+    let now: DateTime<Utc> = Utc::now();
+    let book: Book = match Book::from_external_at(external.clone(), now) {
+        Ok(t) if *t.market() == market => t,
+        invalid => {
+            let reason: String = match invalid {
+                Ok(t) => format!("Book is for market {:?}", t.market()),
+                Err(e) => e.to_string(),
             };
-            return Ok(warp::reply::with_status(
-                warp::reply::json(&resp_body),
-                status,
-            ));
+            warn!("Refusing to reload book {}: {}", market, reason);
+            return Ok(ApiError::new(
+                StatusCode::BAD_GATEWAY,
+                "INVALID_SOURCE_BOOK",
+                &format!("Fetched book is invalid: {}", reason),
+            )
+            .reply());
+        }
+    };
+
+    let mut ome_state: RwLockWriteGuard<OmeState> = state.write().await;
+
+    /* reject changes while draining for shutdown */
+    if ome_state.shutting_down() {
+        return Ok(ApiError::shutting_down().reply());
+    }
+
+    /* the book may have been removed while its replacement was fetched */
+    let (orders_before, sequence) = match ome_state.handle(market) {
+        Some(handle) => {
+            match handle.query(|t| (t.order_count(), t.sequence)).await {
+                Ok(t) => t,
+                Err(e) => return Ok(ApiError::from(e).reply()),
+            }
         }
+        None => match ome_state.book(market) {
+            Some(t) => (t.order_count(), t.sequence),
+            None => return Ok(ApiError::market_not_found().reply()),
+        },
     };
+    let orders_after: usize = book.order_count();
+
+    ome_state.add_book(book);
+    let change: WalChange = WalChange::ReloadBook { book: external };
+    ome_state.log(market, sequence, now, change);
 
-    Ok(warp::reply::with_status(json(&order), StatusCode::OK))
+    info!(
+        "Reloaded book {} ({} orders, previously {})",
+        market, orders_after, orders_before
+    );
+
+    let payload: ReloadBookResponse = ReloadBookResponse {
+        market,
+        orders_before,
+        orders_after,
+    };
+    Ok(warp::reply::with_status(json(&payload), StatusCode::OK))
 }
 
-/// REST API route handler for deleting a single order
+/// REST API route handler for blocking a trader from submitting orders
 ///
-/// Note that this is equivalent to order cancellation
-pub async fn destroy_order_handler(
-    market: Address,
-    id: OrderId,
-    state: Arc<Mutex<OmeState>>,
+/// With `cancel_existing`, the trader's open orders in every book are
+/// cancelled along with the block. Otherwise they are left as they are, and
+/// can still be cancelled (but not added to) by the trader.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        put,
+        path = "/admin/blocklist/{trader}",
+        tag = "admin",
+        params(
+            ("trader" = String, Path, description = "Address of the trader"),
+            BlockTraderQuery
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The trader is blocked",
+                body = BlockTraderResponse
+            ),
+            (
+                status = 401,
+                description = "Missing or wrong admin token",
+                body = ApiError
+            )
+        ),
+        security(("admin_token" = []))
+    )
+)]
+pub async fn block_trader_handler(
+    trader: Address,
+    query: BlockTraderQuery,
+    state: Arc<RwLock<OmeState>>,
 ) -> Result<impl Reply, Rejection> {
-    let mut ome_state: MutexGuard<OmeState> = state.lock().await;
+    let mut ome_state: RwLockWriteGuard<OmeState> = state.write().await;
 
-    /* retrieve order book */
-    let book: &mut Book = match ome_state.book_mut(market) {
-        Some(b) => b,
-        None => {
-            return Ok(warp::reply::with_status(
-                warp::reply::json(&"Market does not exist".to_string()),
-                http::StatusCode::NOT_FOUND,
-            )
-            .into_response());
+    if query.cancel_existing {
+        /* reject changes while draining for shutdown */
+        if ome_state.shutting_down() {
+            return Ok(ApiError::shutting_down().reply());
         }
-    };
 
-    /* cancel order */
-    match book.cancel(id) {
-        Ok(_t) => {}
-        Err(_e) => {
-            return Ok(warp::reply::with_status(
-                warp::reply::json(
-                    &"Order does not exist in market".to_string(),
-                ),
-                http::StatusCode::NOT_FOUND,
-            )
-            .into_response());
+        /* hosted books are only sent orders and cancellations */
+        if ome_state.hosting() {
+            return Ok(ApiError::unsupported_engine_mode().reply());
         }
+    }
+
+    let changed: bool = ome_state.block(trader);
+    let cancelled: Vec<OrderId> = if query.cancel_existing {
+        ome_state.cancel_all(trader, Utc::now())
+    } else {
+        vec![]
     };
 
-    let status: StatusCode = http::StatusCode::OK;
-    let resp_body: OmeResponse = OmeResponse {
-        status: status.as_u16(),
-        message: "Order cancelled".to_string(),
+    info!(
+        "Blocked trader {:?} (cancelling {} open orders)",
+        trader,
+        cancelled.len()
+    );
+
+    let payload: BlockTraderResponse = BlockTraderResponse {
+        trader,
+        blocked: true,
+        changed,
+        cancelled,
     };
-    Ok(
-        warp::reply::with_status(warp::reply::json(&resp_body), status)
-            .into_response(),
-    )
+    Ok(warp::reply::with_status(json(&payload), StatusCode::OK))
 }
 
-#[allow(clippy::into_iter_on_ref)]
-pub async fn market_user_orders_handler(
-    market: Address,
-    user: Address,
-    state: Arc<Mutex<OmeState>>,
+/// REST API route handler for letting a blocked trader submit orders again
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        delete,
+        path = "/admin/blocklist/{trader}",
+        tag = "admin",
+        params(
+            ("trader" = String, Path, description = "Address of the trader")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The trader is no longer blocked",
+                body = BlockTraderResponse
+            ),
+            (
+                status = 401,
+                description = "Missing or wrong admin token",
+                body = ApiError
+            )
+        ),
+        security(("admin_token" = []))
+    )
+)]
+pub async fn unblock_trader_handler(
+    trader: Address,
+    state: Arc<RwLock<OmeState>>,
 ) -> Result<impl Reply, Rejection> {
-    let mut ome_state: MutexGuard<OmeState> = state.lock().await;
+    let changed: bool = state.write().await.unblock(trader);
 
-    /* retrieve order book */
-    let book: &mut Book = match ome_state.book_mut(market) {
-        Some(b) => b,
-        None => {
-            let status: StatusCode = StatusCode::NOT_FOUND;
-            let resp_body: OmeResponse = OmeResponse {
-                status: status.as_u16(),
-                message: "Market does not exist".to_string(),
-            };
-            return Ok(warp::reply::with_status(
+    info!("Unblocked trader {:?}", trader);
+
+    let payload: BlockTraderResponse = BlockTraderResponse {
+        trader,
+        blocked: false,
+        changed,
+        cancelled: vec![],
+    };
+    Ok(warp::reply::with_status(json(&payload), StatusCode::OK))
+}
+
+/// REST API route handler for listing the traders blocked from submitting
+/// orders
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/admin/blocklist",
+        tag = "admin",
+        responses(
+            (
+                status = 200,
+                description = "The blocked traders",
+                body = BlocklistResponse
+            ),
+            (
+                status = 401,
+                description = "Missing or wrong admin token",
+                body = ApiError
+            )
+        ),
+        security(("admin_token" = []))
+    )
+)]
+pub async fn index_blocklist_handler(
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let payload: BlocklistResponse = BlocklistResponse {
+        traders: state.read().await.blocklist().iter().copied().collect(),
+    };
+    Ok(warp::reply::with_status(json(&payload), StatusCode::OK))
+}
+
+/// REST API route handler for retrieving a single order book
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/book/{market}",
+        tag = "books",
+        params(
+            ("market" = String, Path, description = "Address of the market")
+        ),
+        responses(
+            (status = 200, description = "The book", body = ExternalBook),
+            (
+                status = 404,
+                description = "No book for the market",
+                body = ApiError
+            )
+        )
+    )
+)]
+pub async fn read_book_handler(
+    market: Address,
+    format: Format,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    /* serialized from the book itself, rather than from a copy */
+    let payload: Vec<u8> =
+        match actor::query_book(&state, market, move |book| {
+            format.encode(&BookSnapshot(book))
+        })
+        .await
+        {
+            Some(Ok(t)) => t,
+            Some(Err(e)) => {
+                error!("Failed to encode book {}: {}", market, e);
+                return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+            }
+            None => {
+                return Ok(ApiError::market_not_found()
+                    .reply()
+                    .into_response());
+            }
+        };
+    Ok(warp::reply::with_header(
+        payload,
+        http::header::CONTENT_TYPE,
+        format.content_type(),
+    )
+    .into_response())
+}
+
+/// REST API route handler for catching up with an order book from a known
+/// sequence number
+///
+/// Responds with the deltas since `since_seq` while the book still retains
+/// them, and with a snapshot of the entire book otherwise (including when no
+/// sequence number is given).
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/book/{market}/snapshot",
+        tag = "books",
+        params(
+            ("market" = String, Path, description = "Address of the market"),
+            SnapshotQuery
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The book, or the changes to it since a sequence",
+                body = SnapshotResponse
+            ),
+            (
+                status = 404,
+                description = "No book for the market",
+                body = ApiError
+            )
+        )
+    )
+)]
+pub async fn read_book_snapshot_handler(
+    market: Address,
+    query: SnapshotQuery,
+    format: Format,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let payload: SnapshotResponse =
+        match actor::query_book(&state, market, move |book| {
+            match query.since_seq.and_then(|t| book.deltas_since(t)) {
+                Some(deltas) => SnapshotResponse::Deltas {
+                    seq: book.delta_sequence,
+                    deltas,
+                },
+                None => SnapshotResponse::Snapshot {
+                    seq: book.sequence,
+                    book: book.clone().into(),
+                },
+            }
+        })
+        .await
+        {
+            Some(t) => t,
+            None => {
+                return Ok(ApiError::market_not_found()
+                    .reply()
+                    .into_response());
+            }
+        };
+
+    Ok(encoded(&payload, format))
+}
+
+/// REST API route handler for retrieving the aggregated depth of an order book
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/book/{market}/depth",
+        tag = "books",
+        params(
+            ("market" = String, Path, description = "Address of the market"),
+            DepthQuery
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The aggregated depth of the book",
+                body = DepthResponse
+            ),
+            (
+                status = 404,
+                description = "No book for the market",
+                body = ApiError
+            )
+        )
+    )
+)]
+pub async fn read_depth_handler(
+    market: Address,
+    query: DepthQuery,
+    format: Format,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let to_strings = |levels: AggregatedLevels| -> Vec<(String, String)> {
+        levels
+            .iter()
+            .map(|(price, quantity)| (price.to_string(), quantity.to_string()))
+            .collect()
+    };
+    let payload: DepthResponse =
+        match actor::query_book(&state, market, move |book| {
+            let (bids, asks) = book.aggregated_depth(query.levels);
+            DepthResponse {
+                bids: to_strings(bids),
+                asks: to_strings(asks),
+                sequence: book.sequence,
+            }
+        })
+        .await
+        {
+            Some(t) => t,
+            None => {
+                return Ok(ApiError::market_not_found()
+                    .reply()
+                    .into_response());
+            }
+        };
+
+    Ok(encoded(&payload, format))
+}
+
+/// REST API route handler for retrieving the ticker of an order book
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/book/{market}/ticker",
+        tag = "books",
+        params(
+            ("market" = String, Path, description = "Address of the market")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The ticker of the book",
+                body = TickerResponse
+            ),
+            (
+                status = 404,
+                description = "No book for the market",
+                body = ApiError
+            )
+        )
+    )
+)]
+pub async fn read_ticker_handler(
+    market: Address,
+    format: Format,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let ticker: Ticker = match actor::query_book(&state, market, Book::ticker)
+        .await
+    {
+        Some(t) => t,
+        None => {
+            return Ok(ApiError::market_not_found().reply().into_response());
+        }
+    };
+
+    let payload: TickerResponse = ticker.into();
+    Ok(encoded(&payload, format))
+}
+
+/// REST API route handler for retrieving the trade statistics of an order
+/// book over the last 24 hours
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/book/{market}/stats",
+        tag = "books",
+        params(
+            ("market" = String, Path, description = "Address of the market")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The statistics of the book",
+                body = StatsResponse
+            ),
+            (
+                status = 404,
+                description = "No book for the market",
+                body = ApiError
+            )
+        )
+    )
+)]
+pub async fn read_stats_handler(
+    market: Address,
+    format: Format,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let payload: StatsResponse = match actor::query_book(&state, market, |t| {
+        StatsResponse::new(t.stats(Utc::now()), t.ltp)
+    })
+    .await
+    {
+        Some(t) => t,
+        None => {
+            return Ok(ApiError::market_not_found().reply().into_response());
+        }
+    };
+
+    Ok(encoded(&payload, format))
+}
+
+/// REST API route handler for retrieving the recent trades of an order book
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/book/{market}/trades",
+        tag = "books",
+        params(
+            ("market" = String, Path, description = "Address of the market"),
+            TradesQuery
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The most recent trades, newest first",
+                body = TradesResponse
+            ),
+            (
+                status = 404,
+                description = "No book for the market",
+                body = ApiError
+            )
+        )
+    )
+)]
+pub async fn read_trades_handler(
+    market: Address,
+    query: TradesQuery,
+    format: Format,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let payload: TradesResponse =
+        match actor::query_book(&state, market, move |book| TradesResponse {
+            trades: book
+                .recent_trades(query.limit)
+                .into_iter()
+                .map(|trade| trade.clone().into())
+                .collect(),
+            sequence: book.sequence,
+        })
+        .await
+        {
+            Some(t) => t,
+            None => {
+                return Ok(ApiError::market_not_found()
+                    .reply()
+                    .into_response());
+            }
+        };
+
+    Ok(encoded(&payload, format))
+}
+
+/// Parses an order submitted to the book of the given market
+fn parse_order(
+    market: Address,
+    request: CreateOrderRequest,
+) -> Result<Order, ApiError> {
+    /* the order must be for the market of the book it was submitted to */
+    if request.target_tracer != market {
+        return Err(BookError::WrongMarket.into());
+    }
+
+    /* bounds check price and amount */
+    if request.price > U256::from(u128::MAX)
+        || request.amount > U256::from(u128::MAX)
+    {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "INTEGER_OUT_OF_BOUNDS",
+            "Integer out of bounds",
+        ));
+    }
+
+    Order::try_from(ExternalOrder::from(request)).map_err(ApiError::from)
+}
+
+/// Parses an order submitted to the book of the given market and checks that
+/// it may be sent to the engine
+///
+/// The order must not have expired, and must have been created within
+/// `max_clock_skew` of now. If `signature_chain_id` is provided, the order
+/// must carry a valid EIP-712 signature by its trader for that chain. The
+/// order must also be deemed valid by the executioner.
+async fn validate_order(
+    market: Address,
+    request: CreateOrderRequest,
+    executioner: &RpcClient,
+    signature_chain_id: Option<u64>,
+    max_clock_skew: Duration,
+) -> Result<Order, ApiError> {
+    /* the timestamps are checked before they are hashed into the ID of the
+     * order by parsing it (in the same order as parsing would) */
+    if request.expiration <= request.created {
+        return Err(OrderParseError::ExpiryBeforeCreation.into());
+    }
+    let max_clock_skew: chrono::Duration =
+        chrono::Duration::from_std(max_clock_skew)
+            .unwrap_or_else(|_e| chrono::Duration::max_value());
+    order::check_timestamps(
+        request.expiration,
+        request.created,
+        Utc::now(),
+        max_clock_skew,
+    )?;
+
+    /* parse the order exactly once, using it for everything that follows */
+    let order: Order = parse_order(market, request)?;
+
+    if let Some(chain_id) = signature_chain_id {
+        if let Err(e) = order.verify_signature(chain_id) {
+            warn!("Rejecting {}: {}", order, e);
+            return Err(e.into());
+        }
+    }
+
+    let valid_order: bool =
+        match executioner.check_order_validity(order.clone()).await {
+            Ok(t) => t,
+            Err(e) => {
+                warn!("Failed to check order {}: {}", order, e);
+                return Err(e.into());
+            }
+        };
+
+    if !valid_order {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "INVALID_ORDER",
+            "Invalid order",
+        ));
+    }
+
+    Ok(order)
+}
+
+/// Returns the handle to the task hosting the book of the given market, if
+/// books are hosted by tasks of their own (see `actor`)
+///
+/// Fails if the OME is shutting down, as the books may no longer be changed.
+async fn hosted_book(
+    state: &RwLock<OmeState>,
+    market: Address,
+) -> Result<Option<BookHandle>, ApiError> {
+    let ome_state: RwLockReadGuard<OmeState> = state.read().await;
+
+    /* reject changes while draining for shutdown */
+    if ome_state.shutting_down() {
+        return Err(ApiError::shutting_down());
+    }
+
+    Ok(ome_state.handle(market))
+}
+
+/// Builds the API response to an order that the engine has just matched
+fn match_response(
+    submitted: Submitted,
+    client_order_id: Option<String>,
+) -> MatchResponse {
+    let match_result: MatchResult = submitted.result;
+
+    MatchResponse {
+        status: StatusCode::OK.as_u16(),
+        message: match_result.order_status.to_string(),
+        order_id: format!("{:?}", match_result.order_id),
+        client_order_id,
+        sequence: submitted.sequence,
+        remaining: match_result.remaining.to_string(),
+        resting: submitted.resting.to_string(),
+        average_price: match_result.average_price.to_string(),
+        fills: match_result
+            .fills
+            .into_iter()
+            .map(ExternalFill::from)
+            .collect(),
+        triggered: match_result
+            .triggered
+            .iter()
+            .map(|id| format!("{:?}", id))
+            .collect(),
+        closed_makers: match_result
+            .closed_makers
+            .iter()
+            .map(|id| format!("{:?}", id))
+            .collect(),
+        reduced_makers: match_result
+            .reduced_makers
+            .iter()
+            .map(|(id, remaining)| (format!("{:?}", id), remaining.to_string()))
+            .collect(),
+    }
+}
+
+/// REST API route handler for creating a single order
+///
+/// The order must not have expired, and must have been created within
+/// `max_clock_skew` of now. If `signature_chain_id` is provided, the order
+/// must carry a valid EIP-712 signature by its trader for that chain. Orders
+/// of traders that may not trade (see `OmeState::may_trade`) are refused.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        post,
+        path = "/book/{market}/order",
+        tag = "orders",
+        request_body = CreateOrderRequest,
+        params(
+            ("market" = String, Path, description = "Address of the market")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The order was matched",
+                body = MatchResponse
+            ),
+            (status = 400, description = "Invalid order", body = ApiError),
+            (
+                status = 403,
+                description = "The trader is blocked",
+                body = ApiError
+            ),
+            (
+                status = 404,
+                description = "No book for the market",
+                body = ApiError
+            ),
+            (status = 429, description = "Too many requests", body = ApiError)
+        )
+    )
+)]
+pub async fn create_order_handler(
+    market: Address,
+    request: CreateOrderRequest,
+    state: Arc<RwLock<OmeState>>,
+    executioner: Executioner,
+    signature_chain_id: Option<u64>,
+    max_clock_skew: Duration,
+) -> Result<impl Reply, Rejection> {
+    let internal_order: Order = match validate_order(
+        market,
+        request,
+        executioner.client(),
+        signature_chain_id,
+        max_clock_skew,
+    )
+    .await
+    {
+        Ok(t) => t,
+        Err(e) => return Ok(e.reply()),
+    };
+
+    if !state.read().await.may_trade(internal_order.trader) {
+        return Ok(ApiError::trader_blocked().reply());
+    }
+
+    info!("Creating order {}...", internal_order);
+
+    let order_id: OrderId = internal_order.id;
+    let client_order_id: Option<String> =
+        internal_order.client_order_id.clone();
+
+    /* hosted books match orders in their own tasks, without locking */
+    match hosted_book(&state, market).await {
+        Ok(Some(handle)) => {
+            return Ok(
+                match handle.submit(internal_order, Some(executioner)).await {
+                    Ok(submitted) => {
+                        info!("Created order {:?}", order_id);
+                        let resp_body: MatchResponse =
+                            match_response(submitted, client_order_id);
+                        warp::reply::with_status(
+                            warp::reply::json(&resp_body),
+                            StatusCode::OK,
+                        )
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Failed to create order {:?}! Engine said: {}",
+                            order_id, e
+                        );
+                        ApiError::from(e).reply()
+                    }
+                },
+            );
+        }
+        Ok(None) => {}
+        Err(e) => return Ok(e.reply()),
+    }
+
+    /* acquire lock on global state */
+    let mut ome_state: RwLockWriteGuard<OmeState> = state.write().await;
+
+    /* reject changes while draining for shutdown */
+    if ome_state.shutting_down() {
+        return Ok(ApiError::shutting_down().reply());
+    }
+
+    /* retrieve order book from global state */
+    let book: &mut Book = match ome_state.book_mut(market) {
+        Some(b) => b,
+        None => {
+            warn!(
+                "Failed to create order {} as market does not exist!",
+                internal_order
+            );
+            return Ok(ApiError::market_not_found().reply());
+        }
+    };
+
+    /* submit order to the engine for matching */
+    let change: WalChange = WalChange::Submit {
+        order: internal_order.clone().into(),
+    };
+    let sequence: u64 = book.sequence;
+    let now: DateTime<Utc> = Utc::now();
+    match book.submit_at(internal_order, Some(executioner), now).await {
+        Ok(match_result) => {
+            info!("Created order {:?}", order_id);
+            let resp_body: MatchResponse = match_response(
+                Submitted::new(book, match_result),
+                client_order_id,
+            );
+            ome_state.log(market, sequence, now, change);
+            ome_state.publish(market);
+            Ok(warp::reply::with_status(
                 warp::reply::json(&resp_body),
-                status,
+                StatusCode::OK,
+            ))
+        }
+        Err(e) => {
+            warn!("Failed to create order {:?}! Engine said: {}", order_id, e);
+            Ok(ApiError::from(e).reply())
+        }
+    }
+}
+
+/// REST API route handler for quoting an order without submitting it
+///
+/// The order is matched against the book as it stands, without changing it.
+/// Neither the order's signature nor its validity with the executioner is
+/// checked, as the order is never executed.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        post,
+        path = "/book/{market}/quote",
+        tag = "orders",
+        request_body = CreateOrderRequest,
+        params(
+            ("market" = String, Path, description = "Address of the market")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "How the order would match",
+                body = QuoteResponse
+            ),
+            (status = 400, description = "Invalid order", body = ApiError),
+            (
+                status = 404,
+                description = "No book for the market",
+                body = ApiError
             )
-            .into_response());
+        )
+    )
+)]
+pub async fn quote_order_handler(
+    market: Address,
+    request: CreateOrderRequest,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let order: Order = match parse_order(market, request) {
+        Ok(t) => t,
+        Err(e) => return Ok(e.reply()),
+    };
+
+    /* quote against the book as it stands */
+    let (match_result, sequence): (MatchResult, u64) =
+        match actor::query_book(&state, market, move |book| {
+            (book.quote_at(&order, Utc::now()), book.sequence)
+        })
+        .await
+        {
+            Some(t) => t,
+            None => {
+                return Ok(ApiError::market_not_found().reply());
+            }
+        };
+
+    /* quote only orders that would be accepted */
+    if let OrderStatus::Rejected { reason } = match_result.order_status {
+        return Ok(ApiError::from(reason).reply());
+    }
+    let slippage: U256 = match match_result.fills.first() {
+        Some(best) if best.price > match_result.average_price => {
+            best.price - match_result.average_price
         }
+        Some(best) => match_result.average_price - best.price,
+        None => U256::zero(),
     };
 
-    let bids: Vec<Order> = book
-        .bids
-        .values()
-        .into_iter()
-        .flat_map(|levels| levels.into_iter().filter(|o| o.trader == user))
-        .cloned()
+    let payload: QuoteResponse = QuoteResponse {
+        message: match_result.order_status.to_string(),
+        order_id: format!("{:?}", match_result.order_id),
+        sequence,
+        remaining: match_result.remaining.to_string(),
+        average_price: match_result.average_price.to_string(),
+        slippage: slippage.to_string(),
+        fills: match_result
+            .fills
+            .into_iter()
+            .map(ExternalFill::from)
+            .collect(),
+    };
+
+    Ok(warp::reply::with_status(json(&payload), StatusCode::OK))
+}
+
+/// REST API route handler for atomically replacing a single order
+///
+/// The new order is validated as for `create_order_handler` and then swapped
+/// in for the existing order under a single lock acquisition. The existing
+/// order is left untouched if the new order is rejected.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        post,
+        path = "/book/{market}/order/{id}/replace",
+        tag = "orders",
+        request_body = CreateOrderRequest,
+        params(
+            ("market" = String, Path, description = "Address of the market"),
+            ("id" = String, Path, description = "ID of the order")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The order was replaced",
+                body = ReplaceOrderResponse
+            ),
+            (status = 400, description = "Invalid order", body = ApiError),
+            (
+                status = 403,
+                description = "The trader is blocked",
+                body = ApiError
+            ),
+            (
+                status = 404,
+                description = "No such book or order",
+                body = ApiError
+            ),
+            (status = 429, description = "Too many requests", body = ApiError)
+        )
+    )
+)]
+pub async fn replace_order_handler(
+    market: Address,
+    id: OrderId,
+    request: CreateOrderRequest,
+    state: Arc<RwLock<OmeState>>,
+    executioner: Executioner,
+    signature_chain_id: Option<u64>,
+    max_clock_skew: Duration,
+) -> Result<impl Reply, Rejection> {
+    let internal_order: Order = match validate_order(
+        market,
+        request,
+        executioner.client(),
+        signature_chain_id,
+        max_clock_skew,
+    )
+    .await
+    {
+        Ok(t) => t,
+        Err(e) => return Ok(e.reply().into_response()),
+    };
+
+    let mut ome_state: RwLockWriteGuard<OmeState> = state.write().await;
+
+    if !ome_state.may_trade(internal_order.trader) {
+        return Ok(ApiError::trader_blocked().reply().into_response());
+    }
+
+    /* reject changes while draining for shutdown */
+    if ome_state.shutting_down() {
+        return Ok(ApiError::shutting_down().reply().into_response());
+    }
+
+    /* hosted books are only sent orders and cancellations */
+    if ome_state.hosting() {
+        return Ok(ApiError::unsupported_engine_mode().reply().into_response());
+    }
+
+    /* retrieve order book */
+    let book: &mut Book = match ome_state.book_mut(market) {
+        Some(b) => b,
+        None => {
+            return Ok(ApiError::market_not_found().reply().into_response());
+        }
+    };
+
+    /* cancel the existing order and submit its replacement */
+    let client_order_id: Option<String> =
+        internal_order.client_order_id.clone();
+    let change: WalChange = WalChange::Replace {
+        order_id: id,
+        order: internal_order.clone().into(),
+    };
+    let sequence: u64 = book.sequence;
+    let now: DateTime<Utc> = Utc::now();
+    match book
+        .replace_at(id, internal_order, Some(executioner), now)
+        .await
+    {
+        Ok((cancelled, match_result)) => {
+            let payload: ReplaceOrderResponse = ReplaceOrderResponse {
+                cancelled: cancelled.into(),
+                created: match_response(
+                    Submitted::new(book, match_result),
+                    client_order_id,
+                ),
+            };
+            ome_state.log(market, sequence, now, change);
+            ome_state.publish(market);
+            Ok(warp::reply::with_status(json(&payload), StatusCode::OK)
+                .into_response())
+        }
+        Err(e) => {
+            warn!("Failed to replace order {:?}! Engine said: {}", id, e);
+            Ok(ApiError::from(e).reply().into_response())
+        }
+    }
+}
+
+/// REST API route handler for retrieving a single order
+///
+/// From `ApiVersion::V2`, the order is served along with its status, and
+/// orders that have closed are still served until their histories are
+/// evicted. Earlier versions serve open orders alone, as they are.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/book/{market}/order/{id}",
+        tag = "orders",
+        params(
+            ("market" = String, Path, description = "Address of the market"),
+            ("id" = String, Path, description = "ID of the order")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The order and its status",
+                body = OrderView
+            ),
+            (
+                status = 404,
+                description = "No such book or order",
+                body = ApiError
+            )
+        )
+    )
+)]
+pub async fn read_order_handler(
+    market: Address,
+    id: OrderId,
+    format: Format,
+    version: ApiVersion,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    /* retrieve order */
+    let view: OrderView = match actor::query_book(&state, market, move |t| {
+        OrderView::new(id, t.order(id), t.order_history(id))
+    })
+    .await
+    {
+        Some(Some(t)) => t,
+        Some(None) => {
+            return Ok(ApiError::from(BookError::NoSuchOrder)
+                .reply()
+                .into_response());
+        }
+        None => {
+            return Ok(ApiError::market_not_found().reply().into_response());
+        }
+    };
+
+    match version {
+        ApiVersion::V1 => match view.order {
+            Some(order) => Ok(encoded(&order, format)),
+            None => Ok(ApiError::from(BookError::NoSuchOrder)
+                .reply()
+                .into_response()),
+        },
+        ApiVersion::V2 => Ok(encoded(&view, format)),
+    }
+}
+
+/// REST API route handler for retrieving the history of a single order
+///
+/// Unlike `read_order_handler`, this serves every event of the order.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/book/{market}/order/{id}/history",
+        tag = "orders",
+        params(
+            ("market" = String, Path, description = "Address of the market"),
+            ("id" = String, Path, description = "ID of the order")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The history of the order",
+                body = OrderHistoryResponse
+            ),
+            (
+                status = 404,
+                description = "No such book or order",
+                body = ApiError
+            )
+        )
+    )
+)]
+pub async fn read_order_history_handler(
+    market: Address,
+    id: OrderId,
+    format: Format,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    /* retrieve order history */
+    let response: OrderHistoryResponse =
+        match actor::query_book(&state, market, move |book| {
+            book.order_history(id)
+                .map(|t| OrderHistoryResponse::new(id, t))
+        })
+        .await
+        {
+            Some(Some(t)) => t,
+            None => {
+                return Ok(ApiError::market_not_found()
+                    .reply()
+                    .into_response());
+            }
+            Some(None) => {
+                return Ok(ApiError::new(
+                    StatusCode::NOT_FOUND,
+                    "HISTORY_NOT_FOUND",
+                    "No history for this order in this market",
+                )
+                .reply()
+                .into_response());
+            }
+        };
+
+    Ok(encoded(&response, format))
+}
+
+/// REST API route handler for retrieving a single open order by the ID its
+/// trader assigned to it
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/book/{market}/order/by-client-id/{client_order_id}",
+        tag = "orders",
+        params(
+            ("market" = String, Path, description = "Address of the market"),
+            (
+                "client_order_id" = String, Path,
+                description = "ID from the trader"
+            ),
+            ClientOrderQuery
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The open order",
+                body = ExternalOrder
+            ),
+            (
+                status = 404,
+                description = "No such book or order",
+                body = ApiError
+            )
+        )
+    )
+)]
+pub async fn read_client_order_handler(
+    market: Address,
+    client_order_id: String,
+    query: ClientOrderQuery,
+    format: Format,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    /* retrieve order */
+    let order: ExternalOrder =
+        match actor::query_book(&state, market, move |book| {
+            book.order_by_client_id(query.user, &client_order_id)
+                .cloned()
+        })
+        .await
+        {
+            Some(Some(o)) => o.into(),
+            Some(None) => {
+                return Ok(ApiError::from(BookError::NoSuchOrder)
+                    .reply()
+                    .into_response());
+            }
+            None => {
+                return Ok(ApiError::market_not_found()
+                    .reply()
+                    .into_response());
+            }
+        };
+
+    Ok(encoded(&order, format))
+}
+
+/// REST API route handler for retrieving a single order in any market
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/order/{id}",
+        tag = "orders",
+        params(
+            ("id" = String, Path, description = "ID of the order")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The open order",
+                body = ExternalOrder
+            ),
+            (status = 404, description = "No such order", body = ApiError)
+        )
+    )
+)]
+pub async fn read_global_order_handler(
+    id: OrderId,
+    format: Format,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let ome_state: RwLockReadGuard<OmeState> = state.read().await;
+
+    /* orders aren't indexed across hosted books */
+    if ome_state.hosting() {
+        return Ok(ApiError::unsupported_engine_mode().reply().into_response());
+    }
+
+    /* retrieve order, wherever it is */
+    let order: ExternalOrder = match ome_state.find_order(id) {
+        Some((_market, o)) => o.clone().into(),
+        None => {
+            return Ok(ApiError::from(BookError::NoSuchOrder)
+                .reply()
+                .into_response());
+        }
+    };
+
+    Ok(encoded(&order, format))
+}
+
+/// REST API route handler for amending a single order
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        put,
+        path = "/book/{market}/order/{id}",
+        tag = "orders",
+        request_body = UpdateOrderRequest,
+        params(
+            ("market" = String, Path, description = "Address of the market"),
+            ("id" = String, Path, description = "ID of the order")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The amended order",
+                body = ExternalOrder
+            ),
+            (status = 400, description = "Invalid amendment", body = ApiError),
+            (
+                status = 404,
+                description = "No such book or order",
+                body = ApiError
+            ),
+            (status = 429, description = "Too many requests", body = ApiError)
+        )
+    )
+)]
+pub async fn update_order_handler(
+    market: Address,
+    id: OrderId,
+    request: UpdateOrderRequest,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let mut ome_state: RwLockWriteGuard<OmeState> = state.write().await;
+
+    /* reject changes while draining for shutdown */
+    if ome_state.shutting_down() {
+        return Ok(ApiError::shutting_down().reply().into_response());
+    }
+
+    /* hosted books are only sent orders and cancellations */
+    if ome_state.hosting() {
+        return Ok(ApiError::unsupported_engine_mode().reply().into_response());
+    }
+
+    /* retrieve order book */
+    let book: &mut Book = match ome_state.book_mut(market) {
+        Some(b) => b,
+        None => {
+            return Ok(ApiError::market_not_found().reply().into_response());
+        }
+    };
+
+    /* amend order */
+    let sequence: u64 = book.sequence;
+    let now: DateTime<Utc> = Utc::now();
+    match book.amend_at(id, request.price, request.amount, now) {
+        Ok(order) => {
+            let change: WalChange = WalChange::Amend {
+                order_id: id,
+                price: request.price,
+                quantity: request.amount,
+            };
+            ome_state.log(market, sequence, now, change);
+            ome_state.publish(market);
+            let payload: ExternalOrder = order.into();
+            Ok(warp::reply::with_status(json(&payload), StatusCode::OK)
+                .into_response())
+        }
+        Err(e) => Ok(ApiError::from(e).reply().into_response()),
+    }
+}
+
+/// REST API route handler for deleting a single order
+///
+/// Note that this is equivalent to order cancellation
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        delete,
+        path = "/book/{market}/order/{id}",
+        tag = "orders",
+        params(
+            ("market" = String, Path, description = "Address of the market"),
+            ("id" = String, Path, description = "ID of the order")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The cancelled order",
+                body = ExternalOrder
+            ),
+            (
+                status = 404,
+                description = "No such book or order",
+                body = ApiError
+            ),
+            (status = 429, description = "Too many requests", body = ApiError)
+        )
+    )
+)]
+pub async fn destroy_order_handler(
+    market: Address,
+    id: OrderId,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    /* hosted books cancel orders in their own tasks, without locking */
+    match hosted_book(&state, market).await {
+        Ok(Some(handle)) => {
+            return Ok(match handle.cancel(id).await {
+                Ok(Some(order)) => {
+                    let payload: ExternalOrder = order.into();
+                    warp::reply::with_status(json(&payload), StatusCode::OK)
+                        .into_response()
+                }
+                Ok(None) => ApiError::from(BookError::NoSuchOrder)
+                    .reply()
+                    .into_response(),
+                Err(e) => ApiError::from(e).reply().into_response(),
+            });
+        }
+        Ok(None) => {}
+        Err(e) => return Ok(e.reply().into_response()),
+    }
+
+    let mut ome_state: RwLockWriteGuard<OmeState> = state.write().await;
+
+    /* reject changes while draining for shutdown */
+    if ome_state.shutting_down() {
+        return Ok(ApiError::shutting_down().reply().into_response());
+    }
+
+    /* retrieve order book */
+    let book: &mut Book = match ome_state.book_mut(market) {
+        Some(b) => b,
+        None => {
+            return Ok(ApiError::market_not_found().reply().into_response());
+        }
+    };
+
+    /* cancel order, treating an unknown ID as an error */
+    let sequence: u64 = book.sequence;
+    let now: DateTime<Utc> = Utc::now();
+    match book
+        .cancel_at(id, now)
+        .and_then(|t| t.ok_or(BookError::NoSuchOrder))
+    {
+        Ok(order) => {
+            let change: WalChange = WalChange::Cancel { order_id: id };
+            ome_state.log(market, sequence, now, change);
+            ome_state.publish(market);
+            let payload: ExternalOrder = order.into();
+            Ok(warp::reply::with_status(json(&payload), StatusCode::OK)
+                .into_response())
+        }
+        Err(e) => Ok(ApiError::from(e).reply().into_response()),
+    }
+}
+
+/// REST API route handler for deleting a single order in any market
+///
+/// Note that this is equivalent to order cancellation
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        delete,
+        path = "/order/{id}",
+        tag = "orders",
+        params(
+            ("id" = String, Path, description = "ID of the order")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The cancelled order",
+                body = ExternalOrder
+            ),
+            (status = 404, description = "No such order", body = ApiError),
+            (status = 429, description = "Too many requests", body = ApiError)
+        )
+    )
+)]
+pub async fn destroy_global_order_handler(
+    id: OrderId,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let mut ome_state: RwLockWriteGuard<OmeState> = state.write().await;
+
+    /* reject changes while draining for shutdown */
+    if ome_state.shutting_down() {
+        return Ok(ApiError::shutting_down().reply().into_response());
+    }
+
+    /* hosted books are only sent orders and cancellations */
+    if ome_state.hosting() {
+        return Ok(ApiError::unsupported_engine_mode().reply().into_response());
+    }
+
+    /* locate the order's market */
+    let market: Address = match ome_state.find_order(id) {
+        Some((market, _order)) => market,
+        None => {
+            return Ok(ApiError::from(BookError::NoSuchOrder)
+                .reply()
+                .into_response());
+        }
+    };
+    let book: &mut Book = match ome_state.book_mut(market) {
+        Some(b) => b,
+        None => {
+            return Ok(ApiError::market_not_found().reply().into_response());
+        }
+    };
+
+    /* cancel order, treating an unknown ID as an error */
+    let sequence: u64 = book.sequence;
+    let now: DateTime<Utc> = Utc::now();
+    match book
+        .cancel_at(id, now)
+        .and_then(|t| t.ok_or(BookError::NoSuchOrder))
+    {
+        Ok(order) => {
+            let change: WalChange = WalChange::Cancel { order_id: id };
+            ome_state.log(market, sequence, now, change);
+            ome_state.publish(market);
+            let payload: ExternalOrder = order.into();
+            Ok(warp::reply::with_status(json(&payload), StatusCode::OK)
+                .into_response())
+        }
+        Err(e) => Ok(ApiError::from(e).reply().into_response()),
+    }
+}
+
+/// REST API route handler for cancelling several orders in a market at once
+///
+/// Every order is cancelled under a single lock acquisition. IDs without a
+/// matching open order are reported as such rather than failing the batch.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        post,
+        path = "/book/{market}/orders/cancel",
+        tag = "orders",
+        request_body = CancelOrdersRequest,
+        params(
+            ("market" = String, Path, description = "Address of the market")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The outcome for each order",
+                body = CancelOrdersResponse
+            ),
+            (
+                status = 401,
+                description = "Missing or wrong admin token",
+                body = ApiError
+            ),
+            (
+                status = 404,
+                description = "No book for the market",
+                body = ApiError
+            )
+        ),
+        security(("admin_token" = []))
+    )
+)]
+pub async fn cancel_orders_handler(
+    market: Address,
+    request: CancelOrdersRequest,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let mut ome_state: RwLockWriteGuard<OmeState> = state.write().await;
+
+    /* reject changes while draining for shutdown */
+    if ome_state.shutting_down() {
+        return Ok(ApiError::shutting_down().reply().into_response());
+    }
+
+    /* hosted books are only sent orders and cancellations */
+    if ome_state.hosting() {
+        return Ok(ApiError::unsupported_engine_mode().reply().into_response());
+    }
+
+    /* retrieve order book */
+    let book: &mut Book = match ome_state.book_mut(market) {
+        Some(b) => b,
+        None => {
+            return Ok(ApiError::market_not_found().reply().into_response());
+        }
+    };
+
+    let now: DateTime<Utc> = Utc::now();
+    let mut changes: Vec<(u64, WalChange)> = vec![];
+    let results: Vec<CancelResult> = request
+        .order_ids
+        .iter()
+        .map(|id| {
+            let sequence: u64 = book.sequence;
+            let cancelled: Option<Order> =
+                book.cancel_at(*id, now).ok().flatten();
+            if cancelled.is_some() {
+                changes.push((sequence, WalChange::Cancel { order_id: *id }));
+            }
+            CancelResult {
+                order_id: format!("{:?}", id),
+                outcome: match cancelled {
+                    Some(_) => CancelOutcome::Cancelled,
+                    None => CancelOutcome::NotFound,
+                },
+                remaining: cancelled.map(|order| order.remaining.to_string()),
+            }
+        })
         .collect();
+    let payload: CancelOrdersResponse = CancelOrdersResponse {
+        results,
+        sequence: book.sequence,
+    };
+    for (sequence, change) in changes {
+        ome_state.log(market, sequence, now, change);
+    }
+    ome_state.publish(market);
+
+    Ok(json(&payload).into_response())
+}
+
+/// REST API route handler for cancelling all of a trader's orders in a market
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        delete,
+        path = "/book/{market}/orders/{trader}",
+        tag = "orders",
+        params(
+            ("market" = String, Path, description = "Address of the market"),
+            ("trader" = String, Path, description = "Address of the trader")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The IDs of the cancelled orders",
+                body = HashMap<String, Vec<String>>
+            ),
+            (
+                status = 401,
+                description = "Missing or wrong admin token",
+                body = ApiError
+            ),
+            (
+                status = 404,
+                description = "No book for the market",
+                body = ApiError
+            )
+        ),
+        security(("admin_token" = []))
+    )
+)]
+pub async fn cancel_user_orders_handler(
+    market: Address,
+    user: Address,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let mut ome_state: RwLockWriteGuard<OmeState> = state.write().await;
+
+    /* reject changes while draining for shutdown */
+    if ome_state.shutting_down() {
+        return Ok(ApiError::shutting_down().reply().into_response());
+    }
+
+    /* hosted books are only sent orders and cancellations */
+    if ome_state.hosting() {
+        return Ok(ApiError::unsupported_engine_mode().reply().into_response());
+    }
+
+    /* retrieve order book */
+    let book: &mut Book = match ome_state.book_mut(market) {
+        Some(b) => b,
+        None => {
+            return Ok(ApiError::market_not_found().reply().into_response());
+        }
+    };
 
-    let asks: Vec<Order> = book
-        .asks
-        .values()
+    let sequence: u64 = book.sequence;
+    let now: DateTime<Utc> = Utc::now();
+    let mut result: HashMap<String, Vec<OrderId>> = HashMap::new();
+    result.insert(
+        "cancelled".to_string(),
+        book.cancel_all_at(user, now)
+            .iter()
+            .map(|order| order.id)
+            .collect(),
+    );
+    ome_state.log(market, sequence, now, WalChange::CancelAll { trader: user });
+    ome_state.publish(market);
+
+    Ok(json(&result).into_response())
+}
+
+/// REST API route handler for listing a trader's resting orders in a market
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/book/{market}/{trader}",
+        tag = "users",
+        params(
+            ("market" = String, Path, description = "Address of the market"),
+            ("trader" = String, Path, description = "Address of the trader")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The open orders of the trader",
+                body = Vec<ExternalOrder>
+            ),
+            (
+                status = 404,
+                description = "No book for the market",
+                body = ApiError
+            )
+        )
+    )
+)]
+pub async fn market_user_orders_handler(
+    market: Address,
+    user: Address,
+    format: Format,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let orders: Vec<ExternalOrder> =
+        match actor::query_book(&state, market, move |book| {
+            book.orders_by_trader(user)
+                .into_iter()
+                .cloned()
+                .map(ExternalOrder::from)
+                .collect()
+        })
+        .await
+        {
+            Some(t) => t,
+            None => {
+                return Ok(ApiError::market_not_found()
+                    .reply()
+                    .into_response());
+            }
+        };
+
+    Ok(encoded(&orders, format))
+}
+
+/// REST API route handler for listing a trader's resting orders in every
+/// market, grouped by market
+///
+/// Markets are walked in order of address, and pagination applies to the
+/// orders across all of them (before grouping).
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/user/{trader}/orders",
+        tag = "users",
+        params(
+            ("trader" = String, Path, description = "Address of the trader"),
+            UserOrdersQuery
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The open orders of the trader, by market",
+                body = HashMap<String, Vec<ExternalOrder>>
+            )
+        )
+    )
+)]
+pub async fn user_orders_handler(
+    user: Address,
+    query: UserOrdersQuery,
+    format: Format,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let ome_state: RwLockReadGuard<OmeState> = state.read().await;
+
+    /* hosted books are queried one market at a time */
+    if ome_state.hosting() {
+        return Ok(ApiError::unsupported_engine_mode().reply().into_response());
+    }
+
+    let mut markets: Vec<&Address> = ome_state.books().keys().collect();
+    markets.sort();
+
+    let limit: usize = if query.limit == 0 {
+        usize::MAX
+    } else {
+        query.limit
+    };
+    let mut payload: UserOrdersResponse = BTreeMap::new();
+
+    for (market, order) in markets
         .into_iter()
-        .flat_map(|levels| levels.into_iter().filter(|o| o.trader == user))
-        .cloned()
-        .collect();
+        .filter_map(|market| Some((market, ome_state.book(*market)?)))
+        .flat_map(|(market, book)| {
+            book.orders_by_trader(user)
+                .into_iter()
+                .map(move |order| (market, order))
+        })
+        .skip(query.offset)
+        .take(limit)
+    {
+        payload
+            .entry(format!("{:?}", market))
+            .or_default()
+            .push(order.clone().into());
+    }
 
-    let orders: Vec<ExternalOrder> = bids
-        .iter()
-        .cloned()
-        .chain(asks.iter().cloned())
-        .map(ExternalOrder::from)
+    Ok(encoded(&payload, format))
+}
+
+/// REST API route handler for summarising a trader's open orders and exposure
+/// in every market, and in total
+///
+/// Markets in which the trader has no open orders are left out, so a trader
+/// unknown to every book has no markets and a total of zero.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/user/{trader}/summary",
+        tag = "users",
+        params(
+            ("trader" = String, Path, description = "Address of the trader")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The exposure of the trader",
+                body = UserSummaryResponse
+            )
+        )
+    )
+)]
+pub async fn user_summary_handler(
+    user: Address,
+    format: Format,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let mut markets: Vec<Address> = state.read().await.markets();
+    markets.sort();
+
+    let mut total: TraderExposure = TraderExposure::default();
+    let mut by_market: BTreeMap<String, ExposureResponse> = BTreeMap::new();
+
+    for market in markets {
+        /* books may be removed in the meantime, and are then skipped */
+        let exposure: TraderExposure =
+            match actor::query_book(&state, market, move |book| {
+                book.trader_exposure(user)
+            })
+            .await
+            {
+                Some(t) if t.open_orders > 0 => t,
+                _ => continue,
+            };
+
+        total = total.combine(exposure);
+        by_market.insert(format!("{:?}", market), exposure.into());
+    }
+
+    let payload: UserSummaryResponse = UserSummaryResponse {
+        markets: by_market,
+        total: total.into(),
+    };
+    Ok(encoded(&payload, format))
+}
+
+/// REST API route handler for registering a webhook, which is notified of
+/// every subsequent fill it asks for
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        post,
+        path = "/webhooks",
+        tag = "webhooks",
+        request_body = CreateWebhookRequest,
+        responses(
+            (
+                status = 201,
+                description = "The webhook was registered",
+                body = WebhookResponse
+            ),
+            (status = 400, description = "Invalid webhook", body = ApiError),
+            (
+                status = 401,
+                description = "Missing or wrong admin token",
+                body = ApiError
+            )
+        ),
+        security(("admin_token" = []))
+    )
+)]
+pub async fn create_webhook_handler(
+    request: CreateWebhookRequest,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let valid_url: bool = Url::parse(&request.url)
+        .is_ok_and(|t| t.scheme() == "http" || t.scheme() == "https");
+
+    if !valid_url || request.secret.is_empty() {
+        return Ok(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "INVALID_WEBHOOK",
+            "Webhook URL must be HTTP(S) and its secret must not be empty",
+        )
+        .reply());
+    }
+
+    let mut ome_state: RwLockWriteGuard<OmeState> = state.write().await;
+    let response: WebhookResponse =
+        WebhookResponse::from(ome_state.webhooks_mut().register(
+            request.url,
+            request.market,
+            request.user,
+            request.secret,
+        ));
+
+    info!("Registered webhook {} at {}", response.id, response.url);
+
+    Ok(warp::reply::with_status(
+        json(&response),
+        StatusCode::CREATED,
+    ))
+}
+
+/// REST API route handler for listing the registered webhooks
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/webhooks",
+        tag = "webhooks",
+        responses(
+            (
+                status = 200,
+                description = "The registered webhooks",
+                body = Vec<WebhookResponse>
+            ),
+            (
+                status = 401,
+                description = "Missing or wrong admin token",
+                body = ApiError
+            )
+        ),
+        security(("admin_token" = []))
+    )
+)]
+pub async fn index_webhook_handler(
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let ome_state: RwLockReadGuard<OmeState> = state.read().await;
+    let webhooks: Vec<WebhookResponse> = ome_state
+        .webhooks()
+        .list()
+        .into_iter()
+        .map(WebhookResponse::from)
         .collect();
 
-    Ok(json(&orders).into_response())
+    Ok(warp::reply::with_status(json(&webhooks), StatusCode::OK))
+}
+
+/// REST API route handler for unregistering a webhook
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        delete,
+        path = "/webhooks/{id}",
+        tag = "webhooks",
+        params(
+            ("id" = u64, Path, description = "ID of the webhook")
+        ),
+        responses(
+            (
+                status = 200,
+                description = "The webhook was removed",
+                body = WebhookResponse
+            ),
+            (
+                status = 401,
+                description = "Missing or wrong admin token",
+                body = ApiError
+            ),
+            (status = 404, description = "No such webhook", body = ApiError)
+        ),
+        security(("admin_token" = []))
+    )
+)]
+pub async fn destroy_webhook_handler(
+    id: u64,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Rejection> {
+    let mut ome_state: RwLockWriteGuard<OmeState> = state.write().await;
+
+    let response: WebhookResponse = match ome_state.webhooks_mut().remove(id) {
+        Some(t) => WebhookResponse::from(&t),
+        None => {
+            return Ok(ApiError::new(
+                StatusCode::NOT_FOUND,
+                "WEBHOOK_NOT_FOUND",
+                "Webhook does not exist",
+            )
+            .reply());
+        }
+    };
+
+    info!("Unregistered webhook {} at {}", response.id, response.url);
+
+    Ok(warp::reply::with_status(json(&response), StatusCode::OK))
+}
+
+/// WebSocket route handler for subscribing to changes to order books
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/ws",
+        tag = "feed",
+        responses(
+            (
+                status = 101,
+                description = "Switched to the feed of changes to books"
+            )
+        )
+    )
+)]
+pub async fn ws_handler(
+    ws: Ws,
+    state: Arc<RwLock<OmeState>>,
+) -> Result<impl Reply, Infallible> {
+    Ok(ws.on_upgrade(move |socket| ws::handle_socket(socket, state)))
 }
@@ -0,0 +1,2884 @@
+use std::convert::{Infallible, TryFrom};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use ethereum_types::{Address, U256};
+use futures_util::future;
+use tokio::sync::RwLock;
+use tokio::time;
+use warp::http::{Method, StatusCode};
+use warp::{Filter, Rejection, Reply};
+
+use crate::actor;
+use crate::api::{
+    ApiError, ApiVersion, BlockTraderQuery, BlockTraderResponse,
+    BlocklistResponse, CancelOrdersResponse, CancelOutcome, ClientOrderQuery,
+    CreateOrderRequest, DepthQuery, DepthResponse, DumpResponse,
+    HealthResponse, MatchResponse, OrderHistoryResponse, OrderView,
+    OrderViewStatus, QuoteResponse, ReloadBookResponse, ReplaceOrderResponse,
+    SnapshotQuery, SnapshotResponse, StatsResponse, TickerResponse,
+    TradesQuery, TradesResponse, UserOrdersQuery, UserOrdersResponse,
+    UserSummaryResponse, WebhookResponse,
+};
+use crate::args::{AllowedOrigins, DEFAULT_MAX_CLOCK_SKEW};
+use crate::book::{Book, ExternalBook, ExternalFill, MAX_DELTAS};
+use crate::book_tests::test_executioner;
+use crate::codec::{Format, MSGPACK_CONTENT_TYPE};
+use crate::event::LevelChange;
+use crate::executioner::Executioner;
+use crate::handler::{self, DEFAULT_MAX_BODY_SIZE};
+use crate::history::OrderEventKind;
+use crate::limit::{self, RateLimiter};
+use crate::order::{
+    ExternalOrder, Order, OrderId, OrderSide, OrderType, TimeInForce,
+};
+use crate::routes::{self, LegacyRouteMetrics, RouteContext};
+use crate::rpc::RpcClient;
+use crate::rpc_tests::mock_client;
+use crate::state::{OmeState, Readiness};
+
+/* builds the same DELETE route as `main` over the supplied state */
+fn destroy_order_route(
+    state: Arc<RwLock<OmeState>>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("book" / Address / "order" / OrderId)
+        .and(warp::delete())
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::destroy_order_handler)
+}
+
+/* the maximum clock skew orders are submitted with, as in `main` */
+fn max_clock_skew() -> std::time::Duration {
+    std::time::Duration::from_secs(DEFAULT_MAX_CLOCK_SKEW)
+}
+
+/* creates engine state holding a single book with one resting bid */
+async fn setup() -> (Arc<RwLock<OmeState>>, Address, Order) {
+    let market: Address = Address::from_low_u64_be(1);
+    let order: Order = Order::new(
+        Address::from_low_u64_be(2),
+        market,
+        OrderSide::Bid,
+        U256::from(100),
+        U256::from(10),
+        Utc::now() + Duration::days(365),
+        Utc::now(),
+        vec![],
+    );
+
+    let mut book: Book = Book::new(market);
+    book.submit(order.clone(), test_executioner())
+        .await
+        .unwrap();
+
+    let mut state: OmeState = OmeState::new();
+    state.add_book(book);
+
+    (Arc::new(RwLock::new(state)), market, order)
+}
+
+#[tokio::test]
+pub async fn test_destroy_order_unknown_book() {
+    let (state, _, order) = setup().await;
+
+    let res = warp::test::request()
+        .method("DELETE")
+        .path(&format!(
+            "/book/{:x}/order/{:x}",
+            Address::from_low_u64_be(99),
+            order.id
+        ))
+        .reply(&destroy_order_route(state))
+        .await;
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.status, 404);
+    assert_eq!(body.code, "MARKET_NOT_FOUND");
+}
+
+#[tokio::test]
+pub async fn test_destroy_order_unknown_order() {
+    let (state, market, _) = setup().await;
+
+    let res = warp::test::request()
+        .method("DELETE")
+        .path(&format!(
+            "/book/{:x}/order/{:x}",
+            market,
+            OrderId::from_low_u64_be(42)
+        ))
+        .reply(&destroy_order_route(state.clone()))
+        .await;
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.code, "ORDER_NOT_FOUND");
+    assert_eq!(body.message, "Order does not exist in this market");
+    assert_eq!(state.read().await.book(market).unwrap().depth(), (1, 0));
+}
+
+#[tokio::test]
+pub async fn test_destroy_order_success() {
+    let (state, market, order) = setup().await;
+
+    let res = warp::test::request()
+        .method("DELETE")
+        .path(&format!("/book/{:x}/order/{:x}", market, order.id))
+        .reply(&destroy_order_route(state.clone()))
+        .await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: ExternalOrder = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body, ExternalOrder::from(order.clone()));
+    assert_eq!(body.amount_left, "10");
+    assert!(state
+        .read()
+        .await
+        .book(market)
+        .unwrap()
+        .order(order.id)
+        .is_none());
+}
+
+#[tokio::test]
+pub async fn test_destroy_order_hosted() {
+    let (state, market, order) = setup().await;
+    state.write().await.host_books();
+
+    let res = warp::test::request()
+        .method("DELETE")
+        .path(&format!("/book/{:x}/order/{:x}", market, order.id))
+        .reply(&destroy_order_route(state.clone()))
+        .await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: ExternalOrder = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body, ExternalOrder::from(order.clone()));
+
+    /* the book's own task made the change, so it's gone from its copy */
+    let res = warp::test::request()
+        .method("DELETE")
+        .path(&format!("/book/{:x}/order/{:x}", market, order.id))
+        .reply(&destroy_order_route(state.clone()))
+        .await;
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.code, "ORDER_NOT_FOUND");
+    assert_eq!(
+        actor::query_book(&state, market, |t| t.depth()).await,
+        Some((0, 0))
+    );
+}
+
+#[tokio::test]
+pub async fn test_read_depth() {
+    let (state, market, _) = setup().await;
+    let route = warp::path!("book" / Address / "depth")
+        .and(warp::get())
+        .and(warp::query::<DepthQuery>())
+        .and(handler::accept())
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::read_depth_handler);
+
+    let res = warp::test::request()
+        .path(&format!("/book/{:x}/depth?levels=25", market))
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: DepthResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.bids, vec![("100".to_string(), "10".to_string())]);
+    assert!(body.asks.is_empty());
+
+    let res = warp::test::request()
+        .path(&format!("/book/{:x}/depth", Address::from_low_u64_be(99)))
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+/* catches up with the given market via the same route as `main` */
+async fn read_snapshot(
+    state: Arc<RwLock<OmeState>>,
+    market: Address,
+    query: &str,
+) -> SnapshotResponse {
+    let route = warp::path!("book" / Address / "snapshot")
+        .and(warp::get())
+        .and(warp::query::<SnapshotQuery>())
+        .and(handler::accept())
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::read_book_snapshot_handler);
+
+    let res = warp::test::request()
+        .path(&format!("/book/{:x}/snapshot{}", market, query))
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    serde_json::from_slice(res.body()).unwrap()
+}
+
+#[tokio::test]
+pub async fn test_read_book_snapshot() {
+    let (state, market, order) = setup().await;
+    warp::test::request()
+        .method("DELETE")
+        .path(&format!("/book/{:x}/order/{:x}", market, order.id))
+        .reply(&destroy_order_route(state.clone()))
+        .await;
+
+    /* recent enough to catch up from the deltas alone */
+    match read_snapshot(state.clone(), market, "?since_seq=1").await {
+        SnapshotResponse::Deltas { seq, deltas } => {
+            assert_eq!(seq, 2);
+            assert_eq!(deltas.len(), 1);
+            assert_eq!(
+                deltas[0].changes,
+                vec![LevelChange {
+                    side: OrderSide::Bid,
+                    price: "100".to_string(),
+                    new_total: "0".to_string(),
+                }]
+            );
+        }
+        other => panic!("Expected deltas, got {:?}", other),
+    }
+
+    /* without a sequence number, there is nothing to catch up from */
+    assert!(matches!(
+        read_snapshot(state.clone(), market, "").await,
+        SnapshotResponse::Snapshot { seq: 2, .. }
+    ));
+
+    /* too old, here's a full snapshot */
+    for price in 1..=(MAX_DELTAS as u64) {
+        let bid: Order = Order::new(
+            Address::from_low_u64_be(3),
+            market,
+            OrderSide::Bid,
+            U256::from(price),
+            U256::from(1),
+            Utc::now() + Duration::days(365),
+            Utc::now(),
+            vec![],
+        );
+        let mut ome_state = state.write().await;
+        ome_state
+            .book_mut(market)
+            .unwrap()
+            .submit(bid, test_executioner())
+            .await
+            .unwrap();
+        ome_state.publish(market);
+    }
+
+    match read_snapshot(state.clone(), market, "?since_seq=1").await {
+        SnapshotResponse::Snapshot { seq, book } => {
+            assert_eq!(seq, 2 + MAX_DELTAS as u64);
+            assert_eq!(book.sequence, seq);
+            assert_eq!(book.bids.len(), MAX_DELTAS);
+        }
+        other => panic!("Expected a snapshot, got {:?}", other),
+    }
+}
+
+/* fetches the ticker of the given market via the same route as `main` */
+async fn read_ticker(
+    state: Arc<RwLock<OmeState>>,
+    market: Address,
+) -> TickerResponse {
+    let route = warp::path!("book" / Address / "ticker")
+        .and(warp::get())
+        .and(handler::accept())
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::read_ticker_handler);
+
+    let res = warp::test::request()
+        .path(&format!("/book/{:x}/ticker", market))
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    serde_json::from_slice(res.body()).unwrap()
+}
+
+#[tokio::test]
+pub async fn test_read_ticker_empty_book() {
+    let market: Address = Address::from_low_u64_be(1);
+    let mut state: OmeState = OmeState::new();
+    state.add_book(Book::new(market));
+
+    let ticker: TickerResponse =
+        read_ticker(Arc::new(RwLock::new(state)), market).await;
+
+    assert_eq!(
+        ticker,
+        TickerResponse {
+            best_bid: None,
+            best_ask: None,
+            bid_volume_at_best: None,
+            ask_volume_at_best: None,
+            last_traded_price: "0".to_string(),
+            last_trade: None,
+            spread: "0".to_string(),
+            sequence: 0,
+        }
+    );
+}
+
+#[tokio::test]
+pub async fn test_read_ticker_one_sided_book() {
+    let (state, market, _) = setup().await;
+
+    let ticker: TickerResponse = read_ticker(state, market).await;
+
+    assert_eq!(ticker.best_bid, Some("100".to_string()));
+    assert_eq!(ticker.bid_volume_at_best, Some("10".to_string()));
+    assert_eq!(ticker.best_ask, None);
+    assert_eq!(ticker.ask_volume_at_best, None);
+    assert_eq!(ticker.spread, "0");
+}
+
+#[tokio::test]
+pub async fn test_read_ticker_two_sided_book() {
+    let (state, market, _) = setup().await;
+
+    for (trader, quantity) in [(3, 4), (4, 6)] {
+        let ask: Order = Order::new(
+            Address::from_low_u64_be(trader),
+            market,
+            OrderSide::Ask,
+            U256::from(105),
+            U256::from(quantity),
+            Utc::now() + Duration::days(365),
+            Utc::now(),
+            vec![],
+        );
+        state
+            .write()
+            .await
+            .book_mut(market)
+            .unwrap()
+            .submit(ask, test_executioner())
+            .await
+            .unwrap();
+    }
+
+    let ticker: TickerResponse = read_ticker(state, market).await;
+
+    assert_eq!(
+        ticker,
+        TickerResponse {
+            best_bid: Some("100".to_string()),
+            best_ask: Some("105".to_string()),
+            bid_volume_at_best: Some("10".to_string()),
+            ask_volume_at_best: Some("10".to_string()),
+            last_traded_price: "0".to_string(),
+            last_trade: None,
+            spread: "5".to_string(),
+            sequence: 3,
+        }
+    );
+}
+
+#[tokio::test]
+pub async fn test_read_stats() {
+    let (state, market, _) = setup().await;
+    let route_state: Arc<RwLock<OmeState>> = state.clone();
+    let route = warp::path!("book" / Address / "stats")
+        .and(warp::get())
+        .and(handler::accept())
+        .and(warp::any().map(move || route_state.clone()))
+        .and_then(handler::read_stats_handler);
+
+    /* fills the resting bid of 10 at 100, resting the other 5 */
+    let executioner: Executioner = mock_executioner(StatusCode::OK);
+    let res = create_order(state, market, executioner, "15").await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = warp::test::request()
+        .path(&format!("/book/{:x}/stats", market))
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: StatsResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body,
+        StatsResponse {
+            volume_24h: "10".to_string(),
+            notional_24h: "0".to_string(), /* worth less than a unit */
+            trades_24h: "1".to_string(),
+            high_24h: Some("100".to_string()),
+            low_24h: Some("100".to_string()),
+            last_price: "100".to_string(),
+        }
+    );
+
+    let res = warp::test::request()
+        .path(&format!("/book/{:x}/stats", Address::from_low_u64_be(99)))
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+pub async fn test_read_trades() {
+    let (state, market, resting) = setup().await;
+    let route = {
+        let state = state.clone();
+        warp::path!("book" / Address / "trades")
+            .and(warp::get())
+            .and(warp::query::<TradesQuery>())
+            .and(handler::accept())
+            .and(warp::any().map(move || state.clone()))
+            .and_then(handler::read_trades_handler)
+    };
+
+    /* match the resting bid in two halves */
+    for trader in [3, 4] {
+        let ask: Order = Order::new(
+            Address::from_low_u64_be(trader),
+            market,
+            OrderSide::Ask,
+            U256::from(100),
+            U256::from(5),
+            Utc::now() + Duration::days(365),
+            Utc::now(),
+            vec![],
+        );
+        state
+            .write()
+            .await
+            .book_mut(market)
+            .unwrap()
+            .submit(ask, test_executioner())
+            .await
+            .unwrap();
+    }
+
+    let res = warp::test::request()
+        .path(&format!("/book/{:x}/trades?limit=1", market))
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: TradesResponse = serde_json::from_slice(res.body()).unwrap();
+    let trades: Vec<ExternalFill> = body.trades;
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].maker_id, format!("{:?}", resting.id));
+    assert_eq!(
+        trades[0].taker_address,
+        format!("{:?}", Address::from_low_u64_be(4))
+    );
+    assert_eq!(trades[0].price, "100");
+    assert_eq!(trades[0].quantity, "5");
+    assert_eq!(trades[0].trade_id, body.sequence);
+
+    let res = warp::test::request()
+        .path(&format!("/book/{:x}/trades", market))
+        .reply(&route)
+        .await;
+
+    let body: TradesResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.trades.len(), 2);
+}
+
+#[tokio::test]
+pub async fn test_create_order_rejects_bad_signatures() {
+    let (state, market, _) = setup().await;
+    let route = warp::path!("book" / Address / "order")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(warp::any().map(move || state.clone()))
+        .and(warp::any().map(test_executioner))
+        .and(warp::any().map(|| Some(1337u64)))
+        .and(warp::any().map(max_clock_skew))
+        .and_then(handler::create_order_handler);
+
+    for (signed_data, status, message) in [
+        ("0x", StatusCode::BAD_REQUEST, "Malformed signature"),
+        (
+            "0xabc",
+            StatusCode::BAD_REQUEST,
+            "Invalid signed_data (expected empty or 65 bytes of hexadecimal, \
+             optionally 0x-prefixed)",
+        ),
+        (
+            /* a well-formed signature of something else entirely */
+            "0xdc7ae45111271ec2855c62311f8835bb4db24ae37c746fd2ac539308752463ec0cb5456d9e1121a485fa9ff59a2c7543b6ab6e1ab456a6dd4d61af30ee7c94361b",
+            StatusCode::UNAUTHORIZED,
+            "Invalid signature",
+        ),
+    ] {
+        let res = warp::test::request()
+            .method("POST")
+            .path(&format!("/book/{:x}/order", market))
+            .json(&serde_json::json!({
+                "user": format!("{:?}", Address::from_low_u64_be(3)),
+                "target_tracer": format!("{:?}", market),
+                "side": "Ask",
+                "price": "100",
+                "amount": "1",
+                "expiration": (Utc::now() + Duration::days(1)).timestamp(),
+                "created": Utc::now().timestamp(),
+                "signed_data": signed_data,
+            }))
+            .reply(&route)
+            .await;
+
+        assert_eq!(res.status(), status);
+        let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(body.message, message);
+    }
+}
+
+/* serves an executioner that answers order validity checks with the given
+ * status, and executes every match */
+pub fn mock_executioner(check_status: StatusCode) -> Executioner {
+    let check = warp::path!("check")
+        .and(warp::post())
+        .map(move || warp::reply::with_status("", check_status));
+    let submit = warp::path!("submit").and(warp::post()).map(|| "[]");
+    let (address, server) =
+        warp::serve(check.or(submit)).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    Executioner::new(mock_client(address))
+}
+
+/* submits a 0x-signed ask to the given market via the same route as `main` */
+async fn create_order(
+    state: Arc<RwLock<OmeState>>,
+    market: Address,
+    executioner: Executioner,
+    amount: &str,
+) -> warp::http::Response<warp::hyper::body::Bytes> {
+    let route = warp::path!("book" / Address / "order")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(warp::any().map(move || state.clone()))
+        .and(warp::any().map(move || executioner.clone()))
+        .and(warp::any().map(|| None))
+        .and(warp::any().map(max_clock_skew))
+        .and_then(handler::create_order_handler);
+
+    warp::test::request()
+        .method("POST")
+        .path(&format!("/book/{:x}/order", market))
+        .json(&serde_json::json!({
+            "user": format!("{:?}", Address::from_low_u64_be(3)),
+            "target_tracer": format!("{:?}", market),
+            "side": "Ask",
+            "price": "100",
+            "amount": amount,
+            "expiration": (Utc::now() + Duration::days(1)).timestamp(),
+            "created": Utc::now().timestamp(),
+            "signed_data": "0x",
+        }))
+        .reply(&route)
+        .await
+}
+
+#[tokio::test]
+pub async fn test_create_order_success() {
+    let (state, market, resting) = setup().await;
+    let executioner: Executioner = mock_executioner(StatusCode::OK);
+
+    let res = create_order(state.clone(), market, executioner, "15").await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: MatchResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.message, "PartialMatch");
+    assert_eq!(body.remaining, "5");
+    assert_eq!(body.resting, "5");
+    assert_eq!(body.fills.len(), 1);
+    assert_eq!(body.fills[0].maker_id, format!("{:?}", resting.id));
+    assert_eq!(body.fills[0].maker_fee, "0"); /* the book charges no fees */
+    assert_eq!(body.fills[0].taker_fee, "0");
+    assert_eq!(body.closed_makers, vec![format!("{:?}", resting.id)]);
+    assert!(body.reduced_makers.is_empty());
+
+    let ome_state = state.read().await;
+    let order: &Order = ome_state
+        .book(market)
+        .unwrap()
+        .asks
+        .values()
+        .next()
+        .unwrap()
+        .front()
+        .unwrap();
+    assert_eq!(body.order_id, format!("{:?}", order.id));
+}
+
+#[tokio::test]
+pub async fn test_create_order_failures() {
+    let (state, market, _) = setup().await;
+
+    /* orders the executioner considers invalid never reach the book */
+    let executioner: Executioner = mock_executioner(StatusCode::BAD_REQUEST);
+    let res = create_order(state.clone(), market, executioner, "15").await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.status, 400);
+    assert_eq!(body.code, "INVALID_ORDER");
+    assert_eq!(body.message, "Invalid order");
+
+    /* the engine's own rejections carry the same envelope */
+    let executioner: Executioner = mock_executioner(StatusCode::OK);
+    let res = create_order(state.clone(), market, executioner, "0").await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.status, 400);
+    assert_eq!(body.code, "INVALID_ORDER");
+    assert_eq!(body.message, "Invalid order");
+    assert_eq!(state.read().await.book(market).unwrap().depth(), (1, 0));
+}
+
+/* builds the order routes of `main`, including its rejection handling */
+fn order_routes(
+    state: Arc<RwLock<OmeState>>,
+) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
+    let create_state = state.clone();
+    let create = warp::path!("book" / Address / "order")
+        .and(warp::post())
+        .and(handler::json_body(DEFAULT_MAX_BODY_SIZE))
+        .and(warp::any().map(move || create_state.clone()))
+        .and(warp::any().map(test_executioner))
+        .and(warp::any().map(|| None))
+        .and(warp::any().map(max_clock_skew))
+        .and_then(handler::create_order_handler);
+    let read = warp::path!("book" / Address / "order" / OrderId)
+        .and(warp::get())
+        .and(handler::accept())
+        .and(warp::any().map(|| ApiVersion::V2))
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::read_order_handler);
+
+    create.or(read).recover(handler::handle_rejection)
+}
+
+/* asserts that a response carries the error envelope with the given code */
+fn assert_api_error(
+    res: &warp::http::Response<warp::hyper::body::Bytes>,
+    status: StatusCode,
+    code: &str,
+) {
+    assert_eq!(res.status(), status);
+    let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.status, status.as_u16());
+    assert_eq!(body.code, code);
+    assert!(!body.message.is_empty());
+}
+
+#[tokio::test]
+pub async fn test_api_error_unknown_book() {
+    let (state, _, order) = setup().await;
+
+    let res = warp::test::request()
+        .method("GET")
+        .path(&format!(
+            "/book/{:x}/order/{:x}",
+            Address::from_low_u64_be(99),
+            order.id
+        ))
+        .reply(&order_routes(state))
+        .await;
+
+    assert_api_error(&res, StatusCode::NOT_FOUND, "MARKET_NOT_FOUND");
+}
+
+#[tokio::test]
+pub async fn test_api_error_unknown_order() {
+    let (state, market, _) = setup().await;
+
+    let res = warp::test::request()
+        .method("GET")
+        .path(&format!(
+            "/book/{:x}/order/{:x}",
+            market,
+            OrderId::from_low_u64_be(42)
+        ))
+        .reply(&order_routes(state))
+        .await;
+
+    assert_api_error(&res, StatusCode::NOT_FOUND, "ORDER_NOT_FOUND");
+}
+
+#[tokio::test]
+pub async fn test_api_error_invalid_order_body() {
+    let (state, market, _) = setup().await;
+
+    let res = warp::test::request()
+        .method("POST")
+        .path(&format!("/book/{:x}/order", market))
+        .json(&serde_json::json!({
+            "user": format!("{:?}", Address::from_low_u64_be(3)),
+            "target_tracer": format!("{:?}", market),
+            "side": "Sideways",
+            "price": "100",
+            "amount": "1",
+            "expiration": (Utc::now() + Duration::days(1)).timestamp(),
+            "created": Utc::now().timestamp(),
+            "signed_data": "0x",
+        }))
+        .reply(&order_routes(state.clone()))
+        .await;
+
+    assert_api_error(&res, StatusCode::BAD_REQUEST, "INVALID_BODY");
+    assert_eq!(state.read().await.book(market).unwrap().depth(), (1, 0));
+}
+
+#[tokio::test]
+pub async fn test_create_order_market_mismatch() {
+    let (state, market, _) = setup().await;
+    let other: Address = Address::from_low_u64_be(2);
+    state.write().await.add_book(Book::new(other));
+
+    let res = warp::test::request()
+        .method("POST")
+        .path(&format!("/book/{:x}/order", market))
+        .json(&serde_json::json!({
+            "user": format!("{:?}", Address::from_low_u64_be(3)),
+            "target_tracer": format!("{:?}", other),
+            "side": "Ask",
+            "price": "100",
+            "amount": "1",
+            "expiration": (Utc::now() + Duration::days(1)).timestamp(),
+            "created": Utc::now().timestamp(),
+            "signed_data": "0x",
+        }))
+        .reply(&order_routes(state.clone()))
+        .await;
+
+    assert_api_error(&res, StatusCode::BAD_REQUEST, "MARKET_MISMATCH");
+    let ome_state = state.read().await;
+    assert_eq!(ome_state.book(market).unwrap().depth(), (1, 0));
+    assert_eq!(ome_state.book(other).unwrap().depth(), (0, 0));
+}
+
+#[tokio::test]
+pub async fn test_create_order_bad_timestamps() {
+    let (state, market, _) = setup().await;
+    let now: DateTime<Utc> = Utc::now();
+    let skew: Duration = Duration::minutes(5);
+
+    for (created, expiration, code) in [
+        (now, now - Duration::hours(1), "EXPIRY_BEFORE_CREATION"),
+        (now, now, "EXPIRY_BEFORE_CREATION"),
+        (
+            now - Duration::days(2),
+            now - Duration::days(1),
+            "ORDER_EXPIRED",
+        ),
+        (now + skew * 2, now + Duration::days(1), "CREATED_IN_FUTURE"),
+        (now - skew * 2, now + Duration::days(1), "CREATED_TOO_OLD"),
+    ] {
+        let res = warp::test::request()
+            .method("POST")
+            .path(&format!("/book/{:x}/order", market))
+            .json(&serde_json::json!({
+                "user": format!("{:?}", Address::from_low_u64_be(3)),
+                "target_tracer": format!("{:?}", market),
+                "side": "Ask",
+                "price": "100",
+                "amount": "1",
+                "expiration": expiration.timestamp(),
+                "created": created.timestamp(),
+                "signed_data": "0x",
+            }))
+            .reply(&order_routes(state.clone()))
+            .await;
+
+        assert_api_error(&res, StatusCode::BAD_REQUEST, code);
+    }
+
+    assert_eq!(state.read().await.book(market).unwrap().depth(), (1, 0));
+}
+
+#[tokio::test]
+pub async fn test_create_order_negative_timestamps() {
+    let (state, market, _) = setup().await;
+    let quote_state = state.clone();
+    let quote = warp::path!("book" / Address / "quote")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(warp::any().map(move || quote_state.clone()))
+        .and_then(handler::quote_order_handler);
+
+    /* timestamps before the epoch can't be hashed into an order ID, so are
+     * refused rather than reaching the engine */
+    for field in ["created", "expiration"] {
+        let mut order = serde_json::json!({
+            "user": format!("{:?}", Address::from_low_u64_be(3)),
+            "target_tracer": format!("{:?}", market),
+            "side": "Ask",
+            "price": "100",
+            "amount": "1",
+            "expiration": (Utc::now() + Duration::days(1)).timestamp(),
+            "created": Utc::now().timestamp(),
+            "signed_data": "0x",
+        });
+        order[field] = serde_json::json!(-1);
+
+        let res = warp::test::request()
+            .method("POST")
+            .path(&format!("/book/{:x}/order", market))
+            .json(&order)
+            .reply(&order_routes(state.clone()))
+            .await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let res = warp::test::request()
+            .method("POST")
+            .path(&format!("/book/{:x}/quote", market))
+            .json(&order)
+            .reply(&quote)
+            .await;
+        assert_api_error(&res, StatusCode::BAD_REQUEST, "INVALID_ORDER");
+        let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+        assert!(
+            body.message.starts_with(&format!("Invalid {}", field)),
+            "{}",
+            body.message
+        );
+    }
+
+    assert_eq!(state.read().await.book(market).unwrap().depth(), (1, 0));
+}
+
+#[tokio::test]
+pub async fn test_api_error_malformed_json() {
+    let (state, market, _) = setup().await;
+
+    let res = warp::test::request()
+        .method("POST")
+        .path(&format!("/book/{:x}/order", market))
+        .header("content-type", "application/json")
+        .body("{\"user\": ")
+        .reply(&order_routes(state))
+        .await;
+
+    assert_api_error(&res, StatusCode::BAD_REQUEST, "MALFORMED_JSON");
+}
+
+#[tokio::test]
+pub async fn test_api_error_names_missing_field() {
+    let (state, market, _) = setup().await;
+
+    let res = warp::test::request()
+        .method("POST")
+        .path(&format!("/book/{:x}/order", market))
+        .json(&serde_json::json!({
+            "user": format!("{:?}", Address::from_low_u64_be(3)),
+            "target_tracer": format!("{:?}", market),
+            "side": "Ask",
+            "amount": "1",
+            "expiration": (Utc::now() + Duration::days(1)).timestamp(),
+            "created": Utc::now().timestamp(),
+            "signed_data": "0x",
+        }))
+        .reply(&order_routes(state))
+        .await;
+
+    assert_api_error(&res, StatusCode::BAD_REQUEST, "INVALID_BODY");
+    let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+    assert!(body.message.contains("missing field `price`"));
+}
+
+#[tokio::test]
+pub async fn test_api_error_names_invalid_field() {
+    let (state, market, _) = setup().await;
+
+    let res = warp::test::request()
+        .method("POST")
+        .path(&format!("/book/{:x}/order", market))
+        .json(&serde_json::json!({
+            "user": format!("{:?}", Address::from_low_u64_be(3)),
+            "target_tracer": format!("{:?}", market),
+            "side": "Ask",
+            "price": "100",
+            "amount": "0xten",
+            "expiration": (Utc::now() + Duration::days(1)).timestamp(),
+            "created": Utc::now().timestamp(),
+            "signed_data": "0x",
+        }))
+        .reply(&order_routes(state))
+        .await;
+
+    assert_api_error(&res, StatusCode::BAD_REQUEST, "INVALID_BODY");
+    let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+    assert!(
+        body.message
+            .contains("amount: invalid value: string \"0xten\""),
+        "{}",
+        body.message
+    );
+}
+
+#[tokio::test]
+pub async fn test_api_error_names_offending_field() {
+    let (state, market, _) = setup().await;
+    let created: i64 = Utc::now().timestamp();
+
+    for (field, value, code, named) in [
+        ("price", serde_json::json!("1.5"), "INVALID_BODY", "price: "),
+        (
+            "user",
+            serde_json::json!("0xnope"),
+            "INVALID_BODY",
+            "user: ",
+        ),
+        (
+            "expiration",
+            serde_json::json!("tomorrow"),
+            "INVALID_BODY",
+            "expiration: ",
+        ),
+        (
+            "expiration",
+            serde_json::json!(created),
+            "EXPIRY_BEFORE_CREATION",
+            "Invalid expiration",
+        ),
+    ] {
+        let mut order = serde_json::json!({
+            "user": format!("{:?}", Address::from_low_u64_be(3)),
+            "target_tracer": format!("{:?}", market),
+            "side": "Ask",
+            "price": "100",
+            "amount": "1",
+            "expiration": (Utc::now() + Duration::days(1)).timestamp(),
+            "created": created,
+            "signed_data": "0x",
+        });
+        order[field] = value;
+
+        let res = warp::test::request()
+            .method("POST")
+            .path(&format!("/book/{:x}/order", market))
+            .json(&order)
+            .reply(&order_routes(state.clone()))
+            .await;
+
+        assert_api_error(&res, StatusCode::BAD_REQUEST, code);
+        let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+        assert!(body.message.contains(named), "{}", body.message);
+    }
+}
+
+#[tokio::test]
+pub async fn test_api_error_payload_too_large() {
+    let (state, market, _) = setup().await;
+
+    let res = warp::test::request()
+        .method("POST")
+        .path(&format!("/book/{:x}/order", market))
+        .json(&serde_json::json!({
+            "user": format!("{:?}", Address::from_low_u64_be(3)),
+            "target_tracer": format!("{:?}", market),
+            "side": "Ask",
+            "price": "100",
+            "amount": "1",
+            "expiration": (Utc::now() + Duration::days(1)).timestamp(),
+            "created": Utc::now().timestamp(),
+            "signed_data": format!("0x{}", "0".repeat(10 * 1024 * 1024)),
+        }))
+        .reply(&order_routes(state.clone()))
+        .await;
+
+    assert_api_error(&res, StatusCode::PAYLOAD_TOO_LARGE, "PAYLOAD_TOO_LARGE");
+    assert_eq!(state.read().await.book(market).unwrap().depth(), (1, 0));
+}
+
+#[tokio::test]
+pub async fn test_api_error_unmatched_route() {
+    let (state, market, _) = setup().await;
+
+    let res = warp::test::request()
+        .method("PATCH")
+        .path(&format!("/book/{:x}/order", market))
+        .reply(&order_routes(state))
+        .await;
+
+    assert_api_error(
+        &res,
+        StatusCode::METHOD_NOT_ALLOWED,
+        "METHOD_NOT_ALLOWED",
+    );
+}
+
+#[tokio::test]
+pub async fn test_cancel_orders_batch() {
+    let (state, market, order) = setup().await;
+    let route = warp::path!("book" / Address / "orders" / "cancel")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::cancel_orders_handler);
+    let unknown: OrderId = OrderId::from_low_u64_be(42);
+
+    let res = warp::test::request()
+        .method("POST")
+        .path(&format!("/book/{:x}/orders/cancel", market))
+        .json(&serde_json::json!({
+            "order_ids": [
+                format!("{:?}", order.id),
+                format!("{:?}", unknown),
+                format!("{:?}", order.id),
+            ],
+        }))
+        .reply(&route)
+        .await;
+
+    /* partial success is still a success, with each ID labelled */
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: CancelOrdersResponse =
+        serde_json::from_slice(res.body()).unwrap();
+    let outcomes: Vec<(String, CancelOutcome, Option<String>)> = body
+        .results
+        .into_iter()
+        .map(|result| (result.order_id, result.outcome, result.remaining))
+        .collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            (
+                format!("{:?}", order.id),
+                CancelOutcome::Cancelled,
+                Some("10".to_string())
+            ),
+            (format!("{:?}", unknown), CancelOutcome::NotFound, None),
+            (format!("{:?}", order.id), CancelOutcome::NotFound, None),
+        ]
+    );
+    assert_eq!(body.sequence, 2);
+
+    let res = warp::test::request()
+        .method("POST")
+        .path(&format!(
+            "/book/{:x}/orders/cancel",
+            Address::from_low_u64_be(99)
+        ))
+        .json(&serde_json::json!({ "order_ids": [] }))
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.code, "MARKET_NOT_FOUND");
+}
+
+/* replaces an order with a bid of the given price via the same route as
+ * `main` */
+async fn replace_order(
+    state: Arc<RwLock<OmeState>>,
+    market: Address,
+    order: &Order,
+    executioner: Executioner,
+    price: &str,
+) -> warp::http::Response<warp::hyper::body::Bytes> {
+    let route = warp::path!("book" / Address / "order" / OrderId / "replace")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(warp::any().map(move || state.clone()))
+        .and(warp::any().map(move || executioner.clone()))
+        .and(warp::any().map(|| None))
+        .and(warp::any().map(max_clock_skew))
+        .and_then(handler::replace_order_handler);
+
+    warp::test::request()
+        .method("POST")
+        .path(&format!("/book/{:x}/order/{:x}/replace", market, order.id))
+        .json(&serde_json::json!({
+            "user": format!("{:?}", order.trader),
+            "target_tracer": format!("{:?}", market),
+            "side": "Bid",
+            "price": price,
+            "amount": "10",
+            "expiration": (Utc::now() + Duration::days(1)).timestamp(),
+            "created": Utc::now().timestamp(),
+            "signed_data": "0x",
+        }))
+        .reply(&route)
+        .await
+}
+
+#[tokio::test]
+pub async fn test_replace_order() {
+    let (state, market, order) = setup().await;
+
+    /* a rejected replacement leaves the existing order open */
+    let executioner: Executioner = mock_executioner(StatusCode::BAD_REQUEST);
+    let res =
+        replace_order(state.clone(), market, &order, executioner, "90").await;
+    assert_api_error(&res, StatusCode::BAD_REQUEST, "INVALID_ORDER");
+    assert!(state
+        .read()
+        .await
+        .book(market)
+        .unwrap()
+        .order(order.id)
+        .is_some());
+
+    let executioner: Executioner = mock_executioner(StatusCode::OK);
+    let res =
+        replace_order(state.clone(), market, &order, executioner, "90").await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: ReplaceOrderResponse =
+        serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.cancelled.id, format!("{:?}", order.id));
+    assert_eq!(body.cancelled.amount_left, "10");
+    assert_eq!(body.created.message, "Add");
+    assert_eq!(body.created.resting, "10");
+
+    /* the replaced order can't be replaced again */
+    let executioner: Executioner = mock_executioner(StatusCode::OK);
+    let res =
+        replace_order(state.clone(), market, &order, executioner, "80").await;
+    assert_api_error(&res, StatusCode::NOT_FOUND, "ORDER_NOT_FOUND");
+
+    let ome_state = state.read().await;
+    let book: &Book = ome_state.book(market).unwrap();
+    assert!(book.order(order.id).is_none());
+    assert_eq!(book.bids[&U256::from(90)].len(), 1);
+}
+
+#[tokio::test]
+pub async fn test_order_nonce() {
+    let (state, market, _) = setup().await;
+    let executioner: Executioner = mock_executioner(StatusCode::OK);
+    let route = warp::path!("book" / Address / "order")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(warp::any().map(move || state.clone()))
+        .and(warp::any().map(move || executioner.clone()))
+        .and(warp::any().map(|| None))
+        .and(warp::any().map(max_clock_skew))
+        .and_then(handler::create_order_handler);
+    let expiration: i64 = (Utc::now() + Duration::days(1)).timestamp();
+    let created: i64 = Utc::now().timestamp();
+    let request = |nonce: Option<&str>| {
+        let mut body = serde_json::json!({
+            "user": format!("{:?}", Address::from_low_u64_be(3)),
+            "target_tracer": format!("{:?}", market),
+            "side": "Ask",
+            "price": "200",
+            "amount": "10",
+            "expiration": expiration,
+            "created": created,
+            "signed_data": "0x",
+        });
+        if let Some(nonce) = nonce {
+            body["nonce"] = serde_json::json!(nonce);
+        }
+        body
+    };
+    let submit = |request: serde_json::Value| {
+        warp::test::request()
+            .method("POST")
+            .path(&format!("/book/{:x}/order", market))
+            .json(&request)
+            .reply(&route)
+    };
+
+    let res = submit(request(None)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let first: MatchResponse = serde_json::from_slice(res.body()).unwrap();
+
+    /* resubmitting an identical order without a nonce collides... */
+    let res = submit(request(None)).await;
+    assert_api_error(&res, StatusCode::CONFLICT, "DUPLICATE_ORDER");
+    let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+    assert!(body.message.contains("nonce"));
+
+    /* ...whereas distinct nonces give distinct IDs, echoed to the client */
+    let res = submit(request(Some("1"))).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let second: MatchResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_ne!(second.order_id, first.order_id);
+
+    let res = submit(request(Some("0x2"))).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let third: MatchResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_ne!(third.order_id, first.order_id);
+    assert_ne!(third.order_id, second.order_id);
+
+    /* a zero nonce is the same as none at all */
+    let res = submit(request(Some("0"))).await;
+    assert_api_error(&res, StatusCode::CONFLICT, "DUPLICATE_ORDER");
+}
+
+#[tokio::test]
+pub async fn test_client_order_id() {
+    let (state, market, _) = setup().await;
+    let executioner: Executioner = mock_executioner(StatusCode::OK);
+    let create_state = state.clone();
+    let create = warp::path!("book" / Address / "order")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(warp::any().map(move || create_state.clone()))
+        .and(warp::any().map(move || executioner.clone()))
+        .and(warp::any().map(|| None))
+        .and(warp::any().map(max_clock_skew))
+        .and_then(handler::create_order_handler);
+    let lookup =
+        warp::path!("book" / Address / "order" / "by-client-id" / String)
+            .and(warp::get())
+            .and(warp::query::<ClientOrderQuery>())
+            .and(handler::accept())
+            .and(warp::any().map(move || state.clone()))
+            .and_then(handler::read_client_order_handler);
+    let user: Address = Address::from_low_u64_be(3);
+    let request = |price: &str| {
+        serde_json::json!({
+            "user": format!("{:?}", user),
+            "target_tracer": format!("{:?}", market),
+            "side": "Ask",
+            "price": price,
+            "amount": "10",
+            "expiration": (Utc::now() + Duration::days(1)).timestamp(),
+            "created": Utc::now().timestamp(),
+            "signed_data": "0x",
+            "client_order_id": "quote-1",
+        })
+    };
+
+    let res = warp::test::request()
+        .method("POST")
+        .path(&format!("/book/{:x}/order", market))
+        .json(&request("200"))
+        .reply(&create)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: MatchResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.client_order_id, Some("quote-1".to_string()));
+
+    /* the same client order ID can't be reused while the order is open */
+    let res = warp::test::request()
+        .method("POST")
+        .path(&format!("/book/{:x}/order", market))
+        .json(&request("201"))
+        .reply(&create)
+        .await;
+    assert_api_error(&res, StatusCode::CONFLICT, "DUPLICATE_CLIENT_ORDER_ID");
+
+    let res = warp::test::request()
+        .method("GET")
+        .path(&format!(
+            "/book/{:x}/order/by-client-id/quote-1?user={:?}",
+            market, user
+        ))
+        .reply(&lookup)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let order: ExternalOrder = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(order.id, body.order_id);
+    assert_eq!(order.client_order_id, "quote-1");
+
+    /* client order IDs are scoped to the trader that assigned them */
+    let res = warp::test::request()
+        .method("GET")
+        .path(&format!(
+            "/book/{:x}/order/by-client-id/quote-1?user={:?}",
+            market,
+            Address::from_low_u64_be(2)
+        ))
+        .reply(&lookup)
+        .await;
+    assert_api_error(&res, StatusCode::NOT_FOUND, "ORDER_NOT_FOUND");
+}
+
+#[tokio::test]
+pub async fn test_global_order_routes() {
+    let (state, _, order) = setup().await;
+    let read_state = state.clone();
+    let read = warp::path!("order" / OrderId)
+        .and(warp::get())
+        .and(handler::accept())
+        .and(warp::any().map(move || read_state.clone()))
+        .and_then(handler::read_global_order_handler);
+    let destroy = warp::path!("order" / OrderId)
+        .and(warp::delete())
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::destroy_global_order_handler);
+    let routes = read.or(destroy);
+
+    let res = warp::test::request()
+        .method("GET")
+        .path(&format!("/order/{:x}", order.id))
+        .reply(&routes)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: ExternalOrder = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body, ExternalOrder::from(order.clone()));
+
+    let res = warp::test::request()
+        .method("DELETE")
+        .path(&format!("/order/{:x}", order.id))
+        .reply(&routes)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    /* the cancelled order is no longer found in any market */
+    for method in ["GET", "DELETE"] {
+        let res = warp::test::request()
+            .method(method)
+            .path(&format!("/order/{:x}", order.id))
+            .reply(&routes)
+            .await;
+        assert_api_error(&res, StatusCode::NOT_FOUND, "ORDER_NOT_FOUND");
+    }
+}
+
+#[tokio::test]
+pub async fn test_user_orders() {
+    let (state, market, order) = setup().await;
+    let market2: Address = Address::from_low_u64_be(9);
+    let mut book: Book = Book::new(market2);
+    let asks: Vec<Order> = (0..3)
+        .map(|i| {
+            Order::new(
+                order.trader,
+                market2,
+                OrderSide::Ask,
+                U256::from(110 + i),
+                U256::from(10),
+                Utc::now() + Duration::days(365),
+                Utc::now(),
+                vec![],
+            )
+        })
+        .collect();
+    for ask in asks.iter().rev() {
+        book.submit(ask.clone(), test_executioner()).await.unwrap();
+    }
+    state.write().await.add_book(book);
+
+    let route = warp::path!("user" / Address / "orders")
+        .and(warp::get())
+        .and(warp::query::<UserOrdersQuery>())
+        .and(handler::accept())
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::user_orders_handler);
+    let path =
+        |query: &str| format!("/user/{:x}/orders{}", order.trader, query);
+
+    let res = warp::test::request()
+        .method("GET")
+        .path(&path(""))
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: UserOrdersResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body[&format!("{:?}", market)],
+        vec![ExternalOrder::from(order.clone())]
+    );
+    let prices: Vec<String> = body[&format!("{:?}", market2)]
+        .iter()
+        .map(|ask| ask.price.clone())
+        .collect();
+    assert_eq!(prices, vec!["110", "111", "112"]);
+
+    /* pages run across markets, in order of market address */
+    let res = warp::test::request()
+        .method("GET")
+        .path(&path("?limit=2&offset=1"))
+        .reply(&route)
+        .await;
+    let body: UserOrdersResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.len(), 1);
+    let ids: Vec<String> = body[&format!("{:?}", market2)]
+        .iter()
+        .map(|ask| ask.id.clone())
+        .collect();
+    assert_eq!(
+        ids,
+        vec![format!("{:?}", asks[0].id), format!("{:?}", asks[1].id)]
+    );
+
+    let res = warp::test::request()
+        .method("GET")
+        .path(&path("?offset=4"))
+        .reply(&route)
+        .await;
+    let body: UserOrdersResponse = serde_json::from_slice(res.body()).unwrap();
+    assert!(body.is_empty());
+}
+
+#[tokio::test]
+pub async fn test_user_summary() {
+    let (state, market, order) = setup().await;
+    let market2: Address = Address::from_low_u64_be(9);
+    let mut book: Book = Book::new(market2);
+    book.submit(
+        Order::new(
+            order.trader,
+            market2,
+            OrderSide::Ask,
+            U256::from(2_000_000_000_000_000_000u64),
+            U256::from(10),
+            Utc::now() + Duration::days(365),
+            Utc::now(),
+            vec![],
+        ),
+        test_executioner(),
+    )
+    .await
+    .unwrap();
+    state.write().await.add_book(book);
+
+    let route = warp::path!("user" / Address / "summary")
+        .and(warp::get())
+        .and(handler::accept())
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::user_summary_handler);
+
+    let res = warp::test::request()
+        .method("GET")
+        .path(&format!("/user/{:x}/summary", order.trader))
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+
+    /* a price of 100 (wei) is worth nothing once scaled down */
+    assert_eq!(
+        body["markets"][format!("{:?}", market)],
+        serde_json::json!({
+            "open_orders": "1",
+            "bid_quantity": "10",
+            "ask_quantity": "0",
+            "notional": "0",
+        })
+    );
+    assert_eq!(
+        body["total"],
+        serde_json::json!({
+            "open_orders": "2",
+            "bid_quantity": "10",
+            "ask_quantity": "10",
+            "notional": "20",
+        })
+    );
+
+    /* traders without orders have no exposure, rather than being unknown */
+    let res = warp::test::request()
+        .method("GET")
+        .path(&format!("/user/{:x}/summary", Address::from_low_u64_be(99)))
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: UserSummaryResponse = serde_json::from_slice(res.body()).unwrap();
+    assert!(body.markets.is_empty());
+    assert_eq!(body.total.open_orders, "0");
+    assert_eq!(body.total.notional, "0");
+}
+
+#[tokio::test]
+pub async fn test_quote_order() {
+    let (state, market, order) = setup().await;
+    let expected_book: Book = state.read().await.book(market).unwrap().clone();
+    let route_state = state.clone();
+    let route = warp::path!("book" / Address / "quote")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(warp::any().map(move || route_state.clone()))
+        .and_then(handler::quote_order_handler);
+    let request = serde_json::json!({
+        "user": format!("{:?}", Address::from_low_u64_be(3)),
+        "target_tracer": format!("{:?}", market),
+        "side": "Ask",
+        "price": "100",
+        "amount": "15",
+        "expiration": (Utc::now() + Duration::days(1)).timestamp(),
+        "created": Utc::now().timestamp(),
+        "signed_data": "0x",
+    });
+
+    let res = warp::test::request()
+        .method("POST")
+        .path(&format!("/book/{:x}/quote", market))
+        .json(&request)
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: QuoteResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.message, "PartialMatch");
+    assert_eq!(body.remaining, "5");
+    assert_eq!(body.average_price, "100");
+    assert_eq!(body.slippage, "0");
+    assert_eq!(body.sequence, expected_book.sequence);
+    assert_eq!(body.fills.len(), 1);
+    assert_eq!(body.fills[0].maker_id, format!("{:?}", order.id));
+    assert_eq!(state.read().await.book(market).unwrap(), &expected_book);
+
+    /* quoting against an unknown market fails as submitting would */
+    let unknown: Address = Address::from_low_u64_be(99);
+    let mut unknown_request: serde_json::Value = request.clone();
+    unknown_request["target_tracer"] =
+        serde_json::json!(format!("{:?}", unknown));
+    let res = warp::test::request()
+        .method("POST")
+        .path(&format!("/book/{:x}/quote", unknown))
+        .json(&unknown_request)
+        .reply(&route)
+        .await;
+    assert_api_error(&res, StatusCode::NOT_FOUND, "MARKET_NOT_FOUND");
+
+    /* as does quoting an order for another market */
+    let res = warp::test::request()
+        .method("POST")
+        .path(&format!("/book/{:x}/quote", market))
+        .json(&unknown_request)
+        .reply(&route)
+        .await;
+    assert_api_error(&res, StatusCode::BAD_REQUEST, "MARKET_MISMATCH");
+}
+
+#[tokio::test]
+pub async fn test_changes_rejected_while_shutting_down() {
+    let (state, market, order) = setup().await;
+    state.write().await.begin_shutdown();
+
+    let res = warp::test::request()
+        .method("DELETE")
+        .path(&format!("/book/{:x}/order/{:x}", market, order.id))
+        .reply(&destroy_order_route(state.clone()))
+        .await;
+    assert_api_error(&res, StatusCode::SERVICE_UNAVAILABLE, "SHUTTING_DOWN");
+
+    /* reads are still served while in-flight requests drain */
+    let res = warp::test::request()
+        .method("GET")
+        .path(&format!("/book/{:x}/order/{:x}", market, order.id))
+        .reply(&order_routes(state.clone()))
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let ome_state = state.read().await;
+    assert_eq!(
+        ome_state.book(market).unwrap().order(order.id),
+        Some(&order)
+    );
+}
+
+#[tokio::test]
+pub async fn test_reads_proceed_concurrently() {
+    let (state, market, order) = setup().await;
+    let wait: time::Duration = time::Duration::from_secs(1);
+
+    /* another reader holds the state until the guard is dropped */
+    let guard = state.read().await;
+
+    let (book_res, order_res) = time::timeout(
+        wait,
+        future::join(
+            handler::read_book_handler(market, Format::Json, state.clone()),
+            handler::read_order_handler(
+                market,
+                order.id,
+                Format::Json,
+                ApiVersion::V2,
+                state.clone(),
+            ),
+        ),
+    )
+    .await
+    .expect("Reads were blocked by another reader");
+    assert_eq!(book_res.unwrap().into_response().status(), StatusCode::OK);
+    assert_eq!(order_res.unwrap().into_response().status(), StatusCode::OK);
+
+    /* whereas writes still wait for readers to finish */
+    assert!(time::timeout(wait, state.write()).await.is_err());
+    drop(guard);
+    assert!(time::timeout(wait, state.write()).await.is_ok());
+}
+
+/* builds the same readiness route as `main` over the supplied state */
+fn readiness_route(
+    state: Arc<RwLock<OmeState>>,
+    readiness: Arc<Readiness>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("readyz")
+        .and(warp::get())
+        .and(warp::any().map(move || state.clone()))
+        .and(warp::any().map(move || readiness.clone()))
+        .and_then(handler::readiness_handler)
+}
+
+#[tokio::test]
+pub async fn test_not_ready_while_restoring() {
+    let (state, market, order) = setup().await;
+    let readiness: Arc<Readiness> = Arc::new(Readiness::new(None));
+    let destroy_state: Arc<RwLock<OmeState>> = state.clone();
+    let destroy_route = warp::path!("book" / Address / "order" / OrderId)
+        .and(warp::delete())
+        .and(handler::ready(readiness.clone()))
+        .and(warp::any().map(move || destroy_state.clone()))
+        .and_then(handler::destroy_order_handler)
+        .recover(handler::handle_rejection);
+    let health_route = warp::path!("healthz")
+        .and(warp::get())
+        .and_then(handler::health_check_handler);
+    let path: String = format!("/book/{:x}/order/{:x}", market, order.id);
+
+    /* the process is alive, but not ready */
+    let res = warp::test::request()
+        .path("/healthz")
+        .reply(&health_route)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = warp::test::request()
+        .path("/readyz")
+        .reply(&readiness_route(state.clone(), readiness.clone()))
+        .await;
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.code, "NOT_READY");
+    assert_eq!(body.message, "Restoring state");
+
+    /* changes are refused, leaving the book untouched */
+    let res = warp::test::request()
+        .method("DELETE")
+        .path(&path)
+        .reply(&destroy_route)
+        .await;
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.code, "NOT_READY");
+    assert_eq!(state.read().await.book(market).unwrap().depth(), (1, 0));
+
+    readiness.set_restored();
+
+    let res = warp::test::request()
+        .path("/readyz")
+        .reply(&readiness_route(state.clone(), readiness.clone()))
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: HealthResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.message, "Ready");
+
+    let res = warp::test::request()
+        .method("DELETE")
+        .path(&path)
+        .reply(&destroy_route)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(state.read().await.book(market).unwrap().depth(), (0, 0));
+}
+
+#[tokio::test]
+pub async fn test_not_ready_while_state_locked() {
+    let (state, _, _) = setup().await;
+    let readiness: Arc<Readiness> = Arc::new(Readiness::new(None));
+    readiness.set_restored();
+
+    let guard = state.write().await;
+    let res = warp::test::request()
+        .path("/readyz")
+        .reply(&readiness_route(state.clone(), readiness.clone()))
+        .await;
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.message, "State is locked");
+
+    drop(guard);
+    let res = warp::test::request()
+        .path("/readyz")
+        .reply(&readiness_route(state, readiness))
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+pub async fn test_readiness_reports_market_sync() {
+    let (state, market, _) = setup().await;
+    let readiness: Arc<Readiness> =
+        Arc::new(Readiness::new(Some(Duration::minutes(3))));
+    readiness.set_restored();
+    let route = readiness_route(state.clone(), readiness);
+
+    /* markets that have never been synced aren't ready */
+    let res = warp::test::request().path("/readyz").reply(&route).await;
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.code, "NOT_READY");
+
+    /* nor are markets last synced too long ago */
+    let stale: DateTime<Utc> = Utc::now() - Duration::minutes(5);
+    state.write().await.sync_markets(&[market], stale);
+    let res = warp::test::request().path("/readyz").reply(&route).await;
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let now: DateTime<Utc> = Utc::now();
+    state.write().await.sync_markets(&[market], now);
+
+    let res = warp::test::request().path("/readyz").reply(&route).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: HealthResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.message, "Ready");
+    assert_eq!(body.last_market_sync, Some(now));
+}
+
+#[tokio::test]
+pub async fn test_orders_rate_limited() {
+    let (state, market, _) = setup().await;
+    let executioner: Executioner = mock_executioner(StatusCode::OK);
+    let limiter: Arc<RateLimiter> = Arc::new(RateLimiter::new(1.0, 2));
+    let route = warp::path!("book" / Address / "order")
+        .and(warp::post())
+        .and(limit::by_trader(limiter.clone(), DEFAULT_MAX_BODY_SIZE))
+        .and(warp::any().map(move || state.clone()))
+        .and(warp::any().map(move || executioner.clone()))
+        .and(warp::any().map(|| None))
+        .and(warp::any().map(max_clock_skew))
+        .and_then(handler::create_order_handler)
+        .recover(handler::handle_rejection);
+    let order = |trader: u64| {
+        serde_json::json!({
+            "user": format!("{:?}", Address::from_low_u64_be(trader)),
+            "target_tracer": format!("{:?}", market),
+            "side": "Ask",
+            "price": "100",
+            "amount": "1",
+            "expiration": (Utc::now() + Duration::days(1)).timestamp(),
+            "created": Utc::now().timestamp(),
+            "signed_data": "0x",
+        })
+    };
+    let path: String = format!("/book/{:x}/order", market);
+
+    /* the trader may make a burst of two orders */
+    for _ in 0..2 {
+        let res = warp::test::request()
+            .method("POST")
+            .path(&path)
+            .json(&order(3))
+            .reply(&route)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    let res = warp::test::request()
+        .method("POST")
+        .path(&path)
+        .json(&order(3))
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.code, "RATE_LIMITED");
+    let retry_after_ms: u64 = body.retry_after_ms.unwrap();
+    assert!(retry_after_ms > 0 && retry_after_ms <= 1000);
+
+    /* whereas other traders are unaffected */
+    let res = warp::test::request()
+        .method("POST")
+        .path(&path)
+        .json(&order(4))
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+/* builds the same book creation route as `main`, requiring the given token */
+fn create_book_route(
+    state: Arc<RwLock<OmeState>>,
+    token: Option<&str>,
+) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
+    warp::path!("book")
+        .and(warp::post())
+        .and(handler::admin(token.map(str::to_string)))
+        .and(warp::body::json())
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::create_book_handler)
+        .recover(handler::handle_rejection)
+}
+
+#[tokio::test]
+pub async fn test_admin_token_required() {
+    let state: Arc<RwLock<OmeState>> = Arc::new(RwLock::new(OmeState::new()));
+    let route = create_book_route(state.clone(), Some("secret"));
+    let market: Address = Address::from_low_u64_be(1);
+    let body = serde_json::json!({ "market": format!("{:?}", market) });
+
+    for authorization in &[None, Some("Bearer wrong"), Some("secret")] {
+        let mut request = warp::test::request()
+            .method("POST")
+            .path("/book")
+            .json(&body);
+        if let Some(t) = authorization {
+            request = request.header("authorization", *t);
+        }
+
+        let res = request.reply(&route).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let error: ApiError = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(error.code, "UNAUTHORIZED");
+    }
+    assert!(state.read().await.book(market).is_none());
+
+    let res = warp::test::request()
+        .method("POST")
+        .path("/book")
+        .header("authorization", "Bearer secret")
+        .json(&body)
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    assert!(state.read().await.book(market).is_some());
+}
+
+#[tokio::test]
+pub async fn test_admin_routes_open_without_token() {
+    let state: Arc<RwLock<OmeState>> = Arc::new(RwLock::new(OmeState::new()));
+    let market: Address = Address::from_low_u64_be(1);
+
+    let res = warp::test::request()
+        .method("POST")
+        .path("/book")
+        .json(&serde_json::json!({ "market": format!("{:?}", market) }))
+        .reply(&create_book_route(state.clone(), None))
+        .await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    assert!(state.read().await.book(market).is_some());
+}
+
+/* builds the same pause and resume routes as `main`, requiring the given
+ * token */
+fn pause_routes(
+    state: Arc<RwLock<OmeState>>,
+    token: Option<&str>,
+) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
+    let admin = handler::admin(token.map(str::to_string));
+    let pause_state = state.clone();
+    let pause = warp::path!("book" / Address / "pause")
+        .and(warp::post())
+        .and(admin.clone())
+        .and(warp::any().map(move || pause_state.clone()))
+        .and_then(handler::pause_book_handler);
+    let resume = warp::path!("book" / Address / "resume")
+        .and(warp::post())
+        .and(admin)
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::resume_book_handler);
+
+    pause.or(resume).recover(handler::handle_rejection)
+}
+
+/* pauses or resumes the given market as an admin */
+async fn set_paused(
+    state: Arc<RwLock<OmeState>>,
+    market: Address,
+    action: &str,
+) -> warp::http::Response<warp::hyper::body::Bytes> {
+    warp::test::request()
+        .method("POST")
+        .path(&format!("/book/{:x}/{}", market, action))
+        .header("authorization", "Bearer secret")
+        .reply(&pause_routes(state, Some("secret")))
+        .await
+}
+
+#[tokio::test]
+pub async fn test_paused_market_rejects_orders() {
+    let (state, market, resting) = setup().await;
+
+    /* only admins may pause a market */
+    let res = warp::test::request()
+        .method("POST")
+        .path(&format!("/book/{:x}/pause", market))
+        .reply(&pause_routes(state.clone(), Some("secret")))
+        .await;
+    assert_api_error(&res, StatusCode::UNAUTHORIZED, "UNAUTHORIZED");
+    assert!(!state.read().await.book(market).unwrap().paused);
+
+    let res = set_paused(state.clone(), market, "pause").await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(state.read().await.book(market).unwrap().paused);
+
+    let executioner: Executioner = mock_executioner(StatusCode::OK);
+    let res =
+        create_order(state.clone(), market, executioner.clone(), "5").await;
+    assert_api_error(&res, StatusCode::LOCKED, "MARKET_PAUSED");
+    assert_eq!(state.read().await.book(market).unwrap().depth(), (1, 0));
+
+    /* open orders may still be cancelled */
+    let res = warp::test::request()
+        .method("DELETE")
+        .path(&format!("/book/{:x}/order/{:x}", market, resting.id))
+        .reply(&destroy_order_route(state.clone()))
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(state.read().await.book(market).unwrap().depth(), (0, 0));
+
+    let res = set_paused(state.clone(), market, "resume").await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = create_order(state.clone(), market, executioner, "5").await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(state.read().await.book(market).unwrap().depth(), (0, 1));
+
+    let res =
+        set_paused(state.clone(), Address::from_low_u64_be(99), "pause").await;
+    assert_api_error(&res, StatusCode::NOT_FOUND, "MARKET_NOT_FOUND");
+}
+
+#[tokio::test]
+pub async fn test_paused_market_hosted() {
+    let (state, market, resting) = setup().await;
+    state.write().await.host_books();
+
+    let res = set_paused(state.clone(), market, "pause").await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        actor::query_book(&state, market, |t| t.paused).await,
+        Some(true)
+    );
+
+    let executioner: Executioner = mock_executioner(StatusCode::OK);
+    let res = create_order(state.clone(), market, executioner, "5").await;
+    assert_api_error(&res, StatusCode::LOCKED, "MARKET_PAUSED");
+
+    let res = warp::test::request()
+        .method("DELETE")
+        .path(&format!("/book/{:x}/order/{:x}", market, resting.id))
+        .reply(&destroy_order_route(state.clone()))
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+/* changes the size limits of the given market as an admin, via the same
+ * route as `main` */
+async fn update_book_limits(
+    state: Arc<RwLock<OmeState>>,
+    market: Address,
+    body: serde_json::Value,
+) -> warp::http::Response<warp::hyper::body::Bytes> {
+    let route = warp::path!("book" / Address / "limits")
+        .and(warp::put())
+        .and(handler::admin(Some("secret".to_string())))
+        .and(handler::json_body(DEFAULT_MAX_BODY_SIZE))
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::update_book_limits_handler)
+        .recover(handler::handle_rejection);
+
+    warp::test::request()
+        .method("PUT")
+        .path(&format!("/book/{:x}/limits", market))
+        .header("authorization", "Bearer secret")
+        .json(&body)
+        .reply(&route)
+        .await
+}
+
+#[tokio::test]
+pub async fn test_order_size_limits() {
+    let (state, market, _) = setup().await;
+
+    let res = update_book_limits(
+        state.clone(),
+        market,
+        serde_json::json!({ "max_quantity": "0x4" }),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        state.read().await.book(market).unwrap().max_quantity,
+        Some(U256::from(4))
+    );
+
+    /* the resting bid is larger than the new cap, but is left alone */
+    let executioner: Executioner = mock_executioner(StatusCode::OK);
+    let res =
+        create_order(state.clone(), market, executioner.clone(), "5").await;
+    assert_api_error(&res, StatusCode::BAD_REQUEST, "ORDER_TOO_LARGE");
+    let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.limit, Some(U256::from(4)));
+    assert_eq!(state.read().await.book(market).unwrap().depth(), (1, 0));
+
+    let res = create_order(state.clone(), market, executioner, "4").await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    /* a zero cap would refuse every order */
+    let res = update_book_limits(
+        state.clone(),
+        market,
+        serde_json::json!({ "max_notional": "0x0" }),
+    )
+    .await;
+    assert_api_error(&res, StatusCode::BAD_REQUEST, "INVALID_LIMIT");
+
+    /* omitting a cap lifts it */
+    let res =
+        update_book_limits(state.clone(), market, serde_json::json!({})).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(state.read().await.book(market).unwrap().max_quantity, None);
+
+    let res = update_book_limits(
+        state.clone(),
+        Address::from_low_u64_be(99),
+        serde_json::json!({}),
+    )
+    .await;
+    assert_api_error(&res, StatusCode::NOT_FOUND, "MARKET_NOT_FOUND");
+}
+
+/* builds the same blocklist routes as `main`, requiring the given token */
+fn blocklist_routes(
+    state: Arc<RwLock<OmeState>>,
+) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
+    let admin = handler::admin(Some("secret".to_string()));
+    let block_state = state.clone();
+    let block = warp::path!("admin" / "blocklist" / Address)
+        .and(warp::put())
+        .and(admin.clone())
+        .and(warp::query::<BlockTraderQuery>())
+        .and(warp::any().map(move || block_state.clone()))
+        .and_then(handler::block_trader_handler);
+    let unblock_state = state.clone();
+    let unblock = warp::path!("admin" / "blocklist" / Address)
+        .and(warp::delete())
+        .and(admin.clone())
+        .and(warp::any().map(move || unblock_state.clone()))
+        .and_then(handler::unblock_trader_handler);
+    let index = warp::path!("admin" / "blocklist")
+        .and(warp::get())
+        .and(admin)
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::index_blocklist_handler);
+
+    block
+        .or(unblock)
+        .or(index)
+        .recover(handler::handle_rejection)
+}
+
+/* sends a request to the blocklist routes as an admin */
+async fn blocklist_request(
+    state: Arc<RwLock<OmeState>>,
+    method: &str,
+    path: &str,
+) -> warp::http::Response<warp::hyper::body::Bytes> {
+    warp::test::request()
+        .method(method)
+        .path(path)
+        .header("authorization", "Bearer secret")
+        .reply(&blocklist_routes(state))
+        .await
+}
+
+#[tokio::test]
+pub async fn test_blocked_trader_rejected() {
+    let (state, market, _) = setup().await;
+    let trader: Address = Address::from_low_u64_be(3);
+    let executioner: Executioner = mock_executioner(StatusCode::OK);
+
+    /* only admins may block traders */
+    let res = warp::test::request()
+        .method("PUT")
+        .path(&format!("/admin/blocklist/{:x}", trader))
+        .reply(&blocklist_routes(state.clone()))
+        .await;
+    assert_api_error(&res, StatusCode::UNAUTHORIZED, "UNAUTHORIZED");
+
+    let res = blocklist_request(
+        state.clone(),
+        "PUT",
+        &format!("/admin/blocklist/{:x}", trader),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: BlockTraderResponse = serde_json::from_slice(res.body()).unwrap();
+    assert!(body.blocked && body.changed);
+    assert!(body.cancelled.is_empty());
+
+    let res =
+        create_order(state.clone(), market, executioner.clone(), "5").await;
+    assert_api_error(&res, StatusCode::FORBIDDEN, "TRADER_BLOCKED");
+    assert_eq!(state.read().await.book(market).unwrap().depth(), (1, 0));
+
+    let res = blocklist_request(state.clone(), "GET", "/admin/blocklist").await;
+    let body: BlocklistResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.traders, vec![trader]);
+
+    /* blocking is kept in dumps */
+    let dumped: String = serde_json::to_string(&*state.read().await).unwrap();
+    let restored: OmeState = serde_json::from_str(&dumped).unwrap();
+    assert!(!restored.may_trade(trader));
+
+    let res = blocklist_request(
+        state.clone(),
+        "DELETE",
+        &format!("/admin/blocklist/{:x}", trader),
+    )
+    .await;
+    let body: BlockTraderResponse = serde_json::from_slice(res.body()).unwrap();
+    assert!(!body.blocked && body.changed);
+
+    let res = create_order(state.clone(), market, executioner, "5").await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    /* unblocking a trader that isn't blocked changes nothing */
+    let res = blocklist_request(
+        state.clone(),
+        "DELETE",
+        &format!("/admin/blocklist/{:x}", trader),
+    )
+    .await;
+    let body: BlockTraderResponse = serde_json::from_slice(res.body()).unwrap();
+    assert!(!body.changed);
+}
+
+#[tokio::test]
+pub async fn test_block_trader_cancelling_existing_orders() {
+    let (state, market, resting) = setup().await;
+
+    let res = blocklist_request(
+        state.clone(),
+        "PUT",
+        &format!("/admin/blocklist/{:x}?cancel_existing=true", resting.trader),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: BlockTraderResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.cancelled, vec![resting.id]);
+    assert_eq!(state.read().await.book(market).unwrap().depth(), (0, 0));
+
+    /* hosted books can't have their orders cancelled in bulk */
+    state.write().await.host_books();
+    let res = blocklist_request(
+        state.clone(),
+        "PUT",
+        &format!(
+            "/admin/blocklist/{:x}?cancel_existing=true",
+            Address::from_low_u64_be(4)
+        ),
+    )
+    .await;
+    assert_api_error(
+        &res,
+        StatusCode::NOT_IMPLEMENTED,
+        "UNSUPPORTED_ENGINE_MODE",
+    );
+}
+
+#[tokio::test]
+pub async fn test_allowlist_restricts_traders() {
+    let (state, market, _) = setup().await;
+    let executioner: Executioner = mock_executioner(StatusCode::OK);
+
+    state.write().await.set_allowlist(Some(
+        vec![Address::from_low_u64_be(4)].into_iter().collect(),
+    ));
+    let res =
+        create_order(state.clone(), market, executioner.clone(), "5").await;
+    assert_api_error(&res, StatusCode::FORBIDDEN, "TRADER_BLOCKED");
+
+    state.write().await.set_allowlist(Some(
+        vec![Address::from_low_u64_be(3)].into_iter().collect(),
+    ));
+    let res = create_order(state.clone(), market, executioner, "5").await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    /* the blocklist takes precedence */
+    state.write().await.block(Address::from_low_u64_be(3));
+    assert!(!state.read().await.may_trade(Address::from_low_u64_be(3)));
+}
+
+/* builds the same state dump route as `main`, dumping to the given path */
+fn dump_state_route(
+    state: Arc<RwLock<OmeState>>,
+    dumpfile: PathBuf,
+) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
+    warp::path!("admin" / "snapshot")
+        .and(warp::post())
+        .and(handler::admin(Some("secret".to_string())))
+        .and(warp::any().map(move || dumpfile.clone()))
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::dump_state_handler)
+        .recover(handler::handle_rejection)
+}
+
+#[tokio::test]
+pub async fn test_dump_state_on_request() {
+    let dir: PathBuf = std::env::temp_dir()
+        .join(format!("ome-dump-on-request-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let dump_path: PathBuf = dir.join("ome.json");
+    let _ = fs::remove_file(&dump_path);
+
+    let (state, market, order) = setup().await;
+    let route = dump_state_route(state.clone(), dump_path.clone());
+
+    let res = warp::test::request()
+        .method("POST")
+        .path("/admin/snapshot")
+        .reply(&route)
+        .await;
+    assert_api_error(&res, StatusCode::UNAUTHORIZED, "UNAUTHORIZED");
+    assert!(!dump_path.exists());
+
+    let res = warp::test::request()
+        .method("POST")
+        .path("/admin/snapshot")
+        .header("authorization", "Bearer secret")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: DumpResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.path, dump_path.display().to_string());
+    assert_eq!(body.size, fs::metadata(&dump_path).unwrap().len());
+    assert_eq!(
+        OmeState::from_dumpfile(&dump_path).unwrap(),
+        *state.read().await
+    );
+
+    /* hosted books are copied, and carry on matching afterwards */
+    fs::remove_file(&dump_path).unwrap();
+    state.write().await.host_books();
+    let res = warp::test::request()
+        .method("POST")
+        .path("/admin/snapshot")
+        .header("authorization", "Bearer secret")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let dumped: OmeState = OmeState::from_dumpfile(&dump_path).unwrap();
+    assert!(dumped.book(market).unwrap().order(order.id).is_some());
+    assert_eq!(
+        actor::query_book(&state, market, |t| t.depth()).await,
+        Some((1, 0))
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/* builds the same book reload route as `main`, reloading from the given
+ * source of markets */
+fn reload_book_route(
+    state: Arc<RwLock<OmeState>>,
+    client: Option<RpcClient>,
+) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
+    warp::path!("admin" / "book" / Address / "reload")
+        .and(warp::post())
+        .and(handler::admin(None))
+        .and(warp::any().map(move || client.clone()))
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::reload_book_handler)
+        .recover(handler::handle_rejection)
+}
+
+/* serves the given books as the source of markets would, and nothing else */
+fn mock_book_source(books: Vec<(Address, ExternalBook)>) -> RpcClient {
+    let route = warp::path!("book" / String).and(warp::get()).map(
+        move |market: String| match books
+            .iter()
+            .find(|(address, _)| format!("{:?}", address) == market)
+        {
+            Some((_, book)) => warp::reply::json(book).into_response(),
+            None => StatusCode::NOT_FOUND.into_response(),
+        },
+    );
+    let (address, server) =
+        warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    mock_client(address)
+}
+
+/* reloads the given market via the same route as `main` */
+async fn reload(
+    state: Arc<RwLock<OmeState>>,
+    client: Option<RpcClient>,
+    market: Address,
+) -> warp::http::Response<warp::hyper::body::Bytes> {
+    warp::test::request()
+        .method("POST")
+        .path(&format!("/admin/book/{:x}/reload", market))
+        .reply(&reload_book_route(state, client))
+        .await
+}
+
+#[tokio::test]
+pub async fn test_reload_book() {
+    let (state, market, _) = setup().await;
+    let unserved: Address = Address::from_low_u64_be(5);
+    let invalid: Address = Address::from_low_u64_be(6);
+    let mismatched: Address = Address::from_low_u64_be(7);
+    for other in &[unserved, invalid, mismatched] {
+        state.write().await.add_book(Book::new(*other));
+    }
+
+    /* the source holds two asks where the OME holds a single bid */
+    let mut source: Book = Book::new(market);
+    for price in &[110, 120] {
+        let ask: Order = Order::new(
+            Address::from_low_u64_be(3),
+            market,
+            OrderSide::Ask,
+            U256::from(*price),
+            U256::from(5),
+            Utc::now() + Duration::days(365),
+            Utc::now(),
+            vec![],
+        );
+        source.submit(ask, test_executioner()).await.unwrap();
+    }
+
+    let mut corrupt: ExternalBook = ExternalBook::from(source.clone());
+    corrupt.market = format!("{:?}", invalid);
+    for (_, orders) in corrupt.asks.iter_mut() {
+        for order in orders.iter_mut() {
+            order.target_tracer = corrupt.market.clone();
+            order.amount_left = "1000".to_string();
+        }
+    }
+
+    let client: Option<RpcClient> = Some(mock_book_source(vec![
+        (market, ExternalBook::from(source.clone())),
+        (invalid, corrupt),
+        (mismatched, ExternalBook::from(source.clone())),
+    ]));
+
+    let res =
+        reload(state.clone(), client.clone(), Address::from_low_u64_be(99))
+            .await;
+    assert_api_error(&res, StatusCode::NOT_FOUND, "MARKET_NOT_FOUND");
+
+    /* failures leave the current book untouched */
+    let res = reload(state.clone(), client.clone(), unserved).await;
+    assert_api_error(&res, StatusCode::BAD_GATEWAY, "BOOK_SOURCE_UNAVAILABLE");
+    let res = reload(state.clone(), client.clone(), invalid).await;
+    assert_api_error(&res, StatusCode::BAD_GATEWAY, "INVALID_SOURCE_BOOK");
+    let res = reload(state.clone(), client.clone(), mismatched).await;
+    assert_api_error(&res, StatusCode::BAD_GATEWAY, "INVALID_SOURCE_BOOK");
+    for other in &[unserved, invalid, mismatched] {
+        assert_eq!(state.read().await.book(*other), Some(&Book::new(*other)));
+    }
+
+    let res = reload(state.clone(), client, market).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: ReloadBookResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body,
+        ReloadBookResponse {
+            market,
+            orders_before: 1,
+            orders_after: 2,
+        }
+    );
+    /* the book is replaced by the book as served, without its history */
+    let served: Book = Book::try_from(ExternalBook::from(source)).unwrap();
+    assert_eq!(state.read().await.book(market), Some(&served));
+
+    /* without a source of markets, there is nothing to reload from */
+    let res = reload(state.clone(), None, market).await;
+    assert_api_error(&res, StatusCode::SERVICE_UNAVAILABLE, "NO_BOOK_SOURCE");
+}
+
+#[tokio::test]
+pub async fn test_create_book_fees() {
+    let state: Arc<RwLock<OmeState>> = Arc::new(RwLock::new(OmeState::new()));
+    let market: Address = Address::from_low_u64_be(1);
+
+    /* a fee of more than 100% is rejected */
+    let res = warp::test::request()
+        .method("POST")
+        .path("/book")
+        .json(&serde_json::json!({
+            "market": format!("{:?}", market),
+            "maker_fee_bps": 10_001,
+        }))
+        .reply(&create_book_route(state.clone(), None))
+        .await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let error: ApiError = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(error.code, "INVALID_FEE");
+    assert!(state.read().await.book(market).is_none());
+
+    let res = warp::test::request()
+        .method("POST")
+        .path("/book")
+        .json(&serde_json::json!({
+            "market": format!("{:?}", market),
+            "maker_fee_bps": 2,
+            "taker_fee_bps": 5,
+        }))
+        .reply(&create_book_route(state.clone(), None))
+        .await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let ome_state = state.read().await;
+    let book: &Book = ome_state.book(market).unwrap();
+    assert_eq!((book.maker_fee_bps, book.taker_fee_bps), (2, 5));
+
+    let external: ExternalBook = ExternalBook::from(book.clone());
+    assert_eq!((external.maker_fee_bps, external.taker_fee_bps), (2, 5));
+}
+
+#[tokio::test]
+pub async fn test_cors_allowed_origins() {
+    let origins: AllowedOrigins =
+        AllowedOrigins::Only(vec!["https://app.tracer.finance".to_string()]);
+    let route = warp::path!("book")
+        .and(warp::get())
+        .map(warp::reply)
+        .with(handler::cors(&origins, &[Method::GET]));
+
+    let res = warp::test::request()
+        .path("/book")
+        .header("origin", "https://app.tracer.finance")
+        .reply(&route)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers()["access-control-allow-origin"],
+        "https://app.tracer.finance"
+    );
+
+    /* requests from any other origin aren't allowed */
+    let res = warp::test::request()
+        .path("/book")
+        .header("origin", "https://evil.example")
+        .reply(&route)
+        .await;
+    assert!(res.headers().get("access-control-allow-origin").is_none());
+
+    /* whereas every origin is by default */
+    let route = warp::path!("book")
+        .and(warp::get())
+        .map(warp::reply)
+        .with(handler::cors(&AllowedOrigins::Any, &[Method::GET]));
+    let res = warp::test::request()
+        .path("/book")
+        .header("origin", "https://evil.example")
+        .reply(&route)
+        .await;
+    assert_eq!(
+        res.headers()["access-control-allow-origin"],
+        "https://evil.example"
+    );
+}
+
+/* builds the same order history route as `main` over the supplied state */
+fn read_order_history_route(
+    state: Arc<RwLock<OmeState>>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("book" / Address / "order" / OrderId / "history")
+        .and(warp::get())
+        .and(handler::accept())
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::read_order_history_handler)
+}
+
+#[tokio::test]
+pub async fn test_read_order_history() {
+    let (state, market, resting) = setup().await;
+    let executioner: Executioner = mock_executioner(StatusCode::OK);
+
+    let res = create_order(state.clone(), market, executioner, "15").await;
+    let taker: MatchResponse = serde_json::from_slice(res.body()).unwrap();
+
+    /* the resting bid is closed, but its history is still served */
+    let res = warp::test::request()
+        .path(&format!(
+            "/book/{:x}/order/{:x}/history",
+            market, resting.id
+        ))
+        .reply(&read_order_history_route(state.clone()))
+        .await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: OrderHistoryResponse =
+        serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.order_id, format!("{:?}", resting.id));
+    assert!(!body.truncated);
+    assert_eq!(
+        body.events.iter().map(|t| t.kind).collect::<Vec<_>>(),
+        vec![OrderEventKind::Accepted, OrderEventKind::Filled]
+    );
+    assert_eq!(body.events[0].remaining, "10");
+    assert_eq!(body.events[0].price, None);
+    assert_eq!(body.events[1].price, Some("100".to_string()));
+    assert_eq!(body.events[1].quantity, Some("10".to_string()));
+    assert_eq!(body.events[1].remaining, "0");
+
+    /* whereas the taker rests with the remainder */
+    let res = warp::test::request()
+        .path(&format!(
+            "/book/{:x}/order/{}/history",
+            market,
+            taker.order_id.trim_start_matches("0x")
+        ))
+        .reply(&read_order_history_route(state.clone()))
+        .await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: OrderHistoryResponse =
+        serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(
+        body.events.iter().map(|t| t.kind).collect::<Vec<_>>(),
+        vec![OrderEventKind::Accepted, OrderEventKind::PartiallyFilled]
+    );
+    assert_eq!(body.events[1].remaining, "5");
+}
+
+#[tokio::test]
+pub async fn test_read_order_history_not_found() {
+    let (state, market, order) = setup().await;
+
+    let res = warp::test::request()
+        .path(&format!(
+            "/book/{:x}/order/{:x}/history",
+            Address::from_low_u64_be(99),
+            order.id
+        ))
+        .reply(&read_order_history_route(state.clone()))
+        .await;
+    assert_api_error(&res, StatusCode::NOT_FOUND, "MARKET_NOT_FOUND");
+
+    let res = warp::test::request()
+        .path(&format!(
+            "/book/{:x}/order/{:x}/history",
+            market,
+            OrderId::from_low_u64_be(42)
+        ))
+        .reply(&read_order_history_route(state))
+        .await;
+    assert_api_error(&res, StatusCode::NOT_FOUND, "HISTORY_NOT_FOUND");
+}
+
+#[tokio::test]
+pub async fn test_read_order_status() {
+    let (state, market, resting) = setup().await;
+    let path: String = format!("/book/{:x}/order/{:x}", market, resting.id);
+
+    let res = warp::test::request()
+        .path(&path)
+        .reply(&order_routes(state.clone()))
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: OrderView = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.status, OrderViewStatus::Open);
+    assert_eq!(body.remaining, "10");
+    assert_eq!(body.order, Some(ExternalOrder::from(resting.clone())));
+
+    let executioner: Executioner = mock_executioner(StatusCode::OK);
+    let res = create_order(state.clone(), market, executioner, "15").await;
+    let taker: MatchResponse = serde_json::from_slice(res.body()).unwrap();
+
+    /* the taker rests with the remainder... */
+    let res = warp::test::request()
+        .path(&format!(
+            "/book/{:x}/order/{}",
+            market,
+            taker.order_id.trim_start_matches("0x")
+        ))
+        .reply(&order_routes(state.clone()))
+        .await;
+    let body: OrderView = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.status, OrderViewStatus::PartiallyFilled);
+    assert_eq!(body.remaining, "5");
+    assert_eq!(body.order.unwrap().amount_left, "5");
+
+    /* ...whereas the resting bid is gone, though still known to be filled */
+    let res = warp::test::request()
+        .path(&path)
+        .reply(&order_routes(state))
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: OrderView = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.order_id, format!("{:?}", resting.id));
+    assert_eq!(body.status, OrderViewStatus::Filled);
+    assert_eq!(body.remaining, "0");
+    assert_eq!(body.order, None);
+}
+
+#[tokio::test]
+pub async fn test_versioned_routes() {
+    let (state, market, resting) = setup().await;
+    let readiness: Arc<Readiness> = Arc::new(Readiness::new(None));
+    readiness.set_restored();
+    let context: RouteContext = RouteContext {
+        readiness,
+        executioner: mock_executioner(StatusCode::OK),
+        markets_client: None,
+        limiter: Arc::new(RateLimiter::new(1000.0, 1000)),
+        admin_token: Some("secret".to_string()),
+        dumpfile_path: std::env::temp_dir().join("ome-versioned.json"),
+        max_body_size: DEFAULT_MAX_BODY_SIZE,
+        max_clock_skew: max_clock_skew(),
+        signature_chain_id: None,
+        compress: false,
+    };
+    let legacy: Arc<LegacyRouteMetrics> =
+        Arc::new(LegacyRouteMetrics::default());
+    let api = routes::versioned(state.clone(), &context, legacy.clone())
+        .recover(handler::handle_rejection);
+    let path = |prefix: &str, id: OrderId| {
+        format!("{}/book/{:x}/order/{:x}", prefix, market, id)
+    };
+
+    /* the order alone in v1 (as at the unversioned path), and its view in
+     * v2 */
+    let res = warp::test::request()
+        .path(&path("/v1", resting.id))
+        .reply(&api)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: ExternalOrder = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body, ExternalOrder::from(resting.clone()));
+
+    let res = warp::test::request()
+        .path(&path("", resting.id))
+        .reply(&api)
+        .await;
+    let body: ExternalOrder = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body, ExternalOrder::from(resting.clone()));
+
+    let res = warp::test::request()
+        .path(&path("/v2", resting.id))
+        .reply(&api)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: OrderView = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.status, OrderViewStatus::Open);
+    assert_eq!(body.order, Some(ExternalOrder::from(resting.clone())));
+
+    /* once the order has closed, only v2 still knows of it */
+    let executioner: Executioner = mock_executioner(StatusCode::OK);
+    create_order(state.clone(), market, executioner, "10").await;
+
+    let res = warp::test::request()
+        .path(&path("/v1", resting.id))
+        .reply(&api)
+        .await;
+    assert_api_error(&res, StatusCode::NOT_FOUND, "ORDER_NOT_FOUND");
+
+    let res = warp::test::request()
+        .path(&path("/v2", resting.id))
+        .reply(&api)
+        .await;
+    let body: OrderView = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.status, OrderViewStatus::Filled);
+
+    /* the filters are shared, e.g. admin routes are guarded in every
+     * version */
+    for prefix in &["", "/v1", "/v2"] {
+        let res = warp::test::request()
+            .path(&format!("{}/admin/blocklist", prefix))
+            .reply(&api)
+            .await;
+        assert_api_error(&res, StatusCode::UNAUTHORIZED, "UNAUTHORIZED");
+    }
+
+    /* only the unversioned paths are counted as deprecated */
+    assert_eq!(legacy.requests(), 1);
+    assert!(legacy.render().contains("ome_legacy_route_requests 1\n"));
+}
+
+/* builds the same webhook routes as `main` over the supplied state */
+fn webhook_routes(
+    state: Arc<RwLock<OmeState>>,
+    token: Option<&str>,
+) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
+    let admin = handler::admin(token.map(str::to_string));
+    let create_state: Arc<RwLock<OmeState>> = state.clone();
+    let index_state: Arc<RwLock<OmeState>> = state.clone();
+
+    warp::path!("webhooks")
+        .and(warp::post())
+        .and(admin.clone())
+        .and(warp::body::json())
+        .and(warp::any().map(move || create_state.clone()))
+        .and_then(handler::create_webhook_handler)
+        .or(warp::path!("webhooks")
+            .and(warp::get())
+            .and(admin.clone())
+            .and(warp::any().map(move || index_state.clone()))
+            .and_then(handler::index_webhook_handler))
+        .or(warp::path!("webhooks" / u64)
+            .and(warp::delete())
+            .and(admin)
+            .and(warp::any().map(move || state.clone()))
+            .and_then(handler::destroy_webhook_handler))
+        .recover(handler::handle_rejection)
+}
+
+#[tokio::test]
+pub async fn test_webhook_routes() {
+    let state: Arc<RwLock<OmeState>> = Arc::new(RwLock::new(OmeState::new()));
+    let route = webhook_routes(state.clone(), Some("secret"));
+    let market: Address = Address::from_low_u64_be(1);
+
+    let res = warp::test::request()
+        .method("POST")
+        .path("/webhooks")
+        .header("authorization", "Bearer secret")
+        .json(&serde_json::json!({
+            "url": "https://example.com/fills",
+            "market": format!("{:?}", market),
+            "secret": "hunter2",
+        }))
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let created: WebhookResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(created.url, "https://example.com/fills");
+    assert_eq!(created.market, Some(market));
+    assert_eq!(created.user, None);
+
+    /* the secret is never sent back */
+    let res = warp::test::request()
+        .path("/webhooks")
+        .header("authorization", "Bearer secret")
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(!String::from_utf8_lossy(res.body()).contains("hunter2"));
+    let listed: Vec<WebhookResponse> =
+        serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(listed, vec![created.clone()]);
+
+    let path: String = format!("/webhooks/{}", created.id);
+    let res = warp::test::request()
+        .method("DELETE")
+        .path(&path)
+        .header("authorization", "Bearer secret")
+        .reply(&route)
+        .await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let removed: WebhookResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(removed, created);
+    assert!(state.read().await.webhooks().list().is_empty());
+
+    let res = warp::test::request()
+        .method("DELETE")
+        .path(&path)
+        .header("authorization", "Bearer secret")
+        .reply(&route)
+        .await;
+    assert_api_error(&res, StatusCode::NOT_FOUND, "WEBHOOK_NOT_FOUND");
+}
+
+#[tokio::test]
+pub async fn test_webhook_routes_failures() {
+    let state: Arc<RwLock<OmeState>> = Arc::new(RwLock::new(OmeState::new()));
+    let route = webhook_routes(state.clone(), Some("secret"));
+
+    /* management is reserved for admins */
+    let res = warp::test::request().path("/webhooks").reply(&route).await;
+    assert_api_error(&res, StatusCode::UNAUTHORIZED, "UNAUTHORIZED");
+
+    for (url, secret) in &[
+        ("ftp://example.com/fills", "hunter2"),
+        ("not a url", "hunter2"),
+        ("https://example.com/fills", ""),
+    ] {
+        let res = warp::test::request()
+            .method("POST")
+            .path("/webhooks")
+            .header("authorization", "Bearer secret")
+            .json(&serde_json::json!({ "url": url, "secret": secret }))
+            .reply(&route)
+            .await;
+        assert_api_error(&res, StatusCode::BAD_REQUEST, "INVALID_WEBHOOK");
+    }
+
+    assert!(state.read().await.webhooks().list().is_empty());
+}
+
+#[tokio::test]
+pub async fn test_msgpack_negotiated() {
+    let (state, market, _) = setup().await;
+    let executioner: Executioner = mock_executioner(StatusCode::OK);
+    let create_state: Arc<RwLock<OmeState>> = state.clone();
+    let create = warp::path!("book" / Address / "order")
+        .and(warp::post())
+        .and(handler::body(DEFAULT_MAX_BODY_SIZE))
+        .and(warp::any().map(move || create_state.clone()))
+        .and(warp::any().map(move || executioner.clone()))
+        .and(warp::any().map(|| None))
+        .and(warp::any().map(max_clock_skew))
+        .and_then(handler::create_order_handler)
+        .recover(handler::handle_rejection);
+    let depth = warp::path!("book" / Address / "depth")
+        .and(warp::get())
+        .and(warp::query::<DepthQuery>())
+        .and(handler::accept())
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::read_depth_handler);
+    let request: CreateOrderRequest = CreateOrderRequest {
+        user: Address::from_low_u64_be(3),
+        target_tracer: market,
+        side: OrderSide::Ask,
+        price: U256::from(105),
+        amount: U256::from(1),
+        expiration: Utc::now() + Duration::days(1),
+        created: Utc::now(),
+        signed_data: "0x".to_string(),
+        time_in_force: TimeInForce::GoodTillCancel,
+        order_type: OrderType::Limit,
+        trigger_price: None,
+        client_order_id: None,
+        nonce: U256::zero(),
+    };
+
+    /* orders may be submitted as MessagePack... */
+    let res = warp::test::request()
+        .method("POST")
+        .path(&format!("/book/{:x}/order", market))
+        .header("content-type", MSGPACK_CONTENT_TYPE)
+        .body(Format::MessagePack.encode(&request).unwrap())
+        .reply(&create)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    /* ...though malformed ones are answered with the usual envelope */
+    let res = warp::test::request()
+        .method("POST")
+        .path(&format!("/book/{:x}/order", market))
+        .header("content-type", MSGPACK_CONTENT_TYPE)
+        .body(vec![0xc1])
+        .reply(&create)
+        .await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body: ApiError = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body.code, "INVALID_BODY");
+
+    /* reads are answered in MessagePack only if it is accepted */
+    let res = warp::test::request()
+        .path(&format!("/book/{:x}/depth", market))
+        .header("accept", MSGPACK_CONTENT_TYPE)
+        .reply(&depth)
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["content-type"], MSGPACK_CONTENT_TYPE);
+    let msgpack: DepthResponse =
+        Format::MessagePack.decode(res.body()).unwrap();
+    assert_eq!(msgpack.asks, vec![("105".to_string(), "1".to_string())]);
+
+    let res = warp::test::request()
+        .path(&format!("/book/{:x}/depth", market))
+        .header("accept", "text/html")
+        .reply(&depth)
+        .await;
+    assert_eq!(res.headers()["content-type"], "application/json");
+    let json: DepthResponse = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(json, msgpack);
+}
@@ -0,0 +1,236 @@
+//! Contains logic for the audit trail of the orders of order books
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use ethereum_types::U256;
+use serde::{Deserialize, Serialize};
+
+use crate::book::Fill;
+use crate::event::{BookEvent, BookEventKind};
+use crate::order::OrderId;
+use crate::util::{from_hex_de, from_hex_opt_de, from_hex_opt_se, from_hex_se};
+
+/// Maximum number of closed orders whose history is retained in each book
+pub const MAX_CLOSED_ORDERS: usize = 10_000;
+
+/// Maximum number of events retained in the history of each order
+pub const MAX_ORDER_EVENTS: usize = 100;
+
+/// Represents a step in the lifecycle of an order
+#[derive(
+    Clone, Copy, PartialEq, Eq, Debug, Display, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum OrderEventKind {
+    Accepted,        /* the order passed its checks and entered the book */
+    Triggered,       /* the stop order was triggered and is being matched */
+    Amended,         /* the open order was amended */
+    PartiallyFilled, /* the order was matched, with volume remaining */
+    Filled,          /* the order was matched in its entirety */
+    Cancelled,       /* the order was cancelled, by its trader or otherwise */
+    Expired,         /* the open order was removed upon expiry */
+    Killed,          /* the unmatched remainder of the order was dropped */
+}
+
+impl OrderEventKind {
+    /// Returns whether the order is closed after an event of this kind
+    pub fn closes(&self) -> bool {
+        matches!(
+            self,
+            OrderEventKind::Filled
+                | OrderEventKind::Cancelled
+                | OrderEventKind::Expired
+                | OrderEventKind::Killed
+        )
+    }
+}
+
+/// Represents a single event in the history of an order
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct OrderEvent {
+    pub kind: OrderEventKind,
+    pub sequence: u64, /* sequence number of the book upon the event */
+    #[serde(with = "ts_seconds")]
+    pub timestamp: DateTime<Utc>,
+    #[serde(
+        default,
+        serialize_with = "from_hex_opt_se",
+        deserialize_with = "from_hex_opt_de"
+    )]
+    pub price: Option<U256>, /* price of the trade (fills only) */
+    #[serde(
+        default,
+        serialize_with = "from_hex_opt_se",
+        deserialize_with = "from_hex_opt_de"
+    )]
+    pub quantity: Option<U256>, /* amount traded (fills only) */
+    #[serde(serialize_with = "from_hex_se", deserialize_with = "from_hex_de")]
+    pub remaining: U256, /* unfilled amount of the order after the event */
+}
+
+impl OrderEvent {
+    /// Constructor for the `OrderEvent` type, for events other than fills
+    pub fn new(
+        kind: OrderEventKind,
+        sequence: u64,
+        timestamp: DateTime<Utc>,
+        remaining: U256,
+    ) -> Self {
+        Self {
+            kind,
+            sequence,
+            timestamp,
+            price: None,
+            quantity: None,
+            remaining,
+        }
+    }
+
+    /// Builds the event for one side of a fill, given the unfilled amount of
+    /// that side's order afterwards
+    pub fn fill(sequence: u64, fill: &Fill, remaining: U256) -> Self {
+        let kind: OrderEventKind = if remaining.is_zero() {
+            OrderEventKind::Filled
+        } else {
+            OrderEventKind::PartiallyFilled
+        };
+
+        Self {
+            price: Some(fill.price),
+            quantity: Some(fill.quantity),
+            ..OrderEvent::new(kind, sequence, fill.timestamp, remaining)
+        }
+    }
+}
+
+/// Represents the history of a single order, oldest event first
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct OrderRecord {
+    pub events: VecDeque<OrderEvent>,
+    pub truncated: bool, /* were the earliest events evicted? */
+}
+
+/// Tracks the history of the orders of an order book
+///
+/// The histories of open orders are always retained, along with those of the
+/// `MAX_CLOSED_ORDERS` most recently closed orders. Beyond that, the history
+/// of the order that closed the longest ago is evicted as each order closes.
+/// Each order's history is itself limited to its `MAX_ORDER_EVENTS` latest
+/// events, so that an order filled piecemeal can't grow without bound.
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct OrderHistory {
+    orders: HashMap<OrderId, OrderRecord>,
+    closed: VecDeque<OrderId>, /* closed orders, oldest first */
+}
+
+impl OrderHistory {
+    /// Returns the history of the order with the given ID, if retained
+    pub fn get(&self, order_id: OrderId) -> Option<&OrderRecord> {
+        self.orders.get(&order_id)
+    }
+
+    /// Appends an event to the history of the order with the given ID
+    pub fn record(&mut self, order_id: OrderId, event: OrderEvent) {
+        let closes: bool = event.kind.closes();
+        let record: &mut OrderRecord = self.orders.entry(order_id).or_default();
+
+        /* an order resubmitted after closing is open once more */
+        let reopened: bool = event.kind == OrderEventKind::Accepted
+            && record.events.back().is_some_and(|t| t.kind.closes());
+
+        record.events.push_back(event);
+
+        if record.events.len() > MAX_ORDER_EVENTS {
+            record.events.pop_front();
+            record.truncated = true;
+        }
+
+        if reopened {
+            self.closed.retain(|id| *id != order_id);
+        }
+
+        if closes {
+            self.closed.push_back(order_id);
+            self.evict();
+        }
+    }
+
+    /// Appends the events describing a change to the book to the histories
+    /// of the orders it concerns
+    pub fn record_book_event(&mut self, event: &BookEvent, now: DateTime<Utc>) {
+        let (order_id, kind, remaining) = match &event.kind {
+            BookEventKind::Fill {
+                fill,
+                maker_remaining,
+                taker_remaining,
+            } => {
+                self.record(
+                    fill.maker_id,
+                    OrderEvent::fill(event.sequence, fill, *maker_remaining),
+                );
+                self.record(
+                    fill.taker_id,
+                    OrderEvent::fill(event.sequence, fill, *taker_remaining),
+                );
+                return;
+            }
+            /* acceptance is recorded before an order is matched, whereas an
+             * order is added once matching is done */
+            BookEventKind::Added(_) => return,
+            BookEventKind::Amended(order) => {
+                (order.id, OrderEventKind::Amended, order.remaining)
+            }
+            BookEventKind::Cancelled(order) => {
+                (order.id, OrderEventKind::Cancelled, order.remaining)
+            }
+            BookEventKind::Expired(order) => {
+                (order.id, OrderEventKind::Expired, order.remaining)
+            }
+            BookEventKind::Triggered(order) => {
+                (order.id, OrderEventKind::Triggered, order.remaining)
+            }
+        };
+
+        self.record(
+            order_id,
+            OrderEvent::new(kind, event.sequence, now, remaining),
+        );
+    }
+
+    /// Drops the histories of the orders that closed the longest ago, beyond
+    /// the most recent `MAX_CLOSED_ORDERS`
+    fn evict(&mut self) {
+        while self.closed.len() > MAX_CLOSED_ORDERS {
+            if let Some(order_id) = self.closed.pop_front() {
+                self.orders.remove(&order_id);
+            }
+        }
+    }
+}
+
+/// Represents an event in the history of an order in its external (API)
+/// format
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ExternalOrderEvent {
+    pub kind: OrderEventKind,
+    pub sequence: u64,
+    pub timestamp: String,
+    pub price: Option<String>,
+    pub quantity: Option<String>,
+    pub remaining: String,
+}
+
+impl From<OrderEvent> for ExternalOrderEvent {
+    fn from(value: OrderEvent) -> Self {
+        Self {
+            kind: value.kind,
+            sequence: value.sequence,
+            timestamp: value.timestamp.timestamp().to_string(),
+            price: value.price.map(|t| t.to_string()),
+            quantity: value.quantity.map(|t| t.to_string()),
+            remaining: value.remaining.to_string(),
+        }
+    }
+}
@@ -1,16 +1,107 @@
-#![feature(map_first_last)]
-#![feature(result_contains_err)]
-#![feature(destructuring_assignment)]
-
 #[macro_use]
 extern crate enum_display_derive;
 
 #[macro_use]
 extern crate log;
-extern crate pretty_env_logger;
 
+pub mod api;
 pub mod book;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod codec;
+pub mod event;
+pub mod executioner;
+pub mod history;
 pub mod order;
+pub mod replay;
+pub mod stats;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+pub mod tests;
+pub mod util;
+pub mod wal;
+
+#[cfg(feature = "rpc-client")]
 pub mod rpc;
+
+#[cfg(feature = "server")]
+pub mod actor;
+#[cfg(feature = "server")]
+pub mod args;
+#[cfg(feature = "server")]
+pub mod compression;
+#[cfg(feature = "server")]
+pub mod divergence;
+#[cfg(feature = "server")]
+pub mod expiry;
+#[cfg(feature = "server")]
+pub mod handler;
+#[cfg(feature = "server")]
+pub mod limit;
+#[cfg(feature = "server")]
+pub mod markets;
+#[cfg(feature = "openapi")]
+pub mod openapi;
+#[cfg(feature = "server")]
+pub mod routes;
+#[cfg(feature = "server")]
+pub mod shutdown;
+#[cfg(feature = "server")]
 pub mod state;
-pub mod util;
+#[cfg(feature = "server")]
+pub mod tls;
+#[cfg(feature = "server")]
+pub mod webhook;
+#[cfg(feature = "server")]
+pub mod ws;
+
+#[cfg(all(test, feature = "server"))]
+pub mod actor_tests;
+
+#[cfg(all(test, feature = "server"))]
+pub mod args_tests;
+
+#[cfg(all(test, feature = "server"))]
+pub mod book_tests;
+
+#[cfg(all(test, feature = "server", feature = "client"))]
+pub mod client_tests;
+
+#[cfg(all(test, feature = "server"))]
+pub mod compression_tests;
+
+#[cfg(all(test, feature = "server"))]
+pub mod handler_tests;
+
+#[cfg(all(test, feature = "server"))]
+pub mod limit_tests;
+
+#[cfg(test)]
+pub mod codec_tests;
+
+#[cfg(test)]
+pub mod matching_tests;
+
+#[cfg(all(test, feature = "openapi"))]
+pub mod openapi_tests;
+
+#[cfg(test)]
+pub mod replay_tests;
+
+#[cfg(all(test, feature = "server"))]
+pub mod publisher_tests;
+
+#[cfg(all(test, feature = "server"))]
+pub mod rpc_tests;
+
+#[cfg(all(test, feature = "server"))]
+pub mod shutdown_tests;
+
+#[cfg(all(test, feature = "server"))]
+pub mod tls_tests;
+
+#[cfg(all(test, feature = "server"))]
+pub mod webhook_tests;
+
+#[cfg(all(test, feature = "server"))]
+pub mod ws_tests;
@@ -0,0 +1,177 @@
+//! Contains logic for limiting the rate of requests made by each client
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use warp::reject::{self, Reject};
+use warp::{Filter, Rejection};
+use web3::types::Address;
+
+use crate::api::CreateOrderRequest;
+use crate::handler;
+
+/// The default number of requests per second each client may make to routes
+/// that change orders
+pub const DEFAULT_RATE_LIMIT_ORDERS_PER_SEC: f64 = 10.0;
+
+/// The default number of requests each client may make in a single burst
+pub const DEFAULT_RATE_LIMIT_BURST: u32 = 20;
+
+/// How often buckets of idle clients are pruned
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Represents a client whose requests are limited
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RateKey {
+    Trader(Address), /* the trader an order is for */
+    Ip(IpAddr),      /* the remote address of requests with no trader */
+}
+
+/// Represents the allowance of a single client, as a token bucket
+///
+/// The bucket holds up to `burst` tokens and is refilled at `rate` tokens per
+/// second. Each request takes a single token.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bucket {
+    tokens: f64,
+    updated: Instant, /* when `tokens` was last brought up to date */
+}
+
+impl Bucket {
+    /// Constructor for the `Bucket` type, which starts full
+    pub fn new(burst: f64, now: Instant) -> Self {
+        Self {
+            tokens: burst,
+            updated: now,
+        }
+    }
+
+    /// Returns the number of tokens in the bucket as of `now`
+    pub fn tokens(&self, rate: f64, burst: f64, now: Instant) -> f64 {
+        let elapsed: Duration = now.saturating_duration_since(self.updated);
+        (self.tokens + elapsed.as_secs_f64() * rate).min(burst)
+    }
+
+    /// Takes a token from the bucket as of `now`
+    ///
+    /// # Returns #
+    ///
+    /// Returns how long until a token will be available if the bucket is
+    /// empty.
+    pub fn take(
+        &mut self,
+        rate: f64,
+        burst: f64,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        self.tokens = self.tokens(rate, burst, now);
+        self.updated = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    buckets: HashMap<RateKey, Bucket>,
+    pruned: Instant, /* when idle buckets were last pruned */
+}
+
+/// Limits the rate of requests of every client, each with its own bucket
+///
+/// A client whose bucket has refilled is no different from one that has never
+/// made a request, so such buckets are pruned periodically in order that the
+/// number held doesn't grow without bound.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: f64,  /* tokens added per second */
+    burst: f64, /* capacity of each bucket */
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    /// Constructor for the `RateLimiter` type
+    pub fn new(rate: f64, burst: u32) -> Self {
+        Self {
+            rate,
+            burst: f64::from(burst.max(1)),
+            buckets: Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                pruned: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token from the client's bucket as of `now`, as for
+    /// `Bucket::take`
+    pub fn check(&self, key: RateKey, now: Instant) -> Result<(), Duration> {
+        let (rate, burst): (f64, f64) = (self.rate, self.burst);
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if now.saturating_duration_since(buckets.pruned) >= PRUNE_INTERVAL {
+            buckets
+                .buckets
+                .retain(|_key, bucket| bucket.tokens(rate, burst, now) < burst);
+            buckets.pruned = now;
+        }
+
+        buckets
+            .buckets
+            .entry(key)
+            .or_insert_with(|| Bucket::new(burst, now))
+            .take(rate, burst, now)
+    }
+
+    /// Returns the number of clients currently holding a bucket
+    pub fn tracked(&self) -> usize {
+        self.buckets.lock().unwrap().buckets.len()
+    }
+}
+
+/// Represents the rejection of a request made by a client over its limit
+#[derive(Debug)]
+pub struct RateLimited {
+    pub retry_after: Duration, /* until the client may make another */
+}
+
+impl Reject for RateLimited {}
+
+fn reject_limited(retry_after: Duration) -> Rejection {
+    reject::custom(RateLimited { retry_after })
+}
+
+/// Filter that limits the rate of requests from each remote IP address
+///
+/// Requests whose remote address is unknown aren't limited.
+pub fn by_ip(
+    limiter: Arc<RateLimiter>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::addr::remote()
+        .and_then(move |address: Option<SocketAddr>| {
+            let result: Result<(), Duration> = match address {
+                Some(t) => limiter.check(RateKey::Ip(t.ip()), Instant::now()),
+                None => Ok(()),
+            };
+            async move { result.map_err(reject_limited) }
+        })
+        .untuple_one()
+}
+
+/// Filter that extracts an order from the body of the request (as for
+/// `handler::body`), limiting the rate of requests for each trader
+pub fn by_trader(
+    limiter: Arc<RateLimiter>,
+    max_body_size: u64,
+) -> impl Filter<Extract = (CreateOrderRequest,), Error = Rejection> + Clone {
+    handler::body(max_body_size).and_then(move |request: CreateOrderRequest| {
+        let key: RateKey = RateKey::Trader(request.trader());
+        let result: Result<(), Duration> = limiter.check(key, Instant::now());
+        async move { result.map(|_| request).map_err(reject_limited) }
+    })
+}
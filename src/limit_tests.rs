@@ -0,0 +1,55 @@
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use web3::types::Address;
+
+use crate::limit::{Bucket, RateKey, RateLimiter};
+
+#[test]
+pub fn test_bucket_refill() {
+    let start: Instant = Instant::now();
+    let (rate, burst): (f64, f64) = (2.0, 3.0);
+    let mut bucket: Bucket = Bucket::new(burst, start);
+
+    /* a full bucket allows a burst */
+    for _ in 0..3 {
+        assert_eq!(bucket.take(rate, burst, start), Ok(()));
+    }
+    assert_eq!(
+        bucket.take(rate, burst, start),
+        Err(Duration::from_millis(500))
+    );
+
+    /* tokens are added at the rate given, up to the burst size */
+    let later: Instant = start + Duration::from_millis(250);
+    assert!((bucket.tokens(rate, burst, later) - 0.5).abs() < 1e-9);
+    assert_eq!(
+        bucket.take(rate, burst, later),
+        Err(Duration::from_millis(250))
+    );
+
+    let later: Instant = start + Duration::from_millis(500);
+    assert_eq!(bucket.take(rate, burst, later), Ok(()));
+
+    let later: Instant = start + Duration::from_secs(60);
+    assert!((bucket.tokens(rate, burst, later) - burst).abs() < 1e-9);
+}
+
+#[test]
+pub fn test_limiter_keys_and_pruning() {
+    let start: Instant = Instant::now();
+    let limiter: RateLimiter = RateLimiter::new(1.0, 1);
+    let trader: RateKey = RateKey::Trader(Address::from_low_u64_be(1));
+    let ip: RateKey = RateKey::Ip(IpAddr::from([127, 0, 0, 1]));
+
+    /* each client has a bucket of its own */
+    assert!(limiter.check(trader, start).is_ok());
+    assert!(limiter.check(trader, start).is_err());
+    assert!(limiter.check(ip, start).is_ok());
+    assert_eq!(limiter.tracked(), 2);
+
+    /* the buckets of idle clients are pruned */
+    let later: Instant = start + Duration::from_secs(120);
+    assert!(limiter.check(ip, later).is_ok());
+    assert_eq!(limiter.tracked(), 1);
+}
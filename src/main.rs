@@ -1,96 +1,387 @@
-#![feature(map_first_last)]
-#![feature(async_closure)]
-#![feature(result_contains_err)]
-#![feature(destructuring_assignment)]
+use std::collections::BTreeSet;
 use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::sync::Arc;
+use std::time::Duration;
 
-use clap::{App, Arg};
-use tokio::sync::Mutex;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use futures_util::future::{self, FutureExt, LocalBoxFuture};
+use futures_util::stream::{self, Stream};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{oneshot, RwLock, RwLockWriteGuard};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
 use warp::Filter;
-use web3::types::Address;
-
-#[macro_use]
-extern crate enum_display_derive;
+use web3::types::{Address, U256};
 
 #[macro_use]
 extern crate log;
-extern crate pretty_env_logger;
 
-pub mod args;
-pub mod book;
-pub mod handler;
-pub mod order;
-pub mod rpc;
-pub mod state;
-pub mod tests;
-pub mod util;
+use tracer_ome::args::{self, Arguments, EngineMode, Listener, LogFormat};
+use tracer_ome::book::{Book, InvariantViolation};
+use tracer_ome::divergence::{self, DivergenceMetrics};
+use tracer_ome::event::publisher::PublisherMetrics;
+#[cfg(feature = "kafka")]
+use tracer_ome::event::publisher::{self, KafkaSink};
+use tracer_ome::executioner::Executioner;
+use tracer_ome::limit::RateLimiter;
+#[cfg(feature = "openapi")]
+use tracer_ome::openapi;
+use tracer_ome::replay::{self, Replay, ReplayError, ReplayRecord};
+use tracer_ome::routes::{self, LegacyRouteMetrics, RouteContext};
+use tracer_ome::rpc::RpcClient;
+use tracer_ome::state::inspect::{self, MarketDifference};
+use tracer_ome::state::{OmeState, Readiness};
+use tracer_ome::tls::{self, TlsIdentity};
+use tracer_ome::util;
+use tracer_ome::wal::{Wal, DEFAULT_WAL_MAX_SIZE};
+use tracer_ome::{expiry, handler, markets, shutdown, webhook};
 
-#[cfg(test)]
-pub mod book_tests;
+/// Installs the global subscriber that all logging is written through
+///
+/// Records from the `log` macros are bridged into the subscriber too, so they
+/// carry the fields of the span they were made within (e.g., the order ID).
+fn init_logging(format: LogFormat) {
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_span_events(FmtSpan::CLOSE);
 
-use crate::args::Arguments;
-use crate::order::OrderId;
-use crate::state::OmeState;
+    match format {
+        LogFormat::Json => subscriber.json().init(),
+        LogFormat::Pretty => subscriber.init(),
+    }
+}
 
 #[tokio::main]
 async fn main() {
-    pretty_env_logger::init();
-
     /* define our command-line interface using Clap's builder syntax */
     let matches = App::new("Tracer OME")
         .version("0.1.0")
-        .arg(
-            Arg::with_name("address")
-                .short("l")
-                .long("listen")
-                .value_name("address")
-                .help("The address to listen on")
-                .takes_value(true),
-        )
-        .arg(
-            Arg::with_name("port")
-                .short("p")
-                .long("port")
-                .value_name("port")
-                .help("The TCP port to listen on")
-                .takes_value(true),
-        )
-        .arg(
-            Arg::with_name("dumpfile")
-                .long("dumpfile")
-                .value_name("dumpfile")
-                .help("The path to the dump file to use for state resumes")
-                .takes_value(true),
-        )
-        .arg(
-            Arg::with_name("executioner_address")
-                .long("executioner_address")
-                .value_name("executioner_address")
-                .help("Address of the Web3 executioner")
-                .takes_value(true),
+        .args(&args::serve_args())
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("Runs the OME (the default when no subcommand is given)")
+                .args(&args::serve_args()),
         )
-        .arg(
-            Arg::with_name("certificate_path")
-                .long("certificate_path")
-                .value_name("certificate_path")
-                .help("File path to the TLS certificate file")
-                .takes_value(true),
+        .subcommand(
+            SubCommand::with_name("snapshot")
+                .about("Inspects dump files of the OME's state offline")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("inspect")
+                        .about("Summarises every book of a dump file")
+                        .arg(
+                            Arg::with_name("path")
+                                .help("The dump file to inspect")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("validate")
+                        .about("Checks the invariants of every book of a dump file")
+                        .arg(
+                            Arg::with_name("path")
+                                .help("The dump file to validate")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("diff")
+                        .about("Compares the books of two dump files")
+                        .arg(
+                            Arg::with_name("a")
+                                .help("The earlier dump file")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("b")
+                                .help("The later dump file")
+                                .required(true),
+                        ),
+                ),
         )
-        .arg(
-            Arg::with_name("private_key_path")
-                .long("private_key_path")
-                .value_name("private_key_path")
-                .help("File path to the TLS private key file")
-                .takes_value(true),
-        )
-        .arg(
-            Arg::with_name("force-no-tls")
-                .long("force-no-tls")
-                .help("Flag to force TLS to be turned off"),
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("Replays recorded order flow through a single book")
+                .arg(
+                    Arg::with_name("input")
+                        .help("The recorded order flow, as JSON lines")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .help("The directory to write the outcome to")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("market")
+                        .long("market")
+                        .value_name("market")
+                        .help("The market of the book (by default, that of the first order)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("tick_size")
+                        .long("tick-size")
+                        .value_name("tick_size")
+                        .help("The minimum price increment of the book")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("lot_size")
+                        .long("lot-size")
+                        .value_name("lot_size")
+                        .help("The minimum quantity increment of the book")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("maker_fee_bps")
+                        .long("maker-fee-bps")
+                        .value_name("maker_fee_bps")
+                        .help("The fee charged to makers, in basis points")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("taker_fee_bps")
+                        .long("taker-fee-bps")
+                        .value_name("taker_fee_bps")
+                        .help("The fee charged to takers, in basis points")
+                        .takes_value(true),
+                ),
         )
         .get_matches();
 
+    match matches.subcommand() {
+        ("snapshot", Some(t)) => process::exit(snapshot(t)),
+        ("replay", Some(t)) => process::exit(replay(t).await),
+        ("serve", Some(t)) => serve(t.clone()).await,
+        _ => serve(matches).await,
+    }
+}
+
+/// Runs the `snapshot` subcommand, returning the status to exit with
+///
+/// Unreadable dump files exit with 2, and invalid books (or, when comparing,
+/// any difference at all) with 1.
+fn snapshot(matches: &ArgMatches<'_>) -> i32 {
+    match matches.subcommand() {
+        ("inspect", Some(t)) => {
+            let state: OmeState = match read_snapshot(t, "path") {
+                Some(t) => t,
+                None => return 2,
+            };
+
+            for summary in inspect::inspect(&state) {
+                println!(
+                    "{:?}: {} orders ({} bids, {} asks resting), best bid {}, \
+                     best ask {}, open notional {}, sequence {}{}",
+                    summary.market,
+                    summary.orders,
+                    summary.depth.0,
+                    summary.depth.1,
+                    display_price(summary.top.0),
+                    display_price(summary.top.1),
+                    summary.open_notional,
+                    summary.sequence,
+                    if summary.paused { ", paused" } else { "" }
+                );
+            }
+            0
+        }
+        ("validate", Some(t)) => {
+            let state: OmeState = match read_snapshot(t, "path") {
+                Some(t) => t,
+                None => return 2,
+            };
+            let invalid: Vec<(Address, Vec<InvariantViolation>)> =
+                inspect::validate(&state);
+
+            for (market, violations) in &invalid {
+                for violation in violations {
+                    println!("{:?}: {}", market, violation);
+                }
+            }
+            println!(
+                "{} of {} books are invalid",
+                invalid.len(),
+                state.books().len()
+            );
+
+            if invalid.is_empty() {
+                0
+            } else {
+                1
+            }
+        }
+        ("diff", Some(t)) => {
+            let (a, b): (OmeState, OmeState) =
+                match (read_snapshot(t, "a"), read_snapshot(t, "b")) {
+                    (Some(a), Some(b)) => (a, b),
+                    _ => return 2,
+                };
+            let differences: Vec<MarketDifference> = inspect::diff(&a, &b);
+
+            for difference in &differences {
+                match difference {
+                    MarketDifference::Removed(market) => {
+                        println!("{:?}: only in a", market)
+                    }
+                    MarketDifference::Added(market) => {
+                        println!("{:?}: only in b", market)
+                    }
+                    MarketDifference::Changed(market, diff) => {
+                        println!(
+                            "{:?}: top of book {} / {} in a, {} / {} in b",
+                            market,
+                            display_price(diff.top.0),
+                            display_price(diff.top.1),
+                            display_price(diff.other_top.0),
+                            display_price(diff.other_top.1)
+                        );
+                        for level in &diff.levels {
+                            println!(
+                                "  {} {}: {} in a, {} in b",
+                                level.side,
+                                level.price,
+                                level.ours,
+                                level.theirs
+                            );
+                        }
+                    }
+                }
+            }
+
+            if differences.is_empty() {
+                0
+            } else {
+                1
+            }
+        }
+        _ => 2, /* a subcommand is required */
+    }
+}
+
+/* reads the dump file named by the given argument, reporting any error */
+fn read_snapshot(matches: &ArgMatches<'_>, name: &str) -> Option<OmeState> {
+    let path: &str = matches.value_of(name)?;
+
+    match inspect::read(Path::new(path)) {
+        Ok(t) => Some(t),
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            None
+        }
+    }
+}
+
+fn display_price(price: Option<U256>) -> String {
+    price.map_or_else(|| "none".to_string(), |t| t.to_string())
+}
+
+/// Runs the `replay` subcommand, returning the status to exit with
+///
+/// The book is created with the parameters given (the defaults otherwise),
+/// and the summary of the replay is printed once its outcome is written.
+async fn replay(matches: &ArgMatches<'_>) -> i32 {
+    /* both are required */
+    let input: &str = matches.value_of("input").unwrap_or_default();
+    let output: &str = matches.value_of("output").unwrap_or_default();
+
+    let records: Vec<ReplayRecord> = match File::open(input)
+        .map_err(ReplayError::from)
+        .and_then(|t| replay::read_records(BufReader::new(t)))
+    {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("{}: {}", input, e);
+            return 2;
+        }
+    };
+
+    let market: Option<Address> = match matches.value_of("market") {
+        Some(t) => match util::strip_hex_prefix(t).parse() {
+            Ok(t) => Some(t),
+            Err(_e) => {
+                eprintln!("Invalid market");
+                return 2;
+            }
+        },
+        None => records.iter().find_map(|record| match record {
+            ReplayRecord::Order(order) => Some(order.market),
+            ReplayRecord::Cancel { .. } => None,
+        }),
+    };
+    let book: Book = match replay_book(matches, market.unwrap_or_default()) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+
+    let outcome: Replay = replay::replay(book, records).await;
+    if let Err(e) = outcome.write(Path::new(output)) {
+        eprintln!("{}: {}", output, e);
+        return 2;
+    }
+
+    match serde_json::to_string_pretty(&outcome.summary) {
+        Ok(t) => println!("{}", t),
+        Err(e) => eprintln!("{}", e),
+    }
+    0
+}
+
+/* creates the book to replay against, with the parameters given */
+fn replay_book(
+    matches: &ArgMatches<'_>,
+    market: Address,
+) -> Result<Book, &'static str> {
+    let mut book: Book = Book::new(market);
+
+    if let Some(t) = matches.value_of("tick_size") {
+        book = book
+            .with_tick_size(util::parse_u256(t).ok_or("Invalid tick size")?);
+    }
+    if let Some(t) = matches.value_of("lot_size") {
+        book =
+            book.with_lot_size(util::parse_u256(t).ok_or("Invalid lot size")?);
+    }
+    if let Some(t) = matches.value_of("maker_fee_bps") {
+        book = book
+            .with_maker_fee_bps(t.parse().map_err(|_| "Invalid maker fee")?);
+    }
+    if let Some(t) = matches.value_of("taker_fee_bps") {
+        book = book
+            .with_taker_fee_bps(t.parse().map_err(|_| "Invalid taker fee")?);
+    }
+
+    Ok(book)
+}
+
+/// Runs the OME itself
+/* accepts connections on the Unix socket at the given path, replacing any
+ * socket left there by a previous run */
+fn unix_incoming(
+    path: &Path,
+) -> io::Result<impl Stream<Item = io::Result<UnixStream>>> {
+    if fs::symlink_metadata(path).is_ok_and(|t| t.file_type().is_socket()) {
+        fs::remove_file(path)?;
+    }
+
+    let listener: UnixListener = UnixListener::bind(path)?;
+    Ok(stream::poll_fn(move |cx| {
+        listener
+            .poll_accept(cx)
+            .map(|t| Some(t.map(|(connection, _)| connection)))
+    }))
+}
+
+async fn serve(matches: ArgMatches<'_>) {
     let arguments: Arguments = match matches.try_into() {
         Ok(t) => t,
         Err(e) => {
@@ -99,115 +390,350 @@ async fn main() {
         }
     };
 
-    let internal_state = if util::is_existing_state(&arguments.dumpfile_path) {
-        match OmeState::from_dumpfile(&arguments.dumpfile_path) {
-            Some(s) => s,
-            None => OmeState::new(),
-        }
+    init_logging(arguments.log_format);
+
+    if !arguments.unknown_config_keys.is_empty() {
+        warn!(
+            "Ignoring unknown keys in the configuration file: {}",
+            arguments.unknown_config_keys.join(", ")
+        );
+    }
+
+    /* check the TLS configuration up front, as the HTTP server would only
+     * panic on it once listening */
+    let identity: Option<TlsIdentity> = if arguments.force_no_tls {
+        None
     } else {
-        Default::default()
+        match tls::load(&arguments) {
+            Ok(t) => Some(t),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+    };
+
+    let executioner: Executioner = match RpcClient::new(
+        &arguments.executioner_address,
+        arguments.rpc_connect_timeout,
+        arguments.rpc_timeout,
+        arguments.rpc_max_attempts,
+    ) {
+        Ok(t) => Executioner::new(t),
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    let markets_client: Option<RpcClient> = match &arguments.markets_address {
+        Some(address) => match RpcClient::new(
+            address,
+            arguments.rpc_connect_timeout,
+            arguments.rpc_timeout,
+            arguments.rpc_max_attempts,
+        ) {
+            Ok(t) => Some(t),
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    /* webhooks are held to the same timeouts as the executioner */
+    let webhook_client: reqwest::Client = match reqwest::Client::builder()
+        .connect_timeout(arguments.rpc_connect_timeout)
+        .timeout(arguments.rpc_timeout)
+        .build()
+    {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
     };
 
-    /* initialise engine state */
-    let state: Arc<Mutex<OmeState>> = Arc::new(Mutex::new(internal_state));
+    /* books found to differ from their source are counted for the metrics */
+    let divergence_metrics: Arc<DivergenceMetrics> =
+        Arc::new(DivergenceMetrics::default());
+    if arguments.divergence_check_interval.is_some() && markets_client.is_none()
+    {
+        warn!("No markets address is set, so books won't be checked");
+    }
 
-    /* Clone global engine state for each handler. This is only done because of
-     * the nature of move semantics for Rust closures.
-     *
-     * This is quite hacky and likely quite expensive.
-     */
-    let index_book_state: Arc<Mutex<OmeState>> = state.clone();
-    let create_book_state: Arc<Mutex<OmeState>> = state.clone();
-    let read_book_state: Arc<Mutex<OmeState>> = state.clone();
+    /* events are handed to Kafka (if configured) by a task of their own */
+    let publisher_metrics: Arc<PublisherMetrics> =
+        Arc::new(PublisherMetrics::default());
+    #[cfg(feature = "kafka")]
+    let kafka_sink: Option<KafkaSink> = match &arguments.kafka_brokers {
+        Some(brokers) => match KafkaSink::new(
+            brokers,
+            &arguments.kafka_topic_prefix,
+            publisher_metrics.clone(),
+        ) {
+            Ok(t) => Some(t),
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        },
+        None => None,
+    };
 
-    let create_order_state: Arc<Mutex<OmeState>> = state.clone();
-    let read_order_state: Arc<Mutex<OmeState>> = state.clone();
-    let destroy_order_state: Arc<Mutex<OmeState>> = state.clone();
+    /* changes logged against discarded books can't be replayed later */
+    if arguments.no_restore {
+        if let Some(wal_path) = &arguments.wal_path {
+            if Wal::files(wal_path).map_or(true, |files| !files.is_empty()) {
+                eprintln!(
+                    "Refusing to start afresh on top of the write-ahead log \
+                     at {}",
+                    wal_path.display()
+                );
+                return;
+            }
+        }
+    }
 
-    let market_user_orders_state: Arc<Mutex<OmeState>> = state.clone();
+    /* initialise engine state, which is held until it has been restored so
+     * that it is neither read nor dumped in the meantime */
+    let state: Arc<RwLock<OmeState>> = Arc::new(RwLock::new(OmeState::new()));
+    let mut restoring: RwLockWriteGuard<OmeState> = state.write().await;
 
-    /* define CRUD routes for order books */
-    let book_prefix = warp::path!("book");
-    let index_book_route = book_prefix
-        .and(warp::get())
-        .and(warp::any().map(move || index_book_state.clone()))
-        .and_then(handler::index_book_handler);
-    let create_book_route = book_prefix
-        .and(warp::post())
-        .and(warp::body::json())
-        .and(warp::any().map(move || create_book_state.clone()))
-        .and_then(handler::create_book_handler);
-    let read_book_route = warp::path!("book" / Address)
-        .and(warp::get())
-        .and(warp::any().map(move || read_book_state.clone()))
-        .and_then(handler::read_book_handler);
-
-    /* define CRUD routes for orders */
-    let tmp_args: Arguments = arguments.clone();
-    let create_order_route = warp::path!("book" / Address / "order")
-        .and(warp::post())
-        .and(warp::body::json())
-        .and(warp::any().map(move || create_order_state.clone()))
-        .and(warp::any().map(move || tmp_args.executioner_address.clone()))
-        .and_then(handler::create_order_handler);
-    let read_order_route = warp::path!("book" / Address / "order" / OrderId)
-        .and(warp::get())
-        .and(warp::any().map(move || read_order_state.clone()))
-        .and_then(handler::read_order_handler);
-    let destroy_order_route = warp::path!("book" / Address / "order" / OrderId)
-        .and(warp::delete())
-        .and(warp::any().map(move || destroy_order_state.clone()))
-        .and_then(handler::destroy_order_handler);
-
-    let market_user_orders_route = warp::path!("book" / Address / Address)
+    /* the known markets must be synced within a few intervals to be ready */
+    let readiness: Arc<Readiness> =
+        Arc::new(Readiness::new(markets_client.as_ref().map(|_| {
+            chrono::Duration::from_std(arguments.market_sync_interval * 3)
+                .unwrap_or_else(|_e| chrono::Duration::max_value())
+        })));
+
+    let readiness_state: Arc<RwLock<OmeState>> = state.clone();
+    let markets_state: Arc<RwLock<OmeState>> = state.clone();
+    let expiry_state: Arc<RwLock<OmeState>> = state.clone();
+    let webhook_state: Arc<RwLock<OmeState>> = state.clone();
+    let metrics_state: Arc<RwLock<OmeState>> = state.clone();
+    let divergence_state: Arc<RwLock<OmeState>> = state.clone();
+
+    /* creating, pausing and reloading books, dumping the state, cancelling
+     * orders in bulk, and managing webhooks and the blocklist are reserved
+     * for admins */
+    if arguments.admin_token.is_none() {
+        warn!("No admin token is set, so admin routes are open to anyone");
+    }
+
+    /* every version of the API is served from the same context, so that
+     * e.g. the rate limits apply across all of them */
+    let context: RouteContext = RouteContext {
+        readiness: readiness.clone(),
+        executioner,
+        markets_client: markets_client.clone(),
+        limiter: Arc::new(RateLimiter::new(
+            arguments.rate_limit_orders_per_sec,
+            arguments.rate_limit_burst,
+        )),
+        admin_token: arguments.admin_token.clone(),
+        dumpfile_path: arguments.dumpfile_path.clone(),
+        max_body_size: arguments.max_body_size,
+        max_clock_skew: arguments.max_clock_skew,
+        signature_chain_id: if arguments.verify_signatures {
+            Some(arguments.chain_id)
+        } else {
+            None
+        },
+        compress: arguments.enable_compression,
+    };
+    let legacy_metrics: Arc<LegacyRouteMetrics> =
+        Arc::new(LegacyRouteMetrics::default());
+
+    let metrics_publisher_metrics: Arc<PublisherMetrics> =
+        publisher_metrics.clone();
+    let metrics_divergence_metrics: Arc<DivergenceMetrics> =
+        divergence_metrics.clone();
+    let metrics_legacy_metrics: Arc<LegacyRouteMetrics> =
+        legacy_metrics.clone();
+    let metrics_route = warp::path!("metrics")
         .and(warp::get())
-        .and(warp::any().map(move || market_user_orders_state.clone()))
-        .and_then(handler::market_user_orders_handler);
+        .and(warp::any().map(move || metrics_publisher_metrics.clone()))
+        .and(warp::any().map(move || metrics_divergence_metrics.clone()))
+        .and(warp::any().map(move || metrics_legacy_metrics.clone()))
+        .and(warp::any().map(move || metrics_state.clone()))
+        .and_then(handler::metrics_handler);
 
     // Healthcheck
     let health_route = warp::path::end()
+        .or(warp::path!("healthz"))
+        .unify()
         .and(warp::get())
         .and_then(handler::health_check_handler);
+    let readiness_route_readiness: Arc<Readiness> = readiness.clone();
+    let readiness_route = warp::path!("readyz")
+        .and(warp::get())
+        .and(warp::any().map(move || readiness_state.clone()))
+        .and(warp::any().map(move || readiness_route_readiness.clone()))
+        .and_then(handler::readiness_handler);
 
-    /* aggregate all of our order book routes */
-    let book_routes =
-        index_book_route.or(create_book_route).or(read_book_route);
-
-    /* aggregate all of our order routes */
-    let order_routes = create_order_route
-        .or(read_order_route)
-        .or(destroy_order_route);
-
-    let misc_routes = market_user_orders_route;
-
-    let cors = warp::cors()
-        .allow_any_origin()
-        .allow_headers(vec![
-            "Origin",
-            "Access-Control-Request-Headers",
-            "Content-Type",
-            "Access-Control-Allow-Origin",
-        ])
-        .allow_methods(vec!["GET", "POST", "PUT", "DELETE"]);
-
-    /* aggregate all of our routes */
-    let routes = health_route
-        .or(book_routes)
-        .or(order_routes)
-        .or(misc_routes)
-        .with(cors);
-
-    /* start the web server */
-    if arguments.force_no_tls {
-        warp::serve(routes)
-            .run((arguments.listen_address, arguments.listen_port))
-            .await;
-    } else {
-        warp::serve(routes)
-            .tls()
-            .cert_path(arguments.certificate_path)
-            .key_path(arguments.private_key_path)
-            .run((arguments.listen_address, arguments.listen_port))
-            .await;
+    let cors = handler::cors(
+        &arguments.cors_allowed_origins,
+        &arguments.cors_allowed_methods,
+    );
+
+    /* aggregate all of our routes, including every version of the API (and
+     * its specification, if built in), answering rejections with the API
+     * error envelope */
+    let api = health_route
+        .or(readiness_route)
+        .or(metrics_route)
+        .or(routes::versioned(state.clone(), &context, legacy_metrics));
+    #[cfg(feature = "openapi")]
+    let api = api.or(openapi::routes());
+    let routes = api
+        .recover(handler::handle_rejection)
+        .with(cors)
+        .with(warp::trace::request());
+
+    /* start the web server, which shuts down gracefully (dumping the state)
+     * upon SIGTERM or SIGINT */
+    let (drain, drained) = oneshot::channel::<()>();
+    let drained = async move {
+        let _ = drained.await;
+    }
+    .shared();
+
+    /* one server per listener, all of which are drained together (TLS is only
+     * ever served over TCP) */
+    let mut servers: Vec<LocalBoxFuture<'static, ()>> = vec![];
+    for listener in &arguments.listeners {
+        let server: LocalBoxFuture<'static, ()> = match (listener, &identity) {
+            (Listener::Tcp(address), None) => warp::serve(routes.clone())
+                .bind_with_graceful_shutdown(*address, drained.clone())
+                .1
+                .boxed_local(),
+            (Listener::Tcp(address), Some(t)) => warp::serve(routes.clone())
+                .tls()
+                .cert(&t.certificates)
+                .key(&t.private_key)
+                .bind_with_graceful_shutdown(*address, drained.clone())
+                .1
+                .boxed_local(),
+            (Listener::Unix(path), _) => match unix_incoming(path) {
+                Ok(incoming) => warp::serve(routes.clone())
+                    .serve_incoming_with_graceful_shutdown(
+                        incoming,
+                        drained.clone(),
+                    )
+                    .boxed_local(),
+                Err(e) => {
+                    eprintln!("{}: Failed to listen: {}", path.display(), e);
+                    process::exit(1);
+                }
+            },
+        };
+        info!("Listening on {}", listener);
+        servers.push(server);
+    }
+    let server = future::join_all(servers).map(|_| ());
+
+    /* restore the state while serving, reporting readiness once done */
+    let no_restore: bool = arguments.no_restore;
+    let dumpfile_path: PathBuf = arguments.dumpfile_path.clone();
+    let wal_path: Option<PathBuf> = arguments.wal_path.clone();
+    let market_sync_interval: Duration = arguments.market_sync_interval;
+    let divergence_check_interval: Option<Duration> =
+        arguments.divergence_check_interval;
+    let divergence_tolerance: U256 = arguments.divergence_tolerance;
+    let expiry_sweep_interval: Duration = arguments.expiry_sweep_interval;
+    let engine_mode: EngineMode = arguments.engine_mode;
+    let allowed_traders: Option<BTreeSet<Address>> =
+        arguments.allowed_traders.clone();
+    let restore = async move {
+        *restoring = if no_restore {
+            info!("Starting without restoring any books");
+            OmeState::new()
+        } else {
+            match OmeState::restore(&dumpfile_path, wal_path.as_deref()).await {
+                Ok(t) => t,
+                Err(e) => {
+                    error!("{}", e);
+                    process::exit(1);
+                }
+            }
+        };
+
+        if let Some(wal_path) = &wal_path {
+            match Wal::open(wal_path, DEFAULT_WAL_MAX_SIZE) {
+                Ok(t) => restoring.set_wal(t),
+                Err(e) => {
+                    error!("Failed to open write-ahead log: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        /* the allowlist is configured rather than dumped, so is set afresh */
+        restoring.set_allowlist(allowed_traders);
+
+        /* books are handed to their tasks once they log to the right place */
+        if engine_mode == EngineMode::Actor {
+            restoring.host_books();
+        }
+
+        /* subscribe before unlocking, so that no fills are missed */
+        let fills = restoring.subscribe();
+        #[cfg(feature = "kafka")]
+        let published = kafka_sink.map(|sink| (sink, restoring.subscribe()));
+
+        drop(restoring);
+        readiness.set_restored();
+        info!("Ready");
+
+        /* notify webhooks of fills in the background */
+        tokio::spawn(webhook::run(webhook_state, fills, webhook_client));
+
+        /* publish every event to Kafka in the background */
+        #[cfg(feature = "kafka")]
+        if let Some((sink, events)) = published {
+            tokio::spawn(publisher::run(sink, events, publisher_metrics));
+        }
+
+        /* remove expired orders even from books that see no other changes */
+        tokio::spawn(expiry::run(expiry_state, expiry_sweep_interval));
+
+        /* create books for markets deployed while the OME is running, and
+         * check the books against their source (if asked to) */
+        if let Some(client) = markets_client {
+            if let Some(interval) = divergence_check_interval {
+                tokio::spawn(divergence::run(
+                    client.clone(),
+                    divergence_state,
+                    interval,
+                    divergence_tolerance,
+                    divergence_metrics,
+                ));
+            }
+
+            tokio::spawn(markets::run(
+                client,
+                markets_state,
+                market_sync_interval,
+            ));
+        }
+    };
+
+    let (_, dumped) = tokio::join!(
+        restore,
+        shutdown::run(
+            server,
+            shutdown::signal(),
+            drain,
+            state.clone(),
+            &arguments.dumpfile_path,
+            arguments.shutdown_timeout,
+        )
+    );
+
+    if let Err(e) = dumped {
+        eprintln!("Failed to dump state: {}", e);
+        process::exit(1);
     }
 }
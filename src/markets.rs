@@ -0,0 +1,61 @@
+//! Contains logic for keeping the order books in step with the known markets
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use tokio::sync::{RwLock, RwLockWriteGuard};
+use tokio::time::{self, Interval};
+use web3::types::Address;
+
+use crate::rpc::{RpcClient, RpcError};
+use crate::state::OmeState;
+
+/// The default number of seconds between syncs of the known markets
+pub const DEFAULT_MARKET_SYNC_INTERVAL: u64 = 60;
+
+/// Syncs the order books with the known markets every `interval`, forever
+///
+/// A failed sync is logged and tried again at the next interval, rather than
+/// ending the task.
+pub async fn run(
+    client: RpcClient,
+    state: Arc<RwLock<OmeState>>,
+    interval: Duration,
+) {
+    let mut ticks: Interval = time::interval(interval);
+
+    loop {
+        ticks.tick().await;
+
+        if let Err(e) = sync(&client, &state).await {
+            warn!("Failed to sync known markets: {}", e);
+        }
+    }
+}
+
+/// Creates an empty order book for each known market that lacks one,
+/// returning the number created
+///
+/// Books of markets that are no longer known are logged, but left in place.
+pub async fn sync(
+    client: &RpcClient,
+    state: &RwLock<OmeState>,
+) -> Result<usize, RpcError> {
+    let markets: Vec<Address> = client.get_known_markets().await?;
+
+    let mut ome_state: RwLockWriteGuard<OmeState> = state.write().await;
+    let existing: usize = ome_state.markets().len();
+    let unknown: Vec<Address> = ome_state.sync_markets(&markets, Utc::now());
+    let created: usize = ome_state.markets().len() - existing;
+
+    for market in unknown {
+        warn!("Market {} is no longer known, keeping its book", market);
+    }
+
+    info!(
+        "Synced {} known markets, creating {} books",
+        markets.len(),
+        created
+    );
+    Ok(created)
+}
@@ -0,0 +1,418 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use ethereum_types::{Address, U256};
+use proptest::prelude::*;
+use proptest::test_runner::{Config, TestCaseError, TestError, TestRunner};
+
+use crate::book::{Book, MatchResult, MatchingAlgorithm, SelfTradePolicy};
+use crate::order::{Order, OrderId, OrderSide, TimeInForce};
+
+/* bounds of generated scenarios, kept tight so that orders keep meeting */
+const TRADERS: u64 = 4;
+const MIN_PRICE: u64 = 95;
+const MAX_PRICE: u64 = 105;
+const MAX_QUANTITY: u64 = 20;
+const MAX_OPERATIONS: usize = 40;
+
+/* a single step of a scenario */
+#[derive(Clone, Debug)]
+pub enum Operation {
+    Submit {
+        trader: u64,
+        side: OrderSide,
+        price: u64,
+        quantity: u64,
+        immediate: bool, /* immediate-or-cancel, rather than resting */
+    },
+    Cancel {
+        pick: usize, /* position among the open orders (wrapping around) */
+    },
+    CancelAll {
+        trader: u64,
+    },
+}
+
+impl Operation {
+    /* renders the operation as the Rust expression constructing it */
+    fn to_rust(&self) -> String {
+        match self {
+            Operation::Submit {
+                trader,
+                side,
+                price,
+                quantity,
+                immediate,
+            } => format!(
+                "Operation::Submit {{ trader: {}, side: OrderSide::{:?}, \
+                 price: {}, quantity: {}, immediate: {} }}",
+                trader, side, price, quantity, immediate
+            ),
+            Operation::Cancel { pick } => {
+                format!("Operation::Cancel {{ pick: {} }}", pick)
+            }
+            Operation::CancelAll { trader } => {
+                format!("Operation::CancelAll {{ trader: {} }}", trader)
+            }
+        }
+    }
+}
+
+/* a sequence of operations applied to a fresh book */
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    pub self_trade_policy: SelfTradePolicy,
+    pub matching_algorithm: MatchingAlgorithm,
+    pub operations: Vec<Operation>,
+}
+
+impl Scenario {
+    /* renders the scenario as a regression test for this module */
+    fn regression_test(&self) -> String {
+        let operations: String = self
+            .operations
+            .iter()
+            .map(|operation| format!("            {},\n", operation.to_rust()))
+            .collect();
+
+        format!(
+            "#[test]\n\
+             pub fn test_matching_regression() {{\n    \
+                 replay(&Scenario {{\n        \
+                     self_trade_policy: SelfTradePolicy::{:?},\n        \
+                     matching_algorithm: MatchingAlgorithm::{:?},\n        \
+                     operations: vec![\n{}        ],\n    \
+                 }})\n    \
+                 .unwrap();\n\
+             }}\n",
+            self.self_trade_policy, self.matching_algorithm, operations
+        )
+    }
+}
+
+/* running totals of quantity, which must always balance against the book */
+#[derive(Default)]
+struct Ledger {
+    submitted: U256, /* quantity of every accepted order */
+    filled: U256,    /* quantity of every fill (taken from both sides) */
+    removed: U256,   /* remaining quantity cancelled, killed or dropped */
+}
+
+/* checks the fills of an incoming order and records them in the ledger */
+fn check_match(
+    before: &Book,
+    after: &Book,
+    order: &Order,
+    result: &MatchResult,
+    ledger: &mut Ledger,
+) -> Result<(), String> {
+    ledger.submitted += order.quantity;
+
+    for fill in &result.fills {
+        ledger.filled += fill.quantity;
+
+        if fill.maker_address == fill.taker_address {
+            return Err(format!("Trade {} is a self-fill", fill.trade_id));
+        }
+
+        let maker: &Order = before.order(fill.maker_id).ok_or_else(|| {
+            format!("Trade {} has no resting maker", fill.trade_id)
+        })?;
+
+        if fill.price != maker.price {
+            return Err(format!(
+                "Trade {} is at {} rather than its maker's price of {}",
+                fill.trade_id, fill.price, maker.price
+            ));
+        }
+
+        let worse: bool = match order.side {
+            OrderSide::Bid => fill.price > order.price,
+            OrderSide::Ask => fill.price < order.price,
+        };
+
+        if worse {
+            return Err(format!(
+                "Trade {} is at {}, beyond its taker's limit of {}",
+                fill.trade_id, fill.price, order.price
+            ));
+        }
+    }
+
+    /* resting orders cancelled by self-trade prevention */
+    for id in result.cancelled.iter().filter(|id| **id != order.id) {
+        let resting: &Order = before
+            .order(*id)
+            .ok_or_else(|| format!("Cancelled {:?} was not resting", id))?;
+        let filled: U256 = result
+            .fills
+            .iter()
+            .filter(|fill| fill.maker_id == *id)
+            .fold(U256::zero(), |acc, fill| acc + fill.quantity);
+        ledger.removed += resting.remaining - filled;
+    }
+
+    /* whatever remains of an incoming order that didn't rest was dropped */
+    if after.order(order.id).is_none() {
+        ledger.removed += result.remaining;
+    }
+
+    Ok(())
+}
+
+/* checks the invariants of the book, and that no quantity has gone missing */
+fn check_book(book: &Book, ledger: &Ledger) -> Result<(), String> {
+    book.validate().map_err(|violations| {
+        violations
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<String>>()
+            .join("; ")
+    })?;
+
+    let accounted: U256 = ledger.filled
+        + ledger.filled
+        + book.total_resting_quantity()
+        + ledger.removed;
+
+    if accounted != ledger.submitted {
+        return Err(format!(
+            "{} was submitted, but {} was filled (on both sides), {} is \
+             resting and {} was removed",
+            ledger.submitted,
+            ledger.filled,
+            book.total_resting_quantity(),
+            ledger.removed
+        ));
+    }
+
+    Ok(())
+}
+
+async fn run(scenario: &Scenario) -> Result<(), String> {
+    /* fixed times (and nonces) make every replay of a scenario identical */
+    let now: DateTime<Utc> = Utc.timestamp(1_600_000_000, 0);
+    let mut book: Book = Book::new(Address::zero())
+        .with_self_trade_policy(scenario.self_trade_policy)
+        .with_matching_algorithm(scenario.matching_algorithm);
+    let mut ledger: Ledger = Ledger::default();
+
+    for (step, operation) in scenario.operations.iter().enumerate() {
+        match operation {
+            Operation::Submit {
+                trader,
+                side,
+                price,
+                quantity,
+                immediate,
+            } => {
+                let mut order: Order = Order::new(
+                    Address::from_low_u64_be(*trader),
+                    Address::zero(),
+                    *side,
+                    U256::from(*price),
+                    U256::from(*quantity),
+                    now + Duration::days(1),
+                    now,
+                    vec![],
+                )
+                .with_nonce(U256::from(step));
+                if *immediate {
+                    order.time_in_force = TimeInForce::ImmediateOrCancel;
+                }
+
+                let before: Book = book.clone();
+
+                if let Ok(result) =
+                    book.submit_at(order.clone(), None, now).await
+                {
+                    check_match(&before, &book, &order, &result, &mut ledger)
+                        .map_err(|e| format!("Step {}: {}", step, e))?;
+                }
+            }
+            Operation::Cancel { pick } => {
+                let open: Vec<OrderId> = book
+                    .bids
+                    .values()
+                    .chain(book.asks.values())
+                    .flatten()
+                    .map(|order| order.id)
+                    .collect();
+
+                if !open.is_empty() {
+                    if let Ok(Some(order)) =
+                        book.cancel_at(open[pick % open.len()], now)
+                    {
+                        ledger.removed += order.remaining;
+                    }
+                }
+            }
+            Operation::CancelAll { trader } => {
+                for order in
+                    book.cancel_all_at(Address::from_low_u64_be(*trader), now)
+                {
+                    ledger.removed += order.remaining;
+                }
+            }
+        }
+
+        check_book(&book, &ledger)
+            .map_err(|e| format!("Step {} ({:?}): {}", step, operation, e))?;
+    }
+
+    Ok(())
+}
+
+/// Applies a scenario to a fresh book, checking the invariants of matching
+/// after every step and returning the first one broken
+///
+/// Scenarios are replayed deterministically, so a failing scenario (as
+/// printed by `test_matching_invariants`) can be pasted in as a regression
+/// test.
+pub fn replay(scenario: &Scenario) -> Result<(), String> {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(run(scenario))
+}
+
+fn operation() -> impl Strategy<Value = Operation> {
+    prop_oneof![
+        6 => (
+            1..=TRADERS,
+            prop_oneof![Just(OrderSide::Bid), Just(OrderSide::Ask)],
+            MIN_PRICE..=MAX_PRICE,
+            1..=MAX_QUANTITY,
+            prop::bool::weighted(0.2),
+        )
+            .prop_map(|(trader, side, price, quantity, immediate)| {
+                Operation::Submit {
+                    trader,
+                    side,
+                    price,
+                    quantity,
+                    immediate,
+                }
+            }),
+        2 => any::<usize>().prop_map(|pick| Operation::Cancel { pick }),
+        1 => (1..=TRADERS).prop_map(|trader| Operation::CancelAll { trader }),
+    ]
+}
+
+fn scenario() -> impl Strategy<Value = Scenario> {
+    (
+        prop_oneof![
+            Just(SelfTradePolicy::Skip),
+            Just(SelfTradePolicy::CancelResting),
+            Just(SelfTradePolicy::CancelIncoming),
+        ],
+        prop_oneof![
+            Just(MatchingAlgorithm::Fifo),
+            Just(MatchingAlgorithm::ProRata),
+        ],
+        prop::collection::vec(operation(), 1..=MAX_OPERATIONS),
+    )
+        .prop_map(|(self_trade_policy, matching_algorithm, operations)| {
+            Scenario {
+                self_trade_policy,
+                matching_algorithm,
+                operations,
+            }
+        })
+}
+
+#[test]
+pub fn test_matching_invariants() {
+    /* failures are reported as code rather than persisted to disk */
+    let mut runner: TestRunner = TestRunner::new(Config {
+        failure_persistence: None,
+        ..Config::default()
+    });
+
+    match runner.run(&scenario(), |generated| {
+        replay(&generated).map_err(TestCaseError::fail)
+    }) {
+        Ok(()) => {}
+        Err(TestError::Fail(reason, scenario)) => panic!(
+            "{}\n\nReproduce with:\n\n{}",
+            reason,
+            scenario.regression_test()
+        ),
+        Err(TestError::Abort(reason)) => panic!("{}", reason),
+    }
+}
+
+#[test]
+pub fn test_replay_partial_fills() {
+    /* a resting order filled in pieces by several takers, then cancelled */
+    replay(&Scenario {
+        self_trade_policy: SelfTradePolicy::Skip,
+        matching_algorithm: MatchingAlgorithm::Fifo,
+        operations: vec![
+            Operation::Submit {
+                trader: 1,
+                side: OrderSide::Ask,
+                price: 100,
+                quantity: 10,
+                immediate: false,
+            },
+            Operation::Submit {
+                trader: 2,
+                side: OrderSide::Bid,
+                price: 101,
+                quantity: 3,
+                immediate: false,
+            },
+            Operation::Submit {
+                trader: 3,
+                side: OrderSide::Bid,
+                price: 100,
+                quantity: 4,
+                immediate: true,
+            },
+            Operation::Submit {
+                trader: 1,
+                side: OrderSide::Bid,
+                price: 100,
+                quantity: 5,
+                immediate: false,
+            },
+            Operation::Cancel { pick: 0 },
+            Operation::CancelAll { trader: 1 },
+        ],
+    })
+    .unwrap();
+}
+
+#[test]
+pub fn test_regression_test_rendered() {
+    let scenario: Scenario = Scenario {
+        self_trade_policy: SelfTradePolicy::CancelResting,
+        matching_algorithm: MatchingAlgorithm::ProRata,
+        operations: vec![
+            Operation::Submit {
+                trader: 1,
+                side: OrderSide::Bid,
+                price: 99,
+                quantity: 2,
+                immediate: true,
+            },
+            Operation::Cancel { pick: 7 },
+        ],
+    };
+
+    assert_eq!(
+        scenario.regression_test(),
+        "#[test]\n\
+         pub fn test_matching_regression() {\n    \
+             replay(&Scenario {\n        \
+                 self_trade_policy: SelfTradePolicy::CancelResting,\n        \
+                 matching_algorithm: MatchingAlgorithm::ProRata,\n        \
+                 operations: vec![\n            \
+                     Operation::Submit { trader: 1, side: OrderSide::Bid, \
+                     price: 99, quantity: 2, immediate: true },\n            \
+                     Operation::Cancel { pick: 7 },\n        \
+                 ],\n    \
+             })\n    \
+             .unwrap();\n\
+         }\n"
+    );
+}
@@ -0,0 +1,191 @@
+//! Contains the OpenAPI specification of the HTTP API, generated from the
+//! handlers and the types they accept and return
+//!
+//! The specification describes the latest version of the API, so its paths
+//! are relative to the `/v2` server. It is served at `/openapi.json`, along
+//! with a Swagger UI at `/docs` (whose assets are loaded from a CDN).
+use std::convert::Infallible;
+
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+use warp::reply::{html, json};
+use warp::{Filter, Rejection, Reply};
+
+use crate::api::{
+    ApiError, BlockTraderResponse, BlocklistResponse, CancelOrdersRequest,
+    CancelOrdersResponse, CancelOutcome, CancelResult, CreateBookRequest,
+    CreateOrderRequest, CreateWebhookRequest, DepthResponse, DumpResponse,
+    ExposureResponse, MatchResponse, OmeResponse, OrderHistoryResponse,
+    OrderView, OrderViewStatus, QuoteResponse, ReloadBookResponse,
+    ReplaceOrderResponse, SnapshotResponse, StatsResponse, TickerResponse,
+    TradesResponse, UpdateBookLimitsRequest, UpdateOrderRequest,
+    UserSummaryResponse, WebhookResponse,
+};
+use crate::book::{
+    ExternalBook, ExternalFill, ExternalLastTrade, MatchingAlgorithm,
+    SelfTradePolicy,
+};
+use crate::event::{BookDelta, LevelChange};
+use crate::handler;
+use crate::history::{ExternalOrderEvent, OrderEventKind};
+use crate::order::{ExternalOrder, OrderSide, OrderType, TimeInForce};
+
+/* the Swagger UI, pointed at the specification */
+const DOCS: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>Tracer OME API</title>
+  <link rel="stylesheet"
+        href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js">
+  </script>
+  <script>
+    SwaggerUIBundle({url: "/openapi.json", dom_id: "#swagger-ui"});
+  </script>
+</body>
+</html>
+"##;
+
+/// The OpenAPI specification of the latest version of the HTTP API
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Tracer OME", description = "The Tracer matching engine"),
+    servers((url = "/v2", description = "The latest version of the API")),
+    paths(
+        handler::index_book_handler,
+        handler::create_book_handler,
+        handler::pause_book_handler,
+        handler::resume_book_handler,
+        handler::update_book_limits_handler,
+        handler::dump_state_handler,
+        handler::reload_book_handler,
+        handler::block_trader_handler,
+        handler::unblock_trader_handler,
+        handler::index_blocklist_handler,
+        handler::read_book_handler,
+        handler::read_book_snapshot_handler,
+        handler::read_depth_handler,
+        handler::read_ticker_handler,
+        handler::read_stats_handler,
+        handler::read_trades_handler,
+        handler::create_order_handler,
+        handler::quote_order_handler,
+        handler::replace_order_handler,
+        handler::read_order_handler,
+        handler::read_order_history_handler,
+        handler::read_client_order_handler,
+        handler::read_global_order_handler,
+        handler::update_order_handler,
+        handler::destroy_order_handler,
+        handler::destroy_global_order_handler,
+        handler::cancel_orders_handler,
+        handler::cancel_user_orders_handler,
+        handler::market_user_orders_handler,
+        handler::user_orders_handler,
+        handler::user_summary_handler,
+        handler::create_webhook_handler,
+        handler::index_webhook_handler,
+        handler::destroy_webhook_handler,
+        handler::ws_handler,
+    ),
+    components(schemas(
+        ApiError,
+        BlockTraderResponse,
+        BlocklistResponse,
+        BookDelta,
+        CancelOrdersRequest,
+        CancelOrdersResponse,
+        CancelOutcome,
+        CancelResult,
+        CreateBookRequest,
+        CreateOrderRequest,
+        CreateWebhookRequest,
+        DepthResponse,
+        DumpResponse,
+        ExposureResponse,
+        ExternalBook,
+        ExternalFill,
+        ExternalLastTrade,
+        ExternalOrder,
+        ExternalOrderEvent,
+        LevelChange,
+        MatchResponse,
+        MatchingAlgorithm,
+        OmeResponse,
+        OrderEventKind,
+        OrderHistoryResponse,
+        OrderSide,
+        OrderType,
+        OrderView,
+        OrderViewStatus,
+        QuoteResponse,
+        ReloadBookResponse,
+        ReplaceOrderResponse,
+        SelfTradePolicy,
+        SnapshotResponse,
+        StatsResponse,
+        TickerResponse,
+        TimeInForce,
+        TradesResponse,
+        UpdateBookLimitsRequest,
+        UpdateOrderRequest,
+        UserSummaryResponse,
+        WebhookResponse,
+    )),
+    modifiers(&AdminToken),
+    tags(
+        (name = "books", description = "Order books and their market data"),
+        (name = "orders", description = "Orders within a book"),
+        (name = "users", description = "The orders and exposure of traders"),
+        (name = "admin", description = "Operating the OME"),
+        (name = "webhooks", description = "Notifications of fills"),
+        (name = "feed", description = "The WebSocket feed of changes"),
+    )
+)]
+pub struct ApiDoc;
+
+/* admin routes expect the admin token as a bearer token */
+struct AdminToken;
+
+impl Modify for AdminToken {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "admin_token",
+                SecurityScheme::Http(
+                    HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build(),
+                ),
+            );
+        }
+    }
+}
+
+/// Returns the OpenAPI specification of the HTTP API
+pub fn spec() -> utoipa::openapi::OpenApi {
+    ApiDoc::openapi()
+}
+
+/// Returns the routes serving the specification (`GET /openapi.json`) and the
+/// Swagger UI (`GET /docs`)
+pub fn routes(
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let spec_route = warp::path!("openapi.json")
+        .and(warp::get())
+        .and_then(spec_handler);
+    let docs_route =
+        warp::path!("docs").and(warp::get()).and_then(docs_handler);
+
+    spec_route.or(docs_route)
+}
+
+async fn spec_handler() -> Result<impl Reply, Infallible> {
+    Ok(json(&spec()))
+}
+
+async fn docs_handler() -> Result<impl Reply, Infallible> {
+    Ok(html(DOCS))
+}
@@ -0,0 +1,191 @@
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+
+use serde_json::Value;
+use warp::http::StatusCode;
+
+use crate::api::CreateOrderRequest;
+use crate::openapi;
+use crate::order::{ExternalOrder, Order};
+
+fn spec() -> Value {
+    serde_json::to_value(openapi::spec()).unwrap()
+}
+
+/* every `$ref` anywhere within the value */
+fn refs(value: &Value, found: &mut Vec<String>) {
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields {
+                match (key.as_str(), field) {
+                    ("$ref", Value::String(t)) => found.push(t.clone()),
+                    _ => refs(field, found),
+                }
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|t| refs(t, found)),
+        _ => {}
+    }
+}
+
+#[test]
+pub fn test_spec_is_valid() {
+    let spec: Value = spec();
+
+    /* parses as OpenAPI 3 */
+    let parsed: openapiv3::OpenAPI =
+        serde_json::from_value(spec.clone()).unwrap();
+    assert!(parsed.openapi.starts_with("3."), "{}", parsed.openapi);
+    assert_eq!(parsed.servers[0].url, "/v2");
+
+    /* every reference resolves */
+    let mut found: Vec<String> = vec![];
+    refs(&spec, &mut found);
+    assert!(!found.is_empty());
+    for reference in found {
+        let pointer: &str = reference.strip_prefix('#').unwrap();
+        assert!(spec.pointer(pointer).is_some(), "{}", reference);
+    }
+
+    let mut operation_ids: BTreeSet<String> = BTreeSet::new();
+    for (path, item) in spec["paths"].as_object().unwrap() {
+        let templated: BTreeSet<&str> = path
+            .split('/')
+            .filter_map(|t| t.strip_prefix('{')?.strip_suffix('}'))
+            .collect();
+
+        for (method, operation) in item.as_object().unwrap() {
+            /* operations are uniquely named, and describe every response */
+            let id: String = operation["operationId"].as_str().unwrap().into();
+            assert!(operation_ids.insert(id), "{} {}", method, path);
+            assert!(!operation["responses"].as_object().unwrap().is_empty());
+
+            /* the path parameters are exactly those of the template */
+            let declared: BTreeSet<&str> = operation["parameters"]
+                .as_array()
+                .map(|t| t.as_slice())
+                .unwrap_or_default()
+                .iter()
+                .filter(|t| t["in"] == "path")
+                .map(|t| t["name"].as_str().unwrap())
+                .collect();
+            assert_eq!(declared, templated, "{} {}", method, path);
+
+            /* admin routes name a security scheme that exists */
+            if let Some(requirements) = operation["security"].as_array() {
+                for scheme in requirements
+                    .iter()
+                    .flat_map(|t| t.as_object().unwrap().keys())
+                {
+                    assert!(
+                        spec["components"]["securitySchemes"][scheme]
+                            .is_object(),
+                        "{}",
+                        scheme
+                    );
+                }
+            }
+        }
+    }
+    assert!(spec["paths"]["/book/{market}/order"]["post"].is_object());
+    assert_eq!(
+        spec["paths"]["/book"]["post"]["security"][0]["admin_token"],
+        serde_json::json!([])
+    );
+}
+
+#[test]
+pub fn test_custom_serialisations_described() {
+    let spec: Value = spec();
+    let schemas: &Value = &spec["components"]["schemas"];
+
+    /* integers are decimal strings, addresses are hex strings and times are
+     * Unix timestamps */
+    for (schema, field, kind) in &[
+        ("CreateOrderRequest", "price", "string"),
+        ("CreateOrderRequest", "amount", "string"),
+        ("CreateOrderRequest", "nonce", "string"),
+        ("CreateOrderRequest", "user", "string"),
+        ("CreateOrderRequest", "expiration", "integer"),
+        ("CreateOrderRequest", "created", "integer"),
+        ("CreateBookRequest", "market", "string"),
+        ("CreateBookRequest", "tick_size", "string"),
+        ("ExternalOrder", "id", "string"),
+        ("ExternalOrder", "price", "string"),
+        ("ExternalOrder", "amount_left", "string"),
+        ("ExternalOrder", "expiration", "string"),
+        ("ExternalBook", "market", "string"),
+        ("ApiError", "limit", "string"),
+    ] {
+        assert_eq!(
+            schemas[schema]["properties"][field]["type"],
+            Value::from(*kind),
+            "{}.{}",
+            schema,
+            field
+        );
+    }
+
+    /* optional fields are left out of what is required */
+    let required: &Value = &schemas["CreateOrderRequest"]["required"];
+    assert!(required.as_array().unwrap().contains(&Value::from("price")));
+    assert!(!required
+        .as_array()
+        .unwrap()
+        .contains(&Value::from("trigger_price")));
+}
+
+#[test]
+pub fn test_order_creation_example_round_trips() {
+    let example: Value = spec()["components"]["schemas"]["CreateOrderRequest"]
+        ["example"]
+        .clone();
+    assert!(example.is_object());
+
+    /* the example is a request the API accepts, exactly as written */
+    let request: CreateOrderRequest =
+        serde_json::from_value(example.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&request).unwrap(), example);
+
+    /* and describes a valid order */
+    let order: Order =
+        Order::try_from(ExternalOrder::from(request.clone())).unwrap();
+    assert_eq!(order.trader, request.user);
+    assert_eq!(order.price, request.price);
+    assert_eq!(order.quantity, request.amount);
+}
+
+#[tokio::test]
+pub async fn test_spec_served() {
+    let routes = openapi::routes();
+
+    let response = warp::test::request()
+        .method("GET")
+        .path("/openapi.json")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        serde_json::from_slice::<Value>(response.body()).unwrap(),
+        spec()
+    );
+
+    let response = warp::test::request()
+        .method("GET")
+        .path("/docs")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    assert!(String::from_utf8_lossy(response.body()).contains("/openapi.json"));
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/openapi.json")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+}
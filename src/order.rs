@@ -1,45 +1,174 @@
 //! Contains logic and type definitions for orders
 use std::convert::TryFrom;
 use std::fmt;
-use std::fmt::{Display, Formatter};
-use std::num::ParseIntError;
 use std::str::FromStr;
 
-use chrono::{DateTime, NaiveDateTime, ParseError, Utc};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use derive_more::Display;
 use ethabi::Token;
-use hex::FromHexError;
-use serde::{Deserialize, Serialize};
+use serde::de::{self, Unexpected, Visitor};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use thiserror::Error;
 use web3::types::{Address, H256, U256};
 
+use crate::util::{
+    parse_signed_data, parse_u256, strip_hex_prefix, AsString, Hex,
+    SIGNATURE_LEN,
+};
+
 pub type OrderId = H256;
 
+/// Maximum length (in bytes) of client-assigned order IDs
+pub const MAX_CLIENT_ORDER_ID_LEN: usize = 64;
+
 /// Represents which side of the market an order is on
 ///
 /// This type is simply an enum with two fields:
 /// - `Bid` (buy-side)
 /// - `Ask` (sell-side)
-#[derive(
-    Clone, Copy, PartialEq, Eq, Debug, Display, Serialize, Deserialize,
-)]
+///
+/// Sides are always serialized as `Bid` or `Ask`, but are also parsed from
+/// the spellings used on the contracts side (see `FromStr`).
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Display, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum OrderSide {
     Bid,
     Ask,
 }
 
+/// Parses `Bid`, `Buy`, `Long` or `0` as a bid and `Ask`, `Sell`, `Short` or
+/// `1` as an ask, regardless of case
 impl FromStr for OrderSide {
     type Err = OrderParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "Bid" | "bid" | "BID" => Ok(OrderSide::Bid),
-            "Ask" | "ask" | "ASK" => Ok(OrderSide::Ask),
+        match s.to_ascii_lowercase().as_str() {
+            "bid" | "buy" | "long" | "0" => Ok(OrderSide::Bid),
+            "ask" | "sell" | "short" | "1" => Ok(OrderSide::Ask),
             _ => Err(OrderParseError::InvalidSide),
         }
     }
 }
 
+/// Accepts the numeric sides 0 and 1 as integers as well as strings
+impl<'de> Deserialize<'de> for OrderSide {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(OrderSideVisitor)
+    }
+}
+
+struct OrderSideVisitor;
+
+impl<'de> Visitor<'de> for OrderSideVisitor {
+    type Value = OrderSide;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(ORDER_SIDES)
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<OrderSide, E> {
+        match value {
+            0 => Ok(OrderSide::Bid),
+            1 => Ok(OrderSide::Ask),
+            _ => Err(E::invalid_value(Unexpected::Unsigned(value), &self)),
+        }
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<OrderSide, E> {
+        match value {
+            0 => Ok(OrderSide::Bid),
+            1 => Ok(OrderSide::Ask),
+            _ => Err(E::invalid_value(Unexpected::Signed(value), &self)),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<OrderSide, E> {
+        OrderSide::from_str(value)
+            .map_err(|_e| E::invalid_value(Unexpected::Str(value), &self))
+    }
+}
+
+/// The sides accepted upon parsing, as listed in errors
+const ORDER_SIDES: &str = "one of Bid, Buy, Long or 0 (for bids) or Ask, \
+                           Sell, Short or 1 (for asks), in any case";
+
+/// Represents how long an order remains eligible for matching
+///
+/// - `GoodTillCancel` rests any unmatched volume in the book (the default)
+/// - `ImmediateOrCancel` matches whatever crosses and drops the remainder
+/// - `FillOrKill` either matches in its entirety or does nothing at all
+#[derive(
+    Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum TimeInForce {
+    #[default]
+    #[serde(rename = "GTC")]
+    GoodTillCancel,
+    #[serde(rename = "IOC")]
+    ImmediateOrCancel,
+    #[serde(rename = "FOK")]
+    FillOrKill,
+}
+
+impl fmt::Display for TimeInForce {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::GoodTillCancel => write!(f, "GTC"),
+            Self::ImmediateOrCancel => write!(f, "IOC"),
+            Self::FillOrKill => write!(f, "FOK"),
+        }
+    }
+}
+
+impl FromStr for TimeInForce {
+    type Err = OrderParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "GTC" | "gtc" | "" => Ok(TimeInForce::GoodTillCancel),
+            "IOC" | "ioc" => Ok(TimeInForce::ImmediateOrCancel),
+            "FOK" | "fok" => Ok(TimeInForce::FillOrKill),
+            _ => Err(OrderParseError::InvalidTimeInForce),
+        }
+    }
+}
+
+/// Represents how an order is executed
+///
+/// - `Limit` is matched (and rests) at its limit price (the default)
+/// - `Stop` waits until the last traded price reaches its trigger price and
+///   then takes liquidity at any price, dropping any unmatched remainder
+/// - `StopLimit` waits until the last traded price reaches its trigger price
+///   and then becomes a limit order at its limit price
+#[derive(
+    Clone, Copy, PartialEq, Eq, Debug, Default, Display, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum OrderType {
+    #[default]
+    Limit,
+    Stop,
+    StopLimit,
+}
+
+impl FromStr for OrderType {
+    type Err = OrderParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Limit" | "limit" | "" => Ok(OrderType::Limit),
+            "Stop" | "stop" => Ok(OrderType::Stop),
+            "StopLimit" | "stop_limit" => Ok(OrderType::StopLimit),
+            _ => Err(OrderParseError::InvalidOrderType),
+        }
+    }
+}
+
 /// Represents an actual order in the market
 ///
 /// Comprises a struct with all order fields needed for the Tracer market.
@@ -55,6 +184,16 @@ pub struct Order {
     pub expiration: DateTime<Utc>,
     pub created: DateTime<Utc>,
     pub signed_data: Vec<u8>,
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    #[serde(default)]
+    pub order_type: OrderType,
+    #[serde(default)]
+    pub trigger_price: Option<U256>, /* only for stop and stop-limit orders */
+    #[serde(default)]
+    pub client_order_id: Option<String>, /* assigned by the trader */
+    #[serde(default)]
+    pub nonce: U256, /* distinguishes otherwise identical orders */
 }
 
 impl fmt::Display for Order {
@@ -73,49 +212,68 @@ impl fmt::Display for Order {
 }
 
 /// Represents an error in interpreting a byte-level representation of an order
-#[derive(Clone, Copy, Debug, Error, Serialize, Deserialize)]
+///
+/// Errors name the (external) field at fault, so that they can be relayed to
+/// whoever submitted the order.
+#[derive(Clone, Copy, Debug, Error, PartialEq, Eq)]
 pub enum OrderParseError {
-    InvalidHexadecimal,
+    #[error("Invalid {field} (expected hexadecimal, optionally 0x-prefixed)")]
+    InvalidHexadecimal { field: &'static str },
+    #[error("Invalid side (expected {})", ORDER_SIDES)]
     InvalidSide,
-    InvalidTimestamp,
-    IntegerBounds,
-    InvalidDecimal,
+    #[error("Invalid {field} (expected a Unix timestamp in seconds)")]
+    InvalidTimestamp { field: &'static str },
+    #[error(
+        "Invalid {field} (expected a decimal or 0x-prefixed hexadecimal \
+         integer of at most 256 bits)"
+    )]
+    InvalidDecimal { field: &'static str },
+    #[error("Invalid time_in_force (expected GTC, IOC or FOK)")]
+    InvalidTimeInForce,
+    #[error("Invalid order_type (expected Limit, Stop or StopLimit)")]
+    InvalidOrderType,
+    #[error(
+        "Invalid client_order_id (longer than {} characters)",
+        MAX_CLIENT_ORDER_ID_LEN
+    )]
+    InvalidClientOrderId,
+    #[error(
+        "Invalid signed_data (expected empty or 65 bytes of hexadecimal, \
+         optionally 0x-prefixed)"
+    )]
+    InvalidSignedData,
+    #[error("Invalid expiration (expected a time after created)")]
+    ExpiryBeforeCreation,
 }
 
-impl Display for OrderParseError {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        match self {
-            Self::InvalidHexadecimal => write!(f, "Invalid hexadecimal"),
-            Self::InvalidSide => write!(f, "Invalid side"),
-            _ => write!(f, "Unknown"),
-        }
-    }
-}
+/// EIP-712 type of Tracer orders
+pub const ORDER_TYPE: &str = "Order(address maker,address market,uint256 price,uint256 amount,uint256 side,uint256 expires,uint256 created)";
 
-impl From<FromHexError> for OrderParseError {
-    fn from(_value: FromHexError) -> Self {
-        OrderParseError::InvalidHexadecimal
-    }
-}
+/// EIP-712 type of the domain Tracer orders are signed in
+pub const DOMAIN_TYPE: &str = "EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)";
 
-impl From<rustc_hex::FromHexError> for OrderParseError {
-    fn from(_value: rustc_hex::FromHexError) -> Self {
-        OrderParseError::InvalidHexadecimal
-    }
-}
+pub const DOMAIN_NAME: &str = "Tracer Protocol";
+pub const DOMAIN_VERSION: &str = "1.0";
 
-impl From<ParseError> for OrderParseError {
-    fn from(_value: ParseError) -> Self {
-        OrderParseError::InvalidTimestamp
-    }
+/// Represents the reasons the signature of an order can be rejected
+#[derive(Clone, Copy, Debug, Display, Error, PartialEq, Eq)]
+pub enum SignatureError {
+    InvalidLength,     /* signature is not 65 bytes long */
+    InvalidRecoveryId, /* v is not one of 0, 1, 27, or 28 */
+    Unrecoverable,     /* no signer can be recovered from the signature */
+    SignerMismatch,    /* signer is not the trader of the order */
 }
 
-impl From<ParseIntError> for OrderParseError {
-    fn from(_value: ParseIntError) -> Self {
-        OrderParseError::IntegerBounds
-    }
+/// Represents the reasons the timestamps of an order can be rejected upon
+/// submission
+#[derive(Clone, Copy, Debug, Display, Error, PartialEq, Eq)]
+pub enum TimestampError {
+    Expired,         /* expires no later than now */
+    CreatedInFuture, /* created further ahead of now than the clock skew */
+    CreatedTooOld,   /* created further behind now than the clock skew */
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn order_id(
     user: Address,
     target_tracer: Address,
@@ -124,7 +282,37 @@ pub fn order_id(
     amount: U256,
     expiration: DateTime<Utc>,
     created: DateTime<Utc>,
+    nonce: U256,
 ) -> OrderId {
+    let mut components: Vec<Token> = order_tokens(
+        user,
+        target_tracer,
+        side,
+        price,
+        amount,
+        expiration,
+        created,
+    );
+
+    /* orders without a nonce keep the IDs they had before nonces existed */
+    if !nonce.is_zero() {
+        components.push(Token::Uint(nonce));
+    }
+
+    web3::signing::keccak256(&ethabi::encode(&components)).into()
+}
+
+/// Returns the ABI tokens of the fields of an order, in the order they are
+/// hashed by both the order ID and the EIP-712 order type
+fn order_tokens(
+    user: Address,
+    target_tracer: Address,
+    side: OrderSide,
+    price: U256,
+    amount: U256,
+    expiration: DateTime<Utc>,
+    created: DateTime<Utc>,
+) -> Vec<Token> {
     /* handle indirect conversions */
     let side_num: U256 = U256::from(match side {
         OrderSide::Bid => 0u8,
@@ -133,7 +321,7 @@ pub fn order_id(
     let expiration_timestamp: U256 = U256::from(expiration.timestamp());
     let created_timestamp: U256 = U256::from(created.timestamp());
 
-    let components: Vec<Token> = vec![
+    vec![
         Token::Address(user),
         Token::Address(target_tracer),
         Token::Uint(price),
@@ -141,9 +329,7 @@ pub fn order_id(
         Token::Uint(side_num),
         Token::Uint(expiration_timestamp),
         Token::Uint(created_timestamp),
-    ];
-
-    web3::signing::keccak256(&ethabi::encode(&components)).into()
+    ]
 }
 
 impl Order {
@@ -163,7 +349,14 @@ impl Order {
         signed_data: Vec<u8>,
     ) -> Self {
         let id: OrderId = order_id(
-            trader, market, side, price, quantity, expiration, created,
+            trader,
+            market,
+            side,
+            price,
+            quantity,
+            expiration,
+            created,
+            U256::zero(),
         );
 
         Self {
@@ -177,11 +370,151 @@ impl Order {
             expiration,
             created,
             signed_data,
+            time_in_force: TimeInForce::default(),
+            order_type: OrderType::default(),
+            trigger_price: None,
+            client_order_id: None,
+            nonce: U256::zero(),
+        }
+    }
+
+    /// Returns whether this order has expired as of the provided time
+    pub fn expired(&self, now: DateTime<Utc>) -> bool {
+        self.expiration <= now
+    }
+
+    /// Checks the timestamps of this order against the provided time, as of
+    /// its submission (see `check_timestamps`)
+    pub fn check_timestamps(
+        &self,
+        now: DateTime<Utc>,
+        max_clock_skew: Duration,
+    ) -> Result<(), TimestampError> {
+        check_timestamps(self.expiration, self.created, now, max_clock_skew)
+    }
+
+    /// Sets the nonce of this order, recomputing its ID
+    ///
+    /// Orders default to a nonce of zero upon construction. Traders give
+    /// otherwise identical orders distinct nonces to tell them apart.
+    pub fn with_nonce(mut self, nonce: U256) -> Self {
+        self.nonce = nonce;
+        self.id = order_id(
+            self.trader,
+            self.market,
+            self.side,
+            self.price,
+            self.quantity,
+            self.expiration,
+            self.created,
+            nonce,
+        );
+        self
+    }
+
+    /// Sets the time-in-force policy of this order
+    ///
+    /// Orders default to good-till-cancel upon construction.
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    /// Makes this order a stop (or stop-limit) order with the provided
+    /// trigger price
+    ///
+    /// Orders default to limit orders (without a trigger price) upon
+    /// construction.
+    pub fn with_trigger(
+        mut self,
+        order_type: OrderType,
+        trigger_price: U256,
+    ) -> Self {
+        self.order_type = order_type;
+        self.trigger_price = Some(trigger_price);
+        self
+    }
+
+    /// Tags this order with an ID assigned by its trader
+    ///
+    /// Orders carry no client order ID upon construction.
+    pub fn with_client_order_id(mut self, client_order_id: String) -> Self {
+        self.client_order_id = Some(client_order_id);
+        self
+    }
+
+    /// Returns whether this order is a stop (or stop-limit) order
+    pub fn is_stop(&self) -> bool {
+        self.order_type != OrderType::Limit
+    }
+
+    /// Returns the EIP-712 digest of this order that traders sign, within the
+    /// domain of its market on the given chain
+    pub fn signing_hash(&self, chain_id: u64) -> H256 {
+        let keccak = web3::signing::keccak256;
+        let domain_separator: [u8; 32] = keccak(&ethabi::encode(&[
+            Token::FixedBytes(keccak(DOMAIN_TYPE.as_bytes()).to_vec()),
+            Token::FixedBytes(keccak(DOMAIN_NAME.as_bytes()).to_vec()),
+            Token::FixedBytes(keccak(DOMAIN_VERSION.as_bytes()).to_vec()),
+            Token::Uint(U256::from(chain_id)),
+            Token::Address(self.market),
+        ]));
+
+        let mut components: Vec<Token> =
+            vec![Token::FixedBytes(keccak(ORDER_TYPE.as_bytes()).to_vec())];
+        components.extend(order_tokens(
+            self.trader,
+            self.market,
+            self.side,
+            self.price,
+            self.quantity,
+            self.expiration,
+            self.created,
+        ));
+        let struct_hash: [u8; 32] = keccak(&ethabi::encode(&components));
+
+        let mut message: Vec<u8> = vec![0x19, 0x01];
+        message.extend_from_slice(&domain_separator);
+        message.extend_from_slice(&struct_hash);
+        keccak(&message).into()
+    }
+
+    /// Checks that the signed data of this order is an EIP-712 signature of
+    /// the order by its trader
+    ///
+    /// The signature must be 65 bytes long, consisting of `r`, `s`, and then
+    /// `v` (either 0 or 1, or 27 or 28 in Electrum notation).
+    pub fn verify_signature(
+        &self,
+        chain_id: u64,
+    ) -> Result<(), SignatureError> {
+        if self.signed_data.len() != SIGNATURE_LEN {
+            return Err(SignatureError::InvalidLength);
+        }
+
+        let recovery_id: i32 = match self.signed_data[64] {
+            v @ 0..=1 => v as i32,
+            v @ 27..=28 => v as i32 - 27,
+            _ => return Err(SignatureError::InvalidRecoveryId),
+        };
+
+        let signer: Address = web3::signing::recover(
+            self.signing_hash(chain_id).as_bytes(),
+            &self.signed_data[..64],
+            recovery_id,
+        )
+        .map_err(|_e| SignatureError::Unrecoverable)?;
+
+        if signer != self.trader {
+            return Err(SignatureError::SignerMismatch);
         }
+
+        Ok(())
     }
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct ExternalOrder {
     pub id: String,
     pub user: String,
@@ -193,6 +526,16 @@ pub struct ExternalOrder {
     pub expiration: String,
     pub created: String,
     pub signed_data: String,
+    #[serde(default)]
+    pub time_in_force: String,
+    #[serde(default)]
+    pub order_type: String,
+    #[serde(default)]
+    pub trigger_price: String, /* empty if there is no trigger price */
+    #[serde(default)]
+    pub client_order_id: String, /* empty if there is no client order ID */
+    #[serde(default)]
+    pub nonce: String, /* zero, if empty */
 }
 
 impl From<Order> for ExternalOrder {
@@ -211,71 +554,203 @@ impl From<Order> for ExternalOrder {
             expiration: value.expiration.timestamp().to_string(),
             created: value.created.timestamp().to_string(),
             signed_data: "0x".to_string() + &hex::encode(value.signed_data),
+            time_in_force: value.time_in_force.to_string(),
+            order_type: value.order_type.to_string(),
+            trigger_price: value
+                .trigger_price
+                .map(|t| t.to_string())
+                .unwrap_or_default(),
+            client_order_id: value.client_order_id.unwrap_or_default(),
+            nonce: value.nonce.to_string(),
         }
     }
 }
 
+/// Borrows an order to serialize it exactly as its `ExternalOrder` would be,
+/// without converting it first
+pub struct OrderSnapshot<'a>(pub &'a Order);
+
+impl Serialize for OrderSnapshot<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let order: &Order = self.0;
+        let mut state = serializer.serialize_struct("ExternalOrder", 15)?;
+        state.serialize_field("id", &AsString(Hex(order.id.as_bytes())))?;
+        state
+            .serialize_field("user", &AsString(Hex(order.trader.as_bytes())))?;
+        state.serialize_field(
+            "target_tracer",
+            &AsString(Hex(order.market.as_bytes())),
+        )?;
+        state.serialize_field("side", &AsString(order.side))?;
+        state.serialize_field("price", &AsString(order.price))?;
+        state.serialize_field("amount", &AsString(order.quantity))?;
+        state.serialize_field("amount_left", &AsString(order.remaining))?;
+        state.serialize_field(
+            "expiration",
+            &AsString(order.expiration.timestamp()),
+        )?;
+        state
+            .serialize_field("created", &AsString(order.created.timestamp()))?;
+        state.serialize_field(
+            "signed_data",
+            &AsString(Hex(&order.signed_data)),
+        )?;
+        state
+            .serialize_field("time_in_force", &AsString(order.time_in_force))?;
+        state.serialize_field("order_type", &AsString(order.order_type))?;
+        match &order.trigger_price {
+            Some(t) => state.serialize_field("trigger_price", &AsString(t))?,
+            None => state.serialize_field("trigger_price", "")?,
+        }
+        state.serialize_field(
+            "client_order_id",
+            order.client_order_id.as_deref().unwrap_or_default(),
+        )?;
+        state.serialize_field("nonce", &AsString(order.nonce))?;
+        state.end()
+    }
+}
+
+/// Checks the timestamps of an order against the provided time, as of its
+/// submission
+///
+/// The order must not have expired, and must have been created within
+/// `max_clock_skew` of `now` (either side, inclusive), allowing for the clocks
+/// of traders to differ from that of the OME. This needs nothing but the
+/// timestamps, so that submissions can be checked before they are parsed.
+pub fn check_timestamps(
+    expiration: DateTime<Utc>,
+    created: DateTime<Utc>,
+    now: DateTime<Utc>,
+    max_clock_skew: Duration,
+) -> Result<(), TimestampError> {
+    /* a skew beyond the range of timestamps bounds nothing */
+    let latest: Option<DateTime<Utc>> = now.checked_add_signed(max_clock_skew);
+    let earliest: Option<DateTime<Utc>> =
+        now.checked_sub_signed(max_clock_skew);
+
+    if expiration <= now {
+        Err(TimestampError::Expired)
+    } else if latest.is_some_and(|t| created > t) {
+        Err(TimestampError::CreatedInFuture)
+    } else if earliest.is_some_and(|t| created < t) {
+        Err(TimestampError::CreatedTooOld)
+    } else {
+        Ok(())
+    }
+}
+
+/// Parses an address in an external order (or book), which may or may not
+/// carry a 0x prefix
+pub fn parse_address(
+    value: &str,
+    field: &'static str,
+) -> Result<Address, OrderParseError> {
+    Address::from_str(strip_hex_prefix(value))
+        .map_err(|_e| OrderParseError::InvalidHexadecimal { field })
+}
+
+/// Parses a decimal (or hexadecimal) integer in an external order (or book)
+pub fn parse_decimal(
+    value: &str,
+    field: &'static str,
+) -> Result<U256, OrderParseError> {
+    parse_u256(value).ok_or(OrderParseError::InvalidDecimal { field })
+}
+
+/// Parses a Unix timestamp (in seconds) in an external order (or book)
+///
+/// Timestamps before the epoch are refused, as they can't be hashed into the
+/// ID of an order (see `order_tokens`).
+pub fn parse_timestamp(
+    value: &str,
+    field: &'static str,
+) -> Result<DateTime<Utc>, OrderParseError> {
+    value
+        .parse::<i64>()
+        .ok()
+        .filter(|t| *t >= 0)
+        .and_then(|t| NaiveDateTime::from_timestamp_opt(t, 0))
+        .map(|t| DateTime::from_utc(t, Utc))
+        .ok_or(OrderParseError::InvalidTimestamp { field })
+}
+
 impl TryFrom<ExternalOrder> for Order {
     type Error = OrderParseError;
 
     fn try_from(value: ExternalOrder) -> Result<Self, Self::Error> {
-        let trader: Address = match Address::from_str(&value.user) {
-            Ok(t) => t,
-            Err(e) => return Err(e.into()),
-        };
+        /* hexadecimal fields may or may not carry a 0x prefix */
+        let trader: Address = parse_address(&value.user, "user")?;
+        let market: Address =
+            parse_address(&value.target_tracer, "target_tracer")?;
+        let side: OrderSide = OrderSide::from_str(&value.side)?;
+        let price: U256 = parse_decimal(&value.price, "price")?;
+        let quantity: U256 = parse_decimal(&value.amount, "amount")?;
+        let remaining: U256 = parse_decimal(&value.amount_left, "amount_left")?;
+        let expiration: DateTime<Utc> =
+            parse_timestamp(&value.expiration, "expiration")?;
+        let created: DateTime<Utc> =
+            parse_timestamp(&value.created, "created")?;
+
+        if expiration <= created {
+            return Err(OrderParseError::ExpiryBeforeCreation);
+        }
 
-        let market: Address = match Address::from_str(&value.target_tracer) {
-            Ok(t) => t,
-            Err(e) => return Err(e.into()),
-        };
+        /* an empty signature (i.e., "" or "0x") is an empty vector */
+        let signed_data: Vec<u8> = parse_signed_data(&value.signed_data)
+            .ok_or(OrderParseError::InvalidSignedData)?;
 
-        let side: OrderSide = match OrderSide::from_str(&value.side) {
-            Ok(t) => t,
-            Err(e) => return Err(e),
-        };
+        let time_in_force: TimeInForce =
+            TimeInForce::from_str(&value.time_in_force)?;
 
-        let price: U256 = match U256::from_dec_str(&value.price) {
-            Ok(t) => t,
-            Err(_e) => return Err(OrderParseError::InvalidDecimal),
-        };
+        let order_type: OrderType = OrderType::from_str(&value.order_type)?;
 
-        let quantity: U256 = match U256::from_dec_str(&value.amount) {
-            Ok(t) => t,
-            Err(_e) => return Err(OrderParseError::InvalidDecimal),
+        let trigger_price: Option<U256> = match value.trigger_price.as_str() {
+            "" => None,
+            t => Some(parse_decimal(t, "trigger_price")?),
         };
 
-        let remaining: U256 = match U256::from_dec_str(&value.amount_left) {
-            Ok(t) => t,
-            Err(_e) => return Err(OrderParseError::InvalidDecimal),
+        let client_order_id: Option<String> = match value.client_order_id.len()
+        {
+            0 => None,
+            1..=MAX_CLIENT_ORDER_ID_LEN => Some(value.client_order_id),
+            _ => return Err(OrderParseError::InvalidClientOrderId),
         };
 
-        let expiration: DateTime<Utc> = {
-            let timestamp: i64 = match value.expiration.parse::<i64>() {
-                Ok(t) => t,
-                Err(_e) => return Err(OrderParseError::InvalidTimestamp),
-            };
-
-            DateTime::from_utc(NaiveDateTime::from_timestamp(timestamp, 0), Utc)
+        let nonce: U256 = match value.nonce.as_str() {
+            "" => U256::zero(),
+            t => parse_decimal(t, "nonce")?,
         };
 
-        let created: DateTime<Utc> = {
-            let timestamp: i64 = match value.created.parse::<i64>() {
-                Ok(t) => t,
-                Err(_e) => return Err(OrderParseError::InvalidTimestamp),
-            };
-
-            DateTime::from_utc(NaiveDateTime::from_timestamp(timestamp, 0), Utc)
-        };
+        let computed_id: OrderId = order_id(
+            trader, market, side, price, quantity, expiration, created, nonce,
+        );
 
-        let signed_data: Vec<u8> = match hex::decode(&value.signed_data) {
-            Ok(t) => t,
-            Err(e) => return Err(e.into()),
+        /* keep the ID clients already hold, only computing absent ones */
+        let id: OrderId = match strip_hex_prefix(&value.id) {
+            "" => computed_id,
+            t => match OrderId::from_str(t) {
+                Ok(t) if t.is_zero() => computed_id,
+                Ok(t) => {
+                    if t != computed_id {
+                        warn!(
+                            "Order ID {:?} differs from computed ID {:?}",
+                            t, computed_id
+                        );
+                    }
+                    t
+                }
+                Err(_e) => {
+                    return Err(OrderParseError::InvalidHexadecimal {
+                        field: "id",
+                    })
+                }
+            },
         };
 
-        let id: OrderId = order_id(
-            trader, market, side, price, quantity, expiration, created,
-        );
-
         Ok(Self {
             id,
             trader,
@@ -287,6 +762,11 @@ impl TryFrom<ExternalOrder> for Order {
             expiration,
             created,
             signed_data,
+            time_in_force,
+            order_type,
+            trigger_price,
+            client_order_id,
+            nonce,
         })
     }
 }
@@ -0,0 +1,172 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use ethereum_types::{Address, U256};
+use tokio::sync::broadcast::{self, Sender};
+
+use crate::book::Fill;
+use crate::event::publisher::{self, EventSink, PublisherMetrics, SinkError};
+use crate::event::{BookEvent, BookEventKind, EVENT_CAPACITY};
+use crate::order::{Order, OrderId, OrderSide};
+
+/* a sink that records the sequence numbers of the events it accepts,
+ * refusing every event while `full` is set and every event whose sequence
+ * number is in `failing` */
+#[derive(Clone, Default)]
+struct MockSink {
+    accepted: Arc<Mutex<Vec<u64>>>,
+    attempts: Arc<AtomicUsize>,
+    full: Arc<AtomicBool>,
+    failing: Vec<u64>,
+}
+
+impl EventSink for MockSink {
+    fn publish(&mut self, event: &BookEvent) -> Result<(), SinkError> {
+        self.attempts.fetch_add(1, Ordering::SeqCst);
+
+        if self.full.load(Ordering::SeqCst) {
+            Err(SinkError::Full)
+        } else if self.failing.contains(&event.sequence) {
+            Err(SinkError::Failed("broker unreachable".to_string()))
+        } else {
+            self.accepted.lock().unwrap().push(event.sequence);
+            Ok(())
+        }
+    }
+}
+
+/* waits (for up to five seconds) until the condition holds */
+async fn wait_until<F: Fn() -> bool>(condition: F) {
+    for _ in 0..100 {
+        if condition() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+fn event(sequence: u64) -> BookEvent {
+    let order: Order = Order::new(
+        Address::from_low_u64_be(2),
+        Address::from_low_u64_be(1),
+        OrderSide::Bid,
+        U256::from(100),
+        U256::from(10),
+        Utc::now() + chrono::Duration::days(365),
+        Utc::now(),
+        vec![],
+    );
+
+    BookEvent::new(
+        Address::from_low_u64_be(1),
+        sequence,
+        BookEventKind::Added(order),
+    )
+}
+
+fn spawn(sink: MockSink) -> (Sender<BookEvent>, Arc<PublisherMetrics>) {
+    let (sender, events) = broadcast::channel(EVENT_CAPACITY);
+    let metrics: Arc<PublisherMetrics> = Arc::new(PublisherMetrics::default());
+    tokio::spawn(publisher::run(sink, events, metrics.clone()));
+    (sender, metrics)
+}
+
+#[test]
+pub fn test_topics_and_keys() {
+    let added: BookEvent = event(1);
+    assert_eq!(publisher::topic("ome", &added), "ome.orders");
+    assert_eq!(
+        publisher::key(&added),
+        "0x0000000000000000000000000000000000000001"
+    );
+
+    let fill: BookEvent = BookEvent {
+        kind: BookEventKind::Fill {
+            fill: Fill {
+                trade_id: 1,
+                maker_id: OrderId::from_low_u64_be(1),
+                taker_id: OrderId::from_low_u64_be(2),
+                maker_address: Address::from_low_u64_be(2),
+                taker_address: Address::from_low_u64_be(3),
+                price: U256::from(100),
+                quantity: U256::from(10),
+                timestamp: Utc::now(),
+                maker_client_order_id: None,
+                taker_client_order_id: None,
+                maker_fee: U256::zero(),
+                taker_fee: U256::zero(),
+            },
+            maker_remaining: U256::zero(),
+            taker_remaining: U256::zero(),
+        },
+        ..event(2)
+    };
+    assert_eq!(publisher::topic("staging", &fill), "staging.fills");
+}
+
+#[test]
+pub fn test_metrics_render() {
+    let metrics: PublisherMetrics = PublisherMetrics::default();
+    metrics.record_failure();
+
+    let rendered: String = metrics.render();
+    assert!(rendered.contains("# TYPE ome_events_published_total counter\n"));
+    assert!(rendered.contains("\nome_events_published_total 0\n"));
+    assert!(rendered.contains("\nome_events_dropped_total 0\n"));
+    assert!(rendered.contains("\nome_events_failed_total 1\n"));
+}
+
+#[tokio::test]
+pub async fn test_events_published_in_order() {
+    let sink: MockSink = MockSink {
+        failing: vec![2],
+        ..MockSink::default()
+    };
+    let accepted = sink.accepted.clone();
+    let (sender, metrics) = spawn(sink);
+
+    for sequence in 1..=3 {
+        sender.send(event(sequence)).unwrap();
+    }
+
+    wait_until(|| metrics.published() + metrics.failed() == 3).await;
+    assert_eq!(*accepted.lock().unwrap(), vec![1, 3]);
+    assert_eq!(metrics.published(), 2);
+    assert_eq!(metrics.failed(), 1);
+    assert_eq!(metrics.dropped(), 0);
+}
+
+#[tokio::test]
+pub async fn test_full_sink_retried_then_oldest_dropped() {
+    let sink: MockSink = MockSink::default();
+    sink.full.store(true, Ordering::SeqCst);
+    let (accepted, attempts, full) = (
+        sink.accepted.clone(),
+        sink.attempts.clone(),
+        sink.full.clone(),
+    );
+    let (sender, metrics) = spawn(sink);
+
+    /* the first event is held by the publisher while the sink is full... */
+    sender.send(event(1)).unwrap();
+    wait_until(|| attempts.load(Ordering::SeqCst) >= 2).await;
+    assert!(attempts.load(Ordering::SeqCst) >= 2);
+
+    /* ...so the buffer overflows, losing the oldest of those behind it */
+    let overflow: u64 = 10;
+    for sequence in 2..(2 + EVENT_CAPACITY as u64 + overflow) {
+        sender.send(event(sequence)).unwrap();
+    }
+
+    full.store(false, Ordering::SeqCst);
+    wait_until(|| metrics.published() == 1 + EVENT_CAPACITY as u64).await;
+
+    let accepted = accepted.lock().unwrap();
+    assert_eq!(accepted.len(), 1 + EVENT_CAPACITY);
+    assert_eq!(accepted[0], 1);
+    assert_eq!(accepted[1], 2 + overflow);
+    assert_eq!(metrics.dropped(), overflow);
+    assert_eq!(metrics.failed(), 0);
+}
@@ -0,0 +1,230 @@
+//! Contains logic for replaying recorded order flow through the matching
+//! engine, e.g., to evaluate changes to the parameters of a book
+//!
+//! Recorded flow is read as JSON lines, each either an order (an
+//! `ExternalOrder` tagged `"type": "order"`, submitted as of its `created`
+//! time) or a cancellation:
+//!
+//! ```json
+//! {"type": "cancel", "id": "0x...", "timestamp": "1600000000"}
+//! ```
+//!
+//! Records are replayed in timestamp order, with ties kept in the order they
+//! were read. Nothing depends on the wall clock, so replaying the same flow
+//! always produces exactly the same output.
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::Path;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use ethereum_types::U256;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::book::{Book, ExternalBook, ExternalFill, Fill};
+use crate::order::{ExternalOrder, Order, OrderId, OrderParseError};
+use crate::util::{from_hex_opt_se, from_hex_se};
+
+/// Represents a single recorded event of the order flow
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(clippy::large_enum_variant)]
+pub enum ReplayRecord {
+    Order(Order),
+    Cancel {
+        id: OrderId,
+        timestamp: DateTime<Utc>,
+    },
+}
+
+/* the form records are read in */
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
+enum WireRecord {
+    Order(ExternalOrder),
+    Cancel { id: OrderId, timestamp: String },
+}
+
+impl ReplayRecord {
+    /// Returns the time at which the record is replayed
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        match self {
+            ReplayRecord::Order(order) => order.created,
+            ReplayRecord::Cancel { timestamp, .. } => *timestamp,
+        }
+    }
+}
+
+/// Represents the reasons recorded order flow cannot be read
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("Failed to read records: {0}")]
+    Read(#[from] io::Error),
+    #[error("Line {0}: {1}")]
+    Malformed(usize, serde_json::Error),
+    #[error("Line {0}: {1}")]
+    InvalidOrder(usize, OrderParseError),
+    #[error(
+        "Line {0}: Invalid timestamp (expected a Unix timestamp in seconds)"
+    )]
+    InvalidTimestamp(usize),
+}
+
+/// Summarises a replay of recorded order flow
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ReplaySummary {
+    pub orders: u64,    /* orders submitted */
+    pub rejected: u64,  /* orders refused by the book */
+    pub cancelled: u64, /* open orders cancelled */
+    pub trades: u64,
+    #[serde(serialize_with = "from_hex_se")]
+    pub volume: U256, /* total quantity traded */
+    #[serde(serialize_with = "from_hex_opt_se")]
+    pub average_spread: Option<U256>, /* over the samples, rounded down */
+    pub spread_samples: u64, /* minutes at which both sides were quoted */
+}
+
+/// Represents the outcome of replaying recorded order flow
+#[derive(Clone, Debug)]
+pub struct Replay {
+    pub fills: Vec<Fill>, /* in the order they were made */
+    pub book: Book,       /* as left by the last record */
+    pub summary: ReplaySummary,
+}
+
+/// Reads recorded order flow from JSON lines (see the module documentation),
+/// returning the records in the order they are to be replayed
+///
+/// Blank lines are skipped.
+pub fn read_records<R: BufRead>(
+    reader: R,
+) -> Result<Vec<ReplayRecord>, ReplayError> {
+    let mut records: Vec<ReplayRecord> = vec![];
+
+    for (index, line) in reader.lines().enumerate() {
+        let line: String = line?;
+        let number: usize = index + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: ReplayRecord = match serde_json::from_str(&line)
+            .map_err(|e| ReplayError::Malformed(number, e))?
+        {
+            WireRecord::Order(order) => ReplayRecord::Order(
+                Order::try_from(order)
+                    .map_err(|e| ReplayError::InvalidOrder(number, e))?,
+            ),
+            WireRecord::Cancel { id, timestamp } => ReplayRecord::Cancel {
+                id,
+                timestamp: timestamp
+                    .parse::<i64>()
+                    .ok()
+                    .and_then(|t| Utc.timestamp_opt(t, 0).single())
+                    .ok_or(ReplayError::InvalidTimestamp(number))?,
+            },
+        };
+        records.push(record);
+    }
+
+    /* a stable sort, so that ties keep the order they were recorded in */
+    records.sort_by_key(ReplayRecord::timestamp);
+    Ok(records)
+}
+
+/// Replays the provided records (in the order given) against the book
+///
+/// The spread is sampled at the start of every minute from the first record
+/// to the last, and is only counted while both sides of the book are quoted.
+pub async fn replay(mut book: Book, records: Vec<ReplayRecord>) -> Replay {
+    let mut fills: Vec<Fill> = vec![];
+    let mut summary: ReplaySummary = ReplaySummary::default();
+    let mut total_spread: U256 = U256::zero();
+    let mut next_sample: Option<DateTime<Utc>> = None;
+
+    for record in records {
+        let timestamp: DateTime<Utc> = record.timestamp();
+
+        let mut sample: DateTime<Utc> =
+            next_sample.unwrap_or_else(|| minute_from(timestamp));
+        while sample <= timestamp {
+            if let (Some(bid), Some(ask)) = book.top() {
+                if ask > bid {
+                    total_spread = total_spread.saturating_add(ask - bid);
+                    summary.spread_samples += 1;
+                }
+            }
+            sample += Duration::minutes(1);
+        }
+        next_sample = Some(sample);
+
+        match record {
+            ReplayRecord::Order(order) => {
+                summary.orders += 1;
+                match book.submit_at(order, None, timestamp).await {
+                    Ok(result) => {
+                        /* along with those of any stops the order triggered */
+                        fills.extend(result.fills);
+                        fills.extend(result.triggered_fills);
+                    }
+                    Err(_e) => summary.rejected += 1,
+                }
+            }
+            ReplayRecord::Cancel { id, timestamp } => {
+                if let Ok(Some(_)) = book.cancel_at(id, timestamp) {
+                    summary.cancelled += 1;
+                }
+            }
+        }
+    }
+
+    summary.trades = fills.len() as u64;
+    summary.volume = fills
+        .iter()
+        .fold(U256::zero(), |acc, fill| acc.saturating_add(fill.quantity));
+    if summary.spread_samples > 0 {
+        summary.average_spread =
+            Some(total_spread / U256::from(summary.spread_samples));
+    }
+
+    Replay {
+        fills,
+        book,
+        summary,
+    }
+}
+
+/* the first whole minute at or after the given time */
+fn minute_from(time: DateTime<Utc>) -> DateTime<Utc> {
+    let seconds: i64 = time.timestamp();
+    Utc.timestamp_opt(seconds + (60 - seconds.rem_euclid(60)) % 60, 0)
+        .unwrap()
+}
+
+impl Replay {
+    /// Writes the outcome of the replay to the given directory (creating it
+    /// if need be): the fills to `fills.jsonl`, the final state of the book
+    /// to `book.json`, and the summary to `summary.json`
+    pub fn write(&self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+
+        let mut fills = BufWriter::new(File::create(dir.join("fills.jsonl"))?);
+        for fill in &self.fills {
+            serde_json::to_writer(
+                &mut fills,
+                &ExternalFill::from(fill.clone()),
+            )?;
+            fills.write_all(b"\n")?;
+        }
+        fills.flush()?;
+
+        let book: ExternalBook = self.book.clone().into();
+        fs::write(dir.join("book.json"), serde_json::to_vec_pretty(&book)?)?;
+        fs::write(
+            dir.join("summary.json"),
+            serde_json::to_vec_pretty(&self.summary)?,
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+use std::fs;
+use std::path::PathBuf;
+
+use ethereum_types::{Address, U256};
+
+use crate::book::Book;
+use crate::order::{OrderId, OrderSide};
+use crate::replay::{self, Replay, ReplayError, ReplayRecord, ReplaySummary};
+
+const FIXTURE: &str = include_str!("../tests/fixtures/replay.jsonl");
+
+async fn replay_fixture() -> Replay {
+    let records: Vec<ReplayRecord> =
+        replay::read_records(FIXTURE.as_bytes()).unwrap();
+    replay::replay(Book::new(Address::from_low_u64_be(1)), records).await
+}
+
+#[test]
+pub fn test_records_read_in_timestamp_order() {
+    let records: Vec<ReplayRecord> =
+        replay::read_records(FIXTURE.as_bytes()).unwrap();
+    assert_eq!(records.len(), 10);
+    assert!(records
+        .windows(2)
+        .all(|t| t[0].timestamp() <= t[1].timestamp()));
+    assert_eq!(
+        records[4],
+        ReplayRecord::Cancel {
+            id: OrderId::from_low_u64_be(2),
+            timestamp: records[4].timestamp(),
+        }
+    );
+
+    match replay::read_records(&b"\n{\"type\": \"cancel\"}\n"[..]) {
+        Err(ReplayError::Malformed(2, _)) => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+    match replay::read_records(
+        &br#"{"type": "cancel", "id": "0x0000000000000000000000000000000000000000000000000000000000000001", "timestamp": "soon"}"#[..],
+    ) {
+        Err(ReplayError::InvalidTimestamp(1)) => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+}
+
+#[tokio::test]
+pub async fn test_replay_summary() {
+    let replay: Replay = replay_fixture().await;
+
+    /* the order for another market is refused, the stop is triggered by the
+     * trade at 102 (and lifts the ask at 104), and the spread is sampled at
+     * each minute: 10, 10, not at all (no asks), then 2 */
+    assert_eq!(
+        replay.summary,
+        ReplaySummary {
+            orders: 9,
+            rejected: 1,
+            cancelled: 1,
+            trades: 4,
+            volume: U256::from(14),
+            average_spread: Some(U256::from(7)),
+            spread_samples: 3,
+        }
+    );
+    assert_eq!(
+        replay
+            .fills
+            .iter()
+            .map(|t| (t.maker_id, t.price, t.quantity))
+            .collect::<Vec<_>>(),
+        vec![
+            (OrderId::from_low_u64_be(3), U256::from(105), U256::from(5)),
+            (OrderId::from_low_u64_be(1), U256::from(100), U256::from(3)),
+            (OrderId::from_low_u64_be(5), U256::from(102), U256::from(4)),
+            (OrderId::from_low_u64_be(8), U256::from(104), U256::from(2)),
+        ]
+    );
+    assert_eq!(replay.book.top(), (Some(U256::from(100)), None));
+    assert_eq!(replay.book.depth(), (1, 0));
+    assert_eq!(
+        replay.book.order(OrderId::from_low_u64_be(1)).unwrap().side,
+        OrderSide::Bid
+    );
+}
+
+#[tokio::test]
+pub async fn test_replay_deterministic() {
+    let dir: PathBuf =
+        std::env::temp_dir().join(format!("ome-replay-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    replay_fixture().await.write(&dir.join("first")).unwrap();
+    replay_fixture().await.write(&dir.join("second")).unwrap();
+
+    for name in &["fills.jsonl", "book.json", "summary.json"] {
+        assert_eq!(
+            fs::read(dir.join("first").join(name)).unwrap(),
+            fs::read(dir.join("second").join(name)).unwrap(),
+            "{}",
+            name
+        );
+    }
+    assert_eq!(
+        fs::read_to_string(dir.join("first").join("fills.jsonl"))
+            .unwrap()
+            .lines()
+            .count(),
+        4
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
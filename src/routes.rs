@@ -0,0 +1,463 @@
+//! Contains the routes of the HTTP API, which is served once per version
+//!
+//! Every version is built from the same handlers and filters, sharing the
+//! rate limiter and the rest of `RouteContext`, so that the versions differ
+//! only in the shapes of some responses (see `ApiVersion`).
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::RwLock;
+use warp::{Filter, Rejection, Reply};
+use web3::types::Address;
+
+use crate::api::{self, ApiVersion};
+use crate::compression;
+use crate::executioner::Executioner;
+use crate::handler;
+use crate::limit::{self, RateLimiter};
+use crate::order::OrderId;
+use crate::rpc::RpcClient;
+use crate::state::{OmeState, Readiness};
+
+/// Represents what the routes of every version of the API share, besides the
+/// engine state
+#[derive(Clone)]
+pub struct RouteContext {
+    pub readiness: Arc<Readiness>,
+    pub executioner: Executioner,
+    pub markets_client: Option<RpcClient>, /* the source of known markets */
+    pub limiter: Arc<RateLimiter>, /* shared, so versions share the limits */
+    pub admin_token: Option<String>,
+    pub dumpfile_path: PathBuf,
+    pub max_body_size: u64,
+    pub max_clock_skew: Duration,
+    pub signature_chain_id: Option<u64>, /* if signatures are verified */
+    pub compress: bool,
+}
+
+/// Counts the requests served by the unversioned paths of the API, which are
+/// deprecated
+#[derive(Debug, Default)]
+pub struct LegacyRouteMetrics {
+    requests: AtomicU64,
+}
+
+impl LegacyRouteMetrics {
+    /// Returns the number of requests served by the unversioned paths
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    /// Counts a request, warning of the deprecation upon the first
+    pub fn record(&self) {
+        if self.requests.fetch_add(1, Ordering::Relaxed) == 0 {
+            warn!(
+                "Serving a request on an unversioned path, which is \
+                 deprecated (use /v1 or /v2)"
+            );
+        }
+    }
+
+    /// Renders the counter in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        format!(
+            "# HELP ome_legacy_route_requests Requests served by the \
+             deprecated unversioned paths\n\
+             # TYPE ome_legacy_route_requests counter\n\
+             ome_legacy_route_requests {}\n",
+            self.requests()
+        )
+    }
+}
+
+/// Builds every version of the API: each under its own prefix (i.e., `/v1`
+/// and `/v2`), along with the unversioned paths, which are served as `/v1`
+/// and counted by `legacy`
+pub fn versioned(
+    state: Arc<RwLock<OmeState>>,
+    context: &RouteContext,
+    legacy: Arc<LegacyRouteMetrics>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let v1 = warp::path(ApiVersion::V1.prefix()).and(routes(
+        state.clone(),
+        ApiVersion::V1,
+        context,
+    ));
+    let v2 = warp::path(ApiVersion::V2.prefix()).and(routes(
+        state.clone(),
+        ApiVersion::V2,
+        context,
+    ));
+    let unversioned =
+        routes(state, ApiVersion::V1, context).map(move |reply| {
+            legacy.record();
+            reply
+        });
+
+    v1.or(v2).or(unversioned)
+}
+
+/// Builds the routes of the given version of the API, without its prefix
+pub fn routes(
+    state: Arc<RwLock<OmeState>>,
+    version: ApiVersion,
+    context: &RouteContext,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    /* Clone global engine state for each handler. This is only done because of
+     * the nature of move semantics for Rust closures.
+     *
+     * This is quite hacky and likely quite expensive.
+     */
+    let index_book_state: Arc<RwLock<OmeState>> = state.clone();
+    let create_book_state: Arc<RwLock<OmeState>> = state.clone();
+    let pause_book_state: Arc<RwLock<OmeState>> = state.clone();
+    let resume_book_state: Arc<RwLock<OmeState>> = state.clone();
+    let update_book_limits_state: Arc<RwLock<OmeState>> = state.clone();
+    let dump_state_state: Arc<RwLock<OmeState>> = state.clone();
+    let reload_book_state: Arc<RwLock<OmeState>> = state.clone();
+    let read_book_state: Arc<RwLock<OmeState>> = state.clone();
+    let read_depth_state: Arc<RwLock<OmeState>> = state.clone();
+    let read_snapshot_state: Arc<RwLock<OmeState>> = state.clone();
+    let read_ticker_state: Arc<RwLock<OmeState>> = state.clone();
+    let read_stats_state: Arc<RwLock<OmeState>> = state.clone();
+    let read_trades_state: Arc<RwLock<OmeState>> = state.clone();
+
+    let create_order_state: Arc<RwLock<OmeState>> = state.clone();
+    let read_order_state: Arc<RwLock<OmeState>> = state.clone();
+    let read_order_history_state: Arc<RwLock<OmeState>> = state.clone();
+    let read_client_order_state: Arc<RwLock<OmeState>> = state.clone();
+    let update_order_state: Arc<RwLock<OmeState>> = state.clone();
+    let destroy_order_state: Arc<RwLock<OmeState>> = state.clone();
+    let replace_order_state: Arc<RwLock<OmeState>> = state.clone();
+    let read_global_order_state: Arc<RwLock<OmeState>> = state.clone();
+    let quote_order_state: Arc<RwLock<OmeState>> = state.clone();
+    let destroy_global_order_state: Arc<RwLock<OmeState>> = state.clone();
+
+    let market_user_orders_state: Arc<RwLock<OmeState>> = state.clone();
+    let user_orders_state: Arc<RwLock<OmeState>> = state.clone();
+    let user_summary_state: Arc<RwLock<OmeState>> = state.clone();
+    let cancel_user_orders_state: Arc<RwLock<OmeState>> = state.clone();
+    let cancel_orders_state: Arc<RwLock<OmeState>> = state.clone();
+
+    let create_webhook_state: Arc<RwLock<OmeState>> = state.clone();
+    let index_webhook_state: Arc<RwLock<OmeState>> = state.clone();
+    let destroy_webhook_state: Arc<RwLock<OmeState>> = state.clone();
+
+    let block_trader_state: Arc<RwLock<OmeState>> = state.clone();
+    let unblock_trader_state: Arc<RwLock<OmeState>> = state.clone();
+    let index_blocklist_state: Arc<RwLock<OmeState>> = state.clone();
+
+    let ws_state: Arc<RwLock<OmeState>> = state;
+
+    /* bodies are limited in size, so that they needn't be buffered first */
+    let max_body_size: u64 = context.max_body_size;
+
+    /* the larger reads are compressed for clients that accept it */
+    let compress: bool = context.compress;
+
+    /* creating, pausing and reloading books, dumping the state, cancelling
+     * orders in bulk, and managing webhooks and the blocklist are reserved
+     * for admins */
+    let admin = handler::admin(context.admin_token.clone());
+    let readiness: Arc<Readiness> = context.readiness.clone();
+
+    /* define CRUD routes for order books */
+    let book_prefix = warp::path!("book");
+    let index_book_route = book_prefix
+        .and(warp::get())
+        .and(warp::any().map(move || index_book_state.clone()))
+        .and_then(handler::index_book_handler);
+    let create_book_route = book_prefix
+        .and(warp::post())
+        .and(admin.clone())
+        .and(handler::ready(readiness.clone()))
+        .and(handler::json_body(max_body_size))
+        .and(warp::any().map(move || create_book_state.clone()))
+        .and_then(handler::create_book_handler);
+    let pause_book_route = warp::path!("book" / Address / "pause")
+        .and(warp::post())
+        .and(admin.clone())
+        .and(warp::any().map(move || pause_book_state.clone()))
+        .and_then(handler::pause_book_handler);
+    let resume_book_route = warp::path!("book" / Address / "resume")
+        .and(warp::post())
+        .and(admin.clone())
+        .and(warp::any().map(move || resume_book_state.clone()))
+        .and_then(handler::resume_book_handler);
+    let update_book_limits_route = warp::path!("book" / Address / "limits")
+        .and(warp::put())
+        .and(admin.clone())
+        .and(handler::json_body(max_body_size))
+        .and(warp::any().map(move || update_book_limits_state.clone()))
+        .and_then(handler::update_book_limits_handler);
+
+    /* define routes for operators to dump the state and reload books */
+    let dump_state_dumpfile: PathBuf = context.dumpfile_path.clone();
+    let dump_state_route = warp::path!("admin" / "snapshot")
+        .and(warp::post())
+        .and(admin.clone())
+        .and(warp::any().map(move || dump_state_dumpfile.clone()))
+        .and(warp::any().map(move || dump_state_state.clone()))
+        .and_then(handler::dump_state_handler);
+    let reload_book_client: Option<RpcClient> = context.markets_client.clone();
+    let reload_book_route = warp::path!("admin" / "book" / Address / "reload")
+        .and(warp::post())
+        .and(admin.clone())
+        .and(warp::any().map(move || reload_book_client.clone()))
+        .and(warp::any().map(move || reload_book_state.clone()))
+        .and_then(handler::reload_book_handler);
+
+    /* define routes for blocking traders from submitting orders */
+    let block_trader_route = warp::path!("admin" / "blocklist" / Address)
+        .and(warp::put())
+        .and(admin.clone())
+        .and(warp::query::<api::BlockTraderQuery>())
+        .and(warp::any().map(move || block_trader_state.clone()))
+        .and_then(handler::block_trader_handler);
+    let unblock_trader_route = warp::path!("admin" / "blocklist" / Address)
+        .and(warp::delete())
+        .and(admin.clone())
+        .and(warp::any().map(move || unblock_trader_state.clone()))
+        .and_then(handler::unblock_trader_handler);
+    let index_blocklist_route = warp::path!("admin" / "blocklist")
+        .and(warp::get())
+        .and(admin.clone())
+        .and(warp::any().map(move || index_blocklist_state.clone()))
+        .and_then(handler::index_blocklist_handler);
+    let read_book_route = compression::compressed(
+        warp::path!("book" / Address)
+            .and(warp::get())
+            .and(handler::accept())
+            .and(warp::any().map(move || read_book_state.clone()))
+            .and_then(handler::read_book_handler),
+        compress,
+    );
+    let read_depth_route = compression::compressed(
+        warp::path!("book" / Address / "depth")
+            .and(warp::get())
+            .and(warp::query::<api::DepthQuery>())
+            .and(handler::accept())
+            .and(warp::any().map(move || read_depth_state.clone()))
+            .and_then(handler::read_depth_handler),
+        compress,
+    );
+    let read_snapshot_route = compression::compressed(
+        warp::path!("book" / Address / "snapshot")
+            .and(warp::get())
+            .and(warp::query::<api::SnapshotQuery>())
+            .and(handler::accept())
+            .and(warp::any().map(move || read_snapshot_state.clone()))
+            .and_then(handler::read_book_snapshot_handler),
+        compress,
+    );
+    let read_ticker_route = warp::path!("book" / Address / "ticker")
+        .and(warp::get())
+        .and(handler::accept())
+        .and(warp::any().map(move || read_ticker_state.clone()))
+        .and_then(handler::read_ticker_handler);
+    let read_stats_route = warp::path!("book" / Address / "stats")
+        .and(warp::get())
+        .and(handler::accept())
+        .and(warp::any().map(move || read_stats_state.clone()))
+        .and_then(handler::read_stats_handler);
+    let read_trades_route = compression::compressed(
+        warp::path!("book" / Address / "trades")
+            .and(warp::get())
+            .and(warp::query::<api::TradesQuery>())
+            .and(handler::accept())
+            .and(warp::any().map(move || read_trades_state.clone()))
+            .and_then(handler::read_trades_handler),
+        compress,
+    );
+
+    /* define CRUD routes for orders, limiting the rate at which each trader
+     * (or, failing that, each IP address) may change them */
+    let limiter: Arc<RateLimiter> = context.limiter.clone();
+    let create_order_executioner: Executioner = context.executioner.clone();
+    let signature_chain_id: Option<u64> = context.signature_chain_id;
+    let max_clock_skew: Duration = context.max_clock_skew;
+    let create_order_route = warp::path!("book" / Address / "order")
+        .and(warp::post())
+        .and(handler::ready(readiness.clone()))
+        .and(limit::by_trader(limiter.clone(), max_body_size))
+        .and(warp::any().map(move || create_order_state.clone()))
+        .and(warp::any().map(move || create_order_executioner.clone()))
+        .and(warp::any().map(move || signature_chain_id))
+        .and(warp::any().map(move || max_clock_skew))
+        .and_then(handler::create_order_handler);
+    let read_order_route = warp::path!("book" / Address / "order" / OrderId)
+        .and(warp::get())
+        .and(handler::accept())
+        .and(warp::any().map(move || version))
+        .and(warp::any().map(move || read_order_state.clone()))
+        .and_then(handler::read_order_handler);
+    let read_order_history_route =
+        warp::path!("book" / Address / "order" / OrderId / "history")
+            .and(warp::get())
+            .and(handler::accept())
+            .and(warp::any().map(move || read_order_history_state.clone()))
+            .and_then(handler::read_order_history_handler);
+    let read_client_order_route =
+        warp::path!("book" / Address / "order" / "by-client-id" / String)
+            .and(warp::get())
+            .and(warp::query::<api::ClientOrderQuery>())
+            .and(handler::accept())
+            .and(warp::any().map(move || read_client_order_state.clone()))
+            .and_then(handler::read_client_order_handler);
+    let update_order_route = warp::path!("book" / Address / "order" / OrderId)
+        .and(warp::put())
+        .and(handler::ready(readiness.clone()))
+        .and(limit::by_ip(limiter.clone()))
+        .and(handler::json_body(max_body_size))
+        .and(warp::any().map(move || update_order_state.clone()))
+        .and_then(handler::update_order_handler);
+    let destroy_order_route = warp::path!("book" / Address / "order" / OrderId)
+        .and(warp::delete())
+        .and(handler::ready(readiness.clone()))
+        .and(limit::by_ip(limiter.clone()))
+        .and(warp::any().map(move || destroy_order_state.clone()))
+        .and_then(handler::destroy_order_handler);
+    let replace_order_executioner: Executioner = context.executioner.clone();
+    let replace_order_route =
+        warp::path!("book" / Address / "order" / OrderId / "replace")
+            .and(warp::post())
+            .and(handler::ready(readiness.clone()))
+            .and(limit::by_trader(limiter.clone(), max_body_size))
+            .and(warp::any().map(move || replace_order_state.clone()))
+            .and(warp::any().map(move || replace_order_executioner.clone()))
+            .and(warp::any().map(move || signature_chain_id))
+            .and(warp::any().map(move || max_clock_skew))
+            .and_then(handler::replace_order_handler);
+    let quote_order_route = warp::path!("book" / Address / "quote")
+        .and(warp::post())
+        .and(handler::json_body(max_body_size))
+        .and(warp::any().map(move || quote_order_state.clone()))
+        .and_then(handler::quote_order_handler);
+    let read_global_order_route = warp::path!("order" / OrderId)
+        .and(warp::get())
+        .and(handler::accept())
+        .and(warp::any().map(move || read_global_order_state.clone()))
+        .and_then(handler::read_global_order_handler);
+    let destroy_global_order_route = warp::path!("order" / OrderId)
+        .and(warp::delete())
+        .and(handler::ready(readiness.clone()))
+        .and(limit::by_ip(limiter.clone()))
+        .and(warp::any().map(move || destroy_global_order_state.clone()))
+        .and_then(handler::destroy_global_order_handler);
+
+    let market_user_orders_route = compression::compressed(
+        warp::path!("book" / Address / Address)
+            .and(warp::get())
+            .and(handler::accept())
+            .and(warp::any().map(move || market_user_orders_state.clone()))
+            .and_then(handler::market_user_orders_handler),
+        compress,
+    );
+    let user_orders_route = compression::compressed(
+        warp::path!("user" / Address / "orders")
+            .and(warp::get())
+            .and(warp::query::<api::UserOrdersQuery>())
+            .and(handler::accept())
+            .and(warp::any().map(move || user_orders_state.clone()))
+            .and_then(handler::user_orders_handler),
+        compress,
+    );
+    let user_summary_route = warp::path!("user" / Address / "summary")
+        .and(warp::get())
+        .and(handler::accept())
+        .and(warp::any().map(move || user_summary_state.clone()))
+        .and_then(handler::user_summary_handler);
+    let cancel_user_orders_route =
+        warp::path!("book" / Address / "orders" / Address)
+            .and(warp::delete())
+            .and(admin.clone())
+            .and(handler::ready(readiness.clone()))
+            .and(limit::by_ip(limiter.clone()))
+            .and(warp::any().map(move || cancel_user_orders_state.clone()))
+            .and_then(handler::cancel_user_orders_handler);
+    let cancel_orders_route =
+        warp::path!("book" / Address / "orders" / "cancel")
+            .and(warp::post())
+            .and(admin.clone())
+            .and(handler::ready(readiness.clone()))
+            .and(limit::by_ip(limiter))
+            .and(handler::json_body(max_body_size))
+            .and(warp::any().map(move || cancel_orders_state.clone()))
+            .and_then(handler::cancel_orders_handler);
+
+    /* define routes for managing webhooks, which are posted every fill */
+    let create_webhook_route = warp::path!("webhooks")
+        .and(warp::post())
+        .and(admin.clone())
+        .and(handler::json_body(max_body_size))
+        .and(warp::any().map(move || create_webhook_state.clone()))
+        .and_then(handler::create_webhook_handler);
+    let index_webhook_route = warp::path!("webhooks")
+        .and(warp::get())
+        .and(admin.clone())
+        .and(warp::any().map(move || index_webhook_state.clone()))
+        .and_then(handler::index_webhook_handler);
+    let destroy_webhook_route = warp::path!("webhooks" / u64)
+        .and(warp::delete())
+        .and(admin)
+        .and(warp::any().map(move || destroy_webhook_state.clone()))
+        .and_then(handler::destroy_webhook_handler);
+
+    /* define the WebSocket feed of changes to order books */
+    let ws_route = warp::path!("ws")
+        .and(warp::ws())
+        .and(warp::any().map(move || ws_state.clone()))
+        .and_then(handler::ws_handler);
+
+    /* aggregate all of our order book routes */
+    let book_routes = index_book_route
+        .or(create_book_route)
+        .or(pause_book_route)
+        .or(resume_book_route)
+        .or(update_book_limits_route)
+        .or(read_book_route)
+        .or(read_depth_route)
+        .or(read_snapshot_route)
+        .or(read_ticker_route)
+        .or(read_stats_route)
+        .or(read_trades_route);
+
+    /* aggregate all of our order routes */
+    let order_routes = create_order_route
+        .or(read_order_route)
+        .or(read_order_history_route)
+        .or(read_client_order_route)
+        .or(update_order_route)
+        .or(destroy_order_route)
+        .or(replace_order_route)
+        .or(quote_order_route)
+        .or(read_global_order_route)
+        .or(destroy_global_order_route)
+        .or(cancel_user_orders_route)
+        .or(cancel_orders_route);
+
+    /* aggregate all of our webhook routes */
+    let webhook_routes = create_webhook_route
+        .or(index_webhook_route)
+        .or(destroy_webhook_route);
+
+    /* aggregate all of our blocklist routes */
+    let blocklist_routes = block_trader_route
+        .or(unblock_trader_route)
+        .or(index_blocklist_route);
+
+    let misc_routes = market_user_orders_route
+        .or(user_orders_route)
+        .or(user_summary_route)
+        .or(ws_route)
+        .or(dump_state_route)
+        .or(reload_book_route);
+
+    book_routes
+        .or(order_routes)
+        .or(webhook_routes)
+        .or(blocklist_routes)
+        .or(misc_routes)
+}
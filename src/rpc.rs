@@ -1,35 +1,46 @@
-use std::fmt::Display;
-use std::str::FromStr;
+//! Contains logic for communicating with the services the OME relies upon
+use std::time::Duration;
 
-use reqwest::{header, Client, Response};
+use rand::Rng;
+use reqwest::{
+    header, Client, Method, RequestBuilder, Response, StatusCode, Url,
+};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use web3::types::H160;
+use thiserror::Error;
+use web3::types::{Address, H256};
 
+use crate::book::ExternalBook;
+use crate::executioner::Execution;
 use crate::order::{ExternalOrder, Order};
 
-#[derive(Display, Debug)]
-pub enum RpcError {
-    HttpError,
-    ContractError,
-    InvalidResponse,
-}
+/// The default number of seconds to wait for a connection to the executioner
+pub const DEFAULT_RPC_CONNECT_TIMEOUT: u64 = 5;
 
-impl From<reqwest::Error> for RpcError {
-    fn from(_value: reqwest::Error) -> Self {
-        Self::HttpError
-    }
-}
+/// The default number of seconds to wait for the executioner to respond
+pub const DEFAULT_RPC_TIMEOUT: u64 = 10;
 
-impl From<rustc_hex::FromHexError> for RpcError {
-    fn from(_value: rustc_hex::FromHexError) -> Self {
-        Self::InvalidResponse
-    }
+/// The default number of attempts made at requests that are safe to retry
+pub const DEFAULT_RPC_MAX_ATTEMPTS: u32 = 3;
+
+/// The delay before the first retry, which doubles with each subsequent one
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Error)]
+pub enum RpcError {
+    #[error("Invalid URL {0}")]
+    InvalidUrl(String),
+    #[error("Request to {url} failed: {message}")]
+    HttpError { url: Url, message: String },
+    #[error("{url} responded with {status}")]
+    BadStatus { url: Url, status: StatusCode },
+    #[error("Invalid response from {url}: {message}")]
+    InvalidResponse { url: Url, message: String },
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct MatchRequest {
-    maker: ExternalOrder,
-    taker: ExternalOrder,
+    pub matches: Vec<Execution>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -37,81 +48,241 @@ pub struct CheckRequest {
     order: ExternalOrder,
 }
 
-#[allow(unused_must_use)]
-pub async fn check_order_validity(
-    order: Order,
-    address: String,
-) -> Result<bool, RpcError> {
-    let endpoint: String = address + "/check";
-    let client: Client = Client::new();
-    let payload: CheckRequest = CheckRequest {
-        order: ExternalOrder::from(order.clone()),
-    };
-
-    info!(
-        "Checking order validity by sending {} to {}...",
-        order, endpoint
-    );
-
-    let response: Response = match client
-        .post(endpoint.clone())
-        .header(header::CONTENT_TYPE, "application/json")
-        .body(serde_json::to_string(&payload).unwrap())
-        .send()
-        .await
-    {
-        Ok(t) => t,
-        Err(e) => return Err(e.into()),
-    };
-
-    info!("{} said {}", endpoint, response.status());
-
-    Ok(response.status().is_success())
+/// Represents a connection to a service the OME relies upon (i.e., the
+/// executioner or the source of known markets)
+///
+/// Cloning an `RpcClient` is cheap, and clones share the same pool of
+/// connections.
+#[derive(Clone, Debug)]
+pub struct RpcClient {
+    client: Client,
+    base: Url, /* ends in a slash, so that endpoints are joined onto it */
+    max_attempts: u32, /* of requests that are safe to retry */
 }
 
-pub async fn send_matched_orders(
-    maker: Order,
-    taker: Order,
-    address: String,
-) -> Result<H160, RpcError> {
-    info!(
-        "Forwarding matched pair ({}, {}) to {}...",
-        maker, taker, address
-    );
-
-    let payload: MatchRequest = MatchRequest {
-        maker: maker.into(),
-        taker: taker.into(),
-    };
-    let client: Client = Client::new();
-    let endpoint: String = address.clone() + "/submit";
-
-    /* post the matched orders to the forwarder */
-    let result: Response = match client
-        .post(endpoint)
-        .header(header::CONTENT_TYPE, "application/json")
-        .body(serde_json::to_string(&payload).unwrap())
-        .send()
-        .await
-    {
-        Ok(t) => t,
-        Err(e) => {
-            return Err(RpcError::from(e));
+impl RpcClient {
+    /// Constructor for the `RpcClient` type
+    ///
+    /// Requests time out if no connection is made within `connect_timeout`,
+    /// or if no response is received within `timeout`. Requests that are safe
+    /// to retry are made up to `max_attempts` times.
+    pub fn new(
+        address: &str,
+        connect_timeout: Duration,
+        timeout: Duration,
+        max_attempts: u32,
+    ) -> Result<Self, RpcError> {
+        let mut base: Url = match Url::parse(address) {
+            Ok(t) if !t.cannot_be_a_base() => t,
+            _ => return Err(RpcError::InvalidUrl(address.to_string())),
+        };
+
+        /* without a trailing slash, joining would replace the last segment */
+        if !base.path().ends_with('/') {
+            let path: String = format!("{}/", base.path());
+            base.set_path(&path);
+        }
+
+        let client: Client = match Client::builder()
+            .connect_timeout(connect_timeout)
+            .timeout(timeout)
+            .build()
+        {
+            Ok(t) => t,
+            Err(e) => {
+                return Err(RpcError::HttpError {
+                    url: base,
+                    message: e.to_string(),
+                })
+            }
+        };
+
+        Ok(Self {
+            client,
+            base,
+            max_attempts: max_attempts.max(1),
+        })
+    }
+
+    /// Returns the URL of the given endpoint of the service
+    pub fn url(&self, endpoint: &str) -> Result<Url, RpcError> {
+        self.base
+            .join(endpoint)
+            .map_err(|_e| RpcError::InvalidUrl(endpoint.to_string()))
+    }
+
+    /// Posts the JSON payload to the given endpoint of the service, as for
+    /// `RpcClient::send`
+    async fn post<T: Serialize>(
+        &self,
+        endpoint: &str,
+        payload: &T,
+        idempotent: bool,
+    ) -> Result<(Url, Response), RpcError> {
+        let body: String = match serde_json::to_string(payload) {
+            Ok(t) => t,
+            Err(e) => {
+                return Err(RpcError::HttpError {
+                    url: self.url(endpoint)?,
+                    message: e.to_string(),
+                })
+            }
+        };
+
+        self.send(Method::POST, endpoint, Some(body), idempotent)
+            .await
+    }
+
+    /// Sends a request to the given endpoint of the service
+    ///
+    /// If `idempotent`, failures to reach the service and server errors are
+    /// retried with exponential backoff. Any other response is returned,
+    /// regardless of its status.
+    async fn send(
+        &self,
+        method: Method,
+        endpoint: &str,
+        body: Option<String>,
+        idempotent: bool,
+    ) -> Result<(Url, Response), RpcError> {
+        let url: Url = self.url(endpoint)?;
+        let attempts: u32 = if idempotent { self.max_attempts } else { 1 };
+        let mut attempt: u32 = 1;
+
+        loop {
+            let mut request: RequestBuilder =
+                self.client.request(method.clone(), url.clone());
+            if let Some(body) = &body {
+                request = request
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(body.clone());
+            }
+
+            let error: RpcError = match request.send().await {
+                Ok(t) if !t.status().is_server_error() => return Ok((url, t)),
+                Ok(t) => RpcError::BadStatus {
+                    url: url.clone(),
+                    status: t.status(),
+                },
+                Err(e) => RpcError::HttpError {
+                    url: url.clone(),
+                    message: e.to_string(),
+                },
+            };
+
+            if attempt >= attempts {
+                return Err(error);
+            }
+
+            let delay: Duration = backoff(attempt);
+            warn!("{} (attempt {}), retrying in {:?}", error, attempt, delay);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
         }
-    };
+    }
 
-    info!("{} said {}", address, result.status());
+    /// Reads the body of a successful response as JSON
+    async fn json<T: DeserializeOwned>(
+        url: Url,
+        response: Response,
+    ) -> Result<T, RpcError> {
+        if !response.status().is_success() {
+            return Err(RpcError::BadStatus {
+                url,
+                status: response.status(),
+            });
+        }
 
-    /* extract the transaction hash from the response body */
-    let hash: H160 = match result.text().await {
-        Ok(t) => match H160::from_str(&t) {
-            Ok(s) => s,
-            Err(l) => {
-                return Err(RpcError::from(l));
+        let body: String = match response.text().await {
+            Ok(t) => t,
+            Err(e) => {
+                return Err(RpcError::HttpError {
+                    url,
+                    message: e.to_string(),
+                })
             }
-        },
-        Err(e) => return Err(RpcError::from(e)),
-    };
+        };
+
+        serde_json::from_str(&body).map_err(|e| RpcError::InvalidResponse {
+            url,
+            message: e.to_string(),
+        })
+    }
+
+    /// Retrieves the addresses of every market currently known to the
+    /// service (i.e., every deployed Tracer market)
+    pub async fn get_known_markets(&self) -> Result<Vec<Address>, RpcError> {
+        let (url, response): (Url, Response) =
+            self.send(Method::GET, "markets", None, true).await?;
+
+        RpcClient::json(url, response).await
+    }
+
+    /// Retrieves the order book of the given market as the service holds it
+    ///
+    /// The book is returned as it was received, so must still be checked (see
+    /// `Book::try_from`) before it is used.
+    pub async fn get_external_book(
+        &self,
+        market: Address,
+    ) -> Result<ExternalBook, RpcError> {
+        let endpoint: String = format!("book/{:?}", market);
+        let (url, response): (Url, Response) =
+            self.send(Method::GET, &endpoint, None, true).await?;
+
+        RpcClient::json(url, response).await
+    }
+
+    /// Asks the executioner whether the order is valid
+    ///
+    /// As this doesn't change anything, it is retried upon failure.
+    pub async fn check_order_validity(
+        &self,
+        order: Order,
+    ) -> Result<bool, RpcError> {
+        let payload: CheckRequest = CheckRequest {
+            order: ExternalOrder::from(order.clone()),
+        };
+
+        info!("Checking order validity of {}...", order);
+
+        let (url, response): (Url, Response) =
+            self.post("check", &payload, true).await?;
+
+        info!("{} said {}", url, response.status());
+
+        Ok(response.status().is_success())
+    }
+
+    /// Forwards a batch of matches to the executioner, returning the hashes
+    /// of the transactions executing them
+    ///
+    /// This is never retried, as the executioner may have already acted upon
+    /// a request that appeared to fail.
+    pub async fn send_matches(
+        &self,
+        matches: &[Execution],
+    ) -> Result<Vec<H256>, RpcError> {
+        info!("Forwarding {} matches...", matches.len());
+
+        let payload: MatchRequest = MatchRequest {
+            matches: matches.to_vec(),
+        };
+
+        let (url, response): (Url, Response) =
+            self.post("submit", &payload, false).await?;
+
+        info!("{} said {}", url, response.status());
+
+        RpcClient::json(url, response).await
+    }
+}
 
-    Ok(hash)
+/// Returns the delay before retrying the given attempt
+///
+/// The delay doubles with each attempt, plus up to half as much again at
+/// random so that many requests failing at once aren't all retried at once.
+pub(crate) fn backoff(attempt: u32) -> Duration {
+    let delay: Duration = RETRY_BASE_DELAY * 2u32.pow(attempt.min(16) - 1);
+    delay.mul_f64(1.0 + rand::thread_rng().gen_range(0.0..0.5))
 }
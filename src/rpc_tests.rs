@@ -0,0 +1,368 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use ethereum_types::{Address, H256, U256};
+use tokio::sync::RwLock;
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+
+use crate::book::{Book, ExternalBook};
+use crate::book_tests::test_executioner;
+use crate::divergence::{self, DivergenceMetrics};
+use crate::executioner::{Execution, Executioner};
+use crate::markets;
+use crate::order::{Order, OrderSide};
+use crate::rpc::{MatchRequest, RpcClient, RpcError};
+use crate::state::OmeState;
+
+/* a client for a service served locally at the given address */
+pub fn mock_client(address: SocketAddr) -> RpcClient {
+    RpcClient::new(
+        &format!("http://{}", address),
+        Duration::from_secs(1),
+        Duration::from_secs(1),
+        3,
+    )
+    .unwrap()
+}
+
+/* serves the route locally, returning its address */
+fn serve<F>(route: F) -> SocketAddr
+where
+    F: Filter<Error = Rejection> + Clone + Send + Sync + 'static,
+    F::Extract: Reply,
+{
+    let (address, server) =
+        warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    address
+}
+
+/* an endpoint answering with each of the given statuses in turn (repeating
+ * the last), along with the number of requests it has received */
+fn endpoint(
+    name: &'static str,
+    statuses: Vec<StatusCode>,
+    body: &'static str,
+) -> (
+    impl Filter<Extract = impl Reply, Error = Rejection> + Clone,
+    Arc<AtomicUsize>,
+) {
+    let requests: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+    let counter: Arc<AtomicUsize> = requests.clone();
+    let route = warp::path(name).and(warp::post()).map(move || {
+        let n: usize = counter.fetch_add(1, Ordering::SeqCst);
+        let status: StatusCode = statuses[n.min(statuses.len() - 1)];
+        warp::reply::with_status(body, status)
+    });
+
+    (route, requests)
+}
+
+fn order() -> Order {
+    trader_order(1, OrderSide::Bid)
+}
+
+fn trader_order(trader: u64, side: OrderSide) -> Order {
+    Order::new(
+        Address::from_low_u64_be(trader),
+        Address::zero(),
+        side,
+        U256::from(100),
+        U256::from(10),
+        Utc::now() + chrono::Duration::days(365),
+        Utc::now(),
+        vec![],
+    )
+}
+
+fn execution() -> Execution {
+    Execution::new(
+        &trader_order(1, OrderSide::Bid),
+        &trader_order(2, OrderSide::Ask),
+        U256::from(10),
+    )
+}
+
+#[test]
+pub fn test_endpoint_urls() {
+    let timeout: Duration = Duration::from_secs(1);
+
+    for base in &["http://localhost:3000/api", "http://localhost:3000/api/"] {
+        let client: RpcClient =
+            RpcClient::new(base, timeout, timeout, 1).unwrap();
+        assert_eq!(
+            client.url("check").unwrap().as_str(),
+            "http://localhost:3000/api/check"
+        );
+    }
+
+    let client: RpcClient =
+        RpcClient::new("http://localhost:3000", timeout, timeout, 1).unwrap();
+    assert_eq!(
+        client.url("submit").unwrap().as_str(),
+        "http://localhost:3000/submit"
+    );
+
+    assert!(matches!(
+        RpcClient::new("localhost:3000", timeout, timeout, 1),
+        Err(RpcError::InvalidUrl(_))
+    ));
+}
+
+#[tokio::test]
+pub async fn test_check_retries_server_errors() {
+    let (route, requests) = endpoint(
+        "check",
+        vec![StatusCode::INTERNAL_SERVER_ERROR, StatusCode::OK],
+        "",
+    );
+    let client: RpcClient = mock_client(serve(route));
+
+    assert!(client.check_order_validity(order()).await.unwrap());
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+    /* rejections are answers in their own right, so aren't retried */
+    let (route, requests) =
+        endpoint("check", vec![StatusCode::BAD_REQUEST], "");
+    let client: RpcClient = mock_client(serve(route));
+
+    assert!(!client.check_order_validity(order()).await.unwrap());
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+pub async fn test_check_gives_up_after_max_attempts() {
+    let (route, requests) =
+        endpoint("check", vec![StatusCode::BAD_GATEWAY], "");
+    let client: RpcClient = mock_client(serve(route));
+
+    match client.check_order_validity(order()).await {
+        Err(RpcError::BadStatus { url, status }) => {
+            assert_eq!(url, client.url("check").unwrap());
+            assert_eq!(status, StatusCode::BAD_GATEWAY);
+        }
+        other => panic!("Expected a bad status, got {:?}", other),
+    }
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+pub async fn test_check_times_out() {
+    let route = warp::path("check").and(warp::post()).and_then(|| async {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        Ok::<_, Infallible>(warp::reply())
+    });
+    let address: SocketAddr = serve(route);
+    let timeout: Duration = Duration::from_millis(200);
+    let client: RpcClient =
+        RpcClient::new(&format!("http://{}", address), timeout, timeout, 1)
+            .unwrap();
+
+    let result = tokio::time::timeout(
+        Duration::from_secs(2),
+        client.check_order_validity(order()),
+    )
+    .await
+    .expect("Request was never timed out");
+    assert!(matches!(result, Err(RpcError::HttpError { .. })));
+}
+
+#[tokio::test]
+pub async fn test_matches_are_not_retried() {
+    let (route, requests) =
+        endpoint("submit", vec![StatusCode::INTERNAL_SERVER_ERROR], "");
+    let client: RpcClient = mock_client(serve(route));
+
+    assert!(matches!(
+        client.send_matches(&[execution()]).await,
+        Err(RpcError::BadStatus { .. })
+    ));
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+pub async fn test_matches_malformed_response() {
+    let (route, _requests) =
+        endpoint("submit", vec![StatusCode::OK], "not json");
+    let client: RpcClient = mock_client(serve(route));
+
+    assert!(matches!(
+        client.send_matches(&[execution()]).await,
+        Err(RpcError::InvalidResponse { .. })
+    ));
+
+    let (route, _requests) = endpoint(
+        "submit",
+        vec![StatusCode::OK],
+        "[\"0x0000000000000000000000000000000000000000000000000000000000000001\"]",
+    );
+    let client: RpcClient = mock_client(serve(route));
+
+    assert_eq!(
+        client.send_matches(&[execution()]).await.unwrap(),
+        vec![H256::from_low_u64_be(1)]
+    );
+}
+
+#[tokio::test]
+pub async fn test_matches_forwarded_until_executed() {
+    let requests: Arc<Mutex<Vec<MatchRequest>>> = Arc::new(Mutex::new(vec![]));
+    let received: Arc<Mutex<Vec<MatchRequest>>> = requests.clone();
+    let route = warp::path("submit")
+        .and(warp::post())
+        .and(warp::body::json())
+        .map(move |request: MatchRequest| {
+            let mut received = received.lock().unwrap();
+            received.push(request);
+
+            /* the executioner fails the first attempt */
+            match received.len() {
+                1 => warp::reply::with_status("", StatusCode::BAD_GATEWAY),
+                _ => warp::reply::with_status("[]", StatusCode::OK),
+            }
+        });
+    let executioner: Executioner = Executioner::new(mock_client(serve(route)));
+
+    let mut book: Book = Book::new(Address::zero());
+    let mut maker: Order = trader_order(1, OrderSide::Bid);
+    let mut taker: Order = trader_order(2, OrderSide::Ask);
+    book.submit(maker.clone(), executioner.clone())
+        .await
+        .unwrap();
+    book.submit(taker.clone(), executioner.clone())
+        .await
+        .unwrap();
+
+    /* the book doesn't wait for the match to be executed */
+    assert_eq!(book.depth(), (0, 0));
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while requests.lock().unwrap().len() < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("Match was never retried");
+
+    /* the same batch is retried, holding both orders and their signatures */
+    let requests: Vec<MatchRequest> = requests.lock().unwrap().clone();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0], requests[1]);
+    maker.remaining = U256::zero();
+    taker.remaining = U256::zero();
+    assert_eq!(
+        requests[1].matches,
+        vec![Execution::new(&maker, &taker, U256::from(10))]
+    );
+}
+
+#[tokio::test]
+pub async fn test_known_markets_synced() {
+    let requests: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+    let counter: Arc<AtomicUsize> = requests.clone();
+    let route =
+        warp::path("markets").and(warp::get()).map(move || {
+            match counter.fetch_add(1, Ordering::SeqCst) {
+                0 => warp::reply::with_status(
+                    "",
+                    StatusCode::SERVICE_UNAVAILABLE,
+                ),
+                _ => warp::reply::with_status(
+                    "[\"0x0000000000000000000000000000000000000001\"]",
+                    StatusCode::OK,
+                ),
+            }
+        });
+    let client: RpcClient = mock_client(serve(route));
+    let state: RwLock<OmeState> = RwLock::new(OmeState::new());
+    let market: Address = Address::from_low_u64_be(1);
+
+    /* a transient failure is retried */
+    assert_eq!(markets::sync(&client, &state).await.unwrap(), 1);
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+    assert_eq!(state.read().await.book(market), Some(&Book::new(market)));
+    assert!(state.read().await.last_market_sync().is_some());
+
+    assert_eq!(markets::sync(&client, &state).await.unwrap(), 0);
+
+    /* whereas an unreachable source fails the sync, leaving the books be */
+    let timeout: Duration = Duration::from_secs(1);
+    let unreachable: RpcClient =
+        RpcClient::new("http://127.0.0.1:1", timeout, timeout, 1).unwrap();
+    assert!(markets::sync(&unreachable, &state).await.is_err());
+    assert_eq!(state.read().await.books().len(), 1);
+}
+
+/* a book of the given market holding a bid at each of the given prices */
+async fn book_with_bids(market: Address, prices: &[u64]) -> Book {
+    let mut book: Book = Book::new(market);
+    for price in prices {
+        let bid: Order = Order::new(
+            Address::from_low_u64_be(2),
+            market,
+            OrderSide::Bid,
+            U256::from(*price),
+            U256::from(10),
+            Utc::now() + chrono::Duration::days(365),
+            Utc::now(),
+            vec![],
+        );
+        book.submit(bid, test_executioner()).await.unwrap();
+    }
+    book
+}
+
+#[tokio::test]
+pub async fn test_divergence_checked() {
+    let alike: Address = Address::from_low_u64_be(1);
+    let diverged: Address = Address::from_low_u64_be(2);
+    let unserved: Address = Address::from_low_u64_be(3);
+
+    /* the source holds a deeper bid for the diverged market */
+    let source: Vec<(String, ExternalBook)> = vec![
+        (
+            format!("{:?}", alike),
+            book_with_bids(alike, &[100]).await.into(),
+        ),
+        (
+            format!("{:?}", diverged),
+            book_with_bids(diverged, &[100, 90]).await.into(),
+        ),
+    ];
+    let route = warp::path!("book" / String).and(warp::get()).map(
+        move |market: String| match source.iter().find(|t| t.0 == market) {
+            Some((_, book)) => warp::reply::json(book).into_response(),
+            None => StatusCode::NOT_FOUND.into_response(),
+        },
+    );
+    let client: RpcClient = mock_client(serve(route));
+
+    let mut state: OmeState = OmeState::new();
+    for market in &[alike, diverged, unserved] {
+        state.add_book(book_with_bids(*market, &[100]).await);
+    }
+    let state: RwLock<OmeState> = RwLock::new(state);
+    let metrics: DivergenceMetrics = DivergenceMetrics::default();
+
+    assert_eq!(
+        divergence::check(&client, &state, U256::zero(), &metrics).await,
+        vec![diverged]
+    );
+    assert_eq!(metrics.divergences(diverged), 1);
+    assert_eq!(metrics.divergences(alike), 0);
+    assert!(metrics.render().contains(&format!(
+        "ome_book_divergence{{market=\"{:?}\"}} 1\n",
+        diverged
+    )));
+
+    /* levels below the top may differ by up to the tolerance */
+    assert!(divergence::check(&client, &state, U256::from(10), &metrics)
+        .await
+        .is_empty());
+    assert_eq!(metrics.divergences(diverged), 1);
+}
@@ -0,0 +1,95 @@
+//! Contains logic for shutting the OME down without losing its state
+use std::future::Future;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::signal::unix::{self, SignalKind};
+use tokio::sync::{oneshot, RwLock, RwLockWriteGuard};
+use tokio::time::{self, Instant};
+
+use crate::state::OmeState;
+use crate::util;
+
+/// Completes once the process is asked to terminate (i.e., upon SIGTERM or
+/// SIGINT)
+pub async fn signal() {
+    let mut terminate = match unix::signal(SignalKind::terminate()) {
+        Ok(t) => t,
+        Err(e) => {
+            warn!("Failed to listen for SIGTERM: {}", e);
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+
+    tokio::select! {
+        _ = terminate.recv() => info!("Received SIGTERM"),
+        _ = tokio::signal::ctrl_c() => info!("Received SIGINT"),
+    }
+}
+
+/// Drives the server until `trigger` completes, then shuts it down and dumps
+/// the state to the dump file at the given path
+///
+/// Upon shutdown, the order books stop accepting changes and `drain` is sent
+/// to, which should make the server stop accepting new connections and finish
+/// serving in-flight requests (as `bind_with_graceful_shutdown` does). The
+/// state is dumped once the server has stopped, or once `timeout` has elapsed
+/// if it takes longer. Either way, this returns within `timeout` of shutdown
+/// beginning, unless writing the dump itself is what takes longer. Books
+/// hosted by tasks of their own (see `actor`) are taken back to be dumped.
+pub async fn run(
+    server: impl Future<Output = ()>,
+    trigger: impl Future<Output = ()>,
+    drain: oneshot::Sender<()>,
+    state: Arc<RwLock<OmeState>>,
+    dumpfile: &Path,
+    timeout: Duration,
+) -> io::Result<()> {
+    tokio::pin!(server);
+
+    let stopped: bool = tokio::select! {
+        _ = &mut server => {
+            warn!("Server stopped without being shut down");
+            true
+        }
+        _ = trigger => {
+            info!("Shutting down...");
+            false
+        }
+    };
+    let deadline: Instant = Instant::now() + timeout;
+
+    lock_by(deadline, state.write()).await?.begin_shutdown();
+
+    /* the server is only waiting on this if it is still running */
+    let _ = drain.send(());
+
+    if !stopped && time::timeout_at(deadline, &mut server).await.is_err() {
+        warn!("Gave up on in-flight requests after {:?}", timeout);
+    }
+
+    let mut ome_state: RwLockWriteGuard<OmeState> =
+        lock_by(deadline, state.write()).await?;
+
+    /* hosted books are taken back once they've finished their commands */
+    if ome_state.hosting() {
+        ome_state.recall_books().await;
+    }
+
+    util::dump_state(&ome_state, dumpfile)?;
+    info!("Dumped state to {}", dumpfile.display());
+    Ok(())
+}
+
+/// Acquires a lock on the state, unless it remains locked beyond the deadline
+async fn lock_by<G>(
+    deadline: Instant,
+    lock: impl Future<Output = G>,
+) -> io::Result<G> {
+    time::timeout_at(deadline, lock).await.map_err(|_e| {
+        io::Error::new(io::ErrorKind::TimedOut, "State was never unlocked")
+    })
+}
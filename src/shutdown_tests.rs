@@ -0,0 +1,95 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use ethereum_types::{Address, U256};
+use reqwest::StatusCode;
+use tokio::sync::{oneshot, RwLock};
+use warp::Filter;
+
+use crate::book::Book;
+use crate::book_tests::test_executioner;
+use crate::handler;
+use crate::order::{Order, OrderSide};
+use crate::shutdown;
+use crate::state::OmeState;
+
+#[tokio::test]
+pub async fn test_shutdown_dumps_state() {
+    let dir: PathBuf = std::env::temp_dir()
+        .join(format!("ome-shutdown-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let dump_path: PathBuf = dir.join("ome.json");
+    let _ = fs::remove_file(&dump_path);
+
+    let market: Address = Address::from_low_u64_be(1);
+    let order: Order = Order::new(
+        Address::from_low_u64_be(2),
+        market,
+        OrderSide::Bid,
+        U256::from(100),
+        U256::from(10),
+        Utc::now() + chrono::Duration::days(365),
+        Utc::now(),
+        vec![],
+    );
+    let mut book: Book = Book::new(market);
+    book.submit(order.clone(), test_executioner())
+        .await
+        .unwrap();
+    let mut internal_state: OmeState = OmeState::new();
+    internal_state.add_book(book);
+    let state: Arc<RwLock<OmeState>> = Arc::new(RwLock::new(internal_state));
+
+    /* serve a real (if minimal) set of routes, as `main` does */
+    let route_state = state.clone();
+    let routes = warp::path!("book" / Address)
+        .and(warp::get())
+        .and(handler::accept())
+        .and(warp::any().map(move || route_state.clone()))
+        .and_then(handler::read_book_handler);
+    let (drain, drained) = oneshot::channel::<()>();
+    let (address, server) = warp::serve(routes).bind_with_graceful_shutdown(
+        ([127, 0, 0, 1], 0),
+        async move {
+            let _ = drained.await;
+        },
+    );
+
+    let (trigger, triggered) = oneshot::channel::<()>();
+    let run_state = state.clone();
+    let run_path: PathBuf = dump_path.clone();
+    let running = tokio::spawn(async move {
+        shutdown::run(
+            server,
+            async move {
+                let _ = triggered.await;
+            },
+            drain,
+            run_state,
+            &run_path,
+            Duration::from_secs(5),
+        )
+        .await
+    });
+
+    let url: String = format!("http://{}/book/{:x}", address, market);
+    let res = reqwest::get(&url).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(!dump_path.exists());
+
+    trigger.send(()).unwrap();
+    running.await.unwrap().unwrap();
+
+    /* the server has stopped, having dumped the state */
+    assert!(reqwest::get(&url).await.is_err());
+    assert!(state.read().await.shutting_down());
+
+    let restored: OmeState = OmeState::from_dumpfile(&dump_path).unwrap();
+    assert_eq!(restored, *state.read().await);
+    assert_eq!(restored.book(market).unwrap().order(order.id), Some(&order));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,17 +1,89 @@
 //! Contains logic for interacting with the OME's state
-use std::collections::HashMap;
-use std::fs::read_to_string;
-use std::path::Path;
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::fs::{read_to_string, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::broadcast;
 use web3::types::Address;
 
-use crate::book::Book;
+use crate::actor::{self, BookHandle};
+use crate::book::{Book, BookError};
+use crate::event::{BookDelta, BookEvent, BookEventKind, EVENT_CAPACITY};
+use crate::order::{Order, OrderId};
+use crate::util;
+use crate::wal::{self, SharedWal, Wal, WalChange, WalEntry, WalError};
+use crate::webhook::Webhooks;
+
+pub mod inspect;
 
 /// Represents the entire state of the OME
-#[derive(Clone, PartialEq, Eq, Default, Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct OmeState {
     books: HashMap<Address, Book>,
+    #[serde(default)]
+    blocklist: BTreeSet<Address>, /* traders refused new orders */
+    #[serde(skip)]
+    allowlist: Option<BTreeSet<Address>>, /* sole traders accepted (if any) */
+    #[serde(skip)]
+    actors: HashMap<Address, BookHandle>, /* books hosted in tasks */
+    #[serde(skip)]
+    hosting: bool, /* books are handed to tasks of their own */
+    #[serde(skip)]
+    pub(crate) order_markets: HashMap<OrderId, Address>, /* of open orders */
+    #[serde(skip, default = "event_channel")]
+    events: broadcast::Sender<BookEvent>, /* changes to every book */
+    #[serde(skip, default = "delta_channel")]
+    deltas: broadcast::Sender<BookDelta>, /* net changes to every book */
+    #[serde(skip)]
+    wal: Option<SharedWal>, /* log of changes made since startup */
+    #[serde(skip)]
+    shutting_down: bool, /* books may no longer be changed */
+    #[serde(skip)]
+    last_market_sync: Option<DateTime<Utc>>, /* last successful sync */
+    #[serde(skip)]
+    webhooks: Webhooks, /* endpoints notified of fills */
+}
+
+/// Represents the reasons the OME's state cannot be restored on startup
+#[derive(Debug, Error)]
+pub enum RestoreError {
+    #[error("Failed to find write-ahead log: {0}")]
+    Find(io::Error),
+    #[error("{}: {1}", .0.display())]
+    Replay(PathBuf, WalError),
+}
+
+fn event_channel() -> broadcast::Sender<BookEvent> {
+    broadcast::channel(EVENT_CAPACITY).0
+}
+
+fn delta_channel() -> broadcast::Sender<BookDelta> {
+    broadcast::channel(EVENT_CAPACITY).0
+}
+
+/// Subscribers and webhooks take no part in comparisons, as they aren't part of
+/// the state of the engine itself, and nor do the order index (as it is
+/// derived from the books), the write-ahead log or the allowlist (as it is
+/// configured rather than kept)
+impl PartialEq for OmeState {
+    fn eq(&self, other: &Self) -> bool {
+        self.books == other.books && self.blocklist == other.blocklist
+    }
+}
+
+impl Eq for OmeState {}
+
+impl Default for OmeState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl OmeState {
@@ -19,19 +91,101 @@ impl OmeState {
     pub fn new() -> Self {
         Self {
             books: HashMap::new(),
+            blocklist: BTreeSet::new(),
+            allowlist: None,
+            actors: HashMap::new(),
+            hosting: false,
+            order_markets: HashMap::new(),
+            events: event_channel(),
+            deltas: delta_channel(),
+            wal: None,
+            shutting_down: false,
+            last_market_sync: None,
+            webhooks: Webhooks::default(),
         }
     }
 
+    /// Records every change subsequently made to the order books in the
+    /// provided write-ahead log
+    pub fn set_wal(&mut self, wal: Wal) {
+        self.wal = Some(Arc::new(Mutex::new(wal)));
+    }
+
     pub fn from_dumpfile(path: &Path) -> Option<Self> {
         let dump_data: String = match read_to_string(path) {
             Ok(t) => t,
             Err(_e) => return None,
         };
 
-        match serde_json::from_str(&dump_data) {
-            Ok(t) => Some(t),
-            Err(_e) => None,
+        let mut state: Self = match serde_json::from_str(&dump_data) {
+            Ok(t) => t,
+            Err(_e) => return None,
+        };
+
+        /* order indices aren't persisted, so rebuild them */
+        for book in state.books.values_mut() {
+            book.reindex();
         }
+
+        /* skip (rather than trade against) any book that has been corrupted */
+        state
+            .books
+            .retain(|market, book| match book.check_restored() {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Skipping invalid book {}: {}", market, e);
+                    false
+                }
+            });
+
+        let markets: Vec<Address> = state.books.keys().copied().collect();
+        for market in markets {
+            state.index_book(market);
+        }
+
+        Some(state)
+    }
+
+    /// Restores the state from the dump file at the given path, then catches
+    /// up on the changes made since it was written from the write-ahead log
+    /// at the given path (if any)
+    ///
+    /// A missing or malformed dump file is logged and treated as empty, so
+    /// that the OME can still start, whereas a write-ahead log that cannot be
+    /// replayed is an error (as starting without its changes would silently
+    /// lose them).
+    pub async fn restore(
+        dumpfile: &Path,
+        wal_path: Option<&Path>,
+    ) -> Result<Self, RestoreError> {
+        let mut state: Self = if dumpfile.exists() {
+            Self::from_dumpfile(dumpfile).unwrap_or_else(|| {
+                warn!("Ignoring malformed dump file {}", dumpfile.display());
+                Self::new()
+            })
+        } else {
+            Self::new()
+        };
+
+        if let Some(wal_path) = wal_path {
+            for path in Wal::files(wal_path).map_err(RestoreError::Find)? {
+                let replayed: Result<usize, WalError> = match File::open(&path)
+                {
+                    Ok(t) => state.replay(BufReader::new(t)).await,
+                    Err(e) => Err(e.into()),
+                };
+
+                match replayed {
+                    Ok(t) => {
+                        info!("Replayed {} changes from {}", t, path.display())
+                    }
+                    Err(e) => return Err(RestoreError::Replay(path, e)),
+                }
+            }
+        }
+
+        info!("Restored {} books", state.books.len());
+        Ok(state)
     }
 
     /// Returns a reference to the mapping from tickers to `Book` types
@@ -40,6 +194,20 @@ impl OmeState {
         &self.books
     }
 
+    /// Returns the markets of every order book, however it is hosted
+    pub fn markets(&self) -> Vec<Address> {
+        self.books
+            .keys()
+            .chain(self.actors.keys())
+            .copied()
+            .collect()
+    }
+
+    /// Returns whether there is an order book for the given market
+    pub fn has_book(&self, market: Address) -> bool {
+        self.books.contains_key(&market) || self.actors.contains_key(&market)
+    }
+
     /// Returns a reference to a specific order book
     pub fn book(&self, market: Address) -> Option<&Book> {
         self.books.get(&market)
@@ -50,13 +218,561 @@ impl OmeState {
         self.books.get_mut(&market)
     }
 
+    /// Returns the market of the open order (including untriggered stop
+    /// orders) matching the provided order ID, along with the order itself
+    pub fn find_order(&self, id: OrderId) -> Option<(Address, &Order)> {
+        let market: Address = *self.order_markets.get(&id)?;
+        let order: &Order = self.books.get(&market)?.order(id)?;
+        Some((market, order))
+    }
+
     /// Add a new order book to the OME
-    pub fn add_book(&mut self, book: Book) {
-        self.books.insert(*book.market(), book);
+    ///
+    /// Changes made to the book before it was added are never published, as
+    /// subscribers only learn of its existing orders from snapshots.
+    pub fn add_book(&mut self, mut book: Book) {
+        let market: Address = *book.market();
+        book.drain_events();
+        self.remove_book(market);
+
+        if self.hosting {
+            self.host_book(book);
+        } else {
+            self.books.insert(market, book);
+            self.index_book(market);
+        }
+    }
+
+    /// Hands every order book to a task of its own (see `actor`), after which
+    /// each is only reached through its handle
+    ///
+    /// Books added from now on are handed over as they are added. The order
+    /// index isn't kept while books are hosted, so orders can only be found
+    /// within their own markets.
+    pub fn host_books(&mut self) {
+        self.hosting = true;
+        self.order_markets.clear();
+
+        let books: Vec<Book> = self.books.drain().map(|(_, t)| t).collect();
+        for book in books {
+            self.host_book(book);
+        }
+
+        info!("Hosting {} books", self.actors.len());
+    }
+
+    fn host_book(&mut self, book: Book) {
+        let market: Address = *book.market();
+        let handle: BookHandle = actor::spawn(
+            book,
+            self.events.clone(),
+            self.deltas.clone(),
+            self.wal.clone(),
+        );
+        self.actors.insert(market, handle);
+    }
+
+    /// Returns whether the order books are hosted by tasks of their own
+    pub fn hosting(&self) -> bool {
+        self.hosting
+    }
+
+    /// Returns the handle to the task hosting the order book of the given
+    /// market, if books are hosted
+    pub fn handle(&self, market: Address) -> Option<BookHandle> {
+        self.actors.get(&market).cloned()
+    }
+
+    /// Returns the handles to the tasks hosting every order book
+    pub fn handles(&self) -> Vec<BookHandle> {
+        self.actors.values().cloned().collect()
+    }
+
+    /// Takes every order book back from the task hosting it, stopping the
+    /// task, so that the state can be dumped
+    ///
+    /// Books whose tasks have already stopped are lost, and logged as such.
+    pub async fn recall_books(&mut self) {
+        for (market, handle) in self.actors.drain() {
+            match handle.snapshot().await {
+                Ok(book) => {
+                    self.books.insert(market, book);
+                }
+                Err(e) => error!("Failed to recall book {}: {}", market, e),
+            }
+        }
+
+        self.hosting = false;
+        let markets: Vec<Address> = self.books.keys().copied().collect();
+        for market in markets {
+            self.index_book(market);
+        }
+    }
+
+    /// Adds every open order of an order book to the order index
+    fn index_book(&mut self, market: Address) {
+        if let Some(book) = self.books.get(&market) {
+            self.order_markets.extend(
+                book.index
+                    .keys()
+                    .chain(book.stop_index.keys())
+                    .map(|id| (*id, market)),
+            );
+        }
+    }
+
+    /// Updates the order index to reflect a change to an order book
+    fn index_event(&mut self, event: &BookEvent) {
+        match &event.kind {
+            BookEventKind::Added(order) => {
+                self.order_markets.insert(order.id, event.market);
+            }
+            BookEventKind::Cancelled(order)
+            | BookEventKind::Expired(order)
+            | BookEventKind::Triggered(order) => {
+                self.order_markets.remove(&order.id);
+            }
+            BookEventKind::Fill {
+                fill,
+                maker_remaining,
+                ..
+            } => {
+                if maker_remaining.is_zero() {
+                    self.order_markets.remove(&fill.maker_id);
+                }
+            }
+            BookEventKind::Amended(_) => {}
+        }
+    }
+
+    /// Subscribes to the changes to every order book published from now on
+    ///
+    /// Subscribers that fall more than `EVENT_CAPACITY` events behind miss
+    /// events (rather than holding up the engine) and are told so upon their
+    /// next receive.
+    pub fn subscribe(&self) -> broadcast::Receiver<BookEvent> {
+        self.events.subscribe()
+    }
+
+    /// Subscribes to the deltas of every order book published from now on
+    ///
+    /// As with `subscribe`, subscribers that fall too far behind miss deltas,
+    /// and must catch up from `Book::deltas_since` or a snapshot.
+    pub fn subscribe_deltas(&self) -> broadcast::Receiver<BookDelta> {
+        self.deltas.subscribe()
+    }
+
+    /// Publishes the changes recorded by an order book to all subscribers,
+    /// updating the order index to match
+    ///
+    /// Should be called once the book has been mutated, but before the state
+    /// is unlocked, so that events are published in the order they happened
+    /// and the order index never lags behind the books.
+    pub fn publish(&mut self, market: Address) {
+        let (events, delta) = match self.books.get_mut(&market) {
+            Some(t) => {
+                let events: Vec<BookEvent> = t.drain_events();
+                let delta: Option<BookDelta> = if events.is_empty() {
+                    None
+                } else {
+                    t.last_delta().cloned()
+                };
+                (events, delta)
+            }
+            None => return,
+        };
+
+        for event in events {
+            self.index_event(&event);
+
+            /* sending only fails if there are no subscribers at all */
+            let _ = self.events.send(event);
+        }
+
+        if let Some(delta) = delta {
+            let _ = self.deltas.send(delta);
+        }
+    }
+
+    /// Returns the webhooks notified of fills
+    pub fn webhooks(&self) -> &Webhooks {
+        &self.webhooks
+    }
+
+    /// Returns the webhooks notified of fills, for (un)registering them
+    ///
+    /// Registrations are neither logged nor dumped, so they don't survive a
+    /// restart.
+    pub fn webhooks_mut(&mut self) -> &mut Webhooks {
+        &mut self.webhooks
+    }
+
+    /// Returns whether the given trader may submit orders, i.e., that they
+    /// aren't blocked and (if there is an allowlist) are allowed
+    pub fn may_trade(&self, trader: Address) -> bool {
+        !self.blocklist.contains(&trader)
+            && self.allowlist.as_ref().is_none_or(|t| t.contains(&trader))
+    }
+
+    /// Returns the traders blocked from submitting orders
+    pub fn blocklist(&self) -> &BTreeSet<Address> {
+        &self.blocklist
+    }
+
+    /// Blocks the given trader from submitting orders, returning whether they
+    /// weren't already
+    ///
+    /// The trader's open orders are left as they are (see `cancel_all`). The
+    /// blocklist is kept in dumps, but not in the write-ahead log.
+    pub fn block(&mut self, trader: Address) -> bool {
+        self.blocklist.insert(trader)
+    }
+
+    /// Lets a blocked trader submit orders again, returning whether they were
+    /// blocked
+    pub fn unblock(&mut self, trader: Address) -> bool {
+        self.blocklist.remove(&trader)
+    }
+
+    /// Restricts the traders that may submit orders to those given, or lifts
+    /// the restriction if `None`
+    ///
+    /// Blocked traders are refused even if they are allowed.
+    pub fn set_allowlist(&mut self, allowlist: Option<BTreeSet<Address>>) {
+        self.allowlist = allowlist;
+    }
+
+    /// Cancels every open order of the given trader in every (unhosted) order
+    /// book as of the given time, returning the IDs of the orders cancelled
+    pub fn cancel_all(
+        &mut self,
+        trader: Address,
+        now: DateTime<Utc>,
+    ) -> Vec<OrderId> {
+        let mut markets: Vec<Address> = self.books.keys().copied().collect();
+        markets.sort();
+
+        let mut cancelled: Vec<OrderId> = vec![];
+        for market in markets {
+            let book: &mut Book = match self.books.get_mut(&market) {
+                Some(t) => t,
+                None => continue,
+            };
+
+            let sequence: u64 = book.sequence;
+            let orders: Vec<Order> = book.cancel_all_at(trader, now);
+            if orders.is_empty() {
+                continue;
+            }
+
+            cancelled.extend(orders.iter().map(|order| order.id));
+            self.log(market, sequence, now, WalChange::CancelAll { trader });
+            self.publish(market);
+        }
+
+        cancelled
+    }
+
+    /// Prevents the order books from being changed any further, so that the
+    /// state can be dumped before the OME exits
+    pub fn begin_shutdown(&mut self) {
+        self.shutting_down = true;
+    }
+
+    /// Returns whether the OME is shutting down, in which case the order books
+    /// must not be changed
+    pub fn shutting_down(&self) -> bool {
+        self.shutting_down
+    }
+
+    /// Brings the order books into step with the markets currently known to
+    /// exist, creating an empty book for each market that lacks one
+    ///
+    /// Books for markets that are no longer known are kept (rather than
+    /// cancelling their orders), and are returned so that they can be
+    /// reported.
+    pub fn sync_markets(
+        &mut self,
+        markets: &[Address],
+        now: DateTime<Utc>,
+    ) -> Vec<Address> {
+        /* books may not be created while draining for shutdown */
+        if self.shutting_down {
+            return vec![];
+        }
+
+        for market in markets {
+            if self.has_book(*market) {
+                continue;
+            }
+
+            info!("Creating book for newly known market {}...", market);
+            let book: Book = Book::new(*market);
+            let change: WalChange =
+                WalChange::CreateBook { book: book.clone() };
+            self.add_book(book);
+            self.log(*market, 0, now, change);
+        }
+
+        self.last_market_sync = Some(now);
+
+        self.markets()
+            .into_iter()
+            .filter(|market| !markets.contains(market))
+            .collect()
+    }
+
+    /// Writes the state to the dump file at the given path, returning the size
+    /// of the dump in bytes
+    ///
+    /// Unlike when shutting down, hosted books are copied rather than taken
+    /// back from their tasks, so they carry on matching once copied.
+    pub async fn dump(&self, path: &Path) -> io::Result<u64> {
+        if !self.hosting {
+            return util::dump_state(self, path);
+        }
+
+        let mut copy: OmeState = OmeState::new();
+        copy.blocklist = self.blocklist.clone();
+        for (market, handle) in &self.actors {
+            match handle.snapshot().await {
+                Ok(book) => {
+                    copy.books.insert(*market, book);
+                }
+                Err(e) => error!("Failed to copy book {}: {}", market, e),
+            }
+        }
+
+        util::dump_state(&copy, path)
+    }
+
+    /// Returns the time the order books were last synced with the known
+    /// markets, if ever
+    pub fn last_market_sync(&self) -> Option<DateTime<Utc>> {
+        self.last_market_sync
+    }
+
+    /// Records a change made to an order book in the write-ahead log, if any
+    ///
+    /// Should be called once the change has been made, but before the state is
+    /// unlocked, so that changes are logged in the order they were made.
+    /// `sequence` is the sequence number of the book before the change.
+    pub fn log(
+        &mut self,
+        market: Address,
+        sequence: u64,
+        timestamp: DateTime<Utc>,
+        change: WalChange,
+    ) {
+        if let Some(wal) = &self.wal {
+            wal::record(
+                wal,
+                WalEntry::new(market, sequence, timestamp, change),
+            );
+        }
+    }
+
+    /// Makes the changes recorded in a write-ahead log on top of the current
+    /// state
+    ///
+    /// Changes already reflected in the state (e.g., those made before it was
+    /// dumped) are skipped, as are changes to books that don't exist. Each
+    /// change is made as of the time it was originally made at and matches are
+    /// not forwarded to the executioner again. An unparseable final line, as
+    /// left by a crash partway through logging a change, is ignored.
+    ///
+    /// Returns the number of changes made.
+    pub async fn replay<R: BufRead>(
+        &mut self,
+        reader: R,
+    ) -> Result<usize, WalError> {
+        let mut replayed: usize = 0;
+        let mut lines = reader.lines().enumerate().peekable();
+
+        while let Some((number, line)) = lines.next() {
+            let line: String = line?;
+
+            let entry: WalEntry = match serde_json::from_str(&line) {
+                Ok(t) => t,
+                Err(_e) if lines.peek().is_none() => {
+                    warn!("Ignoring partially logged change {}", number + 1);
+                    break;
+                }
+                Err(_e) => return Err(WalError::Malformed(number + 1)),
+            };
+
+            if self.apply(entry).await {
+                replayed += 1;
+            }
+        }
+
+        Ok(replayed)
+    }
+
+    /// Makes a single change recorded in a write-ahead log, returning whether
+    /// it was made (rather than skipped)
+    async fn apply(&mut self, entry: WalEntry) -> bool {
+        let market: Address = entry.market;
+        let now: DateTime<Utc> = entry.timestamp;
+
+        if let WalChange::CreateBook { book } = entry.change {
+            if self.books.contains_key(&market) {
+                return false;
+            }
+
+            self.add_book(book);
+            return true;
+        }
+
+        /* reloaded books replace whatever came before them outright, as they
+         * stood when they were reloaded */
+        if let WalChange::ReloadBook { book } = entry.change {
+            return match Book::from_external_at(book, now) {
+                Ok(t) => {
+                    self.add_book(t);
+                    true
+                }
+                Err(e) => {
+                    warn!("Skipping invalid reloaded book {}: {}", market, e);
+                    false
+                }
+            };
+        }
+
+        let book: &mut Book = match self.books.get_mut(&market) {
+            Some(t) => t,
+            None => {
+                warn!("Skipping logged change to missing book {}", market);
+                return false;
+            }
+        };
+
+        /* the book already reflects every change made before its sequence */
+        if book.sequence > entry.sequence {
+            return false;
+        }
+
+        let result: Result<(), BookError> = match entry.change {
+            WalChange::CreateBook { .. } | WalChange::ReloadBook { .. } => {
+                Ok(()) /* handled above */
+            }
+            WalChange::Submit { order } => match Order::try_from(order) {
+                Ok(order) => book.submit_at(order, None, now).await.map(|_| ()),
+                Err(e) => {
+                    warn!("Skipping malformed logged order: {}", e);
+                    return false;
+                }
+            },
+            WalChange::Replace { order_id, order } => {
+                match Order::try_from(order) {
+                    Ok(order) => book
+                        .replace_at(order_id, order, None, now)
+                        .await
+                        .map(|_| ()),
+                    Err(e) => {
+                        warn!("Skipping malformed logged order: {}", e);
+                        return false;
+                    }
+                }
+            }
+            WalChange::Amend {
+                order_id,
+                price,
+                quantity,
+            } => book.amend_at(order_id, price, quantity, now).map(|_| ()),
+            WalChange::Cancel { order_id } => {
+                book.cancel_at(order_id, now).map(|_| ())
+            }
+            WalChange::CancelAll { trader } => {
+                book.cancel_all_at(trader, now);
+                Ok(())
+            }
+            WalChange::PurgeExpired => {
+                book.purge_expired(now);
+                Ok(())
+            }
+            WalChange::SetPaused { paused } => {
+                if !book.set_paused(paused) {
+                    return false;
+                }
+                Ok(())
+            }
+            WalChange::SetSizeLimits {
+                max_quantity,
+                max_notional,
+            } => {
+                if !book.set_size_limits(max_quantity, max_notional) {
+                    return false;
+                }
+                Ok(())
+            }
+        };
+
+        /* only changes that succeeded are logged, so this means divergence */
+        if let Err(e) = result {
+            warn!("Logged change to book {} failed on replay: {}", market, e);
+        }
+
+        self.publish(market);
+        true
     }
 
     /// Remove an order book from the OME
+    ///
+    /// A book hosted by a task of its own is stopped instead, and so isn't
+    /// returned.
     pub fn remove_book(&mut self, market: Address) -> Option<Book> {
+        self.order_markets
+            .retain(|_id, order_market| *order_market != market);
+        self.actors.remove(&market);
         self.books.remove(&market)
     }
 }
+
+/// Tracks whether the OME is ready to serve traffic
+///
+/// This is kept apart from `OmeState`, as it must be readable while the state
+/// is locked for restoring.
+#[derive(Debug, Default)]
+pub struct Readiness {
+    restored: AtomicBool, /* the state has been restored on startup */
+    market_sync_max_age: Option<Duration>, /* if markets are synced at all */
+}
+
+impl Readiness {
+    /// Constructor for the `Readiness` type
+    ///
+    /// Once restored, the OME is only ready if the known markets were synced
+    /// within `market_sync_max_age` (if given).
+    pub fn new(market_sync_max_age: Option<Duration>) -> Self {
+        Self {
+            restored: AtomicBool::new(false),
+            market_sync_max_age,
+        }
+    }
+
+    /// Marks the state as restored
+    pub fn set_restored(&self) {
+        self.restored.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns whether the state has been restored
+    pub fn restored(&self) -> bool {
+        self.restored.load(Ordering::SeqCst)
+    }
+
+    /// Returns whether the given time of the last market sync is recent
+    /// enough, as of `now`
+    pub fn market_sync_fresh(
+        &self,
+        last_market_sync: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        match self.market_sync_max_age {
+            Some(max_age) => {
+                last_market_sync.is_some_and(|t| now - t <= max_age)
+            }
+            None => true,
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Contains logic for inspecting, validating and comparing the state of the
+//! OME offline, as read from a dump file
+//!
+//! Unlike `OmeState::from_dumpfile`, nothing is skipped when reading a dump
+//! file here: a corrupted book is exactly what an operator wants to see.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::read_to_string;
+use std::io;
+use std::path::Path;
+
+use ethereum_types::U256;
+use thiserror::Error;
+use web3::types::Address;
+
+use crate::book::{Book, BookDiff, InvariantViolation};
+use crate::state::OmeState;
+
+/// Represents the reasons a dump file cannot be read
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("Failed to read dump file: {0}")]
+    Read(#[from] io::Error),
+    #[error("Malformed dump file: {0}")]
+    Parse(#[from] serde_json::Error),
+}
+
+/// Summarises a single order book of a dump file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MarketSummary {
+    pub market: Address,
+    pub sequence: u64,
+    pub orders: usize, /* open orders, including untriggered stop orders */
+    pub depth: (usize, usize), /* resting orders on each side */
+    pub top: (Option<U256>, Option<U256>), /* best bid and ask */
+    pub open_notional: U256, /* see `Book::open_notional` */
+    pub paused: bool,
+}
+
+impl From<&Book> for MarketSummary {
+    fn from(book: &Book) -> Self {
+        Self {
+            market: *book.market(),
+            sequence: book.sequence,
+            orders: book.order_count(),
+            depth: book.depth(),
+            top: book.top(),
+            open_notional: book.open_notional(),
+            paused: book.paused,
+        }
+    }
+}
+
+/// Represents how a market differs between two dump files
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MarketDifference {
+    /// The market only has a book in the first dump file
+    Removed(Address),
+    /// The market only has a book in the second dump file
+    Added(Address),
+    /// The books of the market differ (see `Book::diff`)
+    Changed(Address, BookDiff),
+}
+
+/// Reads the state of the OME from the dump file at the given path
+///
+/// Order indices are rebuilt, as they aren't persisted, but the books are
+/// otherwise left exactly as they were written.
+pub fn read(path: &Path) -> Result<OmeState, SnapshotError> {
+    let mut state: OmeState = serde_json::from_str(&read_to_string(path)?)?;
+
+    for book in state.books.values_mut() {
+        book.reindex();
+    }
+
+    Ok(state)
+}
+
+/// Summarises every order book of the state, in order of market
+pub fn inspect(state: &OmeState) -> Vec<MarketSummary> {
+    sorted(state)
+        .values()
+        .map(|t| MarketSummary::from(*t))
+        .collect()
+}
+
+/// Checks the invariants of every order book of the state (see
+/// `Book::validate`), returning the violations found in each book that has
+/// any, in order of market
+pub fn validate(state: &OmeState) -> Vec<(Address, Vec<InvariantViolation>)> {
+    sorted(state)
+        .into_iter()
+        .filter_map(|(market, book)| book.validate().err().map(|t| (market, t)))
+        .collect()
+}
+
+/// Compares the order books of two states market by market, returning the
+/// markets that differ in order of market
+pub fn diff(first: &OmeState, second: &OmeState) -> Vec<MarketDifference> {
+    let markets: BTreeSet<Address> = first
+        .books
+        .keys()
+        .chain(second.books.keys())
+        .copied()
+        .collect();
+
+    markets
+        .into_iter()
+        .filter_map(|market| {
+            match (first.books.get(&market), second.books.get(&market)) {
+                (Some(ours), Some(theirs)) => {
+                    let diff: BookDiff = ours.diff(theirs);
+                    if diff.is_empty() {
+                        None
+                    } else {
+                        Some(MarketDifference::Changed(market, diff))
+                    }
+                }
+                (Some(_), None) => Some(MarketDifference::Removed(market)),
+                (None, Some(_)) => Some(MarketDifference::Added(market)),
+                (None, None) => None,
+            }
+        })
+        .collect()
+}
+
+fn sorted(state: &OmeState) -> BTreeMap<Address, &Book> {
+    state
+        .books
+        .iter()
+        .map(|(market, book)| (*market, book))
+        .collect()
+}
@@ -0,0 +1,140 @@
+//! Contains logic for the rolling trade statistics of order books
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+use ethereum_types::U256;
+use serde::{Deserialize, Serialize};
+
+use crate::book::Fill;
+use crate::util::{from_hex_de, from_hex_se, scale_18};
+
+/// The number of hours of trades the statistics of each book cover
+pub const STATS_WINDOW_HOURS: i64 = 24;
+
+const SECONDS_PER_HOUR: i64 = 3600;
+
+/// Represents the trades made within a single hour
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct StatsBucket {
+    pub hour: i64, /* start of the hour, in hours since the Unix epoch */
+    #[serde(serialize_with = "from_hex_se", deserialize_with = "from_hex_de")]
+    pub volume: U256, /* total quantity traded */
+    #[serde(serialize_with = "from_hex_se", deserialize_with = "from_hex_de")]
+    pub notional: U256, /* total notional value (see `util::scale_18`) */
+    pub trades: u64,
+    #[serde(serialize_with = "from_hex_se", deserialize_with = "from_hex_de")]
+    pub high: U256, /* highest traded price */
+    #[serde(serialize_with = "from_hex_se", deserialize_with = "from_hex_de")]
+    pub low: U256, /* lowest traded price */
+}
+
+impl StatsBucket {
+    fn new(hour: i64) -> Self {
+        Self {
+            hour,
+            volume: U256::zero(),
+            notional: U256::zero(),
+            trades: 0,
+            high: U256::zero(),
+            low: U256::max_value(),
+        }
+    }
+
+    fn record(&mut self, fill: &Fill) {
+        /* notional can exceed 256 bits, in which case it saturates */
+        let notional: U256 =
+            scale_18(fill.price, fill.quantity).unwrap_or_else(U256::max_value);
+
+        self.volume = self.volume.saturating_add(fill.quantity);
+        self.notional = self.notional.saturating_add(notional);
+        self.trades += 1;
+        self.high = self.high.max(fill.price);
+        self.low = self.low.min(fill.price);
+    }
+}
+
+/// Represents the statistics of the trades of the last
+/// `STATS_WINDOW_HOURS` hours
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Stats {
+    pub volume: U256,
+    pub notional: U256,
+    pub trades: u64,
+    pub high: Option<U256>, /* highest traded price (if any trades) */
+    pub low: Option<U256>,  /* lowest traded price (if any trades) */
+}
+
+/// Tracks the rolling statistics of the trades of an order book, as hourly
+/// buckets
+///
+/// Buckets that fall out of the window are dropped as trades are recorded,
+/// and skipped when the statistics are read, so no background task is needed
+/// to age them out.
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct TradeStats {
+    buckets: VecDeque<StatsBucket>, /* oldest first */
+}
+
+/// Returns the hour of the given time, in hours since the Unix epoch
+fn hour_of(time: DateTime<Utc>) -> i64 {
+    time.timestamp().div_euclid(SECONDS_PER_HOUR)
+}
+
+impl TradeStats {
+    /// Records a trade in the bucket of the hour it was made in
+    pub fn record(&mut self, fill: &Fill) {
+        let hour: i64 = hour_of(fill.timestamp);
+
+        /* a trade stamped earlier than the latest counts towards the latest */
+        match self.buckets.back_mut() {
+            Some(t) if t.hour >= hour => t.record(fill),
+            _ => {
+                let mut bucket: StatsBucket = StatsBucket::new(hour);
+                bucket.record(fill);
+                self.buckets.push_back(bucket);
+            }
+        }
+
+        self.expire(hour);
+    }
+
+    /// Drops the buckets that fall out of the window ending in the given hour
+    fn expire(&mut self, hour: i64) {
+        while self
+            .buckets
+            .front()
+            .is_some_and(|t| t.hour <= hour - STATS_WINDOW_HOURS)
+        {
+            self.buckets.pop_front();
+        }
+    }
+
+    /// Returns the statistics of the trades made in the window ending at
+    /// `now` (i.e., this hour and the `STATS_WINDOW_HOURS - 1` before it)
+    pub fn summary(&self, now: DateTime<Utc>) -> Stats {
+        let hour: i64 = hour_of(now);
+        let mut stats: Stats = Stats {
+            volume: U256::zero(),
+            notional: U256::zero(),
+            trades: 0,
+            high: None,
+            low: None,
+        };
+
+        for bucket in self
+            .buckets
+            .iter()
+            .filter(|t| t.hour > hour - STATS_WINDOW_HOURS && t.trades > 0)
+        {
+            stats.volume = stats.volume.saturating_add(bucket.volume);
+            stats.notional = stats.notional.saturating_add(bucket.notional);
+            stats.trades += bucket.trades;
+            stats.high =
+                Some(stats.high.map_or(bucket.high, |t| t.max(bucket.high)));
+            stats.low =
+                Some(stats.low.map_or(bucket.low, |t| t.min(bucket.low)));
+        }
+
+        stats
+    }
+}
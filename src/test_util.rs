@@ -0,0 +1,98 @@
+//! Contains fixtures shared by the tests and benchmarks of the matching
+//! engine
+use chrono::{DateTime, Duration, Utc};
+use ethereum_types::{Address, U256};
+
+use crate::book::{Book, Level};
+use crate::order::{Order, OrderSide};
+
+/// The quantity of every order made by these fixtures
+pub const FIXTURE_QUANTITY: u64 = 10;
+
+/// The price either side of which `non_crossing_orders` places its orders
+pub const FIXTURE_MID_PRICE: u64 = 1_000_000;
+
+/// Returns an expiration far enough away for orders to stay live for the
+/// whole of a test or benchmark
+pub fn far_future() -> DateTime<Utc> {
+    Utc::now() + Duration::days(365)
+}
+
+/// Returns a live order of `FIXTURE_QUANTITY` from the given trader, i.e.,
+/// `Address::from_low_u64_be(trader)`
+pub fn order(
+    market: Address,
+    trader: u64,
+    side: OrderSide,
+    price: u64,
+) -> Order {
+    Order::new(
+        Address::from_low_u64_be(trader),
+        market,
+        side,
+        U256::from(price),
+        U256::from(FIXTURE_QUANTITY),
+        far_future(),
+        Utc::now(),
+        vec![],
+    )
+}
+
+/// Returns `count` orders, alternating between bids and asks, none of which
+/// cross one another
+///
+/// Bids are placed below `FIXTURE_MID_PRICE` and asks at or above it, spread
+/// over a thousand price levels on each side. Every order has a trader of its
+/// own (counting up from one), so none collide.
+pub fn non_crossing_orders(market: Address, count: u64) -> Vec<Order> {
+    (0..count)
+        .map(|i| {
+            let offset: u64 = (i / 2) % 1000;
+
+            if i % 2 == 0 {
+                order(
+                    market,
+                    i + 1,
+                    OrderSide::Bid,
+                    FIXTURE_MID_PRICE - 1 - offset,
+                )
+            } else {
+                order(market, i + 1, OrderSide::Ask, FIXTURE_MID_PRICE + offset)
+            }
+        })
+        .collect()
+}
+
+/// Returns a book holding `levels` price levels of `per_level` orders each on
+/// one side, starting from `price` and moving away from the other side
+///
+/// The book is populated directly (and then reindexed) rather than by
+/// submitting each order, for speed. Every order has a trader of its own
+/// (counting up from one).
+pub fn deep_book(
+    market: Address,
+    side: OrderSide,
+    price: u64,
+    levels: u64,
+    per_level: u64,
+) -> Book {
+    let mut book: Book = Book::new(market);
+
+    for level in 0..levels {
+        let price: u64 = match side {
+            OrderSide::Bid => price - level,
+            OrderSide::Ask => price + level,
+        };
+        let orders: Level = (0..per_level)
+            .map(|i| order(market, 1 + level * per_level + i, side, price))
+            .collect();
+
+        match side {
+            OrderSide::Bid => book.bids.insert(U256::from(price), orders),
+            OrderSide::Ask => book.asks.insert(U256::from(price), orders),
+        };
+    }
+
+    book.reindex();
+    book
+}
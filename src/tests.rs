@@ -1,16 +1,26 @@
 #[cfg(test)]
 mod order_tests {
-    use chrono::Utc;
+    use std::convert::TryFrom;
+    use std::str::FromStr;
+
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use ethabi::Token;
+    use secp256k1::SecretKey;
+    use web3::signing::{Key, SecretKeyRef, Signature};
     use web3::types::{Address, U256};
 
-    use crate::order::{Order, OrderSide};
+    use crate::api::CreateOrderRequest;
+    use crate::order::{
+        ExternalOrder, Order, OrderId, OrderParseError, OrderSide, OrderType,
+        SignatureError, TimeInForce, TimestampError, MAX_CLIENT_ORDER_ID_LEN,
+    };
 
     #[test]
     pub fn basic_order_info() {
         let address = Address::from_low_u64_be(1);
         let market_address = Address::zero();
-        let price = U256::from_dec_str(&"5").unwrap();
-        let amount = U256::from_dec_str(&"10").unwrap();
+        let price = U256::from_dec_str("5").unwrap();
+        let amount = U256::from_dec_str("10").unwrap();
 
         let order = Order::new(
             address,
@@ -29,15 +39,487 @@ mod order_tests {
         assert_eq!(order.price, price);
         assert_eq!(order.quantity, amount);
         assert!(order.signed_data.is_empty());
+        assert_eq!(order.time_in_force, TimeInForce::GoodTillCancel);
+    }
+
+    #[test]
+    pub fn order_side_aliases() {
+        for (alias, side) in [
+            ("Bid", OrderSide::Bid),
+            ("bid", OrderSide::Bid),
+            ("BID", OrderSide::Bid),
+            ("Buy", OrderSide::Bid),
+            ("buy", OrderSide::Bid),
+            ("LONG", OrderSide::Bid),
+            ("long", OrderSide::Bid),
+            ("0", OrderSide::Bid),
+            ("Ask", OrderSide::Ask),
+            ("ask", OrderSide::Ask),
+            ("ASK", OrderSide::Ask),
+            ("Sell", OrderSide::Ask),
+            ("sell", OrderSide::Ask),
+            ("SHORT", OrderSide::Ask),
+            ("short", OrderSide::Ask),
+            ("1", OrderSide::Ask),
+        ] {
+            assert_eq!(OrderSide::from_str(alias), Ok(side));
+            assert_eq!(
+                serde_json::from_value::<OrderSide>(serde_json::json!(alias))
+                    .unwrap(),
+                side
+            );
+        }
+
+        /* numeric sides may also be integers */
+        assert_eq!(
+            serde_json::from_str::<OrderSide>("0").unwrap(),
+            OrderSide::Bid
+        );
+        assert_eq!(
+            serde_json::from_str::<OrderSide>("1").unwrap(),
+            OrderSide::Ask
+        );
+
+        /* sides are always serialized canonically */
+        assert_eq!(serde_json::to_string(&OrderSide::Bid).unwrap(), "\"Bid\"");
+        assert_eq!(serde_json::to_string(&OrderSide::Ask).unwrap(), "\"Ask\"");
+
+        for reject in ["", "2", "buyer", "b", "-1"] {
+            assert_eq!(
+                OrderSide::from_str(reject),
+                Err(OrderParseError::InvalidSide)
+            );
+        }
+        assert!(serde_json::from_str::<OrderSide>("2").is_err());
+        assert!(serde_json::from_str::<OrderSide>("-1").is_err());
+
+        /* errors list the accepted sides */
+        let error: String = serde_json::from_str::<OrderSide>("\"up\"")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Bid, Buy, Long or 0"));
+        assert!(OrderParseError::InvalidSide
+            .to_string()
+            .contains("Ask, Sell, Short or 1"));
+    }
+
+    #[test]
+    pub fn time_in_force_parsing() {
+        assert_eq!(
+            TimeInForce::from_str("GTC").unwrap(),
+            TimeInForce::GoodTillCancel
+        );
+        assert_eq!(
+            TimeInForce::from_str("ioc").unwrap(),
+            TimeInForce::ImmediateOrCancel
+        );
+        assert_eq!(
+            TimeInForce::from_str("FOK").unwrap(),
+            TimeInForce::FillOrKill
+        );
+        assert!(TimeInForce::from_str("GTD").is_err());
+        assert_eq!(TimeInForce::FillOrKill.to_string(), "FOK");
+    }
+
+    /* whole-second timestamps, as the external format has no finer ones */
+    fn signed_order(signed_data: Vec<u8>) -> Order {
+        Order::new(
+            Address::from_low_u64_be(1),
+            Address::repeat_byte(0xab),
+            OrderSide::Ask,
+            U256::from(5),
+            U256::from(10),
+            Utc.timestamp(1895997399, 0),
+            Utc.timestamp(1495987399, 0),
+            signed_data,
+        )
+    }
+
+    #[test]
+    pub fn external_order_round_trip() {
+        let mut signed_data: Vec<u8> = [0xde, 0xad, 0xbe, 0xef].repeat(16);
+        signed_data.push(0x1b);
+        let order: Order = signed_order(signed_data);
+        let external: ExternalOrder = ExternalOrder::from(order.clone());
+
+        assert!(external.user.starts_with("0x"));
+        assert_eq!(
+            external.signed_data,
+            format!("0x{}1b", "deadbeef".repeat(16))
+        );
+        assert_eq!(Order::try_from(external).unwrap(), order);
+    }
+
+    #[test]
+    pub fn external_order_round_trip_beyond_u128() {
+        let huge: U256 = U256::from(u128::MAX) * U256::from(1000) + 7;
+        let order: Order = Order::new(
+            Address::from_low_u64_be(1),
+            Address::repeat_byte(0xab),
+            OrderSide::Bid,
+            huge,
+            huge + 1,
+            Utc.timestamp(1895997399, 0),
+            Utc.timestamp(1495987399, 0),
+            vec![],
+        );
+        let external: ExternalOrder = ExternalOrder::from(order.clone());
+
+        assert_eq!(external.price, huge.to_string());
+        assert_eq!(external.amount, (huge + 1).to_string());
+        assert_eq!(Order::try_from(external).unwrap(), order);
+    }
+
+    #[test]
+    pub fn external_order_unprefixed_hex() {
+        let mut signed_data: Vec<u8> = [0xde, 0xad, 0xbe, 0xef].repeat(16);
+        signed_data.push(0x1b);
+        let order: Order = signed_order(signed_data);
+        let external: ExternalOrder = ExternalOrder {
+            id: hex::encode(order.id),
+            user: hex::encode(order.trader),
+            target_tracer: hex::encode(order.market),
+            signed_data: format!("{}1b", "deadbeef".repeat(16)),
+            ..ExternalOrder::from(order.clone())
+        };
+
+        assert_eq!(Order::try_from(external).unwrap(), order);
+    }
+
+    #[test]
+    pub fn external_order_empty_signed_data() {
+        let order: Order = signed_order(vec![]);
+
+        for signed_data in ["0x", ""] {
+            let external: ExternalOrder = ExternalOrder {
+                signed_data: signed_data.to_string(),
+                ..ExternalOrder::from(order.clone())
+            };
+
+            assert_eq!(Order::try_from(external).unwrap(), order);
+        }
+
+        let external: ExternalOrder = ExternalOrder {
+            signed_data: "0xabc".to_string(),
+            ..ExternalOrder::from(order)
+        };
+        assert_eq!(
+            Order::try_from(external),
+            Err(OrderParseError::InvalidSignedData)
+        );
+    }
+
+    #[test]
+    pub fn create_order_request_unprefixed_signed_data() {
+        let signature: String = format!("{}1c", "ab".repeat(64));
+        let request: CreateOrderRequest = CreateOrderRequest {
+            user: Address::from_low_u64_be(1),
+            target_tracer: Address::repeat_byte(0xab),
+            side: OrderSide::Ask,
+            price: U256::from(5),
+            amount: U256::from(10),
+            expiration: Utc.timestamp(1895997399, 0),
+            created: Utc.timestamp(1495987399, 0),
+            signed_data: signature.clone(),
+            time_in_force: TimeInForce::GoodTillCancel,
+            order_type: OrderType::Limit,
+            trigger_price: None,
+            client_order_id: None,
+            nonce: U256::zero(),
+        };
+
+        /* no byte of the signature is lost, whether prefixed or not */
+        for signed_data in [signature.clone(), format!("0x{}", signature)] {
+            let order: Order =
+                Order::try_from(ExternalOrder::from(CreateOrderRequest {
+                    signed_data,
+                    ..request.clone()
+                }))
+                .unwrap();
+            assert_eq!(order.signed_data, hex::decode(&signature).unwrap());
+        }
+    }
+
+    #[test]
+    pub fn order_nonce() {
+        let order: Order = signed_order(vec![]);
+
+        /* a zero nonce leaves the ID as it was before nonces existed */
+        let tokens: Vec<Token> = vec![
+            Token::Address(order.trader),
+            Token::Address(order.market),
+            Token::Uint(order.price),
+            Token::Uint(order.quantity),
+            Token::Uint(U256::one()),
+            Token::Uint(U256::from(order.expiration.timestamp())),
+            Token::Uint(U256::from(order.created.timestamp())),
+        ];
+        let legacy_id: OrderId =
+            web3::signing::keccak256(&ethabi::encode(&tokens)).into();
+        assert_eq!(order.id, legacy_id);
+        assert_eq!(order.clone().with_nonce(U256::zero()).id, legacy_id);
+
+        let first: Order = order.clone().with_nonce(U256::one());
+        let second: Order = order.clone().with_nonce(U256::from(2));
+        assert_ne!(first.id, legacy_id);
+        assert_ne!(first.id, second.id);
+
+        /* the nonce survives conversion to the external format and back */
+        let external: ExternalOrder = ExternalOrder::from(second.clone());
+        assert_eq!(external.nonce, "2");
+        assert_eq!(Order::try_from(external).unwrap(), second);
+
+        /* orders from before nonces existed have none */
+        let external: ExternalOrder = ExternalOrder {
+            nonce: String::new(),
+            ..ExternalOrder::from(order.clone())
+        };
+        assert_eq!(Order::try_from(external).unwrap(), order);
+    }
+
+    #[test]
+    pub fn external_order_client_order_id() {
+        let order: Order =
+            signed_order(vec![]).with_client_order_id("quote-1".to_string());
+        let external: ExternalOrder = ExternalOrder::from(order.clone());
+
+        assert_eq!(external.client_order_id, "quote-1");
+        assert_eq!(Order::try_from(external).unwrap(), order);
+
+        let external: ExternalOrder = ExternalOrder {
+            client_order_id: "x".repeat(MAX_CLIENT_ORDER_ID_LEN + 1),
+            ..ExternalOrder::from(order)
+        };
+        assert_eq!(
+            Order::try_from(external),
+            Err(OrderParseError::InvalidClientOrderId)
+        );
+    }
+
+    #[test]
+    pub fn external_order_errors_name_field() {
+        let external: ExternalOrder = ExternalOrder::from(signed_order(vec![]));
+
+        let cases: Vec<(ExternalOrder, OrderParseError)> = vec![
+            (
+                ExternalOrder {
+                    user: "0xnope".to_string(),
+                    ..external.clone()
+                },
+                OrderParseError::InvalidHexadecimal { field: "user" },
+            ),
+            (
+                ExternalOrder {
+                    price: "1.5".to_string(),
+                    ..external.clone()
+                },
+                OrderParseError::InvalidDecimal { field: "price" },
+            ),
+            (
+                ExternalOrder {
+                    trigger_price: "-1".to_string(),
+                    ..external.clone()
+                },
+                OrderParseError::InvalidDecimal {
+                    field: "trigger_price",
+                },
+            ),
+            (
+                ExternalOrder {
+                    expiration: "tomorrow".to_string(),
+                    ..external.clone()
+                },
+                OrderParseError::InvalidTimestamp {
+                    field: "expiration",
+                },
+            ),
+            (
+                ExternalOrder {
+                    created: i64::MAX.to_string(),
+                    ..external.clone()
+                },
+                OrderParseError::InvalidTimestamp { field: "created" },
+            ),
+            (
+                ExternalOrder {
+                    created: "-1".to_string(),
+                    ..external.clone()
+                },
+                OrderParseError::InvalidTimestamp { field: "created" },
+            ),
+            (
+                ExternalOrder {
+                    expiration: "-1".to_string(),
+                    ..external.clone()
+                },
+                OrderParseError::InvalidTimestamp {
+                    field: "expiration",
+                },
+            ),
+            (
+                ExternalOrder {
+                    id: "0xzz".to_string(),
+                    ..external
+                },
+                OrderParseError::InvalidHexadecimal { field: "id" },
+            ),
+        ];
+
+        for (external, error) in cases {
+            assert_eq!(Order::try_from(external), Err(error));
+        }
+
+        assert_eq!(
+            OrderParseError::InvalidDecimal { field: "price" }.to_string(),
+            "Invalid price (expected a decimal or 0x-prefixed hexadecimal \
+             integer of at most 256 bits)"
+        );
+        assert_eq!(
+            OrderParseError::InvalidTimestamp { field: "created" }.to_string(),
+            "Invalid created (expected a Unix timestamp in seconds)"
+        );
+    }
+
+    #[test]
+    pub fn external_order_expiry_before_creation() {
+        let order: Order = signed_order(vec![]);
+
+        for expiration in ["1495987399", "1495987398"] {
+            let external: ExternalOrder = ExternalOrder {
+                expiration: expiration.to_string(),
+                ..ExternalOrder::from(order.clone())
+            };
+            assert_eq!(
+                Order::try_from(external),
+                Err(OrderParseError::ExpiryBeforeCreation)
+            );
+        }
+    }
+
+    #[test]
+    pub fn order_timestamps_checked() {
+        let created: DateTime<Utc> = Utc.timestamp(1495987399, 0);
+        let skew: Duration = Duration::minutes(5);
+        let order: Order = Order {
+            created,
+            ..signed_order(vec![])
+        };
+
+        /* creation times exactly at the limit of the skew are accepted */
+        assert_eq!(order.check_timestamps(created, skew), Ok(()));
+        assert_eq!(order.check_timestamps(created - skew, skew), Ok(()));
+        assert_eq!(order.check_timestamps(created + skew, skew), Ok(()));
+
+        /* ...but no further */
+        let second: Duration = Duration::seconds(1);
+        assert_eq!(
+            order.check_timestamps(created - skew - second, skew),
+            Err(TimestampError::CreatedInFuture)
+        );
+        assert_eq!(
+            order.check_timestamps(created + skew + second, skew),
+            Err(TimestampError::CreatedTooOld)
+        );
+
+        /* orders expire at their expiration */
+        let expiring: Order = Order {
+            expiration: created + second,
+            ..order.clone()
+        };
+        assert_eq!(expiring.check_timestamps(created, skew), Ok(()));
+        assert_eq!(
+            expiring.check_timestamps(created + second, skew),
+            Err(TimestampError::Expired)
+        );
+
+        /* a skew too large to add to the time bounds nothing */
+        assert_eq!(
+            order.check_timestamps(created, Duration::max_value()),
+            Ok(())
+        );
+    }
+
+    /* signs the order with the given key, returning r, s, and then v */
+    fn sign(order: &Order, key: &SecretKey, chain_id: u64) -> Vec<u8> {
+        let signature: Signature = SecretKeyRef::new(key)
+            .sign(order.signing_hash(chain_id).as_bytes(), None)
+            .unwrap();
+
+        let mut signed_data: Vec<u8> = signature.r.as_bytes().to_vec();
+        signed_data.extend_from_slice(signature.s.as_bytes());
+        signed_data.push(signature.v as u8);
+        signed_data
+    }
+
+    #[test]
+    pub fn signature_verification() {
+        let key: SecretKey = SecretKey::from_slice(
+            &hex::decode(
+                "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let signer: Address =
+            Address::from_str("2c7536e3605d9c16a7a3d7b1898e529396a65c23")
+                .unwrap();
+        let chain_id: u64 = 1337;
+
+        let order: Order = Order {
+            trader: signer,
+            ..signed_order(vec![])
+        };
+        let signed: Order = Order {
+            signed_data: sign(&order, &key, chain_id),
+            ..order.clone()
+        };
+        assert_eq!(signed.verify_signature(chain_id), Ok(()));
+
+        /* signatures are specific to the chain and to the order's trader */
+        assert_eq!(
+            signed.verify_signature(1),
+            Err(SignatureError::SignerMismatch)
+        );
+        let impersonated: Order = Order {
+            trader: Address::from_low_u64_be(1),
+            ..signed.clone()
+        };
+        assert_eq!(
+            impersonated.verify_signature(chain_id),
+            Err(SignatureError::SignerMismatch)
+        );
+
+        /* malformed signatures are told apart from mismatched ones */
+        let truncated: Order = Order {
+            signed_data: signed.signed_data[..64].to_vec(),
+            ..signed.clone()
+        };
+        assert_eq!(
+            truncated.verify_signature(chain_id),
+            Err(SignatureError::InvalidLength)
+        );
+        let mut bad_v: Order = signed.clone();
+        bad_v.signed_data[64] = 5;
+        assert_eq!(
+            bad_v.verify_signature(chain_id),
+            Err(SignatureError::InvalidRecoveryId)
+        );
+        assert_eq!(
+            order.verify_signature(chain_id),
+            Err(SignatureError::InvalidLength)
+        );
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "server"))]
 mod state_tests {
-    use web3::types::Address;
+    use chrono::{Duration, Utc};
+    use web3::types::{Address, U256};
 
     use crate::book::Book;
-    use crate::OmeState;
+    use crate::book_tests::test_executioner;
+    use crate::order::{Order, OrderSide};
+    use crate::state::OmeState;
 
     #[test]
     pub fn new_state_should_be_empty() {
@@ -75,4 +557,878 @@ mod state_tests {
         state.remove_book(market);
         assert!(state.books().is_empty());
     }
+
+    fn order(market: Address, trader: u64, side: OrderSide, qty: u64) -> Order {
+        Order::new(
+            Address::from_low_u64_be(trader),
+            market,
+            side,
+            U256::from(100),
+            U256::from(qty),
+            Utc::now() + Duration::days(1),
+            Utc::now(),
+            vec![],
+        )
+    }
+
+    /* submits the order to its market and publishes the resulting changes */
+    async fn submit(state: &mut OmeState, order: Order) {
+        let market: Address = order.market;
+        state
+            .book_mut(market)
+            .unwrap()
+            .submit(order, test_executioner())
+            .await
+            .unwrap();
+        state.publish(market);
+    }
+
+    #[tokio::test]
+    pub async fn find_order() {
+        let mut state = OmeState::new();
+        let market: Address = Address::zero();
+        let market2: Address = Address::repeat_byte(1);
+
+        /* orders already in a book are found once it is added */
+        let mut book = Book::new(market);
+        let bid: Order = order(market, 1, OrderSide::Bid, 10);
+        book.submit(bid.clone(), test_executioner()).await.unwrap();
+        state.add_book(book);
+        state.add_book(Book::new(market2));
+
+        let ask: Order = order(market2, 2, OrderSide::Ask, 5);
+        submit(&mut state, ask.clone()).await;
+
+        assert_eq!(state.find_order(bid.id), Some((market, &bid)));
+        assert_eq!(state.find_order(ask.id), Some((market2, &ask)));
+
+        /* a partial fill leaves the maker order open */
+        let taker: Order = order(market, 3, OrderSide::Ask, 4);
+        submit(&mut state, taker.clone()).await;
+
+        let (found_market, found) = state.find_order(bid.id).unwrap();
+        assert_eq!(found_market, market);
+        assert_eq!(found.remaining, U256::from(6));
+        assert!(state.find_order(taker.id).is_none());
+
+        /* ...until partial fills consume it entirely */
+        submit(&mut state, order(market, 3, OrderSide::Ask, 6)).await;
+
+        assert!(state.find_order(bid.id).is_none());
+        assert!(!state.order_markets.contains_key(&bid.id));
+
+        state.remove_book(market2);
+        assert!(state.find_order(ask.id).is_none());
+        assert!(state.order_markets.is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn sync_markets() {
+        let mut state = OmeState::new();
+        let market: Address = Address::zero();
+        let market2: Address = Address::repeat_byte(1);
+        let market3: Address = Address::repeat_byte(2);
+        let now = Utc::now();
+
+        state.add_book(Book::new(market));
+        let bid: Order = order(market, 1, OrderSide::Bid, 10);
+        submit(&mut state, bid.clone()).await;
+        assert!(state.last_market_sync().is_none());
+
+        /* new markets get empty books, and existing books are untouched */
+        let unknown: Vec<Address> = state.sync_markets(&[market, market2], now);
+        assert!(unknown.is_empty());
+        assert_eq!(state.books().len(), 2);
+        assert_eq!(state.book(market2), Some(&Book::new(market2)));
+        assert_eq!(state.find_order(bid.id), Some((market, &bid)));
+        assert_eq!(state.last_market_sync(), Some(now));
+
+        /* books of markets that disappear are reported but kept */
+        let mut unknown: Vec<Address> = state.sync_markets(&[market3], now);
+        unknown.sort();
+        assert_eq!(unknown, vec![market, market2]);
+        assert_eq!(state.books().len(), 3);
+        assert_eq!(state.find_order(bid.id), Some((market, &bid)));
+
+        /* nothing is created while shutting down */
+        state.begin_shutdown();
+        state.sync_markets(&[Address::repeat_byte(3)], now);
+        assert_eq!(state.books().len(), 3);
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod inspect_tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use chrono::Utc;
+    use web3::types::{Address, U256};
+
+    use crate::book::{Book, InvariantViolation, Level, PriceLevel};
+    use crate::order::OrderSide;
+    use crate::state::inspect::{self, MarketDifference, MarketSummary};
+    use crate::state::OmeState;
+    use crate::test_util::{deep_book, order};
+    use crate::util;
+
+    const UNIT: u64 = 1_000_000_000_000_000_000; /* one, at 18 decimals */
+
+    /* a book with a bid at two and an ask at three */
+    async fn quoted_book(market: Address) -> Book {
+        let mut book: Book = Book::new(market);
+        for (trader, side, price) in
+            [(1, OrderSide::Bid, 2 * UNIT), (2, OrderSide::Ask, 3 * UNIT)]
+        {
+            book.submit_at(
+                order(market, trader, side, price),
+                None,
+                Utc::now(),
+            )
+            .await
+            .unwrap();
+        }
+        book
+    }
+
+    #[tokio::test]
+    pub async fn test_inspect_dump_file() {
+        let dir: PathBuf = std::env::temp_dir()
+            .join(format!("ome-inspect-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path: PathBuf = dir.join("dump.json");
+
+        let mut state: OmeState = OmeState::new();
+        state.add_book(quoted_book(Address::from_low_u64_be(2)).await);
+        state.add_book(deep_book(
+            Address::from_low_u64_be(1),
+            OrderSide::Ask,
+            100,
+            3,
+            2,
+        ));
+        util::dump_state(&state, &path).unwrap();
+
+        let read: OmeState = inspect::read(&path).unwrap();
+        let summaries: Vec<MarketSummary> = inspect::inspect(&read);
+        assert_eq!(
+            summaries,
+            vec![
+                MarketSummary {
+                    market: Address::from_low_u64_be(1),
+                    sequence: 0,
+                    orders: 6,
+                    depth: (0, 6),
+                    top: (None, Some(U256::from(100))),
+                    open_notional: U256::zero(), /* worth less than a unit */
+                    paused: false,
+                },
+                MarketSummary {
+                    market: Address::from_low_u64_be(2),
+                    sequence: read
+                        .book(Address::from_low_u64_be(2))
+                        .unwrap()
+                        .sequence,
+                    orders: 2,
+                    depth: (1, 1),
+                    top: (
+                        Some(U256::from(2 * UNIT)),
+                        Some(U256::from(3 * UNIT))
+                    ),
+                    open_notional: U256::from(50),
+                    paused: false,
+                },
+            ]
+        );
+        assert!(inspect::validate(&read).is_empty());
+        assert!(inspect::diff(&state, &read).is_empty());
+
+        /* files that aren't dumps of the state are errors */
+        fs::write(&path, "{").unwrap();
+        assert!(inspect::read(&path).is_err());
+        assert!(inspect::read(&dir.join("missing.json")).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_validate_reports_corrupted_books() {
+        let market: Address = Address::from_low_u64_be(1);
+        let mut corrupted: Book = quoted_book(market).await;
+        corrupted.bids.insert(U256::from(1), Level::new());
+
+        let mut state: OmeState = OmeState::new();
+        state.add_book(quoted_book(Address::from_low_u64_be(2)).await);
+        state.add_book(corrupted);
+
+        assert_eq!(
+            inspect::validate(&state),
+            vec![(
+                market,
+                vec![InvariantViolation::EmptyLevel(PriceLevel {
+                    side: OrderSide::Bid,
+                    stop: false,
+                    price: U256::from(1),
+                })]
+            )]
+        );
+    }
+
+    #[tokio::test]
+    pub async fn test_diff_states() {
+        let (first, second, third) = (
+            Address::from_low_u64_be(1),
+            Address::from_low_u64_be(2),
+            Address::from_low_u64_be(3),
+        );
+
+        let mut before: OmeState = OmeState::new();
+        before.add_book(quoted_book(first).await);
+        before.add_book(quoted_book(second).await);
+
+        let mut after: OmeState = OmeState::new();
+        after.add_book(quoted_book(first).await);
+        after.add_book(Book::new(third));
+        let mut changed: Book = quoted_book(second).await;
+        changed
+            .submit_at(order(second, 3, OrderSide::Bid, UNIT), None, Utc::now())
+            .await
+            .unwrap();
+        after.add_book(changed);
+
+        let differences: Vec<MarketDifference> = inspect::diff(&before, &after);
+        assert_eq!(differences.len(), 2);
+        match &differences[0] {
+            MarketDifference::Changed(market, diff) => {
+                assert_eq!(*market, second);
+                assert_eq!(diff.levels.len(), 1);
+                assert_eq!(diff.levels[0].price, U256::from(UNIT));
+                assert_eq!(diff.top, diff.other_top);
+            }
+            other => panic!("Unexpected difference {:?}", other),
+        }
+        assert_eq!(differences[1], MarketDifference::Added(third));
+        assert_eq!(
+            inspect::diff(&after, &before)[1],
+            MarketDifference::Removed(third)
+        );
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod wal_tests {
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{BufReader, Write};
+    use std::path::{Path, PathBuf};
+
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use web3::types::{Address, U256};
+
+    use crate::book::{Book, ExternalBook};
+    use crate::order::{Order, OrderSide};
+    use crate::state::{OmeState, RestoreError};
+    use crate::wal::{Wal, WalChange, WalError, DEFAULT_WAL_MAX_SIZE};
+
+    /* a fresh directory for the files of a single test */
+    fn test_dir(name: &str) -> PathBuf {
+        let dir: PathBuf = std::env::temp_dir().join(format!(
+            "ome-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn order(
+        trader: u64,
+        side: OrderSide,
+        price: u64,
+        qty: u64,
+        created: DateTime<Utc>,
+        expiration: DateTime<Utc>,
+    ) -> Order {
+        Order::new(
+            Address::from_low_u64_be(trader),
+            Address::zero(),
+            side,
+            U256::from(price),
+            U256::from(qty),
+            expiration,
+            created,
+            vec![],
+        )
+    }
+
+    /* submits the order as the handlers do, logging it */
+    async fn submit(state: &mut OmeState, order: Order, now: DateTime<Utc>) {
+        let market: Address = order.market;
+        let book: &mut Book = state.book_mut(market).unwrap();
+        let sequence: u64 = book.sequence;
+        let change: WalChange = WalChange::Submit {
+            order: order.clone().into(),
+        };
+
+        book.submit_at(order, None, now).await.unwrap();
+        state.log(market, sequence, now, change);
+        state.publish(market);
+    }
+
+    /* replays every file of the log at the given path */
+    async fn replay(state: &mut OmeState, path: &Path) -> usize {
+        let mut replayed: usize = 0;
+
+        for file in Wal::files(path).unwrap() {
+            let reader = BufReader::new(File::open(file).unwrap());
+            replayed += state.replay(reader).await.unwrap();
+        }
+
+        replayed
+    }
+
+    #[tokio::test]
+    pub async fn replay_reconstructs_state() {
+        let dir: PathBuf = test_dir("replay");
+        let wal_path: PathBuf = dir.join("ome.wal");
+        let dump_path: PathBuf = dir.join("ome.json");
+        let market: Address = Address::zero();
+
+        /* well in the past, so replaying as of the wall-clock time would
+         * expire every order */
+        let start: DateTime<Utc> = Utc.timestamp(1_600_000_000, 0);
+        let at = |seconds: i64| start + Duration::seconds(seconds);
+        let expiry: DateTime<Utc> = at(3600);
+
+        let mut state: OmeState = OmeState::new();
+
+        /* rotate upon every change, so that the log spans several files */
+        state.set_wal(Wal::open(&wal_path, 1).unwrap());
+
+        state.add_book(Book::new(market));
+        state.log(
+            market,
+            0,
+            at(0),
+            WalChange::CreateBook {
+                book: Book::new(market),
+            },
+        );
+
+        let ask: Order = order(1, OrderSide::Ask, 100, 10, at(0), expiry);
+        let other_ask: Order = order(2, OrderSide::Ask, 101, 5, at(1), expiry);
+        let lapsing: Order = order(5, OrderSide::Bid, 90, 1, at(1), at(4));
+        submit(&mut state, ask.clone(), at(1)).await;
+        submit(&mut state, other_ask.clone(), at(1)).await;
+        submit(&mut state, lapsing.clone(), at(2)).await;
+        submit(
+            &mut state,
+            order(3, OrderSide::Bid, 100, 4, at(2), expiry),
+            at(2),
+        )
+        .await;
+
+        /* the state is dumped partway through */
+        fs::write(&dump_path, serde_json::to_string(&state).unwrap()).unwrap();
+
+        let book: &mut Book = state.book_mut(market).unwrap();
+        let sequence: u64 = book.sequence;
+        book.amend_at(other_ask.id, U256::from(102), U256::from(6), at(3))
+            .unwrap();
+        state.log(
+            market,
+            sequence,
+            at(3),
+            WalChange::Amend {
+                order_id: other_ask.id,
+                price: U256::from(102),
+                quantity: U256::from(6),
+            },
+        );
+        state.publish(market);
+
+        submit(
+            &mut state,
+            order(4, OrderSide::Bid, 102, 8, at(4), expiry),
+            at(4),
+        )
+        .await;
+
+        let book: &mut Book = state.book_mut(market).unwrap();
+        let sequence: u64 = book.sequence;
+        book.cancel_at(other_ask.id, at(5)).unwrap().unwrap();
+        state.log(
+            market,
+            sequence,
+            at(5),
+            WalChange::Cancel {
+                order_id: other_ask.id,
+            },
+        );
+        state.publish(market);
+
+        /* the lapsed bid is swept, as by the expiry sweeper */
+        let book: &mut Book = state.book_mut(market).unwrap();
+        let sequence: u64 = book.sequence;
+        assert_eq!(book.purge_expired(at(5)).len(), 1);
+        state.log(market, sequence, at(5), WalChange::PurgeExpired);
+        state.publish(market);
+
+        let book: &Book = state.book(market).unwrap();
+        assert!(book.order(lapsing.id).is_none());
+        assert!(book.order(ask.id).is_none());
+        assert_eq!(book.depth(), (0, 0));
+        assert!(Wal::files(&wal_path).unwrap().len() > 1);
+
+        /* changes already reflected in the dump are skipped */
+        let mut restored: OmeState =
+            OmeState::from_dumpfile(&dump_path).unwrap();
+        assert_eq!(replay(&mut restored, &wal_path).await, 4);
+        assert_eq!(restored, state);
+
+        /* ...and replaying from scratch makes every change */
+        let mut replayed: OmeState = OmeState::new();
+        assert_eq!(replay(&mut replayed, &wal_path).await, 9);
+        assert_eq!(replayed, state);
+        assert_eq!(replay(&mut replayed, &wal_path).await, 0);
+
+        /* a change left partially logged by a crash is ignored, and then
+         * discarded once the log is reopened */
+        OpenOptions::new()
+            .append(true)
+            .open(&wal_path)
+            .unwrap()
+            .write_all(b"{\"market\":")
+            .unwrap();
+
+        let mut replayed: OmeState = OmeState::new();
+        assert_eq!(replay(&mut replayed, &wal_path).await, 9);
+        assert_eq!(replayed, state);
+
+        drop(Wal::open(&wal_path, 1).unwrap());
+        assert!(fs::read(&wal_path).unwrap().ends_with(b"\n"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn replay_reloads_books_as_logged() {
+        let dir: PathBuf = test_dir("replay-reload");
+        let wal_path: PathBuf = dir.join("ome.wal");
+        let market: Address = Address::zero();
+
+        /* the order was live when the book was reloaded, but has long since
+         * expired */
+        let start: DateTime<Utc> = Utc.timestamp(1_600_000_000, 0);
+        let at = |seconds: i64| start + Duration::seconds(seconds);
+        let ask: Order = order(1, OrderSide::Ask, 100, 10, at(0), at(60));
+
+        let mut state: OmeState = OmeState::new();
+        state.set_wal(Wal::open(&wal_path, DEFAULT_WAL_MAX_SIZE).unwrap());
+
+        state.add_book(Book::new(market));
+        state.log(
+            market,
+            0,
+            at(0),
+            WalChange::CreateBook {
+                book: Book::new(market),
+            },
+        );
+
+        let mut source: Book = Book::new(market);
+        source.submit_at(ask.clone(), None, at(0)).await.unwrap();
+        let external: ExternalBook = ExternalBook::from(source);
+        state
+            .add_book(Book::from_external_at(external.clone(), at(1)).unwrap());
+        state.log(market, 0, at(1), WalChange::ReloadBook { book: external });
+
+        /* a change to the reloaded order, which needs it to be replayed */
+        let book: &mut Book = state.book_mut(market).unwrap();
+        let sequence: u64 = book.sequence;
+        book.amend_at(ask.id, U256::from(100), U256::from(6), at(2))
+            .unwrap();
+        state.log(
+            market,
+            sequence,
+            at(2),
+            WalChange::Amend {
+                order_id: ask.id,
+                price: U256::from(100),
+                quantity: U256::from(6),
+            },
+        );
+        state.publish(market);
+
+        let mut replayed: OmeState = OmeState::new();
+        assert_eq!(replay(&mut replayed, &wal_path).await, 3);
+        assert_eq!(replayed, state);
+        assert_eq!(
+            replayed
+                .book(market)
+                .unwrap()
+                .order(ask.id)
+                .unwrap()
+                .remaining,
+            U256::from(6)
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn restore_tolerates_missing_dump() {
+        let dir: PathBuf = test_dir("restore");
+        let wal_path: PathBuf = dir.join("ome.wal");
+        let dump_path: PathBuf = dir.join("ome.json");
+        let market: Address = Address::zero();
+        /* whole seconds, as orders are logged in their external form */
+        let now: DateTime<Utc> = Utc.timestamp(Utc::now().timestamp(), 0);
+        let expiry: DateTime<Utc> = now + Duration::days(365);
+
+        let mut state: OmeState = OmeState::new();
+        state.set_wal(Wal::open(&wal_path, DEFAULT_WAL_MAX_SIZE).unwrap());
+        state.add_book(Book::new(market));
+        state.log(
+            market,
+            0,
+            now,
+            WalChange::CreateBook {
+                book: Book::new(market),
+            },
+        );
+        submit(
+            &mut state,
+            order(1, OrderSide::Ask, 100, 10, now, expiry),
+            now,
+        )
+        .await;
+
+        /* with neither a dump nor a log, there is nothing to restore */
+        let restored: OmeState =
+            OmeState::restore(&dump_path, None).await.unwrap();
+        assert_eq!(restored, OmeState::new());
+
+        /* without a dump, every book comes from the log... */
+        let restored: OmeState = OmeState::restore(&dump_path, Some(&wal_path))
+            .await
+            .unwrap();
+        assert_eq!(restored, state);
+
+        /* ...as it does if the dump is malformed */
+        fs::write(&dump_path, "{").unwrap();
+        let restored: OmeState = OmeState::restore(&dump_path, Some(&wal_path))
+            .await
+            .unwrap();
+        assert_eq!(restored, state);
+
+        /* whereas a corrupted log can't be restored from */
+        OpenOptions::new()
+            .append(true)
+            .open(&wal_path)
+            .unwrap()
+            .write_all(b"garbage\n{}\n")
+            .unwrap();
+        assert!(matches!(
+            OmeState::restore(&dump_path, Some(&wal_path)).await,
+            Err(RestoreError::Replay(_, WalError::Malformed(3)))
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod util_tests {
+    use ethereum_types::{Address, U256, U512};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use crate::api::CreateOrderRequest;
+    use crate::book::Book;
+    use crate::util::{mul_div, parse_signed_data, parse_u256, scale_18};
+
+    /* a request to create an order at the given price, as JSON */
+    fn order_request(price: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "user": "0x0000000000000000000000000000000000000003",
+            "target_tracer": "0x0000000000000000000000000000000000000001",
+            "side": "Bid",
+            "price": price,
+            "amount": "0x0a",
+            "expiration": 1895997399,
+            "created": 1495987399,
+            "signed_data": "0x",
+            "trigger_price": null,
+        })
+    }
+
+    #[test]
+    pub fn test_parse_u256_forms() {
+        assert_eq!(parse_u256("4380090000"), Some(U256::from(4380090000u64)));
+        assert_eq!(parse_u256("0x10512de90"), Some(U256::from(4380090000u64)));
+        assert_eq!(parse_u256("0X10512DE90"), Some(U256::from(4380090000u64)));
+        assert_eq!(parse_u256("0x0"), Some(U256::zero()));
+        assert_eq!(
+            parse_u256(&format!("0x{}", "f".repeat(64))),
+            Some(U256::max_value())
+        );
+        assert_eq!(
+            parse_u256(&format!("0x00{}", "f".repeat(64))),
+            Some(U256::max_value())
+        );
+
+        for garbage in
+            &["", "0x", "-1", "+1", "1.5", "1e18", " 1", "0xg", "ten"]
+        {
+            assert_eq!(parse_u256(garbage), None, "{} was accepted", garbage);
+        }
+
+        /* values beyond 256 bits are rejected rather than wrapped */
+        assert_eq!(parse_u256(&format!("0x1{}", "0".repeat(64))), None);
+        assert_eq!(parse_u256(&(U256::max_value().to_string() + "0")), None);
+    }
+
+    #[test]
+    pub fn test_deserialize_u256_forms() {
+        for price in &[
+            serde_json::json!("4380090000"),
+            serde_json::json!("0x10512de90"),
+            serde_json::json!(4380090000u64),
+        ] {
+            let request: CreateOrderRequest =
+                serde_json::from_value(order_request(price.clone())).unwrap();
+            assert_eq!(request.price, U256::from(4380090000u64));
+            assert_eq!(request.amount, U256::from(10));
+            assert_eq!(request.trigger_price, None);
+
+            /* whatever the input, integers are written as decimal strings */
+            let written: serde_json::Value =
+                serde_json::to_value(&request).unwrap();
+            assert_eq!(written["price"], "4380090000");
+        }
+
+        let mut request: serde_json::Value =
+            order_request(serde_json::json!("1"));
+        request["trigger_price"] = serde_json::json!("0x05");
+        let request: CreateOrderRequest =
+            serde_json::from_value(request).unwrap();
+        assert_eq!(request.trigger_price, Some(U256::from(5)));
+
+        for garbage in &[
+            serde_json::json!("ten"),
+            serde_json::json!(-1),
+            serde_json::json!(1.5),
+            serde_json::json!(true),
+        ] {
+            let error: String = serde_json::from_value::<CreateOrderRequest>(
+                order_request(garbage.clone()),
+            )
+            .unwrap_err()
+            .to_string();
+            assert!(
+                error.contains("expected a decimal string, a 0x-prefixed"),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_parse_signed_data() {
+        let signature: String = "cafebeef".repeat(16) + "1b";
+        let bytes: Vec<u8> = hex::decode(&signature).unwrap();
+
+        assert_eq!(
+            parse_signed_data(&format!("0x{}", signature)),
+            Some(bytes.clone())
+        );
+        assert_eq!(
+            parse_signed_data(&format!("0X{}", signature)),
+            Some(bytes.clone())
+        );
+        assert_eq!(parse_signed_data(&signature), Some(bytes));
+        assert_eq!(parse_signed_data(""), Some(vec![]));
+        assert_eq!(parse_signed_data("0x"), Some(vec![]));
+
+        /* odd lengths, other lengths, and non-hexadecimal strings */
+        assert_eq!(parse_signed_data(&signature[1..]), None);
+        assert_eq!(parse_signed_data(&format!("0x{}", &signature[2..])), None);
+        assert_eq!(parse_signed_data(&format!("0x{}00", signature)), None);
+        assert_eq!(parse_signed_data("0xdeadbeef"), None);
+        assert_eq!(parse_signed_data(&"zz".repeat(65)), None);
+    }
+
+    #[test]
+    pub fn test_book_round_trip_beyond_u128() {
+        let huge: U256 = U256::from(u128::MAX) * U256::from(1000) + 7;
+        let mut book: Book = Book::new(Address::from_low_u64_be(1));
+        book.ltp = huge;
+        book.spread = huge - 1;
+        book.tick_size = huge - 2;
+
+        /* written in full, as decimal strings */
+        let written: serde_json::Value = serde_json::to_value(&book).unwrap();
+        assert_eq!(written["LTP"], huge.to_string());
+        assert_eq!(written["spread"], (huge - 1).to_string());
+        assert_eq!(written["tick_size"], (huge - 2).to_string());
+
+        let read: Book = serde_json::from_value(written).unwrap();
+        assert_eq!(read.ltp, huge);
+        assert_eq!(read.spread, huge - 1);
+        assert_eq!(read.tick_size, huge - 2);
+    }
+
+    #[test]
+    pub fn test_mul_div_boundaries() {
+        let max: U256 = U256::max_value();
+
+        assert_eq!(mul_div(max, max, max), Some(max));
+        assert_eq!(mul_div(max, 2.into(), 2.into()), Some(max));
+        assert_eq!(mul_div(max, 3.into(), 2.into()), None);
+        assert_eq!(mul_div(max, 2.into(), 1.into()), None);
+        assert_eq!(mul_div(U256::zero(), max, 1.into()), Some(U256::zero()));
+        assert_eq!(mul_div(7.into(), 3.into(), 2.into()), Some(10.into()));
+
+        /* a zero denominator is an error rather than a panic */
+        assert_eq!(mul_div(max, max, U256::zero()), None);
+        assert_eq!(mul_div(U256::zero(), U256::zero(), U256::zero()), None);
+    }
+
+    #[test]
+    pub fn test_scale_18_boundaries() {
+        let one: U256 = U256::exp10(18);
+        let max: U256 = U256::max_value();
+
+        assert_eq!(scale_18(one, one), Some(one));
+        assert_eq!(scale_18(one * 3 / 2, one * 2), Some(one * 3));
+        assert_eq!(scale_18(U256::one(), U256::one()), Some(U256::zero()));
+        assert_eq!(scale_18(max, one), Some(max));
+        assert_eq!(scale_18(max, one + 1), None);
+        assert_eq!(scale_18(max, max), None);
+    }
+
+    /* a value of a random number of bits, so that all magnitudes are seen */
+    fn random_u256(rng: &mut StdRng) -> U256 {
+        U256(rng.gen()) >> rng.gen_range(0..256usize)
+    }
+
+    #[test]
+    pub fn test_mul_div_random_values() {
+        let mut rng: StdRng = StdRng::seed_from_u64(0x0e);
+
+        for _ in 0..10_000 {
+            let a: U256 = random_u256(&mut rng);
+            let b: U256 = random_u256(&mut rng);
+            let denominator: U256 = random_u256(&mut rng);
+
+            if denominator.is_zero() {
+                assert_eq!(mul_div(a, b, denominator), None);
+                continue;
+            }
+
+            let product: U512 = a.full_mul(b);
+            let denominator_512: U512 = U512::from(denominator);
+
+            /* the quotient is rounded down, or else doesn't fit */
+            match mul_div(a, b, denominator) {
+                Some(quotient) => {
+                    let quotient: U512 = U512::from(quotient);
+                    assert!(quotient * denominator_512 <= product);
+                    assert!(product < (quotient + 1) * denominator_512);
+                }
+                None => assert!(
+                    product / denominator_512 > U512::from(U256::max_value())
+                ),
+            }
+
+            assert_eq!(mul_div(a, b, denominator), mul_div(b, a, denominator));
+        }
+    }
+}
+
+#[cfg(test)]
+mod history_tests {
+    use chrono::Utc;
+    use web3::types::{H256, U256};
+
+    use crate::history::{
+        OrderEvent, OrderEventKind, OrderHistory, MAX_CLOSED_ORDERS,
+        MAX_ORDER_EVENTS,
+    };
+
+    fn event(kind: OrderEventKind) -> OrderEvent {
+        OrderEvent::new(kind, 0, Utc::now(), U256::zero())
+    }
+
+    #[test]
+    pub fn test_oldest_closed_orders_are_evicted() {
+        let mut history: OrderHistory = OrderHistory::default();
+        let open: H256 = H256::from_low_u64_be(u64::MAX);
+
+        history.record(open, event(OrderEventKind::Accepted));
+
+        for i in 0..=MAX_CLOSED_ORDERS as u64 {
+            history.record(
+                H256::from_low_u64_be(i),
+                event(OrderEventKind::Accepted),
+            );
+            history.record(
+                H256::from_low_u64_be(i),
+                event(OrderEventKind::Killed),
+            );
+        }
+
+        /* only the order that closed first is evicted */
+        assert!(history.get(H256::from_low_u64_be(0)).is_none());
+        assert!(history.get(H256::from_low_u64_be(1)).is_some());
+        assert!(history
+            .get(H256::from_low_u64_be(MAX_CLOSED_ORDERS as u64))
+            .is_some());
+
+        /* open orders are never evicted */
+        assert!(history.get(open).is_some());
+    }
+
+    #[test]
+    pub fn test_reopened_orders_are_not_evicted() {
+        let mut history: OrderHistory = OrderHistory::default();
+        let reopened: H256 = H256::from_low_u64_be(u64::MAX);
+
+        history.record(reopened, event(OrderEventKind::Cancelled));
+        history.record(reopened, event(OrderEventKind::Accepted));
+
+        for i in 0..=MAX_CLOSED_ORDERS as u64 {
+            history.record(
+                H256::from_low_u64_be(i),
+                event(OrderEventKind::Killed),
+            );
+        }
+
+        assert!(history.get(reopened).is_some());
+        assert!(history.get(H256::from_low_u64_be(0)).is_none());
+    }
+
+    #[test]
+    pub fn test_long_histories_are_truncated() {
+        let mut history: OrderHistory = OrderHistory::default();
+        let id: H256 = H256::from_low_u64_be(1);
+
+        history.record(id, event(OrderEventKind::Accepted));
+
+        for _ in 1..MAX_ORDER_EVENTS {
+            history.record(id, event(OrderEventKind::Amended));
+        }
+
+        assert!(!history.get(id).unwrap().truncated);
+
+        history.record(id, event(OrderEventKind::Filled));
+
+        /* the earliest event makes way for the latest */
+        let events = &history.get(id).unwrap().events;
+        assert!(history.get(id).unwrap().truncated);
+        assert_eq!(events.len(), MAX_ORDER_EVENTS);
+        assert_eq!(events.front().unwrap().kind, OrderEventKind::Amended);
+        assert_eq!(events.back().unwrap().kind, OrderEventKind::Filled);
+    }
 }
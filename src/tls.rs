@@ -0,0 +1,182 @@
+//! Contains logic for loading the TLS certificate and private key, so that
+//! misconfiguration is reported before the OME starts rather than when the
+//! HTTP server first tries to use them
+use std::fs;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+use rustls::internal::pemfile;
+use rustls::sign::{self, Signer, SigningKey};
+use rustls::{Certificate, PrivateKey, SignatureScheme};
+use thiserror::Error;
+use webpki::{EndEntityCert, SignatureAlgorithm};
+
+use crate::args::{Arguments, TlsVersion};
+
+/* signed with the private key to check that it matches the certificate */
+const PROBE: &[u8] = b"tracer-ome tls key check";
+
+/// Represents the reasons the TLS configuration cannot be used
+///
+/// Each names the offending file, so that it can be reported as is.
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("{0}: Failed to read file: {1}")]
+    Read(PathBuf, std::io::Error),
+    #[error("{0}: Malformed PEM")]
+    Malformed(PathBuf),
+    #[error("{0}: No certificates found (expected PEM)")]
+    NoCertificates(PathBuf),
+    #[error("{0}: Invalid certificate: {1:?}")]
+    InvalidCertificate(PathBuf, webpki::Error),
+    #[error("{0}: No private key found (expected PKCS#8 or RSA PEM)")]
+    NoKey(PathBuf),
+    #[error("{0}: Unsupported private key type")]
+    UnsupportedKey(PathBuf),
+    #[error("{0}: Private key does not match the certificate {1}")]
+    KeyMismatch(PathBuf, PathBuf),
+    #[error(
+        "TLS {0} can't be required, as the HTTP server always accepts TLS \
+         1.2 (terminate TLS in front of the OME instead)"
+    )]
+    UnsupportedMinVersion(TlsVersion),
+}
+
+/// Represents a certificate chain and the private key of its leaf, as checked
+/// by `load`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TlsIdentity {
+    pub certificates: Vec<u8>, /* PEM, leaf first and then intermediates */
+    pub private_key: Vec<u8>,  /* PEM */
+}
+
+/// Loads and checks the TLS configuration given by the arguments
+///
+/// See `load_files`. The HTTP server never negotiates anything older than TLS
+/// 1.2, so that is the only minimum version that can be honoured.
+pub fn load(arguments: &Arguments) -> Result<TlsIdentity, TlsError> {
+    if arguments.tls_min_version > TlsVersion::V1_2 {
+        return Err(TlsError::UnsupportedMinVersion(arguments.tls_min_version));
+    }
+
+    load_files(
+        &arguments.certificate_path,
+        arguments.tls_chain_path.as_deref(),
+        &arguments.private_key_path,
+    )
+}
+
+/// Reads the certificate (optionally followed by intermediates in a separate
+/// chain file) and the private key, checking that each parses as PEM and that
+/// the key belongs to the leaf certificate
+///
+/// The certificate file may itself hold the full chain. As with the HTTP
+/// server, the first PKCS#8 key in the key file is used, or failing that the
+/// first RSA key.
+pub fn load_files(
+    certificate_path: &Path,
+    chain_path: Option<&Path>,
+    private_key_path: &Path,
+) -> Result<TlsIdentity, TlsError> {
+    let mut certificates: Vec<u8> = read(certificate_path)?;
+    let leaf: Certificate =
+        read_certificates(certificate_path, &certificates)?.remove(0);
+
+    if let Some(path) = chain_path {
+        let chain: Vec<u8> = read(path)?;
+        read_certificates(path, &chain)?;
+        if !certificates.ends_with(b"\n") {
+            certificates.push(b'\n');
+        }
+        certificates.extend(chain);
+    }
+
+    let private_key: Vec<u8> = read(private_key_path)?;
+    let key: PrivateKey = read_private_key(private_key_path, &private_key)?;
+
+    let cert: EndEntityCert = EndEntityCert::from(&leaf.0).map_err(|e| {
+        TlsError::InvalidCertificate(certificate_path.to_path_buf(), e)
+    })?;
+    let signing_key: Box<dyn SigningKey> = sign::any_supported_type(&key)
+        .map_err(|()| {
+            TlsError::UnsupportedKey(private_key_path.to_path_buf())
+        })?;
+
+    if !key_matches(&cert, signing_key.as_ref()) {
+        return Err(TlsError::KeyMismatch(
+            private_key_path.to_path_buf(),
+            certificate_path.to_path_buf(),
+        ));
+    }
+
+    Ok(TlsIdentity {
+        certificates,
+        private_key,
+    })
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, TlsError> {
+    fs::read(path).map_err(|e| TlsError::Read(path.to_path_buf(), e))
+}
+
+/* every certificate in the file, of which there must be at least one */
+fn read_certificates(
+    path: &Path,
+    mut pem: &[u8],
+) -> Result<Vec<Certificate>, TlsError> {
+    let certificates: Vec<Certificate> = pemfile::certs(&mut pem)
+        .map_err(|()| TlsError::Malformed(path.to_path_buf()))?;
+
+    if certificates.is_empty() {
+        return Err(TlsError::NoCertificates(path.to_path_buf()));
+    }
+
+    Ok(certificates)
+}
+
+fn read_private_key(path: &Path, pem: &[u8]) -> Result<PrivateKey, TlsError> {
+    type Parser = fn(&mut dyn BufRead) -> Result<Vec<PrivateKey>, ()>;
+    let parsers: [Parser; 2] =
+        [pemfile::pkcs8_private_keys, pemfile::rsa_private_keys];
+
+    for parser in &parsers {
+        let mut reader: &[u8] = pem;
+        let mut keys: Vec<PrivateKey> = parser(&mut reader)
+            .map_err(|()| TlsError::Malformed(path.to_path_buf()))?;
+        if !keys.is_empty() {
+            return Ok(keys.remove(0));
+        }
+    }
+
+    Err(TlsError::NoKey(path.to_path_buf()))
+}
+
+/* whether a signature made with the key verifies against the certificate */
+fn key_matches(cert: &EndEntityCert, key: &dyn SigningKey) -> bool {
+    let signer: Box<dyn Signer> = match key.choose_scheme(&[
+        SignatureScheme::ECDSA_NISTP256_SHA256,
+        SignatureScheme::ECDSA_NISTP384_SHA384,
+        SignatureScheme::ED25519,
+        SignatureScheme::RSA_PKCS1_SHA256,
+    ]) {
+        Some(t) => t,
+        None => return false,
+    };
+
+    let algorithm: &SignatureAlgorithm = match signer.get_scheme() {
+        SignatureScheme::ECDSA_NISTP256_SHA256 => &webpki::ECDSA_P256_SHA256,
+        SignatureScheme::ECDSA_NISTP384_SHA384 => &webpki::ECDSA_P384_SHA384,
+        SignatureScheme::ED25519 => &webpki::ED25519,
+        SignatureScheme::RSA_PKCS1_SHA256 => {
+            &webpki::RSA_PKCS1_2048_8192_SHA256
+        }
+        _ => return false,
+    };
+
+    match signer.sign(PROBE) {
+        Ok(signature) => {
+            cert.verify_signature(algorithm, PROBE, &signature).is_ok()
+        }
+        Err(_e) => false,
+    }
+}
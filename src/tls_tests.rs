@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+
+use crate::tls::{self, TlsError, TlsIdentity};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/tls")
+        .join(name)
+}
+
+#[test]
+pub fn test_load_valid_pair() {
+    let identity: TlsIdentity =
+        tls::load_files(&fixture("cert.pem"), None, &fixture("key.pem"))
+            .unwrap();
+    assert_eq!(
+        identity.certificates,
+        std::fs::read(fixture("cert.pem")).unwrap()
+    );
+    assert_eq!(
+        identity.private_key,
+        std::fs::read(fixture("key.pem")).unwrap()
+    );
+}
+
+#[test]
+pub fn test_load_full_chain() {
+    let identity: TlsIdentity = tls::load_files(
+        &fixture("cert.pem"),
+        Some(&fixture("chain.pem")),
+        &fixture("key.pem"),
+    )
+    .unwrap();
+    let pem: String = String::from_utf8(identity.certificates).unwrap();
+    assert_eq!(pem.matches("BEGIN CERTIFICATE").count(), 2);
+    assert!(
+        pem.starts_with(&std::fs::read_to_string(fixture("cert.pem")).unwrap())
+    );
+}
+
+#[test]
+pub fn test_load_mismatched_pair() {
+    match tls::load_files(&fixture("cert.pem"), None, &fixture("other_key.pem"))
+    {
+        Err(TlsError::KeyMismatch(key, cert)) => {
+            assert_eq!(key, fixture("other_key.pem"));
+            assert_eq!(cert, fixture("cert.pem"));
+        }
+        other => panic!("Unexpected result {:?}", other),
+    }
+
+    /* the leaf is what the key is checked against, not the intermediates */
+    assert!(matches!(
+        tls::load_files(
+            &fixture("chain.pem"),
+            Some(&fixture("cert.pem")),
+            &fixture("key.pem")
+        ),
+        Err(TlsError::KeyMismatch(_, _))
+    ));
+}
+
+#[test]
+pub fn test_load_garbage() {
+    match tls::load_files(&fixture("garbage.pem"), None, &fixture("key.pem")) {
+        Err(TlsError::NoCertificates(path)) => {
+            assert_eq!(path, fixture("garbage.pem"))
+        }
+        other => panic!("Unexpected result {:?}", other),
+    }
+    assert!(matches!(
+        tls::load_files(
+            &fixture("cert.pem"),
+            Some(&fixture("garbage.pem")),
+            &fixture("key.pem")
+        ),
+        Err(TlsError::NoCertificates(_))
+    ));
+    match tls::load_files(&fixture("cert.pem"), None, &fixture("garbage.pem")) {
+        Err(TlsError::NoKey(path)) => assert_eq!(path, fixture("garbage.pem")),
+        other => panic!("Unexpected result {:?}", other),
+    }
+
+    /* a key where the certificate should be isn't mistaken for one */
+    assert!(matches!(
+        tls::load_files(&fixture("key.pem"), None, &fixture("key.pem")),
+        Err(TlsError::NoCertificates(_))
+    ));
+}
+
+#[test]
+pub fn test_load_missing_file() {
+    let error: TlsError =
+        tls::load_files(&fixture("missing.pem"), None, &fixture("key.pem"))
+            .unwrap_err();
+    assert!(matches!(error, TlsError::Read(_, _)));
+
+    /* the error is a single line naming the file */
+    let message: String = error.to_string();
+    assert!(message.contains("missing.pem"), "{}", message);
+    assert_eq!(message.lines().count(), 1);
+}
@@ -1,14 +1,20 @@
+use std::convert::TryFrom;
+use std::fmt;
+#[cfg(feature = "server")]
 use std::fs;
+#[cfg(feature = "server")]
+use std::io;
 use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::str::FromStr;
 
-use ethereum_types::U256;
-use serde::de::{Error, Unexpected};
-use serde::{Deserialize, Deserializer, Serializer};
+use ethereum_types::{U256, U512};
+use serde::de::{Error, Unexpected, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+#[cfg(feature = "server")]
 use crate::state::OmeState;
 
-/// Helper to convert from hexadecimal strings to decimal strings
+/// Helper to write integers as decimal strings
 ///
 /// This is necessary to override serde's defaults for the underlying field
 /// types we're using.
@@ -16,36 +22,207 @@ pub fn from_hex_se<S>(x: &U256, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
-    /* try to convert to an unsigned 128-bit integer, otherwise strip high bits */
-    let casted_val: u128 = match *x {
-        x if x <= U256::from(u128::MAX) => x.as_u128(),
-        _ => x.low_u128(),
-    };
-
-    serializer.serialize_u128(casted_val)
+    /* written in full as a string, as JSON numbers can't hold 256 bits */
+    serializer.serialize_str(&x.to_string())
 }
 
-/// Helper to convert from hexadecimal strings to decimal strings
+/// Helper to read integers in any of the forms clients send them in
 ///
-/// This is necessary to override serde's defaults for the underlying field
-/// types we're using.
+/// Decimal strings, `0x`-prefixed hexadecimal strings (as encoded for the
+/// contracts) and JSON integers are all accepted (see `parse_u256`).
+/// Integers beyond 64 bits must be sent as strings, as JSON numbers that large
+/// lose precision.
 pub fn from_hex_de<'de, D>(deserializer: D) -> Result<U256, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let s: String = Deserialize::deserialize(deserializer)?;
-    U256::from_dec_str(&s).map_err(|_e| {
-        D::Error::invalid_type(
-            Unexpected::Other("non-decimal string"),
-            &"decimal string",
+    deserializer.deserialize_any(U256Visitor)
+}
+
+/// Optional counterpart to `from_hex_se`
+pub fn from_hex_opt_se<S>(
+    x: &Option<U256>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match x {
+        Some(x) => from_hex_se(x, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Optional counterpart to `from_hex_de`
+pub fn from_hex_opt_de<'de, D>(
+    deserializer: D,
+) -> Result<Option<U256>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value: Option<WireU256> = Deserialize::deserialize(deserializer)?;
+    Ok(value.map(|t| t.0))
+}
+
+/// Parses an integer from either a decimal string or a `0x`-prefixed
+/// hexadecimal string, returning `None` if it is neither (or doesn't fit in
+/// 256 bits)
+pub fn parse_u256(s: &str) -> Option<U256> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(digits) => {
+            let significant: &str = digits.trim_start_matches('0');
+
+            /* longer strings would overflow (and `from_str` would panic) */
+            if digits.is_empty()
+                || significant.len() > 64
+                || !digits.chars().all(|t| t.is_ascii_hexdigit())
+            {
+                return None;
+            }
+
+            U256::from_str(significant).ok()
+        }
+        None if !s.is_empty() && s.chars().all(|t| t.is_ascii_digit()) => {
+            U256::from_dec_str(s).ok()
+        }
+        None => None,
+    }
+}
+
+/* an integer in any of the forms accepted by `from_hex_de` */
+struct WireU256(U256);
+
+impl<'de> Deserialize<'de> for WireU256 {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        from_hex_de(deserializer).map(WireU256)
+    }
+}
+
+struct U256Visitor;
+
+impl<'de> Visitor<'de> for U256Visitor {
+    type Value = U256;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(
+            "a decimal string, a 0x-prefixed hexadecimal string, or a \
+             non-negative integer",
         )
-    })
+    }
+
+    fn visit_u64<E: Error>(self, value: u64) -> Result<U256, E> {
+        Ok(U256::from(value))
+    }
+
+    fn visit_i64<E: Error>(self, value: i64) -> Result<U256, E> {
+        if value < 0 {
+            return Err(E::invalid_value(Unexpected::Signed(value), &self));
+        }
+
+        Ok(U256::from(value as u64))
+    }
+
+    fn visit_str<E: Error>(self, value: &str) -> Result<U256, E> {
+        parse_u256(value)
+            .ok_or_else(|| E::invalid_value(Unexpected::Str(value), &self))
+    }
+}
+
+/// Returns `a * b / denominator`, rounded down, or `None` if the denominator
+/// is zero or the result doesn't fit in 256 bits
+///
+/// The product is taken at 512 bits, so it can't overflow however large `a`
+/// and `b` are.
+pub fn mul_div(a: U256, b: U256, denominator: U256) -> Option<U256> {
+    if denominator.is_zero() {
+        return None;
+    }
+
+    U256::try_from(a.full_mul(b) / U512::from(denominator)).ok()
+}
+
+/// Returns the product of two fixed-point numbers with 18 decimal places
+/// (e.g., the notional value of an amount at a price), rounded down, or
+/// `None` if it doesn't fit in 256 bits
+pub fn scale_18(a: U256, b: U256) -> Option<U256> {
+    mul_div(a, b, U256::exp10(18))
+}
+
+/// Strips an optional `0x` (or `0X`) prefix from a hexadecimal string
+pub fn strip_hex_prefix(s: &str) -> &str {
+    s.strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s)
+}
+
+/// Displays bytes as a `0x`-prefixed hexadecimal string
+pub struct Hex<'a>(pub &'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "0x")?;
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+/// Serializes a value as the string it displays as, without allocating the
+/// string itself
+pub struct AsString<T>(pub T);
+
+impl<T: fmt::Display> Serialize for AsString<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(&self.0)
+    }
+}
+
+/// The length (in bytes) of the signature an order may carry, i.e., `r`, `s`,
+/// and then `v`
+pub const SIGNATURE_LEN: usize = 65;
+
+/// Parses the signed data of an order from a hexadecimal string, with or
+/// without a `0x` prefix, returning `None` if it isn't hexadecimal or is
+/// neither empty nor `SIGNATURE_LEN` bytes long
+pub fn parse_signed_data(s: &str) -> Option<Vec<u8>> {
+    match hex::decode(strip_hex_prefix(s)) {
+        Ok(t) if t.is_empty() || t.len() == SIGNATURE_LEN => Some(t),
+        _ => None,
+    }
+}
+
+/// Compares two byte strings in time independent of their contents, so that
+/// secrets can't be guessed byte by byte from how long comparisons take
+///
+/// Only the lengths of the strings can be learnt from the timing.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
 }
 
 pub fn is_existing_state(path: &Path) -> bool {
     path.exists()
 }
 
-pub fn dump_state(state: Arc<Mutex<OmeState>>, path: &Path) {
-    fs::write(path, serde_json::to_string(&state).unwrap()).unwrap()
+/// Writes the entire state of the OME to the dump file at the given path
+///
+/// The dump is written to a temporary file which then replaces the dump file,
+/// so that a crash partway through never leaves a truncated dump behind.
+/// Returns the size of the dump in bytes.
+#[cfg(feature = "server")]
+pub fn dump_state(state: &OmeState, path: &Path) -> io::Result<u64> {
+    let mut temporary = path.as_os_str().to_os_string();
+    temporary.push(".tmp");
+
+    let dump: String = serde_json::to_string(state)?;
+    fs::write(&temporary, &dump)?;
+    fs::rename(&temporary, path)?;
+    Ok(dump.len() as u64)
 }
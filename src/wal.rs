@@ -0,0 +1,237 @@
+//! Contains logic for the write-ahead log of changes made to order books
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use chrono::{DateTime, Utc};
+use ethereum_types::U256;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use web3::types::Address;
+
+use crate::book::{Book, ExternalBook};
+use crate::order::{ExternalOrder, OrderId};
+
+/// The default size (in bytes) beyond which the write-ahead log is rotated
+pub const DEFAULT_WAL_MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Represents a single change made to an order book, as recorded in the
+/// write-ahead log
+///
+/// Changes are recorded along with the time they were made at, as matching
+/// (in particular, the expiry of orders) depends upon it.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WalEntry {
+    pub market: Address,
+    pub sequence: u64, /* of the book immediately before the change */
+    pub timestamp: DateTime<Utc>, /* time the change was made at */
+    pub change: WalChange,
+}
+
+impl WalEntry {
+    /// Constructor for the `WalEntry` type
+    pub fn new(
+        market: Address,
+        sequence: u64,
+        timestamp: DateTime<Utc>,
+        change: WalChange,
+    ) -> Self {
+        Self {
+            market,
+            sequence,
+            timestamp,
+            change,
+        }
+    }
+}
+
+/// Represents the kinds of changes made to order books
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
+pub enum WalChange {
+    CreateBook {
+        book: Book, /* the new (empty) book */
+    },
+    Submit {
+        order: ExternalOrder, /* the incoming order in its entirety */
+    },
+    Replace {
+        order_id: OrderId, /* the cancelled order */
+        order: ExternalOrder,
+    },
+    Amend {
+        order_id: OrderId,
+        price: U256,
+        quantity: U256,
+    },
+    Cancel {
+        order_id: OrderId,
+    },
+    CancelAll {
+        trader: Address,
+    },
+    PurgeExpired, /* expired orders were removed by the sweeper */
+    SetPaused {
+        paused: bool, /* whether the book now refuses new orders */
+    },
+    SetSizeLimits {
+        max_quantity: Option<U256>, /* the caps now in place (if any) */
+        max_notional: Option<U256>,
+    },
+    ReloadBook {
+        book: ExternalBook, /* as fetched, replacing the book entirely */
+    },
+}
+
+/// Represents the reasons the write-ahead log cannot be replayed
+#[derive(Debug, Error)]
+pub enum WalError {
+    #[error("Failed to read write-ahead log: {0}")]
+    Io(#[from] io::Error),
+    #[error("Malformed write-ahead log entry on line {0}")]
+    Malformed(usize),
+}
+
+/// Represents an append-only log of the changes made to order books
+///
+/// Each change is written as a single line of JSON, and is flushed to disk
+/// before `Wal::append` returns. Once the log grows beyond its maximum size,
+/// it is rotated by renaming it to `<path>.<n>` (where `n` increases with each
+/// rotation) and starting afresh.
+#[derive(Debug)]
+pub struct Wal {
+    path: PathBuf,
+    file: File,
+    size: u64,     /* of the file currently being written */
+    max_size: u64, /* beyond which the log is rotated */
+}
+
+/// A write-ahead log shared by the state and the tasks hosting order books
+/// (see `actor`)
+pub type SharedWal = Arc<Mutex<Wal>>;
+
+/// Records a change made to an order book in a shared write-ahead log
+///
+/// Failures are logged rather than returned, as the change has already been
+/// made by the time it is recorded.
+pub fn record(wal: &SharedWal, entry: WalEntry) {
+    let mut wal = wal.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Err(e) = wal.append(&entry) {
+        error!("Failed to log change to book {}: {}", entry.market, e);
+    }
+}
+
+impl Wal {
+    /// Opens the write-ahead log at the given path for appending, creating it
+    /// if it does not exist
+    ///
+    /// Any change left partially written by a crash is discarded, so that the
+    /// next change starts on a line of its own.
+    pub fn open(path: &Path, max_size: u64) -> io::Result<Self> {
+        let file: File =
+            OpenOptions::new().create(true).append(true).open(path)?;
+        let size: u64 =
+            match fs::read(path)?.iter().rposition(|byte| *byte == b'\n') {
+                Some(t) => t as u64 + 1,
+                None => 0,
+            };
+        file.set_len(size)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            size,
+            max_size,
+        })
+    }
+
+    /// Appends a change to the log, returning once it is on disk
+    pub fn append(&mut self, entry: &WalEntry) -> io::Result<()> {
+        let mut line: Vec<u8> = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        if self.size != 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        self.file.write_all(&line)?;
+        self.file.sync_data()?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Moves the file currently being written aside and starts a new one
+    fn rotate(&mut self) -> io::Result<()> {
+        let next: u64 = Wal::rotated(&self.path)?
+            .last()
+            .map_or(1, |(index, _path)| index + 1);
+        let rotated: PathBuf = Wal::rotated_path(&self.path, next);
+
+        info!("Rotating write-ahead log to {}...", rotated.display());
+        fs::rename(&self.path, &rotated)?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    /// Returns the path of the `index`th rotated file of the log
+    fn rotated_path(path: &Path, index: u64) -> PathBuf {
+        let mut rotated = path.as_os_str().to_os_string();
+        rotated.push(format!(".{}", index));
+        rotated.into()
+    }
+
+    /// Returns the rotated files of the log at the given path, along with
+    /// their indices, oldest first
+    fn rotated(path: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+        let mut indices: Vec<u64> = vec![];
+        let dir: &Path = match path.parent() {
+            Some(t) if !t.as_os_str().is_empty() => t,
+            _ => Path::new("."),
+        };
+        let prefix: String = match path.file_name() {
+            Some(t) => format!("{}.", t.to_string_lossy()),
+            None => return Ok(vec![]),
+        };
+
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+
+        for file in fs::read_dir(dir)? {
+            let name: String = file?.file_name().to_string_lossy().into_owned();
+            if let Some(index) = name
+                .strip_prefix(&prefix)
+                .and_then(|suffix| suffix.parse::<u64>().ok())
+            {
+                indices.push(index);
+            }
+        }
+
+        indices.sort_unstable();
+        Ok(indices
+            .into_iter()
+            .map(|index| (index, Wal::rotated_path(path, index)))
+            .collect())
+    }
+
+    /// Returns every file making up the log at the given path, in the order
+    /// they were written
+    pub fn files(path: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut files: Vec<PathBuf> = Wal::rotated(path)?
+            .into_iter()
+            .map(|(_index, path)| path)
+            .collect();
+
+        if path.exists() {
+            files.push(path.to_path_buf());
+        }
+
+        Ok(files)
+    }
+}
@@ -0,0 +1,217 @@
+//! Contains logic for notifying registered endpoints (webhooks) of fills
+use std::collections::BTreeMap;
+#[cfg(feature = "server")]
+use std::sync::Arc;
+#[cfg(feature = "server")]
+use std::time::Duration;
+
+use hmac::{Hmac, Mac, NewMac};
+#[cfg(feature = "server")]
+use reqwest::{header, Client};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+#[cfg(feature = "server")]
+use tokio::sync::broadcast::error::RecvError;
+#[cfg(feature = "server")]
+use tokio::sync::broadcast::Receiver;
+#[cfg(feature = "server")]
+use tokio::sync::RwLock;
+use web3::types::Address;
+
+use crate::book::{ExternalFill, Fill};
+#[cfg(feature = "server")]
+use crate::event::{BookEvent, BookEventKind};
+#[cfg(feature = "server")]
+use crate::rpc;
+#[cfg(feature = "server")]
+use crate::state::OmeState;
+
+/// The header carrying the signature of each delivered payload
+pub const SIGNATURE_HEADER: &str = "X-OME-Signature";
+
+/// The number of times a failed delivery is retried before it is dropped
+pub const WEBHOOK_RETRIES: u32 = 3;
+
+/// Represents an endpoint registered to be notified of fills
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: u64,
+    pub url: String,
+    pub market: Option<Address>, /* fills in every market, if `None` */
+    pub user: Option<Address>,   /* fills of every trader, if `None` */
+    pub secret: String,          /* key the payloads are signed with */
+}
+
+impl Webhook {
+    /// Returns whether the endpoint should be notified of a fill in the given
+    /// market
+    pub fn wants(&self, market: Address, fill: &Fill) -> bool {
+        self.market.is_none_or(|t| t == market)
+            && self.user.is_none_or(|t| {
+                t == fill.maker_address || t == fill.taker_address
+            })
+    }
+}
+
+/// Holds the webhooks registered with the OME, by ID
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Webhooks {
+    registered: BTreeMap<u64, Webhook>,
+    last_id: u64, /* IDs are never reused */
+}
+
+impl Webhooks {
+    /// Registers a new webhook, returning it
+    pub fn register(
+        &mut self,
+        url: String,
+        market: Option<Address>,
+        user: Option<Address>,
+        secret: String,
+    ) -> &Webhook {
+        self.last_id += 1;
+
+        let webhook: Webhook = Webhook {
+            id: self.last_id,
+            url,
+            market,
+            user,
+            secret,
+        };
+
+        self.registered.entry(webhook.id).or_insert(webhook)
+    }
+
+    /// Returns every registered webhook, in the order they were registered
+    pub fn list(&self) -> Vec<&Webhook> {
+        self.registered.values().collect()
+    }
+
+    /// Removes the webhook with the given ID, returning it (or `None` if
+    /// there is no such webhook)
+    pub fn remove(&mut self, id: u64) -> Option<Webhook> {
+        self.registered.remove(&id)
+    }
+
+    /// Returns the webhooks that should be notified of a fill in the given
+    /// market
+    pub fn wanting(&self, market: Address, fill: &Fill) -> Vec<Webhook> {
+        self.registered
+            .values()
+            .filter(|webhook| webhook.wants(market, fill))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Represents the body of a notification of a fill
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    pub webhook_id: u64,
+    pub market: Address,
+    pub sequence: u64, /* sequence number of the book upon the fill */
+    pub fill: ExternalFill,
+}
+
+/// Returns the signature of a payload, i.e., the hex-encoded HMAC-SHA256 of
+/// the body keyed by the webhook's secret
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac: Hmac<Sha256> = Hmac::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Notifies the registered webhooks of every fill published to `events`,
+/// until the OME exits
+///
+/// Each notification is delivered by a task of its own, so the books are
+/// never locked while a webhook is waited on, and a slow webhook holds up no
+/// other. Fills published while this falls more than `EVENT_CAPACITY` events
+/// behind are not delivered.
+#[cfg(feature = "server")]
+pub async fn run(
+    state: Arc<RwLock<OmeState>>,
+    mut events: Receiver<BookEvent>,
+    client: Client,
+) {
+    loop {
+        let event: BookEvent = match events.recv().await {
+            Ok(t) => t,
+            Err(RecvError::Lagged(missed)) => {
+                warn!("Webhooks missed {} events", missed);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        let fill: &Fill = match &event.kind {
+            BookEventKind::Fill { fill, .. } => fill,
+            _ => continue,
+        };
+
+        let webhooks: Vec<Webhook> =
+            state.read().await.webhooks().wanting(event.market, fill);
+
+        for webhook in webhooks {
+            let payload: WebhookPayload = WebhookPayload {
+                webhook_id: webhook.id,
+                market: event.market,
+                sequence: event.sequence,
+                fill: ExternalFill::from(fill.clone()),
+            };
+            let client: Client = client.clone();
+
+            tokio::spawn(async move {
+                deliver(&client, &webhook, &payload).await;
+            });
+        }
+    }
+}
+
+/// Posts a payload to a webhook, signed with its secret, returning whether
+/// it was delivered
+///
+/// Any response other than a success is a failure, and failures are retried
+/// `WEBHOOK_RETRIES` times with exponential backoff.
+#[cfg(feature = "server")]
+pub async fn deliver(
+    client: &Client,
+    webhook: &Webhook,
+    payload: &WebhookPayload,
+) -> bool {
+    let body: String = serde_json::to_string(payload).unwrap();
+    let signature: String = sign(&webhook.secret, body.as_bytes());
+    let mut attempt: u32 = 1;
+
+    loop {
+        let error: String = match client
+            .post(&webhook.url)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .body(body.clone())
+            .send()
+            .await
+        {
+            Ok(t) if t.status().is_success() => return true,
+            Ok(t) => format!("responded with {}", t.status()),
+            Err(e) => e.to_string(),
+        };
+
+        if attempt > WEBHOOK_RETRIES {
+            warn!(
+                "Dropping fill {} for webhook {} at {}: {}",
+                payload.fill.trade_id, webhook.id, webhook.url, error
+            );
+            return false;
+        }
+
+        let delay: Duration = rpc::backoff(attempt);
+        warn!(
+            "Webhook {} at {} {} (attempt {}), retrying in {:?}",
+            webhook.id, webhook.url, error, attempt, delay
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
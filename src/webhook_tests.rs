@@ -0,0 +1,264 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use ethereum_types::{Address, U256};
+use reqwest::Client;
+use tokio::sync::RwLock;
+use warp::http::StatusCode;
+use warp::hyper::body::Bytes;
+use warp::Filter;
+
+use crate::book::{Book, ExternalFill, Fill};
+use crate::book_tests::test_executioner;
+use crate::order::{Order, OrderId, OrderSide};
+use crate::state::OmeState;
+use crate::webhook::{
+    self, Webhook, WebhookPayload, Webhooks, WEBHOOK_RETRIES,
+};
+
+/* the signatures and bodies of the requests a receiver has been sent */
+type Requests = Arc<Mutex<Vec<(String, String)>>>;
+
+/* serves a webhook locally, answering with each of the given statuses in
+ * turn (repeating the last), and returns its URL along with the requests it
+ * receives */
+fn receiver(statuses: Vec<StatusCode>) -> (String, Requests) {
+    let requests: Requests = Arc::new(Mutex::new(vec![]));
+    let recorded: Requests = requests.clone();
+    let route = warp::path("hook")
+        .and(warp::post())
+        .and(warp::header::<String>(webhook::SIGNATURE_HEADER))
+        .and(warp::body::bytes())
+        .map(move |signature: String, body: Bytes| {
+            let mut requests = recorded.lock().unwrap();
+            requests
+                .push((signature, String::from_utf8(body.to_vec()).unwrap()));
+            let status: StatusCode =
+                statuses[(requests.len() - 1).min(statuses.len() - 1)];
+            warp::reply::with_status("", status)
+        });
+
+    let (address, server) =
+        warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    (format!("http://{}/hook", address), requests)
+}
+
+/* waits (for up to five seconds) until a receiver has been sent the given
+ * number of requests */
+async fn wait_for(requests: &Requests, count: usize) {
+    for _ in 0..100 {
+        if requests.lock().unwrap().len() >= count {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+fn order(trader: u64, side: OrderSide) -> Order {
+    Order::new(
+        Address::from_low_u64_be(trader),
+        Address::from_low_u64_be(1),
+        side,
+        U256::from(100),
+        U256::from(10),
+        Utc::now() + chrono::Duration::days(365),
+        Utc::now(),
+        vec![],
+    )
+}
+
+fn fill(maker: u64, taker: u64) -> Fill {
+    Fill {
+        trade_id: 1,
+        maker_id: OrderId::from_low_u64_be(1),
+        taker_id: OrderId::from_low_u64_be(2),
+        maker_address: Address::from_low_u64_be(maker),
+        taker_address: Address::from_low_u64_be(taker),
+        price: U256::from(100),
+        quantity: U256::from(10),
+        timestamp: Utc::now(),
+        maker_client_order_id: None,
+        taker_client_order_id: None,
+        maker_fee: U256::zero(),
+        taker_fee: U256::zero(),
+    }
+}
+
+fn webhook(url: &str, secret: &str) -> Webhook {
+    Webhook {
+        id: 1,
+        url: url.to_string(),
+        market: None,
+        user: None,
+        secret: secret.to_string(),
+    }
+}
+
+fn payload() -> WebhookPayload {
+    WebhookPayload {
+        webhook_id: 1,
+        market: Address::from_low_u64_be(1),
+        sequence: 2,
+        fill: ExternalFill::from(fill(2, 3)),
+    }
+}
+
+#[test]
+pub fn test_sign_known_vector() {
+    /* RFC 4231, test case 2 */
+    assert_eq!(
+        webhook::sign("Jefe", b"what do ya want for nothing?"),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+}
+
+#[test]
+pub fn test_webhooks_filter_fills() {
+    let market: Address = Address::from_low_u64_be(1);
+    let other: Address = Address::from_low_u64_be(9);
+    let mut webhooks: Webhooks = Webhooks::default();
+
+    let every: u64 = webhooks
+        .register("http://a".to_string(), None, None, "s".to_string())
+        .id;
+    let in_market: u64 = webhooks
+        .register("http://b".to_string(), Some(market), None, "s".to_string())
+        .id;
+    let maker: u64 = webhooks
+        .register(
+            "http://c".to_string(),
+            None,
+            Some(Address::from_low_u64_be(2)),
+            "s".to_string(),
+        )
+        .id;
+    let taker_elsewhere: u64 = webhooks
+        .register(
+            "http://d".to_string(),
+            Some(other),
+            Some(Address::from_low_u64_be(3)),
+            "s".to_string(),
+        )
+        .id;
+
+    let ids = |market: Address, fill: &Fill| -> Vec<u64> {
+        webhooks
+            .wanting(market, fill)
+            .iter()
+            .map(|t| t.id)
+            .collect()
+    };
+
+    assert_eq!(ids(market, &fill(2, 3)), vec![every, in_market, maker]);
+    assert_eq!(ids(other, &fill(2, 3)), vec![every, maker, taker_elsewhere]);
+    assert_eq!(ids(other, &fill(4, 5)), vec![every]);
+
+    /* IDs are never reused, even once the webhook is removed */
+    assert_eq!(webhooks.remove(taker_elsewhere).unwrap().url, "http://d");
+    assert!(webhooks.remove(taker_elsewhere).is_none());
+    assert_eq!(
+        webhooks
+            .register("http://e".to_string(), None, None, "s".to_string())
+            .id,
+        taker_elsewhere + 1
+    );
+}
+
+#[tokio::test]
+pub async fn test_fills_delivered_signed() {
+    let market: Address = Address::from_low_u64_be(1);
+    let (url, requests) = receiver(vec![StatusCode::OK]);
+    let (other_url, other_requests) = receiver(vec![StatusCode::OK]);
+
+    let mut book: Book = Book::new(market);
+    book.submit(order(2, OrderSide::Bid), test_executioner())
+        .await
+        .unwrap();
+    let mut ome_state: OmeState = OmeState::new();
+    ome_state.add_book(book);
+
+    let id: u64 = ome_state
+        .webhooks_mut()
+        .register(
+            url,
+            Some(market),
+            Some(Address::from_low_u64_be(3)),
+            "secret".to_string(),
+        )
+        .id;
+    ome_state.webhooks_mut().register(
+        other_url,
+        None,
+        Some(Address::from_low_u64_be(9)),
+        "secret".to_string(),
+    );
+
+    let state: Arc<RwLock<OmeState>> = Arc::new(RwLock::new(ome_state));
+    let events = state.read().await.subscribe();
+    tokio::spawn(webhook::run(state.clone(), events, Client::new()));
+
+    /* match as a handler would, publishing once done */
+    let taker: Order = order(3, OrderSide::Ask);
+    {
+        let mut ome_state = state.write().await;
+        ome_state
+            .book_mut(market)
+            .unwrap()
+            .submit(taker.clone(), test_executioner())
+            .await
+            .unwrap();
+        ome_state.publish(market);
+    }
+
+    wait_for(&requests, 1).await;
+    let (signature, body) = requests.lock().unwrap()[0].clone();
+    assert_eq!(signature, webhook::sign("secret", body.as_bytes()));
+
+    let payload: WebhookPayload = serde_json::from_str(&body).unwrap();
+    assert_eq!(payload.webhook_id, id);
+    assert_eq!(payload.market, market);
+    assert_eq!(payload.sequence, 2);
+    assert_eq!(payload.fill.taker_id, format!("{:?}", taker.id));
+    assert_eq!(payload.fill.quantity, "10");
+
+    /* fills of other traders aren't delivered */
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(other_requests.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+pub async fn test_delivery_retries_failures() {
+    let (url, requests) = receiver(vec![
+        StatusCode::INTERNAL_SERVER_ERROR,
+        StatusCode::BAD_GATEWAY,
+        StatusCode::OK,
+    ]);
+
+    assert!(
+        webhook::deliver(&Client::new(), &webhook(&url, "key"), &payload())
+            .await
+    );
+
+    /* every attempt carries the same body and signature */
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 3);
+    assert!(requests.iter().all(|t| *t == requests[0]));
+    assert_eq!(
+        requests[0].0,
+        webhook::sign("key", requests[0].1.as_bytes())
+    );
+}
+
+#[tokio::test]
+pub async fn test_delivery_gives_up() {
+    let (url, requests) = receiver(vec![StatusCode::SERVICE_UNAVAILABLE]);
+
+    assert!(
+        !webhook::deliver(&Client::new(), &webhook(&url, "key"), &payload())
+            .await
+    );
+    assert_eq!(requests.lock().unwrap().len(), 1 + WEBHOOK_RETRIES as usize);
+}
@@ -0,0 +1,202 @@
+//! Contains logic for the WebSocket feed of changes to order books
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::RwLock;
+use warp::http::StatusCode;
+use warp::ws::{Message, WebSocket};
+use web3::types::Address;
+
+use crate::actor;
+use crate::api::ApiError;
+use crate::event::{
+    BookDelta, BookEvent, DeltaEvent, ExternalBookEvent, SnapshotEvent,
+};
+use crate::state::OmeState;
+
+/// Close code sent to clients disconnected for falling too far behind
+const LAGGED_CLOSE_CODE: u16 = 1008; /* policy violation */
+
+/// Represents a subscription request sent by a WebSocket client
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(tag = "subscribe", rename_all = "lowercase")]
+pub enum Subscription {
+    Book { market: Address }, /* every change to a single order book */
+    User { address: Address }, /* every change to a trader's orders */
+    Deltas { market: Address }, /* net changes to a single order book */
+}
+
+/// Represents the subscriptions of a single WebSocket client
+#[derive(Clone, Debug, Default)]
+struct Subscriptions {
+    books: HashMap<Address, u64>, /* sequence number of each book's snapshot */
+    users: HashSet<Address>,
+    deltas: HashMap<String, u64>, /* as `books`, keyed by formatted market */
+}
+
+impl Subscriptions {
+    /// Returns whether the event should be sent to the client
+    ///
+    /// Events are only ever sent once, even if the client is subscribed to
+    /// both the book and the trader(s) they concern.
+    fn wants(&self, event: &BookEvent) -> bool {
+        /* the snapshot already reflects earlier events */
+        let book: bool = self
+            .books
+            .get(&event.market)
+            .is_some_and(|sequence| event.sequence > *sequence);
+
+        book || self.users.iter().any(|user| event.concerns(*user))
+    }
+
+    /// Returns whether the delta should be sent to the client
+    fn wants_delta(&self, delta: &BookDelta) -> bool {
+        self.deltas
+            .get(&delta.market)
+            .is_some_and(|sequence| delta.seq > *sequence)
+    }
+}
+
+/// Serves a single WebSocket client until it disconnects
+///
+/// Subscribing to a book first sends a snapshot of it, followed by every
+/// change made to it after that snapshot was taken. Subscribing to a trader
+/// sends every subsequent change to their orders in any book (including fills
+/// of their resting orders). Subscribing to the deltas of a book sends a
+/// snapshot of it just the same, followed by the net change made by each
+/// subsequent mutation (see `BookDelta`). Clients that fall more than
+/// `EVENT_CAPACITY` events or deltas behind are disconnected, as the engine
+/// never waits for them, and may catch up from the snapshot endpoint.
+pub async fn handle_socket(socket: WebSocket, state: Arc<RwLock<OmeState>>) {
+    let (mut sender, mut receiver) = socket.split();
+    let (mut events, mut deltas): (Receiver<BookEvent>, Receiver<BookDelta>) = {
+        let ome_state = state.read().await;
+        (ome_state.subscribe(), ome_state.subscribe_deltas())
+    };
+
+    let mut subscriptions: Subscriptions = Subscriptions::default();
+
+    loop {
+        let outgoing: Message = tokio::select! {
+            message = receiver.next() => match message {
+                Some(Ok(message)) if message.is_close() => break,
+                Some(Ok(message)) => match message.to_str() {
+                    Ok(text) => {
+                        let reply: String =
+                            subscribe(text, &state, &mut subscriptions).await;
+                        Message::text(reply)
+                    }
+                    Err(()) => continue, /* not a text frame */
+                },
+                _ => break,
+            },
+            event = events.recv() => match event {
+                Ok(event) if subscriptions.wants(&event) => {
+                    Message::text(to_json(&ExternalBookEvent::from(event)))
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("Disconnecting subscriber {} events behind", missed);
+                    let _ = sender
+                        .send(Message::close_with(LAGGED_CLOSE_CODE, "Lagged"))
+                        .await;
+                    break;
+                }
+                Err(RecvError::Closed) => break,
+            },
+            delta = deltas.recv() => match delta {
+                Ok(delta) if subscriptions.wants_delta(&delta) => {
+                    Message::text(to_json(&DeltaEvent::from(&delta)))
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("Disconnecting subscriber {} deltas behind", missed);
+                    let _ = sender
+                        .send(Message::close_with(LAGGED_CLOSE_CODE, "Lagged"))
+                        .await;
+                    break;
+                }
+                Err(RecvError::Closed) => break,
+            },
+        };
+
+        if sender.send(outgoing).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Handles a subscription request, returning the message to reply with
+async fn subscribe(
+    text: &str,
+    state: &RwLock<OmeState>,
+    subscriptions: &mut Subscriptions,
+) -> String {
+    let subscription: Subscription = match serde_json::from_str(text) {
+        Ok(t) => t,
+        Err(_e) => {
+            return to_json(&ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "INVALID_SUBSCRIPTION",
+                "Invalid subscription",
+            ))
+        }
+    };
+
+    match subscription {
+        Subscription::Book { market } => {
+            let (sequence, snapshot): (u64, String) =
+                match take_snapshot(state, market).await {
+                    Some(t) => t,
+                    None => return to_json(&ApiError::market_not_found()),
+                };
+
+            info!("Subscribing to book {}", market);
+            subscriptions.books.insert(market, sequence);
+            snapshot
+        }
+        Subscription::Deltas { market } => {
+            let (sequence, snapshot): (u64, String) =
+                match take_snapshot(state, market).await {
+                    Some(t) => t,
+                    None => return to_json(&ApiError::market_not_found()),
+                };
+
+            info!("Subscribing to deltas of book {}", market);
+            subscriptions
+                .deltas
+                .insert(format!("{:?}", market), sequence);
+            snapshot
+        }
+        Subscription::User { address } => {
+            info!("Subscribing to trader {}", address);
+            subscriptions.users.insert(address);
+            to_json(&json!({
+                "subscribed": "user",
+                "address": format!("{:?}", address),
+            }))
+        }
+    }
+}
+
+/// Takes the snapshot sent to new subscribers of a book, along with the
+/// sequence number it was taken at
+async fn take_snapshot(
+    state: &RwLock<OmeState>,
+    market: Address,
+) -> Option<(u64, String)> {
+    /* serialized from the book itself, rather than from a copy */
+    actor::query_book(state, market, |book| {
+        (book.sequence, to_json(&SnapshotEvent::from(book)))
+    })
+    .await
+}
+
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap()
+}
@@ -0,0 +1,256 @@
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use ethereum_types::{Address, U256};
+use tokio::sync::RwLock;
+use warp::test::WsClient;
+use warp::{Filter, Rejection, Reply};
+
+use crate::api::ApiError;
+use crate::book::Book;
+use crate::book_tests::test_executioner;
+use crate::event::{ExternalBookEvent, ExternalBookEventKind};
+use crate::handler;
+use crate::order::{ExternalOrder, Order, OrderSide};
+use crate::state::OmeState;
+
+/* builds the same WebSocket route as `main` over the supplied state */
+fn ws_route(
+    state: Arc<RwLock<OmeState>>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("ws")
+        .and(warp::ws())
+        .and(warp::any().map(move || state.clone()))
+        .and_then(handler::ws_handler)
+}
+
+/* creates engine state holding a single book with one resting bid */
+async fn setup() -> (Arc<RwLock<OmeState>>, Address, Order) {
+    let market: Address = Address::from_low_u64_be(1);
+    let order: Order = Order::new(
+        Address::from_low_u64_be(2),
+        market,
+        OrderSide::Bid,
+        U256::from(100),
+        U256::from(10),
+        Utc::now() + Duration::days(365),
+        Utc::now(),
+        vec![],
+    );
+
+    let mut book: Book = Book::new(market);
+    book.submit(order.clone(), test_executioner())
+        .await
+        .unwrap();
+
+    let mut state: OmeState = OmeState::new();
+    state.add_book(book);
+
+    (Arc::new(RwLock::new(state)), market, order)
+}
+
+async fn connect(state: Arc<RwLock<OmeState>>) -> WsClient {
+    warp::test::ws()
+        .path("/ws")
+        .handshake(ws_route(state))
+        .await
+        .expect("Failed to connect")
+}
+
+async fn receive<T: serde::de::DeserializeOwned>(client: &mut WsClient) -> T {
+    let message = client.recv().await.expect("Failed to receive");
+    serde_json::from_str(message.to_str().unwrap()).unwrap()
+}
+
+#[tokio::test]
+pub async fn test_book_subscription_snapshot_then_events() {
+    let (state, market, order) = setup().await;
+    let mut client: WsClient = connect(state.clone()).await;
+
+    client
+        .send_text(format!(
+            r#"{{"subscribe": "book", "market": "{:?}"}}"#,
+            market
+        ))
+        .await;
+
+    let snapshot: ExternalBookEvent = receive(&mut client).await;
+    assert_eq!(snapshot.market, format!("{:?}", market));
+    assert_eq!(snapshot.sequence, 1);
+    match snapshot.kind {
+        ExternalBookEventKind::Snapshot { book } => {
+            assert_eq!(book.bids.len(), 1);
+            assert_eq!(book.sequence, 1);
+        }
+        other => panic!("Expected a snapshot, got {:?}", other),
+    }
+
+    /* mutate the book as a handler would, publishing once done */
+    {
+        let mut ome_state = state.write().await;
+        ome_state
+            .book_mut(market)
+            .unwrap()
+            .cancel(order.id)
+            .unwrap();
+        ome_state.publish(market);
+    }
+
+    let event: ExternalBookEvent = receive(&mut client).await;
+    assert_eq!(event.sequence, 2);
+    assert_eq!(
+        event.kind,
+        ExternalBookEventKind::Cancelled {
+            order: ExternalOrder::from(order)
+        }
+    );
+}
+
+#[tokio::test]
+pub async fn test_book_subscription_ignores_other_books() {
+    let (state, market, _) = setup().await;
+    let other: Address = Address::from_low_u64_be(9);
+    state.write().await.add_book(Book::new(other));
+    let mut client: WsClient = connect(state.clone()).await;
+
+    client
+        .send_text(format!(
+            r#"{{"subscribe": "book", "market": "{:?}"}}"#,
+            other
+        ))
+        .await;
+    let _snapshot: ExternalBookEvent = receive(&mut client).await;
+
+    {
+        let mut ome_state = state.write().await;
+        ome_state
+            .book_mut(market)
+            .unwrap()
+            .cancel_all(Address::from_low_u64_be(2));
+        ome_state.publish(market);
+    }
+
+    /* the next message received is the reply to this request, rather than an
+     * event for the other book */
+    client
+        .send_text(format!(
+            r#"{{"subscribe": "book", "market": "{:?}"}}"#,
+            other
+        ))
+        .await;
+    let snapshot: ExternalBookEvent = receive(&mut client).await;
+    assert_eq!(snapshot.market, format!("{:?}", other));
+    assert!(matches!(
+        snapshot.kind,
+        ExternalBookEventKind::Snapshot { .. }
+    ));
+}
+
+#[tokio::test]
+pub async fn test_invalid_subscriptions() {
+    let (state, _, _) = setup().await;
+    let mut client: WsClient = connect(state).await;
+
+    client.send_text(r#"{"subscribe": "everything"}"#).await;
+    let error: ApiError = receive(&mut client).await;
+    assert_eq!(error.status, 400);
+    assert_eq!(error.code, "INVALID_SUBSCRIPTION");
+
+    client
+        .send_text(format!(
+            r#"{{"subscribe": "book", "market": "{:?}"}}"#,
+            Address::from_low_u64_be(99)
+        ))
+        .await;
+    let error: ApiError = receive(&mut client).await;
+    assert_eq!(error.status, 404);
+    assert_eq!(error.code, "MARKET_NOT_FOUND");
+}
+
+#[tokio::test]
+pub async fn test_user_subscription_reports_maker_fills() {
+    let (state, market, resting) = setup().await;
+    let maker: Address = resting.trader;
+    let mut client: WsClient = connect(state.clone()).await;
+
+    client
+        .send_text(format!(
+            r#"{{"subscribe": "user", "address": "{:?}"}}"#,
+            maker
+        ))
+        .await;
+    let ack: serde_json::Value = receive(&mut client).await;
+    assert_eq!(ack["subscribed"], "user");
+
+    let order = |trader: u64, side: OrderSide, price: u64, amount: u64| {
+        Order::new(
+            Address::from_low_u64_be(trader),
+            market,
+            side,
+            U256::from(price),
+            U256::from(amount),
+            Utc::now() + Duration::days(365),
+            Utc::now(),
+            vec![],
+        )
+    };
+
+    /* another trader's order, followed by a partial fill of the maker's bid */
+    {
+        let mut ome_state = state.write().await;
+        let book: &mut Book = ome_state.book_mut(market).unwrap();
+        book.submit(order(5, OrderSide::Bid, 90, 1), test_executioner())
+            .await
+            .unwrap();
+        book.submit(order(3, OrderSide::Ask, 100, 4), test_executioner())
+            .await
+            .unwrap();
+        ome_state.publish(market);
+    }
+
+    let event: ExternalBookEvent = receive(&mut client).await;
+    match event.kind {
+        ExternalBookEventKind::Fill {
+            fill,
+            maker_remaining,
+            taker_remaining,
+        } => {
+            assert_eq!(fill.maker_id, format!("{:?}", resting.id));
+            assert_eq!(fill.maker_address, format!("{:?}", maker));
+            assert_eq!(fill.price, "100");
+            assert_eq!(fill.quantity, "4");
+            assert_eq!(maker_remaining, "6");
+            assert_eq!(taker_remaining, "0");
+        }
+        other => panic!("Expected a fill, got {:?}", other),
+    }
+
+    /* by the time the event is received, the book already reflects it */
+    assert_eq!(
+        state
+            .read()
+            .await
+            .book(market)
+            .unwrap()
+            .order(resting.id)
+            .unwrap()
+            .remaining,
+        U256::from(6)
+    );
+
+    /* cancellations of the maker's orders are reported too */
+    {
+        let mut ome_state = state.write().await;
+        ome_state.book_mut(market).unwrap().cancel_all(maker);
+        ome_state.publish(market);
+    }
+
+    let event: ExternalBookEvent = receive(&mut client).await;
+    match event.kind {
+        ExternalBookEventKind::Cancelled { order } => {
+            assert_eq!(order.id, format!("{:?}", resting.id));
+            assert_eq!(order.amount_left, "6");
+        }
+        other => panic!("Expected a cancellation, got {:?}", other),
+    }
+}